@@ -39,6 +39,12 @@ pub(crate) fn expand_versions(versions: &[Version]) -> &[Version] {
     }
 }
 
+/// Whether `version` falls inside a configured version window. An empty window
+/// allows every version, matching the empty-`versions` convention above.
+pub(crate) fn within(window: &[Version], version: Version) -> bool {
+    window.is_empty() || window.contains(&version)
+}
+
 /// Build the ALPN list from `(alpn, versions)` entries.
 ///
 /// Each entry emits `{v.prefix()}{alpn}` per version in `expand_versions(versions)`.
@@ -169,6 +175,17 @@ mod tests {
         assert!(out.is_empty());
     }
 
+    #[test]
+    fn within_empty_window_allows_everything() {
+        for &v in Version::ALL {
+            assert!(within(&[], v));
+        }
+        let window = [Version::QMux02, Version::QMux01];
+        assert!(within(&window, Version::QMux01));
+        assert!(!within(&window, Version::QMux00));
+        assert!(!within(&window, Version::WebTransport));
+    }
+
     #[test]
     fn parse_recognises_prefixed_pairs() {
         assert_eq!(
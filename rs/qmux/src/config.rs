@@ -75,6 +75,18 @@ pub struct Config {
     /// 16382 bytes.
     pub max_datagram_frame_size: u64,
 
+    /// How many outbound datagrams may wait for the writer before
+    /// [`send_datagram`](web_transport_trait::Session::send_datagram) starts
+    /// dropping them.
+    ///
+    /// Datagrams get their own lane, drained after control frames but ahead of
+    /// stream data, so they never queue behind bulk writes. The transport itself
+    /// is still ordered and reliable (none of the WebSocket extensions for
+    /// unreliable delivery are available), so when it backs up the writer stops
+    /// pulling and this lane fills. Keep it small so datagrams are shed rather
+    /// than delivered stale. Default: 64; zero is treated as one.
+    pub datagram_send_buffer: usize,
+
     /// How long [`Session::connect`](crate::Session::connect) /
     /// [`accept`](crate::Session::accept) waits for the peer's transport
     /// parameters before giving up. Bounds the handshake so a peer that completes
@@ -99,6 +111,7 @@ impl Default for Config {
             max_record_size: DEFAULT_MAX_RECORD_SIZE,
             // Fill a full record by default; the record layer bounds the size.
             max_datagram_frame_size: DEFAULT_MAX_RECORD_SIZE,
+            datagram_send_buffer: 64,
             handshake_timeout: Duration::from_secs(10),
         }
     }
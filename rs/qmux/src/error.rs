@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use web_transport_proto::{VarInt, VarIntBoundsExceeded, VarIntUnexpectedEnd};
+use web_transport_trait::{ErrorKind, SessionId};
 
 /// Errors that can occur during QMux session and stream operations.
 #[derive(Debug, thiserror::Error, Clone)]
@@ -108,9 +109,55 @@ pub enum Error {
 
     #[error("datagrams not supported")]
     DatagramsUnsupported,
+
+    /// The close error of a session, tagged with its
+    /// [id](web_transport_trait::Session::id) to match its tracing events. Use
+    /// [`Error::without_id`] to match on the underlying error.
+    #[error("session {1}: {0}")]
+    InSession(Box<Error>, SessionId),
 }
 
 impl Error {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidFrameType(_)
+            | Error::InvalidStreamId
+            | Error::FrameTooLarge
+            | Error::FlowControlError
+            | Error::FrameEncoding
+            | Error::ProtocolViolation
+            | Error::TransportParameter
+            | Error::StreamLimitExceeded
+            | Error::DuplicateParam(_)
+            | Error::Short
+            | Error::InvalidProtocol(_)
+            | Error::UnexpectedProtocols => ErrorKind::Protocol,
+            Error::StreamClosed => ErrorKind::LocallyClosed,
+            Error::ConnectionClosed { .. } | Error::ConnectionReset { .. } | Error::Closed => {
+                ErrorKind::SessionClosed
+            }
+            Error::StreamReset(_) => ErrorKind::StreamReset,
+            Error::StreamStop(_) => ErrorKind::StreamStopped,
+            Error::IdleTimeout | Error::HandshakeTimeout => ErrorKind::TimedOut,
+            Error::InvalidServerName => ErrorKind::InvalidInput,
+            Error::Http(_) => ErrorKind::Rejected,
+            Error::Io(_) => ErrorKind::Io,
+            #[cfg(feature = "ws")]
+            Error::WebSocket(_) => ErrorKind::Io,
+            Error::DatagramsUnsupported => ErrorKind::Unsupported,
+            Error::InSession(err, _) => err.kind(),
+        }
+    }
+
+    /// The error without the [session id](Error::InSession) attached.
+    pub fn without_id(&self) -> &Error {
+        match self {
+            Error::InSession(err, _) => err.without_id(),
+            err => err,
+        }
+    }
+
     /// The wire error code to send on a CONNECTION_CLOSE (0x1c) when *we* tear the
     /// session down because of this error, or `None` when the peer should not (or
     /// cannot) be told: a graceful close, a close the peer already sent us, an idle
@@ -129,6 +176,7 @@ impl Error {
             | Error::FrameTooLarge
             | Error::DatagramsUnsupported
             | Error::Short => Some(1002),
+            Error::InSession(err, _) => err.transport_close(),
             _ => None,
         }
     }
@@ -170,7 +218,7 @@ impl From<tokio_tungstenite::tungstenite::Error> for Error {
 
 impl web_transport_trait::Error for Error {
     fn session_error(&self) -> Option<(u32, String)> {
-        match self {
+        match self.without_id() {
             Error::ConnectionClosed { code, reason } => match code.into_inner().try_into() {
                 Ok(code) => Some((code, reason.clone())),
                 Err(_) => None,
@@ -180,11 +228,15 @@ impl web_transport_trait::Error for Error {
     }
 
     fn stream_error(&self) -> Option<u32> {
-        match self {
+        match self.without_id() {
             Error::StreamReset(code) | Error::StreamStop(code) => code.into_inner().try_into().ok(),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        Error::kind(self)
+    }
 }
 
 #[cfg(all(test, feature = "ws"))]
@@ -207,4 +259,23 @@ mod tests {
         let err: Error = tungstenite::Error::ConnectionClosed.into();
         assert!(matches!(err, Error::WebSocket(_)));
     }
+
+    #[test]
+    fn kind_classifies_across_variants() {
+        use web_transport_trait::Error as _;
+
+        assert_eq!(Error::Http(403).kind(), ErrorKind::Rejected);
+        assert_eq!(Error::IdleTimeout.kind(), ErrorKind::TimedOut);
+        assert_eq!(
+            Error::StreamStop(VarInt::from_u32(7)).kind(),
+            ErrorKind::StreamStopped
+        );
+
+        let closed = Error::ConnectionClosed {
+            code: VarInt::from_u32(9),
+            reason: "bye".into(),
+        };
+        assert_eq!(closed.kind(), ErrorKind::SessionClosed);
+        assert_eq!(closed.code(), Some(9));
+    }
 }
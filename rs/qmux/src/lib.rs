@@ -54,6 +54,7 @@ pub use proto::Version;
 pub use session::{RecvStream, SendStream, Session};
 pub use stream::{StreamDir, StreamId};
 pub use transport::Transport;
+pub use web_transport_trait::ErrorKind;
 // The transport half-traits live at `transport::{Reader, Writer}` and the concrete
 // byte-stream transport at `transport::Stream`, rather than the crate root — the
 // bare `Reader`/`Writer`/`Stream` names would be too generic (and `Stream` would
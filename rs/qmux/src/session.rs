@@ -16,6 +16,7 @@ use crate::{
 };
 use bytes::{Buf, BufMut, Bytes};
 use tokio::sync::{mpsc, watch};
+use tracing::Instrument;
 use web_transport_proto::VarInt;
 use web_transport_trait as generic;
 
@@ -24,12 +25,6 @@ use web_transport_trait as generic;
 /// applying backpressure to the whole session.
 const DATAGRAM_RECV_BUFFER: usize = 1024;
 
-/// How many outbound datagrams to buffer before dropping. The writer pulls from
-/// this lane; when it stalls on transport backpressure it stops pulling, the lane
-/// fills, and `send_datagram` drops on a full lane. Kept small so shedding tracks
-/// real backpressure closely rather than after a deep buffer of stale datagrams.
-const DATAGRAM_SEND_BUFFER: usize = 64;
-
 /// Shared, lock-guarded per-stream backend state. The reader task inserts/looks
 /// up entries as inbound frames arrive; the writer task retires an entry when it
 /// emits that stream's terminal frame (FIN/RESET/STOP_SENDING). Guarded by a
@@ -88,6 +83,10 @@ pub struct Session {
 
     closed: watch::Sender<Option<Error>>,
 
+    // The first APPLICATION_CLOSE from either side, shared with the reader, for
+    // `close_reason()`. Unlike `closed`, it remembers which side it came from.
+    close_record: generic::CloseRecord,
+
     // Negotiated application protocol (via the application_protocols transport
     // parameter). Resolved exactly once, before the session is handed to the
     // caller (see `established()`), so `protocol()` is a plain synchronous
@@ -128,6 +127,13 @@ pub struct Session {
     // to the caller (0 = the peer doesn't accept datagrams).
     datagram_max_size: Arc<AtomicUsize>,
 
+    // The remote address reported by the transport, if it has one.
+    peer_addr: Option<std::net::SocketAddr>,
+
+    // Identifies the session in logs, as the `session` span around the reader,
+    // writer, and timer tasks.
+    id: generic::SessionId,
+
     // Closes the connection when the last `Session` clone drops. Never read.
     _guard: Arc<SessionGuard>,
 }
@@ -205,6 +211,7 @@ struct SessionState<R: Reader> {
     streams: Arc<Mutex<Streams>>,
 
     closed: watch::Sender<Option<Error>>,
+    close_record: generic::CloseRecord,
 
     // Negotiated protocol and handshake-complete signal — see the matching
     // fields on `Session`.
@@ -1253,6 +1260,10 @@ impl<R: Reader> SessionState<R> {
             // APPLICATION_CLOSE (0x1d): a graceful, deliberate peer close — surfaces
             // as a clean session close carrying the peer's code/reason.
             Frame::ApplicationClose(close) => {
+                // A code beyond u32 has no session error to report, as in `session_error`.
+                if let Ok(code) = close.code.into_inner().try_into() {
+                    self.close_record.remote(code, close.reason.as_bytes());
+                }
                 self.closed
                     .send(Some(Error::ConnectionClosed {
                         code: close.code,
@@ -1498,6 +1509,12 @@ impl Session {
         Ok(session)
     }
 
+    /// The wire-format version this session speaks, as negotiated by the
+    /// transport (e.g. the WebSocket subprotocol) or fixed by its [`Config`].
+    pub fn version(&self) -> Version {
+        self.config.version
+    }
+
     /// Wait until the peer's transport parameters have been received and applied.
     /// Folded into [`connect`](Session::connect) / [`accept`](Session::accept);
     /// see those for the timeout and error semantics.
@@ -1562,7 +1579,8 @@ impl Session {
         // backpressure it stops draining `outbound_datagram`, which fills and makes
         // `send_datagram` shed.
         let (recv_datagram_tx, recv_datagram_rx) = mpsc::channel(DATAGRAM_RECV_BUFFER);
-        let (outbound_datagram_tx, outbound_datagram_rx) = mpsc::channel(DATAGRAM_SEND_BUFFER);
+        let (outbound_datagram_tx, outbound_datagram_rx) =
+            mpsc::channel(config.datagram_send_buffer.max(1));
         let datagram_max_size = Arc::new(AtomicUsize::new(0));
 
         // Shared with the writer task: per-stream backend state, plus the two
@@ -1588,6 +1606,10 @@ impl Session {
         let writer_backpressured = Arc::new(AtomicBool::new(false));
 
         let closed = watch::Sender::new(None);
+        let close_record = generic::CloseRecord::new();
+
+        let id = generic::SessionId::next();
+        let span = tracing::info_span!("session", %id);
 
         // The QMux handshake requires TRANSPORT_PARAMETERS as the first frame. It
         // leads the FIFO control lane, so the writer emits it before anything else.
@@ -1601,6 +1623,7 @@ impl Session {
         // backpressure must never stall reads. The writer is the sole producer on
         // the wire, pulling the outbound queues in priority order and sharing the
         // stream maps + scalars above with the reader (no message-passing handoff).
+        let peer_addr = transport.peer_addr();
         let (writer_half, reader_half) = transport.split();
         let mut writer = WriterState {
             writer: writer_half,
@@ -1615,7 +1638,7 @@ impl Session {
             base,
             last_send_at: last_send_at.clone(),
         };
-        tokio::spawn(async move { writer.run().await });
+        tokio::spawn(async move { writer.run().await }.instrument(span.clone()));
 
         // Protocol negotiation. Only `Negotiate` resolves in-band (once the
         // peer's params arrive); the out-of-band cases resolve immediately.
@@ -1668,6 +1691,7 @@ impl Session {
             accept_uni: accept_uni_tx,
             streams: streams.clone(),
             closed: closed.clone(),
+            close_record: close_record.clone(),
             negotiated: negotiated.clone(),
             established: established_tx,
             conn_send_credit: conn_send_credit.clone(),
@@ -1709,11 +1733,14 @@ impl Session {
                 established: established_rx.clone(),
                 pings_sent: pings_sent.clone(),
             };
-            tokio::spawn(timer.run());
+            tokio::spawn(timer.run().instrument(span.clone()));
         }
 
-        tokio::spawn(async move {
+        let backend_task = async move {
             let err = backend.run().await.err().unwrap_or(Error::Closed);
+            // A close recorded first wins: the peer's APPLICATION_CLOSE is usually
+            // followed by EOF, which must not replace it below.
+            let err = backend.closed.borrow().clone().unwrap_or(err);
             // If we tore down because of a protocol/transport violation *we*
             // detected, tell the peer with a CONNECTION_CLOSE (0x1c) so their
             // session rejects too, rather than seeing a bare drop. Enqueue on the
@@ -1751,7 +1778,8 @@ impl Session {
             // awaiting establishment on a peer that closed without sending params).
             // Storing it unconditionally keeps late waiters correct.
             backend.closed.send_replace(Some(err));
-        });
+        };
+        tokio::spawn(backend_task.instrument(span));
 
         // Closes the connection once every `Session` clone has dropped.
         let guard = Arc::new(SessionGuard {
@@ -1767,6 +1795,7 @@ impl Session {
             accept_uni: Arc::new(tokio::sync::Mutex::new(accept_uni_rx)),
             streams,
             closed,
+            close_record,
             negotiated,
             established: established_rx,
             open_bi_credit,
@@ -1776,9 +1805,17 @@ impl Session {
             recv_datagram: Arc::new(tokio::sync::Mutex::new(recv_datagram_rx)),
             datagram_max_size,
             outbound_datagram: outbound_datagram_tx,
+            peer_addr,
+            id,
             _guard: guard,
         }
     }
+
+    // The error behind a closed channel: the recorded close if there is one, so a
+    // peer's APPLICATION_CLOSE isn't reported as a bare drop.
+    fn close_error(&self) -> Error {
+        self.closed.borrow().clone().unwrap_or(Error::Closed)
+    }
 }
 
 impl generic::Session for Session {
@@ -1792,7 +1829,7 @@ impl generic::Session for Session {
             .await
             .recv()
             .await
-            .ok_or(Error::Closed)
+            .ok_or_else(|| self.close_error())
     }
 
     async fn accept_bi(&self) -> Result<(Self::SendStream, Self::RecvStream), Self::Error> {
@@ -1801,7 +1838,7 @@ impl generic::Session for Session {
             .await
             .recv()
             .await
-            .ok_or(Error::Closed)
+            .ok_or_else(|| self.close_error())
     }
 
     async fn open_uni(&self) -> Result<Self::SendStream, Self::Error> {
@@ -1939,6 +1976,11 @@ impl generic::Session for Session {
     }
 
     fn close(&self, code: u32, reason: &str) {
+        // Only the first close from either side is sent; a later one just marks the race.
+        if !self.close_record.local(code, reason.as_bytes()) {
+            return;
+        }
+
         // App-initiated: an APPLICATION_CLOSE (0x1d) the peer surfaces as a clean
         // session close carrying our code/reason.
         let frame = ApplicationClose {
@@ -1957,11 +1999,20 @@ impl generic::Session for Session {
 
     async fn closed(&self) -> Self::Error {
         let mut closed = self.closed.subscribe();
-        closed
+        let err = closed
             .wait_for(|err| err.is_some())
             .await
             .map(|e| e.clone().unwrap_or(Error::Closed))
-            .unwrap_or(Error::Closed)
+            .unwrap_or(Error::Closed);
+        Error::InSession(Box::new(err), self.id)
+    }
+
+    fn close_reason(&self) -> Option<generic::Closed> {
+        self.close_record.get()
+    }
+
+    fn id(&self) -> Option<generic::SessionId> {
+        Some(self.id)
     }
 
     fn send_datagram(&self, payload: Bytes) -> Result<(), Self::Error> {
@@ -1996,7 +2047,7 @@ impl generic::Session for Session {
             .await
             .recv()
             .await
-            .ok_or(Error::Closed)
+            .ok_or_else(|| self.close_error())
     }
 
     fn protocol(&self) -> Option<&str> {
@@ -2004,6 +2055,10 @@ impl generic::Session for Session {
         // construction). `None` here means in-band negotiation is still pending.
         self.negotiated.get().and_then(|p| p.as_deref())
     }
+
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        self.peer_addr
+    }
 }
 
 /// Select the agreed application protocol from two advertised lists.
@@ -2874,7 +2929,7 @@ mod write_cancel_tests {
 mod recv_open_tests {
     use std::time::Duration;
 
-    use bytes::Bytes;
+    use bytes::{Bytes, BytesMut};
     use tokio::sync::mpsc;
     use web_transport_trait::{RecvStream as _, Session as _};
 
@@ -3014,6 +3069,31 @@ mod recv_open_tests {
         assert_eq!(recv.read_all().await.unwrap().as_ref(), b"helloworld");
     }
 
+    /// `read_owned` appends into the buffer it's given and hands it back, so a
+    /// pipeline can keep filling one buffer without borrowing it across awaits.
+    #[tokio::test]
+    async fn read_owned_returns_the_buffer() {
+        let (session, tx) = scripted_session();
+
+        tx.send(uni_stream(0, b"hello", false)).unwrap();
+        tx.send(uni_stream(0, b"world", true)).unwrap();
+
+        let mut recv = tokio::time::timeout(Duration::from_secs(1), session.accept_uni())
+            .await
+            .expect("accept_uni timed out")
+            .expect("accept_uni failed");
+
+        let mut buf = BytesMut::with_capacity(64);
+        loop {
+            let (next, size) = recv.read_owned(buf).await;
+            buf = next;
+            if size.unwrap().is_none() {
+                break;
+            }
+        }
+        assert_eq!(buf.as_ref(), b"helloworld");
+    }
+
     /// A FIN for a higher stream index arriving before the first frame of a lower
     /// one must NOT retire the lower stream. Opening index 10 only *implicitly*
     /// opens 0..10 — it doesn't close them — so a later first frame on index 6 is a
@@ -3108,7 +3188,10 @@ mod recv_open_tests {
         let err = tokio::time::timeout(Duration::from_secs(1), session.closed())
             .await
             .expect("session did not close on RESET_STREAM flow-control violation");
-        assert!(matches!(err, Error::FlowControlError), "got {err:?}");
+        assert!(
+            matches!(err.without_id(), Error::FlowControlError),
+            "got {err:?}"
+        );
     }
 
     /// Reset-only final sizes and ordinary STREAM payloads share the same
@@ -3126,7 +3209,10 @@ mod recv_open_tests {
         let err = tokio::time::timeout(Duration::from_secs(1), session.closed())
             .await
             .expect("session did not close after cumulative MAX_DATA exhaustion");
-        assert!(matches!(err, Error::FlowControlError), "got {err:?}");
+        assert!(
+            matches!(err.without_id(), Error::FlowControlError),
+            "got {err:?}"
+        );
     }
 
     /// A reset gap cannot occupy receive memory, so once it has been charged to
@@ -3233,7 +3319,10 @@ mod datagram_recv_tests {
         raw.write_all(&record(datagram)).await.unwrap();
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::FrameTooLarge));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::FrameTooLarge
+        ));
     }
 
     /// A DATAGRAM on a session that advertised `max_datagram_frame_size = 0` was
@@ -3250,7 +3339,10 @@ mod datagram_recv_tests {
         raw.write_all(&record(datagram)).await.unwrap();
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::DatagramsUnsupported));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::DatagramsUnsupported
+        ));
     }
 
     /// Scan the size-prefixed records the server wrote and return the first
@@ -3291,7 +3383,10 @@ mod datagram_recv_tests {
         raw.write_all(&record(datagram)).await.unwrap();
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::FrameTooLarge));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::FrameTooLarge
+        ));
 
         // Drain everything the server wrote and find the close frame it emitted.
         let mut buf = Vec::new();
@@ -3322,7 +3417,7 @@ mod datagram_recv_tests {
         raw.write_all(&record(close)).await.unwrap();
         raw.flush().await.unwrap();
 
-        match server.closed().await {
+        match server.closed().await.without_id() {
             Error::ConnectionClosed { code, reason } => {
                 assert_eq!(code.into_inner(), 42);
                 assert_eq!(reason, "bye");
@@ -3351,7 +3446,7 @@ mod datagram_recv_tests {
 
         let err = server.closed().await;
         assert!(
-            matches!(err, Error::ConnectionReset { .. }),
+            matches!(err.without_id(), Error::ConnectionReset { .. }),
             "a peer CONNECTION_CLOSE must be abnormal, got {err:?}"
         );
         assert!(err.session_error().is_none());
@@ -3480,7 +3575,10 @@ mod qmux02_recv_tests {
         raw.write_all(&record(&qmux02_params())).await.unwrap();
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::ProtocolViolation));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::ProtocolViolation
+        ));
     }
 
     /// A `max_record_size` below the default minimum is a TRANSPORT_PARAMETER_ERROR
@@ -3517,7 +3615,10 @@ mod qmux02_recv_tests {
         raw.write_all(&record(&ping)).await.unwrap();
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::ProtocolViolation));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::ProtocolViolation
+        ));
     }
 
     /// QX_PING request sequence numbers must strictly increase.
@@ -3536,7 +3637,10 @@ mod qmux02_recv_tests {
         }
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::ProtocolViolation));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::ProtocolViolation
+        ));
     }
 
     /// A RESET_STREAM_AT frame (draft-02, hand-built — we never emit it) is
@@ -3613,7 +3717,10 @@ mod qmux02_recv_tests {
         raw.write_all(&record(&reset_at)).await.unwrap();
         raw.flush().await.unwrap();
 
-        assert!(matches!(server.closed().await, Error::ProtocolViolation));
+        assert!(matches!(
+            server.closed().await.without_id(),
+            Error::ProtocolViolation
+        ));
     }
 }
 
@@ -88,5 +88,6 @@ async fn finish(
     // `build_stream_session` awaits the peer's transport parameters before
     // returning, so `protocol()` is resolved on the session we hand back
     // (bounded by the config's handshake timeout).
-    build_stream_session(stream, config, is_server).await
+    let peer_addr = stream.peer_addr().ok();
+    build_stream_session(stream, peer_addr, config, is_server).await
 }
@@ -69,6 +69,7 @@ impl Client {
         server_name: &str,
     ) -> Result<Session, Error> {
         let stream = TcpStream::connect(&addr).await?;
+        let peer_addr = stream.peer_addr()?;
 
         let server_name = rustls::pki_types::ServerName::try_from(server_name)
             .map_err(|_| Error::InvalidServerName)?
@@ -105,7 +106,8 @@ impl Client {
         }
 
         let session_config = Config::negotiated(version, protocol);
-        let transport = Stream::new(tls_stream, version, session_config.max_record_size);
+        let transport = Stream::new(tls_stream, version, session_config.max_record_size)
+            .with_peer_addr(peer_addr);
         // `connect` awaits the peer's transport parameters so `protocol()` is resolved.
         Session::connect(transport, session_config).await
     }
@@ -137,6 +139,7 @@ impl Server {
     /// connection with `tokio::spawn` so a slow or non-cooperative peer can't
     /// stall your `listener.accept()` loop.
     pub async fn accept(&self, stream: TcpStream) -> Result<Session, Error> {
+        let peer_addr = stream.peer_addr()?;
         let acceptor = TlsAcceptor::from(self.config.clone());
         let tls_stream = acceptor.accept(stream).await?;
 
@@ -148,7 +151,8 @@ impl Server {
         tracing::debug!(?version, ?protocol, "parsed ALPN");
 
         let session_config = Config::negotiated(version, protocol);
-        let transport = Stream::new(tls_stream, version, session_config.max_record_size);
+        let transport = Stream::new(tls_stream, version, session_config.max_record_size)
+            .with_peer_addr(peer_addr);
         // `accept` awaits the peer's transport parameters so `protocol()` is resolved.
         Session::accept(transport, session_config).await
     }
@@ -26,6 +26,13 @@ pub trait Transport: Send + 'static {
 
     /// Split into send and receive halves.
     fn split(self) -> (Self::Writer, Self::Reader);
+
+    /// The remote address of the underlying socket, if it has one. Captured by
+    /// the session before [`split`](Self::split) and surfaced through
+    /// [`Session::peer_addr`](web_transport_trait::Session::peer_addr).
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        None
+    }
 }
 
 /// The send half of a [`Transport`].
@@ -63,6 +70,8 @@ pub trait Reader: Send + 'static {
 // `recv_record`/`recv_qmux00_frame` are safe to keep as-is.
 #[cfg(any(feature = "tcp", all(unix, feature = "uds")))]
 mod stream_transport {
+    use std::net::SocketAddr;
+
     use bytes::{BufMut, Bytes, BytesMut};
     use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
     use tokio::sync::mpsc;
@@ -100,6 +109,7 @@ mod stream_transport {
     pub struct Stream<T> {
         writer: StreamWriter<T>,
         reader: StreamReader,
+        peer_addr: Option<SocketAddr>,
     }
 
     /// The send half of a byte-stream [`Stream`].
@@ -137,8 +147,18 @@ mod stream_transport {
                     version,
                 },
                 reader: StreamReader { rx, reader_task },
+                peer_addr: None,
             }
         }
+
+        /// Report `addr` as the remote address of this stream.
+        ///
+        /// A generic byte stream can't know where it's connected to, so callers
+        /// wrapping a socket pass it along here (e.g. [`TcpStream::peer_addr`](tokio::net::TcpStream::peer_addr)).
+        pub fn with_peer_addr(mut self, addr: SocketAddr) -> Self {
+            self.peer_addr = Some(addr);
+            self
+        }
     }
 
     impl<T: AsyncRead + AsyncWrite + Send + 'static> Transport for Stream<T> {
@@ -148,6 +168,10 @@ mod stream_transport {
         fn split(self) -> (StreamWriter<T>, StreamReader) {
             (self.writer, self.reader)
         }
+
+        fn peer_addr(&self) -> Option<SocketAddr> {
+            self.peer_addr
+        }
     }
 
     impl Drop for StreamReader {
@@ -538,6 +562,8 @@ pub use stream_transport::{Stream, StreamReader, StreamWriter};
 // Shared plumbing for the byte-stream transports (TCP, Unix sockets).
 #[cfg(any(feature = "tcp", all(unix, feature = "uds")))]
 mod stream_session {
+    use std::net::SocketAddr;
+
     use tokio::io::{AsyncRead, AsyncWrite};
 
     use super::Stream;
@@ -548,6 +574,7 @@ mod stream_session {
     /// advertised protocol names first. Used by the `tcp`/`uds` builders.
     pub(crate) async fn build<T: AsyncRead + AsyncWrite + Send + 'static>(
         stream: T,
+        peer_addr: Option<SocketAddr>,
         config: Config,
         is_server: bool,
     ) -> Result<Session, Error> {
@@ -556,7 +583,10 @@ mod stream_session {
                 validate_protocol(protocol)?;
             }
         }
-        let transport = Stream::new(stream, config.version, config.max_record_size);
+        let mut transport = Stream::new(stream, config.version, config.max_record_size);
+        if let Some(addr) = peer_addr {
+            transport = transport.with_peer_addr(addr);
+        }
         if is_server {
             Session::accept(transport, config).await
         } else {
@@ -571,6 +601,7 @@ pub(crate) use stream_session::build as build_stream_session;
 // WsTransport: message I/O over WebSocket.
 #[cfg(feature = "ws")]
 mod ws_transport {
+    use std::net::SocketAddr;
     use std::pin::Pin;
     use std::time::Duration;
 
@@ -615,6 +646,7 @@ mod ws_transport {
         ws: T,
         keep_alive: Option<KeepAlive>,
         record_limit: Option<usize>,
+        peer_addr: Option<SocketAddr>,
     }
 
     impl<T> WsTransport<T> {
@@ -622,6 +654,7 @@ mod ws_transport {
             Self {
                 ws,
                 keep_alive: None,
+                peer_addr: None,
                 record_limit: version
                     .uses_records()
                     .then(|| usize::try_from(max_record_size).unwrap_or(usize::MAX)),
@@ -632,6 +665,11 @@ mod ws_transport {
             self.keep_alive = Some(keep_alive);
             self
         }
+
+        pub fn with_peer_addr(mut self, peer_addr: Option<SocketAddr>) -> Self {
+            self.peer_addr = peer_addr;
+            self
+        }
     }
 
     /// Writer-side keep-alive: emit a Ping every `interval`.
@@ -720,6 +758,10 @@ mod ws_transport {
             let pump = tokio::spawn(ws_pump(stream, deadline, self.record_limit, tx));
             (WsWriter { sink, ping }, WsReader { rx, pump })
         }
+
+        fn peer_addr(&self) -> Option<SocketAddr> {
+            self.peer_addr
+        }
     }
 
     impl<T: WsStream> Writer for WsWriter<T> {
@@ -81,5 +81,6 @@ async fn finish(
     // `build_stream_session` awaits the peer's transport parameters before
     // returning, so `protocol()` is resolved on the session we hand back
     // (bounded by the config's handshake timeout).
-    build_stream_session(stream, config, is_server).await
+    // Unix sockets have no `SocketAddr` to report.
+    build_stream_session(stream, None, config, is_server).await
 }
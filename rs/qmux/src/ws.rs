@@ -53,6 +53,8 @@ pub struct Upgraded<T> {
     ws: T,
     alpn: Option<String>,
     keep_alive: Option<KeepAlive>,
+    datagram_buffer: Option<usize>,
+    peer_addr: Option<std::net::SocketAddr>,
 }
 
 impl<T> Upgraded<T>
@@ -68,6 +70,8 @@ where
             ws,
             alpn: None,
             keep_alive: None,
+            datagram_buffer: None,
+            peer_addr: None,
         }
     }
 
@@ -83,13 +87,32 @@ where
         self
     }
 
+    /// Bound the outbound datagram lane to `datagrams` queued datagrams.
+    ///
+    /// WebSocket offers no unreliable delivery, so datagrams share the ordered
+    /// socket with stream data and are shed once this many are waiting. See
+    /// [`Config::datagram_send_buffer`].
+    pub fn with_datagram_buffer(mut self, datagrams: usize) -> Self {
+        self.datagram_buffer = Some(datagrams);
+        self
+    }
+
+    /// Report `addr` as the remote address of the session.
+    ///
+    /// The framework that performed the upgrade owns the socket, so it has to
+    /// pass the address along for [`Session::peer_addr`](web_transport_trait::Session::peer_addr).
+    pub fn with_peer_addr(mut self, addr: std::net::SocketAddr) -> Self {
+        self.peer_addr = Some(addr);
+        self
+    }
+
     /// Wrap as a client-side session.
     ///
     /// The protocol is already known from the negotiated subprotocol (ALPN), so
     /// this returns synchronously without awaiting in-band parameters.
     pub fn connect(self) -> Session {
         let (version, protocol) = alpn::parse(self.alpn.as_deref());
-        let config = Config::negotiated(version, protocol);
+        let config = self.config(version, protocol);
         let transport = self.into_transport(config.version, config.max_record_size);
         Session::new(transport, false, config)
     }
@@ -100,13 +123,22 @@ where
     /// negotiated subprotocol, so this returns synchronously.
     pub fn accept(self) -> Session {
         let (version, protocol) = alpn::parse(self.alpn.as_deref());
-        let config = Config::negotiated(version, protocol);
+        let config = self.config(version, protocol);
         let transport = self.into_transport(config.version, config.max_record_size);
         Session::new(transport, true, config)
     }
 
+    fn config(&self, version: Version, protocol: Option<String>) -> Config {
+        let mut config = Config::negotiated(version, protocol);
+        if let Some(datagrams) = self.datagram_buffer {
+            config.datagram_send_buffer = datagrams;
+        }
+        config
+    }
+
     fn into_transport(self, version: Version, max_record_size: u64) -> WsTransport<T> {
-        let transport = WsTransport::new(self.ws, version, max_record_size);
+        let transport =
+            WsTransport::new(self.ws, version, max_record_size).with_peer_addr(self.peer_addr);
         match self.keep_alive {
             Some(ka) => transport.with_keep_alive(ka),
             None => transport,
@@ -128,8 +160,10 @@ where
 pub struct Client {
     protocols: Vec<(String, Vec<Version>)>,
     require_protocol: bool,
+    versions: Vec<Version>,
     config: Option<tungstenite::protocol::WebSocketConfig>,
     keep_alive: Option<KeepAlive>,
+    datagram_buffer: Option<usize>,
     #[cfg(feature = "wss")]
     connector: Option<tokio_tungstenite::Connector>,
 }
@@ -170,6 +204,20 @@ impl Client {
         self
     }
 
+    /// Restrict the session to the listed wire-format versions.
+    ///
+    /// Prefixed pairs and bare version ALPNs outside this window are not
+    /// offered, and a server response that picks one anyway is rejected with
+    /// [`Error::InvalidProtocol`]. For a rolling upgrade, pin the window to the
+    /// current and previous drafts (e.g. `&[Version::QMux02, Version::QMux01]`)
+    /// so upgraded clients keep talking to servers that haven't rolled yet,
+    /// then drop the older entry once every server speaks the new one. An
+    /// empty slice (the default) allows every version this crate knows about.
+    pub fn with_versions(mut self, versions: &[Version]) -> Self {
+        self.versions = versions.to_vec();
+        self
+    }
+
     /// Set the WebSocket configuration (e.g. max message/frame sizes).
     pub fn with_config(mut self, config: tungstenite::protocol::WebSocketConfig) -> Self {
         self.config = Some(config);
@@ -185,6 +233,16 @@ impl Client {
         self
     }
 
+    /// Bound the outbound datagram lane to `datagrams` queued datagrams.
+    ///
+    /// WebSocket offers no unreliable delivery, so datagrams share the ordered
+    /// socket with stream data and are shed once this many are waiting. See
+    /// [`Config::datagram_send_buffer`].
+    pub fn with_datagram_buffer(mut self, datagrams: usize) -> Self {
+        self.datagram_buffer = Some(datagrams);
+        self
+    }
+
     /// Set the TLS connector for secure WebSocket connections.
     #[cfg(feature = "wss")]
     pub fn with_connector(mut self, connector: tokio_tungstenite::Connector) -> Self {
@@ -206,7 +264,11 @@ impl Client {
             .protocols
             .iter()
             .map(|(a, vs)| (a.as_str(), vs.as_slice()));
-        let protocol_value = alpn::build(entries, self.require_protocol).join(", ");
+        let protocol_value = alpn::build(entries, self.require_protocol)
+            .into_iter()
+            .filter(|wire| alpn::within(&self.versions, alpn::parse(Some(wire)).0))
+            .collect::<Vec<_>>()
+            .join(", ");
 
         request.headers_mut().insert(
             http::header::SEC_WEBSOCKET_PROTOCOL,
@@ -248,8 +310,29 @@ impl Client {
             ));
         }
 
-        let config = Config::negotiated(version, protocol);
-        let transport = WsTransport::new(ws_stream, config.version, config.max_record_size);
+        // A server outside our version window (including one that answered
+        // without a subprotocol, i.e. legacy `webtransport`) can't be spoken to.
+        if !alpn::within(&self.versions, version) {
+            return Err(Error::InvalidProtocol(
+                negotiated.unwrap_or("<none>").to_string(),
+            ));
+        }
+
+        let peer_addr = match ws_stream.get_ref() {
+            tokio_tungstenite::MaybeTlsStream::Plain(stream) => stream.peer_addr().ok(),
+            #[cfg(feature = "wss")]
+            tokio_tungstenite::MaybeTlsStream::Rustls(stream) => {
+                stream.get_ref().0.peer_addr().ok()
+            }
+            _ => None,
+        };
+
+        let mut config = Config::negotiated(version, protocol);
+        if let Some(datagrams) = self.datagram_buffer {
+            config.datagram_send_buffer = datagrams;
+        }
+        let transport = WsTransport::new(ws_stream, config.version, config.max_record_size)
+            .with_peer_addr(peer_addr);
         let transport = match self.keep_alive {
             Some(ka) => transport.with_keep_alive(ka),
             None => transport,
@@ -273,7 +356,9 @@ impl Client {
 pub struct Server {
     protocols: Vec<(String, Vec<Version>)>,
     require_protocol: bool,
+    versions: Vec<Version>,
     keep_alive: Option<KeepAlive>,
+    datagram_buffer: Option<usize>,
 }
 
 impl Server {
@@ -313,6 +398,19 @@ impl Server {
         self
     }
 
+    /// Restrict the session to the listed wire-format versions.
+    ///
+    /// Offered subprotocols (prefixed pairs and bare version ALPNs) outside
+    /// this window are ignored, so a client that only speaks a retired draft
+    /// is refused during the handshake. For a rolling upgrade, accept the
+    /// current and previous drafts (e.g. `&[Version::QMux02, Version::QMux01]`)
+    /// while clients migrate, then drop the older entry. An empty slice (the
+    /// default) allows every version this crate knows about.
+    pub fn with_versions(mut self, versions: &[Version]) -> Self {
+        self.versions = versions.to_vec();
+        self
+    }
+
     /// Send periodic Pings and close the session if the peer goes silent.
     ///
     /// WebSocket has no built-in idle timeout, so without this a crashed peer
@@ -322,7 +420,20 @@ impl Server {
         self
     }
 
+    /// Bound the outbound datagram lane to `datagrams` queued datagrams.
+    ///
+    /// WebSocket offers no unreliable delivery, so datagrams share the ordered
+    /// socket with stream data and are shed once this many are waiting. See
+    /// [`Config::datagram_send_buffer`].
+    pub fn with_datagram_buffer(mut self, datagrams: usize) -> Self {
+        self.datagram_buffer = Some(datagrams);
+        self
+    }
+
     /// Accept a WebSocket connection, negotiating an offered `(alpn, version)`.
+    ///
+    /// `socket` can be any byte stream, so the session has no
+    /// [`peer_addr`](web_transport_trait::Session::peer_addr) to report.
     pub async fn accept<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
         &self,
         socket: T,
@@ -338,6 +449,7 @@ impl Server {
         let negotiated_clone = negotiated.clone();
         let supported = self.protocols.clone();
         let require_protocol = self.require_protocol;
+        let window = self.versions.clone();
 
         #[allow(clippy::result_large_err)]
         let callback = move |req: &server::Request,
@@ -358,6 +470,9 @@ impl Server {
             // the first `{prefix}{alpn}` permutation the client offered.
             for (alpn, versions) in &supported {
                 for &version in alpn::expand_versions(versions) {
+                    if !alpn::within(&window, version) {
+                        continue;
+                    }
                     let wire = format!("{}{}", version.prefix(), alpn);
                     if header_protocols.iter().any(|p| *p == wire) {
                         response.headers_mut().insert(
@@ -377,6 +492,9 @@ impl Server {
             // whichever bare ALPN won.
             if !require_protocol {
                 for &version in alpn::BARE_ALPNS {
+                    if !alpn::within(&window, version) {
+                        continue;
+                    }
                     let bare = version.alpn();
                     if header_protocols.contains(&bare) {
                         response.headers_mut().insert(
@@ -403,7 +521,10 @@ impl Server {
             .take()
             .expect("negotiated must be set after successful handshake");
 
-        let config = Config::negotiated(version, protocol);
+        let mut config = Config::negotiated(version, protocol);
+        if let Some(datagrams) = self.datagram_buffer {
+            config.datagram_send_buffer = datagrams;
+        }
         let transport = WsTransport::new(ws, config.version, config.max_record_size);
         let transport = match self.keep_alive {
             Some(ka) => transport.with_keep_alive(ka),
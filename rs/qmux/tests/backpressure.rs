@@ -73,6 +73,10 @@ impl Reader for GatedReader {
 /// A client/server pair where only the *client's* writer is gated by `gate`.
 /// The gate is open during construction so the handshake completes.
 async fn pair(gate: watch::Receiver<bool>) -> (Session, Session) {
+    pair_with(gate, Config::new(Version::QMux01)).await
+}
+
+async fn pair_with(gate: watch::Receiver<bool>, config: Config) -> (Session, Session) {
     let (c2s_tx, c2s_rx) = mpsc::channel(256);
     let (s2c_tx, s2c_rx) = mpsc::channel(256);
 
@@ -87,7 +91,6 @@ async fn pair(gate: watch::Receiver<bool>) -> (Session, Session) {
         gate: None,
     };
 
-    let config = Config::new(Version::QMux01);
     let (client, server) = tokio::join!(
         Session::connect(client_transport, config.clone()),
         Session::accept(server_transport, config),
@@ -184,7 +187,7 @@ async fn datagrams_are_shed_under_backpressure() {
         received += 1;
     }
 
-    // The outbound datagram lane is 64 deep (DATAGRAM_SEND_BUFFER), so at most
+    // The outbound datagram lane is 64 deep (Config::datagram_send_buffer), so at most
     // ~64 of the 200 can sit buffered while the writer is wedged; the rest are
     // shed. Bound the count well under N so a regression that grows the lane or
     // stops shedding is caught — `received < N` alone would still pass with, say,
@@ -197,6 +200,47 @@ async fn datagrams_are_shed_under_backpressure() {
     filler.abort();
 }
 
+/// The depth of the outbound datagram lane is configurable, so a latency-sensitive
+/// application can shed almost everything queued behind a backed-up transport.
+#[tokio::test]
+async fn datagram_lane_depth_is_configurable() {
+    let mut config = Config::new(Version::QMux01);
+    config.datagram_send_buffer = 4;
+
+    let (gate_tx, gate_rx) = watch::channel(true);
+    let (client, server) = pair_with(gate_rx, config).await;
+
+    gate_tx.send(false).unwrap();
+    let client_filler = client.clone();
+    let filler = tokio::spawn(async move {
+        let mut s = client_filler.open_uni().await.unwrap();
+        let _ = s.write(&vec![b'F'; 512 * 1024]).await;
+        client_filler
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    const N: usize = 200;
+    for i in 0..N {
+        client.send_datagram(Bytes::from(vec![i as u8; 8])).unwrap();
+    }
+
+    gate_tx.send(true).unwrap();
+    let mut received = 0usize;
+    while let Ok(Ok(_)) =
+        tokio::time::timeout(Duration::from_millis(200), server.recv_datagram()).await
+    {
+        received += 1;
+    }
+
+    // Four in the lane, plus at most one the writer already pulled.
+    assert!(
+        received <= 5,
+        "only the 4-deep lane should survive backpressure, but {received}/{N} arrived"
+    );
+
+    filler.abort();
+}
+
 /// A connection whose writer is wedged on transport backpressure DEFERS its idle
 /// timeout — the wedge proves the peer's receive window is full, so it's alive and
 /// we simply can't get a keep-alive out. But the deferral is *bounded*: a peer that
@@ -264,7 +308,7 @@ async fn idle_timeout_deferred_but_bounded_under_backpressure() {
         .expect("bounded deferral must eventually idle-close a stuck-backpressured peer")
         .unwrap();
     assert!(
-        matches!(reason, Error::IdleTimeout),
+        matches!(reason.without_id(), Error::IdleTimeout),
         "expected IdleTimeout once the grace elapses, got {reason:?}"
     );
 
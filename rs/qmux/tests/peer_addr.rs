@@ -0,0 +1,57 @@
+//! The remote address each transport reports through `Session::peer_addr`.
+
+#![cfg(feature = "tcp")]
+
+use qmux::Version;
+use tokio::net::TcpListener;
+use web_transport_trait::Session as _;
+
+/// Both ends of a TCP session see the other's socket address.
+#[tokio::test]
+async fn tcp_reports_socket_addresses() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (sock, remote) = listener.accept().await.unwrap();
+        let session = qmux::tcp::Config::new(Version::QMux01)
+            .accept(sock)
+            .await
+            .unwrap();
+        (session, remote)
+    });
+
+    let client = qmux::tcp::Config::new(Version::QMux01)
+        .connect(addr)
+        .await
+        .unwrap();
+    let (server, remote) = server.await.unwrap();
+
+    assert_eq!(client.peer_addr(), Some(addr));
+    assert_eq!(server.peer_addr(), Some(remote));
+}
+
+/// A bare byte stream has no address unless the caller supplies one.
+#[tokio::test]
+async fn stream_without_addr_reports_none() {
+    use qmux::{transport::Stream, Config, Session};
+
+    let (a, b) = tokio::io::duplex(4096);
+    let config = Config::new(Version::QMux01);
+    let addr = "192.0.2.1:4443".parse().unwrap();
+
+    let server = tokio::spawn({
+        let config = config.clone();
+        async move {
+            let transport = Stream::new(b, config.version, config.max_record_size);
+            Session::accept(transport, config).await.unwrap()
+        }
+    });
+
+    let transport = Stream::new(a, config.version, config.max_record_size).with_peer_addr(addr);
+    let client = Session::connect(transport, config).await.unwrap();
+    let server = server.await.unwrap();
+
+    assert_eq!(client.peer_addr(), Some(addr));
+    assert_eq!(server.peer_addr(), None);
+}
@@ -0,0 +1,7 @@
+use qmux::{Error, RecvStream, SendStream, Session};
+use static_assertions::assert_impl_all;
+
+assert_impl_all!(Session: web_transport_trait::Session, Send, Sync, Clone);
+assert_impl_all!(SendStream: web_transport_trait::SendStream, Send);
+assert_impl_all!(RecvStream: web_transport_trait::RecvStream, Send);
+assert_impl_all!(Error: web_transport_trait::Error, Send, Sync);
@@ -0,0 +1,76 @@
+//! Wire-format version negotiation over the WebSocket subprotocol.
+
+#![cfg(feature = "ws")]
+
+use qmux::{Error, Version};
+use tokio::net::TcpListener;
+
+/// Spawn a server restricted to `window`, returning its URL and accept task.
+async fn serve(
+    window: &'static [Version],
+) -> (
+    String,
+    tokio::task::JoinHandle<Result<qmux::Session, Error>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let (sock, _) = listener.accept().await.unwrap();
+        qmux::ws::Server::new()
+            .with_protocol("moq-lite-04", &[])
+            .with_versions(window)
+            .accept(sock)
+            .await
+    });
+
+    (url, server)
+}
+
+/// A client one version behind still connects to a server accepting N and N−1.
+#[tokio::test]
+async fn rolling_upgrade_accepts_previous_version() {
+    let (url, server) = serve(&[Version::QMux02, Version::QMux01]).await;
+
+    let client = qmux::ws::Client::new()
+        .with_protocol("moq-lite-04", &[])
+        .with_versions(&[Version::QMux01])
+        .connect(&url)
+        .await
+        .unwrap();
+    let server = server.await.unwrap().unwrap();
+
+    assert_eq!(client.version(), Version::QMux01);
+    assert_eq!(server.version(), Version::QMux01);
+}
+
+/// Without a window pinned on the client, the newest shared version wins.
+#[tokio::test]
+async fn prefers_newest_shared_version() {
+    let (url, server) = serve(&[Version::QMux02, Version::QMux01]).await;
+
+    let client = qmux::ws::Client::new()
+        .with_protocol("moq-lite-04", &[])
+        .connect(&url)
+        .await
+        .unwrap();
+    let server = server.await.unwrap().unwrap();
+
+    assert_eq!(client.version(), Version::QMux02);
+    assert_eq!(server.version(), Version::QMux02);
+}
+
+/// A client that only speaks a retired version is refused in the handshake.
+#[tokio::test]
+async fn rejects_version_outside_window() {
+    let (url, server) = serve(&[Version::QMux02, Version::QMux01]).await;
+
+    let client = qmux::ws::Client::new()
+        .with_protocol("moq-lite-04", &[])
+        .with_versions(&[Version::QMux00, Version::WebTransport])
+        .connect(&url)
+        .await;
+
+    assert!(client.is_err(), "expected the handshake to be refused");
+    assert!(server.await.unwrap().is_err());
+}
@@ -0,0 +1,453 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_quiche::settings::{CertificateKind, Hooks, TlsCertificatePaths};
+
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+use crate::socket::capabilities;
+use crate::tls::{keylog_file, ClientHook, ClientVerify};
+use crate::DriverState;
+use crate::SocketOptions;
+
+use super::{
+    happy_eyeballs, Clock, Connection, ConnectionError, Driver, Lock, Settings, TokioClock,
+    DEFAULT_CONNECTION_ATTEMPT_DELAY,
+};
+
+// Local buffer between the application and the driver task — *not* the QUIC
+// datagram queue (configured via `Settings::dgram_send_max_queue_len`). It
+// absorbs scheduling latency between `send_datagram()` and the driver picking
+// the buffer up, and holds datagrams while quiche's queue is full, so a small
+// fixed size is sufficient. Anything past this is dropped at the channel
+// boundary by `send_datagram()`, which is consistent with the unreliable QUIC
+// datagram contract, or waited on by `send_datagram_wait()`.
+pub(super) const DGRAM_CHANNEL_CAPACITY: usize = 64;
+
+/// Construct a QUIC client using sane defaults.
+///
+/// Unlike [ServerBuilder](super::ServerBuilder), there is no metrics
+/// counterpart. `tokio-quiche` hardcodes its own `DefaultMetrics` inside
+/// `quic::connect_with_config`, so there is no client-side hook to attach a
+/// custom [Metrics](super::Metrics) implementation to.
+pub struct ClientBuilder {
+    settings: Settings,
+    socket: Option<tokio::net::UdpSocket>,
+    tls: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    verify: ClientVerify,
+    server_name: Option<String>,
+    keep_alive: Option<Duration>,
+    gso: bool,
+    attempt_delay: Duration,
+    socket_options: SocketOptions,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    /// Create a new client builder.
+    pub fn new() -> Self {
+        let mut settings = Settings::default();
+        settings.verify_peer = true;
+
+        Self {
+            settings,
+            socket: None,
+            tls: None,
+            verify: ClientVerify::Default,
+            server_name: None,
+            keep_alive: None,
+            gso: true,
+            attempt_delay: DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            socket_options: SocketOptions::default(),
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    /// Wait this long for a connection attempt before racing the next resolved address.
+    ///
+    /// When a host resolves to several addresses, they're tried in turn with the address
+    /// families interleaved (RFC 8305, "Happy Eyeballs"), and the first to complete the
+    /// handshake wins. Defaults to [DEFAULT_CONNECTION_ATTEMPT_DELAY].
+    ///
+    /// Racing needs a socket per attempt, so it's disabled by [ClientBuilder::with_socket].
+    pub fn with_connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    /// Send a PING on this interval, keeping an idle connection alive.
+    ///
+    /// Disabled by default. This must be shorter than the peer's
+    /// [Settings::max_idle_timeout] to have any effect; a third of it is a
+    /// reasonable choice.
+    pub fn with_keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+
+    /// Measure keep-alives and datagram expiry with `clock` instead of the tokio timer.
+    ///
+    /// Defaults to [TokioClock]. QUIC's own timers, like the idle timeout, still use quiche's.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Close the connection after this long without hearing from the server.
+    ///
+    /// Each side advertises its own, and the connection uses the smaller of the two.
+    /// Defaults to [Settings::max_idle_timeout]; set this after
+    /// [ClientBuilder::with_settings], which replaces it.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.max_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Queue up to this many outgoing datagrams that haven't been sent yet.
+    ///
+    /// Once it's full, [Connection::send_datagram] drops new datagrams while
+    /// [Connection::send_datagram_wait] waits. Defaults to
+    /// [Settings::dgram_send_max_queue_len]; set this after
+    /// [ClientBuilder::with_settings], which replaces it.
+    pub fn with_datagram_send_buffer(mut self, datagrams: usize) -> Self {
+        self.settings.dgram_send_max_queue_len = datagrams;
+        self
+    }
+
+    /// Enable UDP generic segmentation offload (GSO), on by default.
+    ///
+    /// GSO cuts syscall overhead at high throughput by handing the kernel
+    /// several packets at once, but some NICs and virtual network stacks
+    /// mishandle it. Turn it off if large sends are being dropped.
+    ///
+    /// Only Linux supports GSO; elsewhere this does nothing.
+    pub fn with_gso(mut self, enabled: bool) -> Self {
+        self.gso = enabled;
+        self
+    }
+
+    /// Apply these [SocketOptions] to the UDP socket used for each connection.
+    ///
+    /// Set this before [ClientBuilder::with_bind], which needs it to apply
+    /// [SocketOptions::with_ipv6_only].
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Listen for incoming packets on the given socket.
+    ///
+    /// Defaults to an ephemeral port if not specified.
+    pub fn with_socket(self, socket: std::net::UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        let socket = tokio::net::UdpSocket::from_std(socket)?;
+
+        Ok(Self {
+            socket: Some(socket),
+            ..self
+        })
+    }
+
+    /// Listen for incoming packets on the given address.
+    ///
+    /// Defaults to an ephemeral port if not specified.
+    pub fn with_bind<A: std::net::ToSocketAddrs>(self, addrs: A) -> io::Result<Self> {
+        // We use std to avoid async
+        let socket = self.socket_options.bind(addrs)?;
+        self.with_socket(socket)
+    }
+
+    /// Use the provided [Settings] instead of the defaults.
+    ///
+    /// WARNING: [Settings::verify_peer] is set to false by default.
+    /// This will completely bypass certificate verification and is generally not recommended.
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Write a qlog trace of each connection to a file in `dir`.
+    ///
+    /// The traces can be loaded into a viewer such as qvis to debug interop issues.
+    /// Disabled by default; see [Settings::qlog_compression] to compress them.
+    /// Set this after [ClientBuilder::with_settings], which replaces it.
+    pub fn with_qlog_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.settings.qlog_dir = Some(dir.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// Wireshark can use the file to decrypt packet captures. Nothing is written if the
+    /// variable isn't set, or unless the `keylog` feature is enabled. Disabled by default,
+    /// since the secrets decrypt every connection. Set this after
+    /// [ClientBuilder::with_settings], which replaces it.
+    pub fn with_keylog(mut self, enabled: bool) -> Self {
+        self.settings.keylog_file = keylog_file(enabled);
+        self
+    }
+
+    /// Optional: Use a client certificate for mTLS.
+    pub fn with_single_cert(
+        self,
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
+    ) -> Self {
+        Self {
+            tls: Some((chain.into_iter().collect(), key.into())),
+            ..self
+        }
+    }
+
+    /// Use this name for SNI and certificate verification instead of the host
+    /// passed to [ClientBuilder::connect].
+    ///
+    /// The dial target is unchanged; only the name the server certificate must
+    /// match is. This is how you reach a host by IP, or through a tunnel, while
+    /// still verifying the certificate it was actually issued for.
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Verify the server certificate against an explicit set of root
+    /// certificates instead of the system trust store.
+    pub fn with_root_certificates(
+        mut self,
+        roots: impl IntoIterator<Item = CertificateDer<'static>>,
+    ) -> Self {
+        self.verify = ClientVerify::Roots(roots.into_iter().collect());
+        self
+    }
+
+    /// Accept the server certificate only if the SHA-256 of its DER encoding
+    /// matches one of the provided hashes, bypassing CA verification.
+    ///
+    /// This mirrors the browser's `serverCertificateHashes` option and is the
+    /// usual way to reach a relay using a short-lived self-signed certificate.
+    pub fn with_server_certificate_hashes(mut self, hashes: Vec<[u8; 32]>) -> Self {
+        self.verify = ClientVerify::Hashes(hashes);
+        self
+    }
+
+    /// Connect to the QUIC server at the given host and port.
+    ///
+    /// `host` is the dial target: it's resolved via DNS and, unless
+    /// [ClientBuilder::with_server_name] overrides it, is also the name the
+    /// server's certificate must match. An IP address is matched against the
+    /// certificate's IP address SANs and isn't sent as SNI.
+    ///
+    /// This takes ownership because the underlying quiche implementation doesn't support reusing the same socket.
+    pub async fn connect(self, host: &str, port: u16) -> io::Result<Connecting> {
+        let remotes = match tokio::net::lookup_host((host, port)).await {
+            Ok(remotes) => happy_eyeballs::interleave(remotes),
+            Err(err) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::HostUnreachable,
+                    err.to_string(),
+                ));
+            }
+        };
+
+        self.connect_addrs(host, remotes).await
+    }
+
+    /// Connect to the QUIC server at whichever of the given addresses answers first.
+    ///
+    /// `host` is only used for SNI and certificate verification, as in [ClientBuilder::connect].
+    /// With more than one address and no [ClientBuilder::with_socket], the attempts are raced
+    /// (see [ClientBuilder::with_connection_attempt_delay]) and the returned [Connecting] has
+    /// already completed the handshake. Otherwise only the first address is dialed.
+    pub async fn connect_addrs(
+        self,
+        host: &str,
+        remotes: Vec<SocketAddr>,
+    ) -> io::Result<Connecting> {
+        let Some(&first) = remotes.first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::HostUnreachable,
+                "no addresses found for host",
+            ));
+        };
+
+        if self.socket.is_some() || remotes.len() == 1 {
+            return self.connect_to(host, first).await;
+        }
+
+        let delay = self.attempt_delay;
+        happy_eyeballs::race(&*self.clock, remotes, delay, |remote| {
+            let attempt = self.fork();
+            async move {
+                let connecting = attempt.connect_to(host, remote).await?;
+                connecting
+                    .handshake()
+                    .await
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                Ok(connecting)
+            }
+        })
+        .await
+    }
+
+    /// A copy of this builder without the socket, for one more connection attempt.
+    ///
+    /// Each connection needs its own socket, so the copy binds a new one when it connects.
+    pub fn fork(&self) -> Self {
+        Self {
+            settings: self.settings.clone(),
+            socket: None,
+            tls: self
+                .tls
+                .as_ref()
+                .map(|(chain, key)| (chain.clone(), key.clone_key())),
+            verify: self.verify.clone(),
+            server_name: self.server_name.clone(),
+            keep_alive: self.keep_alive,
+            gso: self.gso,
+            attempt_delay: self.attempt_delay,
+            socket_options: self.socket_options.clone(),
+            clock: self.clock.clone(),
+        }
+    }
+
+    /// Dial a single resolved address.
+    async fn connect_to(mut self, host: &str, remote: SocketAddr) -> io::Result<Connecting> {
+        if self.socket.is_none() {
+            // Bind to the remote's family; dual-stack sockets aren't available everywhere.
+            self = match remote {
+                SocketAddr::V4(_) => self.with_bind("0.0.0.0:0")?,
+                SocketAddr::V6(_) => self.with_bind("[::]:0")?,
+            };
+        }
+
+        let socket = self.socket.take().unwrap();
+        socket.connect(remote).await?;
+        self.socket_options.apply(&socket);
+
+        // Enable the offloads the kernel supports before the socket is wrapped;
+        // `from_udp` starts with everything disabled.
+        let capabilities = capabilities(&socket, self.gso);
+
+        // Connect to the server using the addr we just resolved.
+        let mut socket = tokio_quiche::socket::Socket::<
+            Arc<tokio::net::UdpSocket>,
+            Arc<tokio::net::UdpSocket>,
+        >::from_udp(socket)?;
+        socket.capabilities = capabilities;
+
+        // Only the fully-insecure path (no verification of any kind) deserves a
+        // warning; hash- and root-based verification still authenticate the peer.
+        if !self.settings.verify_peer && matches!(self.verify, ClientVerify::Default) {
+            tracing::warn!("TLS certificate verification is disabled, a MITM attack is possible");
+        }
+
+        // quiche uses the server name for both SNI and the certificate's hostname
+        // check. Neither works for an IP address: SNI can't carry one, and the
+        // certificate must match an IP SAN instead, which the hook verifies.
+        let server_name = self.server_name.as_deref().unwrap_or(host);
+        let server_ip = server_name
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .ok();
+
+        // Install a TLS hook whenever we present a client certificate, need a
+        // non-default verification policy, or must verify an IP address. The SSL
+        // context is built (and the certificate material validated) here so a bad
+        // cert/key/root fails the connection rather than silently dropping the
+        // policy inside the hook.
+        // ALPN is left to tokio-quiche, which applies it after the hook runs.
+        let needs_hook = self.tls.is_some()
+            || !matches!(self.verify, ClientVerify::Default)
+            || (server_ip.is_some() && self.settings.verify_peer);
+        let (tls_cert, hooks) = if needs_hook {
+            let ctx = crate::tls::build_client_context(self.tls.as_ref(), &self.verify, server_ip)?;
+            let hook = ClientHook::new(ctx);
+            // ConnectionHook is only invoked when tls_cert is set, so we provide a dummy.
+            let dummy_tls = TlsCertificatePaths {
+                cert: "",
+                private_key: "",
+                kind: CertificateKind::X509,
+            };
+            let hooks = Hooks {
+                connection_hook: Some(Arc::new(hook)),
+            };
+            (Some(dummy_tls), hooks)
+        } else {
+            (None, Hooks::default())
+        };
+
+        let sni = server_ip.is_none().then_some(server_name);
+
+        let params = tokio_quiche::ConnectionParams::new_client(self.settings, tls_cert, hooks);
+
+        let accept_bi = flume::unbounded();
+        let accept_uni = flume::unbounded();
+        let dgram_in = flume::bounded(DGRAM_CHANNEL_CAPACITY);
+        let dgram_out = flume::bounded(DGRAM_CHANNEL_CAPACITY);
+        let dgram_max = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let driver = Lock::new(DriverState::new(false, None).with_clock(self.clock.clone()));
+        let app = Driver::new(
+            driver.clone(),
+            accept_bi.0,
+            accept_uni.0,
+            dgram_in.0,
+            dgram_out.1,
+            dgram_max.clone(),
+            self.keep_alive,
+        );
+
+        let conn = tokio_quiche::quic::connect_with_config(socket, sni, &params, app)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        let conn = Connection::new(
+            conn,
+            driver.clone(),
+            accept_bi.1,
+            accept_uni.1,
+            dgram_in.1,
+            dgram_out.0,
+            dgram_max,
+        );
+        Ok(Connecting {
+            connection: conn,
+            driver,
+        })
+    }
+}
+
+/// A QUIC connection that is still completing the TLS handshake.
+///
+/// This is the client-side equivalent of [super::Incoming] on the server side.
+/// Call [Connecting::established] to wait for the handshake to complete.
+pub struct Connecting {
+    connection: Connection,
+    driver: Lock<DriverState>,
+}
+
+impl Connecting {
+    /// Wait for the TLS handshake to complete.
+    ///
+    /// Returns the connection once the handshake is complete, or an error if the connection
+    /// is closed before the handshake finishes.
+    pub async fn established(self) -> Result<Connection, ConnectionError> {
+        self.handshake().await?;
+        Ok(self.connection)
+    }
+
+    async fn handshake(&self) -> Result<(), ConnectionError> {
+        use std::future::poll_fn;
+
+        poll_fn(|cx| self.driver.lock().poll_handshake(cx.waker())).await
+    }
+}
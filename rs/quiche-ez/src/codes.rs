@@ -0,0 +1,24 @@
+//! QUIC error codes sent by this crate itself, rather than by the application.
+//!
+//! These are raw QUIC codes with no application meaning; protocols layered on
+//! top (like WebTransport) close or reset with their own codes first, so these
+//! only reach the wire when a [Connection](crate::Connection) or stream is used
+//! directly. The drop codes are ASCII, so they're easy to spot in a capture.
+
+/// The connection was dropped without calling `close`: "drop" in ASCII.
+pub const CONNECTION_DROPPED: u64 = 0x64726F70;
+
+/// A send stream was dropped without calling `finish` or `reset`: "send" in ASCII.
+pub const SEND_DROPPED: u64 = 0x73656E64;
+
+/// A receive stream was dropped without calling `stop` or reading to the end: "recv" in ASCII.
+pub const RECV_DROPPED: u64 = 0x72656376;
+
+/// The server was closed before the connection was accepted: "shut" in ASCII.
+pub const SERVER_CLOSED: u64 = 0x73687574;
+
+/// quiche reported an error, closing the connection.
+pub const QUICHE_ERROR: u64 = 500;
+
+/// tokio-quiche reported an unexpected error, closing the connection.
+pub const UNKNOWN_ERROR: u64 = 501;
@@ -13,10 +13,14 @@ use std::{
 };
 use thiserror::Error;
 use tokio_quiche::quiche;
+use web_transport_trait::ErrorKind;
 
-use crate::ez::DriverState;
+use crate::DriverState;
 
-use super::{Lock, RecvStream, SendStream};
+use super::{
+    Clock, Datagram, DatagramOptions, Lock, MemoryAccount, OutgoingDatagram, RecvStream,
+    SendStream, StreamInfo,
+};
 
 /// A point-in-time snapshot of QUIC connection statistics.
 ///
@@ -41,6 +45,11 @@ pub struct ConnectionStats {
     /// Estimated send rate in bits per second (from the congestion controller),
     /// if a path is established.
     pub send_rate: Option<u64>,
+    /// Congestion window in bytes for the active path, if one is established.
+    pub cwnd: Option<u64>,
+    /// Datagrams dropped because the send queue or the application's receive queue
+    /// was full, or the [MemoryBudget](crate::MemoryBudget) couldn't cover them.
+    pub datagrams_dropped: u64,
 }
 
 impl ConnectionStats {
@@ -59,12 +68,16 @@ impl ConnectionStats {
             rtt: path.as_ref().map(|p| p.rtt),
             // quiche reports the delivery rate in bytes/sec; the trait wants bits/sec.
             send_rate: path.as_ref().map(|p| p.delivery_rate.saturating_mul(8)),
+            cwnd: path.as_ref().map(|p| p.cwnd as u64),
+            // Counted by the driver, which is where they're dropped.
+            datagrams_dropped: 0,
         }
     }
 }
 
 /// An errors returned by [Connection].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ConnectionError {
     #[error("quiche error: {0}")]
     Quiche(#[from] quiche::Error),
@@ -79,11 +92,28 @@ pub enum ConnectionError {
     #[error("connection dropped")]
     Dropped,
 
+    /// Nothing was heard from the peer within the idle timeout, including during the handshake.
+    #[error("timed out")]
+    TimedOut,
+
     /// An unknown error occurred in tokio-quiche.
     #[error("unknown error: {0}")]
     Unknown(String),
 }
 
+impl ConnectionError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Quiche(_) => ErrorKind::Protocol,
+            Self::Remote(..) => ErrorKind::SessionClosed,
+            Self::Local(..) | Self::Dropped => ErrorKind::LocallyClosed,
+            Self::TimedOut => ErrorKind::TimedOut,
+            Self::Unknown(_) => ErrorKind::Other,
+        }
+    }
+}
+
 #[derive(Default)]
 struct ConnectionClosedState {
     err: Option<ConnectionError>,
@@ -182,8 +212,8 @@ pub struct Connection {
 
     // Datagram plumbing. Both channels are bounded; drops on full are silent
     // and consistent with the unreliable QUIC datagram contract.
-    dgram_in: flume::Receiver<Bytes>,
-    dgram_out: flume::Sender<Bytes>,
+    dgram_in: flume::Receiver<Datagram>,
+    dgram_out: flume::Sender<OutgoingDatagram>,
     dgram_max: Arc<AtomicUsize>,
 
     driver: Lock<DriverState>,
@@ -198,8 +228,8 @@ impl Connection {
         driver: Lock<DriverState>,
         accept_bi: flume::Receiver<(SendStream, RecvStream)>,
         accept_uni: flume::Receiver<RecvStream>,
-        dgram_in: flume::Receiver<Bytes>,
-        dgram_out: flume::Sender<Bytes>,
+        dgram_in: flume::Receiver<Datagram>,
+        dgram_out: flume::Sender<OutgoingDatagram>,
         dgram_max: Arc<AtomicUsize>,
     ) -> Self {
         let close = Arc::new(ConnectionClose::new(driver.clone()));
@@ -266,7 +296,8 @@ impl Connection {
     pub async fn read_datagram(&self) -> Result<Bytes, ConnectionError> {
         tokio::select! {
             res = self.dgram_in.recv_async() => match res {
-                Ok(bytes) => Ok(bytes),
+                // Handing the datagram over returns its bytes to the budget.
+                Ok((bytes, _permit)) => Ok(bytes),
                 // Sender dropped — the driver closed; surface the close reason.
                 Err(_) => Err(self.close.error().await),
             },
@@ -280,8 +311,36 @@ impl Connection {
     /// is **dropped** (returning `Ok(())`) — backpressure surfaces as packet
     /// loss, which matches the QUIC datagram contract. Returns
     /// `Err(ConnectionError::Dropped)` only when the driver itself is gone.
+    /// The same applies when the [memory budget](Connection::memory) is exhausted.
     pub fn send_datagram(&self, data: Bytes) -> Result<(), ConnectionError> {
-        match self.dgram_out.try_send(data) {
+        self.send_datagram_with(data, DatagramOptions::default())
+    }
+
+    /// Queue an application datagram for the driver to send, with [DatagramOptions].
+    ///
+    /// A datagram with a [max_age](DatagramOptions::max_age) is dropped if it's still waiting
+    /// for room in quiche's queue once that has passed. quiche has no datagram priorities,
+    /// so [priority](DatagramOptions::priority) is ignored. Otherwise like [Connection::send_datagram].
+    pub fn send_datagram_with(
+        &self,
+        data: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), ConnectionError> {
+        let expires = options
+            .max_age
+            .map(|age| self.driver.lock().clock().now() + age);
+        let permit = match self.memory() {
+            Some(memory) => match memory.try_reserve(data.len()) {
+                Some(permit) => Some(permit),
+                None => {
+                    tracing::trace!("dropping outbound datagram: memory budget exhausted");
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        match self.dgram_out.try_send((data, permit, expires)) {
             Ok(()) => {}
             Err(flume::TrySendError::Full(_)) => {
                 tracing::trace!("dropping outbound datagram: channel full");
@@ -300,6 +359,51 @@ impl Connection {
         Ok(())
     }
 
+    /// Queue an application datagram for the driver to send, waiting for room instead of dropping it.
+    ///
+    /// Waits while quiche's queue ([Settings::dgram_send_max_queue_len](super::Settings::dgram_send_max_queue_len))
+    /// and the channel in front of it are both full, and for the [memory budget](Connection::memory)
+    /// if one is configured. The datagram may still be lost in the network, or dropped by the
+    /// driver if it's too large or the peer didn't negotiate datagrams.
+    pub async fn send_datagram_wait(&self, data: Bytes) -> Result<(), ConnectionError> {
+        let queue = async {
+            let permit = match self.memory() {
+                Some(memory) => Some(memory.reserve(data.len()).await),
+                None => None,
+            };
+
+            self.dgram_out
+                .send_async((data, permit, None))
+                .await
+                .map_err(|_| ConnectionError::Dropped)
+        };
+
+        tokio::select! {
+            res = queue => res?,
+            err = self.close.error() => return Err(err),
+        }
+
+        // Nudge the driver so it picks up the new datagram on the next poll.
+        let waker = self.driver.lock().wake();
+        if let Some(w) = waker {
+            w.wake();
+        }
+        Ok(())
+    }
+
+    /// This connection's share of the server's [MemoryBudget](super::MemoryBudget), if one was configured.
+    ///
+    /// [MemoryAccount::used] reports the bytes currently queued for its streams
+    /// and datagrams.
+    pub fn memory(&self) -> Option<MemoryAccount> {
+        self.driver.lock().memory().cloned()
+    }
+
+    /// The [Clock] this connection measures time with, as given to the builder's `with_clock`.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.driver.lock().clock().clone()
+    }
+
     /// Maximum size of a datagram that can be sent right now.
     ///
     /// Returns `None` when datagrams are disabled in the peer's transport parameters.
@@ -364,6 +468,50 @@ impl Connection {
     pub fn stats(&self) -> ConnectionStats {
         self.driver.lock().stats()
     }
+
+    /// Returns how many bidirectional streams the peer allows: those opened so far plus [Self::remaining_bi].
+    pub fn max_open_bi(&self) -> u64 {
+        let (opened, remaining) = self.driver.lock().streams(true);
+        opened + remaining
+    }
+
+    /// Returns how many more bidirectional streams can be opened before [Self::open_bi] waits.
+    ///
+    /// This is zero until the handshake completes, and refreshed each time the driver runs.
+    pub fn remaining_bi(&self) -> u64 {
+        self.driver.lock().streams(true).1
+    }
+
+    /// Returns how many unidirectional streams the peer allows: those opened so far plus [Self::remaining_uni].
+    pub fn max_open_uni(&self) -> u64 {
+        let (opened, remaining) = self.driver.lock().streams(false);
+        opened + remaining
+    }
+
+    /// Returns how many more unidirectional streams can be opened before [Self::open_uni] waits.
+    ///
+    /// This is zero until the handshake completes, and refreshed each time the driver runs.
+    pub fn remaining_uni(&self) -> u64 {
+        self.driver.lock().streams(false).1
+    }
+
+    /// Returns every stream the driver is still tracking, in order of ID, for diagnostics.
+    ///
+    /// The driver takes the snapshot the next time it runs, so this waits for it. A
+    /// stream is dropped from the list once both halves are done: read to the end, reset
+    /// or stopped, and for the sending half, acknowledged by the peer.
+    pub async fn open_streams(&self) -> Result<Vec<StreamInfo>, ConnectionError> {
+        let (reply, snapshot) = tokio::sync::oneshot::channel();
+        let waker = self.driver.lock().snapshot(reply);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        tokio::select! {
+            Ok(streams) = snapshot => Ok(streams),
+            err = self.close.error() => Err(err),
+        }
+    }
 }
 
 impl Deref for Connection {
@@ -382,7 +530,7 @@ mod tests {
 
     #[test]
     fn local_close_is_an_error_before_driver_is_closed() {
-        let close = ConnectionClose::new(Lock::new(DriverState::new(false)));
+        let close = ConnectionClose::new(Lock::new(DriverState::new(false, None)));
 
         close.close(ConnectionError::Local(42, "done".to_string()));
 
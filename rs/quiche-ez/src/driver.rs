@@ -1,14 +1,14 @@
 use bytes::Bytes;
 use rustls_pki_types::CertificateDer;
 use std::{
-    collections::{hash_map, HashMap, HashSet},
+    collections::{hash_map, BTreeMap, HashMap, HashSet},
     future::poll_fn,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
     task::{Context, Poll, Waker},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio_quiche::{
     buf_factory::BufFactory,
@@ -16,20 +16,24 @@ use tokio_quiche::{
     quiche,
 };
 
-use crate::ez::Lock;
+use crate::Lock;
+use web_transport_trait::{boxed::BoxFuture, Clock, MemoryAccount, MemoryPermit, TokioClock};
 
 use super::{
-    ConnectionClosed, ConnectionError, ConnectionStats, Metrics, RecvState, RecvStream, SendState,
-    SendStream, StreamId,
+    codes, ConnectionClosed, ConnectionError, ConnectionStats, Metrics, RecvState, RecvStream,
+    SendState, SendStream, StreamId, StreamInfo,
 };
 
-// "drop" in ascii; if you see this then close(code)
-const DROP_CODE: u64 = 0x64726F70;
-
 type OpenBiResult =
     Poll<Result<(Option<Waker>, StreamId, Lock<SendState>, Lock<RecvState>), ConnectionError>>;
 type OpenUniResult = Poll<Result<(Option<Waker>, StreamId, Lock<SendState>), ConnectionError>>;
 
+// A datagram and the budget it holds until the other side of the channel takes it.
+pub(super) type Datagram = (Bytes, Option<MemoryPermit>);
+
+// An outgoing datagram, also carrying when it expires, if ever.
+pub(super) type OutgoingDatagram = (Bytes, Option<MemoryPermit>, Option<Instant>);
+
 pub(super) struct DriverState {
     send: HashSet<StreamId>,
     recv: HashSet<StreamId>,
@@ -62,10 +66,19 @@ pub(super) struct DriverState {
 
     /// Latest connection statistics, refreshed by the driver each poll.
     stats: ConnectionStats,
+
+    /// This connection's share of the server's memory budget, if one was configured.
+    memory: Option<MemoryAccount>,
+
+    /// The time that keep-alives and datagram expiry are measured against.
+    clock: Arc<dyn Clock>,
+
+    /// Callers of `Connection::open_streams`, answered on the driver's next poll.
+    snapshots: Vec<tokio::sync::oneshot::Sender<Vec<StreamInfo>>>,
 }
 
 impl DriverState {
-    pub fn new(server: bool) -> Self {
+    pub fn new(server: bool, memory: Option<MemoryAccount>) -> Self {
         let next_uni = match server {
             true => StreamId::SERVER_UNI,
             false => StreamId::CLIENT_UNI,
@@ -89,14 +102,41 @@ impl DriverState {
             peer_certs: None,
             handshake_wakers: Vec::new(),
             stats: ConnectionStats::default(),
+            memory,
+            clock: Arc::new(TokioClock),
+            snapshots: Vec::new(),
         }
     }
 
+    /// Measure time with `clock` instead of the tokio timer.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the account charged for this connection's buffers, if any.
+    pub fn memory(&self) -> Option<&MemoryAccount> {
+        self.memory.as_ref()
+    }
+
+    /// Returns the clock this connection measures time with.
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
     /// Returns the most recent connection statistics snapshot.
     pub fn stats(&self) -> ConnectionStats {
         self.stats
     }
 
+    /// Returns how many streams we've opened, and how many more the peer allows right now.
+    pub fn streams(&self, bi: bool) -> (u64, u64) {
+        match bi {
+            true => (self.bi.next.index(), self.bi.capacity),
+            false => (self.uni.next.index(), self.uni.capacity),
+        }
+    }
+
     pub fn close(&mut self, err: ConnectionError) -> Vec<Waker> {
         self.close_requested.abort(err)
     }
@@ -165,6 +205,16 @@ impl DriverState {
         std::mem::take(&mut self.handshake_wakers)
     }
 
+    /// Ask the driver for a list of its streams, on its next poll.
+    #[must_use = "wake the driver"]
+    pub fn snapshot(
+        &mut self,
+        reply: tokio::sync::oneshot::Sender<Vec<StreamInfo>>,
+    ) -> Option<Waker> {
+        self.snapshots.push(reply);
+        self.waker.take()
+    }
+
     /// Take the driver's waker, if any. The caller is responsible for waking it.
     #[must_use = "wake the driver"]
     pub fn wake(&mut self) -> Option<Waker> {
@@ -206,8 +256,8 @@ impl DriverState {
         let id = self.bi.next.increment();
         tracing::trace!(?id, "opening bidirectional stream");
 
-        let send = Lock::new(SendState::new(id));
-        let recv = Lock::new(RecvState::new(id));
+        let send = Lock::new(SendState::new(id, self.memory.as_ref()));
+        let recv = Lock::new(RecvState::new(id, self.memory.as_ref()));
         self.bi.create.push((id, (send.clone(), recv.clone())));
 
         let wakeup = self.waker.take();
@@ -229,7 +279,7 @@ impl DriverState {
         let id = self.uni.next.increment();
         tracing::trace!(?id, "opening unidirectional stream");
 
-        let send = Lock::new(SendState::new(id));
+        let send = Lock::new(SendState::new(id, self.memory.as_ref()));
         self.uni.create.push((id, send.clone()));
 
         let wakeup = self.waker.take();
@@ -242,34 +292,40 @@ impl DriverState {
 /// bindings open.
 struct KeepAlive {
     period: Duration,
+    clock: Arc<dyn Clock>,
     /// Created on the first poll so the timer registers with the runtime that
     /// actually drives the connection, not whoever built the endpoint.
-    ticker: Option<tokio::time::Interval>,
+    next: Option<BoxFuture<'static, ()>>,
 }
 
 impl KeepAlive {
-    fn new(period: Duration) -> Self {
+    fn new(period: Duration, clock: Arc<dyn Clock>) -> Self {
         Self {
             period,
-            ticker: None,
+            clock,
+            next: None,
         }
     }
 
     /// Returns true when a keep-alive is due.
     fn poll(&mut self, cx: &mut Context) -> bool {
-        let period = self.period;
-        let ticker = self.ticker.get_or_insert_with(|| {
-            // The first tick is one period out; `interval` would instead fire
-            // immediately and ping a connection that just finished handshaking.
-            let start = tokio::time::Instant::now() + period;
-            let mut ticker = tokio::time::interval_at(start, period);
-            // A late tick means the connection was busy, which is exactly when a
-            // keep-alive is unnecessary. Don't replay the backlog.
-            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
-            ticker
-        });
+        // The first tick is one period out, rather than pinging a connection that
+        // just finished handshaking.
+        let next = self
+            .next
+            .get_or_insert_with(|| self.clock.sleep(self.period));
+        if next.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+
+        // A late tick means the connection was busy, which is exactly when a
+        // keep-alive is unnecessary. Count the next period from now rather than
+        // replaying the backlog.
+        let mut next = self.clock.sleep(self.period);
+        let _ = next.as_mut().poll(cx);
+        self.next = Some(next);
 
-        ticker.poll_tick(cx).is_ready()
+        true
     }
 }
 
@@ -279,19 +335,28 @@ pub(super) struct Driver {
     send: HashMap<StreamId, Lock<SendState>>,
     recv: HashMap<StreamId, Lock<RecvState>>,
 
+    // Finished streams waiting for the peer to acknowledge the FIN.
+    unacked: HashMap<StreamId, Lock<SendState>>,
+
     buf: Vec<u8>,
 
     accept_bi: flume::Sender<(SendStream, RecvStream)>,
     accept_uni: flume::Sender<RecvStream>,
 
     // Datagrams.
-    dgram_in: flume::Sender<Bytes>,
-    dgram_out: flume::Receiver<Bytes>,
+    dgram_in: flume::Sender<Datagram>,
+    dgram_out: flume::Receiver<OutgoingDatagram>,
     // Writable datagram size in bytes, published once at handshake. 0 means the
     // peer didn't negotiate the datagram extension.
     dgram_max: Arc<AtomicUsize>,
+    // Datagrams dropped in either direction, reported in the stats.
+    dgram_dropped: u64,
 
     keep_alive: Option<KeepAlive>,
+
+    // Copied from the DriverState so the hot path doesn't need its lock.
+    memory: Option<MemoryAccount>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Driver {
@@ -299,22 +364,31 @@ impl Driver {
         state: Lock<DriverState>,
         accept_bi: flume::Sender<(SendStream, RecvStream)>,
         accept_uni: flume::Sender<RecvStream>,
-        dgram_in: flume::Sender<Bytes>,
-        dgram_out: flume::Receiver<Bytes>,
+        dgram_in: flume::Sender<Datagram>,
+        dgram_out: flume::Receiver<OutgoingDatagram>,
         dgram_max: Arc<AtomicUsize>,
         keep_alive: Option<Duration>,
     ) -> Self {
+        let (memory, clock) = {
+            let state = state.lock();
+            (state.memory.clone(), state.clock.clone())
+        };
+
         Self {
             state,
             send: HashMap::new(),
             recv: HashMap::new(),
+            unacked: HashMap::new(),
             buf: vec![0u8; BufFactory::MAX_BUF_SIZE],
             accept_bi,
             accept_uni,
             dgram_in,
             dgram_out,
             dgram_max,
-            keep_alive: keep_alive.map(KeepAlive::new),
+            dgram_dropped: 0,
+            keep_alive: keep_alive.map(|period| KeepAlive::new(period, clock.clone())),
+            memory,
+            clock,
         }
     }
 
@@ -414,7 +488,7 @@ impl Driver {
     ) -> Result<(), ConnectionError> {
         tracing::trace!(?stream_id, "accepting bidirectional stream");
 
-        let mut state = RecvState::new(stream_id);
+        let mut state = RecvState::new(stream_id, self.memory.as_ref());
         state.flush(qconn)?;
 
         let state = Lock::new(state);
@@ -422,7 +496,7 @@ impl Driver {
         self.recv.insert(stream_id, state.clone());
         let recv = RecvStream::new(stream_id, state.clone(), self.state.clone());
 
-        let mut state = SendState::new(stream_id);
+        let mut state = SendState::new(stream_id, self.memory.as_ref());
         state.flush(qconn)?;
 
         let state = Lock::new(state);
@@ -443,7 +517,7 @@ impl Driver {
     ) -> Result<(), ConnectionError> {
         tracing::trace!(?stream_id, "accepting unidirectional stream");
 
-        let mut state = RecvState::new(stream_id);
+        let mut state = RecvState::new(stream_id, self.memory.as_ref());
         state.flush(qconn)?;
 
         let state = Lock::new(state);
@@ -468,10 +542,14 @@ impl Driver {
 
                     let waker = state.flush(qconn)?;
                     let closed = state.is_closed();
+                    let unacked = state.is_unacked();
                     drop(state);
 
                     if closed {
-                        entry.remove();
+                        let state = entry.remove();
+                        if unacked {
+                            self.unacked.insert(stream_id, state);
+                        }
                     }
 
                     if let Some(waker) = waker {
@@ -505,16 +583,21 @@ impl Driver {
                         ConnectionError::Local(code, reason) => {
                             qconn.close(true, code, reason.as_bytes())
                         }
-                        ConnectionError::Dropped => qconn.close(true, DROP_CODE, b"dropped"),
+                        ConnectionError::Dropped => {
+                            qconn.close(true, codes::CONNECTION_DROPPED, b"dropped")
+                        }
                         ConnectionError::Remote(code, reason) => {
                             // This shouldn't happen, but just echo it back in case.
                             qconn.close(true, code, reason.as_bytes())
                         }
                         ConnectionError::Quiche(e) => {
-                            qconn.close(true, 500, e.to_string().as_bytes())
+                            qconn.close(true, codes::QUICHE_ERROR, e.to_string().as_bytes())
                         }
                         ConnectionError::Unknown(reason) => {
-                            qconn.close(true, 501, reason.as_bytes())
+                            qconn.close(true, codes::UNKNOWN_ERROR, reason.as_bytes())
+                        }
+                        ConnectionError::TimedOut => {
+                            qconn.close(true, codes::UNKNOWN_ERROR, b"timed out")
                         }
                     }
                     .map_err(ConnectionError::Quiche),
@@ -538,9 +621,12 @@ impl Driver {
         }
 
         // Snapshot stats while we hold an immutable view; stored under the lock below.
-        let stats = ConnectionStats::from_quiche(qconn);
+        let stats = ConnectionStats {
+            datagrams_dropped: self.dgram_dropped,
+            ..ConnectionStats::from_quiche(qconn)
+        };
 
-        let (sleep, send, recv, bi_wakers, uni_wakers) = {
+        let (sleep, send, recv, bi_wakers, uni_wakers, snapshots) = {
             let mut driver = self.state.lock();
             driver.stats = stats;
             // Park the waker before checking for work. `send_datagram` pushes
@@ -550,7 +636,8 @@ impl Driver {
             // already enqueued an item we will see here.
             driver.waker = Some(waker.clone());
 
-            let dgram_work = !self.dgram_out.is_empty();
+            // Datagrams wait in the channel while quiche's queue is full, rather than spinning.
+            let dgram_work = !self.dgram_out.is_empty() && !qconn.is_dgram_send_queue_full();
 
             let sleep = driver.bi.create.is_empty()
                 && driver.uni.create.is_empty()
@@ -580,8 +667,9 @@ impl Driver {
 
             let send = std::mem::take(&mut driver.send);
             let recv = std::mem::take(&mut driver.recv);
+            let snapshots = std::mem::take(&mut driver.snapshots);
 
-            (sleep, send, recv, bi_wakers, uni_wakers)
+            (sleep, send, recv, bi_wakers, uni_wakers, snapshots)
         };
 
         for waker in bi_wakers.unwrap_or_default() {
@@ -600,6 +688,17 @@ impl Driver {
             self.flush_send(qconn, stream_id)?;
         }
 
+        self.check_acked(qconn);
+
+        // Answered after the flushes above, and without the driver lock: streams take
+        // their own lock before the driver's.
+        if !snapshots.is_empty() {
+            let streams = self.streams();
+            for reply in snapshots {
+                reply.send(streams.clone()).ok();
+            }
+        }
+
         // Returning Ready hands control back to the io loop, which flushes the
         // scheduled PING to the socket.
         if sleep && !keep_alive {
@@ -647,10 +746,14 @@ impl Driver {
 
             let waker = state.flush(qconn)?;
             let closed = state.is_closed();
+            let unacked = state.is_unacked();
             drop(state);
 
             if closed {
-                entry.remove();
+                let state = entry.remove();
+                if unacked {
+                    self.unacked.insert(stream_id, state);
+                }
             }
 
             if let Some(waker) = waker {
@@ -663,6 +766,46 @@ impl Driver {
         Ok(())
     }
 
+    // Every stream the driver is tracking, in order of ID.
+    fn streams(&self) -> Vec<StreamInfo> {
+        let mut streams = BTreeMap::new();
+        let blank = |id| StreamInfo {
+            id,
+            send: None,
+            recv: None,
+        };
+
+        for (&id, state) in self.send.iter().chain(&self.unacked) {
+            streams.entry(id).or_insert_with(|| blank(id)).send = Some(state.lock().info());
+        }
+
+        for (&id, state) in &self.recv {
+            streams.entry(id).or_insert_with(|| blank(id)).recv = Some(state.lock().info());
+        }
+
+        streams.into_values().collect()
+    }
+
+    // quiche drops a stream once the peer has acknowledged everything, including the FIN.
+    fn check_acked(&mut self, qconn: &mut QuicheConnection) {
+        let mut wakers = Vec::new();
+
+        self.unacked.retain(|stream_id, state| {
+            let stop = match qconn.stream_capacity((*stream_id).into()) {
+                Ok(_) => return true,
+                Err(quiche::Error::StreamStopped(code)) => Some(code),
+                Err(_) => None,
+            };
+
+            wakers.extend(state.lock().set_acked(stop));
+            false
+        });
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
     fn abort(&mut self, err: ConnectionError) {
         let wakers = self.state.lock().close_requested.abort(err);
         for waker in wakers {
@@ -712,15 +855,31 @@ impl tokio_quiche::ApplicationOverQuic for Driver {
 
         // Drain any incoming datagrams into the application-side flume channel.
         // The channel is bounded — if the application can't keep up we drop
-        // the new datagram (consistent with the unreliable contract).
+        // the new datagram (consistent with the unreliable contract). The same
+        // goes for a datagram the memory budget can't cover.
         loop {
             match qconn.dgram_recv(&mut self.buf) {
                 Ok(len) => {
+                    let permit = match &self.memory {
+                        Some(memory) => match memory.try_reserve(len) {
+                            Some(permit) => Some(permit),
+                            None => {
+                                tracing::trace!(
+                                    "dropping incoming datagram: memory budget exhausted"
+                                );
+                                self.dgram_dropped += 1;
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+
                     let buf = Bytes::copy_from_slice(&self.buf[..len]);
-                    match self.dgram_in.try_send(buf) {
+                    match self.dgram_in.try_send((buf, permit)) {
                         Ok(()) => {}
                         Err(flume::TrySendError::Full(_)) => {
                             tracing::trace!("dropping incoming datagram: channel full");
+                            self.dgram_dropped += 1;
                         }
                         Err(flume::TrySendError::Disconnected(_)) => {
                             // Receiver dropped — connection gone or not interested.
@@ -745,14 +904,27 @@ impl tokio_quiche::ApplicationOverQuic for Driver {
             return Ok(());
         }
 
-        // Datagrams are unreliable by spec — on any send failure (queue full,
-        // too large, peer didn't negotiate, etc.) we drop the datagram rather
-        // than buffer it and risk leaking memory under backpressure.
-        while let Ok(buf) = self.dgram_out.try_recv() {
+        // Leave datagrams in the bounded channel while quiche's queue is full, so
+        // `send_datagram_wait` callers block on it. Datagrams are unreliable by spec —
+        // on any other send failure (too large, peer didn't negotiate, etc.) we drop
+        // the datagram rather than buffer it and risk leaking memory. One that waited
+        // in the channel past its max age is dropped too, rather than sent late.
+        while !qconn.is_dgram_send_queue_full() {
+            let Ok((buf, _permit, expires)) = self.dgram_out.try_recv() else {
+                break;
+            };
+
+            if expires.is_some_and(|expires| expires <= self.clock.now()) {
+                tracing::trace!(len = buf.len(), "dropping expired outbound datagram");
+                self.dgram_dropped += 1;
+                continue;
+            }
+
             match qconn.dgram_send(&buf) {
                 Ok(()) => {}
                 Err(err) => {
                     tracing::trace!(?err, len = buf.len(), "dropping outbound datagram");
+                    self.dgram_dropped += 1;
                 }
             }
         }
@@ -776,6 +948,8 @@ impl tokio_quiche::ApplicationOverQuic for Driver {
         } else if let Some(peer) = qconn.peer_error() {
             let reason = String::from_utf8_lossy(&peer.reason).to_string();
             ConnectionError::Remote(peer.error_code, reason)
+        } else if qconn.is_timed_out() {
+            ConnectionError::TimedOut
         } else if let Err(err) = connection_result {
             ConnectionError::Unknown(err.to_string())
         } else {
@@ -825,7 +999,7 @@ mod tests {
         // The established flag, not the ALPN, is what resolves the handshake: a
         // connection that negotiates no ALPN must still hand back a Connection
         // rather than wait forever.
-        let mut state = DriverState::new(false);
+        let mut state = DriverState::new(false, None);
         let waker = Waker::noop();
 
         assert!(state.poll_handshake(waker).is_pending());
@@ -839,7 +1013,7 @@ mod tests {
 
     #[test]
     fn closed_waits_for_driver_completion() {
-        let mut state = DriverState::new(false);
+        let mut state = DriverState::new(false, None);
         let waker = Waker::noop();
         let err = ConnectionError::Local(42, "done".to_string());
 
@@ -0,0 +1,58 @@
+//! Easy-to-use QUIC connection and stream management.
+//!
+//! This crate provides a simplified async interface for working with raw QUIC connections
+//! using [tokio-quiche](https://docs.rs/tokio-quiche). It handles the low-level details of
+//! connection management, stream creation, and I/O operations.
+//!
+//! # Clients
+//! [ClientBuilder] dials a host, racing its resolved addresses ([happy_eyeballs]) and
+//! verifying the server against the system roots unless told otherwise.
+//!
+//! # Servers
+//! [ServerBuilder] listens on one or more sockets and hands out an [Incoming] for each
+//! connection attempt. The TLS certificate is either fixed ([ServerBuilder::with_single_cert])
+//! or picked per connection from the SNI ([ServerBuilder::with_cert_resolver]).
+//!
+//! # Connections
+//! A [Connection] exposes QUIC streams ([SendStream], [RecvStream]) and datagrams, plus the
+//! ALPN, SNI, and peer certificates negotiated during the handshake.
+//!
+//! There's nothing HTTP/3 or WebTransport specific here; see
+//! [web-transport-quiche](https://docs.rs/web-transport-quiche) for that.
+
+mod client;
+pub mod codes;
+mod connection;
+mod driver;
+mod lock;
+mod recv;
+mod send;
+mod server;
+mod socket;
+mod stream;
+pub mod tls;
+
+pub use client::*;
+pub use connection::*;
+pub use happy_eyeballs::*;
+pub use recv::*;
+pub use send::*;
+pub use server::*;
+pub use socket::SocketOptions;
+pub use stream::*;
+
+use driver::*;
+use lock::*;
+
+pub use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+/// Re-export the TLS implementation and certificate types, so callers don't need matching versions.
+pub use boring;
+pub use rustls_pki_types as pki_types;
+pub use tls::{CertResolver, CertifiedKey, ClientAuth};
+pub use tokio_quiche::metrics::{DefaultMetrics, Metrics};
+/// Compression applied to the qlog traces written to [`Settings::qlog_dir`].
+pub use tokio_quiche::settings::QlogCompression;
+pub use tokio_quiche::settings::QuicSettings as Settings;
+pub use web_transport_trait::happy_eyeballs;
+pub use web_transport_trait::{Clock, DatagramOptions, MemoryAccount, MemoryBudget, TokioClock};
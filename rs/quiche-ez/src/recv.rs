@@ -11,14 +11,12 @@ use tokio_quiche::quiche;
 use bytes::{BufMut, Bytes, BytesMut};
 use tokio::io::{AsyncRead, ReadBuf};
 
-use crate::ez::DriverState;
+use crate::DriverState;
 
-use super::{Lock, StreamError, StreamId};
+use super::{codes, Lock, RecvInfo, StreamError, StreamId};
 
 use tokio_quiche::quic::QuicheConnection;
-
-// "recv" in ascii; if you see this then read everything or close(code)
-const DROP_CODE: u64 = 0x72656376;
+use web_transport_trait::{MemoryAccount, MemoryPermit};
 
 pub(super) struct RecvState {
     id: StreamId,
@@ -49,10 +47,13 @@ pub(super) struct RecvState {
 
     // Set when FIN is received, STOP_SENDING is sent, or RESET_STREAM is received.
     closed: bool,
+
+    // Budget covering the queued bytes, if the server has a memory budget.
+    memory: Option<MemoryPermit>,
 }
 
 impl RecvState {
-    pub fn new(id: StreamId) -> Self {
+    pub fn new(id: StreamId, memory: Option<&MemoryAccount>) -> Self {
         Self {
             id,
             queued: Default::default(),
@@ -64,6 +65,7 @@ impl RecvState {
             buf: BytesMut::with_capacity(64),
             buf_capacity: 64,
             closed: false,
+            memory: memory.map(MemoryAccount::empty),
         }
     }
 
@@ -85,6 +87,7 @@ impl RecvState {
                 let remain = chunk.split_off(max);
                 self.queued.push_front(remain);
             }
+            self.release(chunk.len());
             return Poll::Ready(Ok(Some(chunk)));
         }
 
@@ -103,6 +106,26 @@ impl RecvState {
         Poll::Pending
     }
 
+    // Move queued chunks into `chunks` without waiting, up to `max` bytes in total.
+    pub fn take_queued(&mut self, mut max: usize, chunks: &mut Vec<Bytes>) {
+        if self.reset.is_some() || self.stop.is_some() {
+            return;
+        }
+
+        while max > 0 {
+            let Some(mut chunk) = self.queued.pop_front() else {
+                break;
+            };
+            if chunk.len() > max {
+                let remain = chunk.split_off(max);
+                self.queued.push_front(remain);
+            }
+            max -= chunk.len();
+            self.release(chunk.len());
+            chunks.push(chunk);
+        }
+    }
+
     pub fn poll_closed(&mut self, waker: &Waker) -> Poll<Result<(), StreamError>> {
         if self.fin && self.queued.is_empty() {
             Poll::Ready(Ok(()))
@@ -132,6 +155,7 @@ impl RecvState {
                 Err(e) => return Err(e),
             }
             self.closed = true;
+            self.release(usize::MAX);
             return Ok(self.blocked.take());
         }
 
@@ -156,10 +180,32 @@ impl RecvState {
                     self.buf.spare_capacity_mut(),
                 )
             };
-            let n = buf.len().min(self.max);
+            let mut n = buf.len().min(self.max);
+
+            // Leave the data in quiche, where flow control bounds it, until the
+            // budget can cover it. The reader is woken once bytes are released
+            // and asks the driver to try again.
+            if let Some(memory) = &mut self.memory {
+                let permit = match &self.blocked {
+                    Some(waker) => memory.account().poll_reserve_up_to(waker, n),
+                    None => memory
+                        .account()
+                        .try_reserve_up_to(n)
+                        .map_or(Poll::Pending, Poll::Ready),
+                };
+                let Poll::Ready(permit) = permit else {
+                    tracing::trace!(stream_id = ?self.id, "memory budget exhausted");
+                    break;
+                };
+                n = permit.size();
+                memory.absorb(permit);
+            }
+            let reserved = n;
 
             match qconn.stream_recv(self.id.into(), &mut buf[..n]) {
                 Ok((n, done)) => {
+                    self.release(reserved - n);
+
                     // Advance the buffer by the number of bytes read.
                     unsafe { self.buf.set_len(self.buf.len() + n) };
 
@@ -184,6 +230,7 @@ impl RecvState {
                     }
                 }
                 Err(quiche::Error::Done) => {
+                    self.release(reserved);
                     if qconn.stream_finished(self.id.into()) {
                         tracing::trace!(stream_id = ?self.id, "received FIN");
 
@@ -198,6 +245,7 @@ impl RecvState {
 
                     self.reset = Some(code);
                     self.closed = true;
+                    self.release(usize::MAX);
                     return Ok(self.blocked.take());
                 }
                 Err(e) => return Err(e),
@@ -215,6 +263,23 @@ impl RecvState {
     pub fn is_closed(&self) -> bool {
         self.closed
     }
+
+    // A snapshot for Connection::open_streams.
+    pub fn info(&self) -> RecvInfo {
+        RecvInfo {
+            queued: self.queued.iter().map(Bytes::len).sum(),
+            fin: self.fin,
+            reset: self.reset,
+            stopped: self.stop,
+        }
+    }
+
+    // Return bytes that are no longer queued to the memory budget.
+    fn release(&mut self, size: usize) {
+        if let Some(memory) = &mut self.memory {
+            memory.release(size);
+        }
+    }
 }
 
 /// A stream that can be used to receive bytes.
@@ -276,6 +341,25 @@ impl RecvStream {
         Poll::Pending
     }
 
+    /// Read every chunk that's already queued, up to `max` bytes in total.
+    ///
+    /// Only waits if nothing is queued, so a burst is drained with one wakeup.
+    /// Returns [None] if the stream has been finished by the remote.
+    pub async fn read_ready_chunks(
+        &mut self,
+        max: usize,
+    ) -> Result<Option<Vec<Bytes>>, StreamError> {
+        let Some(first) = self.read_chunk(max).await? else {
+            return Ok(None);
+        };
+
+        let remaining = max - first.len();
+        let mut chunks = vec![first];
+        self.state.lock().take_queued(remaining, &mut chunks);
+
+        Ok(Some(chunks))
+    }
+
     /// Read data into a mutable buffer and return the amount read.
     ///
     /// The buffer will be advanced by the number of bytes read.
@@ -303,6 +387,16 @@ impl RecvStream {
         Ok(limit.into_inner().freeze())
     }
 
+    /// Read until the end of the stream, failing with [StreamError::TooLong] if it exceeds `max`.
+    pub async fn read_to_end(&mut self, max: usize) -> Result<Bytes, StreamError> {
+        // Read one byte past the limit, to tell a stream that ends there from a longer one.
+        let buf = self.read_all(max.saturating_add(1)).await?;
+        if buf.len() > max {
+            return Err(StreamError::TooLong { limit: max });
+        }
+        Ok(buf)
+    }
+
     /// Tell the other end to stop sending data with the given error code.
     ///
     /// This sends a STOP_SENDING frame to the remote.
@@ -315,6 +409,11 @@ impl RecvStream {
         }
     }
 
+    /// Returns the code from the peer's RESET_STREAM, once it has arrived.
+    pub fn reset_code(&self) -> Option<u64> {
+        self.state.lock().reset
+    }
+
     /// Returns true if the stream is closed by either side.
     ///
     /// This includes:
@@ -355,7 +454,7 @@ impl Drop for RecvStream {
         let mut state = self.state.lock();
 
         if !state.fin && state.reset.is_none() && state.stop.is_none() {
-            state.stop = Some(DROP_CODE);
+            state.stop = Some(codes::RECV_DROPPED);
             // Avoid two locks at once.
             drop(state);
 
@@ -11,13 +11,11 @@ use bytes::{Buf, Bytes};
 use tokio::io::AsyncWrite;
 
 use tokio_quiche::quic::QuicheConnection;
+use web_transport_trait::{MemoryAccount, MemoryPermit};
 
-use crate::ez::DriverState;
+use crate::DriverState;
 
-use super::{Lock, StreamError, StreamId};
-
-// "send" in ascii; if you see this then call finish().await or close(code)
-const DROP_CODE: u64 = 0x73656E64;
+use super::{codes, Lock, SendInfo, StreamError, StreamId};
 
 // TODO Move a lot of this into a state machine enum.
 pub(super) struct SendState {
@@ -44,12 +42,21 @@ pub(super) struct SendState {
     // received SET_PRIORITY
     priority: Option<u8>,
 
+    // The driver has been told about this stream and hasn't flushed it yet.
+    scheduled: bool,
+
     // No more progress can be made on the stream.
     closed: bool,
+
+    // The peer acknowledged everything, including the FIN.
+    acked: bool,
+
+    // Budget covering the queued bytes, if the server has a memory budget.
+    memory: Option<MemoryPermit>,
 }
 
 impl SendState {
-    pub fn new(id: StreamId) -> Self {
+    pub fn new(id: StreamId, memory: Option<&MemoryAccount>) -> Self {
         Self {
             id,
             capacity: 0,
@@ -59,7 +66,10 @@ impl SendState {
             reset: None,
             stop: None,
             priority: None,
+            scheduled: false,
             closed: false,
+            acked: false,
+            memory: memory.map(MemoryAccount::empty),
         }
     }
 
@@ -83,7 +93,15 @@ impl SendState {
             return Poll::Pending;
         }
 
-        let n = self.capacity.min(buf.remaining());
+        let mut n = self.capacity.min(buf.remaining());
+
+        // Wait for the budget when it can't cover even a byte; the waker is
+        // woken as soon as any other stream or connection releases some.
+        if let Some(memory) = &mut self.memory {
+            let permit = ready!(memory.account().poll_reserve_up_to(cx.waker(), n));
+            n = permit.size();
+            memory.absorb(permit);
+        }
 
         // NOTE: Avoids a copy when Buf is Bytes.
         let chunk = buf.copy_to_bytes(n);
@@ -94,6 +112,34 @@ impl SendState {
         Poll::Ready(Ok(n))
     }
 
+    // Queue as much of the buffer as the capacity allows, returning Pending once it runs out.
+    // Returns the number of bytes queued, which may be non-zero even when Pending or an error.
+    fn poll_write_all_buf<B: Buf>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut B,
+    ) -> (Poll<Result<(), StreamError>>, usize) {
+        let mut queued = 0;
+
+        while buf.has_remaining() {
+            match self.poll_write_buf(cx, buf) {
+                Poll::Ready(Ok(n)) => queued += n,
+                Poll::Ready(Err(err)) => return (Poll::Ready(Err(err)), queued),
+                Poll::Pending => return (Poll::Pending, queued),
+            }
+        }
+
+        (Poll::Ready(Ok(())), queued)
+    }
+
+    // Returns true if the driver needs to be told about this stream.
+    //
+    // Writers that queue more data before the driver gets around to flushing
+    // piggyback on the first notification instead of locking the driver again.
+    fn schedule(&mut self) -> bool {
+        !std::mem::replace(&mut self.scheduled, true)
+    }
+
     pub fn poll_closed(&mut self, waker: &Waker) -> Poll<Result<(), StreamError>> {
         if let Some(reset) = self.reset {
             return Poll::Ready(Err(StreamError::Reset(reset)));
@@ -110,6 +156,20 @@ impl SendState {
         Poll::Pending
     }
 
+    pub fn poll_acked(&mut self, waker: &Waker) -> Poll<Result<(), StreamError>> {
+        if let Some(reset) = self.reset {
+            return Poll::Ready(Err(StreamError::Reset(reset)));
+        } else if let Some(stop) = self.stop {
+            return Poll::Ready(Err(StreamError::Stop(stop)));
+        } else if self.acked {
+            return Poll::Ready(Ok(()));
+        }
+
+        self.blocked = Some(waker.clone());
+
+        Poll::Pending
+    }
+
     pub fn poll_flushed(&mut self, waker: &Waker) -> Poll<Result<(), StreamError>> {
         if let Some(reset) = self.reset {
             return Poll::Ready(Err(StreamError::Reset(reset)));
@@ -126,6 +186,9 @@ impl SendState {
 
     #[must_use = "wake the driver"]
     pub fn flush(&mut self, qconn: &mut QuicheConnection) -> quiche::Result<Option<Waker>> {
+        // Anything queued from now on needs another notification.
+        self.scheduled = false;
+
         if let Some(code) = self.reset {
             tracing::trace!(stream_id = ?self.id, code, "sending RESET_STREAM");
             // Resetting a single stream must never tear down the whole connection.
@@ -136,6 +199,7 @@ impl SendState {
                 Err(e) => return Err(e),
             }
             self.closed = true;
+            self.discard();
             return Ok(self.blocked.take());
         }
 
@@ -157,11 +221,16 @@ impl SendState {
 
                     self.stop = Some(code);
                     self.closed = true;
+                    self.discard();
                     return Ok(self.blocked.take());
                 }
                 Err(e) => return Err(e),
             };
 
+            if let Some(memory) = &mut self.memory {
+                memory.release(n);
+            }
+
             tracing::trace!(
                 stream_id = ?self.id,
                 size = n,
@@ -196,6 +265,7 @@ impl SendState {
 
                 self.stop = Some(code);
                 self.closed = true;
+                self.discard();
                 return Ok(self.blocked.take());
             }
             Err(e) => return Err(e),
@@ -211,6 +281,14 @@ impl SendState {
         Ok(None)
     }
 
+    // Drop anything still queued, returning it to the memory budget.
+    fn discard(&mut self) {
+        self.queued.clear();
+        if let Some(memory) = &mut self.memory {
+            memory.release(memory.size());
+        }
+    }
+
     pub fn is_finished(&self) -> Result<bool, StreamError> {
         if let Some(reset) = self.reset {
             Err(StreamError::Reset(reset))
@@ -224,6 +302,32 @@ impl SendState {
     pub fn is_closed(&self) -> bool {
         self.closed
     }
+
+    // The FIN was sent, but we don't know yet if the peer received it.
+    pub fn is_unacked(&self) -> bool {
+        self.closed && self.fin && self.reset.is_none() && self.stop.is_none() && !self.acked
+    }
+
+    // Called by the driver once quiche has dropped the stream or reported STOP_SENDING.
+    #[must_use = "wake the application"]
+    pub fn set_acked(&mut self, stop: Option<u64>) -> Option<Waker> {
+        match stop {
+            Some(code) => self.stop = Some(code),
+            None => self.acked = true,
+        }
+        self.blocked.take()
+    }
+
+    // A snapshot for Connection::open_streams.
+    pub fn info(&self) -> SendInfo {
+        SendInfo {
+            queued: self.queued.iter().map(Bytes::len).sum(),
+            fin: self.fin,
+            reset: self.reset,
+            stopped: self.stop,
+            unacked: self.is_unacked(),
+        }
+    }
 }
 
 /// A stream that can be used to send bytes.
@@ -257,14 +361,20 @@ impl SendStream {
         cx: &mut Context<'_>,
         buf: &mut B,
     ) -> Poll<Result<usize, StreamError>> {
-        if let Poll::Ready(res) = self.state.lock().poll_write_buf(cx, buf) {
+        let (res, schedule) = {
+            let mut state = self.state.lock();
+            let res = state.poll_write_buf(cx, buf);
+            let schedule = res.is_ready() && state.schedule();
+            (res, schedule)
+        };
+
+        if schedule {
             // Tell the driver that the stream has data to send.
-            let waker = self.driver.lock().send(self.id);
-            if let Some(waker) = waker {
-                waker.wake();
-            }
+            self.wake_driver();
+        }
 
-            return Poll::Ready(res);
+        if res.is_ready() {
+            return res;
         }
 
         if let Poll::Ready(res) = self.driver.lock().error(cx.waker()) {
@@ -274,13 +384,41 @@ impl SendStream {
         Poll::Pending
     }
 
-    /// Write all of the slice to the stream.
-    pub async fn write_all(&mut self, mut buf: &[u8]) -> Result<(), StreamError> {
-        while !buf.is_empty() {
-            let n = self.write(buf).await?;
-            buf = &buf[n..];
+    // Write the entire buffer to the stream, advancing the internal position.
+    //
+    // Each poll queues as much as the capacity allows under a single lock and wakes
+    // the driver at most once, rather than once per chunk.
+    fn poll_write_all_buf<B: Buf>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut B,
+    ) -> Poll<Result<(), StreamError>> {
+        let (res, schedule) = {
+            let mut state = self.state.lock();
+            let (res, queued) = state.poll_write_all_buf(cx, buf);
+            let schedule = queued > 0 && state.schedule();
+            (res, schedule)
+        };
+
+        if schedule {
+            self.wake_driver();
         }
-        Ok(())
+
+        if res.is_ready() {
+            return res;
+        }
+
+        if let Poll::Ready(res) = self.driver.lock().error(cx.waker()) {
+            return Poll::Ready(Err(res.into()));
+        }
+
+        Poll::Pending
+    }
+
+    /// Write all of the slice to the stream.
+    pub async fn write_all(&mut self, buf: &[u8]) -> Result<(), StreamError> {
+        let mut buf = io::Cursor::new(buf);
+        poll_fn(|cx| self.poll_write_all_buf(cx, &mut buf)).await
     }
 
     /// Write some of the buffer to the stream, advancing the internal position.
@@ -292,10 +430,14 @@ impl SendStream {
 
     /// Write the entire buffer to the stream, advancing the internal position.
     pub async fn write_buf_all<B: Buf>(&mut self, buf: &mut B) -> Result<(), StreamError> {
-        while buf.has_remaining() {
-            self.write_buf(buf).await?;
+        poll_fn(|cx| self.poll_write_all_buf(cx, buf)).await
+    }
+
+    fn wake_driver(&self) {
+        let waker = self.driver.lock().send(self.id);
+        if let Some(waker) = waker {
+            waker.wake();
         }
-        Ok(())
     }
 
     /// Mark the stream as finished, such that no more data can be written.
@@ -342,6 +484,11 @@ impl SendStream {
         }
     }
 
+    /// Returns the code from the peer's STOP_SENDING, once it has arrived.
+    pub fn stop_code(&self) -> Option<u64> {
+        self.state.lock().stop
+    }
+
     /// Returns true if the stream is closed by either side.
     ///
     /// This includes:
@@ -364,6 +511,18 @@ impl SendStream {
         Poll::Pending
     }
 
+    fn poll_acked(&mut self, waker: &Waker) -> Poll<Result<(), StreamError>> {
+        if let Poll::Ready(res) = self.state.lock().poll_acked(waker) {
+            return Poll::Ready(res);
+        }
+
+        if let Poll::Ready(res) = self.driver.lock().error(waker) {
+            return Poll::Ready(Err(res.into()));
+        }
+
+        Poll::Pending
+    }
+
     fn poll_flushed(&mut self, waker: &Waker) -> Poll<Result<(), StreamError>> {
         if let Poll::Ready(res) = self.state.lock().poll_flushed(waker) {
             return Poll::Ready(res);
@@ -388,6 +547,13 @@ impl SendStream {
         poll_fn(|cx| self.poll_closed(cx.waker())).await
     }
 
+    /// Wait until the peer has acknowledged all data, including the FIN from [SendStream::finish].
+    ///
+    /// Returns an error if the stream was reset or the peer sent STOP_SENDING first.
+    pub async fn acked(&mut self) -> Result<(), StreamError> {
+        poll_fn(|cx| self.poll_acked(cx.waker())).await
+    }
+
     /// Set the priority of this stream.
     ///
     /// Lower priority values are sent first. Defaults to 0.
@@ -407,7 +573,7 @@ impl Drop for SendStream {
 
         if !state.fin && state.reset.is_none() && state.stop.is_none() {
             // Reset the stream if we're dropped without calling finish.
-            state.reset = Some(DROP_CODE);
+            state.reset = Some(codes::SEND_DROPPED);
             drop(state);
 
             let waker = self.driver.lock().send(self.id);
@@ -454,3 +620,48 @@ impl AsyncWrite for SendStream {
             .map_err(|e| io::Error::other(e.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_all_queues_up_to_capacity_and_schedules_once() {
+        let mut state = SendState::new(StreamId::from(2u64), None);
+        state.capacity = 10;
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut buf = Bytes::from_static(&[0u8; 25]);
+
+        let (res, queued) = state.poll_write_all_buf(&mut cx, &mut buf);
+        assert!(res.is_pending());
+        assert_eq!(queued, 10);
+        assert_eq!(buf.remaining(), 15);
+        assert_eq!(state.queued.len(), 1);
+
+        // The first writer tells the driver; the rest ride along until it flushes.
+        assert!(state.schedule());
+        assert!(!state.schedule());
+    }
+
+    #[test]
+    fn write_waits_for_memory_budget() {
+        let budget = web_transport_trait::MemoryBudget::new(4);
+        let account = budget.account();
+        let mut state = SendState::new(StreamId::from(2u64), Some(&account));
+        state.capacity = 10;
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut buf = Bytes::from_static(&[0u8; 10]);
+
+        // Only what the budget covers is queued, even with capacity to spare.
+        let (res, queued) = state.poll_write_all_buf(&mut cx, &mut buf);
+        assert!(res.is_pending());
+        assert_eq!(queued, 4);
+        assert_eq!(account.used(), 4);
+
+        // Discarding the queue hands the bytes back.
+        state.discard();
+        assert_eq!(budget.used(), 0);
+    }
+}
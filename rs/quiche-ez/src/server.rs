@@ -1,8 +1,9 @@
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{io, marker::PhantomData};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio::task::JoinSet;
 use tokio_quiche::quic::SimpleConnectionIdGenerator;
 use tokio_quiche::settings::{CertificateKind, Hooks, TlsCertificatePaths};
@@ -10,14 +11,16 @@ use tokio_quiche::socket::QuicListener;
 
 use rustls_pki_types::{CertificateDer, PrivateKeyDer};
 
-use crate::ez::socket::capabilities;
-use crate::ez::tls::{DynamicCertHook, StaticCertHook};
-use crate::ez::DriverState;
+use crate::codes;
+use crate::socket::capabilities;
+use crate::tls::{keylog_file, DynamicCertHook, StaticCertHook};
+use crate::DriverState;
+use crate::SocketOptions;
 
 use super::client::DGRAM_CHANNEL_CAPACITY;
 use super::{
-    CertResolver, ClientAuth, Connection, ConnectionError, DefaultMetrics, Driver, Lock, Metrics,
-    Settings,
+    CertResolver, ClientAuth, Clock, Connection, ConnectionError, DefaultMetrics, Driver, Lock,
+    MemoryBudget, Metrics, Settings, TokioClock,
 };
 
 /// Used with [ServerBuilder] to require specific parameters.
@@ -49,6 +52,9 @@ pub struct ServerBuilder<M: Metrics = DefaultMetrics, S = ServerInit> {
     keep_alive: Option<Duration>,
     gso: bool,
     client_auth: ClientAuth,
+    socket_options: SocketOptions,
+    memory_budget: Option<MemoryBudget>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Default for ServerBuilder<DefaultMetrics> {
@@ -70,6 +76,9 @@ impl ServerBuilder<DefaultMetrics, ServerInit> {
             keep_alive: None,
             gso: true,
             client_auth: ClientAuth::None,
+            socket_options: SocketOptions::default(),
+            memory_budget: None,
+            clock: Arc::new(TokioClock),
         }
     }
 }
@@ -84,6 +93,9 @@ impl<M: Metrics> ServerBuilder<M, ServerInit> {
             keep_alive: self.keep_alive,
             gso: self.gso,
             client_auth: self.client_auth,
+            socket_options: self.socket_options,
+            memory_budget: self.memory_budget,
+            clock: self.clock,
         }
     }
 
@@ -114,6 +126,22 @@ impl<M: Metrics> ServerBuilder<M, ServerInit> {
         self
     }
 
+    /// Write a qlog trace of each connection to a file in `dir`.
+    ///
+    /// See [ServerBuilder::with_qlog_dir](ServerBuilder::<M, ServerWithListener>::with_qlog_dir).
+    pub fn with_qlog_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.settings.qlog_dir = Some(dir.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// See [ServerBuilder::with_keylog](ServerBuilder::<M, ServerWithListener>::with_keylog).
+    pub fn with_keylog(mut self, enabled: bool) -> Self {
+        self.settings.keylog_file = keylog_file(enabled);
+        self
+    }
+
     /// Send a PING to each client on this interval, keeping idle connections alive.
     ///
     /// See [ServerBuilder::with_keep_alive](ServerBuilder::<M, ServerWithListener>::with_keep_alive).
@@ -122,6 +150,22 @@ impl<M: Metrics> ServerBuilder<M, ServerInit> {
         self
     }
 
+    /// Close a connection after this long without hearing from the client.
+    ///
+    /// See [ServerBuilder::with_idle_timeout](ServerBuilder::<M, ServerWithListener>::with_idle_timeout).
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.max_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Queue up to this many outgoing datagrams per connection that haven't been sent yet.
+    ///
+    /// See [ServerBuilder::with_datagram_send_buffer](ServerBuilder::<M, ServerWithListener>::with_datagram_send_buffer).
+    pub fn with_datagram_send_buffer(mut self, datagrams: usize) -> Self {
+        self.settings.dgram_send_max_queue_len = datagrams;
+        self
+    }
+
     /// Enable UDP generic segmentation offload (GSO), on by default.
     ///
     /// See [ServerBuilder::with_gso](ServerBuilder::<M, ServerWithListener>::with_gso).
@@ -130,6 +174,14 @@ impl<M: Metrics> ServerBuilder<M, ServerInit> {
         self
     }
 
+    /// Apply these [SocketOptions] to the UDP sockets the server listens on.
+    ///
+    /// See [ServerBuilder::with_socket_options](ServerBuilder::<M, ServerWithListener>::with_socket_options).
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
     /// Authenticate clients with mTLS.
     ///
     /// Defaults to [ClientAuth::None].
@@ -137,6 +189,22 @@ impl<M: Metrics> ServerBuilder<M, ServerInit> {
         self.client_auth = auth;
         self
     }
+
+    /// Cap the bytes queued across every connection with a shared [MemoryBudget].
+    ///
+    /// See [ServerBuilder::with_memory_budget](ServerBuilder::<M, ServerWithListener>::with_memory_budget).
+    pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Measure keep-alives and datagram expiry with `clock` instead of the tokio timer.
+    ///
+    /// See [ServerBuilder::with_clock](ServerBuilder::<M, ServerWithListener>::with_clock).
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
 }
 
 impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
@@ -160,7 +228,7 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
     /// Listen for incoming packets on the given address.
     pub fn with_bind<A: std::net::ToSocketAddrs>(self, addrs: A) -> io::Result<Self> {
         // We use std to avoid async
-        let socket = std::net::UdpSocket::bind(addrs)?;
+        let socket = self.socket_options.bind(addrs)?;
         self.with_socket(socket)
     }
 
@@ -173,6 +241,27 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
         self
     }
 
+    /// Write a qlog trace of each connection to a file in `dir`.
+    ///
+    /// The traces can be loaded into a viewer such as qvis to debug interop issues
+    /// with browsers. Disabled by default; see [Settings::qlog_compression] to
+    /// compress them. Set this after [ServerBuilder::with_settings], which replaces it.
+    pub fn with_qlog_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.settings.qlog_dir = Some(dir.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// Wireshark can use the file to decrypt packet captures. Nothing is written if the
+    /// variable isn't set, or unless the `keylog` feature is enabled. Disabled by default,
+    /// since the secrets decrypt every connection. Set this after
+    /// [ServerBuilder::with_settings], which replaces it.
+    pub fn with_keylog(mut self, enabled: bool) -> Self {
+        self.settings.keylog_file = keylog_file(enabled);
+        self
+    }
+
     /// Send a PING to each client on this interval, keeping idle connections alive.
     ///
     /// Disabled by default. A server usually wants to let idle clients time out
@@ -184,6 +273,27 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
         self
     }
 
+    /// Close a connection after this long without hearing from the client.
+    ///
+    /// Each side advertises its own, and the connection uses the smaller of the two.
+    /// Defaults to [Settings::max_idle_timeout]; set this after
+    /// [ServerBuilder::with_settings], which replaces it.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.max_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Queue up to this many outgoing datagrams per connection that haven't been sent yet.
+    ///
+    /// Once it's full, [Connection::send_datagram] drops new datagrams while
+    /// [Connection::send_datagram_wait] waits. Defaults to
+    /// [Settings::dgram_send_max_queue_len]; set this after
+    /// [ServerBuilder::with_settings], which replaces it.
+    pub fn with_datagram_send_buffer(mut self, datagrams: usize) -> Self {
+        self.settings.dgram_send_max_queue_len = datagrams;
+        self
+    }
+
     /// Enable UDP generic segmentation offload (GSO), on by default.
     ///
     /// GSO cuts syscall overhead at high throughput by handing the kernel
@@ -198,6 +308,17 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
         self
     }
 
+    /// Apply these [SocketOptions] to the UDP sockets the server listens on.
+    ///
+    /// Like [ServerBuilder::with_gso], this applies to sockets from
+    /// [ServerBuilder::with_socket] and [ServerBuilder::with_bind], not to a
+    /// [ServerBuilder::with_listener] listener. [SocketOptions::with_ipv6_only] is
+    /// applied while binding, so only to [ServerBuilder::with_bind] calls that come after.
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
     /// Authenticate clients with mTLS.
     ///
     /// Defaults to [ClientAuth::None].
@@ -206,19 +327,40 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
         self
     }
 
+    /// Cap the bytes queued across every connection with a shared [MemoryBudget].
+    ///
+    /// Stream data waiting to be sent or read and datagrams waiting in either
+    /// direction are charged to the budget, each connection through its own
+    /// [MemoryAccount](super::MemoryAccount). Once it's exhausted, writers wait,
+    /// incoming stream data stays in quiche (and so under flow control), and
+    /// datagrams are dropped. Clone the budget to share it between servers, or
+    /// keep a clone to report [MemoryBudget::used] as a metric.
+    pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Measure keep-alives and datagram expiry with `clock` instead of the tokio timer.
+    ///
+    /// Defaults to [TokioClock]. QUIC's own timers, like the idle timeout, still use quiche's.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
     /// Configure the server to use a static certificate for TLS.
     pub fn with_single_cert(
         mut self,
-        chain: Vec<CertificateDer<'static>>,
-        key: PrivateKeyDer<'static>,
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
     ) -> io::Result<Server<M>> {
         self.client_auth.validate()?;
 
         let alpn = std::mem::take(&mut self.alpn);
         let client_auth = std::mem::take(&mut self.client_auth);
         let hook = StaticCertHook {
-            chain,
-            key,
+            chain: chain.into_iter().collect(),
+            key: key.into(),
             alpn,
             client_auth,
         };
@@ -268,11 +410,14 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
             .into_iter()
             .map(|listener| match listener {
                 Listener::Ready(listener) => listener,
-                Listener::Socket(socket) => QuicListener {
-                    capabilities: capabilities(&socket, self.gso),
-                    socket,
-                    cid_generator: Arc::new(SimpleConnectionIdGenerator),
-                },
+                Listener::Socket(socket) => {
+                    self.socket_options.apply(&socket);
+                    QuicListener {
+                        capabilities: capabilities(&socket, self.gso),
+                        socket,
+                        cid_generator: Arc::new(SimpleConnectionIdGenerator),
+                    }
+                }
             })
             .collect();
 
@@ -284,7 +429,13 @@ impl<M: Metrics> ServerBuilder<M, ServerWithListener> {
 
         let params = tokio_quiche::ConnectionParams::new_server(self.settings, dummy_tls, hooks);
         let server = tokio_quiche::listen_with_capabilities(listeners, params, self.metrics)?;
-        Ok(Server::new(server, local_addrs, self.keep_alive))
+        Ok(Server::new(
+            server,
+            local_addrs,
+            self.keep_alive,
+            self.memory_budget,
+            self.clock,
+        ))
     }
 }
 
@@ -315,6 +466,11 @@ impl Incoming {
         self.connection.local_addr()
     }
 
+    /// The [Clock] this connection measures time with.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.connection.clock()
+    }
+
     /// Reject the connection with an error code and reason.
     ///
     /// This is equivalent to [Connection::close].
@@ -338,11 +494,14 @@ impl Incoming {
 
 /// A QUIC server that accepts new connections.
 pub struct Server<M: Metrics = DefaultMetrics> {
-    accept: mpsc::Receiver<Incoming>,
+    accept: tokio::sync::Mutex<mpsc::Receiver<Incoming>>,
     local_addrs: Vec<SocketAddr>,
-    // Cancels socket tasks when dropped.
-    #[allow(dead_code)]
-    tasks: JoinSet<io::Result<()>>,
+    // Cancels socket tasks when dropped or closed.
+    tasks: Mutex<JoinSet<()>>,
+    // Set once the server stops accepting, by close() or a listener error.
+    closed: watch::Sender<bool>,
+    // The first listener error, which also closes the server.
+    error: watch::Sender<Option<Arc<io::Error>>>,
     _metrics: PhantomData<M>,
 }
 
@@ -351,22 +510,45 @@ impl<M: Metrics> Server<M> {
         sockets: Vec<tokio_quiche::QuicConnectionStream<M>>,
         local_addrs: Vec<SocketAddr>,
         keep_alive: Option<Duration>,
+        memory_budget: Option<MemoryBudget>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
         let mut tasks = JoinSet::default();
 
         let accept = mpsc::channel(sockets.len());
+        let closed = watch::Sender::new(false);
+        let error = watch::Sender::new(None);
 
         for socket in sockets {
             let accept = accept.0.clone();
-            // TODO close all when one errors
-            tasks.spawn(Self::run_socket(socket, accept, keep_alive));
+            let memory_budget = memory_budget.clone();
+            let clock = clock.clone();
+            let closed = closed.clone();
+            let error = error.clone();
+
+            tasks.spawn(async move {
+                let res = Self::run_socket(socket, accept, keep_alive, memory_budget, clock).await;
+                if let Err(err) = res {
+                    tracing::warn!(?err, "listener failed, closing the server");
+                    error.send_if_modified(|first| {
+                        let unset = first.is_none();
+                        first.get_or_insert_with(|| Arc::new(err));
+                        unset
+                    });
+                }
+
+                // Close all when one stops, so a dead listener isn't silently ignored.
+                closed.send_replace(true);
+            });
         }
 
         Self {
-            accept: accept.1,
+            accept: tokio::sync::Mutex::new(accept.1),
             local_addrs,
+            tasks: Mutex::new(tasks),
+            closed,
+            error,
             _metrics: PhantomData,
-            tasks,
         }
     }
 
@@ -374,6 +556,8 @@ impl<M: Metrics> Server<M> {
         socket: tokio_quiche::QuicConnectionStream<M>,
         accept: mpsc::Sender<Incoming>,
         keep_alive: Option<Duration>,
+        memory_budget: Option<MemoryBudget>,
+        clock: Arc<dyn Clock>,
     ) -> io::Result<()> {
         let mut rx = socket.into_inner();
         while let Some(initial) = rx.recv().await {
@@ -385,7 +569,8 @@ impl<M: Metrics> Server<M> {
             let dgram_out = flume::bounded(DGRAM_CHANNEL_CAPACITY);
             let dgram_max = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
 
-            let state = Lock::new(DriverState::new(true));
+            let memory = memory_budget.as_ref().map(MemoryBudget::account);
+            let state = Lock::new(DriverState::new(true, memory).with_clock(clock.clone()));
             let session = Driver::new(
                 state.clone(),
                 accept_bi.0,
@@ -411,7 +596,9 @@ impl<M: Metrics> Server<M> {
                 driver: state,
             };
 
-            if accept.send(incoming).await.is_err() {
+            if let Err(err) = accept.send(incoming).await {
+                // The server was closed while this connection was queued.
+                err.0.reject(codes::SERVER_CLOSED, "server closed");
                 return Ok(());
             }
         }
@@ -421,9 +608,67 @@ impl<M: Metrics> Server<M> {
 
     /// Accept a new QUIC [Incoming] from a client.
     ///
-    /// Returns `None` when the server is shutting down.
-    pub async fn accept(&mut self) -> Option<Incoming> {
-        self.accept.recv().await
+    /// Returns `None` once the server is closed, by [Server::close] or a listener error.
+    /// This may be called from multiple tasks at once; each connection goes to one of them.
+    pub async fn accept(&self) -> Option<Incoming> {
+        let mut closed = self.closed.subscribe();
+
+        let mut accept = tokio::select! {
+            biased;
+            _ = closed.wait_for(|closed| *closed) => return None,
+            accept = self.accept.lock() => accept,
+        };
+
+        tokio::select! {
+            biased;
+            _ = closed.wait_for(|closed| *closed) => {
+                Self::reject_queued(&mut accept);
+                None
+            }
+            incoming = accept.recv() => incoming,
+        }
+    }
+
+    /// Stop accepting connections.
+    ///
+    /// The listeners stop, any connection queued for [Server::accept] is rejected,
+    /// and every pending and future [Server::accept] returns `None`. Connections
+    /// already accepted are unaffected; close them individually.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+        self.tasks.lock().unwrap().abort_all();
+
+        // If an accept() holds the queue, it rejects the rest once it wakes.
+        if let Ok(mut accept) = self.accept.try_lock() {
+            Self::reject_queued(&mut accept);
+        }
+    }
+
+    fn reject_queued(accept: &mut mpsc::Receiver<Incoming>) {
+        accept.close();
+        while let Ok(incoming) = accept.try_recv() {
+            incoming.reject(codes::SERVER_CLOSED, "server closed");
+        }
+    }
+
+    /// Returns true once the server has stopped accepting connections.
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// Wait until the server stops accepting connections.
+    pub async fn closed(&self) {
+        // The sender lives as long as self, so this can't fail.
+        let _ = self.closed.subscribe().wait_for(|closed| *closed).await;
+    }
+
+    /// Watch for a listener I/O error.
+    ///
+    /// The value is `None` until a listener fails, then holds the first error.
+    /// A failed listener closes the whole server, as if [Server::close] was called,
+    /// so await [watch::Receiver::wait_for] to learn why [Server::accept] returned `None`.
+    pub fn error(&self) -> watch::Receiver<Option<Arc<io::Error>>> {
+        self.error.subscribe()
     }
 
     /// Returns the local addresses of all listeners.
@@ -0,0 +1,226 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use tokio_quiche::socket::SocketCapabilities;
+
+/// Enable the socket options tokio-quiche knows how to use, optionally leaving
+/// `UDP_SEGMENT` (GSO) off.
+///
+/// All of these are Linux-only; every other platform reports no capabilities.
+#[cfg(target_os = "linux")]
+pub(super) fn capabilities<S: std::os::fd::AsFd>(socket: &S, gso: bool) -> SocketCapabilities {
+    use tokio_quiche::socket::SocketCapabilitiesBuilder;
+
+    if gso {
+        return SocketCapabilities::apply_all_and_get_compatibility(socket);
+    }
+
+    // `apply_all_and_get_compatibility` always turns GSO on, so opting out means
+    // reproducing it here minus the `gso()` call. Each option is independently
+    // best-effort: a kernel that rejects one still gets the rest.
+    let mut builder = SocketCapabilitiesBuilder::new(socket);
+    let _ = builder.check_udp_drop();
+    let _ = builder.txtime();
+    let _ = builder.gro();
+    let _ = builder.rcvmark();
+    let _ = builder.ip_mtu_discover_probe();
+    let _ = builder.ipv6_mtu_discover_probe();
+
+    // Source-address control is only useful when the socket may send from an
+    // address it isn't bound to.
+    if let Ok(true) = builder.allows_nonlocal_source() {
+        let _ = builder.ipv4_pktinfo();
+        let _ = builder.ipv4_recvorigdstaddr();
+        let _ = builder.ipv6_pktinfo();
+        let _ = builder.ipv6_recvorigdstaddr();
+    }
+
+    builder.finish()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn capabilities<S>(_socket: &S, _gso: bool) -> SocketCapabilities {
+    SocketCapabilities::default()
+}
+
+/// Options for the UDP sockets a builder binds.
+///
+/// Every option is best-effort: when the platform doesn't support one, or the kernel
+/// rejects it, a warning is logged and the socket is used without it. The only hard
+/// failure is binding itself.
+#[derive(Clone, Debug, Default)]
+pub struct SocketOptions {
+    tos: Option<u8>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    ipv6_only: Option<bool>,
+}
+
+impl SocketOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark outgoing packets with the given DSCP codepoint (0-63), e.g. 46 for Expedited Forwarding.
+    ///
+    /// Shorthand for [Self::with_tos] with the ECN bits left clear.
+    pub fn with_dscp(self, dscp: u8) -> Self {
+        self.with_tos(dscp << 2)
+    }
+
+    /// Set the IPv4 TOS byte, or the IPv6 traffic class, on outgoing packets.
+    pub fn with_tos(mut self, tos: u8) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// Request a kernel receive buffer of this many bytes (`SO_RCVBUF`).
+    ///
+    /// The kernel may round or cap the value; Linux doubles it and caps it at `net.core.rmem_max`.
+    pub fn with_recv_buffer_size(mut self, bytes: usize) -> Self {
+        self.recv_buffer_size = Some(bytes);
+        self
+    }
+
+    /// Request a kernel send buffer of this many bytes (`SO_SNDBUF`).
+    pub fn with_send_buffer_size(mut self, bytes: usize) -> Self {
+        self.send_buffer_size = Some(bytes);
+        self
+    }
+
+    /// Whether an IPv6 socket refuses IPv4-mapped traffic (`IPV6_V6ONLY`).
+    ///
+    /// Ignored for IPv4 addresses. When unset, the platform default is used, which is
+    /// dual-stack on Linux and macOS but IPv6-only on Windows and the BSDs.
+    ///
+    /// This can only be set before binding, so unlike the other options it has no
+    /// effect on sockets the caller binds.
+    pub fn with_ipv6_only(mut self, ipv6_only: bool) -> Self {
+        self.ipv6_only = Some(ipv6_only);
+        self
+    }
+
+    /// Bind a UDP socket to the first of `addrs` that works, like [std::net::UdpSocket::bind].
+    ///
+    /// Only `IPV6_V6ONLY` is set here; the rest is left to [Self::apply].
+    pub(super) fn bind<A: ToSocketAddrs>(&self, addrs: A) -> io::Result<std::net::UdpSocket> {
+        let mut last_err = None;
+
+        for addr in addrs.to_socket_addrs()? {
+            let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+
+            if let (SocketAddr::V6(_), Some(only)) = (addr, self.ipv6_only) {
+                if let Err(err) = socket.set_only_v6(only) {
+                    tracing::warn!(%err, only, "failed to set IPV6_V6ONLY");
+                }
+            }
+
+            match socket.bind(&addr.into()) {
+                Ok(()) => return Ok(socket.into()),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    /// Apply the options that can change after binding.
+    pub(super) fn apply(&self, socket: &tokio::net::UdpSocket) {
+        let v6 = matches!(socket.local_addr(), Ok(SocketAddr::V6(_)));
+        let socket = SockRef::from(socket);
+
+        if let Some(tos) = self.tos {
+            let res = match v6 {
+                // Dual-stack sockets send IPv4 too, so set both where we can.
+                true => {
+                    let _ = set_tos_v4(&socket, tos);
+                    set_tclass_v6(&socket, tos)
+                }
+                false => set_tos_v4(&socket, tos),
+            };
+            if let Err(err) = res {
+                tracing::warn!(%err, tos, "failed to set TOS, packets will be unmarked");
+            }
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            match socket.set_recv_buffer_size(size) {
+                Ok(()) => tracing::debug!(
+                    requested = size,
+                    actual = ?socket.recv_buffer_size().ok(),
+                    "set receive buffer size"
+                ),
+                Err(err) => tracing::warn!(%err, size, "failed to set receive buffer size"),
+            }
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            match socket.set_send_buffer_size(size) {
+                Ok(()) => tracing::debug!(
+                    requested = size,
+                    actual = ?socket.send_buffer_size().ok(),
+                    "set send buffer size"
+                ),
+                Err(err) => tracing::warn!(%err, size, "failed to set send buffer size"),
+            }
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "fuchsia",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "illumos",
+    target_os = "haiku",
+    target_os = "wasi",
+)))]
+fn set_tos_v4(socket: &Socket, tos: u8) -> io::Result<()> {
+    socket.set_tos_v4(tos.into())
+}
+
+#[cfg(any(
+    target_os = "fuchsia",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "illumos",
+    target_os = "haiku",
+    target_os = "wasi",
+))]
+fn set_tos_v4(_socket: &Socket, _tos: u8) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn set_tclass_v6(socket: &Socket, tos: u8) -> io::Result<()> {
+    socket.set_tclass_v6(tos.into())
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn set_tclass_v6(_socket: &Socket, _tos: u8) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
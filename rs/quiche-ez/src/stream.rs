@@ -0,0 +1,167 @@
+use std::{fmt, sync::atomic::AtomicU64};
+use thiserror::Error;
+use web_transport_trait::ErrorKind;
+
+use super::ConnectionError;
+
+/// An error when reading or writing to a stream.
+#[derive(Clone, Error, Debug)]
+#[non_exhaustive]
+pub enum StreamError {
+    #[error("connection error: {0}")]
+    Connection(#[from] ConnectionError),
+
+    #[error("RESET_STREAM: {0}")]
+    Reset(u64),
+
+    #[error("STOP_SENDING: {0}")]
+    Stop(u64),
+
+    #[error("stream closed")]
+    Closed,
+
+    #[error("stream longer than the limit of {limit} bytes")]
+    TooLong { limit: usize },
+}
+
+impl StreamError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Connection(e) => e.kind(),
+            Self::Reset(_) => ErrorKind::StreamReset,
+            Self::Stop(_) => ErrorKind::StreamStopped,
+            Self::Closed => ErrorKind::LocallyClosed,
+            Self::TooLong { .. } => ErrorKind::TooLarge,
+        }
+    }
+}
+
+/// A QUIC stream identifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StreamId(u64);
+
+impl StreamId {
+    /// The first client-initiated bidirectional stream ID.
+    pub const CLIENT_BI: StreamId = StreamId(0);
+
+    /// The first server-initiated bidirectional stream ID.
+    pub const SERVER_BI: StreamId = StreamId(1);
+
+    /// The first client-initiated unidirectional stream ID.
+    pub const CLIENT_UNI: StreamId = StreamId(2);
+
+    /// The first server-initiated unidirectional stream ID.
+    pub const SERVER_UNI: StreamId = StreamId(3);
+
+    /// Returns true if this is a unidirectional stream.
+    pub fn is_uni(&self) -> bool {
+        // 2, 3, 6, 7, etc
+        self.0 & 0b10 == 0b10
+    }
+
+    /// Returns true if this is a bidirectional stream.
+    pub fn is_bi(&self) -> bool {
+        !self.is_uni()
+    }
+
+    /// Returns true if this stream was initiated by the server.
+    pub fn is_server(&self) -> bool {
+        // 1, 3, 5, 7, etc
+        self.0 & 0b01 == 0b01
+    }
+
+    /// Returns true if this stream was initiated by the client.
+    pub fn is_client(&self) -> bool {
+        !self.is_server()
+    }
+
+    /// Returns true if this stream was opened by the local endpoint, given whether it's a server.
+    pub fn is_local(&self, server: bool) -> bool {
+        self.is_server() == server
+    }
+
+    /// The position of this stream among those of the same type and initiator, from 0.
+    pub fn index(&self) -> u64 {
+        self.0 >> 2
+    }
+
+    /// Increment to the next stream ID and return the current one.
+    pub fn increment(&mut self) -> StreamId {
+        let id = *self;
+        self.0 += 4;
+        id
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<StreamId> for AtomicU64 {
+    fn from(id: StreamId) -> Self {
+        AtomicU64::new(id.0)
+    }
+}
+
+impl From<StreamId> for u64 {
+    fn from(id: StreamId) -> Self {
+        id.0
+    }
+}
+
+impl From<u64> for StreamId {
+    fn from(id: u64) -> Self {
+        StreamId(id)
+    }
+}
+
+/// A snapshot of a stream the driver is still tracking, from [Connection::open_streams](crate::Connection::open_streams).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// The stream's ID.
+    pub id: StreamId,
+
+    /// The sending half, unless the stream is receive-only or the driver is done with it.
+    pub send: Option<SendInfo>,
+
+    /// The receiving half, unless the stream is send-only or the driver is done with it.
+    pub recv: Option<RecvInfo>,
+}
+
+/// The state of a stream's sending half. See [StreamInfo].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SendInfo {
+    /// Bytes written by the application that quiche hasn't accepted yet.
+    pub queued: usize,
+
+    /// The application finished the stream.
+    pub fin: bool,
+
+    /// The code we reset the stream with.
+    pub reset: Option<u64>,
+
+    /// The code the peer sent STOP_SENDING with.
+    pub stopped: Option<u64>,
+
+    /// Everything including the FIN was sent, but the peer hasn't acknowledged it all yet.
+    pub unacked: bool,
+}
+
+/// The state of a stream's receiving half. See [StreamInfo].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecvInfo {
+    /// Bytes received that the application hasn't read yet.
+    pub queued: usize,
+
+    /// The peer finished the stream.
+    pub fin: bool,
+
+    /// The code the peer reset the stream with.
+    pub reset: Option<u64>,
+
+    /// The code we sent STOP_SENDING with.
+    pub stopped: Option<u64>,
+}
@@ -1,4 +1,5 @@
 use std::io;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use boring::ec::EcKey;
@@ -278,6 +279,7 @@ impl ConnectionHook for DynamicCertHook {
 }
 
 /// How a client verifies the server's certificate.
+#[derive(Clone)]
 pub(crate) enum ClientVerify {
     /// Standard verification against the SSL context's default trust store.
     /// The driver layers `verify_peer` from [super::Settings] on top.
@@ -296,6 +298,10 @@ pub(crate) enum ClientVerify {
 /// Build the client SSL context: optionally present a client certificate
 /// (mTLS) and install the requested server-verification policy.
 ///
+/// When the server is addressed by `ip`, the certificate must carry a matching
+/// IP address SAN. quiche only checks DNS names (and IP literals aren't allowed
+/// in SNI), so the address is pinned on the trust store's verify parameters.
+///
 /// Fallible up front (at connect time) so a malformed certificate, key, or
 /// root surfaces as a connection error. The [ConnectionHook] returns
 /// `Option<SslContextBuilder>` and `None` silently falls back to tokio-quiche's
@@ -303,6 +309,7 @@ pub(crate) enum ClientVerify {
 pub(crate) fn build_client_context(
     cert: Option<&(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
     verify: &ClientVerify,
+    ip: Option<IpAddr>,
 ) -> io::Result<SslContextBuilder> {
     let mut builder = SslContextBuilder::new(SslMethod::tls()).map_err(io::Error::other)?;
 
@@ -311,13 +318,32 @@ pub(crate) fn build_client_context(
     }
 
     match verify {
-        ClientVerify::Default => {}
+        ClientVerify::Default => {
+            // A custom context doesn't inherit quiche's trust store, so load the
+            // same system defaults it would have.
+            builder
+                .set_default_verify_paths()
+                .map_err(io::Error::other)?;
+            if let Some(ip) = ip {
+                builder
+                    .cert_store_mut()
+                    .verify_param_mut()
+                    .set_ip(ip)
+                    .map_err(io::Error::other)?;
+            }
+        }
         ClientVerify::Roots(roots) => {
             let mut store = X509StoreBuilder::new().map_err(io::Error::other)?;
             for der in roots {
                 let cert = X509::from_der(der.as_ref()).map_err(io::Error::other)?;
                 store.add_cert(cert).map_err(io::Error::other)?;
             }
+            if let Some(ip) = ip {
+                store
+                    .verify_param_mut()
+                    .set_ip(ip)
+                    .map_err(io::Error::other)?;
+            }
             builder.set_cert_store_builder(store);
             builder.set_verify(SslVerifyMode::PEER);
         }
@@ -405,3 +431,11 @@ impl ConnectionHook for ClientHook {
         builder
     }
 }
+
+/// The [Settings::keylog_file](crate::Settings::keylog_file) for a `with_keylog` builder call.
+pub(crate) fn keylog_file(enabled: bool) -> Option<String> {
+    match enabled {
+        true => std::env::var("SSLKEYLOGFILE").ok(),
+        false => None,
+    }
+}
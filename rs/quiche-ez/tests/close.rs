@@ -0,0 +1,46 @@
+//! Closing a server resolves pending and future accepts with None.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use quiche_ez::ServerBuilder;
+use rcgen::{CertifiedKey, KeyPair};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+#[tokio::test]
+async fn close_resolves_accept() -> Result<()> {
+    let CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+    let cert = CertificateDer::from(cert.der().to_vec());
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(KeyPair::serialize_der(
+        &signing_key,
+    )));
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let server = Arc::new(
+        ServerBuilder::default()
+            .with_bind(bind)?
+            .with_single_cert(vec![cert], key)?,
+    );
+    assert!(!server.is_closed());
+
+    // Two callers waiting at once: one holds the queue, the other waits for it.
+    let pending = [server.clone(), server.clone()]
+        .map(|server| tokio::spawn(async move { server.accept().await.is_none() }));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    server.close();
+    for pending in pending {
+        assert!(tokio::time::timeout(Duration::from_secs(1), pending).await??);
+    }
+
+    assert!(server.is_closed());
+    assert!(server.accept().await.is_none());
+    assert!(server.error().borrow().is_none(), "closing isn't an error");
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! A raw QUIC echo over loopback, with no HTTP/3 involved.
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::{Context, Result};
+use quiche_ez::{ClientBuilder, ServerBuilder, Settings};
+use rcgen::{CertifiedKey, KeyPair};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+const ALPN: &[u8] = b"echo";
+
+fn settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.alpn = vec![ALPN.to_vec()];
+    settings
+}
+
+#[tokio::test]
+async fn echo_bi() -> Result<()> {
+    let CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+    let cert = CertificateDer::from(cert.der().to_vec());
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(KeyPair::serialize_der(
+        &signing_key,
+    )));
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_settings(settings())
+        .with_single_cert(vec![cert.clone()], key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+
+    let server = tokio::spawn(async move {
+        let conn = server
+            .accept()
+            .await
+            .context("no connection")?
+            .accept()
+            .await?;
+        let (mut send, mut recv) = conn.accept_bi().await?;
+        let data = recv.read_all(1024).await?;
+        send.write_all(&data).await?;
+        send.finish()?;
+        send.closed().await?;
+        anyhow::Ok(())
+    });
+
+    let conn = ClientBuilder::new()
+        .with_settings(settings())
+        .with_root_certificates(vec![cert])
+        .connect("localhost", addr.port())
+        .await?
+        .established()
+        .await?;
+    assert_eq!(conn.alpn().as_deref(), Some(ALPN));
+
+    let (mut send, mut recv) = conn.open_bi().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+    assert_eq!(&recv.read_all(1024).await?[..], b"hello");
+
+    server.await??;
+    Ok(())
+}
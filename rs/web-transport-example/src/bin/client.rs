@@ -0,0 +1,100 @@
+//! The client: the same application code, run natively over Quinn or in the browser.
+//!
+//! Natively, pass the server URL and (for the self-signed `dev/setup` certificate) its hash:
+//! `cargo run --bin client -- --fingerprint $(cat dev/localhost.hex)`.
+//!
+//! In the browser, build with `trunk serve` and open the page with the same values in the query
+//! string: `http://localhost:8080/?url=https://localhost:4443&fingerprint=<hex>`.
+
+use url::Url;
+use web_transport::{ClientBuilder, Session};
+use web_transport_example::{decode_hex, PROTOCOL};
+
+// WASM errors hold a `JsValue`, so this can't require `Send`.
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Connect to `url`, trusting only `fingerprint` (a hex sha256 certificate hash) when given.
+async fn connect(url: Url, fingerprint: Option<&str>) -> Result<Session> {
+    let client = ClientBuilder::new().with_protocols([PROTOCOL]);
+    let client = match fingerprint {
+        Some(hex) => {
+            let hash = decode_hex(hex).ok_or("invalid certificate fingerprint")?;
+            client.with_server_certificate_hashes(vec![hash])?
+        }
+        None => client.with_system_roots()?,
+    };
+
+    Ok(client.connect(url).await?)
+}
+
+/// Connect and run the shared application, returning a line to show the user.
+async fn run(url: Url, fingerprint: Option<&str>) -> Result<String> {
+    let session = connect(url, fingerprint).await?;
+    if session.protocol() != Some(PROTOCOL) {
+        return Err(format!("server negotiated {:?}", session.protocol()).into());
+    }
+
+    web_transport_example::run_client(&session).await?;
+    Ok("stream and datagram echoed, session closed".to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[tokio::main]
+async fn main() -> Result<()> {
+    use clap::Parser;
+
+    #[derive(Parser, Debug)]
+    #[command(author, version, about, long_about = None)]
+    struct Args {
+        #[arg(short, long, default_value = "https://localhost:4443")]
+        url: Url,
+
+        /// Accept only the certificate with this hex-encoded sha256 hash.
+        #[arg(long)]
+        fingerprint: Option<String>,
+    }
+
+    let args = Args::parse();
+    let status = run(args.url, args.fingerprint.as_deref()).await?;
+    println!("{status}");
+
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    wasm_bindgen_futures::spawn_local(async {
+        let status = match browser().await {
+            Ok(status) => status,
+            Err(err) => format!("error: {err}"),
+        };
+
+        web_sys::console::log_1(&status.as_str().into());
+        if let Some(log) = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id("log"))
+        {
+            log.set_text_content(Some(&status));
+        }
+    });
+}
+
+/// Read the server URL and fingerprint from the page's query string, then run.
+#[cfg(target_arch = "wasm32")]
+async fn browser() -> Result<String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let href = window.location().href().map_err(|_| "no location")?;
+    let page = Url::parse(&href)?;
+
+    let mut url = Url::parse("https://localhost:4443")?;
+    let mut fingerprint = None;
+    for (key, value) in page.query_pairs() {
+        match key.as_ref() {
+            "url" => url = Url::parse(&value)?,
+            "fingerprint" => fingerprint = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+
+    run(url, fingerprint.as_deref()).await
+}
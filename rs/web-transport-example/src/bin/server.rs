@@ -0,0 +1,104 @@
+//! The native server: accepts sessions with Quinn and hands them to the shared application.
+
+// Browsers can't accept WebTransport sessions, so there is no WASM server.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    native::main().await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::path;
+
+    use anyhow::Context;
+    use clap::Parser;
+    use web_transport::quinn::{self, proto::ConnectResponse};
+    use web_transport::Session;
+    use web_transport_example::{Error, CLOSE_TOO_LARGE, PROTOCOL};
+
+    #[derive(Parser, Debug)]
+    #[command(author, version, about, long_about = None)]
+    struct Args {
+        #[arg(short, long, default_value = "[::]:4443")]
+        addr: std::net::SocketAddr,
+
+        /// Use the certificates at this path, encoded as PEM.
+        #[arg(long)]
+        tls_cert: path::PathBuf,
+
+        /// Use the private key at this path, encoded as PEM.
+        #[arg(long)]
+        tls_key: path::PathBuf,
+    }
+
+    pub async fn main() -> anyhow::Result<()> {
+        // Enable info logging.
+        tracing_subscriber::fmt()
+            .with_env_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+            )
+            .init();
+
+        let args = Args::parse();
+
+        let (chain, key) = quinn::tls::load_pem(&args.tls_cert, &args.tls_key)
+            .context("failed to load certificate")?;
+
+        let mut server = quinn::ServerBuilder::new()
+            .with_addr(args.addr)
+            .with_certificate(chain, key)?;
+
+        tracing::info!(addr = %args.addr, "listening");
+
+        while let Some(request) = server.accept().await {
+            tokio::spawn(async move {
+                if let Err(err) = run_request(request).await {
+                    tracing::warn!(?err, "session failed");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn run_request(request: quinn::Request) -> anyhow::Result<()> {
+        tracing::info!(url = %request.url, "received WebTransport request");
+
+        let mut response = ConnectResponse::OK;
+        if request.protocols.iter().any(|p| p == PROTOCOL) {
+            response = response.with_protocol(PROTOCOL);
+        }
+
+        let session: Session = request.respond(response).await?.into();
+        tracing::info!(protocol = ?session.protocol(), "accepted session");
+
+        loop {
+            tokio::select! {
+                res = session.accept_bi() => {
+                    let (send, recv) = res?;
+                    let session = session.clone();
+                    tokio::spawn(async move {
+                        match web_transport_example::serve_stream(send, recv).await {
+                            Ok(()) => {}
+                            Err(Error::TooLarge) => session.close(CLOSE_TOO_LARGE, "request too large"),
+                            Err(err) => tracing::warn!(?err, "stream failed"),
+                        }
+                    });
+                }
+                res = session.recv_datagram() => {
+                    web_transport_example::serve_datagram(&session, res?).await?;
+                }
+                err = session.closed() => {
+                    // The client hangs up with CLOSE_DONE once it's finished.
+                    tracing::info!(%err, "session closed");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
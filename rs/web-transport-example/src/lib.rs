@@ -0,0 +1,112 @@
+//! The application shared by the example `client` and `server` binaries.
+//!
+//! Everything here is written against [web_transport], which picks web-transport-quinn on native
+//! targets and web-transport-wasm in the browser. The same code therefore compiles for both:
+//! the server runs it over Quinn, and the client runs it over Quinn or the browser's WebTransport.
+//!
+//! The protocol is deliberately small:
+//! - each bidirectional stream carries one request, echoed back in upper case;
+//! - each datagram is echoed back unchanged;
+//! - the client hangs up with [CLOSE_DONE], and the server hangs up with [CLOSE_TOO_LARGE]
+//!   when a request exceeds [MAX_REQUEST].
+
+use bytes::{Buf, Bytes, BytesMut};
+use web_transport::{RecvStream, SendStream, Session};
+
+/// The WebTransport subprotocol both sides negotiate.
+pub const PROTOCOL: &str = "web-transport-example";
+
+/// The largest request the server will buffer.
+pub const MAX_REQUEST: usize = 4096;
+
+/// Close code: the client finished and is hanging up.
+pub const CLOSE_DONE: u32 = 0;
+
+/// Close code: the peer sent a request larger than [MAX_REQUEST].
+pub const CLOSE_TOO_LARGE: u32 = 1;
+
+/// An error from the example protocol.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("transport error: {0}")]
+    Transport(#[from] web_transport::Error),
+
+    #[error("request too large")]
+    TooLarge,
+
+    #[error("unexpected response: {0:?}")]
+    Unexpected(Bytes),
+}
+
+/// Read the rest of the stream, up to `max` bytes.
+pub async fn read_to_end(recv: &mut RecvStream, max: usize) -> Result<Bytes, Error> {
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = recv.read(max + 1 - buf.len()).await? {
+        buf.extend_from_slice(&chunk);
+        if buf.len() > max {
+            return Err(Error::TooLarge);
+        }
+    }
+
+    Ok(buf.freeze())
+}
+
+/// Write the whole buffer to the stream.
+pub async fn write_all(send: &mut SendStream, mut buf: Bytes) -> Result<(), Error> {
+    while buf.has_remaining() {
+        send.write_buf(&mut buf).await?;
+    }
+
+    Ok(())
+}
+
+/// Answer one request on a bidirectional stream.
+pub async fn serve_stream(mut send: SendStream, mut recv: RecvStream) -> Result<(), Error> {
+    let request = read_to_end(&mut recv, MAX_REQUEST).await?;
+    write_all(&mut send, Bytes::from(request.to_ascii_uppercase())).await?;
+    send.finish()?;
+
+    Ok(())
+}
+
+/// Answer one datagram.
+pub async fn serve_datagram(session: &Session, datagram: Bytes) -> Result<(), Error> {
+    session.send_datagram(datagram).await?;
+    Ok(())
+}
+
+/// Run the client side: a request over a stream, a datagram round trip, then hang up.
+pub async fn run_client(session: &Session) -> Result<(), Error> {
+    let (mut send, mut recv) = session.open_bi().await?;
+    write_all(&mut send, Bytes::from_static(b"hello")).await?;
+    send.finish()?;
+
+    let response = read_to_end(&mut recv, MAX_REQUEST).await?;
+    if response.as_ref() != b"HELLO" {
+        return Err(Error::Unexpected(response));
+    }
+
+    // Datagrams are unreliable, but over a quiet session a single round trip is
+    // good enough for a demo.
+    session.send_datagram(Bytes::from_static(b"ping")).await?;
+    let datagram = session.recv_datagram().await?;
+    if datagram.as_ref() != b"ping" {
+        return Err(Error::Unexpected(datagram));
+    }
+
+    session.close(CLOSE_DONE, "done");
+    Ok(())
+}
+
+/// Decode a hex-encoded certificate hash, as written by `dev/setup`.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -135,12 +135,20 @@ pub fn map_session_error(err: web_transport_quinn::SessionError) -> WebTransport
                 WebTransportError::SessionClosedByPeer {
                     closed_by: "session".into(),
                     code: Some(*code as u64),
-                    reason: reason.clone(),
+                    reason: String::from_utf8_lossy(reason).into_owned(),
                 }
             }
             _ => WebTransportError::protocol(wte.to_string()),
         },
         web_transport_quinn::SessionError::SendDatagramError(sde) => map_send_datagram_error(sde),
+        web_transport_quinn::SessionError::GoAway => WebTransportError::SessionClosedByPeer {
+            closed_by: "goaway".into(),
+            code: None,
+            reason: String::new(),
+        },
+        web_transport_quinn::SessionError::WithHistory(err, _)
+        | web_transport_quinn::SessionError::InSession(err, _) => map_session_error(*err),
+        err => WebTransportError::protocol(err.to_string()),
     }
 }
 
@@ -155,6 +163,7 @@ pub fn map_write_error(err: web_transport_quinn::WriteError) -> WebTransportErro
         }
         web_transport_quinn::WriteError::SessionError(se) => map_session_error(se),
         web_transport_quinn::WriteError::ClosedStream => WebTransportError::StreamClosedLocally,
+        err => WebTransportError::protocol(err.to_string()),
     }
 }
 
@@ -172,18 +181,19 @@ pub fn map_read_error(err: web_transport_quinn::ReadError) -> WebTransportError
         web_transport_quinn::ReadError::IllegalOrderedRead => {
             WebTransportError::protocol("illegal ordered read on unordered stream")
         }
+        err => WebTransportError::protocol(err.to_string()),
     }
 }
 
-pub fn map_read_to_end_error(
-    err: web_transport_quinn::ReadToEndError,
-    limit: usize,
-) -> WebTransportError {
+pub fn map_read_to_end_error(err: web_transport_quinn::ReadToEndError) -> WebTransportError {
     match err {
-        web_transport_quinn::ReadToEndError::TooLong => WebTransportError::StreamTooLong {
-            limit: limit as u64,
-        },
+        web_transport_quinn::ReadToEndError::TooLong { limit } => {
+            WebTransportError::StreamTooLong {
+                limit: limit as u64,
+            }
+        }
         web_transport_quinn::ReadToEndError::ReadError(re) => map_read_error(re),
+        err => WebTransportError::protocol(err.to_string()),
     }
 }
 
@@ -201,6 +211,7 @@ pub fn map_read_exact_error(
             }
         }
         web_transport_quinn::ReadExactError::ReadError(re) => map_read_error(re),
+        err => WebTransportError::protocol(err.to_string()),
     }
 }
 
@@ -220,9 +231,13 @@ pub fn map_send_datagram_error(err: quinn::SendDatagramError) -> WebTransportErr
 pub fn map_client_error(err: web_transport_quinn::ClientError) -> WebTransportError {
     match &err {
         web_transport_quinn::ClientError::HttpError(
-            web_transport_quinn::ConnectError::ProtoError(
-                web_transport_quinn::proto::ConnectError::WrongStatus(Some(status)),
-            ),
+            web_transport_quinn::ConnectError::Rejected { status, .. },
+        ) => WebTransportError::SessionRejected {
+            status_code: status.as_u16(),
+            detail: err.to_string(),
+        },
+        web_transport_quinn::ClientError::HttpError(
+            web_transport_quinn::ConnectError::Redirect { status, .. },
         ) => WebTransportError::SessionRejected {
             status_code: status.as_u16(),
             detail: err.to_string(),
@@ -234,15 +249,7 @@ pub fn map_client_error(err: web_transport_quinn::ClientError) -> WebTransportEr
 pub fn map_server_error(err: web_transport_quinn::ServerError) -> WebTransportError {
     match err {
         web_transport_quinn::ServerError::Connection(ce) => map_connection_error(ce),
-        web_transport_quinn::ServerError::UnexpectedEnd
-        | web_transport_quinn::ServerError::WriteError(_)
-        | web_transport_quinn::ServerError::ReadError(_)
-        | web_transport_quinn::ServerError::SettingsError(_)
-        | web_transport_quinn::ServerError::ConnectError(_)
-        | web_transport_quinn::ServerError::IoError(_)
-        | web_transport_quinn::ServerError::Rustls(_) => {
-            WebTransportError::protocol(err.to_string())
-        }
+        err => WebTransportError::protocol(err.to_string()),
     }
 }
 
@@ -102,7 +102,7 @@ impl RecvStream {
             let data = guard
                 .read_to_end(limit)
                 .await
-                .map_err(|e| map_read_to_end_error(e, limit))?;
+                .map_err(map_read_to_end_error)?;
             eof.store(true, Ordering::Release);
             Ok(data)
         })
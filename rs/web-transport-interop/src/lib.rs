@@ -0,0 +1,10 @@
+//! Integration tests between the native backends, run over loopback UDP.
+//!
+//! Each backend is tested against itself in its own crate, which can't catch a disagreement
+//! about the wire format they share: SETTINGS, stream headers, error codes, capsules. The tests
+//! here pair a [web-transport-quinn] client with a [web-transport-quiche] server and vice versa.
+//!
+//! This crate has no API; see `tests/`.
+//!
+//! [web-transport-quinn]: https://docs.rs/web-transport-quinn
+//! [web-transport-quiche]: https://docs.rs/web-transport-quiche
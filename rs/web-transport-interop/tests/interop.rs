@@ -0,0 +1,214 @@
+//! A quinn client against a quiche server, and a quiche client against a quinn server.
+//!
+//! The checks are generic over [Session], so each runs unchanged in both directions, with
+//! either backend opening, resetting or closing.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use rcgen::{CertifiedKey, KeyPair};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_trait::{Error as _, RecvStream as _, SendStream as _, Session};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// The client offers both, and the server selects the last one offered.
+const PROTOCOLS: [&str; 2] = ["interop-v1", "interop-v2"];
+
+fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
+            .context("rcgen self-signed")?;
+
+    let cert_der = CertificateDer::from(cert.der().to_vec());
+    let key_bytes = KeyPair::serialize_der(&signing_key);
+    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
+
+    Ok((vec![cert_der], key_der))
+}
+
+/// A quinn client connected to a quiche server.
+async fn quinn_to_quiche() -> Result<(
+    web_transport_quinn::Session,
+    web_transport_quiche::Connection,
+)> {
+    use web_transport_quinn::proto::{ConnectRequest, ConnectResponse};
+
+    let (chain, key) = make_self_signed()?;
+    let mut server = web_transport_quiche::ServerBuilder::default()
+        .with_bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let protocol = request.protocols.last().cloned().context("no protocols")?;
+        let response = ConnectResponse::OK.with_protocol(protocol);
+        anyhow::Ok(request.respond(response).await?)
+    });
+
+    let request = ConnectRequest::new(url).with_protocols(PROTOCOLS.map(String::from));
+    let client = web_transport_quinn::ClientBuilder::new()
+        .dangerous()
+        .with_no_certificate_verification()?
+        .connect(request)
+        .await?;
+    let server = tokio::time::timeout(TIMEOUT, accepted).await???;
+
+    Ok((client, server))
+}
+
+/// A quiche client connected to a quinn server.
+async fn quiche_to_quinn() -> Result<(
+    web_transport_quiche::Connection,
+    web_transport_quinn::Session,
+)> {
+    use web_transport_quiche::proto::{ConnectRequest, ConnectResponse};
+
+    let (chain, key) = make_self_signed()?;
+    let mut server = web_transport_quinn::ServerBuilder::new()
+        .with_addr(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+        .with_certificate(chain, key)?;
+    let url = Url::parse(&format!(
+        "https://127.0.0.1:{}/",
+        server.local_addr()?.port()
+    ))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let protocol = request.protocols.last().cloned().context("no protocols")?;
+        let response = ConnectResponse::OK.with_protocol(protocol);
+        anyhow::Ok(request.respond(response).await?)
+    });
+
+    let mut settings = web_transport_quiche::Settings::default();
+    settings.verify_peer = false;
+
+    let request = ConnectRequest::new(url).with_protocols(PROTOCOLS.map(String::from));
+    let client = web_transport_quiche::ClientBuilder::default()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect(request)
+        .await?
+        .established()
+        .await?;
+    let server = tokio::time::timeout(TIMEOUT, accepted).await???;
+
+    Ok((client, server))
+}
+
+/// Everything short of closing the session, run from both ends.
+async fn exchange<C: Session, S: Session>(client: &C, server: &S) -> Result<()> {
+    assert_eq!(client.protocol(), Some(PROTOCOLS[1]));
+    assert_eq!(server.protocol(), Some(PROTOCOLS[1]));
+
+    streams(client, server).await.context("client streams")?;
+    streams(server, client).await.context("server streams")?;
+    resets(client, server).await.context("client resets")?;
+    resets(server, client).await.context("server resets")?;
+    datagrams(client, server)
+        .await
+        .context("client datagrams")?;
+    datagrams(server, client)
+        .await
+        .context("server datagrams")?;
+
+    Ok(())
+}
+
+/// `a` opens a bidirectional stream and `b` answers on it, then `a` opens a unidirectional one.
+async fn streams<A: Session, B: Session>(a: &A, b: &B) -> Result<()> {
+    let (mut send, mut recv) = a.open_bi().await?;
+    send.write_all(b"ping").await?;
+    send.finish()?;
+
+    let (mut reply, mut request) = b.accept_bi().await?;
+    assert_eq!(request.read_all().await?, Bytes::from_static(b"ping"));
+    reply.write_all(b"pong").await?;
+    reply.finish()?;
+    assert_eq!(recv.read_all().await?, Bytes::from_static(b"pong"));
+
+    let mut send = a.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = b.accept_uni().await?;
+    assert_eq!(recv.read_all().await?, Bytes::from_static(b"hello"));
+
+    Ok(())
+}
+
+/// `a` opens a stream that `b` resets and stops, and `a` sees both codes.
+async fn resets<A: Session, B: Session>(a: &A, b: &B) -> Result<()> {
+    let (mut send, mut recv) = a.open_bi().await?;
+    send.write_all(b"ping").await?;
+
+    let (mut reply, mut request) = b.accept_bi().await?;
+    assert_eq!(request.read_to_end(4).await?, Bytes::from_static(b"ping"));
+    reply.reset(42);
+    request.stop(7);
+
+    let err = recv.read_all().await.err().context("read past a reset")?;
+    assert_eq!(err.stream_error(), Some(42));
+
+    let err = send.closed().await.err().context("stop went unnoticed")?;
+    assert_eq!(err.stream_error(), Some(7));
+
+    Ok(())
+}
+
+/// A datagram from `a` to `b`, and one back.
+async fn datagrams<A: Session, B: Session>(a: &A, b: &B) -> Result<()> {
+    assert!(a.datagrams_supported());
+    assert!(b.datagrams_supported());
+
+    a.send_datagram(Bytes::from_static(b"ping"))?;
+    assert_eq!(b.recv_datagram().await?, Bytes::from_static(b"ping"));
+
+    b.send_datagram(Bytes::from_static(b"pong"))?;
+    assert_eq!(a.recv_datagram().await?, Bytes::from_static(b"pong"));
+
+    Ok(())
+}
+
+/// `a` closes the session, and `b` sees its code and reason.
+async fn close<A: Session, B: Session>(a: &A, b: &B) -> Result<()> {
+    a.close(9, "done");
+
+    let err = tokio::time::timeout(TIMEOUT, b.closed()).await?;
+    assert_eq!(err.session_error(), Some((9, "done".to_string())));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quinn_client_quiche_server() -> Result<()> {
+    let (client, server) = quinn_to_quiche().await?;
+    tokio::time::timeout(TIMEOUT, exchange(&client, &server)).await??;
+    close(&client, &server).await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quiche_client_quinn_server() -> Result<()> {
+    let (client, server) = quiche_to_quinn().await?;
+    tokio::time::timeout(TIMEOUT, exchange(&client, &server)).await??;
+    close(&client, &server).await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quiche_server_closes_quinn_client() -> Result<()> {
+    let (client, server) = quinn_to_quiche().await?;
+    close(&server, &client).await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn quinn_server_closes_quiche_client() -> Result<()> {
+    let (client, server) = quiche_to_quinn().await?;
+    close(&server, &client).await
+}
@@ -1,12 +1,17 @@
 use std::ops::Deref;
 
+use bytes::Bytes;
 use iroh::endpoint::{self, Connection, RecvStream, SendStream};
 use n0_error::stack_error;
 use web_transport_proto::{ConnectRequest, ConnectResponse, VarInt};
+use web_transport_trait::ErrorKind;
+
+use crate::error::{connection_kind, endpoint_read_kind, endpoint_write_kind};
 
 /// An error during the HTTP/3 CONNECT handshake.
 #[derive(Clone)]
 #[stack_error(derive, from_sources)]
+#[non_exhaustive]
 pub enum ConnectError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -30,6 +35,23 @@ pub enum ConnectError {
     ProtocolMismatch(String),
 }
 
+impl ConnectError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::ProtoError(web_transport_proto::ConnectError::WrongStatus(_)) => {
+                ErrorKind::Rejected
+            }
+            Self::ProtoError(_) | Self::ProtocolMismatch(_) => ErrorKind::Protocol,
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::ReadError(e) => endpoint_read_kind(e),
+            Self::WriteError(e) => endpoint_write_kind(e),
+            Self::ErrorStatus(_) => ErrorKind::Rejected,
+        }
+    }
+}
+
 /// An in-progress HTTP/3 CONNECT handshake, awaiting a response.
 #[derive(Debug)]
 pub struct Connecting {
@@ -165,7 +187,7 @@ impl Connected {
     }
 
     // Keep reading from the control stream until it's closed.
-    pub(crate) async fn run_closed(&mut self) -> (u32, String) {
+    pub(crate) async fn run_closed(&mut self) -> (u32, Bytes) {
         loop {
             match web_transport_proto::Capsule::read(&mut self.recv).await {
                 Ok(Some(web_transport_proto::Capsule::CloseWebTransportSession {
@@ -174,15 +196,30 @@ impl Connected {
                 })) => {
                     return (code, reason);
                 }
+                Ok(Some(web_transport_proto::Capsule::DrainWebTransportSession)) => {
+                    tracing::debug!("peer is draining the session");
+                }
+                // Session-level flow control isn't enforced by this backend.
+                Ok(Some(
+                    capsule @ (web_transport_proto::Capsule::WtMaxData { .. }
+                    | web_transport_proto::Capsule::WtMaxStreams { .. }
+                    | web_transport_proto::Capsule::WtDataBlocked { .. }
+                    | web_transport_proto::Capsule::WtStreamsBlocked { .. }),
+                )) => {
+                    tracing::debug!(?capsule, "ignoring flow control capsule");
+                }
+                Ok(Some(web_transport_proto::Capsule::ReconnectHint { url })) => {
+                    tracing::debug!(%url, "ignoring reconnect hint");
+                }
                 Ok(Some(web_transport_proto::Capsule::Grease { .. })) => {}
                 Ok(Some(web_transport_proto::Capsule::Unknown { typ, payload })) => {
                     tracing::warn!(%typ, size = payload.len(), "unknown capsule");
                 }
                 Ok(None) => {
-                    return (0, "stream closed".to_string());
+                    return (0, Bytes::from_static(b"stream closed"));
                 }
                 Err(_) => {
-                    return (1, "capsule error".to_string());
+                    return (1, Bytes::from_static(b"capsule error"));
                 }
             }
         }
@@ -1,13 +1,16 @@
 use std::sync::Arc;
 
+use bytes::Bytes;
 use iroh::endpoint;
 use n0_error::stack_error;
+use web_transport_trait::{ErrorKind, SessionId};
 
 use crate::{ConnectError, SettingsError};
 
 /// An error returned when connecting to a WebTransport endpoint.
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum ClientError {
     #[error("unexpected end of stream")]
     UnexpectedEnd,
@@ -37,9 +40,27 @@ pub enum ClientError {
     Bind(#[error(source)] Arc<endpoint::BindError>),
 }
 
+impl ClientError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Connect(_) => ErrorKind::Other,
+            Self::Connection(e) => connection_kind(e),
+            Self::WriteError(e) => endpoint_write_kind(e),
+            Self::ReadError(e) => endpoint_read_kind(e),
+            Self::SettingsError(e) => e.kind(),
+            Self::HttpError(e) => e.kind(),
+            Self::InvalidUrl => ErrorKind::InvalidInput,
+            Self::Bind(_) => ErrorKind::Io,
+        }
+    }
+}
+
 /// An error returned by [`crate::Session`], split between underlying QUIC errors and WebTransport errors.
-#[stack_error(derive, from_sources)]
+#[stack_error(derive)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum SessionError {
     #[error("connection error")]
     ConnectionError(#[error(source, from, std_err)] endpoint::ConnectionError),
@@ -49,14 +70,52 @@ pub enum SessionError {
 
     #[error("send datagram error")]
     SendDatagramError(#[error(source, from, std_err)] endpoint::SendDatagramError),
+
+    /// The close error of a session, tagged with its [id](crate::Session::id).
+    ///
+    /// Displays as `session <id>`, matching the `session` field of its tracing events, with
+    /// the error it wraps as the source. Use [SessionError::without_id] to match on that error.
+    #[error("session {id}")]
+    InSession {
+        #[error(source, std_err)]
+        source: Box<SessionError>,
+        id: SessionId,
+    },
+}
+
+impl SessionError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::WebTransportError(e) => e.kind(),
+            Self::SendDatagramError(e) => match e {
+                endpoint::SendDatagramError::UnsupportedByPeer
+                | endpoint::SendDatagramError::Disabled => ErrorKind::Unsupported,
+                endpoint::SendDatagramError::TooLarge => ErrorKind::TooLarge,
+                endpoint::SendDatagramError::ConnectionLost(e) => connection_kind(e),
+            },
+            Self::InSession { source, .. } => source.kind(),
+        }
+    }
+
+    /// The error without the [session id](SessionError::InSession) attached.
+    pub fn without_id(&self) -> &SessionError {
+        match self {
+            Self::InSession { source, .. } => source.without_id(),
+            e => e,
+        }
+    }
 }
 
 /// An error that can occur when reading/writing the WebTransport stream header.
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum WebTransportError {
-    #[error("closed: code={code} reason={reason}")]
-    Closed { code: u32, reason: String },
+    /// The session was closed by the peer. The reason is kept verbatim, as it needn't be UTF-8.
+    #[error("closed: code={code} reason={reason:?}")]
+    Closed { code: u32, reason: Bytes },
 
     #[error("unknown session")]
     UnknownSession,
@@ -68,9 +127,23 @@ pub enum WebTransportError {
     WriteError(#[error(source, from, std_err)] endpoint::WriteError),
 }
 
+impl WebTransportError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Closed { .. } => ErrorKind::SessionClosed,
+            Self::UnknownSession => ErrorKind::Protocol,
+            Self::ReadError(endpoint::ReadExactError::FinishedEarly(_)) => ErrorKind::UnexpectedEnd,
+            Self::ReadError(endpoint::ReadExactError::ReadError(e)) => endpoint_read_kind(e),
+            Self::WriteError(e) => endpoint_write_kind(e),
+        }
+    }
+}
+
 /// An error when writing to [`crate::SendStream`]. Similar to [`iroh::endpoint::WriteError`].
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum WriteError {
     #[error("STOP_SENDING: {_0}")]
     Stopped(u32),
@@ -101,9 +174,22 @@ impl From<endpoint::WriteError> for WriteError {
     }
 }
 
+impl WriteError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Stopped(_) => ErrorKind::StreamStopped,
+            Self::InvalidStopped(_) => ErrorKind::Protocol,
+            Self::SessionError(e) => e.kind(),
+            Self::ClosedStream => ErrorKind::LocallyClosed,
+        }
+    }
+}
+
 /// An error when reading from [`crate::RecvStream`]. Similar to [`iroh::endpoint::ReadError`].
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum ReadError {
     #[error("session error")]
     SessionError(#[error(source, from)] SessionError),
@@ -134,9 +220,22 @@ impl From<endpoint::ReadError> for ReadError {
     }
 }
 
+impl ReadError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::SessionError(e) => e.kind(),
+            Self::Reset(_) => ErrorKind::StreamReset,
+            Self::InvalidReset(_) => ErrorKind::Protocol,
+            Self::ClosedStream => ErrorKind::LocallyClosed,
+        }
+    }
+}
+
 /// An error returned by [`crate::RecvStream::read_exact`]. Similar to [`iroh::endpoint::ReadExactError`].
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum ReadExactError {
     #[error("finished early")]
     FinishedEarly(usize),
@@ -154,22 +253,34 @@ impl From<endpoint::ReadExactError> for ReadExactError {
     }
 }
 
+impl ReadExactError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::FinishedEarly(_) => ErrorKind::UnexpectedEnd,
+            Self::ReadError(e) => e.kind(),
+        }
+    }
+}
+
 /// An error returned by [`crate::RecvStream::read_to_end`]. Similar to [`iroh::endpoint::ReadToEndError`].
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum ReadToEndError {
-    #[error("too long")]
-    TooLong,
+    #[error("stream longer than the limit of {limit} bytes")]
+    TooLong { limit: usize },
 
     #[error("read error")]
     ReadError(#[error(source, from)] ReadError),
 }
 
-impl From<endpoint::ReadToEndError> for ReadToEndError {
-    fn from(e: endpoint::ReadToEndError) -> Self {
-        match e {
-            endpoint::ReadToEndError::TooLong => ReadToEndError::TooLong,
-            endpoint::ReadToEndError::Read(e) => ReadToEndError::ReadError(e.into()),
+impl ReadToEndError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TooLong { .. } => ErrorKind::TooLarge,
+            Self::ReadError(e) => e.kind(),
         }
     }
 }
@@ -189,6 +300,7 @@ impl From<endpoint::ClosedStream> for ClosedStream {
 /// An error returned when receiving a new WebTransport session.
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum ServerError {
     #[error("unexpected end of stream")]
     UnexpectedEnd,
@@ -218,14 +330,46 @@ pub enum ServerError {
     SettingsError(#[error(source, from, std_err)] SettingsError),
 }
 
+impl ServerError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Connection(e) => connection_kind(e),
+            Self::Connecting(_) => ErrorKind::Other,
+            Self::WriteError(e) => endpoint_write_kind(e),
+            Self::ReadError(e) => endpoint_read_kind(e),
+            Self::IoError(_) | Self::Bind(_) => ErrorKind::Io,
+            Self::HttpError(e) => e.kind(),
+            Self::SettingsError(e) => e.kind(),
+        }
+    }
+}
+
 impl web_transport_trait::Error for SessionError {
     fn session_error(&self) -> Option<(u32, String)> {
-        if let SessionError::WebTransportError(WebTransportError::Closed { code, reason }) = self {
-            return Some((*code, reason.to_string()));
+        if let SessionError::WebTransportError(WebTransportError::Closed { code, reason }) =
+            self.without_id()
+        {
+            return Some((*code, String::from_utf8_lossy(reason).into_owned()));
         }
 
         None
     }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let SessionError::WebTransportError(WebTransportError::Closed { code, reason }) =
+            self.without_id()
+        {
+            return Some((*code, reason.clone()));
+        }
+
+        None
+    }
+
+    fn kind(&self) -> ErrorKind {
+        SessionError::kind(self)
+    }
 }
 
 impl web_transport_trait::Error for WriteError {
@@ -237,12 +381,24 @@ impl web_transport_trait::Error for WriteError {
         None
     }
 
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let WriteError::SessionError(e) = self {
+            return e.session_error_bytes();
+        }
+
+        None
+    }
+
     fn stream_error(&self) -> Option<u32> {
         match self {
             WriteError::Stopped(code) => Some(*code),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        WriteError::kind(self)
+    }
 }
 
 impl web_transport_trait::Error for ReadError {
@@ -254,10 +410,53 @@ impl web_transport_trait::Error for ReadError {
         None
     }
 
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let ReadError::SessionError(e) = self {
+            return e.session_error_bytes();
+        }
+
+        None
+    }
+
     fn stream_error(&self) -> Option<u32> {
         match self {
             ReadError::Reset(code) => Some(*code),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        ReadError::kind(self)
+    }
+}
+
+pub(crate) fn connection_kind(err: &endpoint::ConnectionError) -> ErrorKind {
+    match err {
+        endpoint::ConnectionError::ApplicationClosed(_)
+        | endpoint::ConnectionError::ConnectionClosed(_)
+        | endpoint::ConnectionError::Reset => ErrorKind::SessionClosed,
+        endpoint::ConnectionError::LocallyClosed => ErrorKind::LocallyClosed,
+        endpoint::ConnectionError::TimedOut => ErrorKind::TimedOut,
+        endpoint::ConnectionError::VersionMismatch
+        | endpoint::ConnectionError::TransportError(_) => ErrorKind::Protocol,
+        endpoint::ConnectionError::CidsExhausted => ErrorKind::Other,
+    }
+}
+
+pub(crate) fn endpoint_write_kind(err: &endpoint::WriteError) -> ErrorKind {
+    match err {
+        endpoint::WriteError::Stopped(_) => ErrorKind::StreamStopped,
+        endpoint::WriteError::ClosedStream => ErrorKind::LocallyClosed,
+        endpoint::WriteError::ConnectionLost(e) => connection_kind(e),
+        endpoint::WriteError::ZeroRttRejected => ErrorKind::Other,
+    }
+}
+
+pub(crate) fn endpoint_read_kind(err: &endpoint::ReadError) -> ErrorKind {
+    match err {
+        endpoint::ReadError::Reset(_) => ErrorKind::StreamReset,
+        endpoint::ReadError::ClosedStream => ErrorKind::LocallyClosed,
+        endpoint::ReadError::ConnectionLost(e) => connection_kind(e),
+        endpoint::ReadError::ZeroRttRejected => ErrorKind::Other,
+    }
 }
@@ -53,3 +53,5 @@ pub use iroh;
 pub use web_transport_proto as proto;
 /// Re-export the generic WebTransport implementation.
 pub use web_transport_trait as generic;
+/// A backend-independent classification returned by each error's `kind()`.
+pub use web_transport_trait::ErrorKind;
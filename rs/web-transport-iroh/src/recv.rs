@@ -4,7 +4,7 @@ use std::{
     task::{Context, Poll},
 };
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use iroh::endpoint;
 
 use crate::{ClosedStream, ReadError, ReadExactError, ReadToEndError, SessionError};
@@ -13,11 +13,39 @@ use crate::{ClosedStream, ReadError, ReadExactError, ReadToEndError, SessionErro
 #[derive(Debug)]
 pub struct RecvStream {
     inner: endpoint::RecvStream,
+
+    // Payload that arrived in the same chunk as the stream header, served before
+    // reading from `inner`.
+    buffered: Bytes,
 }
 
 impl RecvStream {
     pub(crate) fn new(stream: endpoint::RecvStream) -> Self {
-        Self { inner: stream }
+        Self {
+            inner: stream,
+            buffered: Bytes::new(),
+        }
+    }
+
+    /// Attach payload bytes that were read along with the stream header.
+    pub(crate) fn with_buffered(mut self, buffered: Bytes) -> Self {
+        self.buffered = buffered;
+        self
+    }
+
+    /// Data that has already been received but not yet read.
+    ///
+    /// When a stream is accepted, any payload that arrived in the same packet as
+    /// the WebTransport header is kept here instead of being dropped back into
+    /// QUIC, so a short stream can be inspected (or fully read) without waiting
+    /// on another wakeup. Every read method consumes these bytes first.
+    pub fn peek(&self) -> &[u8] {
+        &self.buffered
+    }
+
+    // Take up to `max` bytes from the front of the buffered payload.
+    fn take_buffered(&mut self, max: usize) -> Bytes {
+        self.buffered.split_to(max.min(self.buffered.len()))
     }
 
     /// Tell the other end to stop sending data with the given error code. See [`iroh::endpoint::RecvStream::stop`].
@@ -32,16 +60,30 @@ impl RecvStream {
 
     /// Read some data into the buffer and return the amount read. See [`iroh::endpoint::RecvStream::read`].
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, ReadError> {
+        if !self.buffered.is_empty() {
+            let chunk = self.take_buffered(buf.len());
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            return Ok(Some(chunk.len()));
+        }
         self.inner.read(buf).await.map_err(Into::into)
     }
 
     /// Fill the entire buffer with data. See [`iroh::endpoint::RecvStream::read_exact`].
     pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ReadExactError> {
+        let chunk = self.take_buffered(buf.len());
+        let (head, buf) = buf.split_at_mut(chunk.len());
+        head.copy_from_slice(&chunk);
+        if buf.is_empty() {
+            return Ok(());
+        }
         self.inner.read_exact(buf).await.map_err(Into::into)
     }
 
     /// Read a chunk of data from the stream. See [`iroh::endpoint::RecvStream::read_chunk`].
     pub async fn read_chunk(&mut self, max_length: usize) -> Result<Option<Bytes>, ReadError> {
+        if !self.buffered.is_empty() {
+            return Ok(Some(self.take_buffered(max_length)));
+        }
         self.inner.read_chunk(max_length).await.map_err(Into::into)
     }
 
@@ -50,12 +92,27 @@ impl RecvStream {
         &mut self,
         bufs: &mut [Bytes],
     ) -> Result<Option<usize>, ReadError> {
+        if let (Some(first), false) = (bufs.first_mut(), self.buffered.is_empty()) {
+            *first = self.take_buffered(usize::MAX);
+            return Ok(Some(1));
+        }
         self.inner.read_many_chunks(bufs).await.map_err(Into::into)
     }
 
-    /// Read until the end of the stream or the limit is hit. See [`iroh::endpoint::RecvStream::read_to_end`].
+    /// Read until the end of the stream, failing with [ReadToEndError::TooLong] if it exceeds `size_limit`. See [`iroh::endpoint::RecvStream::read_to_end`].
     pub async fn read_to_end(&mut self, size_limit: usize) -> Result<Vec<u8>, ReadToEndError> {
-        self.inner.read_to_end(size_limit).await.map_err(Into::into)
+        let head = self.take_buffered(usize::MAX);
+        let limit = size_limit
+            .checked_sub(head.len())
+            .ok_or(ReadToEndError::TooLong { limit: size_limit })?;
+        let tail = self.inner.read_to_end(limit).await.map_err(|e| match e {
+            endpoint::ReadToEndError::Read(e) => ReadToEndError::ReadError(e.into()),
+            endpoint::ReadToEndError::TooLong => ReadToEndError::TooLong { limit: size_limit },
+        })?;
+        if head.is_empty() {
+            return Ok(tail);
+        }
+        Ok([&head[..], &tail[..]].concat())
     }
 
     /// Block until the stream has been reset and return the error code. See [`iroh::endpoint::RecvStream::received_reset`].
@@ -75,7 +132,9 @@ impl RecvStream {
     /// This is the offset of the next byte to be read, i.e. the length of the contiguous
     /// prefix of the stream consumed by the application.
     pub fn bytes_read(&self) -> Result<u64, ClosedStream> {
-        self.inner.bytes_read().map_err(|_| ClosedStream)
+        // Buffered bytes were pulled off `inner` but haven't been read by the caller.
+        let read = self.inner.bytes_read().map_err(|_| ClosedStream)?;
+        Ok(read - self.buffered.len() as u64)
     }
 
     // We purposely don't expose the stream ID or 0RTT because it's not valid with WebTransport
@@ -87,6 +146,12 @@ impl tokio::io::AsyncRead for RecvStream {
         cx: &mut Context<'_>,
         buf: &mut tokio::io::ReadBuf,
     ) -> Poll<io::Result<()>> {
+        if !self.buffered.is_empty() {
+            let n = buf.remaining().min(self.buffered.len());
+            buf.put_slice(&self.buffered[..n]);
+            self.buffered.advance(n);
+            return Poll::Ready(Ok(()));
+        }
         Pin::new(&mut self.inner).poll_read(cx, buf)
     }
 }
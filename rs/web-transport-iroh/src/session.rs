@@ -8,13 +8,15 @@ use std::{
     task::{Context, Poll, ready},
 };
 
-use bytes::{Bytes, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use iroh::endpoint::{self, Connection, PathStats};
 use n0_future::{
     FuturesUnordered,
     stream::{Stream, StreamExt},
 };
+use tracing::Instrument;
 use web_transport_proto::{ConnectRequest, ConnectResponse, Frame, StreamUni, VarInt};
+use web_transport_trait::{CloseRecord, Closed, SessionId};
 
 use crate::{
     ClientError, Connected, RecvStream, SendStream, SessionError, Settings, WebTransportError,
@@ -33,6 +35,12 @@ use crate::{
 pub struct Session {
     conn: Connection,
     h3: Option<H3SessionState>,
+
+    // Identifies the session in logs, as the `session` span around its background task.
+    id: SessionId,
+
+    // The first close code and reason from either side, kept for the trait's close_reason().
+    close_record: CloseRecord,
 }
 
 impl Session {
@@ -41,7 +49,12 @@ impl Session {
     /// This is used to pretend like a QUIC connection is a WebTransport session.
     /// It's a hack, but it makes it much easier to support WebTransport and raw QUIC simultaneously.
     pub fn raw(conn: Connection) -> Self {
-        Self { conn, h3: None }
+        Self {
+            conn,
+            h3: None,
+            id: SessionId::next(),
+            close_record: CloseRecord::new(),
+        }
     }
 
     /// Connect using an established QUIC connection if you want to create the connection yourself.
@@ -68,16 +81,26 @@ impl Session {
     /// Creates a session from pre-established HTTP/3 handshake components.
     pub fn new_h3(conn: Connection, settings: Settings, mut connect: Connected) -> Self {
         let h3 = H3SessionState::connect(conn.clone(), settings, &connect);
-        let this = Session { conn, h3: Some(h3) };
+        let this = Session {
+            conn,
+            h3: Some(h3),
+            id: SessionId::next(),
+            close_record: CloseRecord::new(),
+        };
         // Run a background task to check if the connect stream is closed.
         let this2 = this.clone();
-        tokio::spawn(async move {
-            let (code, reason) = connect.run_closed().await;
-            if this2.conn().close_reason().is_none() {
-                // TODO We shouldn't be closing the QUIC connection with the same error.
-                this2.close(code, reason.as_bytes());
+        let span = tracing::info_span!("session", id = %this.id);
+        tokio::spawn(
+            async move {
+                let (code, reason) = connect.run_closed().await;
+                if this2.conn().close_reason().is_none() && this2.close_record.remote(code, &reason)
+                {
+                    // TODO We shouldn't be closing the QUIC connection with the same error.
+                    this2.close_conn(code, &reason);
+                }
             }
-        });
+            .instrument(span),
+        );
         this
     }
 
@@ -86,6 +109,14 @@ impl Session {
         &self.conn
     }
 
+    /// The identifier this session logs with. See [`SessionId`].
+    ///
+    /// Every tracing event from the session's background task is inside a `session`
+    /// span with this `id`, so log it with your own events to correlate the two.
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
     /// Returns the [`ConnectRequest`] if this session was established over HTTP/3.
     pub fn request(&self) -> Option<&ConnectRequest> {
         self.h3.as_ref().map(|s| &s.request)
@@ -213,7 +244,16 @@ impl Session {
     }
 
     /// Immediately close the connection with an error code and reason. See [`iroh::endpoint::Connection::close`].
+    ///
+    /// Only the first close takes effect, whether it was ours or the peer's; later calls
+    /// are ignored. [`web_transport_trait::Session::close_reason`] returns the one that won.
     pub fn close(&self, code: u32, reason: &[u8]) {
+        if self.close_record.local(code, reason) {
+            self.close_conn(code, reason);
+        }
+    }
+
+    fn close_conn(&self, code: u32, reason: &[u8]) {
         let code = if self.h3.is_some() {
             web_transport_proto::error_to_http3(code)
                 .try_into()
@@ -227,12 +267,22 @@ impl Session {
 
     /// Wait until the session is closed, returning the error. See [`iroh::endpoint::Connection::closed`].
     pub async fn closed(&self) -> SessionError {
-        self.conn.closed().await.into()
+        self.tag_close(self.conn.closed().await)
     }
 
     /// Return why the session was closed, or None if it's not closed. See [`iroh::endpoint::Connection::close_reason`].
+    ///
+    /// Use [`web_transport_trait::Session::close_reason`] for the code and reason of the first close instead.
     pub fn close_reason(&self) -> Option<SessionError> {
-        self.conn.close_reason().map(Into::into)
+        self.conn.close_reason().map(|e| self.tag_close(e))
+    }
+
+    // Tag the close error with the session's id.
+    fn tag_close(&self, e: endpoint::ConnectionError) -> SessionError {
+        SessionError::InSession {
+            source: Box::new(e.into()),
+            id: self.id,
+        }
     }
 }
 
@@ -343,9 +393,48 @@ type AcceptUni = dyn Stream<Item = Result<endpoint::RecvStream, endpoint::Connec
 type AcceptBi = dyn Stream<Item = Result<(endpoint::SendStream, endpoint::RecvStream), endpoint::ConnectionError>>
     + Send;
 type PendingUni =
-    dyn Future<Output = Result<(StreamUni, endpoint::RecvStream), SessionError>> + Send;
-type PendingBi = dyn Future<Output = Result<Option<(endpoint::SendStream, endpoint::RecvStream)>, SessionError>>
-    + Send;
+    dyn Future<Output = Result<(StreamUni, endpoint::RecvStream, Header), SessionError>> + Send;
+type PendingBi = dyn Future<
+        Output = Result<Option<(endpoint::SendStream, endpoint::RecvStream, Header)>, SessionError>,
+    > + Send;
+
+// Reads a stream header a chunk at a time instead of a byte at a time, keeping any
+// payload that arrived alongside it so the first read doesn't need another wakeup.
+#[derive(Default)]
+struct Header {
+    // Received bytes not yet consumed by the header.
+    buf: Bytes,
+}
+
+impl Header {
+    async fn read_varint(
+        &mut self,
+        recv: &mut endpoint::RecvStream,
+    ) -> Result<VarInt, SessionError> {
+        loop {
+            let mut cursor = &self.buf[..];
+            if let Ok(v) = VarInt::decode(&mut cursor) {
+                self.buf.advance(self.buf.len() - cursor.len());
+                return Ok(v);
+            }
+
+            let chunk = recv
+                .read_chunk(usize::MAX)
+                .await
+                .ok()
+                .flatten()
+                .ok_or(WebTransportError::UnknownSession)?;
+            self.buf = match self.buf.is_empty() {
+                true => chunk,
+                false => [&self.buf[..], &chunk[..]].concat().into(),
+            };
+        }
+    }
+
+    fn into_recv(self, recv: endpoint::RecvStream) -> RecvStream {
+        RecvStream::new(recv).with_buffered(self.buf)
+    }
+}
 
 // Logic just for accepting streams, which is annoying because of the stream header.
 struct H3SessionAccept {
@@ -408,7 +497,7 @@ impl H3SessionAccept {
             }
 
             // Poll the list of pending streams.
-            let (typ, recv) = match ready!(self.pending_uni.poll_next(cx)) {
+            let (typ, recv, header) = match ready!(self.pending_uni.poll_next(cx)) {
                 Some(Ok(res)) => res,
                 Some(Err(err)) => {
                     // Ignore the error, the stream was probably reset early.
@@ -421,7 +510,7 @@ impl H3SessionAccept {
             // Decide if we keep looping based on the type.
             match typ {
                 StreamUni::WEBTRANSPORT => {
-                    let recv = RecvStream::new(recv);
+                    let recv = header.into_recv(recv);
                     return Poll::Ready(Ok(recv));
                 }
                 StreamUni::QPACK_DECODER => {
@@ -442,25 +531,22 @@ impl H3SessionAccept {
     async fn decode_uni(
         mut recv: endpoint::RecvStream,
         expected_session: VarInt,
-    ) -> Result<(StreamUni, endpoint::RecvStream), SessionError> {
+    ) -> Result<(StreamUni, endpoint::RecvStream, Header), SessionError> {
+        let mut header = Header::default();
+
         // Read the VarInt at the start of the stream.
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
-        let typ = StreamUni(typ);
+        let typ = StreamUni(header.read_varint(&mut recv).await?);
 
         if typ == StreamUni::WEBTRANSPORT {
             // Read the session_id and validate it
-            let session_id = VarInt::read(&mut recv)
-                .await
-                .map_err(|_| WebTransportError::UnknownSession)?;
+            let session_id = header.read_varint(&mut recv).await?;
             if session_id != expected_session {
                 return Err(WebTransportError::UnknownSession.into());
             }
         }
 
         // We need to keep a reference to the qpack streams if the endpoint (incorrectly) creates them, so return everything.
-        Ok((typ, recv))
+        Ok((typ, recv, header))
     }
 
     pub fn poll_accept_bi(
@@ -489,10 +575,10 @@ impl H3SessionAccept {
                 None => return Poll::Pending,
             };
 
-            if let Some((send, recv)) = res {
+            if let Some((send, recv, header)) = res {
                 // Wrap the streams in our own types for correct error codes.
                 let send = SendStream::new(send);
-                let recv = RecvStream::new(recv);
+                let recv = header.into_recv(recv);
                 return Poll::Ready(Ok((send, recv)));
             }
 
@@ -505,24 +591,22 @@ impl H3SessionAccept {
         send: endpoint::SendStream,
         mut recv: endpoint::RecvStream,
         expected_session: VarInt,
-    ) -> Result<Option<(endpoint::SendStream, endpoint::RecvStream)>, SessionError> {
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
+    ) -> Result<Option<(endpoint::SendStream, endpoint::RecvStream, Header)>, SessionError> {
+        let mut header = Header::default();
+
+        let typ = header.read_varint(&mut recv).await?;
         if Frame(typ) != Frame::WEBTRANSPORT {
             tracing::debug!("ignoring unknown bidirectional stream: {typ:?}");
             return Ok(None);
         }
 
         // Read the session ID and validate it.
-        let session_id = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
+        let session_id = header.read_varint(&mut recv).await?;
         if session_id != expected_session {
             return Err(WebTransportError::UnknownSession.into());
         }
 
-        Ok(Some((send, recv)))
+        Ok(Some((send, recv, header)))
     }
 }
 
@@ -551,10 +635,22 @@ impl web_transport_trait::Session for Session {
         Self::close(self, code, reason.as_bytes());
     }
 
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        Self::close(self, code, reason);
+    }
+
     async fn closed(&self) -> Self::Error {
         Self::closed(self).await
     }
 
+    fn close_reason(&self) -> Option<Closed> {
+        self.close_record.get()
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        Some(self.id)
+    }
+
     fn send_datagram(&self, data: Bytes) -> Result<(), Self::Error> {
         Self::send_datagram(self, data)
     }
@@ -631,4 +727,8 @@ impl web_transport_trait::Stats for SessionStats {
             None
         }
     }
+
+    fn congestion_window(&self) -> Option<u64> {
+        self.selected_path_stats.map(|p| p.cwnd)
+    }
 }
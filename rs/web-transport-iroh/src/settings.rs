@@ -1,10 +1,14 @@
 use iroh::endpoint;
 use n0_error::stack_error;
 use tokio::try_join;
+use web_transport_trait::ErrorKind;
+
+use crate::error::{connection_kind, endpoint_read_kind, endpoint_write_kind};
 
 /// An error during the HTTP/3 SETTINGS frame exchange.
 #[stack_error(derive, from_sources)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum SettingsError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -12,8 +16,8 @@ pub enum SettingsError {
     #[error("protocol error")]
     ProtoError(#[error(source, from, std_err)] web_transport_proto::SettingsError),
 
-    #[error("WebTransport is not supported")]
-    WebTransportUnsupported,
+    #[error("WebTransport is not supported: {_0}")]
+    WebTransportUnsupported(web_transport_proto::UnsupportedSettings),
 
     #[error("connection error")]
     ConnectionError(#[error(source, from, std_err)] endpoint::ConnectionError),
@@ -25,6 +29,20 @@ pub enum SettingsError {
     WriteError(#[error(source, from, std_err)] endpoint::WriteError),
 }
 
+impl SettingsError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::ProtoError(_) => ErrorKind::Protocol,
+            Self::WebTransportUnsupported(_) => ErrorKind::Unsupported,
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::ReadError(e) => endpoint_read_kind(e),
+            Self::WriteError(e) => endpoint_write_kind(e),
+        }
+    }
+}
+
 /// Maintains the HTTP/3 control stream by holding references to the send/recv streams.
 #[derive(Debug)]
 pub struct Settings {
@@ -54,7 +72,9 @@ impl Settings {
         tracing::debug!("received SETTINGS frame: {settings:?}");
 
         if settings.supports_webtransport() == 0 {
-            return Err(SettingsError::WebTransportUnsupported);
+            return Err(SettingsError::WebTransportUnsupported(
+                web_transport_proto::UnsupportedSettings { settings },
+            ));
         }
 
         Ok(recv)
@@ -44,7 +44,7 @@ async fn h3_smoke() -> n0_error::Result<()> {
             stream.finish().unwrap();
             let reason = session.closed().await;
             assert!(
-                matches!(reason, SessionError::ConnectionError(ConnectionError::ApplicationClosed(frame)) if web_transport_proto::error_from_http3(frame.error_code.into_inner()) == Some(23))
+                matches!(reason.without_id(), SessionError::ConnectionError(ConnectionError::ApplicationClosed(frame)) if web_transport_proto::error_from_http3(frame.error_code.into_inner()) == Some(23))
             );
 
             drop(session);
@@ -114,7 +114,7 @@ async fn quic_smoke() -> n0_error::Result<()> {
             assert!(session.request().is_none());
             let reason = session.closed().await;
             assert!(
-                matches!(reason, SessionError::ConnectionError(ConnectionError::ApplicationClosed(frame)) if frame.error_code.into_inner() == 23)
+                matches!(reason.without_id(), SessionError::ConnectionError(ConnectionError::ApplicationClosed(frame)) if frame.error_code.into_inner() == 23)
             );
             client.close().await;
         }.instrument(tracing::error_span!("client"))
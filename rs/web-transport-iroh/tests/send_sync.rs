@@ -0,0 +1,9 @@
+use static_assertions::assert_impl_all;
+use web_transport_iroh::{ReadError, RecvStream, SendStream, Session, SessionError, WriteError};
+
+assert_impl_all!(Session: web_transport_trait::Session, Send, Sync, Clone);
+assert_impl_all!(SendStream: web_transport_trait::SendStream, Send);
+assert_impl_all!(RecvStream: web_transport_trait::RecvStream, Send);
+assert_impl_all!(SessionError: web_transport_trait::Error, Send, Sync);
+assert_impl_all!(WriteError: web_transport_trait::Error, Send, Sync);
+assert_impl_all!(ReadError: web_transport_trait::Error, Send, Sync);
@@ -0,0 +1,431 @@
+//! The on-disk format shared by the [recorder](crate::recorder) and [replay](crate::replay).
+//!
+//! A capture is a short header followed by length-prefixed records. Each record is a
+//! timestamp (microseconds since the session was wrapped) and one [Event], with integers
+//! encoded as QUIC variable-length integers to keep the file compact.
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use web_transport_proto::VarInt;
+
+const MAGIC: &[u8; 5] = b"WTCAP";
+const VERSION: u8 = 1;
+
+/// An error returned when reading a capture.
+#[derive(thiserror::Error, Debug)]
+pub enum CaptureError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("not a capture file")]
+    BadMagic,
+
+    #[error("unsupported capture version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("truncated record")]
+    Truncated,
+
+    #[error("unknown event type: {0}")]
+    UnknownEvent(u8),
+
+    #[error("invalid UTF-8")]
+    InvalidUtf8,
+}
+
+/// A session-level event, as seen by the application that was recorded.
+///
+/// Stream IDs are assigned by the recorder in the order streams are seen, starting at zero.
+/// They are not QUIC stream IDs, which the trait doesn't expose.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The application opened a bidirectional stream.
+    OpenBi { stream: u64 },
+
+    /// The application opened a unidirectional stream.
+    OpenUni { stream: u64 },
+
+    /// The peer opened a bidirectional stream.
+    AcceptBi { stream: u64 },
+
+    /// The peer opened a unidirectional stream.
+    AcceptUni { stream: u64 },
+
+    /// The application wrote data to a stream.
+    Write { stream: u64, data: Bytes },
+
+    /// The application finished a stream.
+    Finish { stream: u64 },
+
+    /// The application reset a stream.
+    Reset { stream: u64, code: u32 },
+
+    /// The application read data from a stream.
+    Read { stream: u64, data: Bytes },
+
+    /// The application reached the end of a stream.
+    Fin { stream: u64 },
+
+    /// The peer reset a stream.
+    ResetReceived { stream: u64, code: u32 },
+
+    /// The application asked the peer to stop sending.
+    Stop { stream: u64, code: u32 },
+
+    /// The application sent a datagram.
+    DatagramSent { data: Bytes },
+
+    /// The application received a datagram.
+    DatagramReceived { data: Bytes },
+
+    /// The application closed the session.
+    Close { code: u32, reason: String },
+
+    /// The session was closed, with the application code if there was one.
+    ///
+    /// When `code` is `None`, `reason` is the error the session closed with.
+    Closed { code: Option<u32>, reason: String },
+}
+
+impl Event {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::OpenBi { .. } => 1,
+            Self::OpenUni { .. } => 2,
+            Self::AcceptBi { .. } => 3,
+            Self::AcceptUni { .. } => 4,
+            Self::Write { .. } => 5,
+            Self::Finish { .. } => 6,
+            Self::Reset { .. } => 7,
+            Self::Read { .. } => 8,
+            Self::Fin { .. } => 9,
+            Self::ResetReceived { .. } => 10,
+            Self::Stop { .. } => 11,
+            Self::DatagramSent { .. } => 12,
+            Self::DatagramReceived { .. } => 13,
+            Self::Close { .. } => 14,
+            Self::Closed { .. } => 15,
+        }
+    }
+}
+
+/// A timestamped [Event].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// How long after the session was wrapped the event happened.
+    pub at: Duration,
+    pub event: Event,
+}
+
+impl Record {
+    pub fn encode<B: BufMut>(&self, buf: &mut B) {
+        let micros = u64::try_from(self.at.as_micros()).unwrap_or(u64::MAX);
+        VarInt::from_u64(micros).unwrap_or(VarInt::MAX).encode(buf);
+        buf.put_u8(self.event.tag());
+
+        match &self.event {
+            Event::OpenBi { stream }
+            | Event::OpenUni { stream }
+            | Event::AcceptBi { stream }
+            | Event::AcceptUni { stream }
+            | Event::Finish { stream }
+            | Event::Fin { stream } => encode_u64(buf, *stream),
+            Event::Write { stream, data } | Event::Read { stream, data } => {
+                encode_u64(buf, *stream);
+                encode_bytes(buf, data);
+            }
+            Event::Reset { stream, code }
+            | Event::ResetReceived { stream, code }
+            | Event::Stop { stream, code } => {
+                encode_u64(buf, *stream);
+                VarInt::from_u32(*code).encode(buf);
+            }
+            Event::DatagramSent { data } | Event::DatagramReceived { data } => {
+                encode_bytes(buf, data)
+            }
+            Event::Close { code, reason } => {
+                VarInt::from_u32(*code).encode(buf);
+                encode_bytes(buf, reason.as_bytes());
+            }
+            Event::Closed { code, reason } => {
+                match code {
+                    Some(code) => {
+                        buf.put_u8(1);
+                        VarInt::from_u32(*code).encode(buf);
+                    }
+                    None => buf.put_u8(0),
+                }
+                encode_bytes(buf, reason.as_bytes());
+            }
+        }
+    }
+
+    pub fn decode<B: Buf>(buf: &mut B) -> Result<Self, CaptureError> {
+        let at = Duration::from_micros(decode_u64(buf)?);
+        if !buf.has_remaining() {
+            return Err(CaptureError::Truncated);
+        }
+
+        let event = match buf.get_u8() {
+            1 => Event::OpenBi {
+                stream: decode_u64(buf)?,
+            },
+            2 => Event::OpenUni {
+                stream: decode_u64(buf)?,
+            },
+            3 => Event::AcceptBi {
+                stream: decode_u64(buf)?,
+            },
+            4 => Event::AcceptUni {
+                stream: decode_u64(buf)?,
+            },
+            5 => Event::Write {
+                stream: decode_u64(buf)?,
+                data: decode_bytes(buf)?,
+            },
+            6 => Event::Finish {
+                stream: decode_u64(buf)?,
+            },
+            7 => Event::Reset {
+                stream: decode_u64(buf)?,
+                code: decode_u32(buf)?,
+            },
+            8 => Event::Read {
+                stream: decode_u64(buf)?,
+                data: decode_bytes(buf)?,
+            },
+            9 => Event::Fin {
+                stream: decode_u64(buf)?,
+            },
+            10 => Event::ResetReceived {
+                stream: decode_u64(buf)?,
+                code: decode_u32(buf)?,
+            },
+            11 => Event::Stop {
+                stream: decode_u64(buf)?,
+                code: decode_u32(buf)?,
+            },
+            12 => Event::DatagramSent {
+                data: decode_bytes(buf)?,
+            },
+            13 => Event::DatagramReceived {
+                data: decode_bytes(buf)?,
+            },
+            14 => Event::Close {
+                code: decode_u32(buf)?,
+                reason: decode_string(buf)?,
+            },
+            15 => {
+                if !buf.has_remaining() {
+                    return Err(CaptureError::Truncated);
+                }
+                let code = match buf.get_u8() {
+                    0 => None,
+                    _ => Some(decode_u32(buf)?),
+                };
+                Event::Closed {
+                    code,
+                    reason: decode_string(buf)?,
+                }
+            }
+            tag => return Err(CaptureError::UnknownEvent(tag)),
+        };
+
+        Ok(Self { at, event })
+    }
+}
+
+/// Writes [Record]s to a capture file.
+pub struct CaptureWriter<W: Write> {
+    out: W,
+    buf: BytesMut,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Start a new capture, writing the header immediately.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+
+        Ok(Self {
+            out,
+            buf: BytesMut::new(),
+        })
+    }
+
+    /// Append a record to the capture.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        self.buf.clear();
+        record.encode(&mut self.buf);
+
+        let size = u32::try_from(self.buf.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record too large"))?;
+        self.out.write_all(&size.to_be_bytes())?;
+        self.out.write_all(&self.buf)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Reads [Record]s from a capture file, in the order they were written.
+pub struct CaptureReader<R: Read> {
+    input: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Open a capture, validating the header.
+    pub fn new(mut input: R) -> Result<Self, CaptureError> {
+        let mut header = [0u8; 6];
+        input.read_exact(&mut header).map_err(truncated)?;
+
+        if &header[..5] != MAGIC {
+            return Err(CaptureError::BadMagic);
+        }
+
+        if header[5] != VERSION {
+            return Err(CaptureError::UnsupportedVersion(header[5]));
+        }
+
+        Ok(Self { input })
+    }
+
+    /// Read the next record, or `None` at the end of the capture.
+    pub fn read(&mut self) -> Result<Option<Record>, CaptureError> {
+        let mut size = [0u8; 4];
+        match self.input.read(&mut size[..1])? {
+            0 => return Ok(None),
+            _ => self.input.read_exact(&mut size[1..]).map_err(truncated)?,
+        }
+
+        let mut body = vec![0u8; u32::from_be_bytes(size) as usize];
+        self.input.read_exact(&mut body).map_err(truncated)?;
+
+        let mut body = Bytes::from(body);
+        let record = Record::decode(&mut body)?;
+        if body.has_remaining() {
+            return Err(CaptureError::Truncated);
+        }
+
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<Record, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+fn truncated(err: io::Error) -> CaptureError {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => CaptureError::Truncated,
+        _ => err.into(),
+    }
+}
+
+fn encode_u64<B: BufMut>(buf: &mut B, v: u64) {
+    VarInt::from_u64(v).unwrap_or(VarInt::MAX).encode(buf);
+}
+
+fn encode_bytes<B: BufMut>(buf: &mut B, data: &[u8]) {
+    encode_u64(buf, data.len() as u64);
+    buf.put_slice(data);
+}
+
+fn decode_u64<B: Buf>(buf: &mut B) -> Result<u64, CaptureError> {
+    VarInt::decode(buf)
+        .map(VarInt::into_inner)
+        .map_err(|_| CaptureError::Truncated)
+}
+
+fn decode_u32<B: Buf>(buf: &mut B) -> Result<u32, CaptureError> {
+    u32::try_from(decode_u64(buf)?).map_err(|_| CaptureError::Truncated)
+}
+
+fn decode_bytes<B: Buf>(buf: &mut B) -> Result<Bytes, CaptureError> {
+    let size = decode_u64(buf)? as usize;
+    if buf.remaining() < size {
+        return Err(CaptureError::Truncated);
+    }
+
+    Ok(buf.copy_to_bytes(size))
+}
+
+fn decode_string<B: Buf>(buf: &mut B) -> Result<String, CaptureError> {
+    String::from_utf8(decode_bytes(buf)?.to_vec()).map_err(|_| CaptureError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let records = vec![
+            Record {
+                at: Duration::ZERO,
+                event: Event::OpenBi { stream: 0 },
+            },
+            Record {
+                at: Duration::from_millis(3),
+                event: Event::Write {
+                    stream: 0,
+                    data: Bytes::from_static(b"hello"),
+                },
+            },
+            Record {
+                at: Duration::from_millis(4),
+                event: Event::ResetReceived {
+                    stream: 0,
+                    code: u32::MAX,
+                },
+            },
+            Record {
+                at: Duration::from_secs(1),
+                event: Event::Closed {
+                    code: None,
+                    reason: "timed out".into(),
+                },
+            },
+        ];
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let file = writer.into_inner();
+
+        let decoded = CaptureReader::new(file.as_slice())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, records);
+
+        // Cutting the last record short is an error, not a clean end.
+        let cut = &file[..file.len() - 1];
+        let res: Result<Vec<_>, _> = CaptureReader::new(cut).unwrap().collect();
+        assert!(matches!(res, Err(CaptureError::Truncated)));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(
+            CaptureReader::new(&b"GIF89a"[..]),
+            Err(CaptureError::BadMagic)
+        ));
+        assert!(matches!(
+            CaptureReader::new(&b"WTCAP\x09"[..]),
+            Err(CaptureError::UnsupportedVersion(9))
+        ));
+    }
+}
@@ -0,0 +1,13 @@
+//! Testing support for code written against [web_transport_trait].
+//!
+//! - [recorder] wraps a session and logs every session-level event to a compact capture file.
+//! - [replay] plays the peer's side of a capture against another session, reproducing the run.
+//! - [capture] is the file format the two share.
+//! - [memory] connects a pair of sessions in memory, for tests without a network.
+//! - [selftest] runs a conformance script between two endpoints and reports what passed.
+
+pub mod capture;
+pub mod memory;
+pub mod recorder;
+pub mod replay;
+pub mod selftest;
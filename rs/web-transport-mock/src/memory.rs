@@ -0,0 +1,822 @@
+//! A pair of sessions connected in memory, for tests that don't need a network.
+//!
+//! [pair] returns both ends of a session without sockets, certificates or a runtime
+//! beyond the current one. Everything is delivered in order and nothing is lost, except
+//! datagrams that overflow the peer's queue, so protocol logic can be unit tested
+//! deterministically. Stream limits, resets, stops and close codes behave as they do
+//! over QUIC; flow control and congestion are not modelled.
+//!
+//! ```
+//! # use web_transport_trait::{RecvStream, SendStream, Session};
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let (client, server) = web_transport_mock::memory::pair();
+//!
+//! let mut send = client.open_uni().await.unwrap();
+//! send.write_all(b"hello").await.unwrap();
+//! send.finish().unwrap();
+//!
+//! let mut recv = server.accept_uni().await.unwrap();
+//! assert_eq!(recv.read_all().await.unwrap(), "hello");
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use tokio::sync::Notify;
+use web_transport_trait::{CloseRecord, Closed, ErrorKind, SessionId, Stats};
+
+/// An error from an in-memory session or stream.
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum MemoryError {
+    #[error("session closed by peer: {code} {}", String::from_utf8_lossy(.reason))]
+    Closed { code: u32, reason: Bytes },
+
+    #[error("session closed locally")]
+    LocallyClosed,
+
+    #[error("stream reset by peer: {0}")]
+    Reset(u32),
+
+    #[error("stream stopped by peer: {0}")]
+    Stopped(u32),
+
+    #[error("stream already closed")]
+    StreamClosed,
+
+    #[error("datagram too large: {size} > {max}")]
+    DatagramTooLarge { size: usize, max: usize },
+}
+
+impl web_transport_trait::Error for MemoryError {
+    fn session_error(&self) -> Option<(u32, String)> {
+        match self {
+            Self::Closed { code, reason } => {
+                Some((*code, String::from_utf8_lossy(reason).into_owned()))
+            }
+            _ => None,
+        }
+    }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        match self {
+            Self::Closed { code, reason } => Some((*code, reason.clone())),
+            _ => None,
+        }
+    }
+
+    fn stream_error(&self) -> Option<u32> {
+        match self {
+            Self::Reset(code) | Self::Stopped(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Closed { .. } => ErrorKind::SessionClosed,
+            Self::LocallyClosed | Self::StreamClosed => ErrorKind::LocallyClosed,
+            Self::Reset(_) => ErrorKind::StreamReset,
+            Self::Stopped(_) => ErrorKind::StreamStopped,
+            Self::DatagramTooLarge { .. } => ErrorKind::TooLarge,
+        }
+    }
+}
+
+/// Configures the sessions created by [MemoryBuilder::pair].
+///
+/// Both ends share the same limits.
+#[derive(Clone, Debug)]
+pub struct MemoryBuilder {
+    max_bi_streams: usize,
+    max_uni_streams: usize,
+    max_datagram_size: usize,
+    datagram_queue: usize,
+    protocol: Option<String>,
+}
+
+impl Default for MemoryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryBuilder {
+    pub fn new() -> Self {
+        Self {
+            max_bi_streams: 100,
+            max_uni_streams: 100,
+            max_datagram_size: 1200,
+            datagram_queue: 32,
+            protocol: None,
+        }
+    }
+
+    /// Allow each side to open at most `max` bidirectional streams at once. Defaults to 100.
+    ///
+    /// A stream counts until both ends have dropped it, and opening another blocks
+    /// until then, like running out of MAX_STREAMS credit.
+    pub fn with_max_bi_streams(mut self, max: usize) -> Self {
+        self.max_bi_streams = max;
+        self
+    }
+
+    /// Allow each side to open at most `max` unidirectional streams at once. Defaults to 100.
+    pub fn with_max_uni_streams(mut self, max: usize) -> Self {
+        self.max_uni_streams = max;
+        self
+    }
+
+    /// Reject datagrams larger than `max` bytes. Defaults to 1200; 0 disables datagrams.
+    pub fn with_max_datagram_size(mut self, max: usize) -> Self {
+        self.max_datagram_size = max;
+        self
+    }
+
+    /// Hold up to `max` unread datagrams per side, dropping any more. Defaults to 32.
+    pub fn with_datagram_queue(mut self, max: usize) -> Self {
+        self.datagram_queue = max;
+        self
+    }
+
+    /// Report `protocol` as the negotiated subprotocol on both ends.
+    pub fn with_protocol(mut self, protocol: impl Into<String>) -> Self {
+        self.protocol = Some(protocol.into());
+        self
+    }
+
+    /// Create a connected pair of sessions: the client end, then the server end.
+    pub fn pair(self) -> (MemorySession, MemorySession) {
+        let link = Arc::new(Link {
+            config: self,
+            state: Mutex::default(),
+            changed: Notify::new(),
+        });
+
+        let client = MemorySession::new(link.clone(), 0);
+        let server = MemorySession::new(link, 1);
+        (client, server)
+    }
+}
+
+/// Create a connected pair of sessions with the default limits.
+///
+/// See [MemoryBuilder] to change them.
+pub fn pair() -> (MemorySession, MemorySession) {
+    MemoryBuilder::new().pair()
+}
+
+// Everything both ends share, behind one lock.
+struct Link {
+    config: MemoryBuilder,
+    state: Mutex<State>,
+
+    // Woken on any change, so waiters re-check whatever they're waiting for.
+    changed: Notify,
+}
+
+impl Link {
+    fn update<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let res = f(&mut self.state.lock().unwrap());
+        self.changed.notify_waiters();
+        res
+    }
+
+    // Wait until `f` returns Some, re-checking after every change.
+    async fn wait<T>(&self, mut f: impl FnMut(&mut State) -> Option<T>) -> T {
+        loop {
+            let mut changed = pin!(self.changed.notified());
+            changed.as_mut().enable();
+
+            let ready = f(&mut self.state.lock().unwrap());
+            if let Some(res) = ready {
+                self.changed.notify_waiters();
+                return res;
+            }
+
+            changed.await;
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    sides: [Side; 2],
+    pipes: HashMap<u64, Pipe>,
+    next_pipe: u64,
+}
+
+impl State {
+    fn open_pipe(&mut self, limit: Option<(usize, Dir)>) -> u64 {
+        let id = self.next_pipe;
+        self.next_pipe += 1;
+        self.pipes.insert(
+            id,
+            Pipe {
+                limit,
+                ..Default::default()
+            },
+        );
+        id
+    }
+
+    // Forget a pipe once neither end holds it, releasing its stream limit.
+    // Close the session from `side`. The first close wins; a later one from the other end only marks the race.
+    fn close(&mut self, side: usize, code: u32, reason: Bytes) {
+        self.sides[side].close.local(code, &reason);
+        self.sides[1 - side].close.remote(code, &reason);
+
+        if self.sides[side].error.is_some() {
+            return;
+        }
+
+        self.sides[side].error = Some(MemoryError::LocallyClosed);
+        self.sides[1 - side].error = Some(MemoryError::Closed { code, reason });
+    }
+
+    fn release(&mut self, id: u64) {
+        let Some(pipe) = self.pipes.get(&id) else {
+            return;
+        };
+        if pipe.writer || pipe.reader {
+            return;
+        }
+
+        if let Some((side, dir)) = self.pipes.remove(&id).and_then(|pipe| pipe.limit) {
+            *self.sides[side].active(dir) -= 1;
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Dir {
+    Bi,
+    Uni,
+}
+
+// One end's view of the session.
+#[derive(Default)]
+struct Side {
+    // Streams opened by the peer and not accepted yet.
+    uni: VecDeque<u64>,
+    bi: VecDeque<(u64, u64)>,
+
+    // Streams opened by the peer that still count against our limits.
+    active_bi: usize,
+    active_uni: usize,
+
+    datagrams: VecDeque<Bytes>,
+    datagrams_dropped: u64,
+
+    // Set once either end closes the session.
+    error: Option<MemoryError>,
+
+    // The first close from either end, as this end saw it.
+    close: CloseRecord,
+}
+
+impl Side {
+    fn active(&mut self, dir: Dir) -> &mut usize {
+        match dir {
+            Dir::Bi => &mut self.active_bi,
+            Dir::Uni => &mut self.active_uni,
+        }
+    }
+}
+
+// The bytes flowing in one direction of a stream.
+struct Pipe {
+    data: VecDeque<Bytes>,
+
+    // Set by the writer.
+    fin: bool,
+    reset: Option<u32>,
+
+    // Set by the reader.
+    fin_read: bool,
+    stop: Option<u32>,
+
+    // Whether each end is still held.
+    writer: bool,
+    reader: bool,
+
+    // The side whose stream limit this pipe counts against, if any.
+    limit: Option<(usize, Dir)>,
+}
+
+impl Default for Pipe {
+    fn default() -> Self {
+        Self {
+            data: VecDeque::new(),
+            fin: false,
+            reset: None,
+            fin_read: false,
+            stop: None,
+            writer: true,
+            reader: true,
+            limit: None,
+        }
+    }
+}
+
+// Closes the session once every clone is dropped.
+struct Handle {
+    link: Arc<Link>,
+    side: usize,
+    id: SessionId,
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // Dropping after a close isn't a close of its own, so it never races the peer's.
+        self.link.update(|state| {
+            if state.sides[self.side].error.is_none() {
+                state.close(self.side, 0, Bytes::new());
+            }
+        });
+    }
+}
+
+/// One end of an in-memory session, created by [pair].
+///
+/// Cloning is cheap; the session is closed with code 0 once every clone is dropped.
+#[derive(Clone)]
+pub struct MemorySession {
+    handle: Arc<Handle>,
+}
+
+impl MemorySession {
+    fn new(link: Arc<Link>, side: usize) -> Self {
+        Self {
+            handle: Arc::new(Handle {
+                link,
+                side,
+                id: SessionId::next(),
+            }),
+        }
+    }
+
+    fn link(&self) -> &Arc<Link> {
+        &self.handle.link
+    }
+
+    fn side(&self) -> usize {
+        self.handle.side
+    }
+
+    fn peer(&self) -> usize {
+        1 - self.handle.side
+    }
+
+    fn send(&self, id: u64) -> MemorySendStream {
+        MemorySendStream {
+            link: self.link().clone(),
+            side: self.side(),
+            id,
+        }
+    }
+
+    fn recv(&self, id: u64) -> MemoryRecvStream {
+        MemoryRecvStream {
+            link: self.link().clone(),
+            side: self.side(),
+            id,
+        }
+    }
+
+    // Wait for room under the peer's limit, then open the pipes with `open`.
+    async fn open<T>(&self, dir: Dir, open: impl Fn(&mut State) -> T) -> Result<T, MemoryError> {
+        let max = match dir {
+            Dir::Bi => self.link().config.max_bi_streams,
+            Dir::Uni => self.link().config.max_uni_streams,
+        };
+        let (side, peer) = (self.side(), self.peer());
+
+        self.link()
+            .wait(|state| {
+                if let Some(err) = &state.sides[side].error {
+                    return Some(Err(err.clone()));
+                }
+                if *state.sides[peer].active(dir) >= max {
+                    return None;
+                }
+
+                *state.sides[peer].active(dir) += 1;
+                Some(Ok(open(state)))
+            })
+            .await
+    }
+}
+
+impl web_transport_trait::Session for MemorySession {
+    type SendStream = MemorySendStream;
+    type RecvStream = MemoryRecvStream;
+    type Error = MemoryError;
+
+    async fn accept_uni(&self) -> Result<MemoryRecvStream, MemoryError> {
+        let side = self.side();
+        let id = self
+            .link()
+            .wait(|state| {
+                let side = &mut state.sides[side];
+                match side.uni.pop_front() {
+                    Some(id) => Some(Ok(id)),
+                    None => side.error.clone().map(Err),
+                }
+            })
+            .await?;
+
+        Ok(self.recv(id))
+    }
+
+    async fn accept_bi(&self) -> Result<(MemorySendStream, MemoryRecvStream), MemoryError> {
+        let side = self.side();
+        let (send, recv) = self
+            .link()
+            .wait(|state| {
+                let side = &mut state.sides[side];
+                match side.bi.pop_front() {
+                    Some(ids) => Some(Ok(ids)),
+                    None => side.error.clone().map(Err),
+                }
+            })
+            .await?;
+
+        Ok((self.send(send), self.recv(recv)))
+    }
+
+    async fn open_bi(&self) -> Result<(MemorySendStream, MemoryRecvStream), MemoryError> {
+        let peer = self.peer();
+        let (send, recv) = self
+            .open(Dir::Bi, |state| {
+                // Our sending half counts against the peer's limit until it drops the reader.
+                let send = state.open_pipe(Some((peer, Dir::Bi)));
+                let recv = state.open_pipe(None);
+                state.sides[peer].bi.push_back((recv, send));
+                (send, recv)
+            })
+            .await?;
+
+        Ok((self.send(send), self.recv(recv)))
+    }
+
+    async fn open_uni(&self) -> Result<MemorySendStream, MemoryError> {
+        let peer = self.peer();
+        let send = self
+            .open(Dir::Uni, |state| {
+                let send = state.open_pipe(Some((peer, Dir::Uni)));
+                state.sides[peer].uni.push_back(send);
+                send
+            })
+            .await?;
+
+        Ok(self.send(send))
+    }
+
+    fn send_datagram(&self, payload: Bytes) -> Result<(), MemoryError> {
+        let max = self.link().config.max_datagram_size;
+        if payload.len() > max {
+            return Err(MemoryError::DatagramTooLarge {
+                size: payload.len(),
+                max,
+            });
+        }
+
+        let queue = self.link().config.datagram_queue;
+        let (side, peer) = (self.side(), self.peer());
+        self.link().update(|state| {
+            if let Some(err) = &state.sides[side].error {
+                return Err(err.clone());
+            }
+
+            let peer = &mut state.sides[peer];
+            match peer.datagrams.len() < queue {
+                true => peer.datagrams.push_back(payload),
+                false => peer.datagrams_dropped += 1,
+            }
+            Ok(())
+        })
+    }
+
+    async fn recv_datagram(&self) -> Result<Bytes, MemoryError> {
+        let side = self.side();
+        self.link()
+            .wait(|state| {
+                let side = &mut state.sides[side];
+                match side.datagrams.pop_front() {
+                    Some(datagram) => Some(Ok(datagram)),
+                    None => side.error.clone().map(Err),
+                }
+            })
+            .await
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.link().config.max_datagram_size
+    }
+
+    fn protocol(&self) -> Option<&str> {
+        self.link().config.protocol.as_deref()
+    }
+
+    fn close(&self, code: u32, reason: &str) {
+        self.close_bytes(code, reason.as_bytes())
+    }
+
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        let side = self.side();
+        let reason = Bytes::copy_from_slice(reason);
+        self.link().update(|state| state.close(side, code, reason));
+    }
+
+    async fn closed(&self) -> MemoryError {
+        let side = self.side();
+        self.link()
+            .wait(|state| state.sides[side].error.clone())
+            .await
+    }
+
+    fn close_reason(&self) -> Option<Closed> {
+        let side = self.side();
+        self.link().state.lock().unwrap().sides[side].close.get()
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        Some(self.handle.id)
+    }
+
+    fn stats(&self) -> impl Stats {
+        let side = self.side();
+        let dropped = self.link().state.lock().unwrap().sides[side].datagrams_dropped;
+        MemoryStats {
+            datagrams_dropped: dropped,
+        }
+    }
+}
+
+// A snapshot of what an in-memory session tracks.
+struct MemoryStats {
+    datagrams_dropped: u64,
+}
+
+impl Stats for MemoryStats {
+    fn datagrams_dropped(&self) -> Option<u64> {
+        Some(self.datagrams_dropped)
+    }
+}
+
+/// The sending half of an in-memory stream.
+///
+/// Writes never block, since there's no flow control. Dropping the stream without
+/// [finish](web_transport_trait::SendStream::finish) resets it with code 0.
+pub struct MemorySendStream {
+    link: Arc<Link>,
+    side: usize,
+    id: u64,
+}
+
+impl MemorySendStream {
+    // Append to the pipe, unless the stream or the session is closed.
+    fn push(&self, chunk: Bytes) -> Result<(), MemoryError> {
+        let (side, id) = (self.side, self.id);
+        self.link.update(|state| {
+            if let Some(err) = &state.sides[side].error {
+                return Err(err.clone());
+            }
+
+            let pipe = state.pipes.get_mut(&id).expect("pipe held by its writer");
+            if let Some(code) = pipe.stop {
+                return Err(MemoryError::Stopped(code));
+            }
+            if pipe.fin || pipe.reset.is_some() {
+                return Err(MemoryError::StreamClosed);
+            }
+
+            if !chunk.is_empty() {
+                pipe.data.push_back(chunk);
+            }
+            Ok(())
+        })
+    }
+}
+
+impl web_transport_trait::SendStream for MemorySendStream {
+    type Error = MemoryError;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, MemoryError> {
+        self.push(Bytes::copy_from_slice(buf))?;
+        Ok(buf.len())
+    }
+
+    async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), MemoryError> {
+        self.push(chunk)
+    }
+
+    // Each pipe is its own queue, so there's nothing to schedule between streams.
+    fn set_priority(&mut self, _order: u8) {}
+
+    fn finish(&mut self) -> Result<(), MemoryError> {
+        let (side, id) = (self.side, self.id);
+        self.link.update(|state| {
+            if let Some(err) = &state.sides[side].error {
+                return Err(err.clone());
+            }
+
+            let pipe = state.pipes.get_mut(&id).expect("pipe held by its writer");
+            if pipe.fin || pipe.reset.is_some() {
+                return Err(MemoryError::StreamClosed);
+            }
+
+            pipe.fin = true;
+            Ok(())
+        })
+    }
+
+    fn reset(&mut self, code: u32) {
+        let id = self.id;
+        self.link.update(|state| {
+            let pipe = state.pipes.get_mut(&id).expect("pipe held by its writer");
+            if pipe.reset.is_none() && !pipe.fin_read {
+                // Anything unread is discarded, as with RESET_STREAM.
+                pipe.reset = Some(code);
+                pipe.data.clear();
+            }
+        });
+    }
+
+    async fn closed(&mut self) -> Result<(), MemoryError> {
+        let (side, id) = (self.side, self.id);
+        self.link
+            .wait(|state| {
+                let pipe = &state.pipes[&id];
+                if let Some(code) = pipe.stop {
+                    return Some(Err(MemoryError::Stopped(code)));
+                }
+                if pipe.reset.is_some() || pipe.fin_read || !pipe.reader {
+                    return Some(Ok(()));
+                }
+                state.sides[side].error.clone().map(Err)
+            })
+            .await
+    }
+}
+
+impl Drop for MemorySendStream {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.link.update(|state| {
+            let pipe = state.pipes.get_mut(&id).expect("pipe held by its writer");
+            if !pipe.fin && pipe.reset.is_none() {
+                pipe.reset = Some(0);
+                pipe.data.clear();
+            }
+            pipe.writer = false;
+            state.release(id);
+        });
+    }
+}
+
+/// The receiving half of an in-memory stream.
+///
+/// Dropping the stream before reading to the end stops it with code 0.
+pub struct MemoryRecvStream {
+    link: Arc<Link>,
+    side: usize,
+    id: u64,
+}
+
+impl MemoryRecvStream {
+    // Wait for data, taking what `take` returns from the front of the pipe.
+    async fn next<T>(
+        &mut self,
+        mut take: impl FnMut(&mut VecDeque<Bytes>) -> T,
+    ) -> Result<Option<T>, MemoryError> {
+        let (side, id) = (self.side, self.id);
+        self.link
+            .wait(|state| {
+                let pipe = state.pipes.get_mut(&id).expect("pipe held by its reader");
+                if let Some(code) = pipe.reset {
+                    return Some(Err(MemoryError::Reset(code)));
+                }
+                if pipe.stop.is_some() {
+                    return Some(Err(MemoryError::StreamClosed));
+                }
+                if !pipe.data.is_empty() {
+                    return Some(Ok(Some(take(&mut pipe.data))));
+                }
+                if pipe.fin {
+                    pipe.fin_read = true;
+                    return Some(Ok(None));
+                }
+                state.sides[side].error.clone().map(Err)
+            })
+            .await
+    }
+}
+
+impl web_transport_trait::RecvStream for MemoryRecvStream {
+    type Error = MemoryError;
+
+    async fn read(&mut self, dst: &mut [u8]) -> Result<Option<usize>, MemoryError> {
+        self.next(|data| {
+            let mut read = 0;
+            while read < dst.len() {
+                let Some(chunk) = data.front_mut() else {
+                    break;
+                };
+
+                let size = chunk.len().min(dst.len() - read);
+                dst[read..read + size].copy_from_slice(&chunk.split_to(size));
+                read += size;
+
+                if chunk.is_empty() {
+                    data.pop_front();
+                }
+            }
+            read
+        })
+        .await
+    }
+
+    async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, MemoryError> {
+        self.next(|data| {
+            let chunk = data.front_mut().expect("data is not empty");
+            if chunk.len() <= max {
+                return data.pop_front().unwrap();
+            }
+            chunk.split_to(max)
+        })
+        .await
+    }
+
+    async fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> Result<Option<Vec<Bytes>>, MemoryError> {
+        self.next(|data| {
+            let mut chunks = Vec::new();
+            let mut total = 0;
+            while let Some(chunk) = data.front_mut() {
+                if total >= max_total {
+                    break;
+                }
+
+                let size = chunk.len().min(max_total - total);
+                chunks.push(chunk.split_to(size));
+                total += size;
+
+                if chunk.is_empty() {
+                    data.pop_front();
+                }
+            }
+            chunks
+        })
+        .await
+    }
+
+    fn stop(&mut self, code: u32) {
+        let id = self.id;
+        self.link.update(|state| {
+            let pipe = state.pipes.get_mut(&id).expect("pipe held by its reader");
+            if pipe.stop.is_none() && !pipe.fin_read {
+                pipe.stop = Some(code);
+                pipe.data.clear();
+            }
+        });
+    }
+
+    async fn closed(&mut self) -> Result<(), MemoryError> {
+        let (side, id) = (self.side, self.id);
+        self.link
+            .wait(|state| {
+                let pipe = &state.pipes[&id];
+                if let Some(code) = pipe.reset {
+                    return Some(Err(MemoryError::Reset(code)));
+                }
+                if pipe.stop.is_some() || (pipe.fin && pipe.data.is_empty()) {
+                    return Some(Ok(()));
+                }
+                state.sides[side].error.clone().map(Err)
+            })
+            .await
+    }
+}
+
+impl Drop for MemoryRecvStream {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.link.update(|state| {
+            let pipe = state.pipes.get_mut(&id).expect("pipe held by its reader");
+            if pipe.stop.is_none() && pipe.reset.is_none() && !pipe.fin_read {
+                pipe.stop = Some(0);
+                pipe.data.clear();
+            }
+            pipe.reader = false;
+            state.release(id);
+        });
+    }
+}
@@ -0,0 +1,402 @@
+//! Record every session-level event to a capture file.
+//!
+//! Wrap a session with [Recorder::new] and use the wrapper in its place. Everything the
+//! application does, and everything it observes from the peer, is appended to the capture
+//! as it happens. Recording is best-effort: if the writer fails, a warning is logged and
+//! recording stops, but the session carries on.
+//!
+//! ```no_run
+//! # async fn run(session: impl web_transport_trait::Session) -> std::io::Result<()> {
+//! let file = std::fs::File::create("session.wtcap")?;
+//! let session = web_transport_mock::recorder::Recorder::new(session, file)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use bytes::{Buf, Bytes};
+use web_transport_trait::{
+    Closed, DatagramOptions, Error, MaybeSend, RecvStream, SendStream, Session, SessionId, Stats,
+    StreamOptions,
+};
+
+use crate::capture::{CaptureWriter, Event, Record};
+
+type Writer = CaptureWriter<Box<dyn Write + Send>>;
+
+struct Log {
+    start: Instant,
+    next_stream: AtomicU64,
+
+    // None once a write has failed.
+    writer: Mutex<Option<Writer>>,
+}
+
+impl Log {
+    fn record(&self, event: Event) {
+        let mut writer = self.writer.lock().unwrap();
+        let Some(out) = writer.as_mut() else {
+            return;
+        };
+
+        let record = Record {
+            at: self.start.elapsed(),
+            event,
+        };
+
+        if let Err(err) = out.write(&record) {
+            tracing::warn!(%err, "failed to write capture, recording stopped");
+            *writer = None;
+        }
+    }
+
+    fn next_stream(&self) -> u64 {
+        self.next_stream.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// A [Session] that records everything that happens on the session it wraps.
+pub struct Recorder<S> {
+    inner: S,
+    log: Arc<Log>,
+}
+
+impl<S: Session> Recorder<S> {
+    /// Wrap `session`, writing the capture to `out`.
+    ///
+    /// The header is written immediately, so an unwritable destination fails here.
+    pub fn new(session: S, out: impl Write + Send + 'static) -> io::Result<Self> {
+        let writer = CaptureWriter::new(Box::new(out) as Box<dyn Write + Send>)?;
+
+        Ok(Self {
+            inner: session,
+            log: Arc::new(Log {
+                start: Instant::now(),
+                next_stream: AtomicU64::new(0),
+                writer: Mutex::new(Some(writer)),
+            }),
+        })
+    }
+
+    /// The session being recorded.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Flush any buffered records to the writer.
+    pub fn flush(&self) -> io::Result<()> {
+        match self.log.writer.lock().unwrap().as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn send(&self, stream: u64, inner: S::SendStream) -> RecordedSendStream<S::SendStream> {
+        RecordedSendStream {
+            inner,
+            stream,
+            log: self.log.clone(),
+        }
+    }
+
+    fn recv(&self, stream: u64, inner: S::RecvStream) -> RecordedRecvStream<S::RecvStream> {
+        RecordedRecvStream {
+            inner,
+            stream,
+            log: self.log.clone(),
+        }
+    }
+}
+
+impl<S: Clone> Clone for Recorder<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            log: self.log.clone(),
+        }
+    }
+}
+
+impl<S: Session> Session for Recorder<S> {
+    type SendStream = RecordedSendStream<S::SendStream>;
+    type RecvStream = RecordedRecvStream<S::RecvStream>;
+    type Error = S::Error;
+
+    async fn accept_uni(&self) -> Result<Self::RecvStream, Self::Error> {
+        let recv = self.inner.accept_uni().await?;
+
+        let stream = self.log.next_stream();
+        self.log.record(Event::AcceptUni { stream });
+
+        Ok(self.recv(stream, recv))
+    }
+
+    async fn accept_bi(&self) -> Result<(Self::SendStream, Self::RecvStream), Self::Error> {
+        let (send, recv) = self.inner.accept_bi().await?;
+
+        let stream = self.log.next_stream();
+        self.log.record(Event::AcceptBi { stream });
+
+        Ok((self.send(stream, send), self.recv(stream, recv)))
+    }
+
+    async fn open_bi(&self) -> Result<(Self::SendStream, Self::RecvStream), Self::Error> {
+        let (send, recv) = self.inner.open_bi().await?;
+
+        let stream = self.log.next_stream();
+        self.log.record(Event::OpenBi { stream });
+
+        Ok((self.send(stream, send), self.recv(stream, recv)))
+    }
+
+    async fn open_uni(&self) -> Result<Self::SendStream, Self::Error> {
+        let send = self.inner.open_uni().await?;
+
+        let stream = self.log.next_stream();
+        self.log.record(Event::OpenUni { stream });
+
+        Ok(self.send(stream, send))
+    }
+
+    async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(Self::SendStream, Self::RecvStream), Self::Error> {
+        let (send, recv) = self.inner.open_bi_with(options).await?;
+
+        let stream = self.log.next_stream();
+        self.log.record(Event::OpenBi { stream });
+
+        Ok((self.send(stream, send), self.recv(stream, recv)))
+    }
+
+    async fn open_uni_with(&self, options: StreamOptions) -> Result<Self::SendStream, Self::Error> {
+        let send = self.inner.open_uni_with(options).await?;
+
+        let stream = self.log.next_stream();
+        self.log.record(Event::OpenUni { stream });
+
+        Ok(self.send(stream, send))
+    }
+
+    fn send_datagram(&self, payload: Bytes) -> Result<(), Self::Error> {
+        self.inner.send_datagram(payload.clone())?;
+        self.log.record(Event::DatagramSent { data: payload });
+        Ok(())
+    }
+
+    async fn send_datagram_wait(&self, payload: Bytes) -> Result<(), Self::Error> {
+        self.inner.send_datagram_wait(payload.clone()).await?;
+        self.log.record(Event::DatagramSent { data: payload });
+        Ok(())
+    }
+
+    fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), Self::Error> {
+        self.inner.send_datagram_with(payload.clone(), options)?;
+        self.log.record(Event::DatagramSent { data: payload });
+        Ok(())
+    }
+
+    async fn recv_datagram(&self) -> Result<Bytes, Self::Error> {
+        let data = self.inner.recv_datagram().await?;
+        self.log
+            .record(Event::DatagramReceived { data: data.clone() });
+        Ok(data)
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.inner.max_datagram_size()
+    }
+
+    fn datagrams_supported(&self) -> bool {
+        self.inner.datagrams_supported()
+    }
+
+    fn protocol(&self) -> Option<&str> {
+        self.inner.protocol()
+    }
+
+    fn close(&self, code: u32, reason: &str) {
+        self.log.record(Event::Close {
+            code,
+            reason: reason.to_string(),
+        });
+        self.inner.close(code, reason)
+    }
+
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        // The capture format stores text; the peer still gets the exact bytes.
+        self.log.record(Event::Close {
+            code,
+            reason: String::from_utf8_lossy(reason).into_owned(),
+        });
+        self.inner.close_bytes(code, reason)
+    }
+
+    fn close_reason(&self) -> Option<Closed> {
+        self.inner.close_reason()
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        self.inner.id()
+    }
+
+    async fn closed(&self) -> Self::Error {
+        let err = self.inner.closed().await;
+
+        let event = match err.session_error() {
+            Some((code, reason)) => Event::Closed {
+                code: Some(code),
+                reason,
+            },
+            None => Event::Closed {
+                code: None,
+                reason: err.to_string(),
+            },
+        };
+        self.log.record(event);
+
+        err
+    }
+
+    fn stats(&self) -> impl Stats {
+        self.inner.stats()
+    }
+
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn max_open_uni(&self) -> Option<u64> {
+        self.inner.max_open_uni()
+    }
+
+    fn remaining_uni(&self) -> Option<u64> {
+        self.inner.remaining_uni()
+    }
+
+    fn max_open_bi(&self) -> Option<u64> {
+        self.inner.max_open_bi()
+    }
+
+    fn remaining_bi(&self) -> Option<u64> {
+        self.inner.remaining_bi()
+    }
+}
+
+/// A [SendStream] opened through a [Recorder].
+pub struct RecordedSendStream<T> {
+    inner: T,
+    stream: u64,
+    log: Arc<Log>,
+}
+
+impl<T: SendStream> SendStream for RecordedSendStream<T> {
+    type Error = T::Error;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let size = self.inner.write(buf).await?;
+        self.log.record(Event::Write {
+            stream: self.stream,
+            data: Bytes::copy_from_slice(&buf[..size]),
+        });
+        Ok(size)
+    }
+
+    async fn write_buf<B: Buf + MaybeSend>(&mut self, buf: &mut B) -> Result<usize, Self::Error> {
+        // Go through `write` so the bytes are recorded before `buf` is advanced.
+        let size = self.write(buf.chunk()).await?;
+        buf.advance(size);
+        Ok(size)
+    }
+
+    async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), Self::Error> {
+        // Hand the chunk to the inner stream whole so its zero-copy path survives
+        // the wrapper; the recorded copy is just a refcount bump.
+        self.inner.write_chunk(chunk.clone()).await?;
+        self.log.record(Event::Write {
+            stream: self.stream,
+            data: chunk,
+        });
+        Ok(())
+    }
+
+    fn set_priority(&mut self, order: u8) {
+        self.inner.set_priority(order)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.inner.finish()?;
+        self.log.record(Event::Finish {
+            stream: self.stream,
+        });
+        Ok(())
+    }
+
+    fn reset(&mut self, code: u32) {
+        self.log.record(Event::Reset {
+            stream: self.stream,
+            code,
+        });
+        self.inner.reset(code)
+    }
+
+    async fn closed(&mut self) -> Result<(), Self::Error> {
+        self.inner.closed().await
+    }
+}
+
+/// A [RecvStream] accepted through a [Recorder].
+pub struct RecordedRecvStream<T> {
+    inner: T,
+    stream: u64,
+    log: Arc<Log>,
+}
+
+impl<T: RecvStream> RecvStream for RecordedRecvStream<T> {
+    type Error = T::Error;
+
+    async fn read(&mut self, dst: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        let stream = self.stream;
+
+        match self.inner.read(dst).await {
+            Ok(Some(size)) if size > 0 => {
+                self.log.record(Event::Read {
+                    stream,
+                    data: Bytes::copy_from_slice(&dst[..size]),
+                });
+                Ok(Some(size))
+            }
+            Ok(res) => {
+                self.log.record(Event::Fin { stream });
+                Ok(res)
+            }
+            Err(err) => {
+                if let Some(code) = err.stream_error() {
+                    self.log.record(Event::ResetReceived { stream, code });
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn stop(&mut self, code: u32) {
+        self.log.record(Event::Stop {
+            stream: self.stream,
+            code,
+        });
+        self.inner.stop(code)
+    }
+
+    async fn closed(&mut self) -> Result<(), Self::Error> {
+        self.inner.closed().await
+    }
+}
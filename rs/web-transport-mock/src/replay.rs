@@ -0,0 +1,177 @@
+//! Play the peer's side of a capture against a live session.
+//!
+//! A capture records one application's view of a session. [Replay] acts as the peer:
+//! it opens the streams the application accepted, writes what the application read,
+//! resets and closes where the peer did, and checks that the application writes what
+//! it wrote in the capture. Run the application under test against the other end of
+//! the session, over loopback for example, to reproduce the original run.
+//!
+//! Events the peer had no part in, like the application resetting its own stream or
+//! sending a datagram, are skipped.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use web_transport_trait::{RecvStream, SendStream, Session};
+
+use crate::capture::{CaptureError, CaptureReader, Event, Record};
+
+/// An error returned while replaying a capture.
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayError {
+    /// The session or one of its streams failed; the error types differ, so only the message is kept.
+    #[error("session error: {0}")]
+    Session(String),
+
+    #[error("capture refers to unknown stream {0}")]
+    UnknownStream(u64),
+
+    #[error("application diverged from the capture at record {index}")]
+    Diverged { index: usize },
+}
+
+impl ReplayError {
+    fn session(err: impl std::error::Error) -> Self {
+        Self::Session(err.to_string())
+    }
+}
+
+/// A capture loaded into memory, ready to be played against a [Session].
+#[derive(Clone, Debug)]
+pub struct Replay {
+    records: Vec<Record>,
+    realtime: bool,
+}
+
+impl Replay {
+    pub fn new(records: Vec<Record>) -> Self {
+        Self {
+            records,
+            realtime: false,
+        }
+    }
+
+    /// Load every record from a capture file.
+    pub fn read(input: impl Read) -> Result<Self, CaptureError> {
+        let records = CaptureReader::new(input)?.collect::<Result<_, _>>()?;
+        Ok(Self::new(records))
+    }
+
+    /// Wait until each record's original timestamp before playing it.
+    ///
+    /// Off by default, which plays the capture as fast as the application keeps up.
+    pub fn with_realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Play the peer's side of the capture on `session`.
+    ///
+    /// Returns once the capture is exhausted or the session was closed in it.
+    pub async fn run<S: Session>(&self, session: &S) -> Result<(), ReplayError> {
+        let start = tokio::time::Instant::now();
+
+        let mut send = HashMap::new();
+        let mut recv = HashMap::new();
+
+        for (index, record) in self.records.iter().enumerate() {
+            if self.realtime {
+                tokio::time::sleep_until(start + record.at).await;
+            }
+
+            match &record.event {
+                // The application opened a stream, so wait for it to show up.
+                Event::OpenBi { stream } => {
+                    let (s, r) = session.accept_bi().await.map_err(ReplayError::session)?;
+                    send.insert(*stream, s);
+                    recv.insert(*stream, r);
+                }
+                Event::OpenUni { stream } => {
+                    let r = session.accept_uni().await.map_err(ReplayError::session)?;
+                    recv.insert(*stream, r);
+                }
+
+                // The peer opened a stream, so open it for the application to accept.
+                Event::AcceptBi { stream } => {
+                    let (s, r) = session.open_bi().await.map_err(ReplayError::session)?;
+                    send.insert(*stream, s);
+                    recv.insert(*stream, r);
+                }
+                Event::AcceptUni { stream } => {
+                    let s = session.open_uni().await.map_err(ReplayError::session)?;
+                    send.insert(*stream, s);
+                }
+
+                // The application wrote, so it had better write the same thing again.
+                Event::Write { stream, data } => {
+                    let r = recv
+                        .get_mut(stream)
+                        .ok_or(ReplayError::UnknownStream(*stream))?;
+
+                    let mut buf = vec![0u8; data.len()];
+                    let mut pos = 0;
+                    while pos < buf.len() {
+                        match r
+                            .read(&mut buf[pos..])
+                            .await
+                            .map_err(ReplayError::session)?
+                        {
+                            Some(size) if size > 0 => pos += size,
+                            _ => return Err(ReplayError::Diverged { index }),
+                        }
+                    }
+
+                    if buf != data.as_ref() {
+                        return Err(ReplayError::Diverged { index });
+                    }
+                }
+
+                // The application read, fell off the end, or was reset, so the peer caused it.
+                Event::Read { stream, data } => {
+                    send.get_mut(stream)
+                        .ok_or(ReplayError::UnknownStream(*stream))?
+                        .write_all(data)
+                        .await
+                        .map_err(ReplayError::session)?;
+                }
+                Event::Fin { stream } => {
+                    send.get_mut(stream)
+                        .ok_or(ReplayError::UnknownStream(*stream))?
+                        .finish()
+                        .map_err(ReplayError::session)?;
+                }
+                Event::ResetReceived { stream, code } => {
+                    send.get_mut(stream)
+                        .ok_or(ReplayError::UnknownStream(*stream))?
+                        .reset(*code);
+                }
+
+                Event::DatagramReceived { data } => {
+                    session
+                        .send_datagram(data.clone())
+                        .map_err(ReplayError::session)?;
+                }
+
+                Event::Closed {
+                    code: Some(code),
+                    reason,
+                } => {
+                    session.close(*code, reason);
+                    return Ok(());
+                }
+                Event::Close { .. } | Event::Closed { code: None, .. } => return Ok(()),
+
+                Event::Finish { .. }
+                | Event::Reset { .. }
+                | Event::Stop { .. }
+                | Event::DatagramSent { .. } => {}
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+//! A conformance self-check that two endpoints run against each other.
+//!
+//! Call [run] on both ends of a session, one as [Role::Initiator] and the other as
+//! [Role::Responder]. The initiator drives a fixed script of streams, resets, stops,
+//! datagrams and a close; the responder plays along. Each side returns a [Report] of
+//! what it could verify from its end, so a new backend can be checked against a
+//! reference one (quinn, say) by running it on either side.
+//!
+//! ```no_run
+//! # async fn run(session: impl web_transport_trait::Session) {
+//! use web_transport_mock::selftest::{self, Role};
+//!
+//! let report = selftest::run(&session, Role::Initiator).await;
+//! println!("{report}");
+//! assert!(report.passed());
+//! # }
+//! ```
+//!
+//! The steps run in order over fresh streams, and each one is bounded by a timeout, so
+//! a backend that hangs fails that step instead of the whole run. A failed step can
+//! still leave the two sides out of step, so read the first failure first.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use bytes::Bytes;
+use web_transport_trait::{CloseSide, Error, RecvStream, SendStream, Session};
+
+/// How long any one step may take before it's failed.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the initiator waits for each datagram echo before resending.
+const DATAGRAM_RETRY: Duration = Duration::from_millis(250);
+
+/// Datagrams are unreliable, so the initiator resends this many times before giving up.
+const DATAGRAM_ATTEMPTS: usize = 8;
+
+/// The stream error code the initiator resets with.
+pub const RESET_CODE: u32 = 7;
+
+/// The stream error code the responder stops with.
+pub const STOP_CODE: u32 = 8;
+
+/// The session error code the initiator closes with.
+pub const CLOSE_CODE: u32 = 42;
+
+/// The reason the initiator closes with.
+pub const CLOSE_REASON: &str = "selftest done";
+
+/// Which end of the script to play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Opens every stream, sends the datagrams and closes the session.
+    Initiator,
+
+    /// Accepts what the initiator opens and echoes it back.
+    Responder,
+}
+
+/// The result of one step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    /// The step doesn't apply, for example datagrams when the peer doesn't accept them.
+    Skipped(String),
+}
+
+/// One step of the script and how it went.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// Everything one side verified.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub role: Role,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// True if no step failed. Skipped steps don't count against it.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| !matches!(check.outcome, Outcome::Failed(_)))
+    }
+
+    /// The steps that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.outcome, Outcome::Failed(_)))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "selftest ({:?})", self.role)?;
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed => writeln!(f, "  pass  {}", check.name)?,
+                Outcome::Failed(why) => writeln!(f, "  FAIL  {}: {why}", check.name)?,
+                Outcome::Skipped(why) => writeln!(f, "  skip  {}: {why}", check.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+type StepResult = Result<(), String>;
+
+fn fail(err: impl fmt::Display) -> String {
+    err.to_string()
+}
+
+fn expect(what: &str, got: &[u8], want: &[u8]) -> StepResult {
+    if got == want {
+        Ok(())
+    } else {
+        Err(format!(
+            "{what}: expected {:?}, got {:?}",
+            String::from_utf8_lossy(want),
+            String::from_utf8_lossy(got)
+        ))
+    }
+}
+
+/// Run the script as `role`, returning what this side verified.
+pub async fn run<S: Session>(session: &S, role: Role) -> Report {
+    let mut report = Report {
+        role,
+        checks: Vec::new(),
+    };
+
+    match role {
+        Role::Initiator => {
+            step(&mut report, "bidirectional echo", initiate_bi(session)).await;
+            step(&mut report, "unidirectional echo", initiate_uni(session)).await;
+            step(&mut report, "reset code", initiate_reset(session)).await;
+            step(&mut report, "stop code", initiate_stop(session)).await;
+            step(&mut report, "priorities", initiate_priorities(session)).await;
+
+            if session.max_datagram_size() == 0 {
+                skip(&mut report, "datagrams", "peer doesn't accept datagrams");
+            } else {
+                step(&mut report, "datagrams", initiate_datagrams(session)).await;
+            }
+
+            step(&mut report, "close", initiate_close(session)).await;
+        }
+        Role::Responder => {
+            step(&mut report, "bidirectional echo", respond_bi(session)).await;
+            step(&mut report, "unidirectional echo", respond_uni(session)).await;
+            step(&mut report, "reset code", respond_reset(session)).await;
+            step(&mut report, "stop code", respond_stop(session)).await;
+            step(&mut report, "priorities", respond_priorities(session)).await;
+
+            // Datagrams are echoed until the initiator closes, so both share one step.
+            step(&mut report, "close", respond_close(session)).await;
+        }
+    }
+
+    report
+}
+
+async fn step(report: &mut Report, name: &'static str, f: impl Future<Output = StepResult>) {
+    let outcome = match tokio::time::timeout(STEP_TIMEOUT, f).await {
+        Ok(Ok(())) => Outcome::Passed,
+        Ok(Err(why)) => Outcome::Failed(why),
+        Err(_) => Outcome::Failed(format!("timed out after {STEP_TIMEOUT:?}")),
+    };
+    report.checks.push(Check { name, outcome });
+}
+
+fn skip(report: &mut Report, name: &'static str, why: &str) {
+    report.checks.push(Check {
+        name,
+        outcome: Outcome::Skipped(why.to_string()),
+    });
+}
+
+async fn initiate_bi<S: Session>(session: &S) -> StepResult {
+    let (mut send, mut recv) = session.open_bi().await.map_err(fail)?;
+    send.write_all(b"bi ping").await.map_err(fail)?;
+    send.finish().map_err(fail)?;
+
+    let echo = recv.read_all().await.map_err(fail)?;
+    expect("echo", &echo, b"bi ping")
+}
+
+async fn respond_bi<S: Session>(session: &S) -> StepResult {
+    let (mut send, mut recv) = session.accept_bi().await.map_err(fail)?;
+    let data = recv.read_all().await.map_err(fail)?;
+    send.write_all(&data).await.map_err(fail)?;
+    send.finish().map_err(fail)?;
+    expect("request", &data, b"bi ping")
+}
+
+async fn initiate_uni<S: Session>(session: &S) -> StepResult {
+    let mut send = session.open_uni().await.map_err(fail)?;
+    send.write_all(b"uni ping").await.map_err(fail)?;
+    send.finish().map_err(fail)?;
+
+    let mut recv = session.accept_uni().await.map_err(fail)?;
+    let echo = recv.read_all().await.map_err(fail)?;
+    expect("echo", &echo, b"uni ping")
+}
+
+async fn respond_uni<S: Session>(session: &S) -> StepResult {
+    let mut recv = session.accept_uni().await.map_err(fail)?;
+    let data = recv.read_all().await.map_err(fail)?;
+
+    let mut send = session.open_uni().await.map_err(fail)?;
+    send.write_all(&data).await.map_err(fail)?;
+    send.finish().map_err(fail)?;
+    expect("request", &data, b"uni ping")
+}
+
+// The reset only goes out once the responder has acknowledged the data, so the stream
+// is known to have arrived rather than being discarded along with its header.
+async fn initiate_reset<S: Session>(session: &S) -> StepResult {
+    let (mut send, mut recv) = session.open_bi().await.map_err(fail)?;
+    send.write_all(b"reset").await.map_err(fail)?;
+
+    let mut ack = [0u8; 1];
+    match recv.read(&mut ack).await.map_err(fail)? {
+        Some(1) => {}
+        _ => return Err("responder hung up before acknowledging".to_string()),
+    }
+
+    send.reset(RESET_CODE);
+    Ok(())
+}
+
+async fn respond_reset<S: Session>(session: &S) -> StepResult {
+    let (mut send, mut recv) = session.accept_bi().await.map_err(fail)?;
+
+    let mut data = [0u8; 5];
+    let mut filled = 0;
+    while filled < data.len() {
+        match recv.read(&mut data[filled..]).await.map_err(fail)? {
+            Some(n) if n > 0 => filled += n,
+            _ => return Err("stream ended before the reset".to_string()),
+        }
+    }
+    expect("request", &data, b"reset")?;
+    send.write_all(b"!").await.map_err(fail)?;
+
+    match recv.read_all().await {
+        Ok(rest) => Err(format!(
+            "stream finished ({} bytes) instead of resetting",
+            rest.len()
+        )),
+        Err(err) if err.stream_error() == Some(RESET_CODE) => Ok(()),
+        Err(err) => Err(format!("expected reset code {RESET_CODE}, got {err}")),
+    }
+}
+
+async fn initiate_stop<S: Session>(session: &S) -> StepResult {
+    let mut send = session.open_uni().await.map_err(fail)?;
+    send.write_all(b"stop").await.map_err(fail)?;
+
+    // Keep the stream open; the responder stops it once the data arrives.
+    match send.closed().await {
+        Ok(()) => Err("stream closed cleanly instead of being stopped".to_string()),
+        Err(err) if err.stream_error() == Some(STOP_CODE) => Ok(()),
+        Err(err) => Err(format!("expected stop code {STOP_CODE}, got {err}")),
+    }
+}
+
+async fn respond_stop<S: Session>(session: &S) -> StepResult {
+    let mut recv = session.accept_uni().await.map_err(fail)?;
+
+    let mut data = [0u8; 4];
+    let mut filled = 0;
+    while filled < data.len() {
+        match recv.read(&mut data[filled..]).await.map_err(fail)? {
+            Some(n) if n > 0 => filled += n,
+            _ => return Err("stream ended before it was stopped".to_string()),
+        }
+    }
+
+    recv.stop(STOP_CODE);
+    expect("request", &data, b"stop")
+}
+
+// Priorities only influence scheduling, which can't be observed reliably from the
+// other end; this checks that prioritized streams still arrive intact.
+async fn initiate_priorities<S: Session>(session: &S) -> StepResult {
+    for priority in [1u8, 2] {
+        let mut send = session.open_uni().await.map_err(fail)?;
+        send.set_priority(priority);
+        send.write_all(&[priority; 64]).await.map_err(fail)?;
+        send.finish().map_err(fail)?;
+    }
+
+    Ok(())
+}
+
+async fn respond_priorities<S: Session>(session: &S) -> StepResult {
+    let mut seen = Vec::new();
+    for _ in 0..2 {
+        let mut recv = session.accept_uni().await.map_err(fail)?;
+        let data = recv.read_all().await.map_err(fail)?;
+
+        match data.first() {
+            Some(&priority) if data.len() == 64 && data.iter().all(|&b| b == priority) => {
+                seen.push(priority)
+            }
+            _ => return Err(format!("corrupt stream of {} bytes", data.len())),
+        }
+    }
+
+    seen.sort_unstable();
+    if seen != [1, 2] {
+        return Err(format!("expected streams 1 and 2, got {seen:?}"));
+    }
+
+    Ok(())
+}
+
+async fn initiate_datagrams<S: Session>(session: &S) -> StepResult {
+    let payload = Bytes::from_static(b"datagram ping");
+
+    for _ in 0..DATAGRAM_ATTEMPTS {
+        session.send_datagram(payload.clone()).map_err(fail)?;
+
+        match tokio::time::timeout(DATAGRAM_RETRY, session.recv_datagram()).await {
+            Ok(Ok(echo)) => return expect("echo", &echo, &payload),
+            Ok(Err(err)) => return Err(fail(err)),
+            Err(_) => continue,
+        }
+    }
+
+    Err(format!("no echo after {DATAGRAM_ATTEMPTS} attempts"))
+}
+
+async fn initiate_close<S: Session>(session: &S) -> StepResult {
+    session.close(CLOSE_CODE, CLOSE_REASON);
+
+    // Closing again is ignored; the first close wins.
+    session.close(CLOSE_CODE + 1, "closed twice");
+    session.closed().await;
+
+    match session.close_reason() {
+        Some(closed)
+            if closed.side == CloseSide::Local
+                && closed.code == CLOSE_CODE
+                && closed.reason == CLOSE_REASON =>
+        {
+            Ok(())
+        }
+        other => Err(format!(
+            "expected close_reason to be our close {CLOSE_CODE} {CLOSE_REASON:?}, got {other:?}"
+        )),
+    }
+}
+
+// Echo datagrams until the initiator closes, then check the close it sent.
+async fn respond_close<S: Session>(session: &S) -> StepResult {
+    let err = loop {
+        tokio::select! {
+            res = session.recv_datagram() => match res {
+                Ok(datagram) => {
+                    // Best effort, like the datagram itself; the initiator retries.
+                    let _ = session.send_datagram(datagram);
+                }
+                Err(err) => break err,
+            },
+            err = session.closed() => break err,
+        }
+    };
+
+    match err.session_error() {
+        Some((CLOSE_CODE, reason)) if reason == CLOSE_REASON => Ok(()),
+        Some((code, reason)) => Err(format!(
+            "expected close {CLOSE_CODE} {CLOSE_REASON:?}, got {code} {reason:?}"
+        )),
+        None => Err(format!("expected an application close, got {err}")),
+    }
+}
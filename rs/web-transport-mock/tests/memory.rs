@@ -0,0 +1,169 @@
+//! The in-memory pair behaves like a real session: the conformance script passes, and
+//! stream limits, datagram limits, read limits and close codes are enforced.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use web_transport_mock::{
+    memory::{self, MemoryBuilder, MemoryError},
+    selftest::{self, Role},
+};
+use web_transport_trait::{
+    CloseSide, Error, ErrorKind, ReadToEndError, RecvStream, SendStream, Session, Stats,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn memory_passes_selftest() {
+    let (client, server) = memory::pair();
+
+    let (initiator, responder) = tokio::join!(
+        selftest::run(&client, Role::Initiator),
+        selftest::run(&server, Role::Responder),
+    );
+
+    assert!(initiator.passed(), "{initiator}");
+    assert!(responder.passed(), "{responder}");
+}
+
+#[tokio::test]
+async fn opening_blocks_at_the_stream_limit() {
+    let (client, server) = MemoryBuilder::new().with_max_uni_streams(1).pair();
+
+    let mut first = client.open_uni().await.unwrap();
+    first.write_all(b"first").await.unwrap();
+    first.finish().unwrap();
+
+    // The server hasn't released the first stream, so there's no credit for a second.
+    let blocked = tokio::time::timeout(Duration::from_millis(50), client.open_uni()).await;
+    assert!(blocked.is_err(), "opened past the limit");
+
+    let mut recv = server.accept_uni().await.unwrap();
+    assert_eq!(recv.read_all().await.unwrap(), "first");
+    drop(recv);
+    drop(first);
+
+    tokio::time::timeout(TIMEOUT, client.open_uni())
+        .await
+        .expect("still blocked after the stream closed")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn datagrams_are_limited() {
+    let (client, server) = MemoryBuilder::new()
+        .with_max_datagram_size(4)
+        .with_datagram_queue(1)
+        .pair();
+
+    let err = client
+        .send_datagram(Bytes::from_static(b"too big"))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+    // The second overflows the server's queue and is dropped.
+    client.send_datagram(Bytes::from_static(b"one")).unwrap();
+    client.send_datagram(Bytes::from_static(b"two")).unwrap();
+    assert_eq!(server.recv_datagram().await.unwrap(), "one");
+    assert_eq!(server.stats().datagrams_dropped(), Some(1));
+}
+
+#[tokio::test]
+async fn close_reaches_the_peer_and_its_streams() {
+    let (client, server) = MemoryBuilder::new().with_protocol("moq").pair();
+    assert_eq!(server.protocol(), Some("moq"));
+
+    let (_send, mut recv) = client.open_bi().await.unwrap();
+    let accepted = server.accept_bi().await.unwrap();
+
+    server.close_bytes(9, b"\xffbye");
+
+    let err = tokio::time::timeout(TIMEOUT, client.closed())
+        .await
+        .unwrap();
+    assert_eq!(
+        err.session_error_bytes(),
+        Some((9, Bytes::from_static(b"\xffbye")))
+    );
+    assert!(matches!(server.closed().await, MemoryError::LocallyClosed));
+
+    // Pending reads fail with the session's error, and nothing new can be opened.
+    let err = recv.read_all().await.unwrap_err();
+    assert_eq!(err.session_error().map(|(code, _)| code), Some(9));
+    assert!(client.open_uni().await.is_err());
+    drop(accepted);
+
+    // Dropping every clone of a session closes it with code 0.
+    let (client, server) = memory::pair();
+    drop(server);
+    let err = tokio::time::timeout(TIMEOUT, client.closed())
+        .await
+        .unwrap();
+    assert_eq!(err.session_error(), Some((0, String::new())));
+}
+
+#[tokio::test]
+async fn first_close_wins_and_records_the_race() {
+    let (client, server) = memory::pair();
+    assert_eq!(client.close_reason(), None);
+
+    client.close(1, "first");
+    client.close(2, "second");
+
+    let closed = client.close_reason().unwrap();
+    assert_eq!(closed.side, CloseSide::Local);
+    assert_eq!(closed.code, 1);
+    assert_eq!(closed.reason, "first");
+    assert!(!closed.raced);
+
+    // Closing after the peer did keeps the peer's reason, but both ends see the race.
+    server.close(3, "late");
+
+    let closed = server.close_reason().unwrap();
+    assert_eq!(closed.side, CloseSide::Remote);
+    assert_eq!(closed.code, 1);
+    assert_eq!(closed.reason, "first");
+    assert!(closed.raced);
+    assert!(client.close_reason().unwrap().raced);
+
+    // Still there once every handle is gone but one, and not raced by the drop.
+    let (client, server) = memory::pair();
+    client.close(4, "bye");
+    drop(server);
+    assert!(!client.close_reason().unwrap().raced);
+}
+
+#[test]
+fn each_end_has_its_own_id() {
+    let (client, server) = memory::pair();
+    let (other, _) = memory::pair();
+
+    let id = client.id().unwrap();
+    assert_eq!(client.clone().id(), Some(id));
+    assert_ne!(server.id(), Some(id));
+    assert_ne!(other.id(), Some(id));
+}
+
+#[tokio::test]
+async fn read_to_end_strict_rejects_long_streams() {
+    let (client, server) = memory::pair();
+
+    for _ in 0..3 {
+        let mut send = client.open_uni().await.unwrap();
+        send.write_all(b"hello world").await.unwrap();
+        send.finish().unwrap();
+    }
+
+    let mut recv = server.accept_uni().await.unwrap();
+    let err = recv.read_to_end_strict(5).await.unwrap_err();
+    assert!(matches!(err, ReadToEndError::TooLong { limit: 5 }), "{err}");
+    assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+    // A stream that ends exactly at the limit is whole.
+    let mut recv = server.accept_uni().await.unwrap();
+    assert_eq!(recv.read_to_end_strict(11).await.unwrap(), "hello world");
+
+    let mut recv = server.accept_uni().await.unwrap();
+    assert_eq!(recv.read_to_end(5).await.unwrap(), "hello");
+}
@@ -0,0 +1,117 @@
+//! Record a session over loopback, then replay the capture against the same application.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use qmux::{transport::Stream, Config, Session, Version};
+use web_transport_mock::{
+    capture::Event,
+    recorder::Recorder,
+    replay::{Replay, ReplayError},
+};
+use web_transport_trait::{RecvStream as _, SendStream as _, Session as _};
+
+/// A writer the test can read back after the recorder is done with it.
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn pair() -> (Session, Session) {
+    let (a, b) = tokio::io::duplex(64 * 1024);
+    let config = Config::new(Version::QMux01);
+
+    let server = tokio::spawn({
+        let config = config.clone();
+        async move {
+            let transport = Stream::new(b, config.version, config.max_record_size);
+            Session::accept(transport, config).await.unwrap()
+        }
+    });
+
+    let transport = Stream::new(a, config.version, config.max_record_size);
+    let client = Session::connect(transport, config).await.unwrap();
+    (client, server.await.unwrap())
+}
+
+/// The application under test: answer one request, then hang up.
+async fn app<S: web_transport_trait::Session>(session: S, answer: &'static [u8]) {
+    let (mut send, mut recv) = session.accept_bi().await.unwrap();
+    let request = recv.read_all().await.unwrap();
+    assert_eq!(request.as_ref(), b"ping");
+
+    send.write_all(answer).await.unwrap();
+    send.finish().unwrap();
+
+    session.close(7, "done");
+}
+
+#[tokio::test]
+async fn record_then_replay() {
+    // Record the application talking to a real peer.
+    let (client, server) = pair().await;
+    let capture = Shared::default();
+    let server = Recorder::new(server, capture.clone()).unwrap();
+    let app_task = tokio::spawn(app(server, b"pong"));
+
+    let (mut send, mut recv) = client.open_bi().await.unwrap();
+    send.write_all(b"ping").await.unwrap();
+    send.finish().unwrap();
+    assert_eq!(recv.read_all().await.unwrap().as_ref(), b"pong");
+    app_task.await.unwrap();
+
+    let file = capture.0.lock().unwrap().clone();
+    let replay = Replay::read(file.as_slice()).unwrap();
+
+    let events: Vec<_> = replay.records().iter().map(|r| &r.event).collect();
+    assert!(matches!(events[0], Event::AcceptBi { stream: 0 }));
+    assert!(matches!(
+        events.last().unwrap(),
+        Event::Close { code: 7, .. }
+    ));
+
+    // Replay the peer's side against a fresh copy of the application.
+    let (client, server) = pair().await;
+    let app_task = tokio::spawn(app(server, b"pong"));
+    replay.run(&client).await.unwrap();
+    app_task.await.unwrap();
+
+    // An application that answers differently is caught.
+    let (client, server) = pair().await;
+    let app_task = tokio::spawn(app(server, b"pang"));
+    let err = replay.run(&client).await.unwrap_err();
+    assert!(matches!(err, ReplayError::Diverged { .. }), "{err}");
+    app_task.await.unwrap();
+}
+
+#[tokio::test]
+async fn write_chunk_is_recorded() {
+    let (client, server) = pair().await;
+    let capture = Shared::default();
+    let client = Recorder::new(client, capture.clone()).unwrap();
+
+    let (mut send, _recv) = client.open_bi().await.unwrap();
+    send.write_chunk(bytes::Bytes::from_static(b"chunk"))
+        .await
+        .unwrap();
+    send.finish().unwrap();
+
+    let (_send, mut recv) = server.accept_bi().await.unwrap();
+    assert_eq!(recv.read_all().await.unwrap().as_ref(), b"chunk");
+
+    let file = capture.0.lock().unwrap().clone();
+    let replay = Replay::read(file.as_slice()).unwrap();
+    assert!(replay.records().iter().any(|r| matches!(
+        &r.event,
+        Event::Write { data, .. } if data.as_ref() == b"chunk"
+    )));
+}
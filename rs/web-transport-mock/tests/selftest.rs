@@ -0,0 +1,34 @@
+//! The conformance script passes between two qmux endpoints.
+
+use qmux::{transport::Stream, Config, Session, Version};
+use web_transport_mock::selftest::{self, Role};
+
+async fn pair() -> (Session, Session) {
+    let (a, b) = tokio::io::duplex(64 * 1024);
+    let config = Config::new(Version::QMux01);
+
+    let server = tokio::spawn({
+        let config = config.clone();
+        async move {
+            let transport = Stream::new(b, config.version, config.max_record_size);
+            Session::accept(transport, config).await.unwrap()
+        }
+    });
+
+    let transport = Stream::new(a, config.version, config.max_record_size);
+    let client = Session::connect(transport, config).await.unwrap();
+    (client, server.await.unwrap())
+}
+
+#[tokio::test]
+async fn qmux_passes_selftest() {
+    let (client, server) = pair().await;
+
+    let (initiator, responder) = tokio::join!(
+        selftest::run(&client, Role::Initiator),
+        selftest::run(&server, Role::Responder),
+    );
+
+    assert!(initiator.passed(), "{initiator}");
+    assert!(responder.passed(), "{responder}");
+}
@@ -22,12 +22,12 @@ use tokio::sync::Mutex;
 // crash.
 
 fn session_error_to_close_info(err: &web_transport_quinn::SessionError) -> NapiCloseInfo {
-    match err {
+    match err.without_history() {
         web_transport_quinn::SessionError::WebTransportError(
             web_transport_quinn::WebTransportError::Closed(code, reason),
         ) => NapiCloseInfo {
             close_code: *code,
-            reason: reason.clone(),
+            reason: String::from_utf8_lossy(reason).into_owned(),
         },
         other => NapiCloseInfo {
             close_code: 0,
@@ -95,7 +95,9 @@ impl NapiClient {
         let mut request = web_transport_quinn::proto::ConnectRequest::new(url);
         if let Some(opts) = options {
             if let Some(protocols) = opts.protocols {
-                request = request.with_protocols(protocols);
+                request = request
+                    .try_with_protocols(protocols)
+                    .map_err(|e| Error::from_reason(e.to_string()))?;
             }
         }
         let session = client
@@ -1,12 +1,10 @@
-use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
-use crate::proto::ConnectRequest;
+use crate::proto::{ConnectRequest, ConnectTarget};
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 use noq::crypto::rustls::QuicClientConfig;
 use rustls::{client::danger::ServerCertVerifier, pki_types::CertificateDer};
 use tokio::net::lookup_host;
-use url::Host;
 
 use crate::crypto;
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
@@ -218,32 +216,25 @@ impl Client {
     ) -> Result<Session, ClientError> {
         let request = request.into();
 
-        let port = request.url.port().unwrap_or(443);
+        // Reject a URL we can't connect to before doing any network I/O.
+        let target = ConnectTarget::new(&request.url)?;
+        let host = target.server_name();
 
-        // TODO error on username:password in host
-        let (host, remote) = match request
-            .url
-            .host()
-            .ok_or_else(|| ClientError::InvalidDnsName("".to_string()))?
-        {
-            Host::Domain(domain) => {
-                let domain = domain.to_string();
+        let remote = match target.addr() {
+            Some(remote) => remote,
+            None => {
                 // Look up the DNS entry.
-                let mut remotes = match lookup_host((domain.clone(), port)).await {
+                let mut remotes = match lookup_host((host.clone(), target.port)).await {
                     Ok(remotes) => remotes,
-                    Err(_) => return Err(ClientError::InvalidDnsName(domain)),
+                    Err(_) => return Err(ClientError::InvalidDnsName(host)),
                 };
 
                 // Return the first entry.
-                let remote = match remotes.next() {
+                match remotes.next() {
                     Some(remote) => remote,
-                    None => return Err(ClientError::InvalidDnsName(domain)),
-                };
-
-                (domain, remote)
+                    None => return Err(ClientError::InvalidDnsName(host)),
+                }
             }
-            Host::Ipv4(ipv4) => (ipv4.to_string(), SocketAddr::new(IpAddr::V4(ipv4), port)),
-            Host::Ipv6(ipv6) => (ipv6.to_string(), SocketAddr::new(IpAddr::V6(ipv6), port)),
         };
 
         // Connect to the server using the addr we just resolved.
@@ -3,8 +3,12 @@ use std::ops::Deref;
 use web_transport_proto::{ConnectRequest, ConnectResponse, VarInt};
 
 use thiserror::Error;
+use web_transport_trait::ErrorKind;
+
+use crate::error::{connection_kind, noq_read_kind, noq_write_kind};
 
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ConnectError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -28,6 +32,23 @@ pub enum ConnectError {
     ProtocolMismatch(String),
 }
 
+impl ConnectError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::ProtoError(web_transport_proto::ConnectError::WrongStatus(_)) => {
+                ErrorKind::Rejected
+            }
+            Self::ProtoError(_) | Self::ProtocolMismatch(_) => ErrorKind::Protocol,
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::ReadError(e) => noq_read_kind(e),
+            Self::WriteError(e) => noq_write_kind(e),
+            Self::ErrorStatus(_) => ErrorKind::Rejected,
+        }
+    }
+}
+
 /// An HTTP/3 CONNECT request/response for establishing a WebTransport session.
 pub struct Connecting {
     // The request that was sent by the client.
@@ -1,11 +1,14 @@
 use std::sync::Arc;
 
+use bytes::Bytes;
 use thiserror::Error;
+use web_transport_trait::{ErrorKind, SessionId};
 
-use crate::{ConnectError, SettingsError};
+use crate::{proto::UrlError, ConnectError, SettingsError};
 
 /// An error returned when connecting to a WebTransport endpoint.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ClientError {
     #[error("unexpected end of stream")]
     UnexpectedEnd,
@@ -31,13 +34,37 @@ pub enum ClientError {
     #[error("invalid DNS name: {0}")]
     InvalidDnsName(String),
 
+    #[error("invalid url: {0}")]
+    InvalidUrl(#[from] UrlError),
+
     #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
     #[error("rustls error: {0}")]
     Rustls(#[from] rustls::Error),
 }
 
+impl ClientError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Connection(e) => connection_kind(e),
+            Self::WriteError(e) => noq_write_kind(e),
+            Self::ReadError(e) => noq_read_kind(e),
+            Self::SettingsError(e) => e.kind(),
+            Self::HttpError(e) => e.kind(),
+            Self::NoqError(noq::ConnectError::EndpointStopping) => ErrorKind::LocallyClosed,
+            Self::NoqError(_) | Self::InvalidDnsName(_) | Self::InvalidUrl(_) => {
+                ErrorKind::InvalidInput
+            }
+            #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+            Self::Rustls(_) => ErrorKind::InvalidInput,
+        }
+    }
+}
+
 /// An errors returned by [`crate::Session`], split based on if they are underlying QUIC errors or WebTransport errors.
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum SessionError {
     #[error("connection error: {0}")]
     ConnectionError(noq::ConnectionError),
@@ -47,6 +74,13 @@ pub enum SessionError {
 
     #[error("send datagram error: {0}")]
     SendDatagramError(#[from] noq::SendDatagramError),
+
+    /// The close error of a session, tagged with its [id](crate::Session::id).
+    ///
+    /// Displays as `session <id>: <error>`, matching the `session` field of its tracing events.
+    /// Use [SessionError::without_id] to match on the underlying error.
+    #[error("session {1}: {0}")]
+    InSession(Box<SessionError>, SessionId),
 }
 
 impl From<noq::ConnectionError> for SessionError {
@@ -54,11 +88,7 @@ impl From<noq::ConnectionError> for SessionError {
         match &e {
             noq::ConnectionError::ApplicationClosed(close) => {
                 match web_transport_proto::error_from_http3(close.error_code.into_inner()) {
-                    Some(code) => WebTransportError::Closed(
-                        code,
-                        String::from_utf8_lossy(&close.reason).into_owned(),
-                    )
-                    .into(),
+                    Some(code) => WebTransportError::Closed(code, close.reason.clone()).into(),
                     None => SessionError::ConnectionError(e),
                 }
             }
@@ -67,11 +97,39 @@ impl From<noq::ConnectionError> for SessionError {
     }
 }
 
+impl SessionError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::WebTransportError(e) => e.kind(),
+            Self::SendDatagramError(e) => match e {
+                noq::SendDatagramError::UnsupportedByPeer | noq::SendDatagramError::Disabled => {
+                    ErrorKind::Unsupported
+                }
+                noq::SendDatagramError::TooLarge => ErrorKind::TooLarge,
+                noq::SendDatagramError::ConnectionLost(e) => connection_kind(e),
+            },
+            Self::InSession(e, _) => e.kind(),
+        }
+    }
+
+    /// The error without the [session id](SessionError::InSession) attached.
+    pub fn without_id(&self) -> &SessionError {
+        match self {
+            Self::InSession(e, _) => e.without_id(),
+            e => e,
+        }
+    }
+}
+
 /// An error that can occur when reading/writing the WebTransport stream header.
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum WebTransportError {
-    #[error("closed: code={0} reason={1}")]
-    Closed(u32, String),
+    /// The session was closed by the peer. The reason is kept verbatim, as it needn't be UTF-8.
+    #[error("closed: code={} reason={}", .0, String::from_utf8_lossy(.1))]
+    Closed(u32, Bytes),
 
     #[error("unknown session")]
     UnknownSession,
@@ -83,8 +141,22 @@ pub enum WebTransportError {
     WriteError(#[from] noq::WriteError),
 }
 
+impl WebTransportError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Closed(..) => ErrorKind::SessionClosed,
+            Self::UnknownSession => ErrorKind::Protocol,
+            Self::ReadError(noq::ReadExactError::FinishedEarly(_)) => ErrorKind::UnexpectedEnd,
+            Self::ReadError(noq::ReadExactError::ReadError(e)) => noq_read_kind(e),
+            Self::WriteError(e) => noq_write_kind(e),
+        }
+    }
+}
+
 /// An error when writing to [`crate::SendStream`]. Similar to [`noq::WriteError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum WriteError {
     #[error("STOP_SENDING: {0}")]
     Stopped(u32),
@@ -115,8 +187,21 @@ impl From<noq::WriteError> for WriteError {
     }
 }
 
+impl WriteError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Stopped(_) => ErrorKind::StreamStopped,
+            Self::InvalidStopped(_) => ErrorKind::Protocol,
+            Self::SessionError(e) => e.kind(),
+            Self::ClosedStream => ErrorKind::LocallyClosed,
+        }
+    }
+}
+
 /// An error when reading from [`crate::RecvStream`]. Similar to [`noq::ReadError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ReadError {
     #[error("session error: {0}")]
     SessionError(#[from] SessionError),
@@ -147,8 +232,21 @@ impl From<noq::ReadError> for ReadError {
     }
 }
 
+impl ReadError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::SessionError(e) => e.kind(),
+            Self::Reset(_) => ErrorKind::StreamReset,
+            Self::InvalidReset(_) => ErrorKind::Protocol,
+            Self::ClosedStream => ErrorKind::LocallyClosed,
+        }
+    }
+}
+
 /// An error returned by [`crate::RecvStream::read_exact`]. Similar to [`noq::ReadExactError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ReadExactError {
     #[error("finished early")]
     FinishedEarly(usize),
@@ -166,21 +264,33 @@ impl From<noq::ReadExactError> for ReadExactError {
     }
 }
 
+impl ReadExactError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::FinishedEarly(_) => ErrorKind::UnexpectedEnd,
+            Self::ReadError(e) => e.kind(),
+        }
+    }
+}
+
 /// An error returned by [`crate::RecvStream::read_to_end`]. Similar to [`noq::ReadToEndError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ReadToEndError {
-    #[error("too long")]
-    TooLong,
+    #[error("stream longer than the limit of {limit} bytes")]
+    TooLong { limit: usize },
 
     #[error("read error: {0}")]
     ReadError(#[from] ReadError),
 }
 
-impl From<noq::ReadToEndError> for ReadToEndError {
-    fn from(e: noq::ReadToEndError) -> Self {
-        match e {
-            noq::ReadToEndError::TooLong => ReadToEndError::TooLong,
-            noq::ReadToEndError::Read(e) => ReadToEndError::ReadError(e.into()),
+impl ReadToEndError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TooLong { .. } => ErrorKind::TooLarge,
+            Self::ReadError(e) => e.kind(),
         }
     }
 }
@@ -198,6 +308,7 @@ impl From<noq::ClosedStream> for ClosedStream {
 
 /// An error returned when receiving a new WebTransport session.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ServerError {
     #[error("unexpected end of stream")]
     UnexpectedEnd,
@@ -225,6 +336,23 @@ pub enum ServerError {
     Rustls(#[from] rustls::Error),
 }
 
+impl ServerError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Connection(e) => connection_kind(e),
+            Self::WriteError(e) => noq_write_kind(e),
+            Self::ReadError(e) => noq_read_kind(e),
+            Self::SettingsError(e) => e.kind(),
+            Self::ConnectError(e) => e.kind(),
+            Self::IoError(_) => ErrorKind::Io,
+            #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+            Self::Rustls(_) => ErrorKind::InvalidInput,
+        }
+    }
+}
+
 // #[derive(Clone, Error, Debug)]
 // pub enum SendDatagramError {
 //     #[error("Unsupported peer")]
@@ -253,12 +381,28 @@ pub enum ServerError {
 
 impl web_transport_trait::Error for SessionError {
     fn session_error(&self) -> Option<(u32, String)> {
-        if let SessionError::WebTransportError(WebTransportError::Closed(code, reason)) = self {
-            return Some((*code, reason.to_string()));
+        if let SessionError::WebTransportError(WebTransportError::Closed(code, reason)) =
+            self.without_id()
+        {
+            return Some((*code, String::from_utf8_lossy(reason).into_owned()));
+        }
+
+        None
+    }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let SessionError::WebTransportError(WebTransportError::Closed(code, reason)) =
+            self.without_id()
+        {
+            return Some((*code, reason.clone()));
         }
 
         None
     }
+
+    fn kind(&self) -> ErrorKind {
+        SessionError::kind(self)
+    }
 }
 
 impl web_transport_trait::Error for WriteError {
@@ -270,12 +414,24 @@ impl web_transport_trait::Error for WriteError {
         None
     }
 
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let WriteError::SessionError(e) = self {
+            return e.session_error_bytes();
+        }
+
+        None
+    }
+
     fn stream_error(&self) -> Option<u32> {
         match self {
             WriteError::Stopped(code) => Some(*code),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        WriteError::kind(self)
+    }
 }
 
 impl web_transport_trait::Error for ReadError {
@@ -287,10 +443,54 @@ impl web_transport_trait::Error for ReadError {
         None
     }
 
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let ReadError::SessionError(e) = self {
+            return e.session_error_bytes();
+        }
+
+        None
+    }
+
     fn stream_error(&self) -> Option<u32> {
         match self {
             ReadError::Reset(code) => Some(*code),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        ReadError::kind(self)
+    }
+}
+
+pub(crate) fn connection_kind(err: &noq::ConnectionError) -> ErrorKind {
+    match err {
+        noq::ConnectionError::ApplicationClosed(_)
+        | noq::ConnectionError::ConnectionClosed(_)
+        | noq::ConnectionError::Reset => ErrorKind::SessionClosed,
+        noq::ConnectionError::LocallyClosed => ErrorKind::LocallyClosed,
+        noq::ConnectionError::TimedOut => ErrorKind::TimedOut,
+        noq::ConnectionError::VersionMismatch | noq::ConnectionError::TransportError(_) => {
+            ErrorKind::Protocol
+        }
+        noq::ConnectionError::CidsExhausted => ErrorKind::Other,
+    }
+}
+
+pub(crate) fn noq_write_kind(err: &noq::WriteError) -> ErrorKind {
+    match err {
+        noq::WriteError::Stopped(_) => ErrorKind::StreamStopped,
+        noq::WriteError::ClosedStream => ErrorKind::LocallyClosed,
+        noq::WriteError::ConnectionLost(e) => connection_kind(e),
+        noq::WriteError::ZeroRttRejected => ErrorKind::Other,
+    }
+}
+
+pub(crate) fn noq_read_kind(err: &noq::ReadError) -> ErrorKind {
+    match err {
+        noq::ReadError::Reset(_) => ErrorKind::StreamReset,
+        noq::ReadError::ClosedStream => ErrorKind::LocallyClosed,
+        noq::ReadError::ConnectionLost(e) => connection_kind(e),
+        noq::ReadError::ZeroRttRejected => ErrorKind::Other,
+    }
 }
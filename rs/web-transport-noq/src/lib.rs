@@ -48,6 +48,9 @@ use settings::*;
 // Required to access web_transport_noq::proto::ConnectError wrapped in ClientError
 pub use connect::ConnectError;
 
+/// A backend-independent classification returned by each error's `kind()`.
+pub use web_transport_trait::ErrorKind;
+
 /// The HTTP/3 ALPN is required when negotiating a QUIC connection.
 pub const ALPN: &str = "h3";
 
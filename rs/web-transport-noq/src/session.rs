@@ -9,8 +9,10 @@ use std::{
     time::Duration,
 };
 
-use bytes::{Bytes, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use tracing::Instrument;
+use web_transport_trait::{CloseRecord, Closed, SessionId};
 
 use crate::{
     proto::{ConnectRequest, ConnectResponse, Frame, StreamUni, VarInt},
@@ -30,6 +32,9 @@ use crate::{
 pub struct Session {
     conn: noq::Connection,
 
+    // Identifies the session in logs, as the `session` span around its background tasks.
+    id: SessionId,
+
     // The session ID, as determined by the stream ID of the connect request.
     session_id: Option<VarInt>,
 
@@ -54,6 +59,9 @@ pub struct Session {
     // Uses OnceLock for set-once, first-writer-wins semantics with lock-free reads.
     error: Arc<OnceLock<SessionError>>,
 
+    // The first close code and reason from either side, kept for the trait's close_reason().
+    close_record: CloseRecord,
+
     // The request sent by the client.
     request: ConnectRequest,
 
@@ -79,12 +87,14 @@ impl Session {
         session_id.encode(&mut header_datagram);
 
         let error: Arc<OnceLock<SessionError>> = Arc::new(OnceLock::new());
+        let close_record = CloseRecord::new();
 
         // Accept logic is stateful, so use an Arc<Mutex> to share it.
         let accept = SessionAccept::new(conn.clone(), session_id, error.clone());
 
         let this = Self {
             conn,
+            id: SessionId::next(),
             accept: Some(Arc::new(Mutex::new(accept))),
             session_id: Some(session_id),
             header_uni,
@@ -93,13 +103,15 @@ impl Session {
             settings: Some(Arc::new(settings)),
             connect_send: Arc::new(Mutex::new(Some(connect.send))),
             error: error.clone(),
+            close_record: close_record.clone(),
             request: connect.request.clone(),
             response: connect.response.clone(),
         };
 
         // Run a background task to read capsules from the CONNECT recv stream.
         let conn2 = this.conn.clone();
-        tokio::spawn(Self::run_recv(conn2, connect.recv, error));
+        let recv = Self::run_recv(conn2, connect.recv, error, close_record);
+        tokio::spawn(recv.instrument(session_span(this.id)));
 
         this
     }
@@ -110,10 +122,16 @@ impl Session {
         conn: noq::Connection,
         recv: noq::RecvStream,
         error: Arc<OnceLock<SessionError>>,
+        close_record: CloseRecord,
     ) {
         let close_info = Self::read_capsules(recv).await;
         let code = close_info.as_ref().map_or(0, |(c, _)| *c);
 
+        // Recorded even if close() got there first, which marks the race.
+        if let Some((code, reason)) = &close_info {
+            close_record.remote(*code, reason);
+        }
+
         let http3_code: noq::VarInt = web_transport_proto::error_to_http3(code)
             .try_into()
             .unwrap();
@@ -126,7 +144,7 @@ impl Session {
                 if error.set(err.into()).is_err() {
                     return;
                 }
-                conn.close(http3_code, reason.as_bytes());
+                conn.close(http3_code, &reason);
             }
             None => {
                 let err = noq::ConnectionError::LocallyClosed.into();
@@ -141,7 +159,7 @@ impl Session {
     // Keep reading capsules from the CONNECT recv stream until it's closed.
     // Returns Some((code, reason)) if a CloseWebTransportSession capsule was received,
     // or None if the stream closed without a capsule.
-    async fn read_capsules(recv: noq::RecvStream) -> Option<(u32, String)> {
+    async fn read_capsules(recv: noq::RecvStream) -> Option<(u32, Bytes)> {
         let mut reader = web_transport_proto::Http3CapsuleReader::new(recv);
         loop {
             match reader.read().await {
@@ -149,6 +167,21 @@ impl Session {
                     code,
                     reason,
                 })) => return Some((code, reason)),
+                Ok(Some(web_transport_proto::Capsule::DrainWebTransportSession)) => {
+                    tracing::debug!("peer is draining the session");
+                }
+                // Session-level flow control isn't enforced by this backend.
+                Ok(Some(
+                    capsule @ (web_transport_proto::Capsule::WtMaxData { .. }
+                    | web_transport_proto::Capsule::WtMaxStreams { .. }
+                    | web_transport_proto::Capsule::WtDataBlocked { .. }
+                    | web_transport_proto::Capsule::WtStreamsBlocked { .. }),
+                )) => {
+                    tracing::debug!(?capsule, "ignoring flow control capsule");
+                }
+                Ok(Some(web_transport_proto::Capsule::ReconnectHint { url })) => {
+                    tracing::debug!(%url, "ignoring reconnect hint");
+                }
                 Ok(Some(web_transport_proto::Capsule::Grease { .. })) => {}
                 Ok(Some(web_transport_proto::Capsule::Unknown { typ, payload })) => {
                     tracing::warn!(%typ, size = payload.len(), "unknown capsule");
@@ -186,7 +219,7 @@ impl Session {
     /// Accept a new unidirectional stream. See [`noq::Connection::accept_uni`].
     pub async fn accept_uni(&self) -> Result<RecvStream, SessionError> {
         if let Some(accept) = &self.accept {
-            poll_fn(|cx| accept.lock().unwrap().poll_accept_uni(cx))
+            poll_accept(accept, |accept, cx| accept.poll_accept_uni(cx))
                 .await
                 .map_err(|e| self.map_error(e))
         } else {
@@ -202,7 +235,7 @@ impl Session {
     /// Accept a new bidirectional stream. See [`noq::Connection::accept_bi`].
     pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), SessionError> {
         if let Some(accept) = &self.accept {
-            poll_fn(|cx| accept.lock().unwrap().poll_accept_bi(cx))
+            poll_accept(accept, |accept, cx| accept.poll_accept_bi(cx))
                 .await
                 .map_err(|e| self.map_error(e))
         } else {
@@ -354,21 +387,29 @@ impl Session {
     /// The capsule write and connection close happen asynchronously in a spawned task.
     /// Callers should `await` [`Session::closed()`] to ensure the capsule has been
     /// delivered. Session operations will fail once the QUIC connection is closed.
+    ///
+    /// Only the first close takes effect, whether it was ours or the peer's; later calls
+    /// are ignored. [`web_transport_trait::Session::close_reason`] returns the one that won.
     pub fn close(&self, code: u32, reason: &[u8]) {
         // Record the local close error. First writer wins — if the background
         // task already set a remote close error, or close() was already called,
         // this is a no-op.
         let err = SessionError::ConnectionError(noq::ConnectionError::LocallyClosed);
         if self.error.set(err).is_err() {
+            // If the peer closed first, note that this close crossed it.
+            if self.close_record.get().is_some() {
+                self.close_record.local(code, reason);
+            }
             return;
         }
+        self.close_record.local(code, reason);
 
         if self.session_id.is_some() {
             // Take the send stream for the capsule write.
             let send = self.connect_send.lock().unwrap().take();
 
             if let Some(send) = send {
-                let reason = String::from_utf8_lossy(reason).into_owned();
+                let reason = Bytes::copy_from_slice(reason);
                 let conn = self.conn.clone();
                 let capsule =
                     web_transport_proto::Capsule::CloseWebTransportSession { code, reason };
@@ -378,9 +419,10 @@ impl Session {
                     .unwrap_or(Duration::from_millis(100));
                 let timeout = (rtt * 3).max(Duration::from_millis(100));
 
-                tokio::spawn(async move {
-                    Self::close_with_capsule(conn, send, capsule, code, timeout).await;
-                });
+                tokio::spawn(
+                    Self::close_with_capsule(conn, send, capsule, code, timeout)
+                        .instrument(session_span(self.id)),
+                );
             }
         } else {
             // Raw QUIC mode: no capsule needed.
@@ -404,6 +446,10 @@ impl Session {
         // Encode the capsule, then wrap it in an HTTP/3 DATA frame.
         // In HTTP/3, capsule data is carried inside DATA frames on the CONNECT
         // stream (RFC 9297 Section 3.2).
+        web_transport_proto::log_frame(
+            web_transport_proto::Direction::Sent,
+            web_transport_proto::WireFrame::Capsule(&capsule),
+        );
         let mut capsule_bytes = Vec::new();
         capsule.encode(&mut capsule_bytes);
 
@@ -441,19 +487,27 @@ impl Session {
     /// Wait until the session is closed, returning the error. See [`noq::Connection::closed`].
     ///
     /// If the peer sent a `CloseWebTransportSession` capsule, the returned error will be
-    /// [`WebTransportError::Closed`] with the code and reason from the capsule.
+    /// [`WebTransportError::Closed`] with the code and reason from the capsule, tagged with
+    /// the session's [id](Self::id). See [`SessionError::InSession`].
     ///
     /// Unlike [`noq::Connection::closed`], this does **not** return early when
     /// [`close()`](Self::close) has been called. It waits for the underlying QUIC
     /// connection to shut down, ensuring the `CloseWebTransportSession` capsule has
     /// been delivered. Use [`close_reason()`](Self::close_reason) for a non-blocking check.
     pub async fn closed(&self) -> SessionError {
-        self.map_error(self.conn.closed().await)
+        self.tag_close(self.conn.closed().await)
     }
 
     /// Return why the session was closed, or None if it's not closed. See [`noq::Connection::close_reason`].
+    ///
+    /// Use [`web_transport_trait::Session::close_reason`] for the code and reason of the first close instead.
     pub fn close_reason(&self) -> Option<SessionError> {
-        self.conn.close_reason().map(|e| self.map_error(e))
+        self.conn.close_reason().map(|e| self.tag_close(e))
+    }
+
+    // Tag the close error with the session's id.
+    fn tag_close(&self, e: noq::ConnectionError) -> SessionError {
+        SessionError::InSession(Box::new(self.map_error(e)), self.id)
     }
 
     /// Replace connection-level errors with the stored session error if available.
@@ -490,6 +544,7 @@ impl Session {
     ) -> Self {
         Self {
             conn,
+            id: SessionId::next(),
             session_id: None,
             header_uni: Default::default(),
             header_bi: Default::default(),
@@ -498,6 +553,7 @@ impl Session {
             settings: None,
             connect_send: Arc::new(Mutex::new(None)),
             error: Arc::new(OnceLock::new()),
+            close_record: CloseRecord::new(),
             request: request.into(),
             response: response.into(),
         }
@@ -507,6 +563,14 @@ impl Session {
         &self.request
     }
 
+    /// The identifier this session logs with. See [SessionId].
+    ///
+    /// Every tracing event from the session's background tasks is inside a `session`
+    /// span with this `id`, so log it with your own events to correlate the two.
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
     pub fn response(&self) -> &ConnectResponse {
         &self.response
     }
@@ -526,6 +590,11 @@ impl Session {
     }
 }
 
+// The span around a session's background tasks, so their events carry its ID.
+fn session_span(id: SessionId) -> tracing::Span {
+    tracing::info_span!("session", %id)
+}
+
 impl Deref for Session {
     type Target = noq::Connection;
 
@@ -552,9 +621,84 @@ impl Eq for Session {}
 type AcceptUni = dyn Stream<Item = Result<noq::RecvStream, noq::ConnectionError>> + Send;
 type AcceptBi =
     dyn Stream<Item = Result<(noq::SendStream, noq::RecvStream), noq::ConnectionError>> + Send;
-type PendingUni = dyn Future<Output = Result<(StreamUni, noq::RecvStream), SessionError>> + Send;
-type PendingBi =
-    dyn Future<Output = Result<Option<(noq::SendStream, noq::RecvStream)>, SessionError>> + Send;
+type PendingUni =
+    dyn Future<Output = Result<(StreamUni, noq::RecvStream, Header), SessionError>> + Send;
+type PendingBi = dyn Future<Output = Result<Option<(noq::SendStream, noq::RecvStream, Header)>, SessionError>>
+    + Send;
+
+// Reads a stream header a chunk at a time instead of a byte at a time, keeping any
+// payload that arrived alongside it so the first read doesn't need another wakeup.
+#[derive(Default)]
+struct Header {
+    // Received bytes not yet consumed by the header.
+    buf: Bytes,
+}
+
+impl Header {
+    async fn read_varint(&mut self, recv: &mut noq::RecvStream) -> Result<VarInt, SessionError> {
+        loop {
+            let mut cursor = &self.buf[..];
+            if let Ok(v) = VarInt::decode(&mut cursor) {
+                self.buf.advance(self.buf.len() - cursor.len());
+                return Ok(v);
+            }
+
+            let chunk = recv
+                .read_chunk(usize::MAX)
+                .await
+                .ok()
+                .flatten()
+                .ok_or(WebTransportError::UnknownSession)?;
+            self.buf = match self.buf.is_empty() {
+                true => chunk,
+                false => [&self.buf[..], &chunk[..]].concat().into(),
+            };
+        }
+    }
+
+    fn into_recv(self, recv: noq::RecvStream, error: Arc<OnceLock<SessionError>>) -> RecvStream {
+        RecvStream::new(recv, error).with_buffered(self.buf)
+    }
+}
+
+// Poll the accept state shared by every clone of a session until `poll` is ready.
+//
+// The shared accept futures only remember the waker of whoever polled them last.
+// If that caller is dropped while waiting, the others are woken to take over,
+// otherwise they'd stall until something unrelated woke them.
+async fn poll_accept<T>(
+    accept: &Mutex<SessionAccept>,
+    mut poll: impl FnMut(&mut SessionAccept, &mut Context<'_>) -> Poll<T>,
+) -> T {
+    let mut waiting = Waiting {
+        accept,
+        waker: None,
+    };
+
+    poll_fn(|cx| {
+        let res = poll(&mut accept.lock().unwrap(), cx);
+        waiting.waker = res.is_pending().then(|| cx.waker().clone());
+        res
+    })
+    .await
+}
+
+// A pending accept call, handing off to the other callers if dropped.
+struct Waiting<'a> {
+    accept: &'a Mutex<SessionAccept>,
+    waker: Option<Waker>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            // Skip the hand-off if another caller panicked while holding the lock.
+            if let Ok(mut accept) = self.accept.lock() {
+                accept.abandon(&waker);
+            }
+        }
+    }
+}
 
 // Logic just for accepting streams, which is annoying because of the stream header.
 pub struct SessionAccept {
@@ -615,6 +759,17 @@ impl SessionAccept {
         }
     }
 
+    // A caller waiting with `waker` went away. The accept futures may only know its
+    // waker, so wake everyone else to poll them again.
+    fn abandon(&mut self, waker: &Waker) {
+        self.uni_wakers.retain(|w| !w.will_wake(waker));
+        self.bi_wakers.retain(|w| !w.will_wake(waker));
+
+        for waker in self.uni_wakers.drain(..).chain(self.bi_wakers.drain(..)) {
+            waker.wake();
+        }
+    }
+
     // This is poll-based because we accept and decode streams in parallel.
     // In async land I would use tokio::JoinSet, but that requires a runtime.
     // It's better to use FuturesUnordered instead because it's agnostic.
@@ -642,7 +797,7 @@ impl SessionAccept {
             }
 
             // Poll the list of pending streams.
-            let (typ, recv) = match self.pending_uni.poll_next_unpin(cx) {
+            let (typ, recv, header) = match self.pending_uni.poll_next_unpin(cx) {
                 Poll::Ready(Some(Ok(res))) => res,
                 Poll::Ready(Some(Err(err))) => {
                     // Ignore the error, the stream was probably reset early.
@@ -660,7 +815,7 @@ impl SessionAccept {
             // Decide if we keep looping based on the type.
             match typ {
                 StreamUni::WEBTRANSPORT => {
-                    let recv = RecvStream::new(recv, self.error.clone());
+                    let recv = header.into_recv(recv, self.error.clone());
                     for waker in self.uni_wakers.drain(..) {
                         waker.wake();
                     }
@@ -684,25 +839,22 @@ impl SessionAccept {
     async fn decode_uni(
         mut recv: noq::RecvStream,
         expected_session: VarInt,
-    ) -> Result<(StreamUni, noq::RecvStream), SessionError> {
+    ) -> Result<(StreamUni, noq::RecvStream, Header), SessionError> {
+        let mut header = Header::default();
+
         // Read the VarInt at the start of the stream.
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
-        let typ = StreamUni(typ);
+        let typ = StreamUni(header.read_varint(&mut recv).await?);
 
         if typ == StreamUni::WEBTRANSPORT {
             // Read the session_id and validate it
-            let session_id = VarInt::read(&mut recv)
-                .await
-                .map_err(|_| WebTransportError::UnknownSession)?;
+            let session_id = header.read_varint(&mut recv).await?;
             if session_id != expected_session {
                 return Err(WebTransportError::UnknownSession.into());
             }
         }
 
         // We need to keep a reference to the qpack streams if the endpoint (incorrectly) creates them, so return everything.
-        Ok((typ, recv))
+        Ok((typ, recv, header))
     }
 
     pub fn poll_accept_bi(
@@ -744,10 +896,10 @@ impl SessionAccept {
                 }
             };
 
-            if let Some((send, recv)) = res {
+            if let Some((send, recv, header)) = res {
                 // Wrap the streams in our own types for correct error codes.
                 let send = SendStream::new(send, self.error.clone());
-                let recv = RecvStream::new(recv, self.error.clone());
+                let recv = header.into_recv(recv, self.error.clone());
                 for waker in self.bi_wakers.drain(..) {
                     waker.wake();
                 }
@@ -763,24 +915,22 @@ impl SessionAccept {
         send: noq::SendStream,
         mut recv: noq::RecvStream,
         expected_session: VarInt,
-    ) -> Result<Option<(noq::SendStream, noq::RecvStream)>, SessionError> {
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
+    ) -> Result<Option<(noq::SendStream, noq::RecvStream, Header)>, SessionError> {
+        let mut header = Header::default();
+
+        let typ = header.read_varint(&mut recv).await?;
         if Frame(typ) != Frame::WEBTRANSPORT {
             tracing::debug!(?typ, "ignoring unknown bidirectional stream");
             return Ok(None);
         }
 
         // Read the session ID and validate it.
-        let session_id = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
+        let session_id = header.read_varint(&mut recv).await?;
         if session_id != expected_session {
             return Err(WebTransportError::UnknownSession.into());
         }
 
-        Ok(Some((send, recv)))
+        Ok(Some((send, recv, header)))
     }
 }
 
@@ -829,6 +979,10 @@ impl web_transport_trait::Stats for SessionStats {
             None
         }
     }
+
+    fn congestion_window(&self) -> Option<u64> {
+        self.path.map(|path| path.cwnd)
+    }
 }
 
 impl web_transport_trait::Session for Session {
@@ -856,14 +1010,30 @@ impl web_transport_trait::Session for Session {
         Self::close(self, code, reason.as_bytes());
     }
 
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        Self::close(self, code, reason);
+    }
+
     async fn closed(&self) -> Self::Error {
         Self::closed(self).await
     }
 
+    fn close_reason(&self) -> Option<Closed> {
+        self.close_record.get()
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        Some(self.id)
+    }
+
     fn send_datagram(&self, data: Bytes) -> Result<(), Self::Error> {
         Self::send_datagram(self, data)
     }
 
+    async fn send_datagram_wait(&self, data: Bytes) -> Result<(), Self::Error> {
+        Self::send_datagram_wait(self, data).await
+    }
+
     async fn recv_datagram(&self) -> Result<Bytes, Self::Error> {
         Self::read_datagram(self).await
     }
@@ -880,4 +1050,10 @@ impl web_transport_trait::Session for Session {
     fn stats(&self) -> SessionStats {
         Self::stats(self)
     }
+
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        self.conn
+            .path(noq::PathId::ZERO)
+            .and_then(|path| path.remote_address().ok())
+    }
 }
@@ -1,8 +1,12 @@
 use futures::try_join;
 
 use thiserror::Error;
+use web_transport_trait::ErrorKind;
+
+use crate::error::{connection_kind, noq_read_kind, noq_write_kind};
 
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum SettingsError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -10,8 +14,8 @@ pub enum SettingsError {
     #[error("protocol error: {0}")]
     ProtoError(#[from] web_transport_proto::SettingsError),
 
-    #[error("WebTransport is not supported")]
-    WebTransportUnsupported,
+    #[error("WebTransport is not supported: {0}")]
+    WebTransportUnsupported(web_transport_proto::UnsupportedSettings),
 
     #[error("connection error")]
     ConnectionError(#[from] noq::ConnectionError),
@@ -23,6 +27,20 @@ pub enum SettingsError {
     WriteError(#[from] noq::WriteError),
 }
 
+impl SettingsError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::ProtoError(_) => ErrorKind::Protocol,
+            Self::WebTransportUnsupported(_) => ErrorKind::Unsupported,
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::ReadError(e) => noq_read_kind(e),
+            Self::WriteError(e) => noq_write_kind(e),
+        }
+    }
+}
+
 pub struct Settings {
     // A reference to the send/recv stream, so we don't close it until dropped.
     #[allow(dead_code)]
@@ -50,7 +68,9 @@ impl Settings {
         tracing::debug!(?settings, "received SETTINGS frame");
 
         if settings.supports_webtransport() == 0 {
-            return Err(SettingsError::WebTransportUnsupported);
+            return Err(SettingsError::WebTransportUnsupported(
+                web_transport_proto::UnsupportedSettings { settings },
+            ));
         }
 
         Ok(recv)
@@ -2,16 +2,44 @@ use std::sync::Arc;
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use url::Url;
 
-use crate::{Frame, VarInt, VarIntUnexpectedEnd, MAX_FRAME_SIZE};
+use crate::{log_frame, Direction, Frame, VarInt, VarIntUnexpectedEnd, WireFrame, MAX_FRAME_SIZE};
 
 // CloseWebTransportSession capsule type (draft-ietf-webtrans-http3-06).
 const CLOSE_WEBTRANSPORT_SESSION_TYPE: u64 = 0x2843;
 
+// DrainWebTransportSession capsule type (draft-ietf-webtrans-http3-06).
+const DRAIN_WEBTRANSPORT_SESSION_TYPE: u64 = 0x78ae;
+
+// Session-level flow control capsule types (draft-ietf-webtrans-http3-13).
+const WT_MAX_DATA_TYPE: u64 = 0x190b4d3d;
+const WT_MAX_STREAMS_BIDI_TYPE: u64 = 0x190b4d3f;
+const WT_MAX_STREAMS_UNI_TYPE: u64 = 0x190b4d40;
+const WT_DATA_BLOCKED_TYPE: u64 = 0x190b4d41;
+const WT_STREAMS_BLOCKED_BIDI_TYPE: u64 = 0x190b4d43;
+const WT_STREAMS_BLOCKED_UNI_TYPE: u64 = 0x190b4d44;
+
+// ReconnectHint capsule type, a vendor extension that other implementations ignore.
+const RECONNECT_HINT_TYPE: u64 = 0x6d6f7101;
+
+/// A capsule on the CONNECT stream (RFC 9297).
+///
+/// The close reason is raw bytes: the wire doesn't require UTF-8, and relaying it verbatim
+/// matters more than decoding it. Use `String::from_utf8_lossy` to display it.
+///
+/// `ReconnectHint` is a vendor extension asking the peer to reconnect to a URL, usually
+/// sent by a server that is draining. Other implementations ignore it as an unknown type.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Capsule {
-    CloseWebTransportSession { code: u32, reason: String },
+    CloseWebTransportSession { code: u32, reason: Bytes },
+    DrainWebTransportSession,
+    WtMaxData { max: u64 },
+    WtMaxStreams { bidi: bool, max: u64 },
+    WtDataBlocked { limit: u64 },
+    WtStreamsBlocked { bidi: bool, limit: u64 },
     Grease { num: u64 },
+    ReconnectHint { url: Url },
     Unknown { typ: VarInt, payload: Bytes },
 }
 
@@ -52,17 +80,23 @@ impl Capsule {
                     return Err(CapsuleError::MessageTooLong);
                 }
 
-                let mut message_bytes = vec![0u8; message_len];
-                payload.copy_to_slice(&mut message_bytes);
-
-                let error_message =
-                    String::from_utf8(message_bytes).map_err(|_| CapsuleError::InvalidUtf8)?;
+                let error_message = payload.copy_to_bytes(message_len);
 
                 Ok(Self::CloseWebTransportSession {
                     code: error_code,
                     reason: error_message,
                 })
             }
+            DRAIN_WEBTRANSPORT_SESSION_TYPE => {
+                // The payload is empty, but skip anything a future draft may add.
+                payload.advance(payload.remaining());
+                Ok(Self::DrainWebTransportSession)
+            }
+            RECONNECT_HINT_TYPE => {
+                let url = payload.copy_to_bytes(payload.remaining());
+                Self::decode_reconnect_hint(&url)
+            }
+            typ if Self::is_flow(typ) => Self::decode_flow(typ, &mut payload),
             _ => {
                 let mut payload_bytes = vec![0u8; payload.remaining()];
                 payload.copy_to_slice(&mut payload_bytes);
@@ -78,6 +112,14 @@ impl Capsule {
     ///
     /// Returns `Ok(None)` if the stream is cleanly closed (EOF before any bytes).
     pub async fn read<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Option<Self>, CapsuleError> {
+        let capsule = Self::read_raw(stream).await?;
+        if let Some(capsule) = &capsule {
+            log_frame(Direction::Received, WireFrame::Capsule(capsule));
+        }
+        Ok(capsule)
+    }
+
+    async fn read_raw<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Option<Self>, CapsuleError> {
         let typ = match VarInt::read_optional(stream).await {
             Ok(Some(v)) => v,
             Ok(None) => return Ok(None), // Clean EOF
@@ -119,14 +161,16 @@ impl Capsule {
                 }
 
                 let error_code = data.get_u32();
-                let error_message =
-                    String::from_utf8(data.to_vec()).map_err(|_| CapsuleError::InvalidUtf8)?;
+                let error_message = Bytes::copy_from_slice(data);
 
                 Ok(Some(Self::CloseWebTransportSession {
                     code: error_code,
                     reason: error_message,
                 }))
             }
+            DRAIN_WEBTRANSPORT_SESSION_TYPE => Ok(Some(Self::DrainWebTransportSession)),
+            RECONNECT_HINT_TYPE => Self::decode_reconnect_hint(&buf).map(Some),
+            typ if Self::is_flow(typ) => Self::decode_flow(typ, &mut buf.as_slice()).map(Some),
             _ => Ok(Some(Self::Unknown {
                 typ,
                 payload: Bytes::from(buf),
@@ -153,7 +197,34 @@ impl Capsule {
                 buf.put_u32(*error_code);
 
                 // Encode the error message
-                buf.put_slice(error_message.as_bytes());
+                buf.put_slice(error_message);
+            }
+            Self::DrainWebTransportSession => {
+                VarInt::from_u64(DRAIN_WEBTRANSPORT_SESSION_TYPE)
+                    .unwrap()
+                    .encode(buf);
+
+                // Drain capsules have zero-length payload
+                VarInt::from_u32(0).encode(buf);
+            }
+            Self::WtMaxData { max: value }
+            | Self::WtMaxStreams { max: value, .. }
+            | Self::WtDataBlocked { limit: value }
+            | Self::WtStreamsBlocked { limit: value, .. } => {
+                let typ = match self {
+                    Self::WtMaxData { .. } => WT_MAX_DATA_TYPE,
+                    Self::WtMaxStreams { bidi: true, .. } => WT_MAX_STREAMS_BIDI_TYPE,
+                    Self::WtMaxStreams { bidi: false, .. } => WT_MAX_STREAMS_UNI_TYPE,
+                    Self::WtDataBlocked { .. } => WT_DATA_BLOCKED_TYPE,
+                    Self::WtStreamsBlocked { bidi: true, .. } => WT_STREAMS_BLOCKED_BIDI_TYPE,
+                    _ => WT_STREAMS_BLOCKED_UNI_TYPE,
+                };
+                VarInt::from_u64(typ).unwrap().encode(buf);
+
+                // The payload is a single varint, saturated like any other limit.
+                let value = VarInt::from_u64(*value).unwrap_or(VarInt::MAX);
+                VarInt::from_u32(value.size() as u32).encode(buf);
+                value.encode(buf);
             }
             Self::Grease { num } => {
                 // Generate grease type: 0x29 * N + 0x17
@@ -168,6 +239,14 @@ impl Capsule {
                 // Grease capsules have zero-length payload
                 VarInt::from_u32(0).encode(buf);
             }
+            Self::ReconnectHint { url } => {
+                VarInt::from_u64(RECONNECT_HINT_TYPE).unwrap().encode(buf);
+
+                // The payload is the URL, without a length since the capsule has one.
+                let url = url.as_str();
+                VarInt::try_from(url.len()).unwrap().encode(buf);
+                buf.put_slice(url.as_bytes());
+            }
             Self::Unknown { typ, payload } => {
                 // Encode the capsule type
                 typ.encode(buf);
@@ -181,7 +260,59 @@ impl Capsule {
         }
     }
 
+    fn is_flow(typ: u64) -> bool {
+        matches!(
+            typ,
+            WT_MAX_DATA_TYPE
+                | WT_MAX_STREAMS_BIDI_TYPE
+                | WT_MAX_STREAMS_UNI_TYPE
+                | WT_DATA_BLOCKED_TYPE
+                | WT_STREAMS_BLOCKED_BIDI_TYPE
+                | WT_STREAMS_BLOCKED_UNI_TYPE
+        )
+    }
+
+    // Each flow control capsule carries exactly one varint.
+    fn decode_flow<B: Buf>(typ: u64, payload: &mut B) -> Result<Self, CapsuleError> {
+        let value = VarInt::decode(payload)
+            .map_err(|_| CapsuleError::UnexpectedEnd)?
+            .into_inner();
+        if payload.has_remaining() {
+            return Err(CapsuleError::InvalidLength);
+        }
+
+        Ok(match typ {
+            WT_MAX_DATA_TYPE => Self::WtMaxData { max: value },
+            WT_MAX_STREAMS_BIDI_TYPE => Self::WtMaxStreams {
+                bidi: true,
+                max: value,
+            },
+            WT_MAX_STREAMS_UNI_TYPE => Self::WtMaxStreams {
+                bidi: false,
+                max: value,
+            },
+            WT_DATA_BLOCKED_TYPE => Self::WtDataBlocked { limit: value },
+            WT_STREAMS_BLOCKED_BIDI_TYPE => Self::WtStreamsBlocked {
+                bidi: true,
+                limit: value,
+            },
+            _ => Self::WtStreamsBlocked {
+                bidi: false,
+                limit: value,
+            },
+        })
+    }
+
+    // The payload is an absolute URL in UTF-8.
+    fn decode_reconnect_hint(payload: &[u8]) -> Result<Self, CapsuleError> {
+        let url = std::str::from_utf8(payload).map_err(|_| CapsuleError::InvalidUtf8)?;
+        let url = Url::parse(url).map_err(CapsuleError::InvalidUrl)?;
+        Ok(Self::ReconnectHint { url })
+    }
+
     pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), CapsuleError> {
+        log_frame(Direction::Sent, WireFrame::Capsule(self));
+
         let mut buf = BytesMut::new();
         self.encode(&mut buf);
         stream.write_all_buf(&mut buf).await?;
@@ -212,9 +343,15 @@ pub enum CapsuleError {
     #[error("invalid UTF-8")]
     InvalidUtf8,
 
+    #[error("invalid URL: {0}")]
+    InvalidUrl(url::ParseError),
+
     #[error("message too long")]
     MessageTooLong,
 
+    #[error("invalid capsule length")]
+    InvalidLength,
+
     #[error("unknown capsule type: {0:?}")]
     UnknownType(VarInt),
 
@@ -257,6 +394,7 @@ impl<S: AsyncRead + Unpin> Http3CapsuleReader<S> {
                 match Capsule::decode(&mut slice) {
                     Ok(capsule) => {
                         self.buf.advance(self.buf.len() - slice.len());
+                        log_frame(Direction::Received, WireFrame::Capsule(&capsule));
                         return Ok(Some(capsule));
                     }
                     Err(CapsuleError::UnexpectedEnd | CapsuleError::VarInt(_)) => {}
@@ -345,7 +483,7 @@ mod tests {
     fn test_close_webtransport_session_encode() {
         let capsule = Capsule::CloseWebTransportSession {
             code: 420,
-            reason: "test".to_string(),
+            reason: "test".into(),
         };
 
         let mut buf = Vec::new();
@@ -359,7 +497,7 @@ mod tests {
     fn test_close_webtransport_session_roundtrip() {
         let original = Capsule::CloseWebTransportSession {
             code: 12345,
-            reason: "Connection closed by application".to_string(),
+            reason: "Connection closed by application".into(),
         };
 
         let mut buf = Vec::new();
@@ -372,11 +510,115 @@ mod tests {
         assert_eq!(read_buf.len(), 0); // All bytes consumed
     }
 
+    #[test]
+    fn test_drain_webtransport_session() {
+        let mut buf = Vec::new();
+        Capsule::DrainWebTransportSession.encode(&mut buf);
+
+        // Expected format: type(0x78ae as a 4-byte varint) + length(0)
+        assert_eq!(buf, b"\x80\x00\x78\xae\x00");
+
+        let mut read_buf = buf.as_slice();
+        let decoded = Capsule::decode(&mut read_buf).unwrap();
+        assert_eq!(decoded, Capsule::DrainWebTransportSession);
+        assert_eq!(read_buf.len(), 0);
+    }
+
+    #[test]
+    fn test_flow_control_roundtrip() {
+        let capsules = [
+            Capsule::WtMaxData { max: 1 << 20 },
+            Capsule::WtMaxStreams {
+                bidi: true,
+                max: 100,
+            },
+            Capsule::WtMaxStreams {
+                bidi: false,
+                max: 0,
+            },
+            Capsule::WtDataBlocked { limit: 1 << 20 },
+            Capsule::WtStreamsBlocked {
+                bidi: true,
+                limit: 100,
+            },
+            Capsule::WtStreamsBlocked {
+                bidi: false,
+                limit: VarInt::MAX.into_inner(),
+            },
+        ];
+
+        for capsule in capsules {
+            let mut buf = Vec::new();
+            capsule.encode(&mut buf);
+
+            let mut read_buf = buf.as_slice();
+            assert_eq!(Capsule::decode(&mut read_buf).unwrap(), capsule);
+            assert_eq!(read_buf.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_flow_control_trailing_bytes() {
+        let mut data = Vec::new();
+        VarInt::from_u64(0x190b4d3d).unwrap().encode(&mut data);
+        VarInt::from_u32(2).encode(&mut data);
+        data.extend_from_slice(&[0x01, 0x02]);
+
+        let mut buf = data.as_slice();
+        assert!(matches!(
+            Capsule::decode(&mut buf),
+            Err(CapsuleError::InvalidLength)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_hint_roundtrip() {
+        let capsule = Capsule::ReconnectHint {
+            url: Url::parse("https://relay.example.com:4443/moq?region=eu").unwrap(),
+        };
+
+        let mut buf = Vec::new();
+        capsule.encode(&mut buf);
+
+        let mut read_buf = buf.as_slice();
+        assert_eq!(Capsule::decode(&mut read_buf).unwrap(), capsule);
+        assert_eq!(read_buf.len(), 0);
+
+        let mut cursor = std::io::Cursor::new(buf);
+        assert_eq!(Capsule::read(&mut cursor).await.unwrap(), Some(capsule));
+    }
+
+    #[test]
+    fn test_reconnect_hint_invalid_url() {
+        let mut data = Vec::new();
+        VarInt::from_u64(RECONNECT_HINT_TYPE)
+            .unwrap()
+            .encode(&mut data);
+        VarInt::from_u32(9).encode(&mut data);
+        data.extend_from_slice(b"not a url");
+
+        let mut buf = data.as_slice();
+        assert!(matches!(
+            Capsule::decode(&mut buf),
+            Err(CapsuleError::InvalidUrl(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_drain_webtransport_session() {
+        let mut wire = Vec::new();
+        Capsule::DrainWebTransportSession.encode(&mut wire);
+
+        let mut cursor = std::io::Cursor::new(wire);
+        let decoded = Capsule::read(&mut cursor).await.unwrap().unwrap();
+        assert_eq!(decoded, Capsule::DrainWebTransportSession);
+    }
+
     #[test]
     fn test_empty_error_message() {
         let capsule = Capsule::CloseWebTransportSession {
             code: 0,
-            reason: String::new(),
+            reason: Bytes::new(),
         };
 
         let mut buf = Vec::new();
@@ -391,8 +633,8 @@ mod tests {
     }
 
     #[test]
-    fn test_invalid_utf8() {
-        // Create a capsule with invalid UTF-8 in the message
+    fn test_non_utf8_reason() {
+        // The reason isn't required to be UTF-8, so it's kept verbatim.
         let mut data = Vec::new();
         VarInt::from_u64(0x2843).unwrap().encode(&mut data); // type
         VarInt::from_u32(5).encode(&mut data); // length(5)
@@ -400,8 +642,18 @@ mod tests {
         data.push(0xFF); // Invalid UTF-8 byte
 
         let mut buf = data.as_slice();
-        let result = Capsule::decode(&mut buf);
-        assert!(matches!(result, Err(CapsuleError::InvalidUtf8)));
+        let capsule = Capsule::decode(&mut buf).unwrap();
+        assert_eq!(
+            capsule,
+            Capsule::CloseWebTransportSession {
+                code: 0,
+                reason: Bytes::from_static(b"\xff"),
+            }
+        );
+
+        let mut encoded = Vec::new();
+        capsule.encode(&mut encoded);
+        assert_eq!(encoded, data);
     }
 
     #[test]
@@ -496,7 +748,7 @@ mod tests {
     async fn test_read_exact_consumption() {
         let capsule = Capsule::CloseWebTransportSession {
             code: 42,
-            reason: "bye".to_string(),
+            reason: "bye".into(),
         };
         let mut wire = Vec::new();
         capsule.encode(&mut wire);
@@ -516,7 +768,7 @@ mod tests {
     async fn test_read_roundtrip() {
         let capsule = Capsule::CloseWebTransportSession {
             code: 100,
-            reason: "test".to_string(),
+            reason: "test".into(),
         };
         let mut wire = Vec::new();
         capsule.encode(&mut wire);
@@ -655,7 +907,7 @@ mod tests {
     async fn test_http3_reader_skips_non_data_frames() {
         let capsule = Capsule::CloseWebTransportSession {
             code: 0,
-            reason: String::new(),
+            reason: Bytes::new(),
         };
         let mut wire = Vec::new();
         // HEADERS frame before the DATA frame.
@@ -686,6 +938,27 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_http3_reader_close_with_response() {
+        let capsule = Capsule::CloseWebTransportSession {
+            code: 9,
+            reason: "rejected".into(),
+        };
+
+        // The server responds and immediately closes, so both land in one packet.
+        let mut wire = Vec::new();
+        crate::ConnectResponse::OK.encode(&mut wire).unwrap();
+        wire.extend_from_slice(&wrap_in_data_frame(&encode_capsule(&capsule)));
+
+        let mut stream = std::io::Cursor::new(wire);
+        let response = crate::ConnectResponse::read(&mut stream).await.unwrap();
+        assert_eq!(response.status, http::StatusCode::OK);
+
+        let mut reader = Http3CapsuleReader::new(&mut stream);
+        assert_eq!(reader.read().await.unwrap().unwrap(), capsule);
+        assert!(reader.read().await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_http3_reader_empty_data_frame() {
         let capsule = Capsule::CloseWebTransportSession {
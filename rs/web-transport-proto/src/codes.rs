@@ -0,0 +1,115 @@
+//! Error codes sent by the library itself, rather than by the application.
+//!
+//! Every backend uses these same values, so a code seen in a peer's logs means
+//! the same thing regardless of which implementation sent it. Each is a
+//! WebTransport (u32) code, mapped into the HTTP/3 space with [error_to_http3]
+//! before it hits the wire. The drop codes are ASCII, so they're easy to spot
+//! in a packet capture.
+//!
+//! [error_to_http3]: crate::error_to_http3
+
+/// The session was dropped without calling `close`.
+///
+/// "conn" in ASCII: `0x636E6E6F`, or `0x52E50ACE926F` as an HTTP/3 error code.
+pub const SESSION_DROPPED: u32 = 0x636E6E6F;
+
+/// A send stream was dropped without calling `finish` or `reset`.
+///
+/// "send" in ASCII: `0x73656E64`, or `0x52E51B4DCE20` as an HTTP/3 error code.
+pub const SEND_DROPPED: u32 = 0x73656E64;
+
+/// A receive stream was dropped without calling `stop` or reading to the end.
+///
+/// "DECV" in ASCII: `0x44454356`, or `0x52E4EA9B7F80` as an HTTP/3 error code.
+pub const RECV_DROPPED: u32 = 0x44454356;
+
+/// The peer sent a capsule that couldn't be decoded, so the session was closed.
+pub const CAPSULE_ERROR: u32 = 500;
+
+/// A stream was opened on a [datagram-only](crate::SessionMode::DatagramOnly) session.
+///
+/// "dgrm" in ASCII: `0x6467726D`. Both halves of the stream are reset with it.
+pub const STREAMS_DISABLED: u32 = 0x6467726D;
+
+/// HTTP/3 error codes (RFC 9114), used for connections and streams outside a session.
+///
+/// These are sent as-is, not mapped with [error_to_http3](crate::error_to_http3).
+pub mod h3 {
+    /// The connection or a stream was closed without an error.
+    pub const NO_ERROR: u64 = 0x100;
+
+    /// The peer broke the protocol in a way without a more specific code, such as a
+    /// stream header over its [HeaderBudget](crate::HeaderBudget).
+    pub const GENERAL_PROTOCOL_ERROR: u64 = 0x101;
+
+    /// The peer opened more streams of an unknown type than allowed, or one it may not open.
+    pub const STREAM_CREATION_ERROR: u64 = 0x103;
+
+    /// A stream named a session ID that can't belong to a session, or a push ID we never allowed.
+    pub const ID_ERROR: u64 = 0x108;
+
+    /// The client didn't send its SETTINGS in time, or sent invalid ones.
+    pub const SETTINGS_ERROR: u64 = 0x109;
+
+    /// The client didn't send its CONNECT request in time, so it was never processed.
+    pub const REQUEST_REJECTED: u64 = 0x10b;
+
+    /// A stream sent before the CONNECT response didn't fit in the server's buffer.
+    pub const WT_BUFFERED_STREAM_REJECTED: u64 = 0x3994bd84;
+
+    /// A stream arrived for a session that was already closed.
+    pub const WT_SESSION_GONE: u64 = 0x170d7b68;
+}
+
+/// The codes used when a session or stream is dropped without being closed.
+///
+/// These are the only library codes applications may want to change, e.g. to
+/// fit a protocol that assigns meaning to every code. The defaults are
+/// [SESSION_DROPPED], [SEND_DROPPED], and [RECV_DROPPED].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropCodes {
+    /// Closes a session dropped without calling `close`.
+    pub session: u32,
+
+    /// Resets a send stream dropped without calling `finish` or `reset`.
+    ///
+    /// Backends that instead finish a dropped stream, as quinn does, never send it.
+    pub send: u32,
+
+    /// Stops a receive stream dropped without calling `stop` or reading to the end.
+    pub recv: u32,
+}
+
+impl Default for DropCodes {
+    fn default() -> Self {
+        Self {
+            session: SESSION_DROPPED,
+            send: SEND_DROPPED,
+            recv: RECV_DROPPED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error_from_http3, error_to_http3};
+
+    #[test]
+    fn drop_codes_on_the_wire() {
+        assert_eq!(error_to_http3(SESSION_DROPPED), 0x52E50ACE926F);
+        assert_eq!(error_to_http3(SEND_DROPPED), 0x52E51B4DCE20);
+        assert_eq!(error_to_http3(RECV_DROPPED), 0x52E4EA9B7F80);
+
+        let codes = DropCodes::default();
+        for code in [
+            codes.session,
+            codes.send,
+            codes.recv,
+            CAPSULE_ERROR,
+            STREAMS_DISABLED,
+        ] {
+            assert_eq!(error_from_http3(error_to_http3(code)), Some(code));
+        }
+    }
+}
@@ -1,10 +1,12 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use bytes::{Buf, BufMut, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use url::Url;
 
-use super::{qpack, Frame, VarInt, MAX_FRAME_SIZE};
+use super::{
+    log_frame, qpack, Direction, Frame, ResumptionToken, VarInt, WireFrame, MAX_FRAME_SIZE,
+};
 
 use thiserror::Error;
 
@@ -18,6 +20,9 @@ pub enum ConnectError {
     #[error("qpack error")]
     QpackError(#[from] qpack::DecodeError),
 
+    #[error("invalid field: {0}")]
+    InvalidField(#[from] qpack::FieldError),
+
     #[error("unexpected frame {0:?}")]
     UnexpectedFrame(Frame),
 
@@ -57,6 +62,9 @@ pub enum ConnectError {
     #[error("frame too large")]
     FrameTooLarge,
 
+    #[error("field section too large: {size} > {limit}")]
+    FieldSectionTooLarge { size: u64, limit: u64 },
+
     #[error("non-200 status: {0:?}")]
     ErrorStatus(http::StatusCode),
 
@@ -68,6 +76,15 @@ pub enum ConnectError {
 
     #[error("invalid http header name")]
     InvalidHttpHeaderName,
+
+    #[error("invalid subprotocol {0:?}: must be 1-{max} printable ASCII characters", max = Subprotocol::MAX_LEN)]
+    InvalidSubprotocol(String),
+
+    #[error("duplicate subprotocol {0:?}")]
+    DuplicateSubprotocol(String),
+
+    #[error("invalid resumption token")]
+    InvalidResumptionToken,
 }
 
 impl From<std::io::Error> for ConnectError {
@@ -82,6 +99,77 @@ impl From<sfv::Error> for ConnectError {
     }
 }
 
+/// A subprotocol name that is known to encode as a structured field string.
+///
+/// The same rules the browser applies to `new WebTransport(url, { protocols })`:
+/// 1 to [Subprotocol::MAX_LEN] bytes of printable ASCII.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Subprotocol(String);
+
+impl Subprotocol {
+    /// The longest subprotocol name a browser will send.
+    pub const MAX_LEN: usize = 512;
+
+    pub fn new(protocol: impl Into<String>) -> Result<Self, ConnectError> {
+        let protocol = protocol.into();
+
+        let valid = !protocol.is_empty()
+            && protocol.len() <= Self::MAX_LEN
+            && protocol.bytes().all(|b| (0x20..=0x7e).contains(&b));
+
+        match valid {
+            true => Ok(Self(protocol)),
+            false => Err(ConnectError::InvalidSubprotocol(protocol)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Subprotocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Subprotocol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Subprotocol {
+    type Err = ConnectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for Subprotocol {
+    type Error = ConnectError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<&str> for Subprotocol {
+    type Error = ConnectError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl From<Subprotocol> for String {
+    fn from(protocol: Subprotocol) -> Self {
+        protocol.0
+    }
+}
+
 /// A CONNECT request to initiate a WebTransport session.
 #[non_exhaustive]
 #[derive(Debug, Clone)]
@@ -93,6 +181,9 @@ pub struct ConnectRequest {
     pub protocols: Vec<String>,
 
     /// The raw HTTP/3 headers from the request.
+    ///
+    /// Everything but the pseudo-headers and the subprotocol list, such as `origin`,
+    /// `authorization` or `cookie`. A name sent more than once keeps every value, in order.
     pub headers: http::HeaderMap,
 }
 
@@ -105,49 +196,143 @@ impl ConnectRequest {
         }
     }
 
+    /// Offer a subprotocol, without validating it until the request is encoded.
+    ///
+    /// Prefer [Self::try_with_protocol], which rejects an invalid name up front.
     pub fn with_protocol(mut self, protocol: impl Into<String>) -> Self {
         self.protocols.push(protocol.into());
         self
     }
 
+    /// Offer several subprotocols, without validating them until the request is encoded.
+    ///
+    /// Prefer [Self::try_with_protocols], which rejects invalid names up front.
     pub fn with_protocols(mut self, protocols: impl IntoIterator<Item = String>) -> Self {
         self.protocols.extend(protocols);
         self
     }
 
+    /// Offer a subprotocol, failing if it's not a valid [Subprotocol] or was already offered.
+    pub fn try_with_protocol<P>(mut self, protocol: P) -> Result<Self, ConnectError>
+    where
+        P: TryInto<Subprotocol, Error = ConnectError>,
+    {
+        let protocol: String = protocol.try_into()?.into();
+        if self.protocols.contains(&protocol) {
+            return Err(ConnectError::DuplicateSubprotocol(protocol));
+        }
+
+        self.protocols.push(protocol);
+        Ok(self)
+    }
+
+    /// Offer several subprotocols, in order of preference. See [Self::try_with_protocol].
+    pub fn try_with_protocols<P>(
+        self,
+        protocols: impl IntoIterator<Item = P>,
+    ) -> Result<Self, ConnectError>
+    where
+        P: TryInto<Subprotocol, Error = ConnectError>,
+    {
+        protocols.into_iter().try_fold(self, |request, protocol| {
+            request.try_with_protocol(protocol)
+        })
+    }
+
+    /// Send a header with the request, keeping any values already set for `name`.
     pub fn with_header(mut self, name: http::HeaderName, value: http::HeaderValue) -> Self {
         self.headers.append(name, value);
         self
     }
 
+    /// Send these headers with the request, replacing any already set with the same names.
     pub fn with_headers(mut self, headers: http::HeaderMap) -> Self {
         self.headers.extend(headers);
         self
     }
 
-    pub fn decode<B: Buf>(buf: &mut B) -> Result<Self, ConnectError> {
-        let (typ, mut data) = Frame::read(buf).map_err(|_| ConnectError::UnexpectedEnd)?;
-        if typ != Frame::HEADERS {
-            return Err(ConnectError::UnexpectedFrame(typ));
+    /// Present a token from a previous session, asking the server to resume it.
+    ///
+    /// The token is sent as the [ResumptionToken::NAME] header, replacing any already set.
+    pub fn with_resumption_token(mut self, token: &ResumptionToken) -> Self {
+        let value = http::HeaderValue::from_str(token.as_str()).expect("token is a valid header");
+        self.headers
+            .insert(http::HeaderName::from_static(ResumptionToken::NAME), value);
+        self
+    }
+
+    /// The token presented to resume a previous session, if any.
+    ///
+    /// Read from the [ResumptionToken::NAME] header, or from the query parameter of
+    /// the same name since a browser can't set headers. A malformed token is ignored.
+    pub fn resumption_token(&self) -> Option<ResumptionToken> {
+        let header = self
+            .headers
+            .get(ResumptionToken::NAME)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| ResumptionToken::new(value).ok());
+
+        header.or_else(|| {
+            self.url
+                .query_pairs()
+                .find(|(name, _)| name == ResumptionToken::NAME)
+                .and_then(|(_, value)| ResumptionToken::new(value).ok())
+        })
+    }
+
+    /// The origin of the page that opened the session, from the `origin` header.
+    ///
+    /// Browsers always send it; other clients usually don't, giving `None`. A `null`
+    /// or malformed origin decodes as an opaque origin, which matches no other.
+    pub fn origin(&self) -> Option<url::Origin> {
+        let value = self.headers.get(http::header::ORIGIN)?;
+        let origin = value
+            .to_str()
+            .ok()
+            .and_then(|value| Url::parse(value).ok())
+            .map(|url| url.origin())
+            .unwrap_or_else(url::Origin::new_opaque);
+
+        Some(origin)
+    }
+
+    /// Returns true unless the request has an [origin](Self::origin) missing from `allowed`.
+    ///
+    /// A request without an origin didn't come from a browser, so it's allowed; any
+    /// client other than a browser can claim whatever origin it likes anyway.
+    pub fn origin_allowed(&self, allowed: &[url::Origin]) -> bool {
+        match self.origin() {
+            Some(origin) => allowed.contains(&origin),
+            None => true,
         }
+    }
 
-        Self::decode_headers(&mut data)
+    pub fn decode<B: Buf>(buf: &mut B) -> Result<Self, ConnectError> {
+        let mut data = decode_headers_frame(buf)?;
+
+        Self::decode_headers(&mut data, None)
     }
 
-    fn decode_headers<B: Buf>(data: &mut B) -> Result<Self, ConnectError> {
-        let headers = qpack::Headers::decode(data)?;
+    /// Like [Self::decode], but rejects a field section larger than `max_field_section_size`.
+    ///
+    /// This is the limit a server advertises with [Setting::MAX_FIELD_SECTION_SIZE](crate::Setting::MAX_FIELD_SECTION_SIZE).
+    pub fn decode_limited<B: Buf>(
+        buf: &mut B,
+        max_field_section_size: u64,
+    ) -> Result<Self, ConnectError> {
+        let mut data = decode_headers_frame(buf)?;
 
-        let scheme = match headers.get(":scheme") {
-            Some("https") => "https",
-            Some(scheme) => Err(ConnectError::WrongScheme(Some(scheme.to_string())))?,
-            None => return Err(ConnectError::WrongScheme(None)),
-        };
+        Self::decode_headers(&mut data, Some(max_field_section_size))
+    }
 
-        let authority = headers
-            .get(":authority")
-            .ok_or(ConnectError::WrongAuthority)?;
+    fn decode_headers<B: Buf>(data: &mut B, limit: Option<u64>) -> Result<Self, ConnectError> {
+        let headers = decode_fields(data, limit)?;
+        Self::from_fields(&headers)
+    }
 
-        let path_and_query = headers.get(":path").ok_or(ConnectError::WrongPath)?;
+    // Build the request from a decoded field section, which must be a WebTransport CONNECT.
+    pub(crate) fn from_fields(headers: &qpack::Headers) -> Result<Self, ConnectError> {
+        let url = request_url(headers)?;
 
         let method = headers.get(":method");
         match method
@@ -170,36 +355,33 @@ impl ConnectRequest {
             .map_err(|_| ConnectError::InvalidProtocol)?
             .unwrap_or_default();
 
-        let url = Url::parse(&format!("{scheme}://{authority}{path_and_query}"))?;
-
-        // Save all headers, excluding pseudo-headers and protocol negotiation headers
-        // (protocol negotiation is handled via the `protocols` field).
-        let mut raw_headers = http::HeaderMap::new();
-        for (item_header_name, item_header_value) in headers.fields.iter() {
-            if item_header_name.starts_with(':') {
-                continue;
-            }
-            if item_header_name == protocol_negotiation::AVAILABLE_NAME {
-                continue;
-            }
-            let header_name = http::HeaderName::from_bytes(item_header_name.as_bytes())
-                .map_err(|_| ConnectError::InvalidHttpHeaderName)?;
-            let header_value = http::HeaderValue::from_str(item_header_value)
-                .map_err(|_| ConnectError::InvalidHttpHeaderValue)?;
-            raw_headers.append(header_name, header_value);
-        }
+        // Protocol negotiation is handled via the `protocols` field.
+        let headers = header_map(headers, &[protocol_negotiation::AVAILABLE_NAME])?;
 
         Ok(Self {
             url,
             protocols,
-            headers: raw_headers,
+            headers,
         })
     }
 
     /// Read a CONNECT request from a stream, consuming only the exact bytes of the frame.
     pub async fn read<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Self, ConnectError> {
         let buf = read_headers_frame(stream).await?;
-        Self::decode_headers(&mut buf.as_slice())
+        let request = Self::decode_headers(&mut buf.as_slice(), None)?;
+        log_frame(Direction::Received, WireFrame::ConnectRequest(&request));
+        Ok(request)
+    }
+
+    /// Like [Self::read], but rejects a field section larger than `max_field_section_size`.
+    pub async fn read_limited<S: AsyncRead + Unpin>(
+        stream: &mut S,
+        max_field_section_size: u64,
+    ) -> Result<Self, ConnectError> {
+        let buf = read_headers_frame(stream).await?;
+        let request = Self::decode_headers(&mut buf.as_slice(), Some(max_field_section_size))?;
+        log_frame(Direction::Received, WireFrame::ConnectRequest(&request));
+        Ok(request)
     }
 
     pub fn encode<B: BufMut>(&self, buf: &mut B) -> Result<(), ConnectError> {
@@ -214,7 +396,7 @@ impl ConnectRequest {
             let item_header_value_str = item_header_value
                 .to_str()
                 .map_err(|_| ConnectError::InvalidHttpHeaderValue)?;
-            headers.set(item_header_name.as_str(), item_header_value_str);
+            headers.append(item_header_name.as_str(), item_header_value_str);
         }
         headers.set(":method", "CONNECT");
         headers.set(":scheme", self.url.scheme());
@@ -233,7 +415,7 @@ impl ConnectRequest {
 
         // Use a temporary buffer so we can compute the size.
         let mut tmp = Vec::new();
-        headers.encode(&mut tmp);
+        headers.encode(&mut tmp)?;
         let size = VarInt::from_u32(tmp.len() as u32);
 
         Frame::HEADERS.encode(buf);
@@ -246,6 +428,7 @@ impl ConnectRequest {
     pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), ConnectError> {
         let mut buf = BytesMut::new();
         self.encode(&mut buf)?;
+        log_frame(Direction::Sent, WireFrame::ConnectRequest(self));
         stream.write_all_buf(&mut buf).await?;
         Ok(())
     }
@@ -270,35 +453,127 @@ pub struct ConnectResponse {
 
     /// The subprotocol selected by the server, if any
     pub protocol: Option<String>,
+
+    /// Where to reconnect, sent with a 3xx status.
+    pub location: Option<Url>,
+
+    /// A token the client can present to resume this session later.
+    pub resumption_token: Option<ResumptionToken>,
+
+    /// The raw HTTP/3 headers from the response, or `None` if there are none.
+    ///
+    /// Everything but the pseudo-headers and the fields above, such as `www-authenticate`
+    /// on a 401, telling the client how to authenticate before it tries again.
+    pub headers: Option<http::HeaderMap>,
 }
 
 impl ConnectResponse {
     pub const OK: Self = Self {
         status: http::StatusCode::OK,
         protocol: None,
+        location: None,
+        resumption_token: None,
+        headers: None,
     };
 
     pub fn new(status: http::StatusCode) -> Self {
         Self {
             status,
             protocol: None,
+            location: None,
+            resumption_token: None,
+            headers: None,
         }
     }
 
+    /// Send the client elsewhere with a 302 Found, e.g. to drain a server before shutdown.
+    pub fn redirect(location: Url) -> Self {
+        Self::new(http::StatusCode::FOUND).with_location(location)
+    }
+
+    /// Set the location header, which only means something alongside a 3xx status.
+    pub fn with_location(mut self, location: Url) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Select a subprotocol, without validating it until the response is encoded.
+    ///
+    /// Prefer [Self::try_with_protocol], which rejects an invalid name up front.
     pub fn with_protocol(mut self, protocol: impl Into<String>) -> Self {
         self.protocol = Some(protocol.into());
         self
     }
 
+    /// Give the client a token to resume this session after a network change.
+    ///
+    /// See [ResumptionToken] for the full flow; [ResumptionStore](crate::ResumptionStore) mints them.
+    pub fn with_resumption_token(mut self, token: ResumptionToken) -> Self {
+        self.resumption_token = Some(token);
+        self
+    }
+
+    /// Select a subprotocol, failing if it's not a valid [Subprotocol].
+    pub fn try_with_protocol<P>(mut self, protocol: P) -> Result<Self, ConnectError>
+    where
+        P: TryInto<Subprotocol, Error = ConnectError>,
+    {
+        self.protocol = Some(protocol.try_into()?.into());
+        Ok(self)
+    }
+
+    /// Tell a rejected client how long to wait before trying again, in whole seconds rounded up.
+    pub fn with_retry_after(self, delay: Duration) -> Self {
+        let secs = delay.as_secs() + u64::from(delay.subsec_nanos() > 0);
+        self.with_header(http::header::RETRY_AFTER, http::HeaderValue::from(secs))
+    }
+
+    /// Send a header with the response, keeping any values already set for `name`.
+    pub fn with_header(mut self, name: http::HeaderName, value: http::HeaderValue) -> Self {
+        self.headers
+            .get_or_insert_with(http::HeaderMap::new)
+            .append(name, value);
+        self
+    }
+
+    /// Send these headers with the response, replacing any already set with the same names.
+    pub fn with_headers(mut self, headers: http::HeaderMap) -> Self {
+        self.headers
+            .get_or_insert_with(http::HeaderMap::new)
+            .extend(headers);
+        self
+    }
+
+    // Headers derived from the other fields, so they're never taken from `headers`.
+    const DERIVED: [&'static str; 4] = [
+        protocol_negotiation::SELECTED_NAME,
+        "location",
+        ResumptionToken::NAME,
+        "sec-webtransport-http3-draft",
+    ];
+
+    /// Decode a response, failing with [ConnectError::WrongStatus] unless it's a 2xx or 3xx.
     pub fn decode<B: Buf>(buf: &mut B) -> Result<Self, ConnectError> {
-        let (typ, mut data) = Frame::read(buf).map_err(|_| ConnectError::UnexpectedEnd)?;
-        if typ != Frame::HEADERS {
-            return Err(ConnectError::UnexpectedFrame(typ));
-        }
+        Self::decode_any_status(buf)?.check_status()
+    }
+
+    /// Like [Self::decode], but returns rejections too, so the caller can see their headers.
+    ///
+    /// Only an informational (1xx) status fails, since it isn't a final response.
+    pub fn decode_any_status<B: Buf>(buf: &mut B) -> Result<Self, ConnectError> {
+        let mut data = decode_headers_frame(buf)?;
 
         Self::decode_headers(&mut data)
     }
 
+    // Redirects are returned too, so the caller can decide whether to follow.
+    fn check_status(self) -> Result<Self, ConnectError> {
+        match self.status.is_success() || self.status.is_redirection() {
+            true => Ok(self),
+            false => Err(ConnectError::WrongStatus(Some(self.status))),
+        }
+    }
+
     fn decode_headers<B: Buf>(data: &mut B) -> Result<Self, ConnectError> {
         let headers = qpack::Headers::decode(data)?;
 
@@ -309,7 +584,7 @@ impl ConnectResponse {
             })
             .transpose()?
         {
-            Some(status) if status.is_success() => status,
+            Some(status) if !status.is_informational() => status,
             o => return Err(ConnectError::WrongStatus(o)),
         };
 
@@ -319,17 +594,53 @@ impl ConnectResponse {
             .transpose()
             .map_err(|_| ConnectError::InvalidProtocol)?;
 
-        Ok(Self { status, protocol })
+        // Only absolute URLs: there's no base to resolve a relative one against here.
+        let location = headers.get("location").map(Url::parse).transpose()?;
+
+        let resumption_token = headers
+            .get(ResumptionToken::NAME)
+            .map(ResumptionToken::new)
+            .transpose()?;
+
+        let headers = Some(header_map(&headers, &Self::DERIVED)?).filter(|map| !map.is_empty());
+
+        Ok(Self {
+            status,
+            protocol,
+            location,
+            resumption_token,
+            headers,
+        })
     }
 
     /// Read a CONNECT response from a stream, consuming only the exact bytes of the frame.
+    ///
+    /// Fails with [ConnectError::WrongStatus] unless it's a 2xx or 3xx, like [Self::decode].
     pub async fn read<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Self, ConnectError> {
+        Self::read_any_status(stream).await?.check_status()
+    }
+
+    /// Like [Self::read], but returns rejections too, so the caller can see their headers.
+    pub async fn read_any_status<S: AsyncRead + Unpin>(
+        stream: &mut S,
+    ) -> Result<Self, ConnectError> {
         let buf = read_headers_frame(stream).await?;
-        Self::decode_headers(&mut buf.as_slice())
+        let response = Self::decode_headers(&mut buf.as_slice())?;
+        log_frame(Direction::Received, WireFrame::ConnectResponse(&response));
+        Ok(response)
     }
 
     pub fn encode<B: BufMut>(&self, buf: &mut B) -> Result<(), ConnectError> {
         let mut headers = qpack::Headers::default();
+        for (name, value) in self.headers.iter().flatten() {
+            if Self::DERIVED.contains(&name.as_str()) {
+                continue;
+            }
+            let value = value
+                .to_str()
+                .map_err(|_| ConnectError::InvalidHttpHeaderValue)?;
+            headers.append(name.as_str(), value);
+        }
         headers.set(":status", self.status.as_str());
         headers.set("sec-webtransport-http3-draft", "draft02");
 
@@ -338,9 +649,17 @@ impl ConnectResponse {
             headers.set(protocol_negotiation::SELECTED_NAME, &encoded);
         }
 
+        if let Some(location) = self.location.as_ref() {
+            headers.set("location", location.as_str());
+        }
+
+        if let Some(token) = self.resumption_token.as_ref() {
+            headers.set(ResumptionToken::NAME, token.as_str());
+        }
+
         // Use a temporary buffer so we can compute the size.
         let mut tmp = Vec::new();
-        headers.encode(&mut tmp);
+        headers.encode(&mut tmp)?;
         let size = VarInt::from_u32(tmp.len() as u32);
 
         Frame::HEADERS.encode(buf);
@@ -353,6 +672,7 @@ impl ConnectResponse {
     pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), ConnectError> {
         let mut buf = BytesMut::new();
         self.encode(&mut buf)?;
+        log_frame(Direction::Sent, WireFrame::ConnectResponse(self));
         stream.write_all_buf(&mut buf).await?;
         Ok(())
     }
@@ -366,17 +686,92 @@ impl Default for ConnectResponse {
 
 impl From<http::StatusCode> for ConnectResponse {
     fn from(status: http::StatusCode) -> Self {
-        Self {
-            status,
-            protocol: None,
+        Self::new(status)
+    }
+}
+
+/// Decode a field section, rejecting one larger than `limit`.
+pub(crate) fn decode_fields<B: Buf>(
+    data: &mut B,
+    limit: Option<u64>,
+) -> Result<qpack::Headers, ConnectError> {
+    let headers = qpack::Headers::decode(data)?;
+
+    if let Some(limit) = limit {
+        let size = headers.size() as u64;
+        if size > limit {
+            return Err(ConnectError::FieldSectionTooLarge { size, limit });
+        }
+    }
+
+    Ok(headers)
+}
+
+/// Build the request URL from the `:scheme`, `:authority` and `:path` pseudo-headers.
+pub(crate) fn request_url(headers: &qpack::Headers) -> Result<Url, ConnectError> {
+    let scheme = match headers.get(":scheme") {
+        Some("https") => "https",
+        Some(scheme) => Err(ConnectError::WrongScheme(Some(scheme.to_string())))?,
+        None => return Err(ConnectError::WrongScheme(None)),
+    };
+
+    let authority = headers
+        .get(":authority")
+        .ok_or(ConnectError::WrongAuthority)?;
+
+    let path_and_query = headers.get(":path").ok_or(ConnectError::WrongPath)?;
+
+    Ok(Url::parse(&format!(
+        "{scheme}://{authority}{path_and_query}"
+    ))?)
+}
+
+/// Collect every field but the pseudo-headers and those in `skip`.
+pub(crate) fn header_map(
+    headers: &qpack::Headers,
+    skip: &[&str],
+) -> Result<http::HeaderMap, ConnectError> {
+    let mut map = http::HeaderMap::new();
+    for (name, value) in headers.fields.iter() {
+        if name.starts_with(':') || skip.contains(&name.as_str()) {
+            continue;
         }
+        let name = http::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| ConnectError::InvalidHttpHeaderName)?;
+        let value =
+            http::HeaderValue::from_str(value).map_err(|_| ConnectError::InvalidHttpHeaderValue)?;
+        map.append(name, value);
     }
+
+    Ok(map)
 }
 
-/// Read the next HEADERS frame from the stream, skipping any GREASE frames.
+/// Decode the next HEADERS frame, skipping any GREASE or unknown extension frames.
+///
+/// Any other frame is unexpected before the headers (RFC 9114 Section 4.1).
+pub(crate) fn decode_headers_frame<B: Buf>(
+    mut buf: &mut B,
+) -> Result<bytes::buf::Take<&mut B>, ConnectError> {
+    loop {
+        let (typ, mut data) = Frame::read(buf).map_err(|_| ConnectError::UnexpectedEnd)?;
+        if typ == Frame::HEADERS {
+            return Ok(data);
+        }
+        if !typ.is_unknown() {
+            return Err(ConnectError::UnexpectedFrame(typ));
+        }
+
+        data.advance(data.limit());
+        buf = data.into_inner();
+    }
+}
+
+/// Read the next HEADERS frame from the stream, skipping any GREASE or unknown extension frames.
 ///
 /// Returns the raw payload bytes of the HEADERS frame.
-async fn read_headers_frame<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>, ConnectError> {
+pub(crate) async fn read_headers_frame<S: AsyncRead + Unpin>(
+    stream: &mut S,
+) -> Result<Vec<u8>, ConnectError> {
     loop {
         let typ = Frame(
             VarInt::read(stream)
@@ -394,7 +789,7 @@ async fn read_headers_frame<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<
 
         let mut payload = stream.take(size);
 
-        if typ.is_grease() {
+        if typ.is_grease() || typ.is_unknown() {
             let n = tokio::io::copy(&mut payload, &mut tokio::io::sink()).await?;
             if n < size {
                 return Err(ConnectError::UnexpectedEnd);
@@ -424,7 +819,7 @@ mod protocol_negotiation {
 
     use sfv::{Item, ItemSerializer, List, ListEntry, ListSerializer, Parser, StringRef};
 
-    use crate::ConnectError;
+    use crate::{ConnectError, Subprotocol};
 
     /// The header name for the available protocols, sent within the WebTransport Connect request.
     pub const AVAILABLE_NAME: &str = "wt-available-protocols";
@@ -434,9 +829,11 @@ mod protocol_negotiation {
     /// Encode a list of protocol strings as an RFC 8941 Structured Field List.
     pub fn encode_list(protocols: &[String]) -> Result<String, ConnectError> {
         let mut serializer = ListSerializer::new();
-        for protocol in protocols {
-            let s = StringRef::from_str(protocol)?;
-            let _ = serializer.bare_item(s);
+        for (i, protocol) in protocols.iter().enumerate() {
+            if protocols[..i].contains(protocol) {
+                return Err(ConnectError::DuplicateSubprotocol(protocol.clone()));
+            }
+            let _ = serializer.bare_item(string_ref(protocol)?);
         }
         serializer.finish().ok_or(ConnectError::InvalidProtocol)
     }
@@ -460,8 +857,15 @@ mod protocol_negotiation {
 
     /// Encode a single string as an RFC 8941 Structured Field Item.
     pub fn encode_item(protocol: &str) -> Result<String, ConnectError> {
-        let s = StringRef::from_str(protocol)?;
-        Ok(ItemSerializer::new().bare_item(s).finish())
+        Ok(ItemSerializer::new()
+            .bare_item(string_ref(protocol)?)
+            .finish())
+    }
+
+    /// Validate a protocol before it reaches sfv, so the error names it.
+    fn string_ref(protocol: &str) -> Result<&StringRef, ConnectError> {
+        Subprotocol::new(protocol)?;
+        Ok(StringRef::from_str(protocol)?)
     }
 
     /// Decode an RFC 8941 Structured Field Item (single string).
@@ -544,6 +948,44 @@ mod tests {
         assert_eq!(req.url.as_str(), "https://example.com/");
     }
 
+    #[tokio::test]
+    async fn request_skips_unknown_frames() {
+        // An extension frame we don't implement, such as ORIGIN, is ignored.
+        let mut wire = Vec::new();
+        VarInt::from_u32(0x0c).encode(&mut wire);
+        VarInt::from_u32(4).encode(&mut wire);
+        wire.extend_from_slice(b"junk");
+        wire.extend_from_slice(&encode_request("https://example.com/"));
+
+        let req = ConnectRequest::read(&mut Cursor::new(&wire)).await.unwrap();
+        assert_eq!(req.url.as_str(), "https://example.com/");
+
+        let req = ConnectRequest::decode(&mut wire.as_slice()).unwrap();
+        assert_eq!(req.url.as_str(), "https://example.com/");
+    }
+
+    #[tokio::test]
+    async fn request_rejects_reserved_and_push_frames() {
+        // PING from HTTP/2, and PUSH_PROMISE which we never allow.
+        for typ in [Frame(VarInt::from_u32(0x06)), Frame::PUSH_PROMISE] {
+            let mut wire = Vec::new();
+            typ.encode(&mut wire);
+            VarInt::from_u32(0).encode(&mut wire);
+            wire.extend_from_slice(&encode_request("https://example.com/"));
+
+            let err = ConnectRequest::read(&mut Cursor::new(&wire))
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, ConnectError::UnexpectedFrame(f) if f == typ),
+                "expected UnexpectedFrame({typ:?}), got {err:?}"
+            );
+
+            let err = ConnectRequest::decode(&mut wire.as_slice()).unwrap_err();
+            assert!(matches!(err, ConnectError::UnexpectedFrame(f) if f == typ));
+        }
+    }
+
     #[tokio::test]
     async fn request_read_rejects_frame_too_large() {
         // Craft a frame header claiming a huge payload.
@@ -581,6 +1023,29 @@ mod tests {
         assert!(matches!(err, ConnectError::UnexpectedEnd));
     }
 
+    #[tokio::test]
+    async fn request_read_limited() {
+        let req = ConnectRequest::new(Url::parse("https://example.com/").unwrap()).with_header(
+            http::HeaderName::from_static("x-padding"),
+            http::HeaderValue::from_str(&"a".repeat(1024)).unwrap(),
+        );
+        let mut wire = Vec::new();
+        req.encode(&mut wire).unwrap();
+
+        let req = ConnectRequest::read_limited(&mut Cursor::new(&wire), 4096)
+            .await
+            .unwrap();
+        assert_eq!(req.headers["x-padding"].len(), 1024);
+
+        let err = ConnectRequest::read_limited(&mut Cursor::new(&wire), 1024)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ConnectError::FieldSectionTooLarge { size, limit: 1024 } if size > 1024),
+            "expected FieldSectionTooLarge, got {err:?}"
+        );
+    }
+
     // ---- ConnectResponse::read tests ----
 
     #[tokio::test]
@@ -690,4 +1155,190 @@ mod tests {
         let err = ConnectRequest::read(&mut cursor).await.unwrap_err();
         assert!(matches!(err, ConnectError::UnexpectedEnd));
     }
+
+    // ---- Subprotocol validation tests ----
+
+    #[test]
+    fn subprotocol_rejects_invalid_names() {
+        assert!(Subprotocol::new("moq-lite-03").is_ok());
+        assert!(Subprotocol::new("with space").is_ok());
+
+        for bad in [
+            "",
+            "caf\u{e9}",
+            "tab\there",
+            &"a".repeat(Subprotocol::MAX_LEN + 1),
+        ] {
+            let err = Subprotocol::new(bad).unwrap_err();
+            assert!(matches!(err, ConnectError::InvalidSubprotocol(p) if p == bad));
+        }
+    }
+
+    #[test]
+    fn request_rejects_duplicate_protocol() {
+        let err = ConnectRequest::new(Url::parse("https://example.com").unwrap())
+            .try_with_protocols(["a", "b", "a"])
+            .unwrap_err();
+        assert!(matches!(err, ConnectError::DuplicateSubprotocol(p) if p == "a"));
+    }
+
+    #[test]
+    fn encode_names_the_invalid_protocol() {
+        // The unchecked builder defers validation, but the error still says which one.
+        let req = ConnectRequest::new(Url::parse("https://example.com").unwrap())
+            .with_protocol("ok")
+            .with_protocol("bad\n");
+
+        let err = req.encode(&mut Vec::new()).unwrap_err();
+        assert!(matches!(err, ConnectError::InvalidSubprotocol(p) if p == "bad\n"));
+    }
+
+    #[test]
+    fn protocols_roundtrip() {
+        let req = ConnectRequest::new(Url::parse("https://example.com").unwrap())
+            .try_with_protocols(["moq-lite-03", "moq-transport-14"])
+            .unwrap();
+
+        let mut buf = Vec::new();
+        req.encode(&mut buf).unwrap();
+        let decoded = ConnectRequest::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.protocols, ["moq-lite-03", "moq-transport-14"]);
+
+        let resp = ConnectResponse::OK
+            .try_with_protocol("moq-lite-03")
+            .unwrap();
+        let mut buf = Vec::new();
+        resp.encode(&mut buf).unwrap();
+        let decoded = ConnectResponse::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.protocol.as_deref(), Some("moq-lite-03"));
+    }
+
+    #[test]
+    fn redirect_roundtrip() {
+        let location = Url::parse("https://blue.example.com/moq").unwrap();
+        let resp = ConnectResponse::redirect(location.clone());
+
+        let mut buf = Vec::new();
+        resp.encode(&mut buf).unwrap();
+        let decoded = ConnectResponse::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.status, http::StatusCode::FOUND);
+        assert_eq!(decoded.location, Some(location));
+    }
+
+    #[test]
+    fn headers_roundtrip() {
+        let cookie = http::header::COOKIE;
+        let req = ConnectRequest::new(Url::parse("https://example.com/").unwrap())
+            .with_header(http::header::ORIGIN, "https://app.example".parse().unwrap())
+            .with_header(cookie.clone(), "a=1".parse().unwrap())
+            .with_header(cookie.clone(), "b=2".parse().unwrap())
+            .with_header(http::header::AUTHORIZATION, "Bearer t".parse().unwrap());
+
+        let mut buf = Vec::new();
+        req.encode(&mut buf).unwrap();
+        let decoded = ConnectRequest::decode(&mut buf.as_slice()).unwrap();
+
+        // Repeated fields keep every value, in order.
+        let cookies: Vec<_> = decoded.headers.get_all(&cookie).iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(decoded.headers["origin"], "https://app.example");
+        assert_eq!(decoded.headers["authorization"], "Bearer t");
+        assert_eq!(decoded.headers.len(), 4);
+    }
+
+    #[test]
+    fn origin() {
+        let allowed = [Url::parse("https://app.example").unwrap().origin()];
+        let request = |origin: &str| {
+            ConnectRequest::new(Url::parse("https://example.com/").unwrap())
+                .with_header(http::header::ORIGIN, origin.parse().unwrap())
+        };
+
+        // The default port and letter case don't matter.
+        let same = request("https://APP.example:443");
+        assert_eq!(same.origin(), Some(allowed[0].clone()));
+        assert!(same.origin_allowed(&allowed));
+
+        assert!(!request("https://evil.example").origin_allowed(&allowed));
+        assert!(!request("http://app.example").origin_allowed(&allowed));
+
+        let null = request("null");
+        assert!(!null.origin().unwrap().is_tuple());
+        assert!(!null.origin_allowed(&allowed));
+
+        let native = ConnectRequest::new(Url::parse("https://example.com/").unwrap());
+        assert_eq!(native.origin(), None);
+        assert!(native.origin_allowed(&allowed));
+    }
+
+    #[test]
+    fn resumption_token_roundtrip() {
+        let token = ResumptionToken::random();
+
+        let resp = ConnectResponse::OK.with_resumption_token(token.clone());
+        let mut buf = Vec::new();
+        resp.encode(&mut buf).unwrap();
+        let decoded = ConnectResponse::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.resumption_token.as_ref(), Some(&token));
+
+        let req = ConnectRequest::new(Url::parse("https://example.com/").unwrap())
+            .with_resumption_token(&token);
+        let mut buf = Vec::new();
+        req.encode(&mut buf).unwrap();
+        let decoded = ConnectRequest::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.resumption_token(), Some(token));
+    }
+
+    #[test]
+    fn rejection_keeps_headers() {
+        let resp = ConnectResponse::new(http::StatusCode::UNAUTHORIZED)
+            .with_header(
+                http::header::WWW_AUTHENTICATE,
+                http::HeaderValue::from_static("Bearer realm=\"moq\""),
+            )
+            .with_header(
+                http::HeaderName::from_static("location"),
+                http::HeaderValue::from_static("https://ignored.example/"),
+            );
+        let mut buf = Vec::new();
+        resp.encode(&mut buf).unwrap();
+
+        // Only the caller that asks for rejections gets them.
+        assert!(matches!(
+            ConnectResponse::decode(&mut buf.as_slice()),
+            Err(ConnectError::WrongStatus(Some(
+                http::StatusCode::UNAUTHORIZED
+            )))
+        ));
+
+        let decoded = ConnectResponse::decode_any_status(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.status, http::StatusCode::UNAUTHORIZED);
+        let headers = decoded.headers.unwrap();
+        assert_eq!(
+            headers.get(http::header::WWW_AUTHENTICATE).unwrap(),
+            "Bearer realm=\"moq\""
+        );
+        // Headers derived from the other fields aren't duplicated in the map.
+        assert_eq!(decoded.location, None);
+        assert!(!headers.contains_key("sec-webtransport-http3-draft"));
+    }
+
+    #[test]
+    fn retry_after_rounds_up() {
+        let resp = ConnectResponse::new(http::StatusCode::SERVICE_UNAVAILABLE)
+            .with_retry_after(Duration::from_millis(1500));
+        let headers = resp.headers.unwrap();
+        assert_eq!(headers.get(http::header::RETRY_AFTER).unwrap(), "2");
+    }
+
+    #[test]
+    fn resumption_token_from_query() {
+        let url = "https://example.com/moq?wt-resumption-token=abc_-9";
+        let req = ConnectRequest::decode(&mut encode_request(url).as_slice()).unwrap();
+        assert_eq!(req.resumption_token().unwrap().as_str(), "abc_-9");
+
+        let url = "https://example.com/moq?wt-resumption-token=not+valid";
+        let req = ConnectRequest::decode(&mut encode_request(url).as_slice()).unwrap();
+        assert_eq!(req.resumption_token(), None);
+    }
 }
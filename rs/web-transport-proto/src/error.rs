@@ -2,17 +2,66 @@
 const ERROR_FIRST: u64 = 0x52e4a40fa8db;
 const ERROR_LAST: u64 = 0x52e5ac983162;
 
+// Every 0x1f-th code in the range is a reserved HTTP/3 codepoint, which WebTransport skips.
+const ERROR_RESERVED: u64 = 0x21;
+
+/// Recover the WebTransport error code from an HTTP/3 one, as carried by RESET_STREAM,
+/// STOP_SENDING or CONNECTION_CLOSE.
+///
+/// Returns None if the code is outside the WebTransport range, or is one of the reserved
+/// codepoints within it, neither of which [error_to_http3] ever produces.
 pub const fn error_from_http3(code: u64) -> Option<u32> {
     if code < ERROR_FIRST || code > ERROR_LAST {
         return None;
     }
 
+    if (code - ERROR_RESERVED).is_multiple_of(0x1f) {
+        return None;
+    }
+
     let code = code - ERROR_FIRST;
     let code = code - code / 0x1f;
 
     Some(code as u32)
 }
 
+/// Map a WebTransport error code into the HTTP/3 error space, for use on the wire.
+///
+/// Every `u32` has a distinct code, and [error_from_http3] reverses it.
 pub const fn error_to_http3(code: u32) -> u64 {
     ERROR_FIRST + code as u64 + code as u64 / 0x1e
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for code in [0, 1, 0x1d, 0x1e, 0x1f, 0x3c, 0xffff, u32::MAX - 1, u32::MAX] {
+            assert_eq!(error_from_http3(error_to_http3(code)), Some(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(error_to_http3(0), ERROR_FIRST);
+        assert_eq!(error_to_http3(u32::MAX), ERROR_LAST);
+
+        assert_eq!(error_from_http3(0), None);
+        assert_eq!(error_from_http3(ERROR_FIRST - 1), None);
+        assert_eq!(error_from_http3(ERROR_LAST + 1), None);
+    }
+
+    #[test]
+    fn reserved() {
+        // The codepoint after 0x1d is skipped, so 0x1e lands one past it.
+        assert_eq!(error_to_http3(0x1d) + 2, error_to_http3(0x1e));
+        assert_eq!(error_from_http3(error_to_http3(0x1d) + 1), None);
+
+        let reserved = ERROR_FIRST + 0x1e + 0x1f * 1000;
+        assert_eq!(error_from_http3(reserved), None);
+        assert_eq!(error_from_http3(reserved - 1), Some(0x1e * 1001 - 1));
+        assert_eq!(error_from_http3(reserved + 1), Some(0x1e * 1001));
+    }
+}
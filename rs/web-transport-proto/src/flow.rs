@@ -0,0 +1,412 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{Capsule, VarInt};
+
+/// Session-level flow control limits, as sent in SETTINGS and raised by capsules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowLimits {
+    /// The total bytes of stream data that may be sent within the session.
+    pub max_data: u64,
+
+    /// The number of unidirectional streams that may be opened within the session.
+    pub max_streams_uni: u64,
+
+    /// The number of bidirectional streams that may be opened within the session.
+    pub max_streams_bidi: u64,
+}
+
+impl FlowLimits {
+    /// As much as the wire allows.
+    ///
+    /// The connection's flow control already bounds the peer across all of its sessions,
+    /// so there's no reason to advertise anything smaller.
+    pub const UNLIMITED: Self = Self {
+        max_data: VarInt::MAX.into_inner(),
+        max_streams_uni: VarInt::MAX.into_inner(),
+        max_streams_bidi: VarInt::MAX.into_inner(),
+    };
+}
+
+/// Enforces the peer's session-level flow control limits on what we send.
+///
+/// The peer starts us with the limits in its SETTINGS and raises them with `WT_MAX_DATA`
+/// and `WT_MAX_STREAMS` capsules. Opening a stream or writing data waits for credit,
+/// queueing a `WT_DATA_BLOCKED` or `WT_STREAMS_BLOCKED` capsule for the backend to write
+/// on the CONNECT stream.
+///
+/// A peer that sent no limits doesn't use session-level flow control, so nothing waits.
+/// Cloning is cheap; clones share the same credit.
+#[derive(Clone, Default)]
+pub struct SessionFlow {
+    state: Arc<Mutex<FlowState>>,
+}
+
+#[derive(Default)]
+struct FlowState {
+    data: Credit,
+    uni: Credit,
+    bidi: Credit,
+
+    // Woken when a limit is raised or the session closes.
+    blocked: Vec<Waker>,
+
+    // Capsules waiting to be written on the CONNECT stream.
+    outgoing: VecDeque<Capsule>,
+    outgoing_waker: Option<Waker>,
+
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Credit {
+    // None if the peer doesn't limit us.
+    max: Option<u64>,
+    used: u64,
+
+    // The limit we last reported being blocked at, so it's only reported once.
+    blocked_at: Option<u64>,
+}
+
+impl Credit {
+    fn new(max: Option<u64>) -> Self {
+        Self {
+            max,
+            ..Default::default()
+        }
+    }
+
+    // Take up to `want`, returning how much was granted.
+    fn take(&mut self, want: u64) -> u64 {
+        let granted = match self.max {
+            Some(max) => want.min(max.saturating_sub(self.used)),
+            None => want,
+        };
+        self.used += granted;
+        granted
+    }
+
+    // Returns the limit to report as blocked, unless it was already reported.
+    fn block(&mut self) -> Option<u64> {
+        let max = self.max?;
+        if self.blocked_at == Some(max) {
+            return None;
+        }
+        self.blocked_at = Some(max);
+        Some(max)
+    }
+
+    // Limits only ever increase; anything else is ignored.
+    fn raise(&mut self, max: u64) -> bool {
+        match self.max {
+            Some(current) if max > current => {
+                self.max = Some(max);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FlowState {
+    fn queue(&mut self, capsule: Capsule) -> Option<Waker> {
+        self.outgoing.push_back(capsule);
+        self.outgoing_waker.take()
+    }
+
+    fn wait(&mut self, waker: &Waker) {
+        if !self.blocked.iter().any(|w| w.will_wake(waker)) {
+            self.blocked.push(waker.clone());
+        }
+    }
+}
+
+impl SessionFlow {
+    /// Start with the limits from the peer's SETTINGS, or None if it sent none.
+    pub fn new(peer: Option<FlowLimits>) -> Self {
+        let state = FlowState {
+            data: Credit::new(peer.map(|l| l.max_data)),
+            uni: Credit::new(peer.map(|l| l.max_streams_uni)),
+            bidi: Credit::new(peer.map(|l| l.max_streams_bidi)),
+            ..Default::default()
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Claim a stream, or wait until the peer allows another.
+    pub fn poll_open(&self, cx: &mut Context<'_>, bidi: bool) -> Poll<()> {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Poll::Ready(());
+            }
+
+            let credit = if bidi {
+                &mut state.bidi
+            } else {
+                &mut state.uni
+            };
+            if credit.take(1) == 1 {
+                return Poll::Ready(());
+            }
+
+            let blocked = credit.block();
+            state.wait(cx.waker());
+            blocked.and_then(|limit| state.queue(Capsule::WtStreamsBlocked { bidi, limit }))
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Poll::Pending
+    }
+
+    /// Return how many streams have been claimed, and how many more the peer allows.
+    ///
+    /// The second is None if the peer doesn't limit streams.
+    pub fn streams(&self, bidi: bool) -> (u64, Option<u64>) {
+        let state = self.state.lock().unwrap();
+        let credit = if bidi { &state.bidi } else { &state.uni };
+        let remaining = credit.max.map(|max| max.saturating_sub(credit.used));
+        (credit.used, remaining)
+    }
+
+    /// Claim credit to send up to `len` bytes, or wait until there's at least one.
+    ///
+    /// Returns how many bytes may be sent. Give back any that weren't with [Self::unsend].
+    pub fn poll_send(&self, cx: &mut Context<'_>, len: usize) -> Poll<usize> {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed || len == 0 {
+                return Poll::Ready(len);
+            }
+
+            let granted = state.data.take(len as u64);
+            if granted > 0 {
+                return Poll::Ready(granted as usize);
+            }
+
+            let blocked = state.data.block();
+            state.wait(cx.waker());
+            blocked.and_then(|limit| state.queue(Capsule::WtDataBlocked { limit }))
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Poll::Pending
+    }
+
+    /// Return credit claimed by [Self::poll_send] for bytes that weren't sent after all.
+    pub fn unsend(&self, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.data.used = state.data.used.saturating_sub(len as u64);
+            std::mem::take(&mut state.blocked)
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Wait for credit to send up to `len` bytes, see [Self::poll_send].
+    ///
+    /// Credit that isn't marked [FlowReservation::sent] is given back on drop, so a
+    /// cancelled write doesn't leak it.
+    pub async fn reserve(&self, len: usize) -> FlowReservation {
+        let granted = std::future::poll_fn(|cx| self.poll_send(cx, len)).await;
+        FlowReservation {
+            flow: self.clone(),
+            granted,
+        }
+    }
+
+    /// Apply a flow control capsule from the peer. Other capsules are ignored.
+    pub fn on_capsule(&self, capsule: &Capsule) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            let raised = match *capsule {
+                Capsule::WtMaxData { max } => state.data.raise(max),
+                Capsule::WtMaxStreams { bidi: true, max } => state.bidi.raise(max),
+                Capsule::WtMaxStreams { bidi: false, max } => state.uni.raise(max),
+                _ => false,
+            };
+
+            if !raised {
+                return;
+            }
+            std::mem::take(&mut state.blocked)
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// The next capsule to write on the CONNECT stream, or None once closed.
+    pub fn poll_capsule(&self, cx: &mut Context<'_>) -> Poll<Option<Capsule>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(capsule) = state.outgoing.pop_front() {
+            return Poll::Ready(Some(capsule));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+
+        state.outgoing_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Stop enforcing limits once the session is gone, so waiters can observe the error.
+    pub fn close(&self) {
+        let (wakers, waker) = {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            (
+                std::mem::take(&mut state.blocked),
+                state.outgoing_waker.take(),
+            )
+        };
+
+        for waker in wakers.into_iter().chain(waker) {
+            waker.wake();
+        }
+    }
+}
+
+/// Credit claimed by [SessionFlow::reserve].
+#[must_use]
+pub struct FlowReservation {
+    flow: SessionFlow,
+    granted: usize,
+}
+
+impl FlowReservation {
+    /// The number of bytes that may be sent.
+    pub fn size(&self) -> usize {
+        self.granted
+    }
+
+    /// Keep the credit for the `len` bytes that were sent, giving back the rest.
+    pub fn sent(mut self, len: usize) {
+        self.granted -= len.min(self.granted);
+    }
+}
+
+impl Drop for FlowReservation {
+    fn drop(&mut self) {
+        self.flow.unsend(self.granted);
+    }
+}
+
+impl fmt::Debug for SessionFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("SessionFlow")
+            .field("max_data", &state.data.max)
+            .field("sent_data", &state.data.used)
+            .field("max_streams_uni", &state.uni.max)
+            .field("max_streams_bidi", &state.bidi.max)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cx() -> Context<'static> {
+        Context::from_waker(Waker::noop())
+    }
+
+    #[test]
+    fn unlimited_without_settings() {
+        let flow = SessionFlow::new(None);
+        assert_eq!(flow.poll_send(&mut cx(), 1 << 30), Poll::Ready(1 << 30));
+        assert_eq!(flow.poll_open(&mut cx(), true), Poll::Ready(()));
+        assert_eq!(flow.poll_capsule(&mut cx()), Poll::Pending);
+    }
+
+    #[test]
+    fn streams_blocked_until_raised() {
+        let flow = SessionFlow::new(Some(FlowLimits {
+            max_data: 0,
+            max_streams_uni: 1,
+            max_streams_bidi: 0,
+        }));
+
+        assert_eq!(flow.poll_open(&mut cx(), false), Poll::Ready(()));
+        assert_eq!(flow.poll_open(&mut cx(), false), Poll::Pending);
+        assert_eq!(flow.poll_open(&mut cx(), false), Poll::Pending);
+
+        // Blocked is only reported once per limit.
+        assert_eq!(
+            flow.poll_capsule(&mut cx()),
+            Poll::Ready(Some(Capsule::WtStreamsBlocked {
+                bidi: false,
+                limit: 1
+            }))
+        );
+        assert_eq!(flow.poll_capsule(&mut cx()), Poll::Pending);
+
+        // Lowering a limit is ignored.
+        flow.on_capsule(&Capsule::WtMaxStreams {
+            bidi: false,
+            max: 0,
+        });
+        assert_eq!(flow.poll_open(&mut cx(), false), Poll::Pending);
+
+        flow.on_capsule(&Capsule::WtMaxStreams {
+            bidi: false,
+            max: 2,
+        });
+        assert_eq!(flow.poll_open(&mut cx(), false), Poll::Ready(()));
+        assert_eq!(flow.poll_open(&mut cx(), true), Poll::Pending);
+
+        assert_eq!(flow.streams(false), (2, Some(0)));
+        assert_eq!(flow.streams(true), (0, Some(0)));
+        assert_eq!(SessionFlow::new(None).streams(true), (0, None));
+    }
+
+    #[test]
+    fn data_is_granted_partially() {
+        let flow = SessionFlow::new(Some(FlowLimits {
+            max_data: 10,
+            ..FlowLimits::UNLIMITED
+        }));
+
+        assert_eq!(flow.poll_send(&mut cx(), 8), Poll::Ready(8));
+        assert_eq!(flow.poll_send(&mut cx(), 8), Poll::Ready(2));
+        assert_eq!(flow.poll_send(&mut cx(), 8), Poll::Pending);
+        assert_eq!(
+            flow.poll_capsule(&mut cx()),
+            Poll::Ready(Some(Capsule::WtDataBlocked { limit: 10 }))
+        );
+
+        // Unsent credit can be claimed again.
+        flow.unsend(1);
+        assert_eq!(flow.poll_send(&mut cx(), 8), Poll::Ready(1));
+
+        flow.on_capsule(&Capsule::WtMaxData { max: 20 });
+        assert_eq!(flow.poll_send(&mut cx(), 100), Poll::Ready(10));
+
+        // Closing stops enforcement and ends the capsule queue.
+        flow.close();
+        assert_eq!(flow.poll_send(&mut cx(), 100), Poll::Ready(100));
+        assert_eq!(flow.poll_capsule(&mut cx()), Poll::Ready(None));
+    }
+}
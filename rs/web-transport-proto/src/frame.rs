@@ -27,6 +27,19 @@ impl Frame {
         }
     }
 
+    /// Whether HTTP/3 reserves this type, either for GREASE or because HTTP/2 used it.
+    ///
+    /// GREASE frames are skipped, but receiving one of the HTTP/2 types is an
+    /// H3_FRAME_UNEXPECTED error (RFC 9114 Section 7.2.8).
+    pub fn is_reserved(&self) -> bool {
+        self.is_grease() || matches!(self.0.into_inner(), 0x02 | 0x06 | 0x08 | 0x09)
+    }
+
+    /// Whether this is an extension frame we don't understand, which must be skipped.
+    pub fn is_unknown(&self) -> bool {
+        !self.is_reserved() && !Self::KNOWN.contains(self)
+    }
+
     pub fn read<B: Buf>(
         buf: &mut B,
     ) -> Result<(Frame, bytes::buf::Take<&mut B>), VarIntUnexpectedEnd> {
@@ -52,6 +65,9 @@ macro_rules! frames {
     {$($name:ident = $val:expr,)*} => {
         impl Frame {
             $(pub const $name: Frame = Frame(VarInt::from_u32($val));)*
+
+            // Every frame type defined below.
+            const KNOWN: &'static [Frame] = &[$(Frame::$name,)*];
         }
     }
 }
@@ -63,10 +79,47 @@ macro_rules! frames {
 /// crafted frame-length field.
 pub(crate) const MAX_FRAME_SIZE: u64 = 65536;
 
-// Sent at the start of a bidirectional stream.
 frames! {
+    // Sent on request streams, including the CONNECT stream.
     DATA = 0x00,
     HEADERS = 0x01,
+
+    // Sent on the control stream.
     SETTINGS = 0x04,
+    GOAWAY = 0x07,
+
+    // Server push, which WebTransport endpoints never enable.
+    CANCEL_PUSH = 0x03,
+    PUSH_PROMISE = 0x05,
+    MAX_PUSH_ID = 0x0d,
+
+    // Sent at the start of a bidirectional stream.
     WEBTRANSPORT = 0x41,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_and_unknown() {
+        // PRIORITY, PING, WINDOW_UPDATE and CONTINUATION from HTTP/2.
+        for typ in [0x02, 0x06, 0x08, 0x09] {
+            let frame = Frame(VarInt::from_u32(typ));
+            assert!(frame.is_reserved());
+            assert!(!frame.is_unknown());
+        }
+
+        let grease = Frame(VarInt::from_u32(0x21 + 0x1f));
+        assert!(grease.is_reserved());
+        assert!(!grease.is_unknown());
+
+        for frame in [Frame::HEADERS, Frame::PUSH_PROMISE, Frame::WEBTRANSPORT] {
+            assert!(!frame.is_reserved());
+            assert!(!frame.is_unknown());
+        }
+
+        // ORIGIN (RFC 9412) is a real extension, just not one we implement.
+        assert!(Frame(VarInt::from_u32(0x0c)).is_unknown());
+    }
+}
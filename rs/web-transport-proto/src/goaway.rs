@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{log_frame, Direction, Frame, VarInt, WireFrame, MAX_FRAME_SIZE};
+
+/// An HTTP/3 GOAWAY frame, sent on the control stream to start a graceful shutdown.
+///
+/// From a server, `id` is the first client-initiated bidirectional stream it won't
+/// process: CONNECT requests on it or any later stream should be retried on a new
+/// connection, while sessions on earlier streams keep running. From a client it's a
+/// push ID, which WebTransport never uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoAway {
+    pub id: VarInt,
+}
+
+#[derive(Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum GoAwayError {
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+
+    #[error("unexpected frame {0:?}")]
+    UnexpectedFrame(Frame),
+
+    #[error("invalid size")]
+    InvalidSize,
+
+    #[error("frame too large")]
+    FrameTooLarge,
+
+    #[error("io error: {0}")]
+    Io(Arc<std::io::Error>),
+}
+
+impl From<std::io::Error> for GoAwayError {
+    fn from(err: std::io::Error) -> Self {
+        GoAwayError::Io(Arc::new(err))
+    }
+}
+
+impl GoAway {
+    /// A GOAWAY from a server that processed every request before `id`.
+    pub fn new(id: VarInt) -> Self {
+        Self { id }
+    }
+
+    pub fn decode<B: Buf>(buf: &mut B) -> Result<Self, GoAwayError> {
+        let (typ, mut data) = Frame::read(buf).map_err(|_| GoAwayError::UnexpectedEnd)?;
+        if typ != Frame::GOAWAY {
+            return Err(GoAwayError::UnexpectedFrame(typ));
+        }
+
+        Self::decode_payload(&mut data)
+    }
+
+    // The payload is a single varint, with nothing after it.
+    fn decode_payload<B: Buf>(data: &mut B) -> Result<Self, GoAwayError> {
+        let id = VarInt::decode(data).map_err(|_| GoAwayError::InvalidSize)?;
+        if data.has_remaining() {
+            return Err(GoAwayError::InvalidSize);
+        }
+
+        Ok(Self { id })
+    }
+
+    /// Read frames from the control stream, after the SETTINGS, until the next GOAWAY.
+    ///
+    /// Frames that may appear on the control stream but don't matter to WebTransport, like
+    /// MAX_PUSH_ID and unknown extensions, are skipped. Any other frame, including a
+    /// second SETTINGS, is an error, as is the stream ending: the control stream must
+    /// stay open for the life of the connection (RFC 9114 Section 6.2.1).
+    pub async fn read<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Self, GoAwayError> {
+        loop {
+            let typ = Frame(
+                VarInt::read(stream)
+                    .await
+                    .map_err(|_| GoAwayError::UnexpectedEnd)?,
+            );
+            let size = VarInt::read(stream)
+                .await
+                .map_err(|_| GoAwayError::UnexpectedEnd)?
+                .into_inner();
+
+            if size > MAX_FRAME_SIZE {
+                return Err(GoAwayError::FrameTooLarge);
+            }
+
+            let mut payload = stream.take(size);
+
+            let skip = typ.is_grease()
+                || typ.is_unknown()
+                || typ == Frame::MAX_PUSH_ID
+                || typ == Frame::CANCEL_PUSH;
+            if skip {
+                let n = tokio::io::copy(&mut payload, &mut tokio::io::sink()).await?;
+                if n < size {
+                    return Err(GoAwayError::UnexpectedEnd);
+                }
+                continue;
+            }
+
+            if typ != Frame::GOAWAY {
+                return Err(GoAwayError::UnexpectedFrame(typ));
+            }
+
+            let mut buf = Vec::with_capacity(size as usize);
+            payload.read_to_end(&mut buf).await?;
+            if buf.len() < size as usize {
+                return Err(GoAwayError::UnexpectedEnd);
+            }
+
+            let goaway = Self::decode_payload(&mut buf.as_slice())?;
+            log_frame(Direction::Received, WireFrame::GoAway(&goaway));
+            return Ok(goaway);
+        }
+    }
+
+    pub fn encode<B: BufMut>(&self, buf: &mut B) {
+        Frame::GOAWAY.encode(buf);
+        VarInt::from_u32(self.id.size() as u32).encode(buf);
+        self.id.encode(buf);
+    }
+
+    pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), GoAwayError> {
+        log_frame(Direction::Sent, WireFrame::GoAway(self));
+
+        let mut buf = BytesMut::new();
+        self.encode(&mut buf);
+        stream.write_all_buf(&mut buf).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(typ: u32, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        VarInt::from_u32(typ).encode(&mut buf);
+        VarInt::from_u32(payload.len() as u32).encode(&mut buf);
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[tokio::test]
+    async fn roundtrip() {
+        let goaway = GoAway::new(VarInt::from_u32(16_384));
+
+        let mut buf = Vec::new();
+        goaway.encode(&mut buf);
+        assert_eq!(GoAway::decode(&mut buf.as_slice()).unwrap(), goaway);
+
+        let mut stream = buf.as_slice();
+        assert_eq!(GoAway::read(&mut stream).await.unwrap(), goaway);
+        assert!(stream.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_skips_other_control_frames() {
+        let mut buf = Vec::new();
+        buf.extend(frame(0x0d, &[4])); // MAX_PUSH_ID
+        buf.extend(frame(0x21 + 0x1f, b"grease"));
+        buf.extend(frame(0x0c, b"origin")); // ORIGIN, unknown to us
+        GoAway::new(VarInt::from_u32(8)).encode(&mut buf);
+
+        let goaway = GoAway::read(&mut buf.as_slice()).await.unwrap();
+        assert_eq!(goaway.id, VarInt::from_u32(8));
+    }
+
+    #[tokio::test]
+    async fn read_rejects_unexpected_frames() {
+        for typ in [0x00, 0x01, 0x04] {
+            let buf = frame(typ, &[]);
+            let err = GoAway::read(&mut buf.as_slice()).await.unwrap_err();
+            assert!(
+                matches!(err, GoAwayError::UnexpectedFrame(f) if f.0.into_inner() == typ as u64)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn read_rejects_bad_payloads() {
+        let buf = frame(0x07, &[4, 0]);
+        let err = GoAway::read(&mut buf.as_slice()).await.unwrap_err();
+        assert!(matches!(err, GoAwayError::InvalidSize));
+
+        // A control stream must never end.
+        let err = GoAway::read(&mut &b""[..]).await.unwrap_err();
+        assert!(matches!(err, GoAwayError::UnexpectedEnd));
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::Capsule;
+
+/// Something that happened to a session, as recorded by [SessionHistory].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionEventKind {
+    /// The application opened a stream with this QUIC stream ID.
+    StreamOpened { id: u64, bidi: bool },
+
+    /// The application accepted a stream with this QUIC stream ID.
+    StreamAccepted { id: u64, bidi: bool },
+
+    /// A capsule was written to the CONNECT stream.
+    CapsuleSent(Capsule),
+
+    /// A capsule was read from the CONNECT stream.
+    CapsuleReceived(Capsule),
+
+    /// Something went wrong, such as a malformed capsule or a lost connection.
+    Error(String),
+}
+
+/// A [SessionEventKind] and when it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEvent {
+    /// How long after the session was established.
+    pub elapsed: Duration,
+    pub kind: SessionEventKind,
+}
+
+/// The most recent events of a session, kept for post-mortem debugging.
+///
+/// A bounded ring buffer: once `capacity` events are held, each new one evicts the
+/// oldest. A capacity of 0, the default, records nothing and costs next to nothing,
+/// so backends record unconditionally and leave the choice to the application.
+///
+/// Cloning is cheap; clones share the same events.
+#[derive(Clone)]
+pub struct SessionHistory {
+    inner: Arc<Mutex<HistoryState>>,
+}
+
+struct HistoryState {
+    start: Instant,
+    capacity: usize,
+    events: VecDeque<SessionEvent>,
+}
+
+impl SessionHistory {
+    /// Keep the last `capacity` events, timed from now.
+    pub fn new(capacity: usize) -> Self {
+        let state = HistoryState {
+            start: Instant::now(),
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        };
+
+        Self {
+            inner: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Keep the last `capacity` events from now on, dropping the oldest if over.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut state = self.inner.lock().unwrap();
+        state.capacity = capacity;
+
+        let excess = state.events.len().saturating_sub(capacity);
+        state.events.drain(..excess);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.lock().unwrap().capacity
+    }
+
+    /// Record an event, evicting the oldest if full.
+    pub fn record(&self, kind: SessionEventKind) {
+        let mut state = self.inner.lock().unwrap();
+        if state.capacity == 0 {
+            return;
+        }
+
+        if state.events.len() == state.capacity {
+            state.events.pop_front();
+        }
+
+        let elapsed = state.start.elapsed();
+        state.events.push_back(SessionEvent { elapsed, kind });
+    }
+
+    /// A copy of the recorded events, oldest first.
+    pub fn events(&self) -> Vec<SessionEvent> {
+        self.inner.lock().unwrap().events.iter().cloned().collect()
+    }
+}
+
+impl Default for SessionHistory {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl fmt::Debug for SessionHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.events()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest() {
+        let history = SessionHistory::new(2);
+        for id in 0..3 {
+            history.record(SessionEventKind::StreamOpened { id, bidi: true });
+        }
+
+        let ids: Vec<_> = history
+            .events()
+            .into_iter()
+            .map(|event| match event.kind {
+                SessionEventKind::StreamOpened { id, .. } => id,
+                kind => panic!("unexpected event: {kind:?}"),
+            })
+            .collect();
+        assert_eq!(ids, [1, 2]);
+
+        history.set_capacity(1);
+        assert_eq!(history.events().len(), 1);
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let history = SessionHistory::default();
+        history.record(SessionEventKind::Error("lost".to_string()));
+        assert!(history.events().is_empty());
+    }
+}
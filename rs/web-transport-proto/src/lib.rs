@@ -1,18 +1,38 @@
 mod capsule;
 mod connect;
 mod error;
+mod flow;
 mod frame;
+mod goaway;
+mod history;
+mod limit;
+mod request;
+mod resumption;
+mod retry;
 mod settings;
 mod stream;
+mod target;
 mod varint;
+mod wire;
 
 pub use capsule::*;
 pub use connect::*;
 pub use error::*;
+pub use flow::*;
 pub use frame::*;
+pub use goaway::*;
+pub use history::*;
+pub use limit::*;
+pub use request::*;
+pub use resumption::*;
+pub use retry::*;
 pub use settings::*;
 pub use stream::*;
+pub use target::*;
 pub use varint::*;
+pub use wire::*;
+
+pub mod codes;
 
 pub use http;
 
@@ -0,0 +1,329 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::ConnectRequest;
+
+type KeyFn = Arc<dyn Fn(&ConnectRequest) -> Option<String> + Send + Sync>;
+
+// The number of active sessions for each key.
+type ActiveCounts = Arc<Mutex<HashMap<String, usize>>>;
+
+/// Caps the number of concurrent sessions per key, by default the request path.
+///
+/// Servers check each CONNECT request against the limits before handing it to the
+/// application, and answer 503 (Service Unavailable) once a key is full. An accepted
+/// session holds its slot until every clone of it is dropped.
+///
+/// ```
+/// # use web_transport_proto::SessionLimits;
+/// let limits = SessionLimits::new()
+///     .with_limit("/broadcast", 100)
+///     .with_default_limit(10);
+/// ```
+///
+/// Cloning is cheap; clones share the same counts, so one set of limits can span
+/// several servers.
+#[derive(Clone)]
+pub struct SessionLimits {
+    key: KeyFn,
+    limits: HashMap<String, usize>,
+    default: Option<usize>,
+    active: ActiveCounts,
+}
+
+impl SessionLimits {
+    /// Create limits keyed by the request path, with nothing limited yet.
+    pub fn new() -> Self {
+        Self {
+            key: Arc::new(|request| Some(request.url.path().to_string())),
+            limits: HashMap::new(),
+            default: None,
+            active: Arc::default(),
+        }
+    }
+
+    /// Allow at most `max` concurrent sessions for `key`.
+    pub fn with_limit(mut self, key: impl Into<String>, max: usize) -> Self {
+        self.limits.insert(key.into(), max);
+        self
+    }
+
+    /// Allow at most `max` concurrent sessions for any key without its own limit.
+    ///
+    /// Unlimited by default.
+    pub fn with_default_limit(mut self, max: usize) -> Self {
+        self.default = Some(max);
+        self
+    }
+
+    /// Group requests with `key` instead of by path.
+    ///
+    /// Requests mapped to `None` are never limited, nor counted.
+    pub fn with_key(
+        mut self,
+        key: impl Fn(&ConnectRequest) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.key = Arc::new(key);
+        self
+    }
+
+    /// The number of sessions currently holding a slot for `key`.
+    pub fn active(&self, key: &str) -> usize {
+        self.active.lock().unwrap().get(key).copied().unwrap_or(0)
+    }
+
+    /// Claim a slot for `request`, or return None if its key is at the limit.
+    pub fn acquire(&self, request: &ConnectRequest) -> Option<SessionPermit> {
+        let Some(key) = (self.key)(request) else {
+            return Some(SessionPermit { slot: None });
+        };
+
+        let max = self.limits.get(&key).copied().or(self.default);
+
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(key.clone()).or_default();
+        if max.is_some_and(|max| *count >= max) {
+            if *count == 0 {
+                active.remove(&key);
+            }
+            return None;
+        }
+        *count += 1;
+
+        Some(SessionPermit {
+            slot: Some((self.active.clone(), key)),
+        })
+    }
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SessionLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionLimits")
+            .field("limits", &self.limits)
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+/// A slot claimed from [SessionLimits], released when dropped.
+pub struct SessionPermit {
+    slot: Option<(ActiveCounts, String)>,
+}
+
+impl Drop for SessionPermit {
+    fn drop(&mut self) {
+        let Some((active, key)) = self.slot.take() else {
+            return;
+        };
+
+        let mut active = active.lock().unwrap();
+        if let Some(count) = active.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&key);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SessionPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionPermit")
+            .field("key", &self.slot.as_ref().map(|(_, key)| key))
+            .finish()
+    }
+}
+
+/// Bounds how many handshaken sessions can wait for the application to accept them.
+///
+/// Servers finish each handshake in the background and hold the request until the
+/// accept loop asks for it. Once `max` are waiting, any more are answered with 503
+/// (Service Unavailable) and a `retry-after` header, so a stalled accept loop sheds
+/// load instead of quietly piling up sessions.
+///
+/// Cloning is cheap; clones share the same counts, so keep one to report [Self::len]
+/// and [Self::rejected] as metrics.
+#[derive(Clone)]
+pub struct AcceptQueue {
+    max: usize,
+    retry_after: Duration,
+    state: Arc<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+impl AcceptQueue {
+    /// How long rejected clients are told to wait before trying again, unless changed.
+    pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+    /// Hold at most `max` sessions waiting to be accepted.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            retry_after: Self::DEFAULT_RETRY_AFTER,
+            state: Arc::default(),
+        }
+    }
+
+    /// Tell rejected clients to wait this long before trying again.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// How long rejected clients are told to wait before trying again.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+
+    /// The number of sessions waiting to be accepted.
+    pub fn len(&self) -> usize {
+        self.state.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of sessions rejected so far because the queue was full.
+    pub fn rejected(&self) -> u64 {
+        self.state.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Claim a place in the queue, or return None and count a rejection if it's full.
+    pub fn push(&self) -> Option<QueueSlot> {
+        let claimed =
+            self.state
+                .queued
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                    (queued < self.max).then_some(queued + 1)
+                });
+
+        match claimed {
+            Ok(_) => Some(QueueSlot {
+                state: self.state.clone(),
+            }),
+            Err(_) => {
+                self.state.rejected.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AcceptQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptQueue")
+            .field("max", &self.max)
+            .field("retry_after", &self.retry_after)
+            .field("len", &self.len())
+            .field("rejected", &self.rejected())
+            .finish()
+    }
+}
+
+/// A place claimed in an [AcceptQueue], released when dropped.
+pub struct QueueSlot {
+    state: Arc<QueueState>,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        self.state.queued.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl fmt::Debug for QueueSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueueSlot").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> ConnectRequest {
+        ConnectRequest::new(url::Url::parse(&format!("https://example.com{path}")).unwrap())
+    }
+
+    #[test]
+    fn limits_per_path() {
+        let limits = SessionLimits::new().with_limit("/broadcast", 2);
+
+        let a = limits.acquire(&request("/broadcast")).unwrap();
+        let _b = limits.acquire(&request("/broadcast")).unwrap();
+        assert!(limits.acquire(&request("/broadcast")).is_none());
+        assert_eq!(limits.active("/broadcast"), 2);
+
+        // Other paths are unlimited, but still counted.
+        let _other = limits.acquire(&request("/other")).unwrap();
+        assert_eq!(limits.active("/other"), 1);
+
+        drop(a);
+        assert_eq!(limits.active("/broadcast"), 1);
+        assert!(limits.acquire(&request("/broadcast")).is_some());
+    }
+
+    #[test]
+    fn default_limit_and_custom_key() {
+        let limits = SessionLimits::new()
+            .with_default_limit(0)
+            .with_limit("tenant", 1)
+            .with_key(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .find(|(name, _)| name == "tenant")
+                    .map(|_| "tenant".to_string())
+            });
+
+        // No key: never limited.
+        assert!(limits.acquire(&request("/")).is_some());
+
+        let _permit = limits.acquire(&request("/?tenant=a")).unwrap();
+        assert!(limits.acquire(&request("/?tenant=b")).is_none());
+
+        // A zero default rejects everything else.
+        let limits = limits.with_key(|request| Some(request.url.path().to_string()));
+        assert!(limits.acquire(&request("/anything")).is_none());
+        assert_eq!(limits.active("/anything"), 0);
+    }
+
+    #[test]
+    fn accept_queue_overflow() {
+        let queue = AcceptQueue::new(2);
+
+        let a = queue.push().unwrap();
+        let _b = queue.push().unwrap();
+        assert!(queue.push().is_none());
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.rejected(), 1);
+
+        // Accepting a session makes room for the next.
+        drop(a);
+        assert_eq!(queue.len(), 1);
+        assert!(queue.push().is_some());
+    }
+}
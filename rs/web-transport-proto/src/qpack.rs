@@ -2,14 +2,13 @@
 // By refusing to acknowledge the QPACK encoder, we can avoid implementing the dynamic table altogether.
 // This is not recommended for a full HTTP/3 implementation but it's literally more efficient for handling a single WebTransport CONNECT request.
 
-use std::collections::HashMap;
-
 use bytes::{Buf, BufMut};
 
 use super::huffman::{self, HpackStringDecode};
 use thiserror::Error;
 
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum DecodeError {
     #[error("unexpected end of input")]
     UnexpectedEnd,
@@ -28,6 +27,27 @@ pub enum DecodeError {
 
     #[error("invalid utf8 header")] // technically not required by the HTTP spec
     Utf8Error(#[from] std::str::Utf8Error),
+
+    #[error(transparent)]
+    Field(#[from] FieldError),
+}
+
+// A field section that is malformed, whether we're encoding or decoding it.
+// See: https://www.rfc-editor.org/rfc/rfc9114.html#section-4.2
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldError {
+    #[error("invalid header name {0:?}")]
+    InvalidName(String),
+
+    #[error("invalid value for header {0:?}")]
+    InvalidValue(String),
+
+    #[error("duplicate pseudo-header {0:?}")]
+    DuplicatePseudo(String),
+
+    #[error("pseudo-header {0:?} after a regular header")]
+    LatePseudo(String),
 }
 
 #[cfg(target_pointer_width = "64")]
@@ -37,18 +57,75 @@ const MAX_POWER: usize = 10 * 7;
 const MAX_POWER: usize = 5 * 7;
 
 // Simple QPACK implementation that ONLY supports the static table and literals.
+//
+// Fields are kept in order, and a regular name may repeat (ex. several `cookie` fields).
+// Names are lowercase on the wire, so they're lowercased when added and matched without case.
+// A pseudo-header may only appear once, before any regular field.
 #[derive(Debug, Default)]
 pub struct Headers {
-    pub fields: HashMap<String, String>,
+    pub fields: Vec<(String, String)>,
 }
 
 impl Headers {
+    // The first value for `name`.
     pub fn get(&self, name: &str) -> Option<&str> {
-        self.fields.get(name).map(|v| v.as_str())
+        self.fields
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
     }
 
+    // Replace any values for `name`.
     pub fn set(&mut self, name: &str, value: &str) {
-        self.fields.insert(name.to_string(), value.to_string());
+        self.fields
+            .retain(|(field, _)| !field.eq_ignore_ascii_case(name));
+        self.append(name, value);
+    }
+
+    // Add a value for `name`, keeping any others.
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.fields
+            .push((name.to_ascii_lowercase(), value.to_string()));
+    }
+
+    // Check each name and value, and that pseudo-headers come first and only once.
+    fn validate<'a>(
+        fields: impl IntoIterator<Item = &'a (String, String)>,
+    ) -> Result<(), FieldError> {
+        let mut pseudo: Vec<&str> = Vec::new();
+        let mut regular = false;
+
+        for (name, value) in fields {
+            let token = name.strip_prefix(':').unwrap_or(name);
+            if token.is_empty() || !token.bytes().all(is_name_char) {
+                return Err(FieldError::InvalidName(name.clone()));
+            }
+
+            if !is_valid_value(value) {
+                return Err(FieldError::InvalidValue(name.clone()));
+            }
+
+            if !name.starts_with(':') {
+                regular = true;
+            } else if regular {
+                return Err(FieldError::LatePseudo(name.clone()));
+            } else if pseudo.contains(&name.as_str()) {
+                return Err(FieldError::DuplicatePseudo(name.clone()));
+            } else {
+                pseudo.push(name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The size of the field section as defined by RFC 9114 section 4.2.2:
+    /// the length of each name and value plus 32 bytes of overhead per field.
+    pub fn size(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|(name, value)| name.len() + value.len() + 32)
+            .sum()
     }
 
     pub fn decode<B: Buf>(mut buf: &mut B) -> Result<Self, DecodeError> {
@@ -56,7 +133,7 @@ impl Headers {
         let (_, _insert_count) = decode_prefix(buf, 8)?;
         let (_sign, _delta_base) = decode_prefix(buf, 7)?;
 
-        let mut fields = HashMap::new();
+        let mut fields = Vec::new();
         while buf.has_remaining() {
             // Read the first byte;
             let peek = buf.get_u8();
@@ -97,12 +174,14 @@ impl Headers {
                 },
             };
 
-            fields.insert(name, value);
+            fields.push((name, value));
 
             // Get the buffer back.
             (_, buf) = chain.into_inner();
         }
 
+        Self::validate(&fields)?;
+
         Ok(Self { fields })
     }
 
@@ -163,15 +242,20 @@ impl Headers {
         Ok((name.to_string(), value.to_string()))
     }
 
-    pub fn encode<B: BufMut>(&self, buf: &mut B) {
-        // We don't support dynamic entries so we can skip these.
-        encode_prefix(buf, 8, 0, 0);
-        encode_prefix(buf, 7, 0, 0);
-
+    // Nothing is written if a field is invalid, so a malformed section never reaches the peer.
+    pub fn encode<B: BufMut>(&self, buf: &mut B) -> Result<(), FieldError> {
         // We must encode pseudo-headers first.
         // https://datatracker.ietf.org/doc/html/rfc9114#section-4.1.2
+        // The sort is stable, so repeated fields keep their order.
         let mut headers: Vec<_> = self.fields.iter().collect();
-        headers.sort_by_key(|&(key, _)| !key.starts_with(':'));
+        headers.sort_by_key(|(key, _)| !key.starts_with(':'));
+
+        // The fields are public, so they may have been added without lowercasing.
+        Self::validate(headers.iter().copied())?;
+
+        // We don't support dynamic entries so we can skip these.
+        encode_prefix(buf, 8, 0, 0);
+        encode_prefix(buf, 7, 0, 0);
 
         for (name, value) in headers.iter() {
             if let Some(index) = StaticTable::find(name, value) {
@@ -182,6 +266,8 @@ impl Headers {
                 Self::encode_literal(buf, name, value)
             }
         }
+
+        Ok(())
     }
 
     fn encode_index<B: BufMut>(buf: &mut B, index: usize) {
@@ -235,6 +321,26 @@ impl Headers {
     }
 }
 
+// A lowercase token character, the only ones allowed in a name.
+// See: https://www.rfc-editor.org/rfc/rfc9110.html#section-5.6.2
+fn is_name_char(b: u8) -> bool {
+    matches!(b,
+        b'a'..=b'z' | b'0'..=b'9'
+        | b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.'
+        | b'^' | b'_' | b'`' | b'|' | b'~'
+    )
+}
+
+// No NUL, CR or LF anywhere, and no whitespace at either end.
+// See: https://www.rfc-editor.org/rfc/rfc9114.html#section-4.2
+fn is_valid_value(value: &str) -> bool {
+    let edge = |c: char| c == ' ' || c == '\t';
+
+    !value.bytes().any(|b| matches!(b, b'\0' | b'\r' | b'\n'))
+        && !value.starts_with(edge)
+        && !value.ends_with(edge)
+}
+
 // An integer that uses a fixed number of bits, otherwise a variable number of bytes if it's too large.
 // https://www.rfc-editor.org/rfc/rfc7541#section-5.1
 
@@ -625,3 +731,137 @@ const PREDEFINED_HEADERS: [(&str, &str); 99] = [
     ("x-frame-options", "deny"),
     ("x-frame-options", "sameorigin"),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(headers: &Headers) -> Result<Headers, DecodeError> {
+        let mut buf = Vec::new();
+        headers.encode(&mut buf).unwrap();
+        Headers::decode(&mut buf.as_slice())
+    }
+
+    // Encode the fields as literals, in order, skipping the validation on encode.
+    fn literals(fields: &[(&str, &str)]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_prefix(&mut buf, 8, 0, 0);
+        encode_prefix(&mut buf, 7, 0, 0);
+        for (name, value) in fields {
+            Headers::encode_literal(&mut buf, name, value);
+        }
+        buf
+    }
+
+    #[test]
+    fn test_names_lowercased() {
+        let mut headers = Headers::default();
+        headers.append("X-Custom", "a");
+        headers.set("x-CUSTOM", "b");
+
+        assert_eq!(headers.fields, vec![("x-custom".into(), "b".into())]);
+        assert_eq!(headers.get("X-Custom"), Some("b"));
+    }
+
+    #[test]
+    fn test_append_keeps_values() {
+        let mut headers = Headers::default();
+        headers.set(":status", "200");
+        headers.append("cookie", "a=1");
+        headers.append("cookie", "b=2");
+
+        let decoded = roundtrip(&headers).unwrap();
+        assert_eq!(decoded.fields, headers.fields);
+        assert_eq!(decoded.get("cookie"), Some("a=1"));
+    }
+
+    #[test]
+    fn test_encode_invalid() {
+        let cases = [
+            ("", "a", FieldError::InvalidName("".into())),
+            (":", "a", FieldError::InvalidName(":".into())),
+            ("bad name", "a", FieldError::InvalidName("bad name".into())),
+            ("bad:name", "a", FieldError::InvalidName("bad:name".into())),
+            ("x", "a\r\nb: c", FieldError::InvalidValue("x".into())),
+            ("x", "a\0", FieldError::InvalidValue("x".into())),
+            ("x", " a", FieldError::InvalidValue("x".into())),
+            ("x", "a\t", FieldError::InvalidValue("x".into())),
+        ];
+
+        for (name, value, expected) in cases {
+            let mut headers = Headers::default();
+            headers.append(name, value);
+
+            let mut buf = Vec::new();
+            assert_eq!(headers.encode(&mut buf), Err(expected));
+            assert!(buf.is_empty(), "wrote a malformed section");
+        }
+
+        // Fields pushed directly skip the lowercasing in append.
+        let headers = Headers {
+            fields: vec![("X-Upper".into(), "a".into())],
+        };
+        assert_eq!(
+            headers.encode(&mut Vec::new()),
+            Err(FieldError::InvalidName("X-Upper".into()))
+        );
+    }
+
+    #[test]
+    fn test_encode_duplicate_pseudo() {
+        let mut headers = Headers::default();
+        headers.append(":path", "/a");
+        headers.append(":path", "/b");
+
+        assert_eq!(
+            headers.encode(&mut Vec::new()),
+            Err(FieldError::DuplicatePseudo(":path".into()))
+        );
+    }
+
+    #[test]
+    fn test_encode_sorts_pseudo() {
+        let mut headers = Headers::default();
+        headers.append("origin", "https://example.com");
+        headers.append(":method", "CONNECT");
+
+        let decoded = roundtrip(&headers).unwrap();
+        assert_eq!(decoded.fields[0].0, ":method");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let cases = [
+            (
+                vec![("X-Upper", "a")],
+                FieldError::InvalidName("X-Upper".into()),
+            ),
+            (vec![("x", "a\nb")], FieldError::InvalidValue("x".into())),
+            (
+                vec![(":path", "/a"), (":path", "/b")],
+                FieldError::DuplicatePseudo(":path".into()),
+            ),
+            (
+                vec![("x", "a"), (":path", "/")],
+                FieldError::LatePseudo(":path".into()),
+            ),
+        ];
+
+        for (fields, expected) in cases {
+            let buf = literals(&fields);
+            match Headers::decode(&mut buf.as_slice()) {
+                Err(DecodeError::Field(err)) => assert_eq!(err, expected),
+                res => panic!("expected {expected:?}, got {res:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_duplicate_regular() {
+        let buf = literals(&[(":status", "200"), ("via", "a"), ("via", "b")]);
+        let headers = Headers::decode(&mut buf.as_slice()).unwrap();
+
+        assert_eq!(headers.get("via"), Some("a"));
+        assert_eq!(headers.fields.len(), 3);
+    }
+}
@@ -0,0 +1,376 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use url::Url;
+
+use crate::{
+    connect::{decode_fields, decode_headers_frame, header_map, read_headers_frame, request_url},
+    log_frame, qpack, ConnectError, ConnectRequest, Direction, Frame, VarInt, WireFrame,
+    MAX_FRAME_SIZE,
+};
+
+/// The largest response body [HttpResponse::read] will buffer.
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// An ordinary HTTP/3 request, such as a GET sent before upgrading to WebTransport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: http::Method,
+    pub url: Url,
+
+    /// Everything but the pseudo-headers.
+    pub headers: http::HeaderMap,
+}
+
+impl HttpRequest {
+    pub fn new(method: http::Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: Default::default(),
+        }
+    }
+
+    pub fn with_header(mut self, name: http::HeaderName, value: http::HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    fn from_fields(headers: &qpack::Headers) -> Result<Self, ConnectError> {
+        let method = headers
+            .get(":method")
+            .ok_or(ConnectError::WrongMethod(None))?
+            .parse()
+            .map_err(|_| ConnectError::InvalidMethod)?;
+
+        Ok(Self {
+            method,
+            url: request_url(headers)?,
+            headers: header_map(headers, &[])?,
+        })
+    }
+
+    pub fn encode<B: BufMut>(&self, buf: &mut B) -> Result<(), ConnectError> {
+        let mut headers = qpack::Headers::default();
+        for (name, value) in self.headers.iter() {
+            let value = value
+                .to_str()
+                .map_err(|_| ConnectError::InvalidHttpHeaderValue)?;
+            headers.append(name.as_str(), value);
+        }
+        headers.set(":method", self.method.as_str());
+        headers.set(":scheme", self.url.scheme());
+        headers.set(":authority", self.url.authority());
+        let path_and_query = match self.url.query() {
+            Some(query) => format!("{}?{}", self.url.path(), query),
+            None => self.url.path().to_string(),
+        };
+        headers.set(":path", &path_and_query);
+
+        encode_headers_frame(&headers, buf)
+    }
+
+    pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), ConnectError> {
+        let mut buf = BytesMut::new();
+        self.encode(&mut buf)?;
+        stream.write_all_buf(&mut buf).await?;
+        Ok(())
+    }
+}
+
+/// A response to an [HttpRequest], sent in full before the stream is finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: http::StatusCode,
+
+    /// Everything but the `:status` pseudo-header.
+    pub headers: http::HeaderMap,
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    pub fn new(status: http::StatusCode) -> Self {
+        Self {
+            status,
+            headers: Default::default(),
+            body: Bytes::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: http::HeaderName, value: http::HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn decode<B: Buf>(buf: &mut B) -> Result<Self, ConnectError> {
+        let mut data = decode_headers_frame(buf)?;
+        let headers = decode_fields(&mut data, None)?;
+        let mut response = Self::from_fields(&headers)?;
+
+        data.advance(data.remaining());
+        let buf = data.into_inner();
+
+        let mut body = BytesMut::new();
+        while buf.has_remaining() {
+            let (typ, mut data) = Frame::read(buf).map_err(|_| ConnectError::UnexpectedEnd)?;
+            match typ {
+                Frame::DATA => body.put(&mut data),
+                typ if typ.is_unknown() => data.advance(data.limit()),
+                typ => return Err(ConnectError::UnexpectedFrame(typ)),
+            }
+        }
+
+        response.body = body.freeze();
+        Ok(response)
+    }
+
+    fn from_fields(headers: &qpack::Headers) -> Result<Self, ConnectError> {
+        let status = headers
+            .get(":status")
+            .ok_or(ConnectError::InvalidStatus)?
+            .parse()
+            .map_err(|_| ConnectError::InvalidStatus)?;
+
+        Ok(Self {
+            status,
+            headers: header_map(headers, &[])?,
+            body: Bytes::new(),
+        })
+    }
+
+    /// Read a response and its body until the stream is finished.
+    ///
+    /// Fails with [ConnectError::FrameTooLarge] if the body exceeds [MAX_BODY_SIZE].
+    pub async fn read<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Self, ConnectError> {
+        let buf = read_headers_frame(stream).await?;
+        let headers = decode_fields(&mut buf.as_slice(), None)?;
+        let mut response = Self::from_fields(&headers)?;
+
+        let mut body = Vec::new();
+        loop {
+            // A clean end between frames finishes the body.
+            let typ = match VarInt::read_optional(stream).await {
+                Ok(Some(typ)) => Frame(typ),
+                Ok(None) => break,
+                Err(_) => return Err(ConnectError::UnexpectedEnd),
+            };
+            let size = VarInt::read(stream)
+                .await
+                .map_err(|_| ConnectError::UnexpectedEnd)?
+                .into_inner();
+            if size > MAX_FRAME_SIZE {
+                return Err(ConnectError::FrameTooLarge);
+            }
+
+            let mut payload = stream.take(size);
+            let read = match typ {
+                Frame::DATA if body.len() + size as usize > MAX_BODY_SIZE => {
+                    return Err(ConnectError::FrameTooLarge)
+                }
+                Frame::DATA => payload.read_to_end(&mut body).await?,
+                typ if typ.is_unknown() => {
+                    tokio::io::copy(&mut payload, &mut tokio::io::sink()).await? as usize
+                }
+                typ => return Err(ConnectError::UnexpectedFrame(typ)),
+            };
+
+            if read < size as usize {
+                return Err(ConnectError::UnexpectedEnd);
+            }
+        }
+
+        response.body = body.into();
+        Ok(response)
+    }
+
+    pub fn encode<B: BufMut>(&self, buf: &mut B) -> Result<(), ConnectError> {
+        let mut headers = qpack::Headers::default();
+        headers.set(":status", self.status.as_str());
+        for (name, value) in self.headers.iter() {
+            let value = value
+                .to_str()
+                .map_err(|_| ConnectError::InvalidHttpHeaderValue)?;
+            headers.append(name.as_str(), value);
+        }
+
+        encode_headers_frame(&headers, buf)?;
+
+        // Split the body so no frame is larger than a reader accepts.
+        for chunk in self.body.chunks(MAX_FRAME_SIZE as usize) {
+            Frame::DATA.encode(buf);
+            VarInt::from_u32(chunk.len() as u32).encode(buf);
+            buf.put_slice(chunk);
+        }
+
+        Ok(())
+    }
+
+    pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), ConnectError> {
+        let mut buf = BytesMut::new();
+        self.encode(&mut buf)?;
+        stream.write_all_buf(&mut buf).await?;
+        Ok(())
+    }
+}
+
+impl From<http::StatusCode> for HttpResponse {
+    fn from(status: http::StatusCode) -> Self {
+        Self::new(status)
+    }
+}
+
+/// The request that opens a bidirectional stream from the client.
+///
+/// A client may send ordinary HTTP/3 requests on the connection before the CONNECT
+/// that establishes the WebTransport session, so a server reads this rather than a
+/// [ConnectRequest] to answer them instead of failing.
+#[derive(Debug, Clone)]
+pub enum IncomingRequest {
+    /// An extended CONNECT for a WebTransport session.
+    Connect(ConnectRequest),
+
+    /// Any other method, such as a GET before upgrading.
+    Http(HttpRequest),
+}
+
+impl IncomingRequest {
+    /// Decode a request, rejecting a field section larger than `max_field_section_size`.
+    pub fn decode<B: Buf>(
+        buf: &mut B,
+        max_field_section_size: Option<u64>,
+    ) -> Result<Self, ConnectError> {
+        let mut data = decode_headers_frame(buf)?;
+        Self::decode_headers(&mut data, max_field_section_size)
+    }
+
+    /// Read a request from a stream, consuming only the exact bytes of the HEADERS frame.
+    ///
+    /// Any body is left on the stream for the caller to read or stop.
+    pub async fn read<S: AsyncRead + Unpin>(
+        stream: &mut S,
+        max_field_section_size: Option<u64>,
+    ) -> Result<Self, ConnectError> {
+        let buf = read_headers_frame(stream).await?;
+        let request = Self::decode_headers(&mut buf.as_slice(), max_field_section_size)?;
+        if let Self::Connect(request) = &request {
+            log_frame(Direction::Received, WireFrame::ConnectRequest(request));
+        }
+        Ok(request)
+    }
+
+    fn decode_headers<B: Buf>(data: &mut B, limit: Option<u64>) -> Result<Self, ConnectError> {
+        let headers = decode_fields(data, limit)?;
+        match headers.get(":method") {
+            Some("CONNECT") => Ok(Self::Connect(ConnectRequest::from_fields(&headers)?)),
+            _ => Ok(Self::Http(HttpRequest::from_fields(&headers)?)),
+        }
+    }
+}
+
+// Write a HEADERS frame carrying the encoded field section, or nothing if a field is invalid.
+fn encode_headers_frame<B: BufMut>(
+    headers: &qpack::Headers,
+    buf: &mut B,
+) -> Result<(), ConnectError> {
+    // Use a temporary buffer so we can compute the size.
+    let mut tmp = Vec::new();
+    headers.encode(&mut tmp)?;
+    let size = VarInt::from_u32(tmp.len() as u32);
+
+    Frame::HEADERS.encode(buf);
+    size.encode(buf);
+    buf.put_slice(&tmp);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn plain_and_connect_requests() {
+        let url = Url::parse("https://example.com/index.html?v=1").unwrap();
+        let request = HttpRequest::new(http::Method::GET, url.clone()).with_header(
+            http::header::ACCEPT,
+            http::HeaderValue::from_static("text/html"),
+        );
+
+        let mut wire = Vec::new();
+        request.encode(&mut wire).unwrap();
+        ConnectRequest::new(url.clone()).encode(&mut wire).unwrap();
+
+        let mut cursor = Cursor::new(wire);
+        match IncomingRequest::read(&mut cursor, None).await.unwrap() {
+            IncomingRequest::Http(decoded) => assert_eq!(decoded, request),
+            other => panic!("expected a plain request, got {other:?}"),
+        }
+        match IncomingRequest::read(&mut cursor, None).await.unwrap() {
+            IncomingRequest::Connect(decoded) => assert_eq!(decoded.url, url),
+            other => panic!("expected a CONNECT, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_field_writes_nothing() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let request = HttpRequest::new(http::Method::GET, url).with_header(
+            http::header::ACCEPT,
+            http::HeaderValue::from_static("text/html "),
+        );
+
+        let mut wire = Vec::new();
+        let err = request.encode(&mut wire).unwrap_err();
+        assert!(matches!(err, ConnectError::InvalidField(_)), "{err:?}");
+        assert!(wire.is_empty());
+    }
+
+    #[test]
+    fn connect_without_webtransport_is_rejected() {
+        let mut headers = qpack::Headers::default();
+        headers.set(":method", "CONNECT");
+        headers.set(":scheme", "https");
+        headers.set(":authority", "example.com");
+        headers.set(":path", "/");
+
+        let mut wire = Vec::new();
+        encode_headers_frame(&headers, &mut wire).unwrap();
+
+        let err = IncomingRequest::decode(&mut wire.as_slice(), None).unwrap_err();
+        assert!(matches!(err, ConnectError::WrongProtocol(None)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn response_roundtrip() {
+        let response = HttpResponse::new(http::StatusCode::OK)
+            .with_header(
+                http::header::CONTENT_TYPE,
+                http::HeaderValue::from_static("text/plain"),
+            )
+            .with_body("hello");
+
+        let mut wire = Vec::new();
+        response.encode(&mut wire).unwrap();
+        assert_eq!(
+            HttpResponse::decode(&mut wire.as_slice()).unwrap(),
+            response
+        );
+
+        let read = HttpResponse::read(&mut Cursor::new(wire)).await.unwrap();
+        assert_eq!(read, response);
+
+        // A body cut short is an error, not a shorter body.
+        let mut wire = Vec::new();
+        response.encode(&mut wire).unwrap();
+        wire.pop();
+        let err = HttpResponse::read(&mut Cursor::new(wire))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ConnectError::UnexpectedEnd), "{err:?}");
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::ConnectError;
+
+/// An opaque token that lets a client resume an application session on a new connection.
+///
+/// The server mints one when it accepts a session and returns it with
+/// [ConnectResponse::with_resumption_token](crate::ConnectResponse::with_resumption_token).
+/// After a network change, the client presents it in the next CONNECT, either with
+/// [ConnectRequest::with_resumption_token](crate::ConnectRequest::with_resumption_token)
+/// or, for a browser that can't set headers, as the [Self::NAME] query parameter.
+/// The server reads it back with [ConnectRequest::resumption_token](crate::ConnectRequest::resumption_token)
+/// and decides whether to link the new session to the old state, e.g. via [ResumptionStore].
+///
+/// This is unrelated to TLS session resumption, which only skips a handshake round trip.
+///
+/// A token is 1 to [Self::MAX_LEN] characters of unpadded base64url, so it's safe
+/// in both a header and a URL.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ResumptionToken(String);
+
+impl ResumptionToken {
+    /// The header and query parameter that carry the token.
+    pub const NAME: &'static str = "wt-resumption-token";
+
+    /// The longest token accepted.
+    pub const MAX_LEN: usize = 512;
+
+    /// Wrap an already encoded token, failing if it's not valid unpadded base64url.
+    pub fn new(token: impl Into<String>) -> Result<Self, ConnectError> {
+        let token = token.into();
+
+        let valid = !token.is_empty()
+            && token.len() <= Self::MAX_LEN
+            && token.bytes().all(|b| base64url::decode_byte(b).is_some());
+
+        match valid {
+            true => Ok(Self(token)),
+            false => Err(ConnectError::InvalidResumptionToken),
+        }
+    }
+
+    /// Encode arbitrary bytes as a token, e.g. an identifier sealed with the server's key.
+    ///
+    /// Fails if `bytes` is empty or too long to fit in [Self::MAX_LEN] characters.
+    pub fn encode(bytes: &[u8]) -> Result<Self, ConnectError> {
+        Self::new(base64url::encode(bytes))
+    }
+
+    /// Decode the bytes passed to [Self::encode].
+    pub fn decode(&self) -> Vec<u8> {
+        base64url::decode(&self.0)
+    }
+
+    /// Mint a new token from 16 random bytes.
+    pub fn random() -> Self {
+        let mut bytes = [0u8; 16];
+        getrandom::fill(&mut bytes).expect("failed to generate random bytes");
+        Self(base64url::encode(&bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// Tokens are credentials, so keep them out of logs.
+impl fmt::Debug for ResumptionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResumptionToken(..)")
+    }
+}
+
+impl AsRef<str> for ResumptionToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for ResumptionToken {
+    type Err = ConnectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for ResumptionToken {
+    type Error = ConnectError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<&str> for ResumptionToken {
+    type Error = ConnectError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl From<ResumptionToken> for String {
+    fn from(token: ResumptionToken) -> Self {
+        token.0
+    }
+}
+
+/// Server-side state for sessions that may be resumed, keyed by random [ResumptionToken]s.
+///
+/// [Self::mint] stores the state of an accepted session and returns the token to send
+/// to the client. When the client reconnects, [Self::redeem] hands the state back so
+/// it can be attached to the new session. Tokens are single use and expire after the
+/// configured lifetime, so mint a fresh one for every session, resumed or not.
+///
+/// Everything lives in memory; implement the same flow over [ResumptionToken::encode]
+/// and [ResumptionToken::decode] to share state between servers.
+pub struct ResumptionStore<T> {
+    lifetime: Duration,
+    entries: Mutex<HashMap<ResumptionToken, (Instant, T)>>,
+}
+
+impl<T> ResumptionStore<T> {
+    /// Tokens expire after 5 minutes by default.
+    pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(300);
+
+    /// Create a store whose tokens expire after `lifetime`.
+    pub fn new(lifetime: Duration) -> Self {
+        Self {
+            lifetime,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Store `state` and return a new token that redeems it.
+    ///
+    /// Expired entries are pruned first, so the store never grows past the
+    /// number of tokens minted within one lifetime.
+    pub fn mint(&self, state: T) -> ResumptionToken {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (expires, _)| *expires > now);
+
+        let token = ResumptionToken::random();
+        entries.insert(token.clone(), (now + self.lifetime, state));
+        token
+    }
+
+    /// Remove and return the state for `token`, or None if it's unknown, expired, or already redeemed.
+    pub fn redeem(&self, token: &ResumptionToken) -> Option<T> {
+        let (expires, state) = self.entries.lock().unwrap().remove(token)?;
+        (expires > Instant::now()).then_some(state)
+    }
+
+    /// Forget `token` without redeeming it, e.g. when the session closed cleanly.
+    pub fn revoke(&self, token: &ResumptionToken) {
+        self.entries.lock().unwrap().remove(token);
+    }
+
+    /// The number of tokens stored, including any that expired since the last [Self::mint].
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for ResumptionStore<T> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LIFETIME)
+    }
+}
+
+mod base64url {
+    //! Unpadded base64url (RFC 4648 section 5), the encoding used for tokens.
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    pub fn encode(bytes: &[u8]) -> String {
+        let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+
+            // 1 byte needs 2 characters, 2 bytes need 3, and 3 bytes need 4.
+            for i in 0..=chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            }
+        }
+        out
+    }
+
+    pub fn decode_byte(c: u8) -> Option<u8> {
+        ALPHABET.iter().position(|&a| a == c).map(|i| i as u8)
+    }
+
+    /// Decode a string already checked with [decode_byte]; trailing bits are ignored.
+    pub fn decode(s: &str) -> Vec<u8> {
+        let mut out = Vec::with_capacity(s.len() * 3 / 4);
+        for chunk in s.as_bytes().chunks(4) {
+            let n = chunk.iter().enumerate().fold(0u32, |n, (i, c)| {
+                n | (decode_byte(*c).unwrap_or(0) as u32) << (18 - 6 * i)
+            });
+
+            for i in 0..chunk.len().saturating_sub(1) {
+                out.push((n >> (16 - 8 * i)) as u8);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_roundtrip() {
+        for len in 1..=10 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            let token = ResumptionToken::encode(&bytes).unwrap();
+            assert_eq!(token.decode(), bytes);
+        }
+
+        assert_eq!(
+            ResumptionToken::encode(b"\xfb\xff").unwrap().as_str(),
+            "-_8"
+        );
+        assert!(ResumptionToken::encode(b"").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(ResumptionToken::new("abc_-09").is_ok());
+        assert!(ResumptionToken::new("").is_err());
+        assert!(ResumptionToken::new("abc=").is_err());
+        assert!(ResumptionToken::new("a b").is_err());
+        assert!(ResumptionToken::new("a".repeat(ResumptionToken::MAX_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn store_redeems_once() {
+        let store = ResumptionStore::default();
+        let token = store.mint("state");
+        assert_ne!(token, store.mint("other"));
+
+        assert_eq!(store.redeem(&token), Some("state"));
+        assert_eq!(store.redeem(&token), None);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn store_expires() {
+        let store = ResumptionStore::new(Duration::ZERO);
+        let token = store.mint(1);
+        assert_eq!(store.redeem(&token), None);
+
+        store.mint(2);
+        store.mint(3);
+        assert_eq!(store.len(), 1, "expired tokens are pruned on mint");
+    }
+}
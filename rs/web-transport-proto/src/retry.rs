@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+/// How many times to try connecting, and how long to wait in between.
+///
+/// Passed to the native clients' `connect_with_retry`, which only retries failures that
+/// might not happen again: the host didn't resolve, the handshake timed out, or the server
+/// refused. The wait doubles after each failed attempt, up to `max_backoff`, and part of it
+/// is random so that clients disconnected together don't all reconnect together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// The most attempts to make, including the first. 1 never retries.
+    ///
+    /// Defaults to 4.
+    pub max_attempts: u32,
+
+    /// The wait after the first failed attempt.
+    ///
+    /// Defaults to 100ms.
+    pub initial_backoff: Duration,
+
+    /// The longest wait between attempts.
+    ///
+    /// Defaults to 5 seconds.
+    pub max_backoff: Duration,
+
+    /// The fraction of each wait that's random, from 0.0 for none to 1.0 for all of it.
+    ///
+    /// Defaults to 0.5, so each wait is between half and all of the backoff.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        initial_backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+        jitter: 0.0,
+    };
+
+    /// How long to wait after failed attempt number `attempt`, counting from 1.
+    ///
+    /// Jitter only ever shortens the wait, so it never exceeds `max_backoff`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        backoff.mul_f64(1.0 - jitter * random_fraction())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.5,
+        }
+    }
+}
+
+// A uniform f64 in [0, 1).
+fn random_fraction() -> f64 {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).expect("failed to generate random bytes");
+
+    // Use the top 53 bits, the precision of an f64.
+    (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(7), Duration::from_secs(5));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter_only_shortens() {
+        let policy = RetryPolicy::default();
+
+        for attempt in 1..=8 {
+            let full = RetryPolicy {
+                jitter: 0.0,
+                ..policy
+            }
+            .backoff(attempt);
+
+            let backoff = policy.backoff(attempt);
+            assert!(backoff <= full, "{backoff:?} > {full:?}");
+            assert!(backoff >= full / 2, "{backoff:?} < {full:?} / 2");
+        }
+    }
+}
@@ -10,7 +10,10 @@ use bytes::{Buf, BufMut, BytesMut};
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use super::{Frame, StreamUni, VarInt, VarIntUnexpectedEnd, MAX_FRAME_SIZE};
+use super::{
+    log_frame, Direction, FlowLimits, Frame, StreamUni, VarInt, VarIntUnexpectedEnd, WireFrame,
+    MAX_FRAME_SIZE,
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Setting(pub VarInt);
@@ -36,6 +39,14 @@ impl Setting {
             (val - 0x21) % 0x1f == 0
         }
     }
+
+    /// Whether HTTP/3 reserves this identifier, either for GREASE or because HTTP/2 used it.
+    ///
+    /// GREASE settings are ignored, but receiving one of the HTTP/2 settings is an
+    /// H3_SETTINGS_ERROR (RFC 9114 Section 7.2.4.1).
+    pub fn is_reserved(&self) -> bool {
+        self.is_grease() || matches!(self.0.into_inner(), 0x00 | 0x02 | 0x03 | 0x04 | 0x05)
+    }
 }
 
 impl Debug for Setting {
@@ -52,6 +63,13 @@ impl Debug for Setting {
                 write!(f, "WEBTRANSPORT_MAX_SESSIONS_DEPRECATED")
             }
             Setting::WEBTRANSPORT_MAX_SESSIONS => write!(f, "WEBTRANSPORT_MAX_SESSIONS"),
+            Setting::WEBTRANSPORT_INITIAL_MAX_DATA => write!(f, "WEBTRANSPORT_INITIAL_MAX_DATA"),
+            Setting::WEBTRANSPORT_INITIAL_MAX_STREAMS_UNI => {
+                write!(f, "WEBTRANSPORT_INITIAL_MAX_STREAMS_UNI")
+            }
+            Setting::WEBTRANSPORT_INITIAL_MAX_STREAMS_BIDI => {
+                write!(f, "WEBTRANSPORT_INITIAL_MAX_STREAMS_BIDI")
+            }
             x if x.is_grease() => write!(f, "GREASE SETTING [{:x?}]", x.0.into_inner()),
             x => write!(f, "UNKNOWN_SETTING [{:x?}]", x.0.into_inner()),
         }
@@ -83,6 +101,11 @@ settings! {
 
     // New way to enable WebTransport
     WEBTRANSPORT_MAX_SESSIONS = 0xc671706a,
+
+    // Session-level flow control, added in draft 07
+    WEBTRANSPORT_INITIAL_MAX_DATA = 0x2b61,
+    WEBTRANSPORT_INITIAL_MAX_STREAMS_UNI = 0x2b64,
+    WEBTRANSPORT_INITIAL_MAX_STREAMS_BIDI = 0x2b65,
 }
 
 #[derive(Error, Debug, Clone)]
@@ -100,6 +123,9 @@ pub enum SettingsError {
     #[error("invalid size")]
     InvalidSize,
 
+    #[error("reserved setting {0:?}")]
+    ReservedSetting(Setting),
+
     #[error("frame too large")]
     FrameTooLarge,
 
@@ -113,8 +139,37 @@ impl From<std::io::Error> for SettingsError {
     }
 }
 
+/// The peer's SETTINGS didn't enable WebTransport.
+///
+/// Keeps a snapshot of what the peer sent, so an operator can see which settings are
+/// missing. Often the peer does support WebTransport, but a proxy in front of it
+/// terminates HTTP/3 and forwards its own SETTINGS without them.
+#[derive(Debug, Clone)]
+pub struct UnsupportedSettings {
+    /// The SETTINGS the peer sent.
+    pub settings: Settings,
+}
+
+impl UnsupportedSettings {
+    /// The settings the peer left out. See [Settings::missing_webtransport].
+    pub fn missing(&self) -> Vec<Setting> {
+        self.settings.missing_webtransport()
+    }
+}
+
+impl std::fmt::Display for UnsupportedSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "missing {:?} in peer SETTINGS {:?}; check for a proxy stripping them",
+            self.missing(),
+            self.settings.0,
+        )
+    }
+}
+
 // A map of settings to values.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Settings(HashMap<Setting, VarInt>);
 
 impl Settings {
@@ -134,10 +189,7 @@ impl Settings {
             // These return a different error because retrying won't help.
             let id = Setting::decode(&mut data).map_err(|_| SettingsError::InvalidSize)?;
             let value = VarInt::decode(&mut data).map_err(|_| SettingsError::InvalidSize)?;
-            // Only add if it is not grease
-            if !id.is_grease() {
-                settings.0.insert(id, value);
-            }
+            settings.insert_decoded(id, value)?;
         }
 
         Ok(settings)
@@ -195,15 +247,27 @@ impl Settings {
             while data.has_remaining() {
                 let id = Setting::decode(&mut data).map_err(|_| SettingsError::InvalidSize)?;
                 let value = VarInt::decode(&mut data).map_err(|_| SettingsError::InvalidSize)?;
-                if !id.is_grease() {
-                    settings.0.insert(id, value);
-                }
+                settings.insert_decoded(id, value)?;
             }
 
+            log_frame(Direction::Received, WireFrame::Settings(&settings));
             return Ok(settings);
         }
     }
 
+    // Add a setting from the peer, ignoring GREASE and rejecting those HTTP/2 reserved.
+    fn insert_decoded(&mut self, id: Setting, value: VarInt) -> Result<(), SettingsError> {
+        if id.is_grease() {
+            return Ok(());
+        }
+        if id.is_reserved() {
+            return Err(SettingsError::ReservedSetting(id));
+        }
+
+        self.0.insert(id, value);
+        Ok(())
+    }
+
     pub fn encode<B: BufMut>(&self, buf: &mut B) {
         StreamUni::CONTROL.encode(buf);
         Frame::SETTINGS.encode(buf);
@@ -221,6 +285,8 @@ impl Settings {
     }
 
     pub async fn write<S: AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), SettingsError> {
+        log_frame(Direction::Sent, WireFrame::Settings(self));
+
         // TODO avoid allocating to the heap
         let mut buf = BytesMut::new();
         self.encode(&mut buf);
@@ -241,6 +307,59 @@ impl Settings {
         self.insert(Setting::WEBTRANSPORT_ENABLE_DEPRECATED, VarInt::from_u32(1));
     }
 
+    /// Advertise the largest field section (HTTP header block) we're willing to accept.
+    pub fn set_max_field_section_size(&mut self, size: u64) {
+        // Saturate rather than fail; anything this large is effectively unlimited.
+        let size = VarInt::from_u64(size).unwrap_or(VarInt::MAX);
+        self.insert(Setting::MAX_FIELD_SECTION_SIZE, size);
+    }
+
+    /// The largest field section the peer is willing to accept, or None if unlimited.
+    pub fn max_field_section_size(&self) -> Option<u64> {
+        self.get(&Setting::MAX_FIELD_SECTION_SIZE)
+            .map(|v| v.into_inner())
+    }
+
+    /// Advertise the session-level flow control limits the peer starts with.
+    pub fn set_webtransport_initial_limits(&mut self, limits: FlowLimits) {
+        let value = |v: u64| VarInt::from_u64(v).unwrap_or(VarInt::MAX);
+
+        self.insert(
+            Setting::WEBTRANSPORT_INITIAL_MAX_DATA,
+            value(limits.max_data),
+        );
+        self.insert(
+            Setting::WEBTRANSPORT_INITIAL_MAX_STREAMS_UNI,
+            value(limits.max_streams_uni),
+        );
+        self.insert(
+            Setting::WEBTRANSPORT_INITIAL_MAX_STREAMS_BIDI,
+            value(limits.max_streams_bidi),
+        );
+    }
+
+    /// The session-level flow control limits the peer starts us with.
+    ///
+    /// Returns None if the peer didn't send any, meaning it doesn't use session-level flow
+    /// control. Otherwise any it left out default to zero, as the draft requires.
+    pub fn webtransport_initial_limits(&self) -> Option<FlowLimits> {
+        let value = |setting: &Setting| self.get(setting).map(|v| v.into_inner());
+
+        let max_data = value(&Setting::WEBTRANSPORT_INITIAL_MAX_DATA);
+        let max_streams_uni = value(&Setting::WEBTRANSPORT_INITIAL_MAX_STREAMS_UNI);
+        let max_streams_bidi = value(&Setting::WEBTRANSPORT_INITIAL_MAX_STREAMS_BIDI);
+
+        if max_data.is_none() && max_streams_uni.is_none() && max_streams_bidi.is_none() {
+            return None;
+        }
+
+        Some(FlowLimits {
+            max_data: max_data.unwrap_or(0),
+            max_streams_uni: max_streams_uni.unwrap_or(0),
+            max_streams_bidi: max_streams_bidi.unwrap_or(0),
+        })
+    }
+
     // Returns the maximum number of sessions supported.
     pub fn supports_webtransport(&self) -> u64 {
         // Sent by Chrome 114.0.5735.198 (July 19, 2023)
@@ -254,15 +373,30 @@ impl Settings {
 
         // NOTE: The presence of ENABLE_WEBTRANSPORT implies ENABLE_CONNECT is supported.
 
+        if !self.supports_datagrams() {
+            return 0;
+        }
+
+        self.webtransport_max_sessions()
+    }
+
+    /// Returns true if the peer enabled HTTP/3 datagrams (SETTINGS_H3_DATAGRAM).
+    ///
+    /// Datagrams mustn't be sent to a peer that didn't.
+    pub fn supports_datagrams(&self) -> bool {
         let datagram = self
             .get(&Setting::ENABLE_DATAGRAM)
             .or(self.get(&Setting::ENABLE_DATAGRAM_DEPRECATED))
             .map(|v| v.into_inner());
 
-        if datagram != Some(1) {
-            return 0;
-        }
+        datagram == Some(1)
+    }
 
+    /// Returns the maximum number of sessions supported, whether or not datagrams are.
+    ///
+    /// Unlike [Self::supports_webtransport], this accepts a peer that only wants streams;
+    /// check [Self::supports_datagrams] before sending it any datagrams.
+    pub fn webtransport_max_sessions(&self) -> u64 {
         // The deprecated (before draft-07) way of enabling WebTransport was to send two parameters.
         // Both would send ENABLE=1 and the server would send MAX_SESSIONS=N to limit the sessions.
         // Now both just send MAX_SESSIONS, and a non-zero value means WebTransport is enabled.
@@ -283,6 +417,36 @@ impl Settings {
             .map(|v| v.into_inner())
             .unwrap_or(1)
     }
+
+    /// The settings a peer would need to add (or fix) before [Self::supports_webtransport] passes.
+    ///
+    /// [Setting::ENABLE_CONNECT_PROTOCOL] is listed when absent even though it isn't checked,
+    /// since a proxy that strips it usually strips the WebTransport settings too.
+    pub fn missing_webtransport(&self) -> Vec<Setting> {
+        let mut missing = Vec::new();
+        let value = |setting: &Setting| self.get(setting).map(|v| v.into_inner());
+
+        if value(&Setting::ENABLE_CONNECT_PROTOCOL) != Some(1) {
+            missing.push(Setting::ENABLE_CONNECT_PROTOCOL);
+        }
+
+        let datagram =
+            value(&Setting::ENABLE_DATAGRAM).or(value(&Setting::ENABLE_DATAGRAM_DEPRECATED));
+        if datagram != Some(1) {
+            missing.push(Setting::ENABLE_DATAGRAM);
+        }
+
+        let sessions = match value(&Setting::WEBTRANSPORT_MAX_SESSIONS) {
+            Some(max) => max,
+            None if value(&Setting::WEBTRANSPORT_ENABLE_DEPRECATED) == Some(1) => 1,
+            None => 0,
+        };
+        if sessions == 0 {
+            missing.push(Setting::WEBTRANSPORT_MAX_SESSIONS);
+        }
+
+        missing
+    }
 }
 
 impl Deref for Settings {
@@ -339,6 +503,55 @@ mod tests {
         assert_eq!(decoded.supports_webtransport(), 4);
     }
 
+    #[tokio::test]
+    async fn max_field_section_size_roundtrip() {
+        let mut settings = Settings::default();
+        settings.enable_webtransport(1);
+        assert_eq!(settings.max_field_section_size(), None);
+
+        settings.set_max_field_section_size(16 * 1024);
+
+        let wire = encode_settings(&settings);
+        let decoded = Settings::read(&mut Cursor::new(wire)).await.unwrap();
+        assert_eq!(decoded.max_field_section_size(), Some(16 * 1024));
+    }
+
+    #[test]
+    fn missing_webtransport() {
+        let mut settings = Settings::default();
+        settings.enable_webtransport(1);
+        assert!(settings.missing_webtransport().is_empty());
+
+        // A proxy that only forwards plain HTTP/3 settings.
+        let mut settings = Settings::default();
+        settings.insert(Setting::QPACK_MAX_TABLE_CAPACITY, VarInt::from_u32(0));
+        settings.insert(Setting::ENABLE_DATAGRAM, VarInt::from_u32(1));
+        assert_eq!(settings.supports_webtransport(), 0);
+        assert_eq!(
+            settings.missing_webtransport(),
+            [
+                Setting::ENABLE_CONNECT_PROTOCOL,
+                Setting::WEBTRANSPORT_MAX_SESSIONS
+            ]
+        );
+
+        let err = UnsupportedSettings { settings };
+        assert!(err.to_string().contains("WEBTRANSPORT_MAX_SESSIONS"));
+    }
+
+    #[test]
+    fn webtransport_without_datagrams() {
+        let mut settings = Settings::default();
+        settings.enable_webtransport(4);
+        settings.remove(&Setting::ENABLE_DATAGRAM);
+        assert!(settings.supports_datagrams());
+
+        settings.remove(&Setting::ENABLE_DATAGRAM_DEPRECATED);
+        assert!(!settings.supports_datagrams());
+        assert_eq!(settings.supports_webtransport(), 0);
+        assert_eq!(settings.webtransport_max_sessions(), 4);
+    }
+
     #[tokio::test]
     async fn read_empty_stream() {
         let mut cursor = Cursor::new(Vec::<u8>::new());
@@ -415,6 +628,57 @@ mod tests {
         assert_eq!(decoded.supports_webtransport(), 1);
     }
 
+    #[tokio::test]
+    async fn read_chrome_settings() {
+        // The SETTINGS listed in [Settings::supports_webtransport], sent by Chrome 114.
+        let chrome: [(u64, u64); 7] = [
+            (0x1, 65536),
+            (0x6, 16384),
+            (0x7, 100),
+            (0x33, 1),
+            (0xffd277, 1),
+            (0x2b603742, 1),
+            (0x108fab0e1, 454654587),
+        ];
+
+        let mut payload = Vec::new();
+        for (id, value) in chrome {
+            VarInt::from_u64(id).unwrap().encode(&mut payload);
+            VarInt::from_u64(value).unwrap().encode(&mut payload);
+        }
+
+        let mut wire = Vec::new();
+        StreamUni::CONTROL.encode(&mut wire);
+        Frame::SETTINGS.encode(&mut wire);
+        VarInt::from_u32(payload.len() as u32).encode(&mut wire);
+        wire.extend_from_slice(&payload);
+
+        let decoded = Settings::read(&mut Cursor::new(&wire)).await.unwrap();
+        assert_eq!(decoded.supports_webtransport(), 1);
+        assert_eq!(decoded.max_field_section_size(), Some(16384));
+        assert_eq!(decoded.len(), 6, "GREASE should be dropped");
+
+        let decoded = Settings::decode(&mut wire.as_slice()).unwrap();
+        assert_eq!(decoded.supports_webtransport(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_rejects_reserved_setting() {
+        // SETTINGS_ENABLE_PUSH from HTTP/2.
+        let mut wire = Vec::new();
+        StreamUni::CONTROL.encode(&mut wire);
+        Frame::SETTINGS.encode(&mut wire);
+        VarInt::from_u32(2).encode(&mut wire);
+        VarInt::from_u32(0x2).encode(&mut wire);
+        VarInt::from_u32(0).encode(&mut wire);
+
+        let err = Settings::read(&mut Cursor::new(&wire)).await.unwrap_err();
+        assert!(
+            matches!(err, SettingsError::ReservedSetting(s) if s == Setting(VarInt::from_u32(2))),
+            "expected ReservedSetting, got {err:?}"
+        );
+    }
+
     #[tokio::test]
     async fn read_truncated_payload() {
         let mut wire = Vec::new();
@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use bytes::{Buf, BufMut};
 
-use super::{VarInt, VarIntUnexpectedEnd};
+use super::{codes, VarInt, VarIntUnexpectedEnd};
 
 // Sent as the first bytes of a unidirectional stream to identify the type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -26,6 +28,127 @@ impl StreamUni {
             (val - 0x21) % 0x1f == 0
         }
     }
+
+    /// The HTTP/3 error to close the connection with when the peer opens this type of
+    /// stream, or None if it's allowed.
+    ///
+    /// `stream_id` is the QUIC stream ID, which says who opened it. The control stream is
+    /// opened while exchanging SETTINGS, so another is a duplicate. Push streams are never
+    /// allowed: only a server may open one, and only after the client sends MAX_PUSH_ID,
+    /// which a WebTransport endpoint never does (RFC 9114 Section 6.2).
+    pub fn reject_code(&self, stream_id: u64) -> Option<u64> {
+        // The lowest bit of a stream ID is set if the server opened it.
+        let server = stream_id & 0x1 == 1;
+
+        match *self {
+            StreamUni::CONTROL => Some(codes::h3::STREAM_CREATION_ERROR),
+            StreamUni::PUSH if server => Some(codes::h3::ID_ERROR),
+            StreamUni::PUSH => Some(codes::h3::STREAM_CREATION_ERROR),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with an incoming unidirectional stream of an unknown type.
+///
+/// HTTP/3 requires ignoring them, so that new stream types can be deployed, but a
+/// steady trickle usually means a peer bug. Every policy counts them either way.
+/// GREASE streams count too, since a peer may send them to probe for ossification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownStreamPolicy {
+    /// Drop the stream, logging at debug level.
+    #[default]
+    Ignore,
+
+    /// Drop the stream, logging a warning.
+    Warn,
+
+    /// Drop the stream, and close the connection with H3_STREAM_CREATION_ERROR once
+    /// more than this many have arrived.
+    CloseAfter(u64),
+}
+
+/// Limits on reading the header of an incoming stream, before it can be accepted.
+///
+/// A stream waits in a pending set until its type and session ID arrive, so without
+/// limits a peer could open streams and never finish their headers. A stream over
+/// either limit is a [HeaderViolation]: it's stopped with
+/// [GENERAL_PROTOCOL_ERROR](codes::h3::GENERAL_PROTOCOL_ERROR) and dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderBudget {
+    /// The most bytes the header may take, including the stream type.
+    ///
+    /// Defaults to [HeaderBudget::MAX_HEADER], the longest valid header.
+    pub max_bytes: usize,
+
+    /// How long the whole header may take to arrive, or None to wait as long as the stream is open.
+    ///
+    /// Defaults to 10 seconds. Our streams send their header as soon as they're opened.
+    pub timeout: Option<Duration>,
+}
+
+impl HeaderBudget {
+    /// The longest valid header: a stream type and a session ID, each an 8 byte varint.
+    pub const MAX_HEADER: usize = 16;
+
+    /// Decode the next varint of a header from `buf`, after `used` bytes of it.
+    ///
+    /// Returns the value and its length, or None if `buf` doesn't hold all of it yet.
+    /// Fails as soon as the first byte shows the varint can't fit in the budget.
+    pub fn decode_varint(
+        &self,
+        used: usize,
+        buf: &[u8],
+    ) -> Result<Option<(VarInt, usize)>, HeaderViolation> {
+        // The top two bits of the first byte give the length.
+        let size = match buf.first() {
+            Some(first) => 1 << (first >> 6),
+            None => 1,
+        };
+        if used + size > self.max_bytes {
+            return Err(HeaderViolation::TooLong(self.max_bytes));
+        }
+
+        let mut cursor = buf;
+        match VarInt::decode(&mut cursor) {
+            Ok(v) => Ok(Some((v, size))),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Default for HeaderBudget {
+    fn default() -> Self {
+        Self {
+            max_bytes: Self::MAX_HEADER,
+            timeout: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// An incoming stream broke its [HeaderBudget].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum HeaderViolation {
+    #[error("stream header longer than {0} bytes")]
+    TooLong(usize),
+
+    #[error("stream header not received within {0:?}")]
+    TimedOut(Duration),
+}
+
+/// Whether a session carries streams, or only datagrams.
+///
+/// Both sides pick a mode on their own, usually keyed off the negotiated subprotocol.
+/// A datagram-only session can't open streams, and resets every stream the peer opens
+/// with [STREAMS_DISABLED](codes::STREAMS_DISABLED).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionMode {
+    /// Streams and datagrams.
+    #[default]
+    Full,
+
+    /// Datagrams only, such as for a telemetry uplink.
+    DatagramOnly,
 }
 
 macro_rules! streams_uni {
@@ -43,3 +166,73 @@ streams_uni! {
     QPACK_DECODER = 0x03,
     WEBTRANSPORT = 0x54,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_push_and_duplicate_control() {
+        // Client and server initiated unidirectional stream IDs.
+        let (client, server) = (2, 3);
+
+        assert_eq!(
+            StreamUni::PUSH.reject_code(server),
+            Some(codes::h3::ID_ERROR)
+        );
+        assert_eq!(
+            StreamUni::PUSH.reject_code(client),
+            Some(codes::h3::STREAM_CREATION_ERROR)
+        );
+        assert_eq!(
+            StreamUni::CONTROL.reject_code(client),
+            Some(codes::h3::STREAM_CREATION_ERROR)
+        );
+
+        for typ in [
+            StreamUni::WEBTRANSPORT,
+            StreamUni::QPACK_ENCODER,
+            StreamUni(VarInt::from_u32(0x21)),
+        ] {
+            assert_eq!(typ.reject_code(server), None);
+        }
+    }
+
+    #[test]
+    fn header_budget() {
+        let budget = HeaderBudget::default();
+
+        // A complete header fits, and a partial varint waits for more.
+        let mut header = Vec::new();
+        StreamUni::WEBTRANSPORT.encode(&mut header);
+        VarInt::from_u32(4).encode(&mut header);
+        assert_eq!(
+            budget.decode_varint(0, &header),
+            Ok(Some((StreamUni::WEBTRANSPORT.0, 2)))
+        );
+        assert_eq!(
+            budget.decode_varint(2, &header[2..]),
+            Ok(Some((VarInt::from_u32(4), 1)))
+        );
+        assert_eq!(budget.decode_varint(0, &[0xc0, 0, 0]), Ok(None));
+        assert_eq!(budget.decode_varint(0, &[]), Ok(None));
+
+        // The length is known from the first byte, so an oversized varint fails early.
+        let small = HeaderBudget {
+            max_bytes: 4,
+            ..budget
+        };
+        assert_eq!(
+            small.decode_varint(0, &[0xc0]),
+            Err(HeaderViolation::TooLong(4))
+        );
+        assert_eq!(
+            small.decode_varint(2, &[0x80]),
+            Err(HeaderViolation::TooLong(4))
+        );
+        assert_eq!(
+            small.decode_varint(4, &[]),
+            Err(HeaderViolation::TooLong(4))
+        );
+    }
+}
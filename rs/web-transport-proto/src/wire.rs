@@ -0,0 +1,105 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
+
+use crate::{Capsule, ConnectRequest, ConnectResponse, GoAway, Settings};
+
+/// Which way a frame crossed the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A decoded HTTP/3 frame or capsule, passed to the [frame logger](set_frame_logger).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WireFrame<'a> {
+    /// The SETTINGS frame at the start of the control stream.
+    Settings(&'a Settings),
+
+    /// A GOAWAY frame on the control stream, starting a graceful shutdown.
+    GoAway(&'a GoAway),
+
+    /// The HEADERS frame carrying the CONNECT request.
+    ConnectRequest(&'a ConnectRequest),
+
+    /// The HEADERS frame carrying the CONNECT response.
+    ConnectResponse(&'a ConnectResponse),
+
+    /// A capsule on the CONNECT stream, carried in DATA frames.
+    Capsule(&'a Capsule),
+}
+
+type Logger = Arc<dyn Fn(Direction, &WireFrame<'_>) + Send + Sync>;
+
+// Checked first, so logging costs nothing until a logger is installed.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LOGGER: RwLock<Option<Logger>> = RwLock::new(None);
+
+/// Call `logger` with every frame and capsule this process sends or receives.
+///
+/// Meant for debugging interop: every backend reads and writes SETTINGS, the CONNECT
+/// request and response, and capsules through this crate, so they're all seen here
+/// after decoding, without a packet capture and TLS keys. Frames that fail to decode
+/// aren't logged. Replaces any previous logger.
+pub fn set_frame_logger(logger: impl Fn(Direction, &WireFrame<'_>) + Send + Sync + 'static) {
+    *LOGGER.write().unwrap() = Some(Arc::new(logger));
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Remove the logger installed by [set_frame_logger].
+pub fn clear_frame_logger() {
+    ENABLED.store(false, Ordering::Release);
+    LOGGER.write().unwrap().take();
+}
+
+/// Pass a frame to the installed logger, if any.
+///
+/// Backends call this for frames they encode themselves, such as capsules written
+/// inside DATA frames; anything read or written through this crate is logged already.
+pub fn log_frame(direction: Direction, frame: WireFrame<'_>) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+
+    // Call outside the lock, so the logger may replace itself.
+    let logger = LOGGER.read().unwrap().clone();
+    if let Some(logger) = logger {
+        logger(direction, &frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, sync::Mutex};
+
+    #[tokio::test]
+    async fn logs_both_directions() {
+        // Other tests run concurrently, so only record our own request.
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        set_frame_logger({
+            let seen = seen.clone();
+            move |direction, frame| {
+                if let WireFrame::ConnectRequest(request) = frame {
+                    if request.url.path() == "/frame-logger" {
+                        seen.lock().unwrap().push(direction);
+                    }
+                }
+            }
+        });
+
+        let url = url::Url::parse("https://example.com/frame-logger").unwrap();
+        let mut wire = Vec::new();
+        ConnectRequest::new(url).write(&mut wire).await.unwrap();
+        ConnectRequest::read(&mut Cursor::new(wire)).await.unwrap();
+
+        clear_frame_logger();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [Direction::Sent, Direction::Received]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Measures large transfers over loopback, comparing a `write` loop with `write_all`.
+//!
+//! Every poll of the writer after the first is a wakeup, so polls per MiB is a proxy
+//! for how often the writer and the driver ping-pong. Run with:
+//!
+//! ```sh
+//! cargo bench -p web-transport-quiche --bench write_all -- [MiB]
+//! ```
+
+use std::{
+    future::Future,
+    net::{Ipv4Addr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result};
+use rcgen::{CertifiedKey, KeyPair};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quiche::{ClientBuilder, Connection, SendStream, ServerBuilder, Settings};
+
+const MIB: usize = 1024 * 1024;
+
+// Written repeatedly, like an application pushing frames out of a buffer pool.
+const CHUNK: usize = 4 * MIB;
+
+/// Counts how many times the inner future is polled.
+struct Counted<F> {
+    inner: Pin<Box<F>>,
+    polls: usize,
+}
+
+impl<F: Future> Future for Counted<F> {
+    type Output = (F::Output, usize);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.polls += 1;
+        let polls = self.polls;
+        self.inner.as_mut().poll(cx).map(|out| (out, polls))
+    }
+}
+
+fn counted<F: Future>(inner: F) -> Counted<F> {
+    Counted {
+        inner: Box::pin(inner),
+        polls: 0,
+    }
+}
+
+fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["localhost".into()]).context("rcgen")?;
+
+    let cert_der = CertificateDer::from(cert.der().to_vec());
+    let key_bytes = KeyPair::serialize_der(&signing_key);
+    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
+
+    Ok((vec![cert_der], key_der))
+}
+
+/// Connect to a server that drains every uni stream it's given.
+async fn connect() -> Result<Connection> {
+    let (chain, key) = make_self_signed()?;
+
+    let mut server = ServerBuilder::default()
+        .with_bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?
+        .with_single_cert(chain, key)?;
+
+    let addr = *server
+        .local_addrs()
+        .first()
+        .context("server has no local address")?;
+
+    tokio::spawn(async move {
+        let request = server.accept().await.context("accept")?;
+        let session = request.ok().await?;
+
+        while let Ok(mut recv) = session.accept_uni().await {
+            tokio::spawn(
+                async move { while let Ok(Some(_)) = recv.read_chunk(usize::MAX).await {} },
+            );
+        }
+
+        anyhow::Ok(())
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+    let session = ClientBuilder::default()
+        .with_settings(settings)
+        .connect(url)
+        .await?
+        .established()
+        .await?;
+
+    Ok(session)
+}
+
+/// Send `total` bytes by calling `write` until each chunk is gone.
+async fn write_loop(send: &mut SendStream, chunk: &[u8], total: usize) -> Result<usize> {
+    let mut polls = 0;
+
+    for _ in 0..total / chunk.len() {
+        let mut buf = chunk;
+        while !buf.is_empty() {
+            let (res, n) = counted(send.write(buf)).await;
+            buf = &buf[res?..];
+            polls += n;
+        }
+    }
+
+    Ok(polls)
+}
+
+/// Send `total` bytes with one `write_all` per chunk.
+async fn write_all(send: &mut SendStream, chunk: &[u8], total: usize) -> Result<usize> {
+    let mut polls = 0;
+
+    for _ in 0..total / chunk.len() {
+        let (res, n) = counted(send.write_all(chunk)).await;
+        res?;
+        polls += n;
+    }
+
+    Ok(polls)
+}
+
+fn report(name: &str, total: usize, elapsed: Duration, polls: usize) {
+    let mib = (total / MIB) as f64;
+    println!(
+        "{name:>10}: {mib:.0} MiB in {elapsed:.2?} ({:.0} MiB/s), {polls} polls ({:.1} per MiB)",
+        mib / elapsed.as_secs_f64(),
+        polls as f64 / mib,
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // cargo bench passes `--bench`; take the first number as the size in MiB.
+    let total = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(1024)
+        * MIB;
+
+    let session = connect().await?;
+    let chunk = vec![0x55u8; CHUNK];
+
+    let mut send = session.open_uni().await?;
+    let start = Instant::now();
+    let polls = write_loop(&mut send, &chunk, total).await?;
+    send.finish()?;
+    send.closed().await?;
+    report("write", total, start.elapsed(), polls);
+
+    let mut send = session.open_uni().await?;
+    let start = Instant::now();
+    let polls = write_all(&mut send, &chunk, total).await?;
+    send.finish()?;
+    send.closed().await?;
+    report("write_all", total, start.elapsed(), polls);
+
+    session.close(0, "done");
+    session.closed().await;
+
+    Ok(())
+}
@@ -32,17 +32,9 @@ async fn main() -> anyhow::Result<()> {
 
     let args = Args::parse();
 
-    // Load the certificate chain from PEM.
-    let cert_pem = std::fs::read(&args.tls_cert).context("failed to read certificate file")?;
-    let chain: Vec<_> = rustls_pemfile::certs(&mut cert_pem.as_slice())
-        .collect::<Result<_, _>>()
-        .context("failed to parse certificate PEM")?;
-
-    // Load the private key from PEM.
-    let key_pem = std::fs::read(&args.tls_key).context("failed to read private key file")?;
-    let key = rustls_pemfile::private_key(&mut key_pem.as_slice())
-        .context("failed to parse private key PEM")?
-        .context("no private key found in PEM file")?;
+    // Load the certificate chain and private key from PEM.
+    let (chain, key) = web_transport_quiche::tls::load_pem(&args.tls_cert, &args.tls_key)
+        .context("failed to load certificate")?;
 
     let mut server = web_transport_quiche::ServerBuilder::default()
         .with_bind(args.bind)?
@@ -1,10 +1,23 @@
-use std::sync::Arc;
-use web_transport_proto::ConnectRequest;
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use web_transport_proto::{
+    codes::{self, DropCodes},
+    ConnectRequest, ConnectTarget, RetryPolicy, SessionMode, UrlError,
+};
+use web_transport_trait::{Clock, ErrorKind, TokioClock};
 
-use crate::{ez, h3, Connection, Settings};
+use crate::{
+    driver::Spawner, ez, h3, Connection, FaultInjector, Faults, HandshakeTiming, SessionDriver,
+    Settings,
+};
+use ez::happy_eyeballs;
 
 /// An error returned when connecting to a WebTransport endpoint.
 #[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ClientError {
     #[error("io error: {0}")]
     Io(Arc<std::io::Error>),
@@ -19,7 +32,52 @@ pub enum ClientError {
     Connect(#[from] h3::ConnectError),
 
     #[error("invalid URL: {0}")]
-    InvalidUrl(String),
+    InvalidUrl(#[from] UrlError),
+
+    #[error("failed to resolve {0}: {1}")]
+    Dns(String, Arc<std::io::Error>),
+}
+
+impl ClientError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Io(_) | Self::Dns(..) => ErrorKind::Io,
+            Self::Connection(e) => e.kind(),
+            Self::Settings(e) => e.kind(),
+            Self::Connect(e) => e.kind(),
+            Self::InvalidUrl(_) => ErrorKind::InvalidInput,
+        }
+    }
+
+    /// Returns true if connecting again might succeed.
+    ///
+    /// That's when the host didn't resolve, the handshake timed out, or the server refused
+    /// with H3_REQUEST_REJECTED or 503 (Service Unavailable). Used by
+    /// [ClientBuilder::connect_with_retry].
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Dns(..) => true,
+            Self::Connect(
+                h3::ConnectError::Status(status) | h3::ConnectError::Rejected { status, .. },
+            ) => *status == http::StatusCode::SERVICE_UNAVAILABLE,
+            _ => self.connection_error().is_some_and(|err| match err {
+                ez::ConnectionError::TimedOut => true,
+                ez::ConnectionError::Remote(code, _) => *code == codes::h3::REQUEST_REJECTED,
+                _ => false,
+            }),
+        }
+    }
+
+    // The connection error behind this one, however the handshake was interrupted.
+    fn connection_error(&self) -> Option<&ez::ConnectionError> {
+        match self {
+            Self::Connection(e)
+            | Self::Settings(h3::SettingsError::Connection(e))
+            | Self::Connect(h3::ConnectError::Connection(e)) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for ClientError {
@@ -33,7 +91,31 @@ impl From<std::io::Error> for ClientError {
 /// Unlike [ServerBuilder](crate::ServerBuilder), there is no `with_metrics`
 /// counterpart. `tokio-quiche` hardcodes its own `DefaultMetrics` on the client
 /// path, so custom [Metrics](ez::Metrics) are server-only.
-pub struct ClientBuilder(ez::ClientBuilder);
+pub struct ClientBuilder(ez::ClientBuilder, Options);
+
+// Options for the HTTP/3 handshake, on top of the QUIC client's.
+#[derive(Clone)]
+struct Options {
+    faults: Option<Faults>,
+    follow_redirects: usize,
+    drop_codes: DropCodes,
+    mode: SessionMode,
+    handshake_timeout: Option<Duration>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            faults: None,
+            follow_redirects: 0,
+            drop_codes: DropCodes::default(),
+            mode: SessionMode::default(),
+            handshake_timeout: None,
+            clock: Arc::new(TokioClock),
+        }
+    }
+}
 
 impl Default for ClientBuilder {
     fn default() -> Self {
@@ -44,23 +126,21 @@ impl Default for ClientBuilder {
 impl ClientBuilder {
     /// Create a new client builder.
     pub fn new() -> Self {
-        Self(ez::ClientBuilder::new())
+        Self(ez::ClientBuilder::new(), Options::default())
     }
 
     /// Listen for incoming packets on the given socket.
     ///
     /// Defaults to an ephemeral port if not specified.
     pub fn with_socket(self, socket: std::net::UdpSocket) -> Result<Self, ClientError> {
-        Ok(Self(self.0.with_socket(socket)?))
+        Ok(Self(self.0.with_socket(socket)?, self.1))
     }
 
     /// Listen for incoming packets on the given address.
     ///
     /// Defaults to an ephemeral port if not specified.
     pub fn with_bind<A: std::net::ToSocketAddrs>(self, addrs: A) -> Result<Self, ClientError> {
-        // We use std to avoid async
-        let socket = std::net::UdpSocket::bind(addrs)?;
-        self.with_socket(socket)
+        Ok(Self(self.0.with_bind(addrs)?, self.1))
     }
 
     /// Use the provided [Settings] instead of the defaults.
@@ -68,22 +148,41 @@ impl ClientBuilder {
     /// **WARNING**: [Settings::verify_peer] is set to false by default.
     /// This will completely bypass certificate verification and is generally not recommended.
     pub fn with_settings(self, settings: Settings) -> Self {
-        Self(self.0.with_settings(settings))
+        Self(self.0.with_settings(settings), self.1)
+    }
+
+    /// Write a qlog trace of each connection to a file in `dir`.
+    ///
+    /// Disabled by default. Set this after [ClientBuilder::with_settings], which replaces it.
+    pub fn with_qlog_dir(self, dir: impl AsRef<std::path::Path>) -> Self {
+        Self(self.0.with_qlog_dir(dir), self.1)
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// Wireshark can use the file to decrypt packet captures. Nothing is written if the
+    /// variable isn't set, or unless the `keylog` feature is enabled. Disabled by default.
+    /// Set this after [ClientBuilder::with_settings], which replaces it.
+    pub fn with_keylog(self, enabled: bool) -> Self {
+        Self(self.0.with_keylog(enabled), self.1)
     }
 
     /// Optional: Use a client certificate for mTLS.
     pub fn with_single_cert(
         self,
-        chain: Vec<ez::CertificateDer<'static>>,
-        key: ez::PrivateKeyDer<'static>,
+        chain: impl IntoIterator<Item = ez::CertificateDer<'static>>,
+        key: impl Into<ez::PrivateKeyDer<'static>>,
     ) -> Self {
-        Self(self.0.with_single_cert(chain, key))
+        Self(self.0.with_single_cert(chain, key), self.1)
     }
 
     /// Verify the server certificate against an explicit set of root
     /// certificates instead of the system trust store.
-    pub fn with_root_certificates(self, roots: Vec<ez::CertificateDer<'static>>) -> Self {
-        Self(self.0.with_root_certificates(roots))
+    pub fn with_root_certificates(
+        self,
+        roots: impl IntoIterator<Item = ez::CertificateDer<'static>>,
+    ) -> Self {
+        Self(self.0.with_root_certificates(roots), self.1)
     }
 
     /// Use this name for SNI and certificate verification instead of the URL's host.
@@ -92,7 +191,7 @@ impl ClientBuilder {
     /// match is. This is how you reach a host by IP, or through a tunnel, while
     /// still verifying the certificate it was actually issued for.
     pub fn with_server_name(self, name: impl Into<String>) -> Self {
-        Self(self.0.with_server_name(name))
+        Self(self.0.with_server_name(name), self.1)
     }
 
     /// Accept the server certificate only if the SHA-256 of its DER encoding
@@ -101,7 +200,7 @@ impl ClientBuilder {
     /// This mirrors the browser's `serverCertificateHashes` option and is the
     /// usual way to reach a relay using a short-lived self-signed certificate.
     pub fn with_server_certificate_hashes(self, hashes: Vec<[u8; 32]>) -> Self {
-        Self(self.0.with_server_certificate_hashes(hashes))
+        Self(self.0.with_server_certificate_hashes(hashes), self.1)
     }
 
     /// Send a PING on this interval, keeping an idle connection alive.
@@ -110,7 +209,43 @@ impl ClientBuilder {
     /// [Settings::max_idle_timeout] to have any effect; a third of it is a
     /// reasonable choice.
     pub fn with_keep_alive(self, interval: std::time::Duration) -> Self {
-        Self(self.0.with_keep_alive(interval))
+        Self(self.0.with_keep_alive(interval), self.1)
+    }
+
+    /// Close the connection after this long without hearing from the server.
+    ///
+    /// Each side advertises its own, and the connection uses the smaller of the two.
+    /// Defaults to [Settings::max_idle_timeout]; set this after
+    /// [ClientBuilder::with_settings], which replaces it.
+    pub fn with_idle_timeout(self, timeout: Duration) -> Self {
+        Self(self.0.with_idle_timeout(timeout), self.1)
+    }
+
+    /// Queue up to this many outgoing datagrams that haven't been sent yet.
+    ///
+    /// Once it's full, [Connection::send_datagram](crate::Connection::send_datagram) drops
+    /// new datagrams while [Connection::send_datagram_wait](crate::Connection::send_datagram_wait)
+    /// waits. Defaults to [Settings::dgram_send_max_queue_len]; set this after
+    /// [ClientBuilder::with_settings], which replaces it.
+    pub fn with_datagram_send_buffer(self, datagrams: usize) -> Self {
+        Self(self.0.with_datagram_send_buffer(datagrams), self.1)
+    }
+
+    /// Give up on a connection attempt that hasn't finished its handshake after this long.
+    ///
+    /// The deadline covers DNS, the QUIC handshake and the server's response to the
+    /// CONNECT request, and each redirect gets a fresh one. A missed deadline fails with
+    /// [ConnectionError::TimedOut](ez::ConnectionError::TimedOut), which
+    /// [is retryable](ClientError::is_retryable). Disabled by default, leaving only the
+    /// [idle timeout](Self::with_idle_timeout).
+    pub fn with_handshake_timeout(self, timeout: Duration) -> Self {
+        Self(
+            self.0,
+            Options {
+                handshake_timeout: Some(timeout),
+                ..self.1
+            },
+        )
     }
 
     /// Enable UDP generic segmentation offload (GSO), on by default.
@@ -121,13 +256,90 @@ impl ClientBuilder {
     ///
     /// Only Linux supports GSO; elsewhere this does nothing.
     pub fn with_gso(self, enabled: bool) -> Self {
-        Self(self.0.with_gso(enabled))
+        Self(self.0.with_gso(enabled), self.1)
+    }
+
+    /// Apply these [SocketOptions](crate::SocketOptions) to the UDP socket used for each connection.
+    ///
+    /// Set this before [ClientBuilder::with_bind], which needs it to apply
+    /// [SocketOptions::with_ipv6_only](crate::SocketOptions::with_ipv6_only).
+    pub fn with_socket_options(self, options: crate::SocketOptions) -> Self {
+        Self(self.0.with_socket_options(options), self.1)
+    }
+
+    /// Wait this long for a connection attempt before racing the next resolved address.
+    ///
+    /// When a host resolves to several addresses, they're tried in turn with the address
+    /// families interleaved (RFC 8305, "Happy Eyeballs"), and the first to complete the
+    /// handshake wins. Defaults to [DEFAULT_CONNECTION_ATTEMPT_DELAY](crate::DEFAULT_CONNECTION_ATTEMPT_DELAY).
+    pub fn with_connection_attempt_delay(self, delay: std::time::Duration) -> Self {
+        Self(self.0.with_connection_attempt_delay(delay), self.1)
+    }
+
+    /// Follow up to `max` redirects from the server, reconnecting to each new URL in turn.
+    ///
+    /// Disabled by default, so a redirect fails the connection with
+    /// [ConnectError::Redirect](h3::ConnectError::Redirect). Each hop binds a fresh
+    /// ephemeral socket, even if [ClientBuilder::with_socket] was used for the first.
+    pub fn with_follow_redirects(self, max: usize) -> Self {
+        let follow_redirects = max;
+        Self(
+            self.0,
+            Options {
+                follow_redirects,
+                ..self.1
+            },
+        )
+    }
+
+    /// Inject the given [Faults] into the connection, for resilience testing.
+    ///
+    /// **WARNING**: This deliberately degrades the connection; never enable it in production.
+    pub fn with_faults(self, faults: Faults) -> Self {
+        let faults = Some(faults);
+        Self(self.0, Options { faults, ..self.1 })
+    }
+
+    /// Use these codes when the session or a stream is dropped without being closed.
+    ///
+    /// Defaults to the values in [codes](crate::proto::codes), which every backend
+    /// shares so a peer can tell why a stream was abandoned.
+    pub fn with_drop_codes(self, drop_codes: DropCodes) -> Self {
+        Self(
+            self.0,
+            Options {
+                drop_codes,
+                ..self.1
+            },
+        )
+    }
+
+    /// Open sessions as [datagram-only](SessionMode::DatagramOnly), or with streams.
+    ///
+    /// Defaults to [SessionMode::Full]. The server has to make the same choice, usually
+    /// from the negotiated subprotocol, since nothing on the wire says which.
+    pub fn with_session_mode(self, mode: SessionMode) -> Self {
+        Self(self.0, Options { mode, ..self.1 })
+    }
+
+    /// Measure timeouts with `clock` instead of the tokio timer.
+    ///
+    /// This covers the handshake timeout, retry backoff, keep-alives, stream header
+    /// timeouts and datagram expiry. Defaults to [TokioClock], which already follows
+    /// `tokio::time::pause`; QUIC's own timers, like the idle timeout, still use quiche's.
+    pub fn with_clock(self, clock: impl Clock) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(clock);
+        Self(
+            self.0.with_clock(clock.clone()),
+            Options { clock, ..self.1 },
+        )
     }
 
     /// Connect to the WebTransport server at the given URL.
     ///
-    /// DNS resolution and socket setup happen eagerly. The returned [Connecting]
-    /// has an [established](Connecting::established) method to complete the full handshake
+    /// DNS resolution and socket setup happen eagerly, as does the QUIC handshake when
+    /// several addresses are raced. The returned [Connecting] has an
+    /// [established](Connecting::established) method to complete the full handshake
     /// (TLS + SETTINGS + CONNECT).
     ///
     /// This takes ownership because the underlying quiche implementation doesn't support reusing the same socket.
@@ -136,28 +348,95 @@ impl ClientBuilder {
         request: impl Into<ConnectRequest>,
     ) -> Result<Connecting, ClientError> {
         let request = request.into();
-        let (host, port) = Self::target(&request)?;
+        let clock = self.1.clock.clone();
+        let deadline = self
+            .1
+            .handshake_timeout
+            .map(|timeout| clock.now() + timeout);
+
+        // Reject a URL we can't connect to before doing any network I/O.
+        let target = ConnectTarget::new(&request.url)?;
+        let host = target.server_name();
 
-        let connecting = self.0.connect(&host, port).await?;
+        let mut timing = HandshakeTiming::default();
+
+        let remotes = match target.addr() {
+            Some(remote) => vec![remote],
+            None => {
+                let start = Instant::now();
+                let lookup = tokio::net::lookup_host((host.as_str(), target.port));
+                let remotes = within(&*clock, deadline, lookup)
+                    .await?
+                    .map_err(|err| ClientError::Dns(host.clone(), Arc::new(err)))?;
+                timing.dns = Some(start.elapsed());
+                happy_eyeballs::interleave(remotes)
+            }
+        };
+
+        // Keep a copy to dial any redirects with, since this one is consumed.
+        let redirect = (self.1.follow_redirects > 0).then(|| {
+            let follow_redirects = self.1.follow_redirects - 1;
+            let options = Options {
+                follow_redirects,
+                ..self.1.clone()
+            };
+            ClientBuilder(self.0.fork(), options)
+        });
+
+        // When the host has several addresses, this races them through the QUIC handshake.
+        let started = Instant::now();
+        let connecting = within(&*clock, deadline, self.0.connect_addrs(&host, remotes)).await??;
 
         Ok(Connecting {
             connecting,
             request,
+            deadline,
+            clock,
+            faults: self.1.faults,
+            drop_codes: self.1.drop_codes,
+            mode: self.1.mode,
+            timing,
+            started,
+            redirect,
         })
     }
 
-    /// The host and port to dial for a request.
-    fn target(request: &ConnectRequest) -> Result<(String, u16), ClientError> {
-        // `Host` renders IPv6 in URL form, bracketed, which is not what a
-        // resolver or a TLS server name wants.
-        let host = match request.url.host() {
-            Some(url::Host::Domain(host)) => host.to_string(),
-            Some(url::Host::Ipv4(ip)) => ip.to_string(),
-            Some(url::Host::Ipv6(ip)) => ip.to_string(),
-            None => return Err(ClientError::InvalidUrl(request.url.to_string())),
-        };
+    /// Connect and complete the handshake, trying again after failures that might not happen twice.
+    ///
+    /// Only errors that [are retryable](ClientError::is_retryable) are retried, up to
+    /// [RetryPolicy::max_attempts] in all, waiting [RetryPolicy::backoff] in between; any
+    /// other error is returned straight away, as is the last one. Every attempt starts from
+    /// the original request, offering the same protocols in the same order, and the server's
+    /// choice is checked against that offer each time, so a retry that reaches a different
+    /// server can't return a session speaking something that wasn't offered. Retries bind a
+    /// fresh ephemeral socket, like redirects.
+    pub async fn connect_with_retry(
+        self,
+        request: impl Into<ConnectRequest>,
+        policy: RetryPolicy,
+    ) -> Result<Connection, ClientError> {
+        let request = request.into();
+
+        let mut builder = self;
+        let mut attempt = 1;
+        loop {
+            let next = ClientBuilder(builder.0.fork(), builder.1.clone());
+            let res = match builder.connect(request.clone()).await {
+                Ok(connecting) => connecting.established().await,
+                Err(err) => Err(err),
+            };
 
-        Ok((host, request.url.port().unwrap_or(443)))
+            match res {
+                Err(err) if err.is_retryable() && attempt < policy.max_attempts => {
+                    let backoff = policy.backoff(attempt);
+                    tracing::debug!(%err, attempt, ?backoff, "retrying connect");
+                    next.1.clock.sleep(backoff).await;
+                    builder = next;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
     }
 }
 
@@ -168,12 +447,90 @@ impl ClientBuilder {
 pub struct Connecting {
     connecting: ez::Connecting,
     request: ConnectRequest,
+    faults: Option<Faults>,
+    drop_codes: DropCodes,
+    mode: SessionMode,
+    timing: HandshakeTiming,
+
+    // When the QUIC handshake started, so the wait before `established` counts too.
+    started: Instant,
+
+    // When to give up on the handshake, if ever, and the clock that says when that is.
+    deadline: Option<Instant>,
+    clock: Arc<dyn Clock>,
+
+    // Dials the next hop if the server redirects us and we have hops left.
+    redirect: Option<ClientBuilder>,
 }
 
 impl Connecting {
     /// Wait for the full handshake to complete (TLS + SETTINGS + CONNECT).
+    ///
+    /// Redirects are followed here, up to [ClientBuilder::with_follow_redirects] hops.
     pub async fn established(self) -> Result<Connection, ClientError> {
-        let conn = self.connecting.established().await?;
-        Connection::connect(conn, self.request).await
+        self.established_spawned(Spawner::Tokio).await
+    }
+
+    /// Like [Connecting::established], but the session's background work runs in the
+    /// returned [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn established_driven(self) -> Result<(Connection, SessionDriver), ClientError> {
+        let (spawner, driver) = Spawner::driven();
+        let session = self.established_spawned(spawner).await?;
+        Ok((session, driver))
+    }
+
+    async fn established_spawned(mut self, spawner: Spawner) -> Result<Connection, ClientError> {
+        loop {
+            let conn = within(&*self.clock, self.deadline, self.connecting.established()).await??;
+
+            let mut timing = self.timing;
+            timing.quic = Some(self.started.elapsed());
+
+            let faults = self.faults.clone().map(FaultInjector::new);
+            if let Some(stall) = faults.as_ref().and_then(|faults| faults.control_stall()) {
+                self.clock.sleep(stall).await;
+            }
+
+            let connect = Connection::connect_timed(
+                conn,
+                self.request.clone(),
+                timing,
+                self.drop_codes,
+                self.mode,
+                spawner.clone(),
+            );
+            let res = within(&*self.clock, self.deadline, connect)
+                .await
+                .and_then(|res| res);
+            match (res, self.redirect.take()) {
+                (
+                    Err(ClientError::Connect(h3::ConnectError::Redirect { location, .. })),
+                    Some(redirect),
+                ) => {
+                    tracing::debug!(from = %self.request.url, to = %location, "following redirect");
+                    let mut request = self.request;
+                    request.url = location;
+                    self = redirect.connect(request).await?;
+                }
+                (res, _) => return Ok(res?.with_faults(faults)),
+            }
+        }
+    }
+}
+
+// Run `fut` until `deadline`, failing as a timed out connection if it passes first.
+//
+// Dropping the handshake closes any connection it made.
+async fn within<T>(
+    clock: &dyn Clock,
+    deadline: Option<Instant>,
+    fut: impl Future<Output = T>,
+) -> Result<T, ClientError> {
+    match deadline {
+        Some(deadline) => clock
+            .timeout_at(deadline, fut)
+            .await
+            .map_err(|_| ez::ConnectionError::TimedOut.into()),
+        None => Ok(fut.await),
     }
 }
@@ -1,31 +1,57 @@
-use crate::{ez, h3, ClientError, RecvStream, SendStream, SessionError};
+use crate::{
+    driver::Spawner, early::Early, ez, h3, send::urgency, ClientError, FaultInjector, RecvStream,
+    SendStream, SessionDriver, SessionError,
+};
 
-use bytes::{Bytes, BytesMut};
-use futures::{ready, stream::FuturesUnordered, Stream, StreamExt};
-use web_transport_proto::{ConnectRequest, ConnectResponse, Frame, StreamUni, VarInt};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{join, stream::FuturesUnordered, try_join, Stream, StreamExt};
+use tokio::sync::watch;
+use tracing::Instrument;
+use url::Url;
+use web_transport_proto::{
+    codes::{self, DropCodes},
+    Capsule, ConnectRequest, ConnectResponse, Frame, HeaderBudget, HeaderViolation, SessionFlow,
+    SessionMode, SessionPermit, StreamUni, UnknownStreamPolicy, VarInt,
+};
+use web_transport_trait::{Clock, CloseRecord, Closed, DatagramOptions, SessionId, StreamOptions};
 
 use std::{
+    collections::{HashMap, VecDeque},
     future::{poll_fn, Future},
     io::Cursor,
     pin::Pin,
     sync::{Arc, Mutex},
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
 
-// "conn" in ascii; if you see this then close(code)
-// hex: 0x636E6E6F, or 0x52E50ACE926F as an HTTP error code
-// decimal: 1668181615, or 91143682298479 as an HTTP error code
-const DROP_CODE: u64 = web_transport_proto::error_to_http3(0x636E6E6F);
+// Stop a stream the peer opened on a datagram-only session.
+fn refuse_uni(mut recv: ez::RecvStream) {
+    recv.stop(web_transport_proto::error_to_http3(codes::STREAMS_DISABLED));
+}
+
+fn refuse_bi(mut send: ez::SendStream, recv: ez::RecvStream) {
+    send.reset(web_transport_proto::error_to_http3(codes::STREAMS_DISABLED));
+    refuse_uni(recv);
+}
+
+// The span around a session's background tasks, so their events carry its ID.
+fn session_span(id: SessionId) -> tracing::Span {
+    tracing::info_span!("session", %id)
+}
 
 struct ConnectionDrop {
     conn: ez::Connection,
+    code: u32,
+    id: SessionId,
 }
 
 impl Drop for ConnectionDrop {
     fn drop(&mut self) {
         if !self.conn.is_closed() {
-            tracing::warn!("connection dropped without calling `close`");
-            self.conn.close(DROP_CODE, "connection dropped");
+            tracing::warn!(session = %self.id, "connection dropped without calling `close`");
+            let code = web_transport_proto::error_to_http3(self.code);
+            self.conn.close(code, "connection dropped");
         }
     }
 }
@@ -40,6 +66,9 @@ impl Drop for ConnectionDrop {
 pub struct Connection {
     conn: ez::Connection,
 
+    // Identifies the session in logs, as the `session` span around its background tasks.
+    id: SessionId,
+
     // Dropped when all references are dropped.
     #[allow(dead_code)]
     drop: Arc<ConnectionDrop>,
@@ -57,12 +86,49 @@ pub struct Connection {
     header_datagram: Vec<u8>,
 
     // Keep a reference to the settings and connect stream to avoid closing them until dropped.
-    #[allow(dead_code)]
+    // Also tells us whether the peer sent GOAWAY.
     settings: Option<Arc<h3::Settings>>,
 
     // The request and response that were sent and received.
     request: ConnectRequest,
     response: ConnectResponse,
+
+    // The send side of the CONNECT stream, used to write the DrainWebTransportSession capsule.
+    connect_send: Option<Arc<tokio::sync::Mutex<ez::SendStream>>>,
+
+    // Set once the peer sends a DrainWebTransportSession capsule.
+    draining: Arc<watch::Sender<bool>>,
+
+    // The URL from the peer's latest ReconnectHint capsule, if any.
+    reconnect: Arc<watch::Sender<Option<Url>>>,
+
+    // The first close code and reason from either side, kept for close_reason().
+    close_record: CloseRecord,
+
+    // The peer's session-level flow control limits, shared with every stream we send on.
+    flow: SessionFlow,
+
+    // Faults to inject for resilience testing, if configured on the builder.
+    faults: Option<Arc<FaultInjector>>,
+
+    // How long each phase of the client handshake took.
+    handshake: HandshakeTiming,
+
+    // Sent when a stream or the session is dropped without being closed.
+    codes: DropCodes,
+
+    // The slot this session holds in the server's session limits.
+    #[allow(dead_code)]
+    permit: Option<Arc<SessionPermit>>,
+
+    // Datagrams that arrived before the CONNECT response, read before any new ones.
+    early_datagrams: Arc<Mutex<VecDeque<Bytes>>>,
+
+    // Whether streams may be opened and accepted.
+    mode: SessionMode,
+
+    // Runs background tasks, on tokio unless the application drives them.
+    spawner: Spawner,
 }
 
 impl Connection {
@@ -70,6 +136,9 @@ impl Connection {
         conn: ez::Connection,
         settings: h3::Settings,
         connect: h3::Connected,
+        codes: DropCodes,
+        mode: SessionMode,
+        spawner: Spawner,
     ) -> Self {
         // The session ID is the stream ID of the CONNECT request.
         let session_id = connect.session_id();
@@ -86,47 +155,122 @@ impl Connection {
         let mut header_datagram = Vec::new();
         session_id.encode(&mut header_datagram);
 
-        // Accept logic is stateful, so use an Arc<Mutex> to share it.
-        let accept = SessionAccept::new(conn.clone(), session_id);
+        let flow = SessionFlow::new(settings.peer_limits);
+
+        let id = SessionId::next();
+        let span = session_span(id);
+
+        // Our GOAWAY mustn't cover this session, and someone needs to read the peer's.
+        let settings = Arc::new(settings);
+        settings.processed(session_id);
+        if let Some(control) = settings.run() {
+            spawner.spawn(control.instrument(span.clone()));
+        }
+
+        let accept = match mode {
+            SessionMode::Full => {
+                // Accept logic is stateful, so use an Arc<Mutex> to share it.
+                let accept = SessionAccept::new(conn.clone(), session_id, flow.clone(), codes);
+                Some(Arc::new(Mutex::new(accept)))
+            }
+            // Nothing is ever accepted, so streams are reset as they arrive without reading them.
+            SessionMode::DatagramOnly => {
+                let refuse = Self::refuse_streams(conn.clone());
+                spawner.spawn(refuse.instrument(span.clone()));
+                None
+            }
+        };
 
-        let drop = Arc::new(ConnectionDrop { conn: conn.clone() });
+        let drop = Arc::new(ConnectionDrop {
+            conn: conn.clone(),
+            code: codes.session,
+            id,
+        });
 
         let this = Self {
             conn,
+            id,
             drop,
-            accept: Some(Arc::new(Mutex::new(accept))),
+            accept,
             session_id: Some(session_id),
             header_uni,
             header_bi,
             header_datagram,
-            request: connect.request.clone(),
-            response: connect.response.clone(),
-            settings: Some(Arc::new(settings)),
+            request: connect.request,
+            response: connect.response,
+            connect_send: Some(Arc::new(tokio::sync::Mutex::new(connect.send))),
+            draining: Arc::new(watch::Sender::new(false)),
+            reconnect: Arc::new(watch::Sender::new(None)),
+            close_record: CloseRecord::new(),
+            flow,
+            settings: Some(settings),
+            faults: None,
+            handshake: HandshakeTiming::default(),
+            codes,
+            permit: None,
+            early_datagrams: Default::default(),
+            mode,
+            spawner,
         };
 
         // Run a background task to check if the connect stream is closed.
-        tokio::spawn(this.clone().run_closed(connect));
+        let closed = this.clone().run_closed(connect.recv);
+        this.spawner.spawn(closed.instrument(span.clone()));
+
+        // Run another to write the BLOCKED capsules queued by flow control.
+        if let Some(send) = this.connect_send.clone() {
+            let flow = Self::run_flow(this.flow.clone(), send);
+            this.spawner.spawn(flow.instrument(span));
+        }
 
         tracing::debug!(url = %this.request().url, "WebTransport connection established");
 
         this
     }
 
-    // Keep reading from the control stream until it's closed.
-    async fn run_closed(self, mut connect: h3::Connected) {
+    // Keep reading capsules from the CONNECT stream until it's closed.
+    //
+    // The response was read frame-exactly, so any capsules that arrived in the same
+    // packet are still in the stream and are picked up by the first read here.
+    async fn run_closed(self, mut recv: ez::RecvStream) {
+        self.read_capsules(&mut recv).await;
+
+        // Wake anything waiting on flow control so it sees the session is gone.
+        self.flow.close();
+    }
+
+    async fn read_capsules(&self, recv: &mut ez::RecvStream) {
+        let mut reader = web_transport_proto::Http3CapsuleReader::new(recv);
         loop {
-            match web_transport_proto::Capsule::read(&mut connect.recv).await {
-                Ok(Some(web_transport_proto::Capsule::CloseWebTransportSession {
-                    code,
-                    reason,
-                })) => {
-                    // TODO We shouldn't be closing the QUIC connection with the same error.
-                    // Instead, we should return it to the application.
-                    self.close(code, &reason);
+            match reader.read().await {
+                Ok(Some(Capsule::CloseWebTransportSession { code, reason })) => {
+                    // If close() got there first, this only marks the race.
+                    if self.close_record.remote(code, &reason) {
+                        // TODO We shouldn't be closing the QUIC connection with the same error.
+                        // Instead, we should return it to the application.
+                        self.close_conn(code, &String::from_utf8_lossy(&reason));
+                    }
                     return;
                 }
-                Ok(Some(web_transport_proto::Capsule::Grease { .. })) => {}
-                Ok(Some(web_transport_proto::Capsule::Unknown { typ, payload })) => {
+                Ok(Some(Capsule::DrainWebTransportSession)) => {
+                    tracing::debug!("peer is draining the session");
+                    self.draining.send_replace(true);
+                }
+                Ok(Some(Capsule::ReconnectHint { url })) => {
+                    tracing::debug!(%url, "peer sent a reconnect hint");
+                    self.reconnect.send_replace(Some(url));
+                }
+                Ok(Some(capsule @ (Capsule::WtMaxData { .. } | Capsule::WtMaxStreams { .. }))) => {
+                    self.flow.on_capsule(&capsule)
+                }
+                Ok(Some(Capsule::WtDataBlocked { limit })) => {
+                    tracing::debug!(limit, "peer is blocked on session data");
+                }
+                Ok(Some(Capsule::WtStreamsBlocked { bidi, limit })) => {
+                    tracing::debug!(bidi, limit, "peer is blocked on session streams");
+                }
+                Ok(Some(Capsule::Grease { .. })) => {}
+                Ok(Some(Capsule::Unknown { typ, payload })) => {
                     tracing::warn!("unknown capsule: type={typ} size={}", payload.len());
                 }
                 Ok(None) => {
@@ -134,13 +278,24 @@ impl Connection {
                     return;
                 }
                 Err(_) => {
-                    self.close(500, "capsule error");
+                    self.close(codes::CAPSULE_ERROR, "capsule error");
                     return;
                 }
             }
         }
     }
 
+    // Write flow control capsules on the CONNECT stream until the session is closed.
+    async fn run_flow(flow: SessionFlow, send: Arc<tokio::sync::Mutex<ez::SendStream>>) {
+        while let Some(capsule) = poll_fn(|cx| flow.poll_capsule(cx)).await {
+            let frame = capsule_frame(&capsule);
+            if let Err(err) = send.lock().await.write_all(&frame).await {
+                tracing::debug!(?err, "failed to write flow control capsule");
+                return;
+            }
+        }
+    }
+
     /// Connect using an established QUIC connection if you want to create the connection yourself.
     ///
     /// This will only work with a brand new QUIC connection using the HTTP/3 ALPN.
@@ -148,31 +303,147 @@ impl Connection {
         conn: ez::Connection,
         request: impl Into<ConnectRequest>,
     ) -> Result<Connection, ClientError> {
+        Self::connect_timed(
+            conn,
+            request.into(),
+            HandshakeTiming::default(),
+            DropCodes::default(),
+            SessionMode::Full,
+            Spawner::Tokio,
+        )
+        .await
+    }
+
+    /// Like [Connection::connect], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn connect_driven(
+        conn: ez::Connection,
+        request: impl Into<ConnectRequest>,
+    ) -> Result<(Connection, SessionDriver), ClientError> {
+        let (spawner, driver) = Spawner::driven();
+        let session = Self::connect_timed(
+            conn,
+            request.into(),
+            HandshakeTiming::default(),
+            DropCodes::default(),
+            SessionMode::Full,
+            spawner,
+        )
+        .await?;
+
+        Ok((session, driver))
+    }
+
+    /// Finish the handshake, filling in the HTTP/3 phases of `timing`.
+    pub(crate) async fn connect_timed(
+        conn: ez::Connection,
+        request: ConnectRequest,
+        mut timing: HandshakeTiming,
+        codes: DropCodes,
+        mode: SessionMode,
+        spawner: Spawner,
+    ) -> Result<Connection, ClientError> {
+        let start = Instant::now();
+
         // Perform the H3 handshake by sending/reciving SETTINGS frames.
-        let settings = h3::Settings::connect(&conn).await?;
+        let settings = async {
+            let settings = h3::Settings::connect(&conn).await?;
+            Ok::<_, ClientError>((settings, start.elapsed()))
+        };
+
+        // Send the HTTP/3 CONNECT request without waiting for the peer's SETTINGS, saving a round trip.
+        // They're still validated before the session is returned, abandoning the CONNECT if unsupported.
+        let connect = async { Ok::<_, ClientError>(h3::Connected::open(&conn, request).await?) };
 
-        // Send the HTTP/3 CONNECT request.
-        let connect = h3::Connected::open(&conn, request).await?;
+        let ((settings, elapsed), connect) = try_join!(settings, connect)?;
+        timing.settings = Some(elapsed);
+        timing.connect = Some(start.elapsed().saturating_sub(elapsed));
+
+        tracing::debug!(
+            url = %connect.request.url,
+            dns = ?timing.dns,
+            quic = ?timing.quic,
+            settings = ?timing.settings,
+            connect = ?timing.connect,
+            "handshake complete"
+        );
 
         // Return the resulting session with a reference to the control/connect streams.
         // If either stream is closed, then the session will be closed, so we need to keep them around.
-        let session = Connection::new(conn, settings, connect);
+        let mut session = Connection::new(conn, settings, connect, codes, mode, spawner);
+        session.handshake = timing;
 
         Ok(session)
     }
 
+    // Deliver the streams and datagrams that arrived before the CONNECT response.
+    pub(crate) fn with_early(self, early: Early) -> Self {
+        match &self.accept {
+            Some(accept) => accept.lock().unwrap().push_early(early.uni, early.bi),
+            // Only a datagram-only session is accepted without accept state.
+            None => {
+                early.uni.into_iter().for_each(refuse_uni);
+                for (send, recv) in early.bi {
+                    refuse_bi(send, recv);
+                }
+            }
+        }
+        *self.early_datagrams.lock().unwrap() = early.datagrams;
+        self
+    }
+
+    pub(crate) fn with_faults(mut self, faults: Option<Arc<FaultInjector>>) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    pub(crate) fn with_permit(mut self, permit: Option<SessionPermit>) -> Self {
+        self.permit = permit.map(Arc::new);
+        self
+    }
+
+    // Reset every stream the peer opens on a datagram-only session, until the connection closes.
+    async fn refuse_streams(conn: ez::Connection) {
+        let uni = async {
+            while let Ok(recv) = conn.accept_uni().await {
+                refuse_uni(recv);
+            }
+        };
+
+        let bi = async {
+            while let Ok((send, recv)) = conn.accept_bi().await {
+                refuse_bi(send, recv);
+            }
+        };
+
+        join!(uni, bi);
+    }
+
+    /// Whether this session carries streams, or only datagrams.
+    pub fn mode(&self) -> SessionMode {
+        self.mode
+    }
+
+    fn check_streams(&self) -> Result<(), SessionError> {
+        match self.mode {
+            SessionMode::Full => Ok(()),
+            SessionMode::DatagramOnly => Err(SessionError::StreamsDisabled),
+        }
+    }
+
     /// Accept a new unidirectional stream.
     ///
     /// Waits for a new incoming unidirectional stream from the remote peer.
     /// Returns a [RecvStream] that can be used to read data from the stream.
     pub async fn accept_uni(&self) -> Result<RecvStream, SessionError> {
+        self.check_streams()?;
         if let Some(accept) = &self.accept {
-            poll_fn(|cx| accept.lock().unwrap().poll_accept_uni(cx)).await
+            poll_accept(accept, |accept, cx| accept.poll_accept_uni(cx)).await
         } else {
             self.conn
                 .accept_uni()
                 .await
-                .map(RecvStream::new)
+                .map(|recv| RecvStream::new(recv, self.codes.recv))
                 .map_err(Into::into)
         }
     }
@@ -182,29 +453,119 @@ impl Connection {
     /// Waits for a new incoming bidirectional stream from the remote peer.
     /// Returns a ([SendStream], [RecvStream]) pair for sending and receiving data.
     pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), SessionError> {
+        self.check_streams()?;
         if let Some(accept) = &self.accept {
-            poll_fn(|cx| accept.lock().unwrap().poll_accept_bi(cx)).await
+            poll_accept(accept, |accept, cx| accept.poll_accept_bi(cx)).await
         } else {
             self.conn
                 .accept_bi()
                 .await
-                .map(|(send, recv)| (SendStream::new(send), RecvStream::new(recv)))
+                .map(|(send, recv)| {
+                    (
+                        SendStream::new(send, self.codes.send, self.flow.clone()),
+                        RecvStream::new(recv, self.codes.recv),
+                    )
+                })
                 .map_err(Into::into)
         }
     }
 
+    /// Start accepting unidirectional streams of a custom HTTP/3 stream type.
+    ///
+    /// Streams with a type this crate doesn't know, such as HTTP/3 extension streams,
+    /// are dropped once their type has been read. After registering `stream_type`,
+    /// they're queued for [Connection::accept_raw_uni] instead. Register before the
+    /// peer might open one, since any that arrived earlier were already dropped.
+    ///
+    /// The WebTransport and QPACK stream types are always handled internally.
+    pub fn register_uni(&self, stream_type: StreamUni) {
+        if let Some(accept) = &self.accept {
+            accept.lock().unwrap().register_uni(stream_type);
+        }
+    }
+
+    /// Accept a unidirectional stream of a custom HTTP/3 stream type.
+    ///
+    /// This [registers](Connection::register_uni) `stream_type` if it wasn't already.
+    /// The stream type has been read, so the returned stream starts at its payload.
+    ///
+    /// A [raw](Connection::raw) connection has no HTTP/3 framing, so this only returns once it's closed.
+    pub async fn accept_raw_uni(&self, stream_type: StreamUni) -> Result<RecvStream, SessionError> {
+        match &self.accept {
+            Some(accept) => {
+                poll_accept(accept, |accept, cx| {
+                    accept.poll_accept_raw_uni(cx, stream_type)
+                })
+                .await
+            }
+            None => Err(self.closed().await),
+        }
+    }
+
     /// Open a new unidirectional stream.
     ///
     /// Creates a new outgoing unidirectional stream to the remote peer.
     /// Returns a [SendStream] that can be used to send data.
     pub async fn open_uni(&self) -> Result<SendStream, SessionError> {
-        let mut send = self.conn.open_uni().await?;
+        self.open_uni_with_data(Bytes::new()).await
+    }
 
-        send.write_all(&self.header_uni)
+    /// Open a new unidirectional stream, sending `initial` along with the stream header.
+    ///
+    /// The header and payload reach the driver together, in one wakeup, so a short request
+    /// goes out in the first packet rather than waiting on a second write. Like
+    /// [SendStream::write_all], it waits for session flow control credit for all of it.
+    pub async fn open_uni_with_data(&self, initial: Bytes) -> Result<SendStream, SessionError> {
+        self.check_streams()?;
+
+        // Wait until the peer's session-level flow control allows another stream.
+        poll_fn(|cx| self.flow.poll_open(cx, false)).await;
+        self.open_uni_framed(&self.header_uni, self.flow.clone(), initial)
             .await
-            .map_err(SessionError::Header)?;
+    }
+
+    /// Open a new unidirectional stream with [StreamOptions].
+    ///
+    /// The priority reaches quiche before any of your data, as an urgency where lower is
+    /// sent first, so a higher [order](StreamOptions::order_u8) becomes a lower urgency.
+    pub async fn open_uni_with(&self, options: StreamOptions) -> Result<SendStream, SessionError> {
+        let mut send = self.open_uni().await?;
+        if let Some(order) = options.order_u8() {
+            send.set_priority(urgency(order));
+        }
+        Ok(send)
+    }
+
+    /// Open a unidirectional stream with a custom HTTP/3 stream type.
+    ///
+    /// Only `stream_type` is written, with no session ID, so the peer needs to accept it
+    /// by type (ex. [Connection::accept_raw_uni]) rather than with [Connection::accept_uni].
+    pub async fn open_raw_uni(&self, stream_type: StreamUni) -> Result<SendStream, SessionError> {
+        let mut header = Vec::new();
+        stream_type.encode(&mut header);
+
+        // Not part of the WebTransport session, so its flow control doesn't apply.
+        self.open_uni_framed(&header, SessionFlow::default(), Bytes::new())
+            .await
+    }
+
+    async fn open_uni_framed(
+        &self,
+        header: &[u8],
+        flow: SessionFlow,
+        initial: Bytes,
+    ) -> Result<SendStream, SessionError> {
+        if let Some(delay) = self.faults.as_ref().and_then(|faults| faults.open_delay()) {
+            self.conn.clock().sleep(delay).await;
+        }
+
+        let mut send = self.conn.open_uni().await?;
+        Self::write_header(&mut send, header, &flow, initial).await?;
 
-        Ok(SendStream::new(send))
+        let mut send = SendStream::new(send, self.codes.send, flow);
+        self.inject_reset(&mut send);
+
+        Ok(send)
     }
 
     /// Open a new bidirectional stream.
@@ -212,13 +573,78 @@ impl Connection {
     /// Creates a new outgoing bidirectional stream to the remote peer.
     /// Returns a ([SendStream], [RecvStream]) pair for sending and receiving data.
     pub async fn open_bi(&self) -> Result<(SendStream, RecvStream), SessionError> {
+        self.open_bi_with_data(Bytes::new()).await
+    }
+
+    /// Open a new bidirectional stream, sending `initial` along with the stream header.
+    ///
+    /// See [Connection::open_uni_with_data]; this suits a request/response exchange,
+    /// where the request fits in the first flight.
+    pub async fn open_bi_with_data(
+        &self,
+        initial: Bytes,
+    ) -> Result<(SendStream, RecvStream), SessionError> {
+        self.check_streams()?;
+        if let Some(delay) = self.faults.as_ref().and_then(|faults| faults.open_delay()) {
+            self.conn.clock().sleep(delay).await;
+        }
+
+        // Wait until the peer's session-level flow control allows another stream.
+        poll_fn(|cx| self.flow.poll_open(cx, true)).await;
+
         let (mut send, recv) = self.conn.open_bi().await?;
+        Self::write_header(&mut send, &self.header_bi, &self.flow, initial).await?;
 
-        send.write_all(&self.header_bi)
-            .await
-            .map_err(SessionError::Header)?;
+        let mut send = SendStream::new(send, self.codes.send, self.flow.clone());
+        self.inject_reset(&mut send);
 
-        Ok((SendStream::new(send), RecvStream::new(recv)))
+        Ok((send, RecvStream::new(recv, self.codes.recv)))
+    }
+
+    /// Open a new bidirectional stream with [StreamOptions].
+    ///
+    /// See [Connection::open_uni_with].
+    pub async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(SendStream, RecvStream), SessionError> {
+        let (mut send, recv) = self.open_bi().await?;
+        if let Some(order) = options.order_u8() {
+            send.set_priority(urgency(order));
+        }
+        Ok((send, recv))
+    }
+
+    // Queue the stream header and `initial` under one lock, waking the driver once.
+    async fn write_header(
+        send: &mut ez::SendStream,
+        mut header: &[u8],
+        flow: &SessionFlow,
+        mut initial: Bytes,
+    ) -> Result<(), SessionError> {
+        // The payload counts against session flow control, which may only allow part of it at a time.
+        loop {
+            let credit = flow.reserve(initial.len()).await;
+            let chunk = initial.split_to(credit.size());
+            let size = chunk.len();
+
+            send.write_buf_all(&mut header.chain(chunk))
+                .await
+                .map_err(SessionError::Header)?;
+            credit.sent(size);
+
+            header = &[];
+            if initial.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn inject_reset(&self, send: &mut SendStream) {
+        if let Some(code) = self.faults.as_ref().and_then(|f| f.reset_code()) {
+            tracing::debug!(code, "injecting stream reset");
+            send.reset(code);
+        }
     }
 
     /// Asynchronously receives an application datagram from the remote peer.
@@ -227,11 +653,11 @@ impl Connection {
     /// peer over the connection.
     /// It waits for a datagram to become available and returns the received bytes.
     pub async fn read_datagram(&self) -> Result<Bytes, SessionError> {
-        let mut datagram = self
-            .conn
-            .read_datagram()
-            .await
-            .map_err(SessionError::from)?;
+        let early = self.early_datagrams.lock().unwrap().pop_front();
+        let mut datagram = match early {
+            Some(datagram) => datagram,
+            None => self.conn.read_datagram().await?,
+        };
 
         let mut cursor = Cursor::new(&datagram);
 
@@ -249,59 +675,278 @@ impl Connection {
         Ok(datagram)
     }
 
+    /// Returns true if the peer accepts datagrams.
+    ///
+    /// The peer has to enable them in both its HTTP/3 SETTINGS (`SETTINGS_H3_DATAGRAM`) and
+    /// its QUIC transport parameters. A peer may leave them out and only use streams, in
+    /// which case [`send_datagram`](Self::send_datagram) fails with
+    /// [`SessionError::DatagramsUnsupported`].
+    pub fn datagrams_supported(&self) -> bool {
+        self.peer_datagrams() && self.conn.max_datagram_size().is_some()
+    }
+
+    // Whether the peer's SETTINGS allow datagrams. A raw session has none to check.
+    fn peer_datagrams(&self) -> bool {
+        self.settings.as_ref().is_none_or(|s| s.peer_datagrams)
+    }
+
+    // Sending a datagram the peer didn't enable is a protocol violation, so refuse.
+    fn check_datagrams(&self) -> Result<(), SessionError> {
+        match self.peer_datagrams() {
+            true => Ok(()),
+            false => Err(SessionError::DatagramsUnsupported),
+        }
+    }
+
     /// Sends an application datagram to the remote peer.
     ///
     /// Datagrams are unreliable and may be dropped or delivered out of order.
     /// The data must be smaller than [`max_datagram_size`](Self::max_datagram_size).
     pub fn send_datagram(&self, data: Bytes) -> Result<(), SessionError> {
-        if !self.header_datagram.is_empty() {
-            // Unfortunately, we need to allocate/copy each datagram because of the quiche API.
-            // Pls go +1 if you care: https://github.com/quiche-rs/quiche/issues/1724
-            let mut buf = BytesMut::with_capacity(self.header_datagram.len() + data.len());
+        self.check_datagrams()?;
+        if self.faults.as_ref().is_some_and(|f| f.drop_datagram()) {
+            return Ok(());
+        }
 
-            // Prepend the datagram with the header indicating the session ID.
-            buf.extend_from_slice(&self.header_datagram);
-            buf.extend_from_slice(&data);
+        self.conn.send_datagram(self.frame_datagram(data))?;
+        Ok(())
+    }
 
-            self.conn.send_datagram(buf.into())?;
-        } else {
-            self.conn.send_datagram(data)?;
+    /// Sends an application datagram, waiting for buffer space if the send buffer is full.
+    ///
+    /// Unlike [`send_datagram`](Self::send_datagram), this applies backpressure instead of
+    /// dropping the datagram when there are too many outstanding datagrams.
+    ///
+    /// Datagrams are unreliable and may be dropped or delivered out of order.
+    /// The data must be smaller than [`max_datagram_size`](Self::max_datagram_size).
+    pub async fn send_datagram_wait(&self, data: Bytes) -> Result<(), SessionError> {
+        self.check_datagrams()?;
+        if self.faults.as_ref().is_some_and(|f| f.drop_datagram()) {
+            return Ok(());
         }
 
+        self.conn
+            .send_datagram_wait(self.frame_datagram(data))
+            .await?;
         Ok(())
     }
 
+    /// Sends an application datagram with [DatagramOptions].
+    ///
+    /// A datagram with a [max_age](DatagramOptions::max_age) is dropped if it's still queued
+    /// locally once that has passed. The [priority](DatagramOptions::priority) is ignored.
+    pub fn send_datagram_with(
+        &self,
+        data: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), SessionError> {
+        self.check_datagrams()?;
+        if self.faults.as_ref().is_some_and(|f| f.drop_datagram()) {
+            return Ok(());
+        }
+
+        self.conn
+            .send_datagram_with(self.frame_datagram(data), options)?;
+        Ok(())
+    }
+
+    // Prepend the header indicating the session ID, if there is one.
+    fn frame_datagram(&self, data: Bytes) -> Bytes {
+        if self.header_datagram.is_empty() {
+            return data;
+        }
+
+        // Unfortunately, we need to allocate/copy each datagram because of the quiche API.
+        // Pls go +1 if you care: https://github.com/quiche-rs/quiche/issues/1724
+        let mut buf = BytesMut::with_capacity(self.header_datagram.len() + data.len());
+        buf.extend_from_slice(&self.header_datagram);
+        buf.extend_from_slice(&data);
+        buf.into()
+    }
+
     /// Computes the maximum size of datagrams that may be passed to
     /// [`send_datagram`](Self::send_datagram).
     ///
     /// Returns `0` when the peer did not negotiate the QUIC datagram extension
     /// (or the value is otherwise unavailable) — in that case
-    /// [`send_datagram`](Self::send_datagram) will drop everything.
+    /// [`send_datagram`](Self::send_datagram) will drop everything — or didn't
+    /// enable datagrams in its SETTINGS, in which case it fails.
     pub fn max_datagram_size(&self) -> usize {
         match self.conn.max_datagram_size() {
-            Some(mtu) => mtu.saturating_sub(self.header_datagram.len()),
-            None => 0,
+            Some(mtu) if self.peer_datagrams() => mtu.saturating_sub(self.header_datagram.len()),
+            _ => 0,
         }
     }
 
+    /// Returns how many unidirectional streams the peer allows this session: those opened so
+    /// far plus [remaining_uni](Self::remaining_uni).
+    pub fn max_open_uni(&self) -> u64 {
+        self.flow.streams(false).0 + self.remaining_uni()
+    }
+
+    /// Returns how many more unidirectional streams can be opened before [open_uni](Self::open_uni) waits.
+    ///
+    /// This is the lower of the QUIC stream limit, which other sessions on the connection share,
+    /// and the peer's WebTransport session limit, if it set one.
+    pub fn remaining_uni(&self) -> u64 {
+        Self::remaining(self.conn.remaining_uni(), self.flow.streams(false).1)
+    }
+
+    /// Returns how many bidirectional streams the peer allows this session: those opened so
+    /// far plus [remaining_bi](Self::remaining_bi).
+    pub fn max_open_bi(&self) -> u64 {
+        self.flow.streams(true).0 + self.remaining_bi()
+    }
+
+    /// Returns how many more bidirectional streams can be opened before [open_bi](Self::open_bi) waits.
+    ///
+    /// This is the lower of the QUIC stream limit, which other sessions on the connection share,
+    /// and the peer's WebTransport session limit, if it set one.
+    pub fn remaining_bi(&self) -> u64 {
+        Self::remaining(self.conn.remaining_bi(), self.flow.streams(true).1)
+    }
+
+    fn remaining(quic: u64, session: Option<u64>) -> u64 {
+        session.map_or(quic, |session| session.min(quic))
+    }
+
     /// Immediately close the connection with an error code and reason.
     ///
     /// The error code is a u32 with WebTransport since it shares the error space with HTTP/3.
+    /// Only the first close takes effect, whether it was ours or the peer's; later calls are
+    /// ignored. [close_reason](Self::close_reason) returns the one that won.
     pub fn close(&self, code: u32, reason: &str) {
+        if self.close_record.local(code, reason.as_bytes()) {
+            self.close_conn(code, reason);
+        }
+    }
+
+    fn close_conn(&self, code: u32, reason: &str) {
         let code = if self.session_id.is_some() {
             web_transport_proto::error_to_http3(code)
         } else {
             code.into()
         };
 
+        // Wake anything waiting on flow control so it sees the session is closed.
+        self.flow.close();
+
         self.conn.close(code, reason)
     }
 
+    /// Return how the session was closed, or None if it hasn't been.
+    ///
+    /// Unlike [closed](Self::closed), this keeps the code and reason of the first close from
+    /// either side, and whether the other side closed too. A connection lost without a close
+    /// isn't recorded.
+    pub fn close_reason(&self) -> Option<Closed> {
+        self.close_record.get()
+    }
+
+    /// Ask the peer to wrap up the session with a `DrainWebTransportSession` capsule.
+    ///
+    /// Nothing is closed: streams and datagrams keep flowing in both directions, so a
+    /// server can signal a graceful shutdown and [close](Self::close) once the peer is done.
+    /// A [raw](Self::raw) session has no CONNECT stream, so this does nothing.
+    pub async fn drain(&self) -> Result<(), SessionError> {
+        let Some(send) = &self.connect_send else {
+            return Ok(());
+        };
+
+        let frame = capsule_frame(&Capsule::DrainWebTransportSession);
+        send.lock()
+            .await
+            .write_all(&frame)
+            .await
+            .map_err(|e| match e {
+                ez::StreamError::Connection(e) => e.into(),
+                e => SessionError::Header(e),
+            })
+    }
+
+    /// Ask the peer to reconnect to `url` with a `ReconnectHint` capsule.
+    ///
+    /// This is a vendor extension: other implementations ignore it. Nothing is closed, so a
+    /// server migrating its clients usually pairs it with [drain](Self::drain).
+    /// A [raw](Self::raw) session has no CONNECT stream, so this does nothing.
+    pub async fn send_reconnect_hint(&self, url: Url) -> Result<(), SessionError> {
+        let Some(send) = &self.connect_send else {
+            return Ok(());
+        };
+
+        let frame = capsule_frame(&Capsule::ReconnectHint { url });
+        send.lock()
+            .await
+            .write_all(&frame)
+            .await
+            .map_err(|e| match e {
+                ez::StreamError::Connection(e) => e.into(),
+                e => SessionError::Header(e),
+            })
+    }
+
+    /// Wait until the peer asks to wrap up the session with a `DrainWebTransportSession` capsule.
+    ///
+    /// An HTTP/3 GOAWAY from the peer drains the session the same way.
+    /// The session keeps working; it's up to the application to finish what it's doing and
+    /// [close](Self::close). Also returns if the session is closed without being drained.
+    pub async fn draining(&self) {
+        let mut draining = self.draining.subscribe();
+        let going_away = async {
+            match &self.settings {
+                Some(settings) => settings.going_away().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = draining.wait_for(|draining| *draining) => {}
+            _ = going_away => {}
+            _ = self.conn.closed() => {}
+        }
+    }
+
+    /// Wait for the peer to send a `ReconnectHint` capsule, returning the URL it suggested.
+    ///
+    /// Returns immediately if a hint already arrived, or `None` once the session is closed
+    /// without one. A later hint replaces an earlier one.
+    pub async fn reconnect_hint(&self) -> Option<Url> {
+        let mut reconnect = self.reconnect.subscribe();
+
+        tokio::select! {
+            res = reconnect.wait_for(Option::is_some) => res.ok()?.clone(),
+            _ = self.conn.closed() => self.reconnect.borrow().clone(),
+        }
+    }
+
+    /// Returns true if the peer has asked to drain the session. See [Self::draining].
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow() || self.goaway().is_some()
+    }
+
+    // The stream ID from the peer's GOAWAY, if it sent one.
+    fn goaway(&self) -> Option<VarInt> {
+        self.settings.as_ref()?.goaway()
+    }
+
     /// Wait until the session is closed, returning the error.
     ///
     /// This method will block until the connection is closed by either the remote peer or locally.
+    /// If the peer sent GOAWAY and then closed the connection without an error, this returns
+    /// [SessionError::GoAway]. Either way it's tagged with the session's [id](Self::id),
+    /// see [SessionError::InSession].
     pub async fn closed(&self) -> SessionError {
-        self.conn.closed().await.into()
+        let err = self.conn.closed().await;
+
+        let graceful = matches!(
+            &err,
+            ez::ConnectionError::Remote(code, _) if *code == codes::h3::NO_ERROR
+        );
+        let err = match self.goaway() {
+            Some(_) if graceful => SessionError::GoAway,
+            _ => err.into(),
+        };
+        SessionError::InSession(Box::new(err), self.id)
     }
 
     /// Create a new session from a raw QUIC connection and a URL.
@@ -313,9 +958,16 @@ impl Connection {
         request: impl Into<ConnectRequest>,
         response: impl Into<ConnectResponse>,
     ) -> Self {
-        let drop = Arc::new(ConnectionDrop { conn: conn.clone() });
+        let codes = DropCodes::default();
+        let id = SessionId::next();
+        let drop = Arc::new(ConnectionDrop {
+            conn: conn.clone(),
+            code: codes.session,
+            id,
+        });
         Self {
             conn,
+            id,
             drop,
             session_id: None,
             header_uni: Default::default(),
@@ -325,9 +977,29 @@ impl Connection {
             settings: None,
             request: request.into(),
             response: response.into(),
+            connect_send: None,
+            draining: Arc::new(watch::Sender::new(false)),
+            reconnect: Arc::new(watch::Sender::new(None)),
+            close_record: CloseRecord::new(),
+            flow: SessionFlow::default(),
+            faults: None,
+            handshake: HandshakeTiming::default(),
+            codes,
+            permit: None,
+            early_datagrams: Default::default(),
+            mode: SessionMode::Full,
+            spawner: Spawner::Tokio,
         }
     }
 
+    /// The identifier this session logs with. See [SessionId].
+    ///
+    /// Every tracing event from the session's background tasks is inside a `session`
+    /// span with this `id`, so log it with your own events to correlate the two.
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
     pub fn request(&self) -> &ConnectRequest {
         &self.request
     }
@@ -340,6 +1012,142 @@ impl Connection {
     pub fn stats(&self) -> ez::ConnectionStats {
         self.conn.stats()
     }
+
+    /// The certificate chain the peer authenticated with, leaf first.
+    ///
+    /// On a server this is `None` unless [ClientAuth](ez::ClientAuth) requested a client
+    /// certificate and the client presented one. See [ez::Connection::peer_certificates].
+    pub fn peer_certificates(&self) -> Option<Vec<ez::CertificateDer<'static>>> {
+        self.conn.peer_certificates()
+    }
+
+    /// The round-trip time to the peer, as QUIC measures it from acknowledgements.
+    ///
+    /// This is quiche's smoothed estimate for the active path, the same as [Connection::stats],
+    /// so probing costs nothing on the wire. It fails only if the session is closed.
+    pub async fn probe_rtt(&self) -> Result<Duration, SessionError> {
+        if self.conn.is_closed() {
+            return Err(self.closed().await);
+        }
+
+        // There's always an active path once the handshake is done.
+        Ok(self.conn.stats().rtt.unwrap_or_default())
+    }
+
+    /// Returns the session's streams in order of ID, for diagnostics.
+    ///
+    /// The QUIC streams from [ez::Connection::open_streams], without the CONNECT stream
+    /// and HTTP/3's control and QPACK streams. Incoming streams are listed as soon as they
+    /// arrive, even before their header is read and they can be accepted.
+    pub async fn open_streams(&self) -> Result<Vec<ez::StreamInfo>, SessionError> {
+        let mut h3 = Vec::new();
+        h3.extend(
+            self.session_id
+                .map(|id| ez::StreamId::from(id.into_inner())),
+        );
+        if let Some(settings) = &self.settings {
+            h3.extend(settings.control_streams());
+        }
+        if let Some(accept) = &self.accept {
+            h3.extend(accept.lock().unwrap().qpack_streams());
+        }
+
+        let streams = self.conn.open_streams().await?;
+        Ok(streams
+            .into_iter()
+            .filter(|stream| !h3.contains(&stream.id))
+            .collect())
+    }
+
+    /// Returns how many incoming unidirectional streams were dropped because their type was unknown.
+    pub fn ignored_uni_streams(&self) -> u64 {
+        match &self.accept {
+            Some(accept) => accept.lock().unwrap().ignored_uni,
+            None => 0,
+        }
+    }
+
+    /// Choose what happens to incoming unidirectional streams of an unknown type.
+    ///
+    /// Defaults to [UnknownStreamPolicy::Ignore]. Applies to every clone of the session;
+    /// streams ignored before the call still count towards [UnknownStreamPolicy::CloseAfter].
+    pub fn set_unknown_stream_policy(&self, policy: UnknownStreamPolicy) {
+        if let Some(accept) = &self.accept {
+            accept.lock().unwrap().unknown_streams = policy;
+        }
+    }
+
+    /// Limit how long incoming streams may take to send their header, and how long it may be.
+    ///
+    /// Defaults to [HeaderBudget::default]. Applies to every clone of the session, for
+    /// streams that arrive after the call. A stream over the budget is stopped with
+    /// [GENERAL_PROTOCOL_ERROR](codes::h3::GENERAL_PROTOCOL_ERROR) and never accepted.
+    pub fn set_header_budget(&self, budget: HeaderBudget) {
+        if let Some(accept) = &self.accept {
+            accept.lock().unwrap().budget = budget;
+        }
+    }
+
+    /// This session's share of the server's [MemoryBudget](crate::MemoryBudget), if one was configured.
+    pub fn memory(&self) -> Option<ez::MemoryAccount> {
+        self.conn.memory()
+    }
+
+    /// Return how long each phase of the handshake took.
+    ///
+    /// Only client sessions are timed; phases that didn't happen here
+    /// (e.g. everything on the server) are `None`.
+    pub fn handshake_timing(&self) -> HandshakeTiming {
+        self.handshake
+    }
+}
+
+// Capsules are carried inside DATA frames on the CONNECT stream (RFC 9297 Section 3.2).
+fn capsule_frame(capsule: &Capsule) -> Vec<u8> {
+    web_transport_proto::log_frame(
+        web_transport_proto::Direction::Sent,
+        web_transport_proto::WireFrame::Capsule(capsule),
+    );
+
+    let mut payload = Vec::new();
+    capsule.encode(&mut payload);
+
+    let mut frame = Vec::new();
+    Frame::DATA.encode(&mut frame);
+    VarInt::try_from(payload.len()).unwrap().encode(&mut frame);
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+/// A breakdown of where the time went while establishing a client session.
+///
+/// Each phase is measured back-to-back, so their sum is the connect latency.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HandshakeTiming {
+    /// Resolving the host name.
+    pub dns: Option<Duration>,
+
+    /// The QUIC (and TLS) handshake.
+    pub quic: Option<Duration>,
+
+    /// Exchanging HTTP/3 SETTINGS.
+    pub settings: Option<Duration>,
+
+    /// Waiting for the CONNECT response once SETTINGS were exchanged.
+    ///
+    /// The request is sent alongside SETTINGS, so this is often shorter than a round trip.
+    pub connect: Option<Duration>,
+}
+
+impl HandshakeTiming {
+    /// The sum of every recorded phase.
+    pub fn total(&self) -> Duration {
+        [self.dns, self.quic, self.settings, self.connect]
+            .into_iter()
+            .flatten()
+            .sum()
+    }
 }
 
 impl web_transport_trait::Stats for ez::ConnectionStats {
@@ -374,6 +1182,66 @@ impl web_transport_trait::Stats for ez::ConnectionStats {
     fn estimated_send_rate(&self) -> Option<u64> {
         self.send_rate
     }
+
+    fn congestion_window(&self) -> Option<u64> {
+        self.cwnd
+    }
+
+    fn datagrams_dropped(&self) -> Option<u64> {
+        Some(self.datagrams_dropped)
+    }
+}
+
+// The QUIC stats plus what only the session knows.
+struct SessionStats {
+    conn: ez::ConnectionStats,
+    ignored_uni: u64,
+}
+
+impl web_transport_trait::Stats for SessionStats {
+    fn bytes_sent(&self) -> Option<u64> {
+        Some(self.conn.bytes_sent)
+    }
+
+    fn bytes_received(&self) -> Option<u64> {
+        Some(self.conn.bytes_received)
+    }
+
+    fn bytes_lost(&self) -> Option<u64> {
+        Some(self.conn.bytes_lost)
+    }
+
+    fn packets_sent(&self) -> Option<u64> {
+        Some(self.conn.packets_sent)
+    }
+
+    fn packets_received(&self) -> Option<u64> {
+        Some(self.conn.packets_received)
+    }
+
+    fn packets_lost(&self) -> Option<u64> {
+        Some(self.conn.packets_lost)
+    }
+
+    fn rtt(&self) -> Option<std::time::Duration> {
+        self.conn.rtt
+    }
+
+    fn estimated_send_rate(&self) -> Option<u64> {
+        self.conn.send_rate
+    }
+
+    fn congestion_window(&self) -> Option<u64> {
+        self.conn.cwnd
+    }
+
+    fn datagrams_dropped(&self) -> Option<u64> {
+        Some(self.conn.datagrams_dropped)
+    }
+
+    fn ignored_uni_streams(&self) -> Option<u64> {
+        Some(self.ignored_uni)
+    }
 }
 
 impl web_transport_trait::Session for Connection {
@@ -401,6 +1269,29 @@ impl web_transport_trait::Session for Connection {
         self.send_datagram(payload)
     }
 
+    async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(SendStream, RecvStream), SessionError> {
+        self.open_bi_with(options).await
+    }
+
+    async fn open_uni_with(&self, options: StreamOptions) -> Result<SendStream, SessionError> {
+        self.open_uni_with(options).await
+    }
+
+    async fn send_datagram_wait(&self, payload: bytes::Bytes) -> Result<(), SessionError> {
+        self.send_datagram_wait(payload).await
+    }
+
+    fn send_datagram_with(
+        &self,
+        payload: bytes::Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), SessionError> {
+        self.send_datagram_with(payload, options)
+    }
+
     async fn recv_datagram(&self) -> Result<bytes::Bytes, SessionError> {
         self.read_datagram().await
     }
@@ -409,6 +1300,10 @@ impl web_transport_trait::Session for Connection {
         self.max_datagram_size()
     }
 
+    fn datagrams_supported(&self) -> bool {
+        self.datagrams_supported()
+    }
+
     fn protocol(&self) -> Option<&str> {
         self.response().protocol.as_deref()
     }
@@ -421,8 +1316,39 @@ impl web_transport_trait::Session for Connection {
         self.closed().await
     }
 
+    fn close_reason(&self) -> Option<Closed> {
+        self.close_reason()
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        Some(self.id())
+    }
+
     fn stats(&self) -> impl web_transport_trait::Stats {
-        self.conn.stats()
+        SessionStats {
+            conn: self.conn.stats(),
+            ignored_uni: self.ignored_uni_streams(),
+        }
+    }
+
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        Some(self.conn.peer_addr())
+    }
+
+    fn max_open_uni(&self) -> Option<u64> {
+        Some(Self::max_open_uni(self))
+    }
+
+    fn remaining_uni(&self) -> Option<u64> {
+        Some(Self::remaining_uni(self))
+    }
+
+    fn max_open_bi(&self) -> Option<u64> {
+        Some(Self::max_open_bi(self))
+    }
+
+    fn remaining_bi(&self) -> Option<u64> {
+        Some(Self::remaining_bi(self))
     }
 }
 
@@ -430,9 +1356,111 @@ impl web_transport_trait::Session for Connection {
 type AcceptUni = dyn Stream<Item = Result<ez::RecvStream, ez::ConnectionError>> + Send;
 type AcceptBi =
     dyn Stream<Item = Result<(ez::SendStream, ez::RecvStream), ez::ConnectionError>> + Send;
-type PendingUni = dyn Future<Output = Result<(StreamUni, ez::RecvStream), SessionError>> + Send;
-type PendingBi =
-    dyn Future<Output = Result<Option<(ez::SendStream, ez::RecvStream)>, SessionError>> + Send;
+type PendingUni =
+    dyn Future<Output = Result<(StreamUni, ez::RecvStream, Header), SessionError>> + Send;
+type PendingBi = dyn Future<Output = Result<Option<(ez::SendStream, ez::RecvStream, Header)>, SessionError>>
+    + Send;
+
+// Reads a stream header a chunk at a time instead of a byte at a time, keeping any
+// payload that arrived alongside it so the first read doesn't need another wakeup.
+#[derive(Default)]
+struct Header {
+    // Received bytes not yet consumed by the header.
+    buf: Bytes,
+    // How many bytes the header has taken so far.
+    used: usize,
+    // Caps how many bytes the header may take.
+    budget: HeaderBudget,
+}
+
+impl Header {
+    fn new(budget: HeaderBudget) -> Self {
+        Self {
+            budget,
+            ..Default::default()
+        }
+    }
+
+    async fn read_varint(&mut self, recv: &mut ez::RecvStream) -> Result<VarInt, SessionError> {
+        loop {
+            if let Some((v, used)) = self.budget.decode_varint(self.used, &self.buf)? {
+                self.buf.advance(used);
+                self.used += used;
+                return Ok(v);
+            }
+
+            let chunk = recv
+                .read_chunk(usize::MAX)
+                .await
+                .ok()
+                .flatten()
+                .ok_or(SessionError::Unknown)?;
+            self.buf = match self.buf.is_empty() {
+                true => chunk,
+                false => [&self.buf[..], &chunk[..]].concat().into(),
+            };
+        }
+    }
+
+    fn into_recv(self, recv: ez::RecvStream, drop_code: u32) -> RecvStream {
+        RecvStream::new(recv, drop_code).with_buffered(self.buf)
+    }
+}
+
+// Run `read` within the budget's timeout, which counts as a violation.
+async fn within_budget<T>(
+    clock: &dyn Clock,
+    budget: HeaderBudget,
+    read: impl Future<Output = Result<T, SessionError>>,
+) -> Result<T, SessionError> {
+    let Some(timeout) = budget.timeout else {
+        return read.await;
+    };
+
+    match clock.timeout(timeout, read).await {
+        Ok(res) => res,
+        Err(_) => Err(HeaderViolation::TimedOut(timeout).into()),
+    }
+}
+
+// Poll the accept state shared by every clone of a connection until `poll` is ready.
+//
+// The shared accept futures only remember the waker of whoever polled them last.
+// If that caller is dropped while waiting, the others are woken to take over,
+// otherwise they'd stall until something unrelated woke them.
+async fn poll_accept<T>(
+    accept: &Mutex<SessionAccept>,
+    mut poll: impl FnMut(&mut SessionAccept, &mut Context<'_>) -> Poll<T>,
+) -> T {
+    let mut waiting = Waiting {
+        accept,
+        waker: None,
+    };
+
+    poll_fn(|cx| {
+        let res = poll(&mut accept.lock().unwrap(), cx);
+        waiting.waker = res.is_pending().then(|| cx.waker().clone());
+        res
+    })
+    .await
+}
+
+// A pending accept call, handing off to the other callers if dropped.
+struct Waiting<'a> {
+    accept: &'a Mutex<SessionAccept>,
+    waker: Option<Waker>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            // Skip the hand-off if another caller panicked while holding the lock.
+            if let Ok(mut accept) = self.accept.lock() {
+                accept.abandon(&waker);
+            }
+        }
+    }
+}
 
 // Logic just for accepting streams, which is annoying because of the stream header.
 pub struct SessionAccept {
@@ -443,22 +1471,64 @@ pub struct SessionAccept {
     qpack_encoder: Option<ez::RecvStream>,
     qpack_decoder: Option<ez::RecvStream>,
 
+    // Streams decoded by one accept call on behalf of another, keyed by the type
+    // they're waiting for. WebTransport streams are queued in `ready_uni`.
+    ready_uni: VecDeque<RecvStream>,
+    raw_uni: HashMap<StreamUni, VecDeque<RecvStream>>,
+
+    // Wakers from concurrent callers of accept_uni / accept_raw_uni, all woken
+    // whenever a stream is queued since it may be for one of the others.
+    uni_wakers: Vec<Waker>,
+
+    // Wakers from concurrent callers of accept_bi, all woken when one gets a stream
+    // so they can retry; the accept futures only store the last caller's waker.
+    bi_wakers: Vec<Waker>,
+
     accept_uni: Pin<Box<AcceptUni>>,
     accept_bi: Pin<Box<AcceptBi>>,
 
     // Keep track of work being done to read/write the WebTransport stream header.
     pending_uni: FuturesUnordered<Pin<Box<PendingUni>>>,
     pending_bi: FuturesUnordered<Pin<Box<PendingBi>>>,
+
+    // Shared flow control for accepted bidirectional streams.
+    flow: SessionFlow,
+
+    // Applied to the streams we accept.
+    codes: DropCodes,
+
+    // Closed if the peer opens too many unknown streams.
+    conn: ez::Connection,
+
+    // What to do with unknown uni streams, and how many we've dropped.
+    unknown_streams: UnknownStreamPolicy,
+    ignored_uni: u64,
+
+    // Limits on reading the header of each stream we accept.
+    budget: HeaderBudget,
 }
 
 impl SessionAccept {
-    pub(super) fn new(conn: ez::Connection, session_id: VarInt) -> Self {
+    // The QPACK streams the peer opened, if any.
+    fn qpack_streams(&self) -> impl Iterator<Item = ez::StreamId> + '_ {
+        [&self.qpack_encoder, &self.qpack_decoder]
+            .into_iter()
+            .flatten()
+            .map(|recv| recv.id())
+    }
+
+    pub(super) fn new(
+        conn: ez::Connection,
+        session_id: VarInt,
+        flow: SessionFlow,
+        codes: DropCodes,
+    ) -> Self {
         // Create a stream that just outputs new streams, so it's easy to call from poll.
         let accept_uni = Box::pin(futures::stream::unfold(conn.clone(), |conn| async {
             Some((conn.accept_uni().await, conn))
         }));
 
-        let accept_bi = Box::pin(futures::stream::unfold(conn, |conn| async {
+        let accept_bi = Box::pin(futures::stream::unfold(conn.clone(), |conn| async {
             Some((conn.accept_bi().await, conn))
         }));
 
@@ -468,11 +1538,24 @@ impl SessionAccept {
             qpack_decoder: None,
             qpack_encoder: None,
 
+            ready_uni: VecDeque::new(),
+            raw_uni: HashMap::new(),
+            uni_wakers: Vec::new(),
+            bi_wakers: Vec::new(),
+
             accept_uni,
             accept_bi,
 
             pending_uni: FuturesUnordered::new(),
             pending_bi: FuturesUnordered::new(),
+
+            flow,
+            codes,
+
+            conn,
+            unknown_streams: UnknownStreamPolicy::default(),
+            ignored_uni: 0,
+            budget: HeaderBudget::default(),
         }
     }
 
@@ -482,46 +1565,160 @@ impl SessionAccept {
     pub fn poll_accept_uni(
         &mut self,
         cx: &mut Context<'_>,
+    ) -> Poll<Result<RecvStream, SessionError>> {
+        self.poll_uni(cx, None)
+    }
+
+    pub fn poll_accept_raw_uni(
+        &mut self,
+        cx: &mut Context<'_>,
+        typ: StreamUni,
+    ) -> Poll<Result<RecvStream, SessionError>> {
+        self.poll_uni(cx, Some(typ))
+    }
+
+    pub fn register_uni(&mut self, typ: StreamUni) {
+        self.raw_uni.entry(typ).or_default();
+    }
+
+    // Decode the streams that arrived before the CONNECT response like any others.
+    pub(crate) fn push_early(
+        &mut self,
+        uni: Vec<ez::RecvStream>,
+        bi: Vec<(ez::SendStream, ez::RecvStream)>,
+    ) {
+        for recv in uni {
+            let pending = Self::decode_uni(recv, self.session_id, self.budget, self.conn.clock());
+            self.pending_uni.push(Box::pin(pending));
+        }
+        for (send, recv) in bi {
+            let pending =
+                Self::decode_bi(send, recv, self.session_id, self.budget, self.conn.clock());
+            self.pending_bi.push(Box::pin(pending));
+        }
+    }
+
+    // A caller waiting with `waker` went away. The accept futures may only know its
+    // waker, so wake everyone else to poll them again.
+    fn abandon(&mut self, waker: &Waker) {
+        self.uni_wakers.retain(|w| !w.will_wake(waker));
+        self.bi_wakers.retain(|w| !w.will_wake(waker));
+
+        for waker in self.uni_wakers.drain(..).chain(self.bi_wakers.drain(..)) {
+            waker.wake();
+        }
+    }
+
+    // Drive every pending uni stream, returning the next one of the wanted type:
+    // `None` for WebTransport streams, or a custom stream type.
+    fn poll_uni(
+        &mut self,
+        cx: &mut Context<'_>,
+        want: Option<StreamUni>,
     ) -> Poll<Result<RecvStream, SessionError>> {
         loop {
+            // Return any stream another caller already decoded for us.
+            let queue = match want {
+                None => &mut self.ready_uni,
+                Some(typ) => self.raw_uni.entry(typ).or_default(),
+            };
+            if let Some(recv) = queue.pop_front() {
+                return Poll::Ready(Ok(recv));
+            }
+
             // Accept any new streams.
             if let Poll::Ready(Some(res)) = self.accept_uni.poll_next_unpin(cx) {
                 // Start decoding the header and add the future to the list of pending streams.
-                let recv = res?;
-                let pending = Self::decode_uni(recv, self.session_id);
+                let recv = match res {
+                    Ok(recv) => recv,
+                    Err(err) => {
+                        for waker in self.uni_wakers.drain(..) {
+                            waker.wake();
+                        }
+                        return Poll::Ready(Err(err.into()));
+                    }
+                };
+                let pending =
+                    Self::decode_uni(recv, self.session_id, self.budget, self.conn.clock());
                 self.pending_uni.push(Box::pin(pending));
 
                 continue;
             }
 
             // Poll the list of pending streams.
-            let (typ, recv) = match ready!(self.pending_uni.poll_next_unpin(cx)) {
-                Some(Ok(res)) => res,
-                Some(Err(err)) => {
+            let (typ, recv, header) = match self.pending_uni.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(res))) => res,
+                Poll::Ready(Some(Err(err))) => {
                     // Ignore the error, the stream was probably reset early.
                     tracing::warn!(?err, "failed to decode unidirectional stream");
                     continue;
                 }
-                None => return Poll::Pending,
+                Poll::Ready(None) | Poll::Pending => {
+                    if !self.uni_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        self.uni_wakers.push(cx.waker().clone());
+                    }
+                    return Poll::Pending;
+                }
             };
 
-            // Decide if we keep looping based on the type.
-            match typ {
-                StreamUni::WEBTRANSPORT => {
-                    let recv = RecvStream::new(recv);
-                    return Poll::Ready(Ok(recv));
-                }
+            // Queue the stream for whoever wants its type; we'll pick it up next loop.
+            let queue = match typ {
+                StreamUni::WEBTRANSPORT => &mut self.ready_uni,
                 StreamUni::QPACK_DECODER => {
                     self.qpack_decoder = Some(recv);
+                    continue;
                 }
                 StreamUni::QPACK_ENCODER => {
                     self.qpack_encoder = Some(recv);
+                    continue;
                 }
-                _ => {
-                    // ignore unknown streams
-                    tracing::debug!("ignoring unknown unidirectional stream: {typ:?}");
-                }
+                typ => match self.raw_uni.get_mut(&typ) {
+                    Some(queue) => queue,
+                    None => {
+                        match typ.reject_code(u64::from(recv.id())) {
+                            Some(code) => self.reject_uni(typ, code),
+                            None => self.ignore_uni(typ),
+                        }
+                        continue;
+                    }
+                },
+            };
+
+            queue.push_back(header.into_recv(recv, self.codes.recv));
+            for waker in self.uni_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+
+    // Close the connection because the peer opened a stream it isn't allowed to.
+    fn reject_uni(&self, typ: StreamUni, code: u64) {
+        tracing::warn!(?typ, "rejecting forbidden unidirectional stream");
+        self.conn.close(code, "unexpected stream type");
+    }
+
+    // Drop a uni stream of an unknown type, applying the policy.
+    fn ignore_uni(&mut self, typ: StreamUni) {
+        self.ignored_uni += 1;
+
+        match self.unknown_streams {
+            UnknownStreamPolicy::CloseAfter(limit) if self.ignored_uni > limit => {
+                tracing::warn!(
+                    ?typ,
+                    count = self.ignored_uni,
+                    "too many unknown unidirectional streams"
+                );
+                self.conn
+                    .close(codes::h3::STREAM_CREATION_ERROR, "too many unknown streams");
             }
+            UnknownStreamPolicy::Warn => {
+                tracing::warn!(
+                    ?typ,
+                    count = self.ignored_uni,
+                    "ignoring unknown unidirectional stream"
+                );
+            }
+            _ => tracing::debug!(?typ, "ignoring unknown unidirectional stream"),
         }
     }
 
@@ -529,25 +1726,38 @@ impl SessionAccept {
     async fn decode_uni(
         mut recv: ez::RecvStream,
         expected_session: VarInt,
-    ) -> Result<(StreamUni, ez::RecvStream), SessionError> {
-        // Read the VarInt at the start of the stream.
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| SessionError::Unknown)?;
-        let typ = StreamUni(typ);
+        budget: HeaderBudget,
+        clock: Arc<dyn Clock>,
+    ) -> Result<(StreamUni, ez::RecvStream, Header), SessionError> {
+        let mut header = Header::new(budget);
+
+        let read = async {
+            // Read the VarInt at the start of the stream.
+            let typ = StreamUni(header.read_varint(&mut recv).await?);
+
+            if typ == StreamUni::WEBTRANSPORT {
+                // Read the session_id and validate it
+                let session_id = header.read_varint(&mut recv).await?;
+                if session_id != expected_session {
+                    return Err(SessionError::Unknown);
+                }
+            }
 
-        if typ == StreamUni::WEBTRANSPORT {
-            // Read the session_id and validate it
-            let session_id = VarInt::read(&mut recv)
-                .await
-                .map_err(|_| SessionError::Unknown)?;
-            if session_id != expected_session {
-                return Err(SessionError::Unknown);
+            Ok::<_, SessionError>(typ)
+        };
+
+        let typ = match within_budget(&*clock, budget, read).await {
+            Ok(typ) => typ,
+            Err(err) => {
+                if let SessionError::ProtocolViolation(_) = err {
+                    recv.stop(codes::h3::GENERAL_PROTOCOL_ERROR);
+                }
+                return Err(err);
             }
-        }
+        };
 
         // We need to keep a reference to the qpack streams if the endpoint (incorrectly) creates them, so return everything.
-        Ok((typ, recv))
+        Ok((typ, recv, header))
     }
 
     pub fn poll_accept_bi(
@@ -558,28 +1768,45 @@ impl SessionAccept {
             // Accept any new streams.
             if let Poll::Ready(Some(res)) = self.accept_bi.poll_next_unpin(cx) {
                 // Start decoding the header and add the future to the list of pending streams.
-                let (send, recv) = res?;
-                let pending = Self::decode_bi(send, recv, self.session_id);
+                let (send, recv) = match res {
+                    Ok(pair) => pair,
+                    Err(err) => {
+                        for waker in self.bi_wakers.drain(..) {
+                            waker.wake();
+                        }
+                        return Poll::Ready(Err(err.into()));
+                    }
+                };
+                let pending =
+                    Self::decode_bi(send, recv, self.session_id, self.budget, self.conn.clock());
                 self.pending_bi.push(Box::pin(pending));
 
                 continue;
             }
 
             // Poll the list of pending streams.
-            let res = match ready!(self.pending_bi.poll_next_unpin(cx)) {
-                Some(Ok(res)) => res,
-                Some(Err(err)) => {
+            let res = match self.pending_bi.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(res))) => res,
+                Poll::Ready(Some(Err(err))) => {
                     // Ignore the error, the stream was probably reset early.
                     tracing::warn!(?err, "failed to decode bidirectional stream");
                     continue;
                 }
-                None => return Poll::Pending,
+                Poll::Ready(None) | Poll::Pending => {
+                    if !self.bi_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        self.bi_wakers.push(cx.waker().clone());
+                    }
+                    return Poll::Pending;
+                }
             };
 
-            if let Some((send, recv)) = res {
+            if let Some((send, recv, header)) = res {
                 // Wrap the streams in our own types for correct error codes.
-                let send = SendStream::new(send);
-                let recv = RecvStream::new(recv);
+                let send = SendStream::new(send, self.codes.send, self.flow.clone());
+                let recv = header.into_recv(recv, self.codes.recv);
+                for waker in self.bi_wakers.drain(..) {
+                    waker.wake();
+                }
                 return Poll::Ready(Ok((send, recv)));
             }
 
@@ -589,26 +1816,40 @@ impl SessionAccept {
 
     // Reads the stream header, returning Some if it's a WebTransport stream.
     async fn decode_bi(
-        send: ez::SendStream,
+        mut send: ez::SendStream,
         mut recv: ez::RecvStream,
         expected_session: VarInt,
-    ) -> Result<Option<(ez::SendStream, ez::RecvStream)>, SessionError> {
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| SessionError::Unknown)?;
-        if Frame(typ) != Frame::WEBTRANSPORT {
-            tracing::debug!("ignoring unknown bidirectional stream: {typ:?}");
-            return Ok(None);
-        }
+        budget: HeaderBudget,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Option<(ez::SendStream, ez::RecvStream, Header)>, SessionError> {
+        let mut header = Header::new(budget);
+
+        let read = async {
+            let typ = header.read_varint(&mut recv).await?;
+            if Frame(typ) != Frame::WEBTRANSPORT {
+                tracing::debug!("ignoring unknown bidirectional stream: {typ:?}");
+                return Ok(false);
+            }
 
-        // Read the session ID and validate it.
-        let session_id = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| SessionError::Unknown)?;
-        if session_id != expected_session {
-            return Err(SessionError::Unknown);
-        }
+            // Read the session ID and validate it.
+            let session_id = header.read_varint(&mut recv).await?;
+            if session_id != expected_session {
+                return Err(SessionError::Unknown);
+            }
+
+            Ok::<_, SessionError>(true)
+        };
 
-        Ok(Some((send, recv)))
+        match within_budget(&*clock, budget, read).await {
+            Ok(true) => Ok(Some((send, recv, header))),
+            Ok(false) => Ok(None),
+            Err(err) => {
+                if let SessionError::ProtocolViolation(_) = err {
+                    recv.stop(codes::h3::GENERAL_PROTOCOL_ERROR);
+                    send.reset(codes::h3::GENERAL_PROTOCOL_ERROR);
+                }
+                Err(err)
+            }
+        }
     }
 }
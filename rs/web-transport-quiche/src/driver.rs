@@ -0,0 +1,89 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::mpsc;
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// Where a session runs its background work: reading capsules, writing flow control
+// capsules, and so on.
+#[derive(Clone, Default)]
+pub(crate) enum Spawner {
+    // Spawned onto the current tokio runtime.
+    #[default]
+    Tokio,
+
+    // Handed to a [SessionDriver] that the application polls.
+    Driver(mpsc::UnboundedSender<Task>),
+}
+
+impl Spawner {
+    // A spawner that hands its tasks to the returned driver.
+    pub fn driven() -> (Self, SessionDriver) {
+        let (tasks, queued) = mpsc::unbounded_channel();
+        let driver = SessionDriver {
+            queued,
+            running: FuturesUnordered::new(),
+            closed: false,
+        };
+
+        (Self::Driver(tasks), driver)
+    }
+
+    pub fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        match self {
+            Self::Tokio => {
+                tokio::spawn(task);
+            }
+            Self::Driver(tasks) => {
+                // The driver was dropped, so nothing would run the task anyway.
+                tasks.send(Box::pin(task)).ok();
+            }
+        }
+    }
+}
+
+/// Runs the background work of a [Connection](crate::Connection), wherever it's polled.
+///
+/// Returned by the `_driven` variants of the session constructors, such as
+/// [Request::respond_driven](crate::h3::Request::respond_driven), in place of spawning onto
+/// the current tokio runtime. Poll it on the executor of your choice, for example a
+/// `LocalSet` or a dedicated runtime, for as long as the session is in use; without it,
+/// capsules go unread and the session never notices it was closed.
+///
+/// It completes once every clone of the session is dropped and the CONNECT stream is
+/// closed. The QUIC connection itself is still driven by tokio-quiche.
+#[must_use = "the session stalls unless its driver is polled"]
+pub struct SessionDriver {
+    queued: mpsc::UnboundedReceiver<Task>,
+    running: FuturesUnordered<Task>,
+
+    // Every session clone is gone, so nothing more will be queued.
+    closed: bool,
+}
+
+impl Future for SessionDriver {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        while !self.closed {
+            match self.queued.poll_recv(cx) {
+                Poll::Ready(Some(task)) => self.running.push(task),
+                Poll::Ready(None) => self.closed = true,
+                Poll::Pending => break,
+            }
+        }
+
+        // Run every task until it's pending; None means there are none left.
+        while let Poll::Ready(Some(())) = self.running.poll_next_unpin(cx) {}
+
+        match self.closed && self.running.is_empty() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
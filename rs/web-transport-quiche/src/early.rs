@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use tokio::sync::oneshot;
+
+use crate::{ez, proto::codes};
+
+/// How many streams and datagrams a client may send before the CONNECT response, unless configured.
+pub(crate) const EARLY_BUFFER: usize = 16;
+
+/// Streams and datagrams that arrived before the server responded to the CONNECT request.
+#[derive(Default)]
+pub(crate) struct Early {
+    pub uni: Vec<ez::RecvStream>,
+    pub bi: Vec<(ez::SendStream, ez::RecvStream)>,
+    pub datagrams: VecDeque<Bytes>,
+}
+
+// Clients may open streams and send datagrams as soon as the CONNECT request is out.
+// Until the application responds, a background task holds up to `max` of each, so
+// they're delivered once the session exists. Extra streams are rejected with
+// WT_BUFFERED_STREAM_REJECTED and extra datagrams are dropped.
+pub(crate) struct EarlyBuffer {
+    // Dropped to stop buffering, either to hand over or discard what was buffered.
+    stop: oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<Early>,
+}
+
+impl EarlyBuffer {
+    pub fn new(conn: ez::Connection, max: usize) -> Self {
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(Self::run(conn, max, stopped));
+
+        Self { stop, task }
+    }
+
+    async fn run(conn: ez::Connection, max: usize, mut stopped: oneshot::Receiver<()>) -> Early {
+        let code = codes::h3::WT_BUFFERED_STREAM_REJECTED;
+        let mut early = Early::default();
+
+        loop {
+            tokio::select! {
+                _ = &mut stopped => return early,
+                Ok(mut recv) = conn.accept_uni() => {
+                    if early.uni.len() < max {
+                        early.uni.push(recv);
+                    } else {
+                        tracing::debug!("rejecting early unidirectional stream");
+                        recv.stop(code);
+                    }
+                }
+                Ok((mut send, mut recv)) = conn.accept_bi() => {
+                    if early.bi.len() < max {
+                        early.bi.push((send, recv));
+                    } else {
+                        tracing::debug!("rejecting early bidirectional stream");
+                        send.reset(code);
+                        recv.stop(code);
+                    }
+                }
+                Ok(datagram) = conn.read_datagram() => {
+                    if early.datagrams.len() < max {
+                        early.datagrams.push_back(datagram);
+                    } else {
+                        tracing::trace!("dropping early datagram");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stop buffering and return everything that arrived.
+    pub async fn finish(self) -> Early {
+        let Self { stop, task } = self;
+        drop(stop);
+        task.await.unwrap_or_default()
+    }
+}
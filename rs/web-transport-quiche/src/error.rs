@@ -1,9 +1,11 @@
-use web_transport_proto::error_from_http3;
+use web_transport_proto::{error_from_http3, HeaderViolation};
+use web_transport_trait::{ErrorKind, SessionId};
 
 use crate::ez;
 
 /// An error returned by [Connection], split based on whether they are underlying QUIC errors or WebTransport errors.
 #[derive(Clone, thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum SessionError {
     #[error("remote closed: code={0} reason={1}")]
     Remote(u32, String),
@@ -19,10 +21,62 @@ pub enum SessionError {
 
     #[error("unknown session")]
     Unknown,
+
+    /// The session is [datagram-only](crate::proto::SessionMode::DatagramOnly).
+    #[error("streams are disabled")]
+    StreamsDisabled,
+
+    /// The peer didn't enable datagrams in its SETTINGS.
+    #[error("datagrams are not supported by the peer")]
+    DatagramsUnsupported,
+
+    /// An incoming stream broke its [HeaderBudget](crate::proto::HeaderBudget).
+    #[error("protocol violation: {0}")]
+    ProtocolViolation(#[from] HeaderViolation),
+
+    /// The peer sent GOAWAY and then closed the connection without an error.
+    ///
+    /// A graceful shutdown, such as a server restarting, rather than a failure.
+    #[error("peer shut down gracefully")]
+    GoAway,
+
+    /// The close error of a session, tagged with its [id](crate::Connection::id).
+    ///
+    /// Displays as `session <id>: <error>`, matching the `session` field of its tracing events.
+    /// Use [SessionError::without_id] to match on the underlying error.
+    #[error("session {1}: {0}")]
+    InSession(Box<SessionError>, SessionId),
+}
+
+impl SessionError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Remote(..) => ErrorKind::SessionClosed,
+            Self::Local(..) => ErrorKind::LocallyClosed,
+            Self::Connection(e) => e.kind(),
+            Self::Header(e) => e.kind(),
+            Self::Unknown => ErrorKind::Protocol,
+            Self::StreamsDisabled => ErrorKind::Unsupported,
+            Self::DatagramsUnsupported => ErrorKind::Unsupported,
+            Self::ProtocolViolation(_) => ErrorKind::Protocol,
+            Self::GoAway => ErrorKind::SessionClosed,
+            Self::InSession(e, _) => e.kind(),
+        }
+    }
+
+    /// The error without the [session id](SessionError::InSession) attached.
+    pub fn without_id(&self) -> &SessionError {
+        match self {
+            Self::InSession(e, _) => e.without_id(),
+            e => e,
+        }
+    }
 }
 
 /// An error when reading from or writing to a WebTransport stream.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum StreamError {
     #[error("session error: {0}")]
     Session(#[from] SessionError),
@@ -41,6 +95,23 @@ pub enum StreamError {
 
     #[error("stream closed")]
     Closed,
+
+    #[error("stream longer than the limit of {limit} bytes")]
+    TooLong { limit: usize },
+}
+
+impl StreamError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Session(e) => e.kind(),
+            Self::Reset(_) => ErrorKind::StreamReset,
+            Self::Stop(_) => ErrorKind::StreamStopped,
+            Self::InvalidReset(_) | Self::InvalidStop(_) => ErrorKind::Protocol,
+            Self::Closed => ErrorKind::LocallyClosed,
+            Self::TooLong { .. } => ErrorKind::TooLarge,
+        }
+    }
 }
 
 impl From<ez::ConnectionError> for SessionError {
@@ -72,6 +143,7 @@ impl From<ez::StreamError> for StreamError {
                 None => StreamError::InvalidStop(code),
             },
             ez::StreamError::Closed => StreamError::Closed,
+            ez::StreamError::TooLong { limit } => StreamError::TooLong { limit },
         }
     }
 }
@@ -91,13 +163,21 @@ impl web_transport_trait::Error for StreamError {
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        StreamError::kind(self)
+    }
 }
 impl web_transport_trait::Error for SessionError {
     fn session_error(&self) -> Option<(u32, String)> {
-        match self {
+        match self.without_id() {
             SessionError::Remote(code, reason) => Some((*code, reason.clone())),
             SessionError::Local(code, reason) => Some((*code, reason.clone())),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        SessionError::kind(self)
+    }
 }
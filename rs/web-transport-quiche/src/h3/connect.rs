@@ -1,11 +1,20 @@
-use crate::proto::{ConnectRequest, ConnectResponse, VarInt};
+use std::sync::Arc;
+
+use crate::proto::{
+    codes, ConnectRequest, ConnectResponse, HttpRequest, HttpResponse, IncomingRequest, VarInt,
+};
 
 use thiserror::Error;
+use web_transport_trait::ErrorKind;
 
 use crate::ez;
 
+/// Answers the plain HTTP/3 requests a client sends before its CONNECT.
+pub(crate) type HttpHandler = Arc<dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync>;
+
 /// An error returned when exchanging the HTTP/3 CONNECT handshake.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ConnectError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -21,6 +30,36 @@ pub enum ConnectError {
 
     #[error("http error status: {0}")]
     Status(http::StatusCode),
+
+    /// The server rejected the session; the headers may say why, e.g. `www-authenticate` on a 401.
+    #[error("rejected ({status})")]
+    Rejected {
+        status: http::StatusCode,
+        headers: http::HeaderMap,
+    },
+
+    #[error("redirected ({status}) to {location}")]
+    Redirect {
+        status: http::StatusCode,
+        location: url::Url,
+    },
+
+    #[error("server returned protocol not in request: {0}")]
+    ProtocolMismatch(String),
+}
+
+impl ConnectError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Proto(web_transport_proto::ConnectError::WrongStatus(_)) => ErrorKind::Rejected,
+            Self::Proto(_) | Self::ProtocolMismatch(_) => ErrorKind::Protocol,
+            Self::Connection(e) => e.kind(),
+            Self::Stream(e) => e.kind(),
+            Self::Status(_) | Self::Rejected { .. } | Self::Redirect { .. } => ErrorKind::Rejected,
+        }
+    }
 }
 
 /// An HTTP/3 CONNECT request/response for establishing a WebTransport session.
@@ -40,19 +79,74 @@ impl Connecting {
     ///
     /// This is called by the server to receive the CONNECT request.
     pub async fn accept(conn: &ez::Connection) -> Result<Self, ConnectError> {
-        // Accept the stream that will be used to send the HTTP CONNECT request.
-        // If they try to send any other type of HTTP request, we will error out.
-        let (send, mut recv) = conn.accept_bi().await?;
+        Self::accept_with(conn, None, None).await
+    }
 
-        let request = web_transport_proto::ConnectRequest::read(&mut recv).await?;
-        tracing::debug!(?request, "received CONNECT");
+    // Accept the CONNECT request, rejecting headers larger than `max_field_section_size`.
+    //
+    // Plain HTTP/3 requests sent first are answered by `handler`, or with 501 (Not
+    // Implemented) without one, so a client can upgrade to WebTransport later on.
+    pub(crate) async fn accept_with(
+        conn: &ez::Connection,
+        max_field_section_size: Option<u64>,
+        handler: Option<&HttpHandler>,
+    ) -> Result<Self, ConnectError> {
+        loop {
+            // Accept the stream that will be used to send the HTTP CONNECT request.
+            let (mut send, mut recv) = conn.accept_bi().await?;
 
-        // The request was successfully decoded, so we can send a response.
-        Ok(Self {
-            request,
-            send,
-            recv,
-        })
+            let request = match IncomingRequest::read(&mut recv, max_field_section_size).await {
+                Ok(IncomingRequest::Connect(request)) => request,
+                Ok(IncomingRequest::Http(request)) => {
+                    Self::answer(send, recv, &request, handler).await;
+                    continue;
+                }
+                Err(err @ web_transport_proto::ConnectError::FieldSectionTooLarge { .. }) => {
+                    // Tell the client why, as RFC 9114 section 4.2.2 suggests.
+                    let response =
+                        ConnectResponse::new(http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+                    if response.write(&mut send).await.is_ok() {
+                        send.finish().ok();
+                        send.closed().await.ok();
+                    }
+                    return Err(err.into());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            tracing::debug!(?request, "received CONNECT");
+
+            // The request was successfully decoded, so we can send a response.
+            return Ok(Self {
+                request,
+                send,
+                recv,
+            });
+        }
+    }
+
+    // Answer a plain HTTP/3 request, leaving the connection open for the CONNECT.
+    async fn answer(
+        mut send: ez::SendStream,
+        mut recv: ez::RecvStream,
+        request: &HttpRequest,
+        handler: Option<&HttpHandler>,
+    ) {
+        tracing::debug!(?request, "received HTTP request");
+        let response = match handler {
+            Some(handler) => handler(request),
+            None => HttpResponse::new(http::StatusCode::NOT_IMPLEMENTED),
+        };
+
+        // Any request body goes unread, so ask the client to stop (RFC 9114 section 4.1).
+        recv.stop(codes::h3::NO_ERROR);
+
+        // A client that gave up on its request is no reason to drop the connection.
+        match response.write(&mut send).await {
+            Ok(()) => {
+                send.finish().ok();
+            }
+            Err(err) => tracing::debug!(?err, "failed to answer HTTP request"),
+        }
     }
 
     pub async fn ok(self) -> Result<Connected, ConnectError> {
@@ -79,9 +173,22 @@ impl Connecting {
         })
     }
 
-    pub async fn reject(self, status: http::StatusCode) -> Result<(), ConnectError> {
-        let mut connect = self.respond(status).await?;
+    pub async fn reject(self, response: impl Into<ConnectResponse>) -> Result<(), ConnectError> {
+        self.close(response).await
+    }
+
+    /// Send the client to `location` with a 302 Found.
+    pub async fn redirect(self, location: url::Url) -> Result<(), ConnectError> {
+        self.close(ConnectResponse::redirect(location)).await
+    }
+
+    async fn close(self, response: impl Into<ConnectResponse>) -> Result<(), ConnectError> {
+        let mut connect = self.respond(response).await?;
         connect.send.finish()?;
+
+        // Wait for the FIN to be sent; the caller is about to drop the connection,
+        // which would otherwise discard the response before the client reads it.
+        connect.send.closed().await.ok();
         Ok(())
     }
 }
@@ -125,14 +232,35 @@ impl Connected {
         tracing::debug!(?request, "sending CONNECT");
         request.write(&mut send).await?;
 
-        let response = web_transport_proto::ConnectResponse::read(&mut recv).await?;
+        let response = web_transport_proto::ConnectResponse::read_any_status(&mut recv).await?;
         tracing::debug!(?response, "received CONNECT");
 
+        if let (true, Some(location)) = (response.status.is_redirection(), &response.location) {
+            return Err(ConnectError::Redirect {
+                status: response.status,
+                location: location.clone(),
+            });
+        }
+
+        if response.status.is_client_error() || response.status.is_server_error() {
+            return Err(ConnectError::Rejected {
+                status: response.status,
+                headers: response.headers.unwrap_or_default(),
+            });
+        }
+
         // Throw an error if we didn't get a 200 OK.
         if response.status != http::StatusCode::OK {
             return Err(ConnectError::Status(response.status));
         }
 
+        // Validate that the server's protocol was in our request.
+        if let Some(protocol) = &response.protocol {
+            if !request.protocols.contains(protocol) {
+                return Err(ConnectError::ProtocolMismatch(protocol.clone()));
+            }
+        }
+
         Ok(Self {
             request,
             response,
@@ -1,29 +1,119 @@
-use crate::{ez, h3, proto::ConnectResponse, Connection, ServerError};
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{oneshot, watch};
+
+use crate::{
+    driver::Spawner,
+    early::{EarlyBuffer, EARLY_BUFFER},
+    ez, h3,
+    proto::{
+        codes::{self, DropCodes},
+        ConnectResponse, SessionMode, SessionPermit,
+    },
+    Connection, FaultInjector, ServerError, SessionDriver,
+};
 
 /// A mostly complete WebTransport handshake, just awaiting the server's decision on whether to accept or reject the session based on the URL.
 pub struct Request {
     conn: ez::Connection,
     settings: h3::Settings,
     connect: h3::Connecting,
+    faults: Option<Arc<FaultInjector>>,
+    codes: DropCodes,
+    permit: Option<SessionPermit>,
+    mode: SessionMode,
+
+    // Holds streams and datagrams the client sends before we respond.
+    early: EarlyBuffer,
+
+    // Rejects the request if it isn't answered in time.
+    expiry: Option<Expiry>,
 }
 
 impl Request {
     /// Accept a new WebTransport session from a client.
     pub async fn accept(conn: ez::Connection) -> Result<Self, ServerError> {
+        Self::accept_with(conn, None, None, EARLY_BUFFER, None).await
+    }
+
+    // Accept a new session, advertising and enforcing `max_field_section_size`,
+    // disconnecting a client that hasn't sent SETTINGS and CONNECT by `deadline`,
+    // holding up to `early_buffer` streams and datagrams sent before the response, and
+    // answering any plain HTTP/3 requests before the CONNECT with `http_handler`.
+    pub(crate) async fn accept_with(
+        conn: ez::Connection,
+        max_field_section_size: Option<u64>,
+        deadline: Option<Instant>,
+        early_buffer: usize,
+        http_handler: Option<&h3::HttpHandler>,
+    ) -> Result<Self, ServerError> {
         // Perform the H3 handshake by sending/reciving SETTINGS frames.
-        let settings = h3::Settings::connect(&conn).await?;
+        let settings = h3::Settings::connect_with(&conn, max_field_section_size);
+        let settings = before(&conn, deadline, codes::h3::SETTINGS_ERROR, settings).await??;
 
         // Accept the CONNECT request but don't send a response yet.
-        let connect = h3::Connecting::accept(&conn).await?;
+        let connect = h3::Connecting::accept_with(&conn, max_field_section_size, http_handler);
+        let connect = before(&conn, deadline, codes::h3::REQUEST_REJECTED, connect).await??;
+
+        // Start holding anything the client sends before we respond.
+        let early = EarlyBuffer::new(conn.clone(), early_buffer);
 
         // Return the resulting request with a reference to the settings/connect streams.
         Ok(Self {
             conn,
             settings,
             connect,
+            faults: None,
+            codes: DropCodes::default(),
+            permit: None,
+            mode: SessionMode::Full,
+            early,
+            expiry: None,
         })
     }
 
+    pub(crate) fn with_faults(mut self, faults: Option<Arc<FaultInjector>>) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    pub(crate) fn with_drop_codes(mut self, codes: DropCodes) -> Self {
+        self.codes = codes;
+        self
+    }
+
+    pub(crate) fn with_permit(mut self, permit: Option<SessionPermit>) -> Self {
+        self.permit = permit;
+        self
+    }
+
+    // Send GOAWAY on the connection once `shutdown` is set.
+    pub(crate) fn with_shutdown(mut self, shutdown: watch::Receiver<bool>) -> Self {
+        self.settings = self.settings.with_shutdown(shutdown);
+        self
+    }
+
+    pub(crate) fn with_response_timeout(mut self, timeout: Option<Duration>) -> Self {
+        // Nothing drives a session yet, so the timer runs on the default spawner.
+        let spawner = Spawner::default();
+        self.expiry = timeout.map(|timeout| Expiry::new(self.conn.clone(), &spawner, timeout));
+        self
+    }
+
+    /// Accept the session as [datagram-only](SessionMode::DatagramOnly), or with streams.
+    ///
+    /// Defaults to [SessionMode::Full]. Usually chosen from the negotiated subprotocol;
+    /// the client has to make the same choice, since nothing on the wire says which.
+    pub fn with_session_mode(mut self, mode: SessionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     /// Accept the session, returning a 200 OK.
     pub async fn ok(self) -> Result<Connection, ServerError> {
         self.respond(ConnectResponse::OK).await
@@ -34,8 +124,45 @@ impl Request {
         self,
         response: impl Into<ConnectResponse>,
     ) -> Result<Connection, ServerError> {
-        let connect = self.connect.respond(response.into()).await?;
-        Ok(Connection::new(self.conn, self.settings, connect))
+        self.respond_spawned(response.into(), Spawner::Tokio).await
+    }
+
+    /// Like [Request::ok], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn ok_driven(self) -> Result<(Connection, SessionDriver), ServerError> {
+        self.respond_driven(ConnectResponse::OK).await
+    }
+
+    /// Like [Request::respond], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn respond_driven(
+        self,
+        response: impl Into<ConnectResponse>,
+    ) -> Result<(Connection, SessionDriver), ServerError> {
+        let (spawner, driver) = Spawner::driven();
+        let session = self.respond_spawned(response.into(), spawner).await?;
+        Ok((session, driver))
+    }
+
+    async fn respond_spawned(
+        mut self,
+        response: ConnectResponse,
+        spawner: Spawner,
+    ) -> Result<Connection, ServerError> {
+        self.disarm()?;
+        let connect = self.connect.respond(response).await?;
+        let early = self.early.finish().await;
+        Ok(Connection::new(
+            self.conn,
+            self.settings,
+            connect,
+            self.codes,
+            self.mode,
+            spawner,
+        )
+        .with_early(early)
+        .with_faults(self.faults)
+        .with_permit(self.permit))
     }
 
     /// Returns the underlying QUIC connection.
@@ -43,11 +170,81 @@ impl Request {
         &self.conn
     }
 
+    /// The certificate chain the client authenticated with, leaf first.
+    ///
+    /// `None` unless [ClientAuth](ez::ClientAuth) requested a client certificate and the
+    /// client presented one.
+    pub fn peer_certificates(&self) -> Option<Vec<ez::CertificateDer<'static>>> {
+        self.conn.peer_certificates()
+    }
+
     /// Reject the session, returing your favorite HTTP status code.
-    pub async fn reject(self, status: http::StatusCode) -> Result<(), ServerError> {
-        self.connect.reject(status).await?;
+    ///
+    /// Pass a [ConnectResponse] instead to send headers with it, such as
+    /// `www-authenticate` on a 401.
+    pub async fn reject(mut self, response: impl Into<ConnectResponse>) -> Result<(), ServerError> {
+        self.disarm()?;
+        self.connect.reject(response).await?;
         Ok(())
     }
+
+    /// Reject the session with a 302 Found, asking the client to reconnect to `location`.
+    ///
+    /// Clients only follow it when built with [ClientBuilder::with_follow_redirects](crate::ClientBuilder::with_follow_redirects).
+    pub async fn redirect(mut self, location: url::Url) -> Result<(), ServerError> {
+        self.disarm()?;
+        self.connect.redirect(location).await?;
+        Ok(())
+    }
+
+    // Stop the response timeout, failing if it already rejected the request.
+    fn disarm(&mut self) -> Result<(), ServerError> {
+        match self.expiry.take() {
+            Some(expiry) => expiry.disarm(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Closes the connection with H3_REQUEST_REJECTED unless disarmed before the timeout.
+struct Expiry {
+    // Set by the timer task once it closes the connection.
+    expired: Arc<AtomicBool>,
+
+    // Dropped to stop the timer task.
+    _cancel: oneshot::Sender<()>,
+}
+
+impl Expiry {
+    fn new(conn: ez::Connection, spawner: &Spawner, timeout: Duration) -> Self {
+        let sleep = conn.clock().sleep(timeout);
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        let expired = Arc::new(AtomicBool::new(false));
+
+        let fired = expired.clone();
+        spawner.spawn(async move {
+            tokio::select! {
+                _ = sleep => {}
+                _ = cancelled => return,
+            }
+
+            tracing::debug!("rejecting unanswered request");
+            fired.store(true, Ordering::Relaxed);
+            conn.close(codes::h3::REQUEST_REJECTED, "response timeout");
+        });
+
+        Self {
+            expired,
+            _cancel: cancel,
+        }
+    }
+
+    fn disarm(self) -> Result<(), ServerError> {
+        match self.expired.load(Ordering::Relaxed) {
+            true => Err(ServerError::RequestTimeout),
+            false => Ok(()),
+        }
+    }
 }
 
 impl core::ops::Deref for Request {
@@ -57,3 +254,23 @@ impl core::ops::Deref for Request {
         &self.connect
     }
 }
+
+/// Run `fut` until `deadline`, closing the connection with `code` if it passes first.
+async fn before<T>(
+    conn: &ez::Connection,
+    deadline: Option<Instant>,
+    code: u64,
+    fut: impl Future<Output = T>,
+) -> Result<T, ServerError> {
+    let Some(deadline) = deadline else {
+        return Ok(fut.await);
+    };
+
+    match conn.clock().timeout_at(deadline, fut).await {
+        Ok(res) => Ok(res),
+        Err(_) => {
+            conn.close(code, "handshake timeout");
+            Err(ServerError::HandshakeTimeout)
+        }
+    }
+}
@@ -1,11 +1,23 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
 use futures::try_join;
 
 use thiserror::Error;
+use tokio::sync::watch;
+use web_transport_proto::{FlowLimits, GoAway, VarInt};
+use web_transport_trait::ErrorKind;
 
 use crate::ez;
 
 /// An error returned when exchanging HTTP/3 SETTINGS frames.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum SettingsError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -13,8 +25,8 @@ pub enum SettingsError {
     #[error("protocol error: {0}")]
     Proto(#[from] web_transport_proto::SettingsError),
 
-    #[error("WebTransport is not supported")]
-    WebTransportUnsupported,
+    #[error("WebTransport is not supported: {0}")]
+    WebTransportUnsupported(web_transport_proto::UnsupportedSettings),
 
     #[error("connection error")]
     Connection(#[from] ez::ConnectionError),
@@ -23,14 +35,44 @@ pub enum SettingsError {
     Stream(#[from] ez::StreamError),
 }
 
+impl SettingsError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Proto(_) => ErrorKind::Protocol,
+            Self::WebTransportUnsupported(_) => ErrorKind::Unsupported,
+            Self::Connection(e) => e.kind(),
+            Self::Stream(e) => e.kind(),
+        }
+    }
+}
+
 /// HTTP/3 SETTINGS frame exchange for WebTransport support negotiation.
 pub struct Settings {
-    // A reference to the send/recv stream, so we don't close it until dropped.
-    #[allow(dead_code)]
-    send: ez::SendStream,
+    // Our control stream, kept open until dropped and used to send GOAWAY.
+    send: tokio::sync::Mutex<ez::SendStream>,
+
+    // The peer's control stream, taken by whoever reads the rest of it.
+    recv: Mutex<Option<ez::RecvStream>>,
+
+    // The IDs of both control streams, which outlive `recv` being taken.
+    control: [ez::StreamId; 2],
+
+    // The stream ID from the peer's latest GOAWAY, once one arrives.
+    goaway: watch::Sender<Option<VarInt>>,
 
-    #[allow(dead_code)]
-    recv: ez::RecvStream,
+    // The first request stream we haven't processed, advertised in our GOAWAY.
+    next_request: AtomicU64,
+
+    // Set when the server starts shutting down, at which point we send GOAWAY.
+    shutdown: Option<watch::Receiver<bool>>,
+
+    // The session-level flow control limits from the peer's SETTINGS, if any.
+    pub(crate) peer_limits: Option<FlowLimits>,
+
+    // Whether the peer's SETTINGS enabled HTTP/3 datagrams.
+    pub(crate) peer_datagrams: bool,
 }
 
 impl Settings {
@@ -38,30 +80,140 @@ impl Settings {
     ///
     /// This sends and receives SETTINGS frames to ensure both sides support WebTransport.
     pub async fn connect(conn: &ez::Connection) -> Result<Self, SettingsError> {
+        Self::connect_with(conn, None).await
+    }
+
+    // Exchange SETTINGS, advertising the largest field section we'll accept.
+    pub(crate) async fn connect_with(
+        conn: &ez::Connection,
+        max_field_section_size: Option<u64>,
+    ) -> Result<Self, SettingsError> {
         let recv = Self::accept(conn);
-        let send = Self::open(conn);
+        let send = Self::open(conn, max_field_section_size);
 
         // Run both tasks concurrently until one errors or they both complete.
-        let (send, recv) = try_join!(send, recv)?;
-        Ok(Self { send, recv })
+        let (send, (recv, peer_limits, peer_datagrams)) = try_join!(send, recv)?;
+        Ok(Self {
+            control: [send.id(), recv.id()],
+            send: tokio::sync::Mutex::new(send),
+            recv: Mutex::new(Some(recv)),
+            goaway: watch::Sender::new(None),
+            next_request: AtomicU64::new(0),
+            shutdown: None,
+            peer_limits,
+            peer_datagrams,
+        })
+    }
+
+    // Send GOAWAY once `shutdown` is set.
+    pub(crate) fn with_shutdown(mut self, shutdown: watch::Receiver<bool>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
     }
 
-    async fn accept(conn: &ez::Connection) -> Result<ez::RecvStream, SettingsError> {
+    // Record that we've processed the request on this stream, so our GOAWAY won't cover it.
+    pub(crate) fn processed(&self, stream_id: VarInt) {
+        self.next_request
+            .fetch_max(stream_id.into_inner() + 4, Ordering::Relaxed);
+    }
+
+    // Our control stream and the peer's.
+    pub(crate) fn control_streams(&self) -> [ez::StreamId; 2] {
+        self.control
+    }
+
+    // The stream ID from the peer's GOAWAY, if it sent one.
+    pub(crate) fn goaway(&self) -> Option<VarInt> {
+        *self.goaway.borrow()
+    }
+
+    // Wait until the peer sends GOAWAY.
+    pub(crate) async fn going_away(&self) {
+        let mut goaway = self.goaway.subscribe();
+        // The sender is ours, so this can't fail.
+        goaway.wait_for(Option::is_some).await.ok();
+    }
+
+    // Read the peer's control stream and send our GOAWAY on shutdown, until the connection closes.
+    //
+    // Returns None if something else is already doing it.
+    pub(crate) fn run(self: &Arc<Self>) -> Option<impl Future<Output = ()> + Send + 'static> {
+        let mut recv = self.recv.lock().unwrap().take()?;
+        let this = self.clone();
+
+        Some(async move {
+            let read = async {
+                loop {
+                    match GoAway::read(&mut recv).await {
+                        Ok(goaway) => {
+                            tracing::debug!("received GOAWAY frame: {goaway:?}");
+                            this.goaway.send_replace(Some(goaway.id));
+                        }
+                        Err(err) => {
+                            tracing::debug!("stopped reading the control stream: {err}");
+                            return;
+                        }
+                    }
+                }
+            };
+
+            let send = async {
+                if let Some(mut shutdown) = this.shutdown.clone() {
+                    if shutdown.wait_for(|shutdown| *shutdown).await.is_ok() {
+                        this.send_goaway().await;
+                    }
+                }
+                std::future::pending::<()>().await
+            };
+
+            tokio::select! {
+                _ = read => {}
+                _ = send => {}
+            }
+        })
+    }
+
+    async fn send_goaway(&self) {
+        let id = self.next_request.load(Ordering::Relaxed);
+        let goaway = GoAway::new(VarInt::from_u64(id).unwrap_or(VarInt::MAX));
+
+        tracing::debug!("sending GOAWAY frame: {goaway:?}");
+
+        let mut send = self.send.lock().await;
+        if let Err(err) = goaway.write(&mut *send).await {
+            tracing::debug!("failed to send GOAWAY: {err}");
+        }
+    }
+
+    async fn accept(
+        conn: &ez::Connection,
+    ) -> Result<(ez::RecvStream, Option<FlowLimits>, bool), SettingsError> {
         let mut recv = conn.accept_uni().await?;
         let settings = web_transport_proto::Settings::read(&mut recv).await?;
 
         tracing::debug!("received SETTINGS frame: {settings:?}");
 
-        if settings.supports_webtransport() == 0 {
-            return Err(SettingsError::WebTransportUnsupported);
+        // Datagrams are optional: a peer without them still gets streams.
+        if settings.webtransport_max_sessions() == 0 {
+            return Err(SettingsError::WebTransportUnsupported(
+                web_transport_proto::UnsupportedSettings { settings },
+            ));
         }
 
-        Ok(recv)
+        let datagrams = settings.supports_datagrams();
+        Ok((recv, settings.webtransport_initial_limits(), datagrams))
     }
 
-    async fn open(conn: &ez::Connection) -> Result<ez::SendStream, SettingsError> {
+    async fn open(
+        conn: &ez::Connection,
+        max_field_section_size: Option<u64>,
+    ) -> Result<ez::SendStream, SettingsError> {
         let mut settings = web_transport_proto::Settings::default();
         settings.enable_webtransport(1);
+        settings.set_webtransport_initial_limits(FlowLimits::UNLIMITED);
+        if let Some(size) = max_field_section_size {
+            settings.set_max_field_section_size(size);
+        }
 
         tracing::debug!("sending SETTINGS frame: {settings:?}");
 
@@ -22,12 +22,15 @@
 //! This crate avoids that complexity, doing the bare minimum to support a single WebTransport session that owns the entire QUIC connection.
 //! If you want to support HTTP/3 on the same host/port, you should use another crate (ex. `h3-webtransport`).
 //! If you want to support multiple WebTransport sessions over the same QUIC connection... you should just dial a new QUIC connection instead.
+//! `web-transport-quinn` has a `SessionPool` for servers that must accept pooled sessions; there's no equivalent here yet.
 
-pub mod ez;
 pub mod h3;
+pub mod tls;
 
 mod client;
 mod connection;
+mod driver;
+mod early;
 mod error;
 mod recv;
 mod send;
@@ -35,18 +38,25 @@ mod server;
 
 pub use client::*;
 pub use connection::*;
+pub use driver::SessionDriver;
 pub use error::*;
 pub use recv::*;
 pub use send::*;
 pub use server::*;
 
+use web_transport_trait::FaultInjector;
+
 pub use ez::{
-    CertResolver, CertificateDer, CertifiedKey, ClientAuth, PrivateKeyDer, QlogCompression,
-    Settings,
+    CertResolver, CertificateDer, CertifiedKey, ClientAuth, DatagramOptions, MemoryAccount,
+    MemoryBudget, PrivateKeyDer, QlogCompression, Settings, SocketOptions,
+    DEFAULT_CONNECTION_ATTEMPT_DELAY,
 };
 
+pub use ez::{boring, pki_types};
 pub use http;
+pub use quiche_ez as ez;
 pub use web_transport_proto as proto;
+pub use web_transport_trait::{Clock, ErrorKind, Faults, StreamOptions, TokioClock};
 
 /// The ALPN used for WebTransport over HTTP/3.
 pub const ALPN: &str = "h3";
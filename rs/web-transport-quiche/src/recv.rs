@@ -3,30 +3,62 @@ use std::{
     task::{Context, Poll},
 };
 
-use bytes::{BufMut, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio::io::{AsyncRead, ReadBuf};
 
 use crate::{ez, StreamError};
 
-// "recv" in ascii; if you see this then read everything or close(code)
-// hex: 0x44454356, or 0x52E4EA9B7F80 as an HTTP error code
-// decimal: 1146556178, or 91143142080384 as an HTTP error code
-const DROP_CODE: u64 = web_transport_proto::error_to_http3(0x44454356);
-
 /// A stream that can be used to receive bytes.
 pub struct RecvStream {
     inner: ez::RecvStream,
+
+    // Payload that arrived in the same chunk as the stream header, served before
+    // reading from `inner`.
+    buffered: Bytes,
+
+    // Stopped with this WebTransport code if dropped before reading everything.
+    drop_code: u32,
 }
 
 impl RecvStream {
-    pub(super) fn new(inner: ez::RecvStream) -> Self {
-        Self { inner }
+    pub(super) fn new(inner: ez::RecvStream, drop_code: u32) -> Self {
+        Self {
+            inner,
+            buffered: Bytes::new(),
+            drop_code,
+        }
+    }
+
+    /// Attach payload bytes that were read along with the stream header.
+    pub(super) fn with_buffered(mut self, buffered: Bytes) -> Self {
+        self.buffered = buffered;
+        self
+    }
+
+    /// Data that has already been received but not yet read.
+    ///
+    /// When a stream is accepted, any payload that arrived in the same packet as
+    /// the WebTransport header is kept here instead of being dropped back into
+    /// QUIC, so a short stream can be inspected (or fully read) without waiting
+    /// on another wakeup. Every read method consumes these bytes first.
+    pub fn peek(&self) -> &[u8] {
+        &self.buffered
+    }
+
+    // Take up to `max` bytes from the front of the buffered payload.
+    fn take_buffered(&mut self, max: usize) -> Bytes {
+        self.buffered.split_to(max.min(self.buffered.len()))
     }
 
     /// Read some data into the buffer and return the amount read.
     ///
     /// Returns `None` if the stream has been finished.
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, StreamError> {
+        if !self.buffered.is_empty() {
+            let chunk = self.take_buffered(buf.len());
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            return Ok(Some(chunk.len()));
+        }
         self.inner.read(buf).await.map_err(Into::into)
     }
 
@@ -34,19 +66,70 @@ impl RecvStream {
     ///
     /// Returns `None` if the stream has been finished.
     pub async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, StreamError> {
+        if !self.buffered.is_empty() {
+            return Ok(Some(self.take_buffered(max)));
+        }
         self.inner.read_chunk(max).await.map_err(Into::into)
     }
 
+    /// Read every chunk that's already been received, up to `max_total` bytes in total.
+    ///
+    /// Only waits if nothing is ready. Returns `None` if the stream has been finished.
+    pub async fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> Result<Option<Vec<Bytes>>, StreamError> {
+        if !self.buffered.is_empty() {
+            return Ok(Some(vec![self.take_buffered(max_total)]));
+        }
+        self.inner
+            .read_ready_chunks(max_total)
+            .await
+            .map_err(Into::into)
+    }
+
     /// Read data into a mutable buffer and return the amount read.
     ///
     /// Returns `None` if the stream has been finished.
     pub async fn read_buf<B: BufMut>(&mut self, buf: &mut B) -> Result<Option<usize>, StreamError> {
+        if !self.buffered.is_empty() && buf.has_remaining_mut() {
+            let chunk = self.take_buffered(buf.remaining_mut());
+            buf.put_slice(&chunk);
+            return Ok(Some(chunk.len()));
+        }
         self.inner.read_buf(buf).await.map_err(Into::into)
     }
 
+    /// Read data into an owned buffer, handing it back with the amount read.
+    ///
+    /// Nothing is borrowed across the await. Returns `None` if the stream has been finished.
+    /// The buffer comes back even on error, so it can be reused.
+    pub async fn read_owned(
+        &mut self,
+        mut buf: BytesMut,
+    ) -> (BytesMut, Result<Option<usize>, StreamError>) {
+        let size = self.read_buf(&mut buf).await;
+        (buf, size)
+    }
+
     /// Read until the end of the stream or the limit is hit.
     pub async fn read_all(&mut self, max: usize) -> Result<Bytes, StreamError> {
-        self.inner.read_all(max).await.map_err(Into::into)
+        let head = self.take_buffered(max);
+        let tail = self.inner.read_all(max - head.len()).await?;
+        if head.is_empty() {
+            return Ok(tail);
+        }
+        Ok([&head[..], &tail[..]].concat().into())
+    }
+
+    /// Read until the end of the stream, failing with [StreamError::TooLong] if it exceeds `max`.
+    pub async fn read_to_end(&mut self, max: usize) -> Result<Bytes, StreamError> {
+        // Read one byte past the limit, to tell a stream that ends there from a longer one.
+        let buf = self.read_all(max.saturating_add(1)).await?;
+        if buf.len() > max {
+            return Err(StreamError::TooLong { limit: max });
+        }
+        Ok(buf)
     }
 
     /// Tell the other end to stop sending data with the given error code.
@@ -60,13 +143,36 @@ impl RecvStream {
     pub async fn closed(&mut self) -> Result<(), StreamError> {
         self.inner.closed().await.map_err(Into::into)
     }
+
+    /// Block until the peer resets the stream and return its WebTransport error code.
+    ///
+    /// Returns None once the stream is finished and fully read, if it was stopped locally,
+    /// or if the peer's code is not a valid WebTransport error code.
+    pub async fn received_reset(&mut self) -> Result<Option<u32>, StreamError> {
+        match self.inner.closed().await {
+            Ok(()) | Err(ez::StreamError::Stop(_)) => Ok(None),
+            Err(ez::StreamError::Reset(code)) => Ok(web_transport_proto::error_from_http3(code)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The WebTransport code the peer reset the stream with, once the reset has arrived.
+    ///
+    /// Unlike [Self::received_reset], this doesn't wait. Returns None if the peer's code
+    /// is not a valid WebTransport error code, which reads report as [StreamError::InvalidReset].
+    pub fn reset_code(&self) -> Option<u32> {
+        self.inner
+            .reset_code()
+            .and_then(web_transport_proto::error_from_http3)
+    }
 }
 
 impl Drop for RecvStream {
     fn drop(&mut self) {
         if !self.inner.is_closed() {
             tracing::warn!("stream dropped without `stop` or reading all contents");
-            self.inner.stop(DROP_CODE)
+            self.inner
+                .stop(web_transport_proto::error_to_http3(self.drop_code))
         }
     }
 }
@@ -77,6 +183,12 @@ impl AsyncRead for RecvStream {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<Result<(), std::io::Error>> {
+        if !self.buffered.is_empty() {
+            let n = buf.remaining().min(self.buffered.len());
+            buf.put_slice(&self.buffered[..n]);
+            self.buffered.advance(n);
+            return Poll::Ready(Ok(()));
+        }
         let pinned = pin!(&mut self.inner);
         pinned.poll_read(cx, buf)
     }
@@ -94,6 +206,13 @@ impl web_transport_trait::RecvStream for RecvStream {
         self.read_chunk(max).await
     }
 
+    async fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> Result<Option<Vec<Bytes>>, Self::Error> {
+        self.read_ready_chunks(max_total).await
+    }
+
     fn stop(&mut self, code: u32) {
         self.stop(code);
     }
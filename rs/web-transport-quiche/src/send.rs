@@ -1,18 +1,19 @@
 use std::{
     io,
     pin::Pin,
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use bytes::Buf;
+use bytes::{Buf, Bytes};
 use tokio::io::AsyncWrite;
+use web_transport_proto::SessionFlow;
 
 use crate::{ez, StreamError};
 
-// "send" in ascii; if you see this then call finish().await or close(code)
-// hex: 0x73656E64, or 0x52E51B4DCE20 as an HTTP error code
-// decimal: 1685221232, or 91143959072288 as an HTTP error code
-const DROP_CODE: u64 = web_transport_proto::error_to_http3(0x73656E64);
+// The trait sends higher priorities first, but quiche sends lower urgencies first.
+pub(crate) fn urgency(order: u8) -> u8 {
+    u8::MAX - order
+}
 
 /// A stream that can be used to send bytes.
 ///
@@ -20,31 +21,56 @@ const DROP_CODE: u64 = web_transport_proto::error_to_http3(0x73656E64);
 /// WebTransport uses u32 error codes and they're mapped in a reserved HTTP/3 error space.
 pub struct SendStream {
     inner: ez::SendStream,
+
+    // Reset with this WebTransport code if dropped without `finish` or `reset`.
+    drop_code: u32,
+
+    // The peer's session-level flow control, shared with every stream in the session.
+    flow: SessionFlow,
 }
 
 impl SendStream {
-    pub(super) fn new(inner: ez::SendStream) -> Self {
-        Self { inner }
+    pub(super) fn new(inner: ez::SendStream, drop_code: u32, flow: SessionFlow) -> Self {
+        Self {
+            inner,
+            drop_code,
+            flow,
+        }
     }
 
     /// Write some data to the stream, returning the size written.
+    ///
+    /// This may write less than asked when limited by the peer's session-level flow control.
     pub async fn write(&mut self, buf: &[u8]) -> Result<usize, StreamError> {
-        self.inner.write(buf).await.map_err(Into::into)
+        let credit = self.flow.reserve(buf.len()).await;
+        let size = self.inner.write(&buf[..credit.size()]).await?;
+        credit.sent(size);
+        Ok(size)
     }
 
     /// Write data from a buffer to the stream, returning the size written.
     pub async fn write_buf<B: Buf>(&mut self, buf: &mut B) -> Result<usize, StreamError> {
-        self.inner.write_buf(buf).await.map_err(Into::into)
+        let credit = self.flow.reserve(buf.remaining()).await;
+        let size = self.inner.write_buf(&mut buf.take(credit.size())).await?;
+        credit.sent(size);
+        Ok(size)
     }
 
     /// Write all of the data to the stream.
-    pub async fn write_all(&mut self, buf: &[u8]) -> Result<(), StreamError> {
-        self.inner.write_all(buf).await.map_err(Into::into)
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> Result<(), StreamError> {
+        while !buf.is_empty() {
+            let size = self.write(buf).await?;
+            buf = &buf[size..];
+        }
+        Ok(())
     }
 
     /// Write all data from a buffer to the stream.
     pub async fn write_buf_all<B: Buf>(&mut self, buf: &mut B) -> Result<(), StreamError> {
-        self.inner.write_buf_all(buf).await.map_err(Into::into)
+        while buf.has_remaining() {
+            self.write_buf(buf).await?;
+        }
+        Ok(())
     }
 
     /// Mark the stream as finished, such that no more data can be written.
@@ -71,6 +97,28 @@ impl SendStream {
     pub async fn closed(&mut self) -> Result<(), StreamError> {
         self.inner.closed().await.map_err(Into::into)
     }
+
+    /// Wait until the peer stops the stream and return its WebTransport error code.
+    ///
+    /// Returns None once all data has been acknowledged, if the stream was reset locally,
+    /// or if the peer's code is not a valid WebTransport error code.
+    pub async fn stopped(&mut self) -> Result<Option<u32>, StreamError> {
+        match self.inner.acked().await {
+            Ok(()) | Err(ez::StreamError::Reset(_)) => Ok(None),
+            Err(ez::StreamError::Stop(code)) => Ok(web_transport_proto::error_from_http3(code)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The WebTransport code the peer stopped the stream with, once the STOP_SENDING has arrived.
+    ///
+    /// Unlike [Self::stopped], this doesn't wait. Returns None if the peer's code is not
+    /// a valid WebTransport error code, which writes report as [StreamError::InvalidStop].
+    pub fn stop_code(&self) -> Option<u32> {
+        self.inner
+            .stop_code()
+            .and_then(web_transport_proto::error_from_http3)
+    }
 }
 
 impl Drop for SendStream {
@@ -78,7 +126,8 @@ impl Drop for SendStream {
         // Reset the stream if we dropped without calling `close` or `reset`
         if !self.inner.is_finished().unwrap_or(true) {
             tracing::warn!("stream dropped without `close` or `reset`");
-            self.inner.reset(DROP_CODE)
+            self.inner
+                .reset(web_transport_proto::error_to_http3(self.drop_code))
         }
     }
 }
@@ -89,8 +138,17 @@ impl AsyncWrite for SendStream {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, io::Error>> {
+        let credit = ready!(self.flow.poll_send(cx, buf.len()));
+
         let inner = std::pin::pin!(&mut self.inner);
-        inner.poll_write(cx, buf)
+        let res = inner.poll_write(cx, &buf[..credit]);
+        let sent = match &res {
+            Poll::Ready(Ok(size)) => *size,
+            _ => 0,
+        };
+        self.flow.unsend(credit - sent);
+
+        res
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
@@ -114,8 +172,36 @@ impl web_transport_trait::SendStream for SendStream {
         self.write(buf).await
     }
 
+    // The buffer helpers forward to the native ones rather than the trait's
+    // defaults, which go through `buf.chunk()` and copy. These take the bytes with
+    // `copy_to_bytes`, which is free for `Bytes`, and are cancel safe: the buffer
+    // only advances once capacity was reserved for what it gives up.
+
+    async fn write_buf<B: Buf + web_transport_trait::MaybeSend>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<usize, Self::Error> {
+        self.write_buf(buf).await
+    }
+
+    async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), Self::Error> {
+        let mut chunk = chunk;
+        self.write_buf_all(&mut chunk).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.write_all(buf).await
+    }
+
+    async fn write_all_buf<B: Buf + web_transport_trait::MaybeSend>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<(), Self::Error> {
+        self.write_buf_all(buf).await
+    }
+
     fn set_priority(&mut self, order: u8) {
-        self.set_priority(order)
+        self.set_priority(urgency(order))
     }
 
     fn reset(&mut self, code: u32) {
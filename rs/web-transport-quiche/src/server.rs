@@ -1,13 +1,23 @@
 use std::io;
 use std::sync::Arc;
+use std::time::Duration;
 
-use futures::StreamExt;
-use futures::{future::BoxFuture, stream::FuturesUnordered};
+use tokio::{sync::watch, task::JoinSet};
+use web_transport_trait::ErrorKind;
 
-use crate::{ez, h3};
+use crate::{
+    early::EARLY_BUFFER,
+    ez, h3,
+    proto::{
+        codes::DropCodes, AcceptQueue, ConnectResponse, HttpRequest, HttpResponse, QueueSlot,
+        SessionLimits,
+    },
+    FaultInjector, Faults,
+};
 
 /// An error returned when receiving a new WebTransport session.
 #[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ServerError {
     #[error("io error: {0}")]
     Io(Arc<std::io::Error>),
@@ -20,6 +30,37 @@ pub enum ServerError {
 
     #[error("connect error: {0}")]
     Connect(#[from] h3::ConnectError),
+
+    #[error("timed out waiting for the client to finish the handshake")]
+    HandshakeTimeout,
+
+    #[error("too many sessions")]
+    TooManySessions,
+
+    #[error("origin not allowed")]
+    ForbiddenOrigin,
+
+    #[error("too many sessions waiting to be accepted")]
+    AcceptQueueFull,
+
+    #[error("the request wasn't answered before the response timeout")]
+    RequestTimeout,
+}
+
+impl ServerError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Io(_) => ErrorKind::Io,
+            Self::Connection(e) => e.kind(),
+            Self::Settings(e) => e.kind(),
+            Self::Connect(e) => e.kind(),
+            Self::HandshakeTimeout | Self::RequestTimeout => ErrorKind::TimedOut,
+            Self::TooManySessions | Self::ForbiddenOrigin | Self::AcceptQueueFull => {
+                ErrorKind::Rejected
+            }
+        }
+    }
 }
 
 impl From<std::io::Error> for ServerError {
@@ -31,11 +72,43 @@ impl From<std::io::Error> for ServerError {
 /// Construct a WebTransport server using sane defaults.
 pub struct ServerBuilder<M: ez::Metrics = ez::DefaultMetrics, S = ez::ServerInit>(
     ez::ServerBuilder<M, S>,
+    Options,
 );
 
+// Options for the HTTP/3 handshake, on top of the QUIC server's.
+struct Options {
+    faults: Option<Faults>,
+    max_field_section_size: Option<u64>,
+    drop_codes: DropCodes,
+    handshake_timeout: Option<Duration>,
+    session_limits: Option<SessionLimits>,
+    accept_queue: Option<AcceptQueue>,
+    early_buffer: usize,
+    response_timeout: Option<Duration>,
+    allowed_origins: Option<Arc<[url::Origin]>>,
+    http_handler: Option<h3::HttpHandler>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            faults: None,
+            max_field_section_size: None,
+            drop_codes: DropCodes::default(),
+            handshake_timeout: None,
+            session_limits: None,
+            accept_queue: None,
+            early_buffer: EARLY_BUFFER,
+            response_timeout: None,
+            allowed_origins: None,
+            http_handler: None,
+        }
+    }
+}
+
 impl Default for ServerBuilder<ez::DefaultMetrics> {
     fn default() -> Self {
-        Self(ez::ServerBuilder::default())
+        Self(ez::ServerBuilder::default(), Options::default())
     }
 }
 
@@ -44,7 +117,7 @@ impl ServerBuilder<ez::DefaultMetrics, ez::ServerInit> {
     ///
     /// Use [ServerBuilder::default] if you don't care about metrics.
     pub fn with_metrics<M: ez::Metrics>(m: M) -> ServerBuilder<M, ez::ServerInit> {
-        ServerBuilder(ez::ServerBuilder::with_metrics(m))
+        ServerBuilder(ez::ServerBuilder::with_metrics(m), Options::default())
     }
 }
 
@@ -54,7 +127,7 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerInit> {
         self,
         listener: tokio_quiche::socket::QuicListener,
     ) -> ServerBuilder<M, ez::ServerWithListener> {
-        ServerBuilder::<M, ez::ServerWithListener>(self.0.with_listener(listener))
+        ServerBuilder::<M, ez::ServerWithListener>(self.0.with_listener(listener), self.1)
     }
 
     /// Listen for incoming packets on the given socket.
@@ -64,6 +137,7 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerInit> {
     ) -> io::Result<ServerBuilder<M, ez::ServerWithListener>> {
         Ok(ServerBuilder::<M, ez::ServerWithListener>(
             self.0.with_socket(socket)?,
+            self.1,
         ))
     }
 
@@ -74,33 +148,214 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerInit> {
     ) -> io::Result<ServerBuilder<M, ez::ServerWithListener>> {
         Ok(ServerBuilder::<M, ez::ServerWithListener>(
             self.0.with_bind(addrs)?,
+            self.1,
         ))
     }
 
     /// Use the provided [Settings](ez::Settings) instead of the defaults.
     pub fn with_settings(self, settings: ez::Settings) -> Self {
-        Self(self.0.with_settings(settings))
+        Self(self.0.with_settings(settings), self.1)
+    }
+
+    /// Write a qlog trace of each connection to a file in `dir`.
+    ///
+    /// See [ServerBuilder::with_qlog_dir](ServerBuilder::<M, ez::ServerWithListener>::with_qlog_dir).
+    pub fn with_qlog_dir(self, dir: impl AsRef<std::path::Path>) -> Self {
+        Self(self.0.with_qlog_dir(dir), self.1)
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// See [ServerBuilder::with_keylog](ServerBuilder::<M, ez::ServerWithListener>::with_keylog).
+    pub fn with_keylog(self, enabled: bool) -> Self {
+        Self(self.0.with_keylog(enabled), self.1)
     }
 
     /// Send a PING to each client on this interval, keeping idle connections alive.
     ///
     /// See [ServerBuilder::with_keep_alive](ServerBuilder::<M, ez::ServerWithListener>::with_keep_alive).
     pub fn with_keep_alive(self, interval: std::time::Duration) -> Self {
-        Self(self.0.with_keep_alive(interval))
+        Self(self.0.with_keep_alive(interval), self.1)
+    }
+
+    /// Close a connection after this long without hearing from the client.
+    ///
+    /// See [ServerBuilder::with_idle_timeout](ServerBuilder::<M, ez::ServerWithListener>::with_idle_timeout).
+    pub fn with_idle_timeout(self, timeout: Duration) -> Self {
+        Self(self.0.with_idle_timeout(timeout), self.1)
+    }
+
+    /// Queue up to this many outgoing datagrams per connection that haven't been sent yet.
+    ///
+    /// See [ServerBuilder::with_datagram_send_buffer](ServerBuilder::<M, ez::ServerWithListener>::with_datagram_send_buffer).
+    pub fn with_datagram_send_buffer(self, datagrams: usize) -> Self {
+        Self(self.0.with_datagram_send_buffer(datagrams), self.1)
     }
 
     /// Enable UDP generic segmentation offload (GSO), on by default.
     ///
     /// See [ServerBuilder::with_gso](ServerBuilder::<M, ez::ServerWithListener>::with_gso).
     pub fn with_gso(self, enabled: bool) -> Self {
-        Self(self.0.with_gso(enabled))
+        Self(self.0.with_gso(enabled), self.1)
+    }
+
+    /// Apply these [SocketOptions](crate::SocketOptions) to the UDP sockets the server listens on.
+    ///
+    /// See [ServerBuilder::with_socket_options](ServerBuilder::<M, ez::ServerWithListener>::with_socket_options).
+    pub fn with_socket_options(self, options: crate::SocketOptions) -> Self {
+        Self(self.0.with_socket_options(options), self.1)
     }
 
     /// Authenticate clients with mTLS.
     ///
     /// Defaults to [ez::ClientAuth::None].
     pub fn with_client_auth(self, auth: ez::ClientAuth) -> Self {
-        Self(self.0.with_client_auth(auth))
+        Self(self.0.with_client_auth(auth), self.1)
+    }
+
+    /// Cap the bytes queued across every connection with a shared [MemoryBudget](crate::MemoryBudget).
+    ///
+    /// See [ServerBuilder::with_memory_budget](ServerBuilder::<M, ez::ServerWithListener>::with_memory_budget).
+    pub fn with_memory_budget(self, budget: crate::MemoryBudget) -> Self {
+        Self(self.0.with_memory_budget(budget), self.1)
+    }
+
+    /// Measure timeouts with `clock` instead of the tokio timer.
+    ///
+    /// See [ServerBuilder::with_clock](ServerBuilder::<M, ez::ServerWithListener>::with_clock).
+    pub fn with_clock(self, clock: impl crate::Clock) -> Self {
+        Self(self.0.with_clock(clock), self.1)
+    }
+
+    /// Inject the given [Faults] into every connection, for resilience testing.
+    ///
+    /// See [ServerBuilder::with_faults](ServerBuilder::<M, ez::ServerWithListener>::with_faults).
+    pub fn with_faults(self, faults: Faults) -> Self {
+        let faults = Some(faults);
+        Self(self.0, Options { faults, ..self.1 })
+    }
+
+    /// Limit the size of the CONNECT request headers, advertised to clients in SETTINGS.
+    ///
+    /// See [ServerBuilder::with_max_field_section_size](ServerBuilder::<M, ez::ServerWithListener>::with_max_field_section_size).
+    pub fn with_max_field_section_size(self, size: u64) -> Self {
+        let max_field_section_size = Some(size);
+        Self(
+            self.0,
+            Options {
+                max_field_section_size,
+                ..self.1
+            },
+        )
+    }
+
+    /// Use these codes when a session or stream is dropped without being closed.
+    ///
+    /// See [ServerBuilder::with_drop_codes](ServerBuilder::<M, ez::ServerWithListener>::with_drop_codes).
+    pub fn with_drop_codes(self, drop_codes: DropCodes) -> Self {
+        Self(
+            self.0,
+            Options {
+                drop_codes,
+                ..self.1
+            },
+        )
+    }
+
+    /// Give each client this long to finish its handshake, through to the CONNECT request.
+    ///
+    /// See [ServerBuilder::with_handshake_timeout](ServerBuilder::<M, ez::ServerWithListener>::with_handshake_timeout).
+    pub fn with_handshake_timeout(self, timeout: Duration) -> Self {
+        Self(
+            self.0,
+            Options {
+                handshake_timeout: Some(timeout),
+                ..self.1
+            },
+        )
+    }
+
+    /// Cap the number of concurrent sessions per request path, or per a custom key.
+    ///
+    /// See [ServerBuilder::with_session_limits](ServerBuilder::<M, ez::ServerWithListener>::with_session_limits).
+    pub fn with_session_limits(self, limits: SessionLimits) -> Self {
+        let session_limits = Some(limits);
+        Self(
+            self.0,
+            Options {
+                session_limits,
+                ..self.1
+            },
+        )
+    }
+
+    /// Bound the sessions that finished their handshake but wait for [Server::accept].
+    ///
+    /// See [ServerBuilder::with_accept_queue](ServerBuilder::<M, ez::ServerWithListener>::with_accept_queue).
+    pub fn with_accept_queue(self, queue: AcceptQueue) -> Self {
+        let accept_queue = Some(queue);
+        Self(
+            self.0,
+            Options {
+                accept_queue,
+                ..self.1
+            },
+        )
+    }
+
+    /// Hold up to `max` streams (and separately `max` datagrams) sent before the CONNECT response.
+    ///
+    /// See [ServerBuilder::with_early_buffer](ServerBuilder::<M, ez::ServerWithListener>::with_early_buffer).
+    pub fn with_early_buffer(self, early_buffer: usize) -> Self {
+        Self(
+            self.0,
+            Options {
+                early_buffer,
+                ..self.1
+            },
+        )
+    }
+
+    /// Reject a request that isn't answered within `timeout` of [Server::accept] returning it.
+    ///
+    /// See [ServerBuilder::with_response_timeout](ServerBuilder::<M, ez::ServerWithListener>::with_response_timeout).
+    pub fn with_response_timeout(self, response_timeout: Option<Duration>) -> Self {
+        Self(
+            self.0,
+            Options {
+                response_timeout,
+                ..self.1
+            },
+        )
+    }
+
+    /// See [ServerBuilder::with_allowed_origins](ServerBuilder::<M, ez::ServerWithListener>::with_allowed_origins).
+    pub fn with_allowed_origins(self, origins: impl IntoIterator<Item = url::Url>) -> Self {
+        let allowed_origins = Some(origins.into_iter().map(|url| url.origin()).collect());
+        Self(
+            self.0,
+            Options {
+                allowed_origins,
+                ..self.1
+            },
+        )
+    }
+
+    /// Answer plain HTTP/3 requests sent before the CONNECT, so a client can upgrade later.
+    ///
+    /// See [ServerBuilder::with_http_handler](ServerBuilder::<M, ez::ServerWithListener>::with_http_handler).
+    pub fn with_http_handler(
+        self,
+        handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> Self {
+        let http_handler: Option<h3::HttpHandler> = Some(Arc::new(handler));
+        Self(
+            self.0,
+            Options {
+                http_handler,
+                ..self.1
+            },
+        )
     }
 }
 
@@ -110,17 +365,17 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerWithListener> {
     /// The listener is used as-is: it carries its own capabilities and
     /// connection ID generator, so [ServerBuilder::with_gso] does not apply.
     pub fn with_listener(self, listener: tokio_quiche::socket::QuicListener) -> Self {
-        Self(self.0.with_listener(listener))
+        Self(self.0.with_listener(listener), self.1)
     }
 
     /// Listen for incoming packets on the given socket.
     pub fn with_socket(self, socket: std::net::UdpSocket) -> io::Result<Self> {
-        Ok(Self(self.0.with_socket(socket)?))
+        Ok(Self(self.0.with_socket(socket)?, self.1))
     }
 
     /// Listen for incoming packets on the given address.
     pub fn with_bind<A: std::net::ToSocketAddrs>(self, addrs: A) -> io::Result<Self> {
-        Ok(Self(self.0.with_bind(addrs)?))
+        Ok(Self(self.0.with_bind(addrs)?, self.1))
     }
 
     /// Use the provided [Settings](ez::Settings) instead of the defaults.
@@ -128,7 +383,23 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerWithListener> {
     /// **NOTE**: [Settings::verify_peer](ez::Settings::verify_peer) is ignored; use
     /// [ServerBuilder::with_client_auth] to verify client certificates.
     pub fn with_settings(self, settings: ez::Settings) -> Self {
-        Self(self.0.with_settings(settings))
+        Self(self.0.with_settings(settings), self.1)
+    }
+
+    /// Write a qlog trace of each connection to a file in `dir`, for debugging interop.
+    ///
+    /// Disabled by default. Set this after [ServerBuilder::with_settings], which replaces it.
+    pub fn with_qlog_dir(self, dir: impl AsRef<std::path::Path>) -> Self {
+        Self(self.0.with_qlog_dir(dir), self.1)
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// Wireshark can use the file to decrypt packet captures. Nothing is written if the
+    /// variable isn't set, or unless the `keylog` feature is enabled. Disabled by default.
+    /// Set this after [ServerBuilder::with_settings], which replaces it.
+    pub fn with_keylog(self, enabled: bool) -> Self {
+        Self(self.0.with_keylog(enabled), self.1)
     }
 
     /// Send a PING to each client on this interval, keeping idle connections alive.
@@ -138,7 +409,26 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerWithListener> {
     /// path (a NAT or load balancer) drops silent flows sooner than
     /// [Settings::max_idle_timeout](ez::Settings::max_idle_timeout) would.
     pub fn with_keep_alive(self, interval: std::time::Duration) -> Self {
-        Self(self.0.with_keep_alive(interval))
+        Self(self.0.with_keep_alive(interval), self.1)
+    }
+
+    /// Close a connection after this long without hearing from the client.
+    ///
+    /// Each side advertises its own, and the connection uses the smaller of the two.
+    /// Defaults to [Settings::max_idle_timeout](ez::Settings::max_idle_timeout); set this
+    /// after [ServerBuilder::with_settings], which replaces it.
+    pub fn with_idle_timeout(self, timeout: Duration) -> Self {
+        Self(self.0.with_idle_timeout(timeout), self.1)
+    }
+
+    /// Queue up to this many outgoing datagrams per connection that haven't been sent yet.
+    ///
+    /// Once it's full, [Connection::send_datagram](crate::Connection::send_datagram) drops
+    /// new datagrams while [Connection::send_datagram_wait](crate::Connection::send_datagram_wait)
+    /// waits. Defaults to [Settings::dgram_send_max_queue_len](ez::Settings::dgram_send_max_queue_len);
+    /// set this after [ServerBuilder::with_settings], which replaces it.
+    pub fn with_datagram_send_buffer(self, datagrams: usize) -> Self {
+        Self(self.0.with_datagram_send_buffer(datagrams), self.1)
     }
 
     /// Enable UDP generic segmentation offload (GSO), on by default.
@@ -151,23 +441,218 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerWithListener> {
     /// [ServerBuilder::with_bind] only, not to a [ServerBuilder::with_listener]
     /// listener. Only Linux supports GSO; elsewhere this does nothing.
     pub fn with_gso(self, enabled: bool) -> Self {
-        Self(self.0.with_gso(enabled))
+        Self(self.0.with_gso(enabled), self.1)
+    }
+
+    /// Apply these [SocketOptions](crate::SocketOptions) to the UDP sockets the server listens on.
+    ///
+    /// Like [ServerBuilder::with_gso], this applies to sockets from
+    /// [ServerBuilder::with_socket] and [ServerBuilder::with_bind], not to a
+    /// [ServerBuilder::with_listener] listener.
+    /// [SocketOptions::with_ipv6_only](crate::SocketOptions::with_ipv6_only) is applied
+    /// while binding, so only to [ServerBuilder::with_bind] calls that come after.
+    pub fn with_socket_options(self, options: crate::SocketOptions) -> Self {
+        Self(self.0.with_socket_options(options), self.1)
     }
 
     /// Authenticate clients with mTLS.
     ///
     /// Defaults to [ez::ClientAuth::None].
     pub fn with_client_auth(self, auth: ez::ClientAuth) -> Self {
-        Self(self.0.with_client_auth(auth))
+        Self(self.0.with_client_auth(auth), self.1)
+    }
+
+    /// Cap the bytes queued across every connection with a shared [MemoryBudget](crate::MemoryBudget).
+    ///
+    /// See [ez::ServerBuilder::with_memory_budget](ez::ServerBuilder::<M, ez::ServerWithListener>::with_memory_budget)
+    /// for what is charged. [Connection::memory](crate::Connection::memory) reports each session's usage.
+    pub fn with_memory_budget(self, budget: crate::MemoryBudget) -> Self {
+        Self(self.0.with_memory_budget(budget), self.1)
+    }
+
+    /// Measure timeouts with `clock` instead of the tokio timer.
+    ///
+    /// This covers the handshake and response timeouts, keep-alives, stream header timeouts
+    /// and datagram expiry. Defaults to [TokioClock](crate::TokioClock), which already follows
+    /// `tokio::time::pause`; QUIC's own timers, like the idle timeout, still use quiche's.
+    pub fn with_clock(self, clock: impl crate::Clock) -> Self {
+        Self(self.0.with_clock(clock), self.1)
+    }
+
+    /// Inject the given [Faults] into every connection, for resilience testing.
+    ///
+    /// **WARNING**: This deliberately degrades the connection; never enable it in production.
+    pub fn with_faults(self, faults: Faults) -> Self {
+        let faults = Some(faults);
+        Self(self.0, Options { faults, ..self.1 })
+    }
+
+    /// Limit the size of the CONNECT request headers, advertised to clients in SETTINGS.
+    ///
+    /// The size is measured as in RFC 9114: the length of every name and value plus 32
+    /// bytes per header. A larger request is answered with 431 (Request Header Fields
+    /// Too Large) and never returned by [Server::accept]. Unlimited by default, although
+    /// the headers must always fit in a single 64 KiB frame.
+    pub fn with_max_field_section_size(self, size: u64) -> Self {
+        let max_field_section_size = Some(size);
+        Self(
+            self.0,
+            Options {
+                max_field_section_size,
+                ..self.1
+            },
+        )
+    }
+
+    /// Use these codes when a session or stream is dropped without being closed.
+    ///
+    /// Defaults to the values in [codes](crate::proto::codes), which every backend
+    /// shares so a peer can tell why a stream was abandoned.
+    pub fn with_drop_codes(self, drop_codes: DropCodes) -> Self {
+        Self(
+            self.0,
+            Options {
+                drop_codes,
+                ..self.1
+            },
+        )
+    }
+
+    /// Give each client this long to finish its handshake, through to the CONNECT request.
+    ///
+    /// The deadline starts when the first packet arrives, so a slow client can't hold a
+    /// handshake open indefinitely by trickling packets. A client that misses it is
+    /// disconnected, with H3_SETTINGS_ERROR or H3_REQUEST_REJECTED if the QUIC handshake
+    /// was done, and never returned by [Server::accept]. Disabled by default.
+    pub fn with_handshake_timeout(self, timeout: Duration) -> Self {
+        Self(
+            self.0,
+            Options {
+                handshake_timeout: Some(timeout),
+                ..self.1
+            },
+        )
+    }
+
+    /// Cap the number of concurrent sessions per request path, or per a custom key.
+    ///
+    /// Each CONNECT request claims a slot before [Server::accept] returns it. A request
+    /// over the limit is answered with 503 (Service Unavailable) and never returned.
+    /// The slot is released when the request is rejected, or once every clone of the
+    /// accepted [Connection](crate::Connection) is dropped.
+    ///
+    /// Clone the limits to share them with other servers, or keep a clone to report
+    /// [SessionLimits::active] as a metric.
+    pub fn with_session_limits(self, limits: SessionLimits) -> Self {
+        let session_limits = Some(limits);
+        Self(
+            self.0,
+            Options {
+                session_limits,
+                ..self.1
+            },
+        )
+    }
+
+    /// Bound the sessions that finished their handshake but wait for [Server::accept].
+    ///
+    /// Handshakes run in the background, so a stalled accept loop would otherwise let
+    /// them pile up. A request that finds the queue full is answered with 503 (Service
+    /// Unavailable) and never returned. Unbounded by default.
+    ///
+    /// Keep a clone of the queue to report its length and rejections as metrics.
+    pub fn with_accept_queue(self, queue: AcceptQueue) -> Self {
+        let accept_queue = Some(queue);
+        Self(
+            self.0,
+            Options {
+                accept_queue,
+                ..self.1
+            },
+        )
+    }
+
+    /// Hold up to `max` streams (and separately `max` datagrams) sent before the CONNECT response.
+    ///
+    /// Clients may open streams as soon as their request is sent. Those that arrive before
+    /// [Request::respond](h3::Request::respond) are buffered and delivered by the session
+    /// once it's accepted. Any more streams are rejected with WT_BUFFERED_STREAM_REJECTED
+    /// and any more datagrams are dropped. Defaults to 16; 0 rejects every early stream.
+    pub fn with_early_buffer(self, early_buffer: usize) -> Self {
+        Self(
+            self.0,
+            Options {
+                early_buffer,
+                ..self.1
+            },
+        )
+    }
+
+    /// Reject a request that isn't answered within `timeout` of [Server::accept] returning it.
+    ///
+    /// A [Request](h3::Request) can be queued and answered later, away from the accept loop,
+    /// e.g. by an admission controller. Once the timeout passes, the connection is closed
+    /// with H3_REQUEST_REJECTED, which tells the client it's safe to retry, and answering
+    /// the request fails with [ServerError::RequestTimeout]. Defaults to `None`, which
+    /// waits forever.
+    pub fn with_response_timeout(self, response_timeout: Option<Duration>) -> Self {
+        Self(
+            self.0,
+            Options {
+                response_timeout,
+                ..self.1
+            },
+        )
+    }
+
+    /// Only accept sessions opened by pages from one of these origins.
+    ///
+    /// A request whose [origin](crate::proto::ConnectRequest::origin) isn't listed is
+    /// answered with 403 (Forbidden) and never returned by [Server::accept]. Browsers
+    /// always send the origin, so a browser-facing server should set this to stop any
+    /// other site from connecting on behalf of its visitors. Requests without an origin,
+    /// i.e. from native clients, are allowed. Only the scheme, host and port of each URL
+    /// are used.
+    pub fn with_allowed_origins(self, origins: impl IntoIterator<Item = url::Url>) -> Self {
+        let allowed_origins = Some(origins.into_iter().map(|url| url.origin()).collect());
+        Self(
+            self.0,
+            Options {
+                allowed_origins,
+                ..self.1
+            },
+        )
+    }
+
+    /// Answer plain HTTP/3 requests sent before the CONNECT, so a client can upgrade later.
+    ///
+    /// A client may start with an ordinary request on the connection, such as a GET for
+    /// its configuration, and only then open a WebTransport session. `handler` answers
+    /// each one, in order, before [Server::accept] returns the CONNECT; any request body
+    /// is left unread. Without a handler they're answered with 501 (Not Implemented). The
+    /// [handshake timeout](Self::with_handshake_timeout) still applies until the CONNECT.
+    pub fn with_http_handler(
+        self,
+        handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> Self {
+        let http_handler: Option<h3::HttpHandler> = Some(Arc::new(handler));
+        Self(
+            self.0,
+            Options {
+                http_handler,
+                ..self.1
+            },
+        )
     }
 
     /// Configure the server to use a static certificate for TLS.
     pub fn with_single_cert(
         self,
-        chain: Vec<ez::CertificateDer<'static>>,
-        key: ez::PrivateKeyDer<'static>,
+        chain: impl IntoIterator<Item = ez::CertificateDer<'static>>,
+        key: impl Into<ez::PrivateKeyDer<'static>>,
     ) -> io::Result<Server<M>> {
-        Ok(Server::new(self.0.with_single_cert(chain, key)?))
+        let server = Server::new(self.0.with_single_cert(chain, key)?);
+        Ok(server.with_options(self.1))
     }
 
     /// Configure the server to use a dynamic certificate resolver for TLS.
@@ -175,14 +660,19 @@ impl<M: ez::Metrics> ServerBuilder<M, ez::ServerWithListener> {
         self,
         resolver: std::sync::Arc<dyn ez::CertResolver>,
     ) -> io::Result<Server<M>> {
-        Ok(Server::new(self.0.with_cert_resolver(resolver)?))
+        let server = Server::new(self.0.with_cert_resolver(resolver)?);
+        Ok(server.with_options(self.1))
     }
 }
 
 /// A WebTransport server that accepts new sessions.
 pub struct Server<M: ez::Metrics = ez::DefaultMetrics> {
     inner: ez::Server<M>,
-    accept: FuturesUnordered<BoxFuture<'static, Result<h3::Request, ServerError>>>,
+    accept: JoinSet<Result<(h3::Request, Option<QueueSlot>), ServerError>>,
+    options: Options,
+
+    // Set by shutdown(), telling every connection to send GOAWAY.
+    shutdown: watch::Sender<bool>,
 }
 
 impl<M: ez::Metrics> Server<M> {
@@ -193,9 +683,16 @@ impl<M: ez::Metrics> Server<M> {
         Self {
             inner,
             accept: Default::default(),
+            options: Options::default(),
+            shutdown: watch::Sender::new(false),
         }
     }
 
+    fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Returns the local addresses of all listeners.
     pub fn local_addrs(&self) -> &[std::net::SocketAddr] {
         self.inner.local_addrs()
@@ -204,23 +701,127 @@ impl<M: ez::Metrics> Server<M> {
     /// Accept a new WebTransport session [h3::Request] from a client.
     ///
     /// Returns [h3::Request] which allows the server to inspect the URL and decide whether to accept or reject the session.
+    /// Handshakes run in the background, so they make progress between calls.
     pub async fn accept(&mut self) -> Option<h3::Request> {
         loop {
             tokio::select! {
                 Some(incoming) = self.inner.accept() => {
-                    self.accept.push(Box::pin(async move {
-                        let conn = incoming.accept().await?;
-                        h3::Request::accept(conn).await
-                    }));
+                    let faults = self.options.faults.clone().map(FaultInjector::new);
+                    let max_field_section_size = self.options.max_field_section_size;
+                    let drop_codes = self.options.drop_codes;
+                    let handshake_timeout = self.options.handshake_timeout;
+                    let session_limits = self.options.session_limits.clone();
+                    let accept_queue = self.options.accept_queue.clone();
+                    let early_buffer = self.options.early_buffer;
+                    let allowed_origins = self.options.allowed_origins.clone();
+                    let http_handler = self.options.http_handler.clone();
+                    let shutdown = self.shutdown.subscribe();
+                    self.accept.spawn(async move {
+                        // Both handshakes share one deadline, so a slow client can't stretch it.
+                        let clock = incoming.clock();
+                        let deadline = handshake_timeout.map(|timeout| clock.now() + timeout);
+
+                        // Dropping the handshake closes the connection.
+                        let conn = match deadline {
+                            Some(deadline) => clock
+                                .timeout_at(deadline, incoming.accept())
+                                .await
+                                .map_err(|_| ServerError::HandshakeTimeout)??,
+                            None => incoming.accept().await?,
+                        };
+                        if let Some(stall) = faults.as_ref().and_then(|faults| faults.control_stall()) {
+                            clock.sleep(stall).await;
+                        }
+
+                        let request = h3::Request::accept_with(
+                            conn,
+                            max_field_section_size,
+                            deadline,
+                            early_buffer,
+                            http_handler.as_ref(),
+                        )
+                        .await?;
+                        if let Some(allowed) = &allowed_origins {
+                            if !request.origin_allowed(allowed) {
+                                request.reject(http::StatusCode::FORBIDDEN).await?;
+                                return Err(ServerError::ForbiddenOrigin);
+                            }
+                        }
+                        let permit = match &session_limits {
+                            Some(limits) => match limits.acquire(&request) {
+                                Some(permit) => Some(permit),
+                                None => {
+                                    request.reject(http::StatusCode::SERVICE_UNAVAILABLE).await?;
+                                    return Err(ServerError::TooManySessions);
+                                }
+                            },
+                            None => None,
+                        };
+                        let slot = match &accept_queue {
+                            Some(queue) => match queue.push() {
+                                Some(slot) => Some(slot),
+                                None => {
+                                    let response = ConnectResponse::new(
+                                        http::StatusCode::SERVICE_UNAVAILABLE,
+                                    )
+                                    .with_retry_after(queue.retry_after());
+                                    request.reject(response).await?;
+                                    return Err(ServerError::AcceptQueueFull);
+                                }
+                            },
+                            None => None,
+                        };
+                        let request = request
+                            .with_faults(faults)
+                            .with_drop_codes(drop_codes)
+                            .with_permit(permit)
+                            .with_shutdown(shutdown);
+                        Ok((request, slot))
+                    });
                 }
-                Some(res) = self.accept.next() => {
+                Some(res) = self.accept.join_next() => {
                     match res {
-                        Ok(session) => return Some(session),
-                        Err(err) => tracing::warn!("ignoring failed handshake: {}", err),
+                        // Leaving the queue once the application has the request.
+                        Ok(Ok((request, _slot))) => {
+                            return Some(request.with_response_timeout(self.options.response_timeout))
+                        }
+                        Ok(Err(err)) => tracing::warn!("ignoring failed handshake: {}", err),
+                        Err(err) => tracing::warn!("handshake task failed: {}", err),
                     }
                 }
-                else => return None,
+                _ = self.inner.closed() => {
+                    // Abandon any handshakes still in progress.
+                    self.accept.abort_all();
+                    return None;
+                }
             }
         }
     }
+
+    /// Stop accepting sessions.
+    ///
+    /// See [ez::Server::close]. Handshakes still in progress are abandoned, and
+    /// [Server::accept] returns `None` from then on. Established sessions are unaffected.
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Start a graceful shutdown, sending an HTTP/3 GOAWAY on every connection.
+    ///
+    /// Stops accepting sessions like [Server::close]. Sessions already accepted keep
+    /// running, and so do requests that haven't been answered yet; the client sees them as
+    /// [draining](crate::Connection::draining), and if the connection is then closed with
+    /// `H3_NO_ERROR`, [closed](crate::Connection::closed) returns
+    /// [SessionError::GoAway](crate::SessionError::GoAway).
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+        self.close();
+    }
+
+    /// Watch for a listener I/O error, which also closes the server.
+    ///
+    /// See [ez::Server::error].
+    pub fn error(&self) -> tokio::sync::watch::Receiver<Option<Arc<io::Error>>> {
+        self.inner.error()
+    }
 }
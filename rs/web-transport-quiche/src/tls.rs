@@ -0,0 +1,124 @@
+//! Helpers for loading certificates and keys into the types the builders accept.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let (chain, key) = web_transport_quiche::tls::load_pem("cert.pem", "key.pem")?;
+//! let server = web_transport_quiche::ServerBuilder::default()
+//!     .with_bind("[::]:4443")?
+//!     .with_single_cert(chain, key)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use rustls_pki_types::{pem::PemObject, PrivatePkcs8KeyDer};
+
+use crate::ez::{CertificateDer, PrivateKeyDer};
+
+/// An error returned when loading a certificate or key.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid PEM: {0}")]
+    Pem(#[from] rustls_pki_types::pem::Error),
+
+    #[error("invalid PKCS#12: {0}")]
+    Pkcs12(#[from] boring::error::ErrorStack),
+
+    #[error("no certificate found")]
+    MissingCertificate,
+
+    #[error("no private key found")]
+    MissingKey,
+}
+
+/// Load every `CERTIFICATE` section from a PEM file, in order.
+pub fn load_certs(path: impl AsRef<Path>) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = CertificateDer::pem_file_iter(path)?.collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(Error::MissingCertificate);
+    }
+
+    Ok(certs)
+}
+
+/// Load a certificate chain and private key from PEM files.
+///
+/// The chain is every `CERTIFICATE` section in `cert_path`, leaf first.
+/// The key is the first PKCS#1, PKCS#8, or SEC1 private key in `key_path`.
+pub fn load_pem(
+    cert_path: impl AsRef<Path>,
+    key_path: impl AsRef<Path>,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), Error> {
+    let chain = load_certs(cert_path)?;
+
+    let key = match PrivateKeyDer::from_pem_file(key_path) {
+        Ok(key) => key,
+        Err(rustls_pki_types::pem::Error::NoItemsFound) => return Err(Error::MissingKey),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok((chain, key))
+}
+
+/// Load a certificate chain and private key from a password-protected PKCS#12 (`.p12`/`.pfx`) file.
+pub fn load_pkcs12(
+    path: impl AsRef<Path>,
+    password: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), Error> {
+    let data = std::fs::read(path)?;
+    let parsed = boring::pkcs12::Pkcs12::from_der(&data)?.parse(password)?;
+
+    let mut chain = vec![CertificateDer::from(parsed.cert.to_der()?)];
+    for cert in parsed.chain.iter().flatten() {
+        chain.push(CertificateDer::from(cert.to_der()?));
+    }
+
+    let key = PrivatePkcs8KeyDer::from(parsed.pkey.private_key_to_der_pkcs8()?);
+
+    Ok((chain, key.into()))
+}
+
+/// Compute the SHA-256 hash of a certificate, as used by the browser's `serverCertificateHashes`.
+///
+/// Pass the hash of the leaf certificate to [ClientBuilder::with_server_certificate_hashes](crate::ClientBuilder::with_server_certificate_hashes).
+pub fn certificate_hash(cert: &CertificateDer<'_>) -> [u8; 32] {
+    boring::sha::sha256(cert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_pem() {
+        let dir = std::env::temp_dir().join(format!("wtq-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let key = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, key.cert.pem()).unwrap();
+        std::fs::write(&key_path, key.signing_key.serialize_pem()).unwrap();
+
+        let (chain, _) = load_pem(&cert_path, &key_path).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].as_ref(), key.cert.der().as_ref());
+
+        // Swapping the files finds neither a certificate nor a key.
+        assert!(matches!(
+            load_pem(&key_path, &key_path),
+            Err(Error::MissingCertificate)
+        ));
+        assert!(matches!(
+            load_pem(&cert_path, &cert_path),
+            Err(Error::MissingKey)
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,3 +1,5 @@
+mod common;
+
 use std::{
     net::{Ipv4Addr, SocketAddr},
     task::Poll,
@@ -6,27 +8,13 @@ use std::{
 
 use anyhow::{Context, Result};
 use futures::poll;
-use rcgen::{CertifiedKey, KeyPair};
-use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use tokio::io::AsyncWriteExt;
 use url::Url;
 use web_transport_quiche::{ClientBuilder, ServerBuilder, Settings};
 
-fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    let CertifiedKey { cert, signing_key } =
-        rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
-            .context("rcgen self-signed")?;
-
-    let cert_der = CertificateDer::from(cert.der().to_vec());
-    let key_bytes = KeyPair::serialize_der(&signing_key);
-    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
-
-    Ok((vec![cert_der], key_der))
-}
-
 #[tokio::test(flavor = "current_thread")]
 async fn flush_and_shutdown_complete_after_returning_pending() -> Result<()> {
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
 
     let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
     let mut server = ServerBuilder::default()
@@ -0,0 +1,78 @@
+//! Any live clone of a connection keeps accepting, whichever handles or callers go away.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::time::timeout;
+
+use common::pair;
+
+// Long enough for a stream to arrive over loopback, short enough to fail a stalled test fast.
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn clone_accepts_after_original_dropped() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let clone = server.clone();
+    let accept = tokio::spawn(async move { clone.accept_uni().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(server);
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, accept).await.context("accept stalled")???;
+    assert_eq!(&recv.read_all(5).await?[..], b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_accept_uni_hands_off() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let clone = server.clone();
+    let accept = tokio::spawn(async move { clone.accept_uni().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The last caller to poll is the one the connection would wake; cancel it.
+    assert!(timeout(Duration::from_millis(50), server.accept_uni())
+        .await
+        .is_err());
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, accept).await.context("accept stalled")???;
+    assert_eq!(&recv.read_all(5).await?[..], b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_accept_bi_hands_off() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let clone = server.clone();
+    let accept = tokio::spawn(async move { clone.accept_bi().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(timeout(Duration::from_millis(50), server.accept_bi())
+        .await
+        .is_err());
+    drop(server);
+
+    let (mut send, _recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let (_send, mut recv) = timeout(WAIT, accept).await.context("accept stalled")???;
+    assert_eq!(&recv.read_all(5).await?[..], b"hello");
+
+    Ok(())
+}
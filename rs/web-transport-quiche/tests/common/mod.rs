@@ -0,0 +1,63 @@
+//! Setup shared by the integration tests.
+
+// Each test binary uses a different subset.
+#![allow(dead_code)]
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::{Context, Result};
+use rcgen::{CertifiedKey, KeyPair};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quiche::{ClientBuilder, Connection, ServerBuilder, Settings};
+
+/// A self-signed certificate for `localhost` and its loopback addresses, and its key.
+///
+/// rustls matches the dialed name against the SANs, so tests can connect by hostname or address.
+pub fn certificate() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let CertifiedKey { cert, signing_key } = rcgen::generate_simple_self_signed(vec![
+        "localhost".into(),
+        "127.0.0.1".into(),
+        "::1".into(),
+    ])
+    .context("rcgen self-signed")?;
+
+    let chain = vec![CertificateDer::from(cert.der().to_vec())];
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(KeyPair::serialize_der(
+        &signing_key,
+    )));
+
+    Ok((chain, key))
+}
+
+/// Connect to a server, returning both ends of the session.
+pub async fn pair() -> Result<(Connection, Connection)> {
+    let (chain, key) = certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+
+    let accept = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok().await.context("accept")
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+    let client = ClientBuilder::default()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect(url)
+        .await?
+        .established()
+        .await
+        .context("connect")?;
+    let server = accept.await??;
+
+    Ok((client, server))
+}
@@ -4,29 +4,14 @@
 //! straight to a resolver fails. Hostname URLs never exercise that path, which
 //! is why it went unnoticed.
 
+mod common;
+
 use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use anyhow::{Context, Result};
-use rcgen::{CertifiedKey, KeyPair};
-use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use url::Url;
 use web_transport_quiche::{ClientBuilder, ServerBuilder, Settings};
 
-fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    let CertifiedKey { cert, signing_key } = rcgen::generate_simple_self_signed(vec![
-        "localhost".into(),
-        "::1".into(),
-        "127.0.0.1".into(),
-    ])
-    .context("rcgen self-signed")?;
-
-    let cert_der = CertificateDer::from(cert.der().to_vec());
-    let key_bytes = KeyPair::serialize_der(&signing_key);
-    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
-
-    Ok((vec![cert_der], key_der))
-}
-
 fn init_tracing() {
     let _ = tracing_subscriber::fmt()
         .with_env_filter(
@@ -38,7 +23,7 @@ fn init_tracing() {
 }
 
 async fn spawn_server(bind: SocketAddr) -> Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
 
     let mut server = ServerBuilder::default()
         .with_bind(bind)?
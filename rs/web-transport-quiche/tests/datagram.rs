@@ -3,6 +3,8 @@
 //! `ez::Driver` event loop <-> `ez::Connection` flume channels <->
 //! `web_transport_quiche::Connection` header framing <-> WT `Session` trait.
 
+mod common;
+
 use std::{
     net::{Ipv4Addr, SocketAddr},
     time::Duration,
@@ -10,25 +12,8 @@ use std::{
 
 use anyhow::{bail, Context, Result};
 use bytes::Bytes;
-use rcgen::{CertifiedKey, KeyPair};
-use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use url::Url;
-use web_transport_quiche::{ClientBuilder, ServerBuilder, Settings};
-
-fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    // SANs cover both hostname and loopback literal — rustls refuses to verify
-    // "localhost" against a cert with only a 127.0.0.1 SAN and vice versa, even
-    // with verify_peer off on the client we still want a real usable cert.
-    let CertifiedKey { cert, signing_key } =
-        rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
-            .context("rcgen self-signed")?;
-
-    let cert_der = CertificateDer::from(cert.der().to_vec());
-    let key_bytes = KeyPair::serialize_der(&signing_key);
-    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
-
-    Ok((vec![cert_der], key_der))
-}
+use web_transport_quiche::{ClientBuilder, DatagramOptions, ServerBuilder, Settings};
 
 fn dgram_settings() -> Settings {
     // tokio-quiche defaults already enable datagrams with a 65536-entry queue,
@@ -51,7 +36,7 @@ async fn datagram_round_trip() -> Result<()> {
         .with_test_writer()
         .try_init();
 
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
 
     let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
     let mut server = ServerBuilder::default()
@@ -101,6 +86,9 @@ async fn datagram_round_trip() -> Result<()> {
         .await
         .context("client handshake")?;
 
+    // Both ends enable datagrams in their SETTINGS and transport parameters.
+    assert!(session.datagrams_supported());
+
     let payloads: [&[u8]; 3] = [b"hello", b"quic-datagrams", b"round-trip"];
 
     for p in payloads {
@@ -149,7 +137,7 @@ async fn datagram_send_drops_when_channel_full() -> Result<()> {
         .with_test_writer()
         .try_init();
 
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
 
     let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
     let mut server = ServerBuilder::default()
@@ -223,3 +211,124 @@ async fn datagram_send_drops_when_channel_full() -> Result<()> {
 
     Ok(())
 }
+
+/// With a tiny send buffer, `send_datagram_wait` holds the sender back instead of
+/// dropping: every datagram makes it across loopback, in order.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn datagram_send_wait_applies_backpressure() -> Result<()> {
+    const COUNT: u32 = 200;
+
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_settings(dgram_settings())
+        .with_single_cert(chain, key)?;
+
+    let server_addr = *server
+        .local_addrs()
+        .first()
+        .context("server has no local address")?;
+
+    let server_task = tokio::spawn(async move {
+        let request = server.accept().await.context("server accept")?;
+        let session = request.ok().await.context("server session")?;
+
+        let mut received = Vec::new();
+        while received.len() < COUNT as usize {
+            let data = session.read_datagram().await.context("server recv")?;
+            received.push(u32::from_be_bytes(data[..].try_into()?));
+        }
+        anyhow::Ok(received)
+    });
+
+    let mut client_settings = dgram_settings();
+    client_settings.verify_peer = false;
+
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", server_addr.port()))?;
+    let client = ClientBuilder::default()
+        .with_settings(client_settings)
+        .with_datagram_send_buffer(4)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?;
+
+    let session = client
+        .connect(url)
+        .await?
+        .established()
+        .await
+        .context("client handshake")?;
+
+    for i in 0..COUNT {
+        session
+            .send_datagram_wait(Bytes::copy_from_slice(&i.to_be_bytes()))
+            .await?;
+    }
+
+    let received = tokio::time::timeout(Duration::from_secs(5), server_task)
+        .await
+        .context("server didn't receive every datagram")?
+        .context("server task panicked")??;
+    assert_eq!(received, (0..COUNT).collect::<Vec<_>>());
+
+    session.close(0, "bye");
+    session.closed().await;
+
+    Ok(())
+}
+
+/// A datagram still queued past its max age is dropped rather than sent late.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn datagram_max_age_drops_expired() -> Result<()> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_settings(dgram_settings())
+        .with_single_cert(chain, key)?;
+
+    let server_addr = *server
+        .local_addrs()
+        .first()
+        .context("server has no local address")?;
+
+    let server_task = tokio::spawn(async move {
+        let request = server.accept().await.context("server accept")?;
+        let session = request.ok().await.context("server session")?;
+        session.read_datagram().await.context("server recv")
+    });
+
+    let mut client_settings = dgram_settings();
+    client_settings.verify_peer = false;
+
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", server_addr.port()))?;
+    let client = ClientBuilder::default()
+        .with_settings(client_settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?;
+
+    let session = client
+        .connect(url)
+        .await?
+        .established()
+        .await
+        .context("client handshake")?;
+
+    // Expired before the driver can possibly pick them up.
+    let expired = DatagramOptions::default().with_max_age(Duration::ZERO);
+    for _ in 0..10 {
+        session.send_datagram_with(Bytes::from_static(b"stale"), expired)?;
+    }
+    session.send_datagram_with(Bytes::from_static(b"fresh"), DatagramOptions::default())?;
+
+    let received = tokio::time::timeout(Duration::from_secs(5), server_task)
+        .await
+        .context("server didn't receive a datagram")?
+        .context("server task panicked")??;
+    assert_eq!(received, Bytes::from_static(b"fresh"));
+
+    session.close(0, "bye");
+    session.closed().await;
+
+    Ok(())
+}
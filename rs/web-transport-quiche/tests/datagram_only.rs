@@ -0,0 +1,80 @@
+//! A datagram-only session resets the streams its peer opens and refuses to open its own.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{
+    proto::{codes, SessionMode},
+    ClientBuilder, Connection, ServerBuilder, SessionError, Settings, StreamError,
+};
+
+// The server accepts the session as datagram-only, the client with streams.
+async fn pair() -> Result<(Connection, Connection)> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let request = request.with_session_mode(SessionMode::DatagramOnly);
+        request.ok().await.context("accept")
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let client = ClientBuilder::default()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect(url)
+        .await?
+        .established()
+        .await?;
+    let server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    Ok((client, server))
+}
+
+#[tokio::test]
+async fn peer_streams_are_reset() -> Result<()> {
+    let (client, server) = pair().await?;
+    assert_eq!(server.mode(), SessionMode::DatagramOnly);
+
+    let (mut send, mut recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+
+    let err = tokio::time::timeout(Duration::from_secs(5), recv.read_all(1024))
+        .await?
+        .err()
+        .context("read to the end")?;
+    assert!(
+        matches!(err, StreamError::Reset(code) if code == codes::STREAMS_DISABLED),
+        "expected the streams disabled code, got {err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn local_streams_are_refused() -> Result<()> {
+    let (_client, server) = pair().await?;
+
+    let err = server.open_bi().await.map(drop).unwrap_err();
+    assert!(matches!(err, SessionError::StreamsDisabled), "got {err:?}");
+
+    let err = server.accept_uni().await.map(drop).unwrap_err();
+    assert!(matches!(err, SessionError::StreamsDisabled), "got {err:?}");
+
+    Ok(())
+}
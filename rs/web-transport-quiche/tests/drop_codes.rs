@@ -0,0 +1,95 @@
+//! Streams dropped without being closed use the shared drop codes.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{
+    proto::codes::{self, DropCodes},
+    ClientBuilder, Connection, ServerBuilder, Settings, StreamError,
+};
+
+async fn pair(codes: DropCodes) -> Result<(Connection, Connection)> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_drop_codes(codes)
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok().await.context("accept")
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let client = ClientBuilder::default()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect(url)
+        .await?
+        .established()
+        .await?;
+    let server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    Ok((client, server))
+}
+
+#[tokio::test]
+async fn dropped_send_stream() -> Result<()> {
+    let (client, server) = pair(DropCodes::default()).await?;
+
+    // The client writes first so the server learns about the stream.
+    let (mut send, mut recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+
+    let (dropped, _recv) = server.accept_bi().await?;
+    drop(dropped);
+
+    let err = tokio::time::timeout(Duration::from_secs(5), recv.read_all(1024))
+        .await?
+        .err()
+        .context("read to the end")?;
+    assert!(
+        matches!(err, StreamError::Reset(code) if code == codes::SEND_DROPPED),
+        "expected the send drop code, got {err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_drop_codes() -> Result<()> {
+    let codes = DropCodes {
+        recv: 7,
+        ..Default::default()
+    };
+    let (client, server) = pair(codes).await?;
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+
+    let recv = server.accept_uni().await?;
+    drop(recv);
+
+    let err = tokio::time::timeout(Duration::from_secs(5), send.closed())
+        .await?
+        .err()
+        .context("closed without STOP_SENDING")?;
+    assert!(
+        matches!(err, StreamError::Stop(7)),
+        "expected the custom drop code, got {err:?}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Reset and stop codes cover the full `u32` range, and reach the peer as they were sent.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::timeout;
+
+use common::pair;
+
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn full_range_codes() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    // Either side of the first reserved HTTP/3 codepoint, and the largest code.
+    for code in [0, 0x1d, 0x1e, u32::MAX] {
+        let (mut send, mut recv) = client.open_bi().await?;
+        send.write_all(b"ping").await?;
+
+        let (mut reply, mut request) = timeout(WAIT, server.accept_bi()).await??;
+        reply.reset(code);
+        request.stop(code);
+
+        let reset = timeout(WAIT, recv.received_reset()).await??;
+        assert_eq!(reset, Some(code));
+        let stopped = timeout(WAIT, send.stopped()).await??;
+        assert_eq!(stopped, Some(code));
+
+        // Once they've arrived, the codes can be read without waiting.
+        assert_eq!(recv.reset_code(), Some(code));
+        assert_eq!(send.stop_code(), Some(code));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn finished_streams_have_no_code() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let (mut send, mut recv) = client.open_bi().await?;
+    send.write_all(b"ping").await?;
+    send.finish()?;
+
+    let (mut reply, mut request) = timeout(WAIT, server.accept_bi()).await??;
+    assert_eq!(&request.read_all(4).await?[..], b"ping");
+    reply.finish()?;
+
+    assert_eq!(timeout(WAIT, recv.received_reset()).await??, None);
+    assert_eq!(timeout(WAIT, send.stopped()).await??, None);
+    assert_eq!(recv.reset_code(), None);
+    assert_eq!(send.stop_code(), None);
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! The server rejects CONNECT requests with headers beyond its advertised limit.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{
+    h3, proto::ConnectRequest, ClientBuilder, ClientError, ServerBuilder, Settings,
+};
+
+fn client() -> Result<ClientBuilder> {
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    Ok(ClientBuilder::default()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?)
+}
+
+#[tokio::test]
+async fn oversized_headers_are_rejected() -> Result<()> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_max_field_section_size(1024)
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok().await.context("accept")
+    });
+
+    let request = ConnectRequest::new(url.clone()).with_header(
+        http::HeaderName::from_static("x-padding"),
+        http::HeaderValue::from_str(&"a".repeat(2048))?,
+    );
+    let err = client()?
+        .connect(request)
+        .await?
+        .established()
+        .await
+        .err()
+        .context("connected")?;
+    assert!(
+        matches!(
+            err,
+            ClientError::Connect(h3::ConnectError::Rejected { status, .. })
+                if status == http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        ),
+        "expected 431, got {err:?}"
+    );
+
+    // A request under the limit is still accepted.
+    let _client = client()?.connect(url).await?.established().await?;
+    let _server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    Ok(())
+}
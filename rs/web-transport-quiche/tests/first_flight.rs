@@ -0,0 +1,49 @@
+//! Opening a stream with its first payload, queued together with the stream header.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::Bytes;
+use tokio::time::timeout;
+
+use common::pair;
+
+// Long enough for a stream to arrive over loopback, short enough to fail a stalled test fast.
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn bi_carries_its_first_flight() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let (mut send, mut recv) = client
+        .open_bi_with_data(Bytes::from_static(b"ping"))
+        .await?;
+    send.finish()?;
+
+    let (mut reply, mut request) = timeout(WAIT, server.accept_bi()).await??;
+    assert_eq!(&request.read_all(4).await?[..], b"ping");
+
+    reply.write_all(b"pong").await?;
+    reply.finish()?;
+    assert_eq!(&recv.read_all(4).await?[..], b"pong");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn uni_carries_its_first_flight() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let mut send = client
+        .open_uni_with_data(Bytes::from_static(b"hello"))
+        .await?;
+    send.write_all(b" world").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, server.accept_uni()).await??;
+    assert_eq!(&recv.read_all(11).await?[..], b"hello world");
+
+    Ok(())
+}
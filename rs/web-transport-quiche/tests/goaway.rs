@@ -0,0 +1,61 @@
+//! Shutting the server down sends GOAWAY, which drains the client's session.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{ClientBuilder, ServerBuilder, Settings};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn shutdown_sends_goaway() -> Result<()> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let connect = async {
+        ClientBuilder::default()
+            .with_settings(settings)
+            .with_bind((Ipv4Addr::LOCALHOST, 0))?
+            .connect(url)
+            .await?
+            .established()
+            .await
+            .map_err(anyhow::Error::from)
+    };
+    let (client, request) = tokio::join!(connect, server.accept());
+    let client = client?;
+    let session = request.context("no request")?.ok().await?;
+    assert!(!client.is_draining());
+
+    server.shutdown();
+    assert!(server.accept().await.is_none(), "accepted after shutdown");
+
+    tokio::time::timeout(TIMEOUT, client.draining())
+        .await
+        .context("no GOAWAY")?;
+    assert!(client.is_draining());
+
+    // The session still works after GOAWAY.
+    let mut send = session.open_uni().await?;
+    send.write_all(b"bye").await?;
+    send.finish()?;
+    let mut recv = client.accept_uni().await?;
+    assert_eq!(recv.read_all(3).await?, b"bye".as_slice());
+
+    Ok(())
+}
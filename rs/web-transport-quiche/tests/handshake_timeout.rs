@@ -0,0 +1,114 @@
+//! A client that stalls after the QUIC handshake is disconnected instead of pinning the server,
+//! and a client gives up on a server that never answers.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{ez, proto, ClientBuilder, ClientError, ServerBuilder, Settings};
+
+// Connect with a raw QUIC client, send nothing (or only SETTINGS), and return the close code.
+async fn stalled_close_code(send_settings: bool) -> Result<u64> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_handshake_timeout(Duration::from_millis(100))
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    tokio::spawn(async move { server.accept().await.map(|_| ()) });
+
+    let mut settings = ez::Settings::default();
+    settings.verify_peer = false;
+    settings.alpn = vec![web_transport_quiche::ALPN.as_bytes().to_vec()];
+
+    let conn = ez::ClientBuilder::new()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect("localhost", addr.port())
+        .await?
+        .established()
+        .await?;
+
+    // Keep the control stream open, as a real client would.
+    let mut _control = None;
+    if send_settings {
+        let mut settings = proto::Settings::default();
+        settings.enable_webtransport(1);
+
+        let mut buf = Vec::new();
+        settings.encode(&mut buf);
+
+        let mut send = conn.open_uni().await?;
+        send.write_all(&buf).await?;
+        _control = Some(send);
+    }
+
+    let err = tokio::time::timeout(Duration::from_secs(5), conn.closed()).await?;
+    match err {
+        ez::ConnectionError::Remote(code, _) => Ok(code),
+        err => anyhow::bail!("expected a remote close, got {err:?}"),
+    }
+}
+
+#[tokio::test]
+async fn missing_settings() -> Result<()> {
+    let code = stalled_close_code(false).await?;
+    assert_eq!(code, proto::codes::h3::SETTINGS_ERROR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn missing_connect() -> Result<()> {
+    let code = stalled_close_code(true).await?;
+    assert_eq!(code, proto::codes::h3::REQUEST_REJECTED);
+    Ok(())
+}
+
+#[tokio::test]
+async fn client_gives_up_on_an_unanswered_connect() -> Result<()> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+
+    // Take the request but never respond to it.
+    tokio::spawn(async move {
+        let _request = server.accept().await;
+        std::future::pending::<()>().await
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+    let connect = async {
+        ClientBuilder::default()
+            .with_settings(settings)
+            .with_handshake_timeout(Duration::from_millis(200))
+            .with_bind((Ipv4Addr::LOCALHOST, 0))?
+            .connect(url)
+            .await?
+            .established()
+            .await
+    };
+    let Err(err) = tokio::time::timeout(Duration::from_secs(5), connect).await? else {
+        anyhow::bail!("connected without a response");
+    };
+    assert!(
+        matches!(err, ClientError::Connection(ez::ConnectionError::TimedOut)),
+        "{err:?}"
+    );
+    assert!(err.is_retryable());
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Incoming streams must send their header within the session's budget, or be stopped.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{
+    proto::{codes, HeaderBudget, StreamUni},
+    ClientBuilder, Connection, SendStream, ServerBuilder, Settings, StreamError,
+};
+
+const WAIT: Duration = Duration::from_secs(5);
+
+async fn pair(budget: HeaderBudget) -> Result<(Connection, Connection)> {
+    let (chain, key) = common::certificate()?;
+
+    let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
+    let mut server = ServerBuilder::default()
+        .with_bind(bind)?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let session = request.ok().await.context("accept")?;
+        session.set_header_budget(budget);
+        anyhow::Ok(session)
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let client = ClientBuilder::default()
+        .with_settings(settings)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect(url)
+        .await?
+        .established()
+        .await?;
+    let server = tokio::time::timeout(WAIT, accepted).await???;
+
+    Ok((client, server))
+}
+
+/// Wait for the server to stop `send`, returning the code it used.
+async fn stopped(send: &mut SendStream) -> Result<u64> {
+    let err = tokio::time::timeout(WAIT, send.closed())
+        .await
+        .context("never stopped")?
+        .err()
+        .context("closed without an error")?;
+    match err {
+        StreamError::InvalidStop(code) => Ok(code),
+        err => anyhow::bail!("expected an HTTP/3 STOP_SENDING, got {err:?}"),
+    }
+}
+
+#[tokio::test]
+async fn stalled_header_is_stopped() -> Result<()> {
+    let budget = HeaderBudget {
+        timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let (client, server) = pair(budget).await?;
+
+    // Headers are only decoded while someone is accepting.
+    let accept = tokio::spawn({
+        let server = server.clone();
+        async move { server.accept_uni().await }
+    });
+
+    // The WebTransport stream type, but never the session ID that should follow it.
+    let mut send = client.open_raw_uni(StreamUni::WEBTRANSPORT).await?;
+    assert_eq!(stopped(&mut send).await?, codes::h3::GENERAL_PROTOCOL_ERROR);
+
+    // Streams with a whole header are unaffected.
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hi").await?;
+    send.finish()?;
+    let mut recv = tokio::time::timeout(WAIT, accept).await???;
+    assert_eq!(recv.read_all(2).await?, b"hi".as_slice());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oversized_header_is_stopped() -> Result<()> {
+    let budget = HeaderBudget {
+        max_bytes: 1,
+        timeout: None,
+    };
+    let (client, server) = pair(budget).await?;
+    tokio::spawn(async move { server.accept_uni().await });
+
+    // The stream type alone takes two bytes.
+    let mut send = client.open_uni().await?;
+    assert_eq!(stopped(&mut send).await?, codes::h3::GENERAL_PROTOCOL_ERROR);
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Listing a session's open streams, without the HTTP/3 streams underneath it.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::time::timeout;
+
+use common::pair;
+
+// Long enough for a stream to arrive over loopback, short enough to fail a stalled test fast.
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn lists_only_webtransport_streams() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    // Just the CONNECT and control streams so far, none of which are listed.
+    assert!(client.open_streams().await?.is_empty());
+    assert!(server.open_streams().await?.is_empty());
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hi").await?;
+
+    let streams = client.open_streams().await?;
+    assert_eq!(streams.len(), 1, "{streams:?}");
+    let ours = &streams[0];
+    assert!(ours.id.is_uni() && ours.id.is_client(), "{ours:?}");
+    let half = ours.send.context("no send half")?;
+    assert!(!half.fin && half.reset.is_none(), "{half:?}");
+    assert!(ours.recv.is_none());
+
+    let mut recv = timeout(WAIT, server.accept_uni()).await??;
+
+    let streams = server.open_streams().await?;
+    assert_eq!(streams.len(), 1, "{streams:?}");
+    assert_eq!(streams[0].id, ours.id);
+    assert!(streams[0].send.is_none());
+    assert!(streams[0].recv.is_some());
+
+    // Once the stream is finished and read, the server forgets it.
+    send.finish()?;
+    assert_eq!(&recv.read_all(2).await?[..], b"hi");
+    timeout(WAIT, async {
+        while !server.open_streams().await?.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        anyhow::Ok(())
+    })
+    .await
+    .context("stream still listed")??;
+
+    Ok(())
+}
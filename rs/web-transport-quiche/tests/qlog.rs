@@ -1,6 +1,12 @@
-//! `Settings` must keep tokio-quiche's qlog knobs reachable, compression included.
+//! qlog traces can be enabled through `Settings` or the builders' `with_qlog_dir`.
 
-use web_transport_quiche::{QlogCompression, Settings};
+mod common;
+
+use std::{net::Ipv4Addr, time::Duration};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{ClientBuilder, QlogCompression, ServerBuilder, Settings};
 
 #[test]
 fn settings_expose_qlog() {
@@ -11,3 +17,50 @@ fn settings_expose_qlog() {
     assert_eq!(settings.qlog_dir.as_deref(), Some("/tmp/qlog"));
     assert_eq!(settings.qlog_compression, QlogCompression::Gzip);
 }
+
+#[tokio::test]
+async fn builders_write_qlog_files() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("web-transport-qlog-{}", std::process::id()));
+    let server_dir = dir.join("server");
+    let client_dir = dir.join("client");
+    std::fs::create_dir_all(&server_dir)?;
+    std::fs::create_dir_all(&client_dir)?;
+
+    let (chain, key) = common::certificate()?;
+
+    let mut server = ServerBuilder::default()
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .with_qlog_dir(&server_dir)
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://127.0.0.1:{}/", addr.port()))?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok().await.context("accept")
+    });
+
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    let client = ClientBuilder::default()
+        .with_settings(settings)
+        .with_qlog_dir(&client_dir)
+        .with_bind((Ipv4Addr::LOCALHOST, 0))?
+        .connect(url)
+        .await?
+        .established()
+        .await?;
+    let server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    // Each side starts its trace when the connection does.
+    let server_files = std::fs::read_dir(&server_dir)?.count();
+    let client_files = std::fs::read_dir(&client_dir)?.count();
+    drop((client, server));
+    std::fs::remove_dir_all(&dir)?;
+
+    assert_eq!(server_files, 1, "expected one server qlog file");
+    assert_eq!(client_files, 1, "expected one client qlog file");
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! A server can point its clients at another URL before draining them.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::timeout;
+use url::Url;
+
+use common::pair;
+
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn hint_reaches_client() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let target = Url::parse("https://relay.example.com:4443/moq")?;
+    server.send_reconnect_hint(target.clone()).await?;
+    server.drain().await?;
+
+    assert_eq!(timeout(WAIT, client.reconnect_hint()).await?, Some(target));
+    timeout(WAIT, client.draining()).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn latest_hint_wins() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let first = Url::parse("https://a.example.com/")?;
+    let second = Url::parse("https://b.example.com/")?;
+    server.send_reconnect_hint(first).await?;
+    server.send_reconnect_hint(second.clone()).await?;
+    server.drain().await?;
+
+    // The drain capsule follows both hints, so both have been read once it arrives.
+    timeout(WAIT, client.draining()).await?;
+    assert_eq!(client.reconnect_hint().await, Some(second));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_hint_on_close() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    server.close(0, "");
+    assert_eq!(timeout(WAIT, client.reconnect_hint()).await?, None);
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! A server can send clients elsewhere with a 3xx, and clients follow only when asked to.
+
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{h3, ClientBuilder, ClientError, ServerBuilder, Settings};
+
+/// Spawn a server that hands each request to `handle`, returning its URL.
+async fn spawn_server<F, Fut>(handle: F) -> Result<(Url, tokio::task::JoinHandle<()>)>
+where
+    F: Fn(h3::Request) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let (chain, key) = common::certificate()?;
+
+    let mut server = ServerBuilder::default()
+        .with_bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?
+        .with_single_cert(chain, key)?;
+
+    let addr = *server
+        .local_addrs()
+        .first()
+        .context("server has no local address")?;
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+
+    let task = tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            tokio::spawn(handle(request));
+        }
+    });
+
+    Ok((url, task))
+}
+
+/// The blue server redirects every session to green, which accepts it.
+async fn blue_green() -> Result<(Url, Url, [tokio::task::JoinHandle<()>; 2])> {
+    let (green, green_task) = spawn_server(|request| async move {
+        if let Ok(session) = request.ok().await {
+            session.closed().await;
+        }
+    })
+    .await?;
+
+    let location = green.clone();
+    let (blue, blue_task) = spawn_server(move |request| {
+        let location = location.clone();
+        async move {
+            request.redirect(location).await.ok();
+        }
+    })
+    .await?;
+
+    Ok((blue, green, [blue_task, green_task]))
+}
+
+fn client() -> ClientBuilder {
+    // The certificate is self-signed and the subject here is the redirect.
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    ClientBuilder::default().with_settings(settings)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn follows_redirect() -> Result<()> {
+    let (blue, green, tasks) = blue_green().await?;
+
+    let session = client()
+        .with_follow_redirects(1)
+        .connect(blue)
+        .await?
+        .established()
+        .await
+        .context("the redirect should have been followed")?;
+    assert_eq!(session.request().url, green);
+
+    session.close(0, "bye");
+    session.closed().await;
+    tasks.iter().for_each(|task| task.abort());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn redirect_is_an_error_by_default() -> Result<()> {
+    let (blue, green, tasks) = blue_green().await?;
+
+    let err = client()
+        .connect(blue)
+        .await?
+        .established()
+        .await
+        .unwrap_err();
+
+    match err {
+        ClientError::Connect(h3::ConnectError::Redirect { status, location }) => {
+            assert_eq!(status, http::StatusCode::FOUND);
+            assert_eq!(location, green);
+        }
+        err => panic!("expected a redirect, got {err:?}"),
+    }
+
+    tasks.iter().for_each(|task| task.abort());
+    Ok(())
+}
@@ -8,26 +8,14 @@
 //! connection error, killing every other stream on the session. This is exactly
 //! the lite-05 TRACK stream pattern that broke moq-native's quiche backend tests.
 
+mod common;
+
 use std::net::{Ipv4Addr, SocketAddr};
 
 use anyhow::{Context, Result};
-use rcgen::{CertifiedKey, KeyPair};
-use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use url::Url;
 use web_transport_quiche::{ClientBuilder, ServerBuilder, Settings};
 
-fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    let CertifiedKey { cert, signing_key } =
-        rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
-            .context("rcgen self-signed")?;
-
-    let cert_der = CertificateDer::from(cert.der().to_vec());
-    let key_bytes = KeyPair::serialize_der(&signing_key);
-    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
-
-    Ok((vec![cert_der], key_der))
-}
-
 // Current-thread runtime on purpose: it makes the FIN-vs-`stream_shutdown`
 // interleaving deterministic, which is what surfaces the bug. A multi-threaded
 // runtime schedules around the race and hides the regression.
@@ -41,7 +29,7 @@ async fn reset_stream_keeps_connection_alive() -> Result<()> {
         .with_test_writer()
         .try_init();
 
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
 
     let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
     let mut server = ServerBuilder::default()
@@ -0,0 +1,107 @@
+//! Clients can retry a connection the server refused for now, and only that.
+
+mod common;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quiche::{
+    h3, proto::RetryPolicy, ClientBuilder, ClientError, ServerBuilder, Settings,
+};
+
+const POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    initial_backoff: Duration::from_millis(10),
+    max_backoff: Duration::from_millis(50),
+    jitter: 0.5,
+};
+
+/// A server that answers the first `refusals` requests with `status`, and accepts the rest.
+///
+/// Returns its URL and the number of requests it has seen.
+fn server(
+    status: http::StatusCode,
+    refusals: usize,
+) -> Result<(Url, Arc<AtomicUsize>, tokio::task::JoinHandle<()>)> {
+    let (chain, key) = common::certificate()?;
+
+    let mut server = ServerBuilder::default()
+        .with_bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?
+        .with_single_cert(chain, key)?;
+    let addr = *server.local_addrs().first().context("no local address")?;
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counter = seen.clone();
+    let task = tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if counter.fetch_add(1, Ordering::SeqCst) < refusals {
+                request.reject(status).await.ok();
+            } else if let Ok(session) = request.ok().await {
+                tokio::spawn(async move { session.closed().await });
+            }
+        }
+    });
+
+    Ok((url, seen, task))
+}
+
+fn client() -> ClientBuilder {
+    let mut settings = Settings::default();
+    settings.verify_peer = false;
+
+    ClientBuilder::default().with_settings(settings)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn retries_until_the_server_has_room() -> Result<()> {
+    let (url, seen, task) = server(http::StatusCode::SERVICE_UNAVAILABLE, 2)?;
+
+    let session = client()
+        .connect_with_retry(url, POLICY)
+        .await
+        .context("the third attempt should have been accepted")?;
+    assert_eq!(seen.load(Ordering::SeqCst), 3);
+
+    session.close(0, "bye");
+    session.closed().await;
+    task.abort();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn gives_up_after_max_attempts() -> Result<()> {
+    let (url, seen, task) = server(http::StatusCode::SERVICE_UNAVAILABLE, usize::MAX)?;
+
+    let err = client().connect_with_retry(url, POLICY).await.unwrap_err();
+    assert!(err.is_retryable(), "{err:?}");
+    assert_eq!(seen.load(Ordering::SeqCst), 3);
+
+    task.abort();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn does_not_retry_a_refusal_that_would_repeat() -> Result<()> {
+    let (url, seen, task) = server(http::StatusCode::FORBIDDEN, usize::MAX)?;
+
+    let err = client().connect_with_retry(url, POLICY).await.unwrap_err();
+    match err {
+        ClientError::Connect(h3::ConnectError::Rejected { status, .. }) => {
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+        }
+        err => panic!("expected a 403, got {err:?}"),
+    }
+    assert_eq!(seen.load(Ordering::SeqCst), 1);
+
+    task.abort();
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use static_assertions::assert_impl_all;
+use web_transport_quiche::{
+    h3, Connection, RecvStream, SendStream, SessionDriver, SessionError, StreamError,
+};
+
+assert_impl_all!(Connection: web_transport_trait::Session, Send, Sync, Clone);
+assert_impl_all!(SendStream: web_transport_trait::SendStream, Send);
+assert_impl_all!(RecvStream: web_transport_trait::RecvStream, Send);
+assert_impl_all!(SessionError: web_transport_trait::Error, Send, Sync);
+assert_impl_all!(StreamError: web_transport_trait::Error, Send, Sync);
+
+// A request can be queued and answered from another task.
+assert_impl_all!(h3::Request: Send);
+
+// A driver can be run on any executor, including a multi-threaded one.
+assert_impl_all!(SessionDriver: std::future::Future, Send);
@@ -0,0 +1,33 @@
+//! How many more streams a session can open before it has to wait for the peer.
+
+mod common;
+
+use anyhow::Result;
+
+use common::pair;
+
+#[tokio::test]
+async fn opening_a_stream_spends_credit() -> Result<()> {
+    let (client, _server) = pair().await?;
+
+    let (max_bi, remaining_bi) = (client.max_open_bi(), client.remaining_bi());
+    let (max_uni, remaining_uni) = (client.max_open_uni(), client.remaining_uni());
+    assert!(remaining_bi > 0 && remaining_uni > 0);
+
+    let (mut send, _recv) = client.open_bi().await?;
+    send.write_all(b"hi").await?;
+    assert_eq!(client.remaining_bi(), remaining_bi - 1);
+    assert_eq!(client.max_open_bi(), max_bi);
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hi").await?;
+    assert_eq!(client.remaining_uni(), remaining_uni - 1);
+    assert_eq!(client.max_open_uni(), max_uni);
+
+    // The trait reports the same credit.
+    use web_transport_trait::Session;
+    assert_eq!(Session::remaining_bi(&client), Some(remaining_bi - 1));
+    assert_eq!(Session::max_open_uni(&client), Some(max_uni));
+
+    Ok(())
+}
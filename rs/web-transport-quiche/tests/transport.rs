@@ -4,14 +4,14 @@
 //! connection *without* it must actually die of the idle timeout, otherwise the
 //! positive test would pass no matter what the driver does.
 
+mod common;
+
 use std::{
     net::{Ipv4Addr, SocketAddr},
     time::Duration,
 };
 
 use anyhow::{Context, Result};
-use rcgen::{CertifiedKey, KeyPair};
-use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use url::Url;
 use web_transport_quiche::{ClientBuilder, ServerBuilder, Settings};
 
@@ -23,18 +23,6 @@ const KEEP_ALIVE: Duration = Duration::from_millis(200);
 /// Long enough that an un-kept connection is certainly gone.
 const IDLE_WAIT: Duration = Duration::from_secs(3);
 
-fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    let CertifiedKey { cert, signing_key } =
-        rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
-            .context("rcgen self-signed")?;
-
-    let cert_der = CertificateDer::from(cert.der().to_vec());
-    let key_bytes = KeyPair::serialize_der(&signing_key);
-    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
-
-    Ok((vec![cert_der], key_der))
-}
-
 fn init_tracing() {
     let _ = tracing_subscriber::fmt()
         .with_env_filter(
@@ -54,7 +42,7 @@ fn idle_settings() -> Settings {
 /// Spawn a server that accepts one session and holds it open without sending
 /// anything, so the only traffic on the wire is whatever keep-alive produces.
 async fn spawn_server(gso: bool) -> Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
 
     let bind: SocketAddr = (Ipv4Addr::LOCALHOST, 0).into();
     let mut server = ServerBuilder::default()
@@ -4,6 +4,8 @@
 //! The security-critical assertion is the negative one: a wrong hash must
 //! *fail* the handshake, not silently connect.
 
+mod common;
+
 use std::{
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
     time::Duration,
@@ -11,32 +13,32 @@ use std::{
 
 use anyhow::{Context, Result};
 use rcgen::{
-    BasicConstraints, CertificateParams, CertifiedKey, DnType, ExtendedKeyUsagePurpose, IsCa,
-    Issuer, KeyPair, KeyUsagePurpose,
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair,
+    KeyUsagePurpose,
 };
 use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use sha2::{Digest, Sha256};
 use url::Url;
 use web_transport_quiche::{ClientBuilder, ServerBuilder};
 
-fn make_self_signed() -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
-    let CertifiedKey { cert, signing_key } =
-        rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
-            .context("rcgen self-signed")?;
-
-    let cert_der = CertificateDer::from(cert.der().to_vec());
-    let key_bytes = KeyPair::serialize_der(&signing_key);
-    let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_bytes));
-
-    Ok((vec![cert_der], key_der))
-}
-
 /// A CA certificate plus a leaf signed by it. Returns `(ca_root, leaf_chain, leaf_key)`.
 #[allow(clippy::type_complexity)]
 fn make_ca_chain() -> Result<(
     CertificateDer<'static>,
     Vec<CertificateDer<'static>>,
     PrivateKeyDer<'static>,
+)> {
+    make_ca_chain_for(vec!["localhost".into(), "127.0.0.1".into(), "::1".into()])
+}
+
+/// Like [make_ca_chain], with a leaf valid for only the given SANs.
+#[allow(clippy::type_complexity)]
+fn make_ca_chain_for(
+    names: Vec<String>,
+) -> Result<(
+    CertificateDer<'static>,
+    Vec<CertificateDer<'static>>,
+    PrivateKeyDer<'static>,
 )> {
     let ca_key = KeyPair::generate().context("ca key")?;
     let mut ca_params = CertificateParams::new(Vec::new()).context("ca params")?;
@@ -48,8 +50,7 @@ fn make_ca_chain() -> Result<(
     let ca_cert = ca_params.self_signed(&ca_key).context("self-sign ca")?;
 
     let leaf_key = KeyPair::generate().context("leaf key")?;
-    let mut leaf_params = CertificateParams::new(vec!["localhost".into(), "127.0.0.1".into()])
-        .context("leaf params")?;
+    let mut leaf_params = CertificateParams::new(names).context("leaf params")?;
     leaf_params
         .distinguished_name
         .push(DnType::CommonName, "localhost");
@@ -135,7 +136,7 @@ fn loopback_for(addr: SocketAddr) -> SocketAddr {
 async fn cert_hash_accept() -> Result<()> {
     init_tracing();
 
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
     let hash = cert_sha256(&chain);
     let (addr, server) = spawn_server(chain, key).await?;
 
@@ -159,6 +160,10 @@ async fn cert_hash_accept() -> Result<()> {
         stats.rtt.is_some(),
         "expected an RTT estimate once a path is established, got {stats:?}"
     );
+    assert!(
+        stats.cwnd.is_some_and(|cwnd| cwnd > 0),
+        "expected a congestion window once a path is established, got {stats:?}"
+    );
 
     session.close(0, "bye");
     session.closed().await;
@@ -170,7 +175,7 @@ async fn cert_hash_accept() -> Result<()> {
 async fn cert_hash_reject() -> Result<()> {
     init_tracing();
 
-    let (chain, key) = make_self_signed()?;
+    let (chain, key) = common::certificate()?;
     let (addr, server) = spawn_server(chain, key).await?;
 
     // A hash that does not match any certificate.
@@ -254,3 +259,59 @@ async fn custom_roots_reject() -> Result<()> {
     server.abort();
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn custom_roots_ip_accept() -> Result<()> {
+    init_tracing();
+
+    let (ca_root, chain, key) = make_ca_chain()?;
+    let (addr, server) = spawn_server(chain, key).await?;
+
+    // Dial the bound IP directly: the leaf's IP SAN must match, with no SNI.
+    let session = ClientBuilder::default()
+        .with_bind(loopback_for(addr))?
+        .with_root_certificates(vec![ca_root])
+        .connect(Url::parse(&format!("https://{addr}/"))?)
+        .await?
+        .established()
+        .await
+        .context("handshake should succeed when the cert has a matching IP SAN")?;
+
+    session.close(0, "bye");
+    session.closed().await;
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn custom_roots_ip_reject() -> Result<()> {
+    init_tracing();
+
+    // The leaf is trusted but only valid for a DNS name, so dialing by IP must
+    // fail rather than skip the name check.
+    let (ca_root, chain, key) = make_ca_chain_for(vec!["localhost".into()])?;
+    let (addr, server) = spawn_server(chain, key).await?;
+
+    let url = Url::parse(&format!("https://{addr}/"))?;
+    let client_bind = loopback_for(addr);
+
+    let result = tokio::time::timeout(Duration::from_secs(5), async move {
+        ClientBuilder::default()
+            .with_bind(client_bind)?
+            .with_root_certificates(vec![ca_root])
+            .connect(url)
+            .await?
+            .established()
+            .await
+    })
+    .await
+    .context("handshake neither succeeded nor failed within the timeout")?;
+
+    assert!(
+        result.is_err(),
+        "handshake must fail when the cert has no matching IP SAN"
+    );
+
+    server.abort();
+    Ok(())
+}
@@ -0,0 +1,159 @@
+//! Compares the server's default UDP socket with io_uring, over loopback.
+//!
+//! Each socket is measured receiving a stream, sending a stream, and receiving a flood
+//! of datagrams. Only the server's socket changes; the client always uses quinn's.
+//! Run with:
+//!
+//! ```sh
+//! cargo bench -p web-transport-quinn --features io-uring --bench udp_io -- [MiB]
+//! ```
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quinn::{ClientBuilder, ServerBuilder, Session};
+
+const MIB: usize = 1024 * 1024;
+const CHUNK: usize = 64 * 1024;
+
+// Long enough to smooth out scheduling noise, short enough to run both sockets quickly.
+const DATAGRAM_WINDOW: Duration = Duration::from_secs(2);
+const DATAGRAM_SIZE: usize = 1000;
+
+/// Connect a client to a fresh server, returning `(client, server)`.
+async fn connect(io_uring: bool) -> Result<(Session, Session)> {
+    let key = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+    let chain = vec![CertificateDer::from(key.cert.der().to_vec())];
+    let der = rcgen::KeyPair::serialize_der(&key.signing_key);
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(der));
+
+    let mut server = ServerBuilder::new()
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_io_uring(io_uring)
+        .with_certificate(chain, key)?;
+    let url = Url::parse(&format!(
+        "https://localhost:{}/",
+        server.local_addr()?.port()
+    ))?;
+
+    let client = ClientBuilder::new()
+        .dangerous()
+        .with_no_certificate_verification()?;
+    let (client, request) = tokio::join!(client.connect(url), server.accept());
+    let client = client.context("connect")?;
+    let session = request.context("no request")?.ok().await?;
+
+    // Keep the endpoint alive for as long as the session.
+    tokio::spawn(async move {
+        let _server = server;
+        std::future::pending::<()>().await
+    });
+
+    Ok((client, session))
+}
+
+/// Send `total` bytes from `from` and time how long `to` takes to read them all.
+async fn transfer(from: &Session, to: &Session, total: usize) -> Result<Duration> {
+    let chunk = Bytes::from(vec![0x55u8; CHUNK]);
+    let start = Instant::now();
+
+    let send = async {
+        let mut send = from.open_uni().await?;
+        for _ in 0..total / CHUNK {
+            send.write_chunk(chunk.clone()).await?;
+        }
+        send.finish()?;
+        anyhow::Ok(())
+    };
+
+    let recv = async {
+        let mut recv = to.accept_uni().await?;
+        let mut read = 0;
+        while let Some(chunk) = recv.read_chunk(usize::MAX, true).await? {
+            read += chunk.bytes.len();
+        }
+        anyhow::ensure!(read == total / CHUNK * CHUNK, "short read: {read}");
+        anyhow::Ok(())
+    };
+
+    tokio::try_join!(send, recv)?;
+    Ok(start.elapsed())
+}
+
+/// Flood datagrams from `from` for [DATAGRAM_WINDOW], returning how many `to` received.
+async fn flood(from: &Session, to: &Session) -> Result<(usize, usize)> {
+    let payload = Bytes::from(vec![0xaau8; DATAGRAM_SIZE]);
+    let deadline = Instant::now() + DATAGRAM_WINDOW;
+
+    let send = async {
+        let mut sent = 0;
+        while Instant::now() < deadline {
+            from.send_datagram(payload.clone())?;
+            sent += 1;
+            if sent % 32 == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+        anyhow::Ok(sent)
+    };
+
+    let recv = async {
+        let mut received = 0;
+        // Stop once the sender is done and the queue has drained.
+        let grace = deadline + Duration::from_millis(200);
+        while let Ok(Ok(_)) = tokio::time::timeout_at(grace.into(), to.read_datagram()).await {
+            received += 1;
+        }
+        anyhow::Ok(received)
+    };
+
+    tokio::try_join!(send, recv)
+}
+
+fn report_transfer(name: &str, total: usize, elapsed: Duration) {
+    let mib = (total / MIB) as f64;
+    println!(
+        "{name:>20}: {mib:.0} MiB in {elapsed:.2?} ({:.0} MiB/s)",
+        mib / elapsed.as_secs_f64(),
+    );
+}
+
+fn report_flood(name: &str, sent: usize, received: usize) {
+    let seconds = DATAGRAM_WINDOW.as_secs_f64();
+    println!(
+        "{name:>20}: {:.0} datagrams/s received, {:.0} sent ({:.1}% lost)",
+        received as f64 / seconds,
+        sent as f64 / seconds,
+        100.0 * (sent - received.min(sent)) as f64 / sent.max(1) as f64,
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // cargo bench passes `--bench`; take the first number as the size in MiB.
+    let total = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(256)
+        * MIB;
+
+    for (name, io_uring) in [("default", false), ("io_uring", true)] {
+        let (client, server) = connect(io_uring).await?;
+
+        let elapsed = transfer(&client, &server, total).await?;
+        report_transfer(&format!("{name} recv"), total, elapsed);
+
+        let elapsed = transfer(&server, &client, total).await?;
+        report_transfer(&format!("{name} send"), total, elapsed);
+
+        let (sent, received) = flood(&client, &server).await?;
+        report_flood(&format!("{name} datagrams"), sent, received);
+
+        client.close(0, b"done");
+    }
+
+    Ok(())
+}
@@ -1,8 +1,7 @@
-use std::{fs, io, path, sync::Arc};
+use std::{path, sync::Arc};
 
 use anyhow::Context;
 use clap::Parser;
-use rustls::pki_types::CertificateDer;
 use url::Url;
 
 #[derive(Parser, Debug)]
@@ -29,14 +28,8 @@ async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
     // Read the PEM certificate chain
-    let chain = fs::File::open(args.tls_cert).context("failed to open cert file")?;
-    let mut chain = io::BufReader::new(chain);
-
-    let chain: Vec<CertificateDer> = rustls_pemfile::certs(&mut chain)
-        .collect::<Result<_, _>>()
-        .context("failed to load certs")?;
-
-    anyhow::ensure!(!chain.is_empty(), "could not find certificate");
+    let chain =
+        web_transport_quinn::tls::load_certs(&args.tls_cert).context("failed to load certs")?;
 
     let mut roots = rustls::RootCertStore::empty();
     roots.add_parsable_certificates(chain);
@@ -1,8 +1,7 @@
-use std::{fs, io, path};
+use std::path;
 
 use anyhow::Context;
 use clap::Parser;
-use rustls::pki_types::CertificateDer;
 use url::Url;
 use web_transport_quinn::proto::ConnectRequest;
 
@@ -46,14 +45,7 @@ async fn main() -> anyhow::Result<()> {
         client.dangerous().with_no_certificate_verification()?
     } else if let Some(path) = &args.tls_cert {
         // Read the PEM certificate chain
-        let chain = fs::File::open(path).context("failed to open cert file")?;
-        let mut chain = io::BufReader::new(chain);
-
-        let chain: Vec<CertificateDer> = rustls_pemfile::certs(&mut chain)
-            .collect::<Result<_, _>>()
-            .context("failed to load certs")?;
-
-        anyhow::ensure!(!chain.is_empty(), "could not find certificate");
+        let chain = web_transport_quinn::tls::load_certs(path).context("failed to load certs")?;
 
         // Only accept these certificates.
         // Also available: with_server_certificate_hashes
@@ -68,7 +60,7 @@ async fn main() -> anyhow::Result<()> {
     // Connect to the given URL.
     let mut request = ConnectRequest::new(args.url);
     if let Some(protocol) = &args.protocol {
-        request = request.with_protocol(protocol);
+        request = request.try_with_protocol(protocol.as_str())?;
     }
     let session = client.connect(request).await?;
 
@@ -1,17 +1,20 @@
-use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::proto::ConnectRequest;
+use crate::proto::{codes::DropCodes, ConnectRequest, ConnectTarget, RetryPolicy, SessionMode};
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 use quinn::crypto::rustls::QuicClientConfig;
-use rustls::{client::danger::ServerCertVerifier, pki_types::CertificateDer};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio::net::lookup_host;
-use url::Host;
+use web_transport_trait::{Clock, TokioClock};
 
 use crate::crypto;
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 use crate::ALPN;
-use crate::{ClientError, Session};
+use crate::{
+    driver::Spawner, happy_eyeballs, ClientError, ConnectError, FaultInjector, Faults,
+    HandshakeTiming, Session, SessionDriver, SocketOptions, DEFAULT_CONNECTION_ATTEMPT_DELAY,
+};
 
 /// Congestion control algorithm to use for the connection.
 ///
@@ -40,17 +43,34 @@ pub(crate) fn controller_factory(algorithm: CongestionControl) -> Option<Control
     }
 }
 
+/// How long a connection can go without hearing from the peer, unless configured.
+pub(crate) const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// The transport config shared by both builders, so the client and server can't
 /// drift on which knobs actually get applied.
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 pub(crate) fn transport_config(
     congestion_controller: Option<&ControllerFactory>,
+    idle_timeout: Option<Duration>,
+    keep_alive: Option<Duration>,
+    datagram_send_buffer: Option<usize>,
 ) -> Arc<quinn::TransportConfig> {
     let mut transport = quinn::TransportConfig::default();
     if let Some(cc) = congestion_controller {
         transport.congestion_controller_factory(cc.clone());
     }
 
+    // Saturate rather than fail: a timeout past the varint limit is forever in practice.
+    let idle_timeout = idle_timeout.map(|timeout| {
+        quinn::IdleTimeout::try_from(timeout)
+            .unwrap_or_else(|_| quinn::IdleTimeout::from(quinn::VarInt::MAX))
+    });
+    transport.max_idle_timeout(idle_timeout);
+    transport.keep_alive_interval(keep_alive);
+    if let Some(size) = datagram_send_buffer {
+        transport.datagram_send_buffer_size(size);
+    }
+
     Arc::new(transport)
 }
 
@@ -62,6 +82,22 @@ pub(crate) fn transport_config(
 pub struct ClientBuilder {
     provider: crypto::Provider,
     congestion_controller: Option<ControllerFactory>,
+    faults: Option<Faults>,
+    attempt_delay: Duration,
+    handshake_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    keep_alive: Option<Duration>,
+    datagram_send_buffer: Option<usize>,
+    socket_options: SocketOptions,
+    max_redirects: usize,
+    drop_codes: DropCodes,
+    require_protocol: bool,
+    keylog: bool,
+    mode: SessionMode,
+    history: usize,
+    clock: Arc<dyn Clock>,
+    // The key is shared because PrivateKeyDer isn't Clone, and the builder is.
+    client_cert: Option<(Vec<CertificateDer<'static>>, Arc<PrivateKeyDer<'static>>)>,
 }
 
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
@@ -71,6 +107,21 @@ impl ClientBuilder {
         Self {
             provider: crypto::default_provider(),
             congestion_controller: None,
+            faults: None,
+            attempt_delay: DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            handshake_timeout: None,
+            idle_timeout: Some(IDLE_TIMEOUT),
+            keep_alive: None,
+            datagram_send_buffer: None,
+            socket_options: SocketOptions::default(),
+            max_redirects: 0,
+            drop_codes: DropCodes::default(),
+            require_protocol: false,
+            keylog: false,
+            mode: SessionMode::Full,
+            history: 0,
+            clock: Arc::new(TokioClock),
+            client_cert: None,
         }
     }
 
@@ -80,7 +131,149 @@ impl ClientBuilder {
         self
     }
 
+    /// Wait this long for a connection attempt before racing the next resolved address.
+    ///
+    /// When a host resolves to several addresses, they're tried in turn with the address
+    /// families interleaved (RFC 8305, "Happy Eyeballs"), and the first to connect wins.
+    /// Defaults to [DEFAULT_CONNECTION_ATTEMPT_DELAY].
+    pub fn with_connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    /// Give up on a connection attempt that hasn't finished its handshake after this long.
+    ///
+    /// The deadline covers DNS, the QUIC handshake and the server's response to the
+    /// CONNECT request, and each redirect gets a fresh one. A missed deadline fails with
+    /// [quinn::ConnectionError::TimedOut], which [is retryable](ClientError::is_retryable).
+    /// Disabled by default, leaving only the [idle timeout](Self::with_idle_timeout).
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Close the connection after this long without hearing from the server.
+    ///
+    /// Each side advertises its own, and the connection uses the smaller of the two.
+    /// Defaults to 30 seconds.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Send a PING on this interval, keeping an idle connection alive.
+    ///
+    /// Disabled by default. This must be shorter than the idle timeout to have any
+    /// effect, including the server's; a third of it is a reasonable choice.
+    pub fn with_keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+
+    /// Buffer up to this many bytes of outgoing datagrams that haven't been sent yet.
+    ///
+    /// Once it's full, [Session::send_datagram] discards the oldest buffered datagrams to
+    /// make room, while [Session::send_datagram_wait] waits. Defaults to quinn's 1MiB.
+    pub fn with_datagram_send_buffer(mut self, bytes: usize) -> Self {
+        self.datagram_send_buffer = Some(bytes);
+        self
+    }
+
+    /// Follow up to `max` redirects from the server, reconnecting to each new URL in turn.
+    ///
+    /// Disabled by default, so a redirect fails the connection with [ConnectError::Redirect](crate::ConnectError::Redirect).
+    pub fn with_follow_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Fail the connection unless the server selects one of the offered protocols.
+    ///
+    /// By default a server that ignores [ConnectRequest::protocols] is accepted, leaving
+    /// [Session::protocol](crate::Session::protocol) as `None`. When required, such a session is
+    /// closed and [Client::connect] fails with [ConnectError::NoProtocol](crate::ConnectError::NoProtocol).
+    pub fn require_protocol(mut self, required: bool) -> Self {
+        self.require_protocol = required;
+        self
+    }
+
+    /// Apply these [SocketOptions] to the UDP socket the client binds.
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Inject the given [Faults] into every session, for resilience testing.
+    ///
+    /// **WARNING**: This deliberately degrades the connection; never enable it in production.
+    pub fn with_faults(mut self, faults: Faults) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Use these codes when a session or stream is dropped without being closed.
+    ///
+    /// Defaults to the values in [codes](crate::proto::codes), which every backend
+    /// shares so a peer can tell why a stream was abandoned.
+    pub fn with_drop_codes(mut self, codes: DropCodes) -> Self {
+        self.drop_codes = codes;
+        self
+    }
+
+    /// Open sessions in this [SessionMode].
+    ///
+    /// A [datagram-only](SessionMode::DatagramOnly) session can't open streams and resets
+    /// any the server opens. Defaults to [SessionMode::Full].
+    pub fn with_session_mode(mut self, mode: SessionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Keep the last `capacity` events of each session, for post-mortem debugging.
+    ///
+    /// Streams opened and accepted, capsules, and errors are recorded with timestamps.
+    /// See [Session::history]; they're also attached to the close error. Defaults to 0,
+    /// which records nothing.
+    pub fn with_session_history(mut self, capacity: usize) -> Self {
+        self.history = capacity;
+        self
+    }
+
+    /// Measure timeouts with `clock` instead of the tokio timer.
+    ///
+    /// This covers the handshake timeout, retry backoff, stream header timeouts, datagram
+    /// expiry and the wait for the server to acknowledge a close. Defaults to [TokioClock],
+    /// which already follows `tokio::time::pause`; QUIC's own timers, like the idle timeout
+    /// and keep-alives, still use quinn's.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// Wireshark can use the file to decrypt packet captures. Nothing is written if the
+    /// variable isn't set. Disabled by default, since the secrets decrypt every connection.
+    pub fn with_keylog(mut self, enabled: bool) -> Self {
+        self.keylog = enabled;
+        self
+    }
+
+    /// Present this certificate if the server asks for one (mTLS).
+    ///
+    /// The chain is sent leaf first, and the key signs the handshake. Servers that don't
+    /// request a client certificate never see it.
+    pub fn with_client_certificate(
+        mut self,
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
+    ) -> Self {
+        self.client_cert = Some((chain.into_iter().collect(), Arc::new(key.into())));
+        self
+    }
+
     /// Accept any certificate from the server if it uses a known root CA.
+    #[cfg(feature = "native-roots")]
     pub fn with_system_roots(self) -> Result<Client, ClientError> {
         let mut roots = rustls::RootCertStore::empty();
 
@@ -98,28 +291,43 @@ impl ClientBuilder {
             }
         }
 
-        let crypto = self
-            .builder()
-            .with_root_certificates(roots)
-            .with_no_client_auth();
+        let crypto = self.client_auth(self.builder().with_root_certificates(roots))?;
+        self.build(crypto)
+    }
 
+    /// Accept any certificate from the server if it chains to one of these roots.
+    ///
+    /// The certificate must match the URL's host: a DNS name against its DNS
+    /// SANs, or an IP address (e.g. `https://127.0.0.1/`) against its IP SANs.
+    pub fn with_root_certificates(
+        self,
+        certs: impl IntoIterator<Item = CertificateDer<'static>>,
+    ) -> Result<Client, ClientError> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in certs {
+            roots.add(cert)?;
+        }
+
+        let crypto = self.client_auth(self.builder().with_root_certificates(roots))?;
         self.build(crypto)
     }
 
     /// Supply certificates for accepted servers instead of using root CAs.
-    pub fn with_server_certificates(
+    #[cfg(feature = "self-signed")]
+    pub fn with_server_certificates<'a>(
         self,
-        certs: Vec<CertificateDer>,
+        certs: impl IntoIterator<Item = CertificateDer<'a>>,
     ) -> Result<Client, ClientError> {
-        let hashes = certs.iter().map({
+        let hashes = certs.into_iter().map({
             let provider = self.provider.clone();
-            move |cert| crypto::sha256(&provider, cert).as_ref().to_vec()
+            move |cert| crypto::sha256(&provider, &cert).as_ref().to_vec()
         });
 
         self.with_server_certificate_hashes(hashes.collect())
     }
 
     /// Supply sha256 hashes for accepted certificates instead of using root CAs.
+    #[cfg(feature = "self-signed")]
     pub fn with_server_certificate_hashes(
         self,
         hashes: Vec<Vec<u8>>,
@@ -131,12 +339,11 @@ impl ClientBuilder {
         });
 
         // Configure the crypto client.
-        let crypto = self
-            .builder()
-            .dangerous()
-            .with_custom_certificate_verifier(fingerprints.clone())
-            .with_no_client_auth();
-
+        let crypto = self.client_auth(
+            self.builder()
+                .dangerous()
+                .with_custom_certificate_verifier(fingerprints.clone()),
+        )?;
         self.build(crypto)
     }
 
@@ -155,17 +362,58 @@ impl ClientBuilder {
             .unwrap()
     }
 
+    // Finish the TLS config with the client certificate, if there is one.
+    fn client_auth(
+        &self,
+        builder: rustls::ConfigBuilder<rustls::ClientConfig, rustls::client::WantsClientCert>,
+    ) -> Result<rustls::ClientConfig, ClientError> {
+        match &self.client_cert {
+            Some((chain, key)) => {
+                Ok(builder.with_client_auth_cert(chain.clone(), key.clone_key())?)
+            }
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+
     fn build(self, mut crypto: rustls::ClientConfig) -> Result<Client, ClientError> {
         crypto.alpn_protocols = vec![ALPN.as_bytes().to_vec()];
+        if self.keylog {
+            crypto.key_log = Arc::new(rustls::KeyLogFile::new());
+        }
 
         let client_config = QuicClientConfig::try_from(crypto).unwrap();
         let mut client_config = quinn::ClientConfig::new(Arc::new(client_config));
-        client_config.transport_config(transport_config(self.congestion_controller.as_ref()));
+        client_config.transport_config(transport_config(
+            self.congestion_controller.as_ref(),
+            self.idle_timeout,
+            self.keep_alive,
+            self.datagram_send_buffer,
+        ));
+
+        let socket = self
+            .socket_options
+            .bind("[::]:0".parse().unwrap())
+            .map_err(|e| ClientError::IoError(e.into()))?;
+        let client = quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            None,
+            socket,
+            Arc::new(quinn::TokioRuntime),
+        )
+        .map_err(|e| ClientError::IoError(e.into()))?;
 
-        let client = quinn::Endpoint::client("[::]:0".parse().unwrap()).unwrap();
         Ok(Client {
             endpoint: client,
             config: client_config,
+            faults: self.faults,
+            attempt_delay: self.attempt_delay,
+            handshake_timeout: self.handshake_timeout,
+            max_redirects: self.max_redirects,
+            drop_codes: self.drop_codes,
+            require_protocol: self.require_protocol,
+            mode: self.mode,
+            history: self.history,
+            clock: self.clock,
         })
     }
 }
@@ -199,22 +447,48 @@ impl DangerousClientBuilder {
     pub fn with_no_certificate_verification(self) -> Result<Client, ClientError> {
         let noop = NoCertificateVerification(self.inner.provider.clone());
 
-        let crypto = self
-            .inner
-            .builder()
-            .dangerous()
-            .with_custom_certificate_verifier(Arc::new(noop))
-            .with_no_client_auth();
-
+        let crypto = self.inner.client_auth(
+            self.inner
+                .builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(noop)),
+        )?;
         self.inner.build(crypto)
     }
 }
 
 /// A client for connecting to a WebTransport server.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Client {
     endpoint: quinn::Endpoint,
     config: quinn::ClientConfig,
+    faults: Option<Faults>,
+    attempt_delay: Duration,
+    handshake_timeout: Option<Duration>,
+    max_redirects: usize,
+    drop_codes: DropCodes,
+    require_protocol: bool,
+    mode: SessionMode,
+    history: usize,
+    clock: Arc<dyn Clock>,
+}
+
+// The clock isn't Debug, so it's left out.
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("endpoint", &self.endpoint)
+            .field("config", &self.config)
+            .field("faults", &self.faults)
+            .field("attempt_delay", &self.attempt_delay)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("max_redirects", &self.max_redirects)
+            .field("drop_codes", &self.drop_codes)
+            .field("require_protocol", &self.require_protocol)
+            .field("mode", &self.mode)
+            .field("history", &self.history)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Client {
@@ -222,62 +496,241 @@ impl Client {
     ///
     /// The ALPN MUST be set to [ALPN].
     pub fn new(endpoint: quinn::Endpoint, config: quinn::ClientConfig) -> Self {
-        Self { endpoint, config }
+        Self {
+            endpoint,
+            config,
+            faults: None,
+            attempt_delay: DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            handshake_timeout: None,
+            max_redirects: 0,
+            drop_codes: DropCodes::default(),
+            require_protocol: false,
+            mode: SessionMode::Full,
+            history: 0,
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    /// Wait this long for a connection attempt before racing the next resolved address.
+    ///
+    /// See [ClientBuilder::with_connection_attempt_delay].
+    pub fn with_connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    /// Give up on a connection attempt that hasn't finished its handshake after this long.
+    ///
+    /// See [ClientBuilder::with_handshake_timeout].
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Follow up to `max` redirects from the server.
+    ///
+    /// See [ClientBuilder::with_follow_redirects].
+    pub fn with_follow_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Use these codes when a session or stream is dropped without being closed.
+    ///
+    /// See [ClientBuilder::with_drop_codes].
+    pub fn with_drop_codes(mut self, codes: DropCodes) -> Self {
+        self.drop_codes = codes;
+        self
+    }
+
+    /// Fail the connection unless the server selects one of the offered protocols.
+    ///
+    /// See [ClientBuilder::require_protocol].
+    pub fn require_protocol(mut self, required: bool) -> Self {
+        self.require_protocol = required;
+        self
+    }
+
+    /// Open sessions in this [SessionMode].
+    ///
+    /// See [ClientBuilder::with_session_mode].
+    pub fn with_session_mode(mut self, mode: SessionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Keep the last `capacity` events of each session.
+    ///
+    /// See [ClientBuilder::with_session_history].
+    pub fn with_session_history(mut self, capacity: usize) -> Self {
+        self.history = capacity;
+        self
+    }
+
+    /// Measure timeouts with `clock` instead of the tokio timer.
+    ///
+    /// See [ClientBuilder::with_clock].
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
     }
 
     /// Connect to the server.
     pub async fn connect(
         &self,
         request: impl Into<ConnectRequest>,
+    ) -> Result<Session, ClientError> {
+        self.connect_spawned(request.into(), Spawner::Tokio).await
+    }
+
+    /// Connect to the server, trying again after failures that might not happen twice.
+    ///
+    /// Only errors that [are retryable](ClientError::is_retryable) are retried, up to
+    /// [RetryPolicy::max_attempts] in all, waiting [RetryPolicy::backoff] in between; any
+    /// other error is returned straight away, as is the last one. Every attempt starts from
+    /// the original request, offering the same protocols in the same order, and the server's
+    /// choice is checked against that offer each time, so a retry that reaches a different
+    /// server can't return a session speaking something that wasn't offered.
+    pub async fn connect_with_retry(
+        &self,
+        request: impl Into<ConnectRequest>,
+        policy: RetryPolicy,
     ) -> Result<Session, ClientError> {
         let request = request.into();
 
-        let port = request.url.port().unwrap_or(443);
+        let mut attempt = 1;
+        loop {
+            match self.connect(request.clone()).await {
+                Err(err) if err.is_retryable() && attempt < policy.max_attempts => {
+                    let backoff = policy.backoff(attempt);
+                    tracing::debug!(%err, attempt, ?backoff, "retrying connect");
+                    self.clock.sleep(backoff).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
 
-        // TODO error on username:password in host
-        let (host, remote) = match request
-            .url
-            .host()
-            .ok_or_else(|| ClientError::InvalidDnsName("".to_string()))?
-        {
-            Host::Domain(domain) => {
-                let domain = domain.to_string();
+    /// Like [Client::connect], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn connect_driven(
+        &self,
+        request: impl Into<ConnectRequest>,
+    ) -> Result<(Session, SessionDriver), ClientError> {
+        let (spawner, driver) = Spawner::driven();
+        let session = self.connect_spawned(request.into(), spawner).await?;
+        Ok((session, driver))
+    }
+
+    async fn connect_spawned(
+        &self,
+        mut request: ConnectRequest,
+        spawner: Spawner,
+    ) -> Result<Session, ClientError> {
+        for _ in 0..self.max_redirects {
+            match self.connect_once(request.clone(), spawner.clone()).await {
+                Err(ClientError::HttpError(ConnectError::Redirect { location, .. })) => {
+                    tracing::debug!(from = %request.url, to = %location, "following redirect");
+                    request.url = location;
+                }
+                res => return res,
+            }
+        }
+
+        self.connect_once(request, spawner).await
+    }
+
+    async fn connect_once(
+        &self,
+        request: ConnectRequest,
+        spawner: Spawner,
+    ) -> Result<Session, ClientError> {
+        let Some(timeout) = self.handshake_timeout else {
+            return self.handshake(request, spawner).await;
+        };
+
+        // Dropping the attempt closes any connection it made.
+        let handshake = self.handshake(request, spawner);
+        match self.clock.timeout(timeout, handshake).await {
+            Ok(res) => res,
+            Err(_) => Err(quinn::ConnectionError::TimedOut.into()),
+        }
+    }
+
+    async fn handshake(
+        &self,
+        request: ConnectRequest,
+        spawner: Spawner,
+    ) -> Result<Session, ClientError> {
+        // Reject a URL we can't connect to before doing any network I/O.
+        let target = ConnectTarget::new(&request.url)?;
+        let host = target.server_name();
+        let mut timing = HandshakeTiming::default();
+
+        let remotes = match target.addr() {
+            Some(remote) => vec![remote],
+            None => {
                 // Look up the DNS entry.
-                let mut remotes = match lookup_host((domain.clone(), port)).await {
-                    Ok(remotes) => remotes,
-                    Err(_) => return Err(ClientError::InvalidDnsName(domain)),
+                let start = Instant::now();
+                let remotes = match lookup_host((host.clone(), target.port)).await {
+                    Ok(remotes) => happy_eyeballs::interleave(remotes),
+                    Err(_) => return Err(ClientError::InvalidDnsName(host)),
                 };
+                timing.dns = Some(start.elapsed());
 
-                // Return the first entry.
-                let remote = match remotes.next() {
-                    Some(remote) => remote,
-                    None => return Err(ClientError::InvalidDnsName(domain)),
-                };
+                if remotes.is_empty() {
+                    return Err(ClientError::InvalidDnsName(host));
+                }
 
-                (domain, remote)
+                remotes
             }
-            Host::Ipv4(ipv4) => (ipv4.to_string(), SocketAddr::new(IpAddr::V4(ipv4), port)),
-            Host::Ipv6(ipv6) => (ipv6.to_string(), SocketAddr::new(IpAddr::V6(ipv6), port)),
         };
 
-        // Connect to the server using the addr we just resolved.
-        let conn = self
-            .endpoint
-            .connect_with(self.config.clone(), remote, &host)?;
-        let conn = conn.await?;
+        // Race the resolved addresses, keeping whichever QUIC handshake completes first.
+        let start = Instant::now();
+        let conn = happy_eyeballs::race(&*self.clock, remotes, self.attempt_delay, |remote| {
+            let connecting = self
+                .endpoint
+                .connect_with(self.config.clone(), remote, &host);
+            async move { Ok::<_, ClientError>(connecting?.await?) }
+        })
+        .await?;
+        timing.quic = Some(start.elapsed());
+
+        let faults = self.faults.clone().map(FaultInjector::new);
+        if let Some(stall) = faults.as_ref().and_then(|faults| faults.control_stall()) {
+            self.clock.sleep(stall).await;
+        }
 
         // Connect with the connection we established.
-        Session::connect(conn, request).await
+        let session = Session::connect_timed(
+            conn,
+            request,
+            timing,
+            self.drop_codes,
+            self.mode,
+            spawner,
+            self.clock.clone(),
+        )
+        .await?;
+        if self.require_protocol && session.response().protocol.is_none() {
+            session.close(0, b"no protocol selected");
+            return Err(ConnectError::NoProtocol.into());
+        }
+
+        Ok(session.with_faults(faults).with_history(self.history))
     }
 }
 
-#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+#[cfg(all(any(feature = "aws-lc-rs", feature = "ring"), feature = "native-roots"))]
 impl Default for Client {
     fn default() -> Self {
         ClientBuilder::new().with_system_roots().unwrap()
     }
 }
 
+#[cfg(feature = "self-signed")]
 #[cfg_attr(not(any(feature = "aws-lc-rs", feature = "ring")), allow(dead_code))]
 #[derive(Debug)]
 struct ServerFingerprints {
@@ -285,7 +738,8 @@ struct ServerFingerprints {
     fingerprints: Vec<Vec<u8>>,
 }
 
-impl ServerCertVerifier for ServerFingerprints {
+#[cfg(feature = "self-signed")]
+impl rustls::client::danger::ServerCertVerifier for ServerFingerprints {
     fn verify_server_cert(
         &self,
         end_entity: &rustls::pki_types::CertificateDer<'_>,
@@ -1,10 +1,20 @@
 use std::ops::Deref;
+use std::sync::Arc;
 
-use web_transport_proto::{ConnectRequest, ConnectResponse, VarInt};
+use web_transport_proto::{
+    codes, ConnectRequest, ConnectResponse, HttpRequest, HttpResponse, IncomingRequest, VarInt,
+};
 
 use thiserror::Error;
+use web_transport_trait::ErrorKind;
+
+use crate::error::{connection_kind, quinn_read_kind, quinn_write_kind};
+
+/// Answers the plain HTTP/3 requests a client sends before its CONNECT.
+pub(crate) type HttpHandler = Arc<dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync>;
 
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ConnectError {
     #[error("quic stream was closed early")]
     UnexpectedEnd,
@@ -24,8 +34,45 @@ pub enum ConnectError {
     #[error("http error status: {0}")]
     ErrorStatus(http::StatusCode),
 
+    /// The server rejected the session; the headers may say why, e.g. `www-authenticate` on a 401.
+    #[error("rejected ({status})")]
+    Rejected {
+        status: http::StatusCode,
+        headers: http::HeaderMap,
+    },
+
+    #[error("redirected ({status}) to {location}")]
+    Redirect {
+        status: http::StatusCode,
+        location: url::Url,
+    },
+
     #[error("server returned protocol not in request: {0}")]
     ProtocolMismatch(String),
+
+    #[error("server didn't select any of the offered protocols")]
+    NoProtocol,
+}
+
+impl ConnectError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::ProtoError(web_transport_proto::ConnectError::WrongStatus(_)) => {
+                ErrorKind::Rejected
+            }
+            Self::ProtoError(_) | Self::ProtocolMismatch(_) | Self::NoProtocol => {
+                ErrorKind::Protocol
+            }
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::ReadError(e) => quinn_read_kind(e),
+            Self::WriteError(e) => quinn_write_kind(e),
+            Self::ErrorStatus(_) | Self::Rejected { .. } | Self::Redirect { .. } => {
+                ErrorKind::Rejected
+            }
+        }
+    }
 }
 
 /// An HTTP/3 CONNECT request/response for establishing a WebTransport session.
@@ -39,20 +86,72 @@ pub struct Connecting {
 }
 
 impl Connecting {
-    pub async fn accept(conn: &quinn::Connection) -> Result<Self, ConnectError> {
-        // Accept the stream that will be used to send the HTTP CONNECT request.
-        // If they try to send any other type of HTTP request, we will error out.
-        let (send, mut recv) = conn.accept_bi().await?;
-
-        let request = web_transport_proto::ConnectRequest::read(&mut recv).await?;
-        tracing::debug!(?request, "received CONNECT request");
+    // Accept the CONNECT request, rejecting headers larger than `max_field_section_size`.
+    //
+    // Plain HTTP/3 requests sent first are answered by `handler`, or with 501 (Not
+    // Implemented) without one, so a client can upgrade to WebTransport later on.
+    pub async fn accept(
+        conn: &quinn::Connection,
+        max_field_section_size: Option<u64>,
+        handler: Option<&HttpHandler>,
+    ) -> Result<Self, ConnectError> {
+        loop {
+            // Accept the stream that will be used to send the HTTP CONNECT request.
+            let (mut send, mut recv) = conn.accept_bi().await?;
+
+            let request = match IncomingRequest::read(&mut recv, max_field_section_size).await {
+                Ok(IncomingRequest::Connect(request)) => request,
+                Ok(IncomingRequest::Http(request)) => {
+                    Self::answer(send, recv, &request, handler).await;
+                    continue;
+                }
+                Err(err @ web_transport_proto::ConnectError::FieldSectionTooLarge { .. }) => {
+                    // Tell the client why, as RFC 9114 section 4.2.2 suggests.
+                    let response =
+                        ConnectResponse::new(http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+                    if response.write(&mut send).await.is_ok() {
+                        send.finish().ok();
+                        send.stopped().await.ok();
+                    }
+                    return Err(err.into());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            tracing::debug!(?request, "received CONNECT request");
+
+            // The request was successfully decoded, so we can send a response.
+            return Ok(Self {
+                request,
+                send,
+                recv,
+            });
+        }
+    }
 
-        // The request was successfully decoded, so we can send a response.
-        Ok(Self {
-            request,
-            send,
-            recv,
-        })
+    // Answer a plain HTTP/3 request, leaving the connection open for the CONNECT.
+    async fn answer(
+        mut send: quinn::SendStream,
+        mut recv: quinn::RecvStream,
+        request: &HttpRequest,
+        handler: Option<&HttpHandler>,
+    ) {
+        tracing::debug!(?request, "received HTTP request");
+        let response = match handler {
+            Some(handler) => handler(request),
+            None => HttpResponse::new(http::StatusCode::NOT_IMPLEMENTED),
+        };
+
+        // Any request body goes unread, so ask the client to stop (RFC 9114 section 4.1).
+        recv.stop(quinn::VarInt::from_u64(codes::h3::NO_ERROR).unwrap())
+            .ok();
+
+        // A client that gave up on its request is no reason to drop the connection.
+        match response.write(&mut send).await {
+            Ok(()) => {
+                send.finish().ok();
+            }
+            Err(err) => tracing::debug!(?err, "failed to answer HTTP request"),
+        }
     }
 
     // Called by the server to send a response to the client and establish the session.
@@ -80,9 +179,28 @@ impl Connecting {
         })
     }
 
-    pub async fn reject(self, status: http::StatusCode) -> Result<(), ConnectError> {
-        let mut connect = self.respond(status).await?;
+    // The session ID is the stream ID of the CONNECT request.
+    pub fn session_id(&self) -> VarInt {
+        let stream_id = quinn::VarInt::from(self.send.id());
+        VarInt::try_from(stream_id.into_inner()).unwrap()
+    }
+
+    pub async fn reject(self, response: impl Into<ConnectResponse>) -> Result<(), ConnectError> {
+        self.close(response).await
+    }
+
+    /// Send the client to `location` with a 302 Found.
+    pub async fn redirect(self, location: url::Url) -> Result<(), ConnectError> {
+        self.close(ConnectResponse::redirect(location)).await
+    }
+
+    async fn close(self, response: impl Into<ConnectResponse>) -> Result<(), ConnectError> {
+        let mut connect = self.respond(response).await?;
         connect.send.finish().ok();
+
+        // Wait for the response to be acknowledged; the caller is about to drop the
+        // connection, which would otherwise discard it before the client reads it.
+        connect.send.stopped().await.ok();
         Ok(())
     }
 }
@@ -124,9 +242,23 @@ impl Connected {
         tracing::debug!(?request, "sending CONNECT request");
         request.write(&mut send).await?;
 
-        let response = web_transport_proto::ConnectResponse::read(&mut recv).await?;
+        let response = web_transport_proto::ConnectResponse::read_any_status(&mut recv).await?;
         tracing::debug!(?response, "received CONNECT response");
 
+        if let (true, Some(location)) = (response.status.is_redirection(), &response.location) {
+            return Err(ConnectError::Redirect {
+                status: response.status,
+                location: location.clone(),
+            });
+        }
+
+        if response.status.is_client_error() || response.status.is_server_error() {
+            return Err(ConnectError::Rejected {
+                status: response.status,
+                headers: response.headers.unwrap_or_default(),
+            });
+        }
+
         // Throw an error if we didn't get a 200 OK.
         if response.status != http::StatusCode::OK {
             return Err(ConnectError::ErrorStatus(response.status));
@@ -0,0 +1,183 @@
+//! Routing of incoming datagrams to per-prefix queues.
+//!
+//! Protocols that multiplex datagrams usually start each one with a varint, like a
+//! track alias. Instead of funnelling every datagram through one dispatch task, a
+//! receiver registers a [DatagramRoute] for its prefix. Whichever receiver reads a
+//! datagram from quinn hands it to the matching queue and wakes its owner; anything
+//! without a route is left for [Session::read_datagram](crate::Session::read_datagram).
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::{poll_fn, Future},
+    pin::pin,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+use bytes::Bytes;
+
+use crate::{proto::VarInt, Session, SessionError};
+
+// Datagrams held per queue while its receiver is busy; the oldest is dropped beyond this.
+const QUEUE_SIZE: usize = 256;
+
+#[derive(Default)]
+pub(crate) struct Queue {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    datagrams: VecDeque<Bytes>,
+    wakers: Vec<Waker>,
+}
+
+impl Queue {
+    fn push(&self, datagram: Bytes) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            if state.datagrams.len() >= QUEUE_SIZE {
+                state.datagrams.pop_front();
+            }
+            state.datagrams.push_back(datagram);
+            std::mem::take(&mut state.wakers)
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    // Pop the next datagram, or register `waker` to be woken when one is pushed.
+    fn poll_pop(&self, waker: &Waker) -> Option<Bytes> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(datagram) = state.datagrams.pop_front() {
+            return Some(datagram);
+        }
+
+        if !state.wakers.iter().any(|w| w.will_wake(waker)) {
+            state.wakers.push(waker.clone());
+        }
+        None
+    }
+}
+
+/// Shared by a session and its clones, so every reader routes the same way.
+#[derive(Default)]
+pub(crate) struct Router {
+    routes: Mutex<HashMap<u64, Arc<Queue>>>,
+    unrouted: Arc<Queue>,
+}
+
+impl Router {
+    /// Receive the next datagram for [Session::read_datagram](crate::Session::read_datagram).
+    pub(crate) async fn recv_unrouted(&self, session: &Session) -> Result<Bytes, SessionError> {
+        self.recv(session, &self.unrouted).await
+    }
+
+    // Wait for a datagram on `queue`, reading from the connection in the meantime and
+    // delivering whatever arrives to the queue it belongs to.
+    async fn recv(&self, session: &Session, queue: &Arc<Queue>) -> Result<Bytes, SessionError> {
+        enum Next {
+            Queued(Bytes),
+            Read(Result<Bytes, SessionError>),
+        }
+
+        loop {
+            // quinn only hands out a datagram when the read completes, so it's fine to drop.
+            let mut read = pin!(session.read_raw_datagram());
+            let next = poll_fn(|cx| {
+                if let Some(datagram) = queue.poll_pop(cx.waker()) {
+                    return Poll::Ready(Next::Queued(datagram));
+                }
+                read.as_mut().poll(cx).map(Next::Read)
+            })
+            .await;
+
+            let datagram = match next {
+                Next::Queued(datagram) => return Ok(datagram),
+                Next::Read(res) => res?,
+            };
+
+            let target = self.route(&datagram);
+            if Arc::ptr_eq(&target, queue) {
+                return Ok(datagram);
+            }
+            target.push(datagram);
+        }
+    }
+
+    fn route(&self, datagram: &Bytes) -> Arc<Queue> {
+        let prefix = VarInt::decode(&mut datagram.as_ref()).ok();
+        let routes = self.routes.lock().unwrap();
+        prefix
+            .and_then(|prefix| routes.get(&prefix.into_inner()))
+            .unwrap_or(&self.unrouted)
+            .clone()
+    }
+
+    pub(crate) fn register(&self, prefix: u64) -> Arc<Queue> {
+        let queue = Arc::new(Queue::default());
+        self.routes.lock().unwrap().insert(prefix, queue.clone());
+        queue
+    }
+
+    fn unregister(&self, prefix: u64, queue: &Arc<Queue>) {
+        let mut routes = self.routes.lock().unwrap();
+        if routes.get(&prefix).is_some_and(|q| Arc::ptr_eq(q, queue)) {
+            routes.remove(&prefix);
+        }
+    }
+}
+
+/// Receives the datagrams that start with one varint prefix. See [Session::route_datagrams].
+///
+/// Datagrams are returned whole, prefix included. Up to 256 are queued while the
+/// route isn't being read, after which the oldest are dropped. Dropping the route
+/// discards its queue and sends later matches back to
+/// [Session::read_datagram](crate::Session::read_datagram).
+pub struct DatagramRoute {
+    session: Session,
+    prefix: u64,
+    queue: Arc<Queue>,
+}
+
+impl DatagramRoute {
+    pub(crate) fn new(session: Session, prefix: u64, queue: Arc<Queue>) -> Self {
+        Self {
+            session,
+            prefix,
+            queue,
+        }
+    }
+
+    /// The prefix this route matches.
+    pub fn prefix(&self) -> u64 {
+        self.prefix
+    }
+
+    /// Receive the next datagram starting with [Self::prefix].
+    pub async fn recv(&self) -> Result<Bytes, SessionError> {
+        self.session
+            .datagrams()
+            .recv(&self.session, &self.queue)
+            .await
+    }
+}
+
+impl Drop for DatagramRoute {
+    fn drop(&mut self) {
+        self.session
+            .datagrams()
+            .unregister(self.prefix, &self.queue);
+    }
+}
+
+impl fmt::Debug for DatagramRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatagramRoute")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
@@ -1,11 +1,17 @@
 use std::sync::Arc;
 
+use bytes::Bytes;
 use thiserror::Error;
+use web_transport_trait::{happy_eyeballs::NoAddresses, ErrorKind, SessionId};
 
-use crate::{ConnectError, SettingsError};
+use crate::{
+    proto::{HeaderViolation, SessionEvent, UrlError},
+    ConnectError, SettingsError,
+};
 
 /// An error returned when connecting to a WebTransport endpoint.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ClientError {
     #[error("unexpected end of stream")]
     UnexpectedEnd,
@@ -31,13 +37,94 @@ pub enum ClientError {
     #[error("invalid DNS name: {0}")]
     InvalidDnsName(String),
 
+    #[error("{0}")]
+    NoAddresses(#[from] NoAddresses),
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(#[from] UrlError),
+
+    #[error("io error: {0}")]
+    IoError(Arc<std::io::Error>),
+
     #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
     #[error("rustls error: {0}")]
     Rustls(#[from] rustls::Error),
 }
 
+impl ClientError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Connection(e) => connection_kind(e),
+            Self::WriteError(e) => quinn_write_kind(e),
+            Self::ReadError(e) => quinn_read_kind(e),
+            Self::SettingsError(e) => e.kind(),
+            Self::HttpError(e) => e.kind(),
+            Self::QuinnError(quinn::ConnectError::EndpointStopping) => ErrorKind::LocallyClosed,
+            Self::QuinnError(_)
+            | Self::InvalidDnsName(_)
+            | Self::NoAddresses(_)
+            | Self::InvalidUrl(_) => ErrorKind::InvalidInput,
+            Self::IoError(_) => ErrorKind::Io,
+            #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+            Self::Rustls(_) => ErrorKind::InvalidInput,
+        }
+    }
+
+    /// Returns true if connecting again might succeed.
+    ///
+    /// That's when the host didn't resolve, the handshake timed out, or the server refused
+    /// with CONNECTION_REFUSED, H3_REQUEST_REJECTED or 503 (Service Unavailable). Used by
+    /// [Client::connect_with_retry](crate::Client::connect_with_retry).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::InvalidDnsName(_) => true,
+            Self::HttpError(
+                ConnectError::ErrorStatus(status) | ConnectError::Rejected { status, .. },
+            ) => *status == http::StatusCode::SERVICE_UNAVAILABLE,
+            _ => self.connection_error().is_some_and(connection_retryable),
+        }
+    }
+
+    // The connection error behind this one, however the handshake was interrupted.
+    fn connection_error(&self) -> Option<&quinn::ConnectionError> {
+        match self {
+            Self::Connection(e)
+            | Self::WriteError(quinn::WriteError::ConnectionLost(e))
+            | Self::ReadError(quinn::ReadError::ConnectionLost(e))
+            | Self::SettingsError(SettingsError::ConnectionError(e))
+            | Self::SettingsError(SettingsError::ReadError(quinn::ReadError::ConnectionLost(e)))
+            | Self::SettingsError(SettingsError::WriteError(quinn::WriteError::ConnectionLost(
+                e,
+            )))
+            | Self::HttpError(ConnectError::ConnectionError(e))
+            | Self::HttpError(ConnectError::ReadError(quinn::ReadError::ConnectionLost(e)))
+            | Self::HttpError(ConnectError::WriteError(quinn::WriteError::ConnectionLost(e))) => {
+                Some(e)
+            }
+            _ => None,
+        }
+    }
+}
+
+// A timeout, or the server turning us away for now.
+fn connection_retryable(err: &quinn::ConnectionError) -> bool {
+    match err {
+        quinn::ConnectionError::TimedOut => true,
+        quinn::ConnectionError::ConnectionClosed(close) => {
+            close.error_code == quinn::TransportErrorCode::CONNECTION_REFUSED
+        }
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            close.error_code.into_inner() == web_transport_proto::codes::h3::REQUEST_REJECTED
+        }
+        _ => false,
+    }
+}
+
 /// An errors returned by [`crate::Session`], split based on if they are underlying QUIC errors or WebTransport errors.
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum SessionError {
     #[error("connection error: {0}")]
     ConnectionError(quinn::ConnectionError),
@@ -47,6 +134,27 @@ pub enum SessionError {
 
     #[error("send datagram error: {0}")]
     SendDatagramError(#[from] quinn::SendDatagramError),
+
+    /// The peer sent GOAWAY and then closed the connection without an error.
+    ///
+    /// A graceful shutdown, such as a server restarting, rather than a failure:
+    /// reconnecting should work, possibly to another server.
+    #[error("peer shut down gracefully")]
+    GoAway,
+
+    /// The close error of a session that records its [history](crate::Session::history).
+    ///
+    /// Displays as the error it wraps, with the session's last events in its Debug output.
+    /// Use [SessionError::without_history] to match on the underlying error.
+    #[error("{0}")]
+    WithHistory(Box<SessionError>, Vec<SessionEvent>),
+
+    /// The close error of a session, tagged with its [id](crate::Session::id).
+    ///
+    /// Displays as `session <id>: <error>`, matching the `session` field of its tracing events.
+    /// Use [SessionError::without_history] to match on the underlying error.
+    #[error("session {1}: {0}")]
+    InSession(Box<SessionError>, SessionId),
 }
 
 impl From<quinn::ConnectionError> for SessionError {
@@ -54,11 +162,7 @@ impl From<quinn::ConnectionError> for SessionError {
         match &e {
             quinn::ConnectionError::ApplicationClosed(close) => {
                 match web_transport_proto::error_from_http3(close.error_code.into_inner()) {
-                    Some(code) => WebTransportError::Closed(
-                        code,
-                        String::from_utf8_lossy(&close.reason).into_owned(),
-                    )
-                    .into(),
+                    Some(code) => WebTransportError::Closed(code, close.reason.clone()).into(),
                     None => SessionError::ConnectionError(e),
                 }
             }
@@ -67,15 +171,55 @@ impl From<quinn::ConnectionError> for SessionError {
     }
 }
 
+impl SessionError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::WithHistory(e, _) | Self::InSession(e, _) => e.kind(),
+            Self::ConnectionError(e) => connection_kind(e),
+            Self::WebTransportError(e) => e.kind(),
+            Self::GoAway => ErrorKind::SessionClosed,
+            Self::SendDatagramError(e) => match e {
+                quinn::SendDatagramError::UnsupportedByPeer
+                | quinn::SendDatagramError::Disabled => ErrorKind::Unsupported,
+                quinn::SendDatagramError::TooLarge => ErrorKind::TooLarge,
+                quinn::SendDatagramError::ConnectionLost(e) => connection_kind(e),
+            },
+        }
+    }
+
+    /// The error without any [history](SessionError::WithHistory) or [session id](SessionError::InSession) attached.
+    pub fn without_history(&self) -> &SessionError {
+        match self {
+            Self::WithHistory(e, _) | Self::InSession(e, _) => e.without_history(),
+            e => e,
+        }
+    }
+}
+
 /// An error that can occur when reading/writing the WebTransport stream header.
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum WebTransportError {
-    #[error("closed: code={0} reason={1}")]
-    Closed(u32, String),
+    /// The session was closed by the peer. The reason is kept verbatim, as it needn't be UTF-8.
+    #[error("closed: code={} reason={}", .0, String::from_utf8_lossy(.1))]
+    Closed(u32, Bytes),
 
     #[error("unknown session")]
     UnknownSession,
 
+    /// The session is [datagram-only](crate::proto::SessionMode::DatagramOnly).
+    #[error("streams are disabled")]
+    StreamsDisabled,
+
+    /// The peer didn't enable datagrams in its SETTINGS.
+    #[error("datagrams are not supported by the peer")]
+    DatagramsUnsupported,
+
+    /// An incoming stream broke its [HeaderBudget](crate::proto::HeaderBudget).
+    #[error("protocol violation: {0}")]
+    ProtocolViolation(#[from] HeaderViolation),
+
     #[error("read error: {0}")]
     ReadError(#[from] quinn::ReadExactError),
 
@@ -83,8 +227,25 @@ pub enum WebTransportError {
     WriteError(#[from] quinn::WriteError),
 }
 
+impl WebTransportError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Closed(..) => ErrorKind::SessionClosed,
+            Self::UnknownSession => ErrorKind::Protocol,
+            Self::StreamsDisabled => ErrorKind::Unsupported,
+            Self::DatagramsUnsupported => ErrorKind::Unsupported,
+            Self::ProtocolViolation(_) => ErrorKind::Protocol,
+            Self::ReadError(quinn::ReadExactError::FinishedEarly(_)) => ErrorKind::UnexpectedEnd,
+            Self::ReadError(quinn::ReadExactError::ReadError(e)) => quinn_read_kind(e),
+            Self::WriteError(e) => quinn_write_kind(e),
+        }
+    }
+}
+
 /// An error when writing to [`crate::SendStream`]. Similar to [`quinn::WriteError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum WriteError {
     #[error("STOP_SENDING: {0}")]
     Stopped(u32),
@@ -115,8 +276,21 @@ impl From<quinn::WriteError> for WriteError {
     }
 }
 
+impl WriteError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Stopped(_) => ErrorKind::StreamStopped,
+            Self::InvalidStopped(_) => ErrorKind::Protocol,
+            Self::SessionError(e) => e.kind(),
+            Self::ClosedStream => ErrorKind::LocallyClosed,
+        }
+    }
+}
+
 /// An error when reading from [`crate::RecvStream`]. Similar to [`quinn::ReadError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ReadError {
     #[error("session error: {0}")]
     SessionError(#[from] SessionError),
@@ -151,8 +325,22 @@ impl From<quinn::ReadError> for ReadError {
     }
 }
 
+impl ReadError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::SessionError(e) => e.kind(),
+            Self::Reset(_) => ErrorKind::StreamReset,
+            Self::InvalidReset(_) => ErrorKind::Protocol,
+            Self::ClosedStream => ErrorKind::LocallyClosed,
+            Self::IllegalOrderedRead => ErrorKind::InvalidInput,
+        }
+    }
+}
+
 /// An error returned by [`crate::RecvStream::read_exact`]. Similar to [`quinn::ReadExactError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ReadExactError {
     #[error("finished early")]
     FinishedEarly(usize),
@@ -170,21 +358,33 @@ impl From<quinn::ReadExactError> for ReadExactError {
     }
 }
 
+impl ReadExactError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::FinishedEarly(_) => ErrorKind::UnexpectedEnd,
+            Self::ReadError(e) => e.kind(),
+        }
+    }
+}
+
 /// An error returned by [`crate::RecvStream::read_to_end`]. Similar to [`quinn::ReadToEndError`].
 #[derive(Clone, Error, Debug)]
+#[non_exhaustive]
 pub enum ReadToEndError {
-    #[error("too long")]
-    TooLong,
+    #[error("stream longer than the limit of {limit} bytes")]
+    TooLong { limit: usize },
 
     #[error("read error: {0}")]
     ReadError(#[from] ReadError),
 }
 
-impl From<quinn::ReadToEndError> for ReadToEndError {
-    fn from(e: quinn::ReadToEndError) -> Self {
-        match e {
-            quinn::ReadToEndError::TooLong => ReadToEndError::TooLong,
-            quinn::ReadToEndError::Read(e) => ReadToEndError::ReadError(e.into()),
+impl ReadToEndError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TooLong { .. } => ErrorKind::TooLarge,
+            Self::ReadError(e) => e.kind(),
         }
     }
 }
@@ -202,6 +402,7 @@ impl From<quinn::ClosedStream> for ClosedStream {
 
 /// An error returned when receiving a new WebTransport session.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ServerError {
     #[error("unexpected end of stream")]
     UnexpectedEnd,
@@ -227,6 +428,46 @@ pub enum ServerError {
     #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
     #[error("rustls error: {0}")]
     Rustls(#[from] rustls::Error),
+
+    #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+    #[error("invalid client certificate verifier: {0}")]
+    ClientVerifier(#[from] rustls::server::VerifierBuilderError),
+
+    #[error("timed out waiting for the client to finish the handshake")]
+    HandshakeTimeout,
+
+    #[error("too many sessions")]
+    TooManySessions,
+
+    #[error("origin not allowed")]
+    ForbiddenOrigin,
+
+    #[error("too many sessions waiting to be accepted")]
+    AcceptQueueFull,
+
+    #[error("the request wasn't answered before the response timeout")]
+    RequestTimeout,
+}
+
+impl ServerError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEnd => ErrorKind::UnexpectedEnd,
+            Self::Connection(e) => connection_kind(e),
+            Self::WriteError(e) => quinn_write_kind(e),
+            Self::ReadError(e) => quinn_read_kind(e),
+            Self::SettingsError(e) => e.kind(),
+            Self::ConnectError(e) => e.kind(),
+            Self::IoError(_) => ErrorKind::Io,
+            #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+            Self::Rustls(_) | Self::ClientVerifier(_) => ErrorKind::InvalidInput,
+            Self::HandshakeTimeout | Self::RequestTimeout => ErrorKind::TimedOut,
+            Self::TooManySessions | Self::ForbiddenOrigin | Self::AcceptQueueFull => {
+                ErrorKind::Rejected
+            }
+        }
+    }
 }
 
 // #[derive(Clone, Error, Debug)]
@@ -257,12 +498,28 @@ pub enum ServerError {
 
 impl web_transport_trait::Error for SessionError {
     fn session_error(&self) -> Option<(u32, String)> {
-        if let SessionError::WebTransportError(WebTransportError::Closed(code, reason)) = self {
-            return Some((*code, reason.to_string()));
+        if let SessionError::WebTransportError(WebTransportError::Closed(code, reason)) =
+            self.without_history()
+        {
+            return Some((*code, String::from_utf8_lossy(reason).into_owned()));
         }
 
         None
     }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let SessionError::WebTransportError(WebTransportError::Closed(code, reason)) =
+            self.without_history()
+        {
+            return Some((*code, reason.clone()));
+        }
+
+        None
+    }
+
+    fn kind(&self) -> ErrorKind {
+        SessionError::kind(self)
+    }
 }
 
 impl web_transport_trait::Error for WriteError {
@@ -274,12 +531,24 @@ impl web_transport_trait::Error for WriteError {
         None
     }
 
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let WriteError::SessionError(e) = self {
+            return e.session_error_bytes();
+        }
+
+        None
+    }
+
     fn stream_error(&self) -> Option<u32> {
         match self {
             WriteError::Stopped(code) => Some(*code),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        WriteError::kind(self)
+    }
 }
 
 impl web_transport_trait::Error for ReadError {
@@ -291,10 +560,109 @@ impl web_transport_trait::Error for ReadError {
         None
     }
 
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        if let ReadError::SessionError(e) = self {
+            return e.session_error_bytes();
+        }
+
+        None
+    }
+
     fn stream_error(&self) -> Option<u32> {
         match self {
             ReadError::Reset(code) => Some(*code),
             _ => None,
         }
     }
+
+    fn kind(&self) -> ErrorKind {
+        ReadError::kind(self)
+    }
+}
+
+impl web_transport_trait::Error for ReadExactError {
+    fn session_error(&self) -> Option<(u32, String)> {
+        match self {
+            ReadExactError::ReadError(e) => e.session_error(),
+            _ => None,
+        }
+    }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        match self {
+            ReadExactError::ReadError(e) => e.session_error_bytes(),
+            _ => None,
+        }
+    }
+
+    fn stream_error(&self) -> Option<u32> {
+        match self {
+            ReadExactError::ReadError(e) => e.stream_error(),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> ErrorKind {
+        ReadExactError::kind(self)
+    }
+}
+
+impl web_transport_trait::Error for ReadToEndError {
+    fn session_error(&self) -> Option<(u32, String)> {
+        match self {
+            ReadToEndError::ReadError(e) => e.session_error(),
+            _ => None,
+        }
+    }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        match self {
+            ReadToEndError::ReadError(e) => e.session_error_bytes(),
+            _ => None,
+        }
+    }
+
+    fn stream_error(&self) -> Option<u32> {
+        match self {
+            ReadToEndError::ReadError(e) => e.stream_error(),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> ErrorKind {
+        ReadToEndError::kind(self)
+    }
+}
+
+pub(crate) fn connection_kind(err: &quinn::ConnectionError) -> ErrorKind {
+    match err {
+        quinn::ConnectionError::ApplicationClosed(_)
+        | quinn::ConnectionError::ConnectionClosed(_)
+        | quinn::ConnectionError::Reset => ErrorKind::SessionClosed,
+        quinn::ConnectionError::LocallyClosed => ErrorKind::LocallyClosed,
+        quinn::ConnectionError::TimedOut => ErrorKind::TimedOut,
+        quinn::ConnectionError::VersionMismatch | quinn::ConnectionError::TransportError(_) => {
+            ErrorKind::Protocol
+        }
+        quinn::ConnectionError::CidsExhausted => ErrorKind::Other,
+    }
+}
+
+pub(crate) fn quinn_write_kind(err: &quinn::WriteError) -> ErrorKind {
+    match err {
+        quinn::WriteError::Stopped(_) => ErrorKind::StreamStopped,
+        quinn::WriteError::ClosedStream => ErrorKind::LocallyClosed,
+        quinn::WriteError::ConnectionLost(e) => connection_kind(e),
+        quinn::WriteError::ZeroRttRejected => ErrorKind::Other,
+    }
+}
+
+pub(crate) fn quinn_read_kind(err: &quinn::ReadError) -> ErrorKind {
+    match err {
+        quinn::ReadError::Reset(_) => ErrorKind::StreamReset,
+        quinn::ReadError::ClosedStream => ErrorKind::LocallyClosed,
+        quinn::ReadError::ConnectionLost(e) => connection_kind(e),
+        quinn::ReadError::IllegalOrderedRead => ErrorKind::InvalidInput,
+        quinn::ReadError::ZeroRttRejected => ErrorKind::Other,
+    }
 }
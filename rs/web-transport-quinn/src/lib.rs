@@ -12,6 +12,10 @@
 //! Both endpoints can create and close streams (including an error code) with no overhead.
 //! You can think of them as TCP connections, but shared over a single QUIC connection.
 //!
+//! Streams compete for bandwidth according to [SendStream::set_priority], which quinn enforces
+//! among data it has already buffered. For strict ordering across a group of streams, see
+//! [SendOrdering::Strict].
+//!
 //! # Datagrams
 //! QUIC datagrams are unordered, unreliable, and not flow-controlled.
 //! Both endpoints can send datagrams below the MTU size (~1.2kb minimum) and they might arrive out of order or not at all.
@@ -19,49 +23,92 @@
 //!
 //! # Limitations
 //! WebTransport is able to be pooled with HTTP/3 and multiple WebTransport sessions.
-//! This crate avoids that complexity, doing the bare minimum to support a single WebTransport session that owns the entire QUIC connection.
+//! By default, this crate does the bare minimum to support a single WebTransport session that owns the entire QUIC connection.
 //! If you want to support HTTP/3 on the same host/port, you should use another crate (ex. `h3-webtransport`).
-//! If you want to support multiple WebTransport sessions over the same QUIC connection... you should just dial a new QUIC connection instead.
+//! A server can accept multiple WebTransport sessions over the same QUIC connection with a [SessionPool].
 
 // External
 mod client;
+mod datagram;
+mod driver;
 mod error;
+mod memory;
+mod pool;
 mod recv;
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+mod reload;
+mod scheduler;
 mod send;
 mod server;
 mod session;
+mod socket;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
 
 pub use client::*;
+pub use datagram::DatagramRoute;
+pub use driver::SessionDriver;
 pub use error::*;
+pub use pool::SessionPool;
 pub use recv::*;
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+pub use reload::*;
+pub use scheduler::SendOrdering;
 pub use send::*;
 pub use server::*;
 pub use session::*;
+pub use socket::*;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringRuntime;
 
 // Internal
 mod connect;
+mod early;
 mod settings;
 
 use connect::*;
 use settings::*;
+use web_transport_trait::{happy_eyeballs, FaultInjector};
 
 // Required to access web_transport_quinn::proto::ConnectError wrapped in ClientError
 pub use connect::ConnectError;
 
+/// A backend-independent classification returned by each error's `kind()`.
+pub use web_transport_trait::ErrorKind;
+/// The time source for timeouts; see [ClientBuilder::with_clock].
+pub use web_transport_trait::{Clock, TokioClock};
+/// A byte budget shared across sessions; see [ServerBuilder::with_memory_budget].
+pub use web_transport_trait::{DatagramOptions, MemoryAccount, MemoryBudget, StreamOptions};
+
 /// The HTTP/3 ALPN is required when negotiating a QUIC connection.
 pub const ALPN: &str = "h3";
 
 /// Export our simple crypto provider.
 pub mod crypto;
 
+pub mod tls;
+
 /// Re-export the underlying QUIC implementation.
 pub use quinn;
 
+/// Re-export rustls because its config and certificate types are in the public API.
+pub use rustls;
+
+/// The certificate and key types accepted by the builders, from [rustls::pki_types].
+pub use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
 /// Re-export the http crate because it's in the public API.
 pub use http;
 
 /// Re-export the generic WebTransport implementation.
 pub use web_transport_trait as generic;
 
+/// The delay before racing the next resolved address.
+///
+/// See [ClientBuilder::with_connection_attempt_delay].
+pub use web_transport_trait::happy_eyeballs::DEFAULT_CONNECTION_ATTEMPT_DELAY;
+/// Faults to inject for resilience testing; see [ClientBuilder::with_faults].
+pub use web_transport_trait::Faults;
+
 /// Re-export the WebTransport protocol implementation.
 pub use web_transport_proto as proto;
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use crate::generic::{MemoryAccount, MemoryBudget, MemoryPermit};
+
+// quinn owns every stream and datagram buffer, so the budget can't be charged
+// byte by byte. Instead each session reserves its flow-control windows, which
+// bound what quinn will buffer for it, and shrinks them to fit the budget.
+
+// The windows a session asks for in each direction when the budget has room.
+const WINDOW: u64 = 8 * 1024 * 1024;
+
+// The smallest windows a session runs with; below this it waits for the budget.
+// Connections start here, so a handshake in flight is covered by the floor.
+pub(crate) const MIN_WINDOW: u64 = 64 * 1024;
+
+// quinn can't resize datagram buffers at runtime, so they're pinned small and
+// reserved up front.
+pub(crate) const DATAGRAM_BUFFER: usize = 64 * 1024;
+
+/// Bytes a session has reserved from a [MemoryBudget], released when the last
+/// clone of the session is dropped.
+#[derive(Clone)]
+pub(crate) struct Reservation {
+    pub account: MemoryAccount,
+    _permit: Arc<MemoryPermit>,
+}
+
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+/// Start every connection at the floor so nothing is buffered beyond what a
+/// session will reserve once accepted.
+pub(crate) fn configure(transport: &mut quinn::TransportConfig) {
+    transport
+        .receive_window(quinn::VarInt::from_u64(MIN_WINDOW).unwrap())
+        .send_window(MIN_WINDOW)
+        .datagram_receive_buffer_size(Some(DATAGRAM_BUFFER))
+        .datagram_send_buffer_size(DATAGRAM_BUFFER);
+}
+
+/// Reserve windows for `conn`, waiting while the budget can't cover the floor,
+/// then widen them with whatever else the budget can spare.
+pub(crate) async fn reserve(conn: &quinn::Connection, budget: &MemoryBudget) -> Reservation {
+    let account = budget.account();
+
+    let floor = 2 * MIN_WINDOW as usize + 2 * DATAGRAM_BUFFER;
+    let mut permit = account.reserve(floor).await;
+    if let Some(extra) = account.try_reserve_up_to(2 * (WINDOW - MIN_WINDOW) as usize) {
+        permit.absorb(extra);
+    }
+
+    let window = MIN_WINDOW + (permit.size() - floor) as u64 / 2;
+    conn.set_receive_window(quinn::VarInt::from_u64(window).unwrap());
+    conn.set_send_window(window);
+
+    Reservation {
+        account,
+        _permit: Arc::new(permit),
+    }
+}
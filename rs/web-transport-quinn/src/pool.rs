@@ -0,0 +1,558 @@
+//! Multiple WebTransport sessions over one HTTP/3 connection.
+//!
+//! A [Session](crate::Session) normally owns its QUIC connection and reads every stream
+//! and datagram on it. Pooled sessions share the connection, so a background task reads
+//! them instead, routing each to the session named in its header. Each session gets its
+//! own queues, which are dropped when the session closes or the connection does.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, Weak},
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
+use tokio::{
+    io::AsyncReadExt,
+    sync::{mpsc, watch},
+};
+
+use crate::{
+    early::EARLY_BUFFER,
+    proto::{codes, ConnectRequest, ConnectResponse, Frame, StreamUni, VarInt},
+    session::Header,
+    Connecting, Request, ServerError, Settings,
+};
+
+// Datagrams queued per session while it isn't being read; newer ones are dropped beyond this.
+const DATAGRAM_QUEUE: usize = 256;
+
+pub(crate) type RoutedUni = (quinn::RecvStream, Header);
+pub(crate) type RoutedBi = (quinn::SendStream, quinn::RecvStream, Header);
+
+/// Accepts multiple WebTransport sessions over a single HTTP/3 connection.
+///
+/// A [Server](crate::Server) gives every session its own QUIC connection. A pool instead
+/// advertises `max_sessions` in its SETTINGS and returns a [Request] for each CONNECT the
+/// client sends, answered like any other. A background task reads every stream and
+/// datagram on the connection and routes it to the session named in its header, so each
+/// [Session](crate::Session) only sees its own. Closing a session leaves the connection
+/// and the other sessions alone; the connection is closed once the pool and every
+/// session are dropped.
+///
+/// Streams that arrive before their session is accepted are buffered, up to 16 of each
+/// kind, and any more are rejected with WT_BUFFERED_STREAM_REJECTED. Streams for a
+/// closed session are rejected with WT_SESSION_GONE, and datagrams for a session that
+/// isn't known yet are dropped. Custom HTTP/3 stream types aren't routed, so
+/// [Session::accept_raw_uni](crate::Session::accept_raw_uni) never returns.
+pub struct SessionPool {
+    conn: quinn::Connection,
+    settings: Arc<Settings>,
+    requests: mpsc::UnboundedReceiver<(Connecting, PoolRoute)>,
+
+    // Dropped when the pool and every session are dropped.
+    #[allow(dead_code)]
+    drop: Arc<PoolDrop>,
+}
+
+impl SessionPool {
+    /// Exchange SETTINGS on a new connection, allowing up to `max_sessions` at once.
+    ///
+    /// The connection must use the HTTP/3 [ALPN](crate::ALPN). A CONNECT request over
+    /// the limit is answered with 429 (Too Many Requests) and never returned.
+    pub async fn new(conn: quinn::Connection, max_sessions: u32) -> Result<Self, ServerError> {
+        let settings = Settings::connect_with(&conn, None, max_sessions).await?;
+
+        let shared = Arc::new(Shared {
+            max_sessions: max_sessions as usize,
+            state: Default::default(),
+        });
+        let drop = Arc::new(PoolDrop { conn: conn.clone() });
+
+        let (send, requests) = mpsc::unbounded_channel();
+        tokio::spawn(shared.run(conn.clone(), Arc::downgrade(&drop), send));
+
+        // Read the control stream here rather than in whichever session comes first.
+        let settings = Arc::new(settings);
+        if let Some(control) = settings.run() {
+            tokio::spawn(control);
+        }
+
+        Ok(Self {
+            conn,
+            settings,
+            requests,
+            drop,
+        })
+    }
+
+    /// Wait for the next CONNECT request, or None once the connection is closed.
+    pub async fn accept(&mut self) -> Option<Request> {
+        let (connect, route) = self.requests.recv().await?;
+        Some(Request::pooled(
+            self.conn.clone(),
+            self.settings.clone(),
+            connect,
+            route,
+        ))
+    }
+
+    /// Returns the underlying QUIC connection.
+    pub fn conn(&self) -> &quinn::Connection {
+        &self.conn
+    }
+}
+
+// Closes the connection once the pool and every session are dropped.
+struct PoolDrop {
+    conn: quinn::Connection,
+}
+
+impl Drop for PoolDrop {
+    fn drop(&mut self) {
+        if self.conn.close_reason().is_none() {
+            let code = quinn::VarInt::from_u64(codes::h3::NO_ERROR).unwrap();
+            self.conn.close(code, b"");
+        }
+    }
+}
+
+// A stream routed by its session ID.
+enum Incoming {
+    Uni(RoutedUni),
+    Bi(RoutedBi),
+}
+
+impl Incoming {
+    fn reject(self, code: u64) {
+        let code = quinn::VarInt::from_u64(code).unwrap();
+        match self {
+            Self::Uni((mut recv, _)) => {
+                recv.stop(code).ok();
+            }
+            Self::Bi((mut send, mut recv, _)) => {
+                send.reset(code).ok();
+                recv.stop(code).ok();
+            }
+        }
+    }
+}
+
+// The queues for one session ID.
+struct Route {
+    uni: mpsc::UnboundedSender<RoutedUni>,
+    bi: mpsc::UnboundedSender<RoutedBi>,
+    datagrams: mpsc::Sender<Bytes>,
+
+    // The receiving half, held here until the session is accepted.
+    inbox: Option<Inbox>,
+
+    // Whether the CONNECT request arrived, rather than just a stream naming the session.
+    requested: bool,
+}
+
+pub(crate) struct Inbox {
+    pub uni: mpsc::UnboundedReceiver<RoutedUni>,
+    pub bi: mpsc::UnboundedReceiver<RoutedBi>,
+    pub datagrams: mpsc::Receiver<Bytes>,
+}
+
+impl Route {
+    fn new() -> Self {
+        let (uni, uni_recv) = mpsc::unbounded_channel();
+        let (bi, bi_recv) = mpsc::unbounded_channel();
+        let (datagrams, datagrams_recv) = mpsc::channel(DATAGRAM_QUEUE);
+
+        Self {
+            uni,
+            bi,
+            datagrams,
+            inbox: Some(Inbox {
+                uni: uni_recv,
+                bi: bi_recv,
+                datagrams: datagrams_recv,
+            }),
+            requested: false,
+        }
+    }
+
+    // Queue the stream, handing it back with a code to reject it with if it doesn't fit.
+    fn push(&self, stream: Incoming) -> Option<(Incoming, u64)> {
+        // Only buffer a few streams of each kind until the session is accepted.
+        if let Some(inbox) = &self.inbox {
+            let buffered = match &stream {
+                Incoming::Uni(_) => inbox.uni.len(),
+                Incoming::Bi(_) => inbox.bi.len(),
+            };
+            if buffered >= EARLY_BUFFER {
+                return Some((stream, codes::h3::WT_BUFFERED_STREAM_REJECTED));
+            }
+        }
+
+        // The session may have been dropped while its route is being removed.
+        let gone = codes::h3::WT_SESSION_GONE;
+        match stream {
+            Incoming::Uni(uni) => self.uni.send(uni).err().map(|e| (Incoming::Uni(e.0), gone)),
+            Incoming::Bi(bi) => self.bi.send(bi).err().map(|e| (Incoming::Bi(e.0), gone)),
+        }
+    }
+}
+
+impl Inbox {
+    // Reject everything buffered for a session that was never accepted.
+    fn reject(&mut self, code: u64) {
+        while let Ok(uni) = self.uni.try_recv() {
+            Incoming::Uni(uni).reject(code);
+        }
+        while let Ok(bi) = self.bi.try_recv() {
+            Incoming::Bi(bi).reject(code);
+        }
+    }
+}
+
+// The routing state, shared by the background task and every session.
+struct Shared {
+    max_sessions: usize,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    routes: HashMap<VarInt, Route>,
+
+    // Sessions that were closed or rejected, so late streams aren't buffered again.
+    gone: HashSet<VarInt>,
+
+    // The routes with a CONNECT request, counted against the advertised limit.
+    sessions: usize,
+
+    // We need to keep a reference to the qpack streams if the endpoint (incorrectly) creates them.
+    qpack: Vec<quinn::RecvStream>,
+}
+
+impl PoolState {
+    // Find or create the route for a session ID, or return the code to reject its stream with.
+    fn route(&mut self, session_id: VarInt, max_sessions: usize) -> Result<&Route, u64> {
+        if self.gone.contains(&session_id) {
+            return Err(codes::h3::WT_SESSION_GONE);
+        }
+
+        // Sessions are named after the client-initiated bidirectional stream of their CONNECT.
+        if !session_id.into_inner().is_multiple_of(4) {
+            return Err(codes::h3::ID_ERROR);
+        }
+
+        // Buffer for as many sessions as we'd accept, in case the CONNECT is still on its way.
+        let unrequested = self.routes.len() - self.sessions;
+        if !self.routes.contains_key(&session_id) && unrequested >= max_sessions {
+            return Err(codes::h3::WT_BUFFERED_STREAM_REJECTED);
+        }
+
+        Ok(self.routes.entry(session_id).or_insert_with(Route::new))
+    }
+}
+
+impl Shared {
+    // Read every stream and datagram on the connection until it's closed.
+    async fn run(
+        self: Arc<Self>,
+        conn: quinn::Connection,
+        pool: Weak<PoolDrop>,
+        requests: mpsc::UnboundedSender<(Connecting, PoolRoute)>,
+    ) {
+        // Stream headers are decoded concurrently, so a slow stream doesn't hold up the rest.
+        let mut decoding = FuturesUnordered::<BoxFuture<'static, ()>>::new();
+
+        loop {
+            tokio::select! {
+                res = conn.accept_uni() => match res {
+                    Ok(recv) => {
+                        let route = self.clone().route_uni(conn.clone(), recv);
+                        decoding.push(route.boxed());
+                    }
+                    Err(_) => break,
+                },
+                res = conn.accept_bi() => match res {
+                    Ok((send, recv)) => {
+                        let requests = requests.clone();
+                        let route = self.clone().route_bi(send, recv, pool.clone(), requests);
+                        decoding.push(route.boxed());
+                    }
+                    Err(_) => break,
+                },
+                res = conn.read_datagram() => match res {
+                    Ok(datagram) => self.route_datagram(datagram),
+                    Err(_) => break,
+                },
+                Some(()) = decoding.next(), if !decoding.is_empty() => {}
+            }
+        }
+
+        // Drop every route, so the sessions see that the connection is gone.
+        *self.state.lock().unwrap() = PoolState::default();
+    }
+
+    async fn route_uni(self: Arc<Self>, conn: quinn::Connection, mut recv: quinn::RecvStream) {
+        let mut header = Header::default();
+        let typ = match header.read_varint(&mut recv).await {
+            Ok(typ) => StreamUni(typ),
+            Err(err) => {
+                tracing::debug!(?err, "failed to decode unidirectional stream");
+                return;
+            }
+        };
+
+        match typ {
+            StreamUni::WEBTRANSPORT => match header.read_varint(&mut recv).await {
+                Ok(session_id) => self.deliver(session_id, Incoming::Uni((recv, header))),
+                Err(err) => tracing::debug!(?err, "failed to decode unidirectional stream"),
+            },
+            StreamUni::QPACK_ENCODER | StreamUni::QPACK_DECODER => {
+                self.state.lock().unwrap().qpack.push(recv);
+            }
+            typ => match typ.reject_code(quinn::VarInt::from(recv.id()).into_inner()) {
+                Some(code) => {
+                    tracing::warn!(?typ, "rejecting forbidden unidirectional stream");
+                    let code = quinn::VarInt::from_u64(code).unwrap();
+                    conn.close(code, b"unexpected stream type");
+                }
+                None => tracing::debug!(?typ, "ignoring unknown unidirectional stream"),
+            },
+        }
+    }
+
+    async fn route_bi(
+        self: Arc<Self>,
+        send: quinn::SendStream,
+        mut recv: quinn::RecvStream,
+        pool: Weak<PoolDrop>,
+        requests: mpsc::UnboundedSender<(Connecting, PoolRoute)>,
+    ) {
+        // Read exactly the frame type, so a CONNECT request can be parsed from the start.
+        let (typ, raw) = match read_varint_exact(&mut recv).await {
+            Ok(res) => res,
+            Err(err) => {
+                tracing::debug!(?err, "failed to decode bidirectional stream");
+                return;
+            }
+        };
+
+        if Frame(typ) == Frame::WEBTRANSPORT {
+            let mut header = Header::default();
+            match header.read_varint(&mut recv).await {
+                Ok(session_id) => self.deliver(session_id, Incoming::Bi((send, recv, header))),
+                Err(err) => tracing::debug!(?err, "failed to decode bidirectional stream"),
+            }
+            return;
+        }
+
+        // Anything else is a request, so put the frame type back in front of it.
+        let mut reader = AsyncReadExt::chain(&raw[..], &mut recv);
+        let request = match ConnectRequest::read(&mut reader).await {
+            Ok(request) => request,
+            Err(err) => {
+                tracing::debug!(?err, "failed to decode CONNECT request");
+                return;
+            }
+        };
+        tracing::debug!(?request, "received CONNECT request");
+
+        let connect = Connecting {
+            request,
+            send,
+            recv,
+        };
+
+        let route = pool
+            .upgrade()
+            .and_then(|pool| self.register(connect.session_id(), pool));
+        match route {
+            Some(route) => {
+                requests.send((connect, route)).ok();
+            }
+            None => {
+                tracing::debug!("rejecting CONNECT request over the session limit");
+                let mut send = connect.send;
+                let response = ConnectResponse::new(http::StatusCode::TOO_MANY_REQUESTS);
+                if response.write(&mut send).await.is_ok() {
+                    send.finish().ok();
+                }
+            }
+        }
+    }
+
+    fn route_datagram(&self, mut datagram: Bytes) {
+        let Ok(session_id) = VarInt::decode(&mut datagram) else {
+            tracing::trace!("dropping datagram without a session ID");
+            return;
+        };
+
+        let state = self.state.lock().unwrap();
+        let Some(route) = state.routes.get(&session_id) else {
+            tracing::trace!(?session_id, "dropping datagram for unknown session");
+            return;
+        };
+
+        // Like streams, only buffer a few until the session is accepted.
+        if let Some(inbox) = &route.inbox {
+            if inbox.datagrams.len() >= EARLY_BUFFER {
+                return;
+            }
+        }
+
+        // Datagrams are unreliable, so drop them when the session falls behind.
+        route.datagrams.try_send(datagram).ok();
+    }
+
+    fn deliver(&self, session_id: VarInt, stream: Incoming) {
+        let rejected = {
+            let mut state = self.state.lock().unwrap();
+            match state.route(session_id, self.max_sessions) {
+                Ok(route) => route.push(stream),
+                Err(code) => Some((stream, code)),
+            }
+        };
+
+        if let Some((stream, code)) = rejected {
+            tracing::debug!(?session_id, code, "rejecting stream");
+            stream.reject(code);
+        }
+    }
+
+    // Claim a route for a CONNECT request, or None if there are too many sessions.
+    fn register(self: &Arc<Self>, session_id: VarInt, pool: Arc<PoolDrop>) -> Option<PoolRoute> {
+        let mut state = self.state.lock().unwrap();
+        if state.sessions >= self.max_sessions {
+            return None;
+        }
+
+        state.sessions += 1;
+        state
+            .routes
+            .entry(session_id)
+            .or_insert_with(Route::new)
+            .requested = true;
+
+        Some(PoolRoute {
+            shared: self.clone(),
+            session_id,
+            pool,
+        })
+    }
+
+    // Stop routing to a session, rejecting anything it never accepted.
+    fn remove(&self, session_id: VarInt) {
+        let route = {
+            let mut state = self.state.lock().unwrap();
+            let Some(route) = state.routes.remove(&session_id) else {
+                return;
+            };
+            if route.requested {
+                state.sessions -= 1;
+            }
+            state.gone.insert(session_id);
+            route
+        };
+
+        if let Some(mut inbox) = route.inbox {
+            inbox.reject(codes::h3::WT_SESSION_GONE);
+        }
+    }
+}
+
+/// A session's claim on the pool, released when dropped.
+pub(crate) struct PoolRoute {
+    shared: Arc<Shared>,
+    session_id: VarInt,
+
+    // Keeps the connection open while the session is alive.
+    #[allow(dead_code)]
+    pool: Arc<PoolDrop>,
+}
+
+impl PoolRoute {
+    // Hand over everything routed to the session so far, which stops the buffering limit.
+    fn take_inbox(&self) -> Inbox {
+        let mut state = self.shared.state.lock().unwrap();
+        let inbox = state
+            .routes
+            .get_mut(&self.session_id)
+            .and_then(|route| route.inbox.take());
+
+        // The route is only missing once the connection is gone, so hand over empty queues.
+        inbox.unwrap_or_else(|| Route::new().inbox.unwrap())
+    }
+}
+
+impl Drop for PoolRoute {
+    fn drop(&mut self) {
+        self.shared.remove(self.session_id);
+    }
+}
+
+/// The parts of the pool owned by an accepted session.
+pub(crate) struct PooledSession {
+    route: PoolRoute,
+    datagrams: tokio::sync::Mutex<mpsc::Receiver<Bytes>>,
+
+    // Set once the session is closed, while the connection may stay open.
+    closed: watch::Sender<bool>,
+}
+
+impl PooledSession {
+    /// Take over the route, returning the streams for the session's accept task.
+    pub fn new(
+        route: PoolRoute,
+    ) -> (
+        Self,
+        mpsc::UnboundedReceiver<RoutedUni>,
+        mpsc::UnboundedReceiver<RoutedBi>,
+    ) {
+        let inbox = route.take_inbox();
+        let this = Self {
+            route,
+            datagrams: tokio::sync::Mutex::new(inbox.datagrams),
+            closed: watch::Sender::new(false),
+        };
+
+        (this, inbox.uni, inbox.bi)
+    }
+
+    /// Receive the next datagram, without the session ID, or None once the session is gone.
+    pub async fn read_datagram(&self) -> Option<Bytes> {
+        self.datagrams.lock().await.recv().await
+    }
+
+    /// Stop routing to the session and wake anything waiting for it to close.
+    pub fn close(&self) {
+        self.route.shared.remove(self.route.session_id);
+        self.closed.send_replace(true);
+    }
+
+    pub async fn closed(&self) {
+        self.closed
+            .subscribe()
+            .wait_for(|closed| *closed)
+            .await
+            .ok();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+}
+
+// Read a varint without reading any further, returning the bytes it was encoded with.
+async fn read_varint_exact(
+    recv: &mut quinn::RecvStream,
+) -> Result<(VarInt, Vec<u8>), quinn::ReadExactError> {
+    let mut buf = vec![0; 1];
+    recv.read_exact(&mut buf).await?;
+
+    // The two high bits of the first byte give the length.
+    buf.resize(1 << (buf[0] >> 6), 0);
+    recv.read_exact(&mut buf[1..]).await?;
+
+    let v = VarInt::decode(&mut &buf[..]).expect("varint is complete");
+    Ok((v, buf))
+}
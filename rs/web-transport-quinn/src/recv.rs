@@ -2,10 +2,11 @@ use std::{
     io,
     pin::Pin,
     sync::{Arc, OnceLock},
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
+use futures::FutureExt;
 
 use crate::{ReadError, ReadExactError, ReadToEndError, SessionError};
 
@@ -14,19 +15,66 @@ use crate::{ReadError, ReadExactError, ReadToEndError, SessionError};
 pub struct RecvStream {
     inner: quinn::RecvStream,
     error: Arc<OnceLock<SessionError>>,
+
+    // Payload that arrived in the same chunk as the stream header, served before
+    // reading from `inner`. `buffered_offset` is its position in the QUIC stream.
+    buffered: Bytes,
+    buffered_offset: u64,
+
+    // Stopped with this WebTransport code if dropped before reading everything.
+    drop_code: u32,
+
+    // The peer's reset code, once a read or `received_reset` has seen it.
+    reset: OnceLock<u32>,
 }
 
 impl RecvStream {
-    pub(crate) fn new(stream: quinn::RecvStream, error: Arc<OnceLock<SessionError>>) -> Self {
+    pub(crate) fn new(
+        stream: quinn::RecvStream,
+        error: Arc<OnceLock<SessionError>>,
+        drop_code: u32,
+    ) -> Self {
         Self {
             inner: stream,
             error,
+            buffered: Bytes::new(),
+            buffered_offset: 0,
+            drop_code,
+            reset: OnceLock::new(),
         }
     }
 
+    /// Attach payload bytes that were read along with the stream header.
+    pub(crate) fn with_buffered(mut self, buffered: Bytes, offset: u64) -> Self {
+        self.buffered = buffered;
+        self.buffered_offset = offset;
+        self
+    }
+
+    /// Data that has already been received but not yet read.
+    ///
+    /// When a stream is accepted, any payload that arrived in the same packet as
+    /// the WebTransport header is kept here instead of being dropped back into
+    /// QUIC, so a short stream can be inspected (or fully read) without waiting
+    /// on another wakeup. Every read method consumes these bytes first.
+    pub fn peek(&self) -> &[u8] {
+        &self.buffered
+    }
+
+    // Take up to `max` bytes from the front of the buffered payload.
+    fn take_buffered(&mut self, max: usize) -> quinn::Chunk {
+        let bytes = self.buffered.split_to(max.min(self.buffered.len()));
+        let offset = self.buffered_offset;
+        self.buffered_offset += bytes.len() as u64;
+        quinn::Chunk { offset, bytes }
+    }
+
     /// Replace connection-level errors with the stored session error if available.
     fn map_error(&self, e: impl Into<ReadError>) -> ReadError {
         let e = e.into();
+        if let ReadError::Reset(code) = &e {
+            self.reset.set(*code).ok();
+        }
         if let Some(err) = self.error.get() {
             if matches!(&e, ReadError::SessionError(_) | ReadError::InvalidReset(_)) {
                 return ReadError::SessionError(err.clone());
@@ -35,6 +83,19 @@ impl RecvStream {
         e
     }
 
+    /// Like [Self::map_error], for the [quinn::ReadError] that quinn wraps in an [io::Error].
+    ///
+    /// Otherwise `AsyncRead` users would see the HTTP/3 code of a reset, not the WebTransport one.
+    fn map_io_error(&self, err: io::Error) -> io::Error {
+        match err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<quinn::ReadError>())
+        {
+            Some(e) => io::Error::new(err.kind(), self.map_error(e.clone())),
+            None => err,
+        }
+    }
+
     /// Tell the other end to stop sending data with the given error code. See [`quinn::RecvStream::stop`].
     /// This is a u32 with WebTransport since it shares the error space with HTTP/3.
     pub fn stop(&mut self, code: u32) -> Result<(), quinn::ClosedStream> {
@@ -47,11 +108,22 @@ impl RecvStream {
 
     /// Read some data into the buffer and return the amount read. See [`quinn::RecvStream::read`].
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, ReadError> {
+        if !self.buffered.is_empty() {
+            let chunk = self.take_buffered(buf.len());
+            buf[..chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+            return Ok(Some(chunk.bytes.len()));
+        }
         self.inner.read(buf).await.map_err(|e| self.map_error(e))
     }
 
     /// Fill the entire buffer with data. See [`quinn::RecvStream::read_exact`].
     pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ReadExactError> {
+        let chunk = self.take_buffered(buf.len());
+        let (head, buf) = buf.split_at_mut(chunk.bytes.len());
+        head.copy_from_slice(&chunk.bytes);
+        if buf.is_empty() {
+            return Ok(());
+        }
         self.inner.read_exact(buf).await.map_err(|e| match e {
             quinn::ReadExactError::ReadError(e) => self.map_error(e).into(),
             e => e.into(),
@@ -64,6 +136,9 @@ impl RecvStream {
         max_length: usize,
         ordered: bool,
     ) -> Result<Option<quinn::Chunk>, ReadError> {
+        if !self.buffered.is_empty() {
+            return Ok(Some(self.take_buffered(max_length)));
+        }
         self.inner
             .read_chunk(max_length, ordered)
             .await
@@ -72,21 +147,66 @@ impl RecvStream {
 
     /// Read chunks of data from the stream. See [`quinn::RecvStream::read_chunks`].
     pub async fn read_chunks(&mut self, bufs: &mut [Bytes]) -> Result<Option<usize>, ReadError> {
+        if let (Some(first), false) = (bufs.first_mut(), self.buffered.is_empty()) {
+            *first = self.take_buffered(usize::MAX).bytes;
+            return Ok(Some(1));
+        }
         self.inner
             .read_chunks(bufs)
             .await
             .map_err(|e| self.map_error(e))
     }
 
-    /// Read until the end of the stream or the limit is hit. See [`quinn::RecvStream::read_to_end`].
+    /// Read every chunk that has already been received, up to `max_total` bytes in total.
+    ///
+    /// Only waits if nothing is ready, so forwarding a burst costs one wakeup rather than
+    /// one per chunk. Returns None if the stream has been finished.
+    pub async fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> Result<Option<Vec<Bytes>>, ReadError> {
+        let Some(first) = self.read_chunk(max_total, true).await? else {
+            return Ok(None);
+        };
+
+        let mut remaining = max_total - first.bytes.len();
+        let mut chunks = vec![first.bytes];
+
+        // Keep going until a read would wait. A reset discards what the peer sent anyway.
+        while remaining > 0 {
+            match self.read_chunk(remaining, true).now_or_never() {
+                Some(Ok(Some(chunk))) => {
+                    remaining -= chunk.bytes.len();
+                    chunks.push(chunk.bytes);
+                }
+                Some(Err(err)) => return Err(err),
+                Some(Ok(None)) | None => break,
+            }
+        }
+
+        Ok(Some(chunks))
+    }
+
+    /// Read until the end of the stream, failing with [ReadToEndError::TooLong] if it exceeds `size_limit`. See [`quinn::RecvStream::read_to_end`].
     pub async fn read_to_end(&mut self, size_limit: usize) -> Result<Vec<u8>, ReadToEndError> {
-        self.inner
-            .read_to_end(size_limit)
+        let head = self.take_buffered(usize::MAX).bytes;
+        let limit = size_limit
+            .checked_sub(head.len())
+            .ok_or(ReadToEndError::TooLong { limit: size_limit })?;
+        let tail = self
+            .inner
+            .read_to_end(limit)
             .await
-            .map_err(|e| match e {
-                quinn::ReadToEndError::Read(e) => self.map_error(e).into(),
-                e => e.into(),
-            })
+            .map_err(|e| -> ReadToEndError {
+                match e {
+                    quinn::ReadToEndError::Read(e) => self.map_error(e).into(),
+                    quinn::ReadToEndError::TooLong => ReadToEndError::TooLong { limit: size_limit },
+                }
+            })?;
+        if head.is_empty() {
+            return Ok(tail);
+        }
+        Ok([&head[..], &tail[..]].concat())
     }
 
     /// Block until the stream has been reset and return the error code. See [`quinn::RecvStream::received_reset`].
@@ -95,7 +215,13 @@ impl RecvStream {
     pub async fn received_reset(&mut self) -> Result<Option<u32>, SessionError> {
         match self.inner.received_reset().await {
             Ok(None) => Ok(None),
-            Ok(Some(code)) => Ok(web_transport_proto::error_from_http3(code.into_inner())),
+            Ok(Some(code)) => {
+                let code = web_transport_proto::error_from_http3(code.into_inner());
+                if let Some(code) = code {
+                    self.reset.set(code).ok();
+                }
+                Ok(code)
+            }
             Err(quinn::ResetError::ConnectionLost(conn_err)) => {
                 Err(self.error.get().cloned().unwrap_or_else(|| conn_err.into()))
             }
@@ -103,6 +229,14 @@ impl RecvStream {
         }
     }
 
+    /// The WebTransport code the peer reset the stream with, once a read has failed with
+    /// [ReadError::Reset] or [Self::received_reset] has returned it.
+    ///
+    /// Unlike [Self::received_reset], this doesn't wait.
+    pub fn reset_code(&self) -> Option<u32> {
+        self.reset.get().copied()
+    }
+
     /// Return the underlying QUIC stream ID.
     ///
     /// > **Warning**
@@ -123,7 +257,35 @@ impl tokio::io::AsyncRead for RecvStream {
         cx: &mut Context<'_>,
         buf: &mut tokio::io::ReadBuf,
     ) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.inner).poll_read(cx, buf)
+        if !self.buffered.is_empty() {
+            let n = buf.remaining().min(self.buffered.len());
+            buf.put_slice(&self.buffered[..n]);
+            self.buffered.advance(n);
+            self.buffered_offset += n as u64;
+            return Poll::Ready(Ok(()));
+        }
+        let res = ready!(Pin::new(&mut self.inner).poll_read(cx, buf));
+        Poll::Ready(res.map_err(|e| self.map_io_error(e)))
+    }
+}
+
+impl futures::io::AsyncRead for RecvStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        ready!(tokio::io::AsyncRead::poll_read(self, cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+impl Drop for RecvStream {
+    fn drop(&mut self) {
+        // Quinn would stop with code 0, which isn't a valid WebTransport code.
+        // This fails harmlessly if the stream was already stopped or read to the end.
+        self.stop(self.drop_code).ok();
     }
 }
 
@@ -144,6 +306,13 @@ impl web_transport_trait::RecvStream for RecvStream {
             .map(|r| r.map(|chunk| chunk.bytes))
     }
 
+    async fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> Result<Option<Vec<Bytes>>, Self::Error> {
+        self.read_ready_chunks(max_total).await
+    }
+
     async fn closed(&mut self) -> Result<(), Self::Error> {
         self.received_reset().await?;
         Ok(())
@@ -0,0 +1,191 @@
+//! Server certificates that can be replaced without restarting the server.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::time::Duration;
+//! use web_transport_quinn::{tls, CertReloader, ServerBuilder};
+//!
+//! let (chain, key) = tls::load_pem("cert.pem", "key.pem")?;
+//! let reloader = CertReloader::new(chain, key)?;
+//!
+//! // Pick up renewals, like those from Let's Encrypt, within a minute.
+//! let _watcher = reloader.watch_pem("cert.pem", "key.pem", Duration::from_secs(60));
+//!
+//! let server = ServerBuilder::new().with_cert_reloader(reloader)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+
+use crate::{crypto, tls, ServerError};
+
+/// A server certificate that can be swapped while the server is running.
+///
+/// Pass it to [ServerBuilder::with_cert_reloader](crate::ServerBuilder::with_cert_reloader),
+/// then call [set](Self::set) with a new chain and key, or let [watch_pem](Self::watch_pem)
+/// or [watch_with](Self::watch_with) do it. Each handshake uses the certificate current when
+/// it starts; established connections keep the one they negotiated.
+///
+/// Cloning is cheap; clones share the same certificate.
+#[derive(Clone)]
+pub struct CertReloader {
+    provider: crypto::Provider,
+    current: Arc<RwLock<Arc<CertifiedKey>>>,
+}
+
+impl CertReloader {
+    /// Start with this chain and key, using the [default provider](crypto::default_provider).
+    pub fn new(
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
+    ) -> Result<Self, ServerError> {
+        Self::with_provider(crypto::default_provider(), chain, key)
+    }
+
+    /// Start with this chain and key, loading keys with the given crypto provider.
+    pub fn with_provider(
+        provider: crypto::Provider,
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
+    ) -> Result<Self, ServerError> {
+        let certified = certify(&provider, chain.into_iter().collect(), key.into())?;
+
+        Ok(Self {
+            provider,
+            current: Arc::new(RwLock::new(Arc::new(certified))),
+        })
+    }
+
+    /// Replace the certificate used for new handshakes.
+    ///
+    /// The key is loaded and checked against the leaf certificate first, so on error the
+    /// previous certificate stays in use.
+    pub fn set(
+        &self,
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
+    ) -> Result<(), ServerError> {
+        let certified = certify(&self.provider, chain.into_iter().collect(), key.into())?;
+        *self.current.write().unwrap() = Arc::new(certified);
+        Ok(())
+    }
+
+    /// The certificate used for new handshakes.
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Call `reload` every `interval`, replacing the certificate whenever it returns a new chain and key.
+    ///
+    /// Return `None` when nothing changed. A pair that fails to load is logged and skipped,
+    /// keeping the previous certificate. Reloading stops when the returned [CertWatcher] is dropped.
+    pub fn watch_with<F>(&self, interval: Duration, mut reload: F) -> CertWatcher
+    where
+        F: FnMut() -> Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>
+            + Send
+            + 'static,
+    {
+        let this = self.clone();
+        CertWatcher(tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            // The first tick is immediate, and the current certificate is already loaded.
+            ticks.tick().await;
+
+            loop {
+                ticks.tick().await;
+
+                let Some((chain, key)) = reload() else {
+                    continue;
+                };
+
+                match this.set(chain, key) {
+                    Ok(()) => tracing::info!("reloaded server certificate"),
+                    Err(err) => tracing::warn!(%err, "failed to reload server certificate"),
+                }
+            }
+        }))
+    }
+
+    /// Reload the certificate from PEM files whenever either one is modified, checking every `interval`.
+    ///
+    /// The files are read with [tls::load_pem]. A renewal that replaces the certificate and
+    /// key in two steps may fail to load in between; the next change to either file retries.
+    pub fn watch_pem(
+        &self,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+        interval: Duration,
+    ) -> CertWatcher {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+
+        let mut last = (modified(&cert_path), modified(&key_path));
+
+        self.watch_with(interval, move || {
+            let now = (modified(&cert_path), modified(&key_path));
+            if now == last {
+                return None;
+            }
+            last = now;
+
+            match tls::load_pem(&cert_path, &key_path) {
+                Ok(pair) => Some(pair),
+                Err(err) => {
+                    tracing::warn!(%err, cert = ?cert_path, key = ?key_path, "failed to read server certificate");
+                    None
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Debug for CertReloader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertReloader").finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for CertReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+/// Keeps reloading a [CertReloader] until dropped.
+///
+/// Returned by [CertReloader::watch_with] and [CertReloader::watch_pem].
+pub struct CertWatcher(tokio::task::JoinHandle<()>);
+
+impl Drop for CertWatcher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// Load the key and make sure it belongs to the leaf, like ServerConfig::with_single_cert.
+fn certify(
+    provider: &crypto::Provider,
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<CertifiedKey, ServerError> {
+    Ok(CertifiedKey::from_der(chain, key, provider)?)
+}
+
+// The modification time of a file, or None if it can't be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
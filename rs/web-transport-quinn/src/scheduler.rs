@@ -0,0 +1,220 @@
+//! A user-space send scheduler for strict ordering across a group of streams.
+//!
+//! quinn sends higher-priority streams first, but only among data it has already
+//! buffered: a low-priority stream that wrote first still fills the send buffer, and
+//! streams of equal priority are round-robined. For most applications that's fine.
+//! When an ordering violation is costly, like an enhancement layer of a video frame
+//! overtaking its base layer, switch the session to [SendOrdering::Strict] and put the
+//! related streams in a group with [SendStream::set_group](crate::SendStream::set_group).
+//!
+//! Within a group, only one stream has a write in flight at a time. When several are
+//! waiting, the one with the highest [priority](crate::SendStream::set_priority) goes
+//! next, then the one opened first. A write that's blocked on flow control holds the
+//! group until quinn accepts the data, so lower-priority streams can't slip in ahead.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// How a session schedules writes on its send streams.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SendOrdering {
+    /// Leave scheduling to quinn's stream priorities. Groups are ignored.
+    #[default]
+    BestEffort,
+
+    /// Serialize writes within each group of streams in priority order.
+    Strict,
+}
+
+// Waiters are ordered by highest priority first, then by stream ID, which is the order they were opened.
+type Key = (Reverse<i32>, u64);
+
+#[derive(Debug, Default)]
+struct Group {
+    // The stream ID currently writing, if any.
+    active: Option<u64>,
+    waiting: BTreeMap<Key, Waker>,
+}
+
+impl Group {
+    fn wake_next(&self) {
+        if self.active.is_none() {
+            if let Some(waker) = self.waiting.values().next() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+/// Shared by a session and every stream it opens or accepts.
+#[derive(Debug, Default)]
+pub(crate) struct Scheduler {
+    strict: AtomicBool,
+    groups: Mutex<HashMap<u64, Group>>,
+}
+
+impl Scheduler {
+    pub fn set_ordering(&self, ordering: SendOrdering) {
+        self.strict
+            .store(ordering == SendOrdering::Strict, Ordering::Relaxed);
+    }
+
+    pub fn ordering(&self) -> SendOrdering {
+        match self.strict.load(Ordering::Relaxed) {
+            true => SendOrdering::Strict,
+            false => SendOrdering::BestEffort,
+        }
+    }
+
+    /// Queue for a turn to write, or None when writes don't need to be scheduled.
+    pub fn turn(self: &Arc<Self>, group: Option<u64>, stream: u64, priority: i32) -> Option<Turn> {
+        let group = group.filter(|_| self.strict.load(Ordering::Relaxed))?;
+
+        Some(Turn {
+            scheduler: self.clone(),
+            group,
+            key: (Reverse(priority), stream),
+            granted: false,
+        })
+    }
+}
+
+/// A place in a group's queue, and once granted, the right to write.
+///
+/// Dropping it gives up the place, or the turn, so a cancelled write never stalls the group.
+#[derive(Debug)]
+pub(crate) struct Turn {
+    scheduler: Arc<Scheduler>,
+    group: u64,
+    key: Key,
+    granted: bool,
+}
+
+impl Turn {
+    pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.granted {
+            return Poll::Ready(());
+        }
+
+        let mut groups = self.scheduler.groups.lock().unwrap();
+        let group = groups.entry(self.group).or_default();
+
+        let next = group.waiting.keys().next();
+        if group.active.is_none() && next.is_none_or(|next| *next >= self.key) {
+            group.waiting.remove(&self.key);
+            group.active = Some(self.key.1);
+            self.granted = true;
+            return Poll::Ready(());
+        }
+
+        group.waiting.insert(self.key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let mut groups = self.scheduler.groups.lock().unwrap();
+        let Some(group) = groups.get_mut(&self.group) else {
+            return;
+        };
+
+        group.waiting.remove(&self.key);
+        if self.granted {
+            group.active = None;
+        }
+
+        if group.active.is_none() && group.waiting.is_empty() {
+            groups.remove(&self.group);
+        } else {
+            group.wake_next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::poll_fn;
+
+    fn strict() -> Arc<Scheduler> {
+        let scheduler = Arc::new(Scheduler::default());
+        scheduler.set_ordering(SendOrdering::Strict);
+        scheduler
+    }
+
+    #[test]
+    fn best_effort_skips_the_queue() {
+        let scheduler = Arc::new(Scheduler::default());
+        assert!(scheduler.turn(Some(0), 0, 0).is_none());
+
+        // Ungrouped streams are never scheduled, even when strict.
+        assert!(strict().turn(None, 0, 0).is_none());
+    }
+
+    #[tokio::test]
+    async fn highest_priority_goes_next() {
+        let scheduler = strict();
+
+        let mut active = scheduler.turn(Some(1), 0, 0).unwrap();
+        poll_fn(|cx| active.poll_ready(cx)).await;
+
+        // Queue a low-priority stream, then a high-priority one behind it.
+        let mut low = scheduler.turn(Some(1), 4, 0).unwrap();
+        let mut high = scheduler.turn(Some(1), 8, 10).unwrap();
+
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        assert!(low.poll_ready(&mut cx).is_pending());
+        assert!(high.poll_ready(&mut cx).is_pending());
+
+        drop(active);
+        assert!(low.poll_ready(&mut cx).is_pending());
+        assert!(high.poll_ready(&mut cx).is_ready());
+
+        drop(high);
+        assert!(low.poll_ready(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn cancelled_waiter_releases_its_place() {
+        let scheduler = strict();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+
+        let mut active = scheduler.turn(Some(1), 0, 0).unwrap();
+        assert!(active.poll_ready(&mut cx).is_ready());
+
+        let mut high = scheduler.turn(Some(1), 4, 10).unwrap();
+        let mut low = scheduler.turn(Some(1), 8, 0).unwrap();
+        assert!(high.poll_ready(&mut cx).is_pending());
+        assert!(low.poll_ready(&mut cx).is_pending());
+
+        drop(high);
+        drop(active);
+        assert!(low.poll_ready(&mut cx).is_ready());
+
+        drop(low);
+        assert!(scheduler.groups.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn groups_are_independent() {
+        let scheduler = strict();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+
+        let mut a = scheduler.turn(Some(1), 0, 0).unwrap();
+        let mut b = scheduler.turn(Some(2), 4, 0).unwrap();
+        assert!(a.poll_ready(&mut cx).is_ready());
+        assert!(b.poll_ready(&mut cx).is_ready());
+    }
+}
@@ -1,13 +1,18 @@
 use std::{
+    future::poll_fn,
     io,
     pin::Pin,
     sync::{Arc, OnceLock},
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 
-use crate::{ClosedStream, SessionError, WriteError};
+use crate::{
+    proto::SessionFlow,
+    scheduler::{Scheduler, Turn},
+    ClosedStream, SessionError, WriteError,
+};
 
 /// A stream that can be used to send bytes. See [`quinn::SendStream`].
 ///
@@ -17,16 +22,45 @@ use crate::{ClosedStream, SessionError, WriteError};
 pub struct SendStream {
     stream: quinn::SendStream,
     error: Arc<OnceLock<SessionError>>,
+
+    // Shared with the session, which decides whether groups are enforced.
+    scheduler: Arc<Scheduler>,
+    group: Option<u64>,
+
+    // Held across a pending `poll_write`, which can't keep it on the stack.
+    turn: Option<Turn>,
+
+    // The peer's session-level flow control, shared with every stream in the session.
+    flow: SessionFlow,
+
+    // The peer's stop code, once a write or `stopped` has seen it.
+    stop: OnceLock<u32>,
 }
 
 impl SendStream {
-    pub(crate) fn new(stream: quinn::SendStream, error: Arc<OnceLock<SessionError>>) -> Self {
-        Self { stream, error }
+    pub(crate) fn new(
+        stream: quinn::SendStream,
+        error: Arc<OnceLock<SessionError>>,
+        scheduler: Arc<Scheduler>,
+        flow: SessionFlow,
+    ) -> Self {
+        Self {
+            stream,
+            error,
+            scheduler,
+            group: None,
+            turn: None,
+            flow,
+            stop: OnceLock::new(),
+        }
     }
 
     /// Replace connection-level errors with the stored session error if available.
     fn map_error(&self, e: impl Into<WriteError>) -> WriteError {
         let e = e.into();
+        if let WriteError::Stopped(code) = &e {
+            self.stop.set(*code).ok();
+        }
         if let Some(err) = self.error.get() {
             if matches!(
                 &e,
@@ -38,6 +72,20 @@ impl SendStream {
         e
     }
 
+    /// Like [Self::map_error], for the [quinn::WriteError] that quinn wraps in an [io::Error].
+    ///
+    /// Otherwise `AsyncWrite` users would see the HTTP/3 code of a STOP_SENDING, not the
+    /// WebTransport one.
+    fn map_io_error(&self, err: io::Error) -> io::Error {
+        match err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<quinn::WriteError>())
+        {
+            Some(e) => io::Error::new(err.kind(), self.map_error(e.clone())),
+            None => err,
+        }
+    }
+
     /// Abruptly reset the stream with the provided error code. See [`quinn::SendStream::reset`].
     /// This is a u32 with WebTransport because we share the error space with HTTP/3.
     pub fn reset(&mut self, code: u32) -> Result<(), ClosedStream> {
@@ -52,7 +100,13 @@ impl SendStream {
     /// Also unlike Quinn, this returns a SessionError, not a StoppedError, because 0-RTT is not supported.
     pub async fn stopped(&self) -> Result<Option<u32>, SessionError> {
         match self.stream.stopped().await {
-            Ok(Some(code)) => Ok(web_transport_proto::error_from_http3(code.into_inner())),
+            Ok(Some(code)) => {
+                let code = web_transport_proto::error_from_http3(code.into_inner());
+                if let Some(code) = code {
+                    self.stop.set(code).ok();
+                }
+                Ok(code)
+            }
             Ok(None) => Ok(None),
             Err(quinn::StoppedError::ConnectionLost(conn_err)) => {
                 Err(self.error.get().cloned().unwrap_or_else(|| conn_err.into()))
@@ -61,43 +115,117 @@ impl SendStream {
         }
     }
 
+    /// The WebTransport code the peer stopped the stream with, once a write has failed
+    /// with [WriteError::Stopped] or [Self::stopped] has returned it.
+    ///
+    /// Unlike [Self::stopped], this doesn't wait.
+    pub fn stop_code(&self) -> Option<u32> {
+        self.stop.get().copied()
+    }
+
+    /// Wait for this stream's turn to write, if the session schedules its group.
+    async fn turn(&self) -> Option<Turn> {
+        let mut turn = self.scheduler.turn(
+            self.group,
+            self.stream.id().index(),
+            self.stream.priority().unwrap_or_default(),
+        )?;
+        poll_fn(|cx| turn.poll_ready(cx)).await;
+        Some(turn)
+    }
+
     // Unfortunately, we have to wrap WriteError for a bunch of functions.
 
+    // Every write is capped by the peer's session-level flow control, so a write may
+    // wait for credit and write less than asked; the `_all` variants loop until done.
+
     /// Write some data to the stream, returning the size written. See [`quinn::SendStream::write`].
     pub async fn write(&mut self, buf: &[u8]) -> Result<usize, WriteError> {
-        self.stream.write(buf).await.map_err(|e| self.map_error(e))
+        let _turn = self.turn().await;
+        self.write_some(buf).await
     }
 
-    /// Write all of the data to the stream. See [`quinn::SendStream::write_all`].
-    pub async fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError> {
-        self.stream
-            .write_all(buf)
+    async fn write_some(&mut self, buf: &[u8]) -> Result<usize, WriteError> {
+        let credit = self.flow.reserve(buf.len()).await;
+        let size = self
+            .stream
+            .write(&buf[..credit.size()])
             .await
-            .map_err(|e| self.map_error(e))
+            .map_err(|e| self.map_error(e))?;
+        credit.sent(size);
+        Ok(size)
+    }
+
+    /// Write all of the data to the stream. See [`quinn::SendStream::write_all`].
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> Result<(), WriteError> {
+        let _turn = self.turn().await;
+        while !buf.is_empty() {
+            let size = self.write_some(buf).await?;
+            buf = &buf[size..];
+        }
+        Ok(())
     }
 
     /// Write chunks of data to the stream. See [`quinn::SendStream::write_chunks`].
     pub async fn write_chunks(&mut self, bufs: &mut [Bytes]) -> Result<quinn::Written, WriteError> {
-        self.stream
-            .write_chunks(bufs)
+        let _turn = self.turn().await;
+
+        let total = bufs.iter().map(Bytes::len).sum();
+        let credit = self.flow.reserve(total).await;
+        if credit.size() == total {
+            let written = self
+                .stream
+                .write_chunks(bufs)
+                .await
+                .map_err(|e| self.map_error(e))?;
+            credit.sent(written.bytes);
+            return Ok(written);
+        }
+
+        // Only part of the data fits, so write what we can of the first chunk.
+        let index = bufs.iter().position(|buf| !buf.is_empty()).unwrap_or(0);
+        let size = credit.size().min(bufs[index].len());
+        let size = self
+            .stream
+            .write(&bufs[index][..size])
             .await
-            .map_err(|e| self.map_error(e))
+            .map_err(|e| self.map_error(e))?;
+        credit.sent(size);
+        bufs[index].advance(size);
+
+        Ok(quinn::Written {
+            bytes: size,
+            chunks: index + usize::from(bufs[index].is_empty()),
+        })
     }
 
     /// Write a chunk of data to the stream. See [`quinn::SendStream::write_chunk`].
     pub async fn write_chunk(&mut self, buf: Bytes) -> Result<(), WriteError> {
-        self.stream
-            .write_chunk(buf)
-            .await
-            .map_err(|e| self.map_error(e))
+        let _turn = self.turn().await;
+        self.write_whole_chunk(buf).await
+    }
+
+    async fn write_whole_chunk(&mut self, mut buf: Bytes) -> Result<(), WriteError> {
+        while !buf.is_empty() {
+            let credit = self.flow.reserve(buf.len()).await;
+            let chunk = buf.split_to(credit.size());
+            let size = chunk.len();
+            self.stream
+                .write_chunk(chunk)
+                .await
+                .map_err(|e| self.map_error(e))?;
+            credit.sent(size);
+        }
+        Ok(())
     }
 
     /// Write all of the chunks of data to the stream. See [`quinn::SendStream::write_all_chunks`].
     pub async fn write_all_chunks(&mut self, bufs: &mut [Bytes]) -> Result<(), WriteError> {
-        self.stream
-            .write_all_chunks(bufs)
-            .await
-            .map_err(|e| self.map_error(e))
+        let _turn = self.turn().await;
+        for buf in bufs {
+            self.write_whole_chunk(std::mem::take(buf)).await?;
+        }
+        Ok(())
     }
 
     /// Mark the stream as finished, such that no more data can be written. See [`quinn::SendStream::finish`].
@@ -116,6 +244,19 @@ impl SendStream {
         self.stream.priority().map_err(Into::into)
     }
 
+    /// Add this stream to a scheduling group, or remove it with `None`.
+    ///
+    /// Groups only matter once the session uses [SendOrdering::Strict](crate::SendOrdering::Strict):
+    /// then only one stream of a group writes at a time, in [priority](Self::set_priority) order.
+    /// Group IDs are chosen by the application and scoped to the session.
+    pub fn set_group(&mut self, group: Option<u64>) {
+        self.group = group;
+    }
+
+    pub fn group(&self) -> Option<u64> {
+        self.group
+    }
+
     /// Return the underlying QUIC stream ID.
     ///
     /// > **Warning**
@@ -134,16 +275,61 @@ impl tokio::io::AsyncWrite for SendStream {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+
+        if this.turn.is_none() {
+            this.turn = this.scheduler.turn(
+                this.group,
+                this.stream.id().index(),
+                this.stream.priority().unwrap_or_default(),
+            );
+        }
+        if let Some(turn) = &mut this.turn {
+            ready!(turn.poll_ready(cx));
+        }
+
+        let credit = ready!(this.flow.poll_send(cx, buf.len()));
+
         // We have to use this syntax because quinn added its own poll_write method.
-        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.stream), cx, buf)
+        let res = tokio::io::AsyncWrite::poll_write(Pin::new(&mut this.stream), cx, &buf[..credit]);
+        let sent = match &res {
+            Poll::Ready(Ok(size)) => *size,
+            _ => 0,
+        };
+        this.flow.unsend(credit - sent);
+
+        if res.is_ready() {
+            this.turn = None;
+        }
+        res.map_err(|e| this.map_io_error(e))
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.stream).poll_flush(cx)
+        let res = ready!(Pin::new(&mut self.stream).poll_flush(cx));
+        Poll::Ready(res.map_err(|e| self.map_io_error(e)))
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.stream).poll_shutdown(cx)
+        let res = ready!(Pin::new(&mut self.stream).poll_shutdown(cx));
+        Poll::Ready(res.map_err(|e| self.map_io_error(e)))
+    }
+}
+
+impl futures::io::AsyncWrite for SendStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(self, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(self, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(self, cx)
     }
 }
 
@@ -1,18 +1,42 @@
-#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
 
-use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use futures::FutureExt;
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{danger::ClientCertVerifier, ResolvesServerCert, WebPkiClientVerifier},
+};
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinSet,
+};
 
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
-use crate::client::{controller_factory, transport_config, ControllerFactory};
+use crate::client::{controller_factory, transport_config, ControllerFactory, IDLE_TIMEOUT};
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
-use crate::{crypto, CongestionControl};
+use crate::{crypto, CertReloader, CongestionControl, SocketOptions};
 use crate::{
-    proto::{ConnectRequest, ConnectResponse},
-    Connecting, ServerError, Session, Settings,
+    driver::Spawner,
+    early::{EarlyBuffer, EARLY_BUFFER},
+    memory,
+    pool::PoolRoute,
+    proto::{
+        codes::{self, DropCodes},
+        AcceptQueue, ConnectRequest, ConnectResponse, HttpRequest, HttpResponse, QueueSlot,
+        SessionLimits, SessionMode, SessionPermit,
+    },
+    Connecting, FaultInjector, Faults, HttpHandler, MemoryBudget, ServerError, Session,
+    SessionDriver, Settings,
 };
+use web_transport_trait::{Clock, TokioClock};
+
+/// Decides whether a request is safe to accept from 0-RTT data, which may be replayed.
+type ReplaySafe = Arc<dyn Fn(&ConnectRequest) -> bool + Send + Sync>;
 
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 /// Construct a WebTransport [Server] using sane defaults.
@@ -22,6 +46,43 @@ pub struct ServerBuilder {
     provider: crypto::Provider,
     addr: std::net::SocketAddr,
     congestion_controller: Option<ControllerFactory>,
+    faults: Option<Faults>,
+    socket_options: SocketOptions,
+    memory_budget: Option<MemoryBudget>,
+    max_field_section_size: Option<u64>,
+    drop_codes: DropCodes,
+    zero_rtt: Option<ReplaySafe>,
+    handshake_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    keep_alive: Option<Duration>,
+    datagram_send_buffer: Option<usize>,
+    session_limits: Option<SessionLimits>,
+    accept_queue: Option<AcceptQueue>,
+    early_buffer: usize,
+    response_timeout: Option<Duration>,
+    allowed_origins: Option<Arc<[url::Origin]>>,
+    http_handler: Option<HttpHandler>,
+    history: usize,
+    keylog: bool,
+    runtime: Arc<dyn quinn::Runtime>,
+    client_auth: ClientAuth,
+    clock: Arc<dyn Clock>,
+}
+
+/// How a [ServerBuilder] authenticates clients.
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+enum ClientAuth {
+    None,
+    // Turned into a verifier when the config is built, so errors surface there.
+    Roots(Vec<CertificateDer<'static>>),
+    Verifier(Arc<dyn ClientCertVerifier>),
+}
+
+/// The certificate a [ServerBuilder] presents.
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+enum ServerCert {
+    Single(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>),
+    Resolver(Arc<dyn ResolvesServerCert>),
 }
 
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
@@ -39,6 +100,27 @@ impl ServerBuilder {
             provider: crypto::default_provider(),
             addr: "[::]:443".parse().unwrap(),
             congestion_controller: None,
+            faults: None,
+            socket_options: SocketOptions::default(),
+            memory_budget: None,
+            max_field_section_size: None,
+            drop_codes: DropCodes::default(),
+            zero_rtt: None,
+            handshake_timeout: None,
+            idle_timeout: Some(IDLE_TIMEOUT),
+            keep_alive: None,
+            datagram_send_buffer: None,
+            session_limits: None,
+            accept_queue: None,
+            early_buffer: EARLY_BUFFER,
+            response_timeout: None,
+            allowed_origins: None,
+            http_handler: None,
+            history: 0,
+            keylog: false,
+            runtime: Arc::new(quinn::TokioRuntime),
+            client_auth: ClientAuth::None,
+            clock: Arc::new(TokioClock),
         }
     }
 
@@ -53,37 +135,368 @@ impl ServerBuilder {
         self
     }
 
+    /// Apply these [SocketOptions] to the UDP socket the server binds.
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Inject the given [Faults] into every session, for resilience testing.
+    ///
+    /// **WARNING**: This deliberately degrades the connection; never enable it in production.
+    pub fn with_faults(mut self, faults: Faults) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Cap the bytes buffered across every session with a shared [MemoryBudget].
+    ///
+    /// Each session reserves its flow-control windows from the budget when it's
+    /// accepted. Sessions get smaller windows (and so less throughput) as the
+    /// budget fills, and [Request::respond] waits while not even the minimum is
+    /// available. Datagram buffers are pinned to a small fixed size.
+    ///
+    /// Clone the budget to share it with other servers, or keep a clone to
+    /// report [MemoryBudget::used] as a metric.
+    pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Measure timeouts with `clock` instead of the tokio timer.
+    ///
+    /// This covers the handshake and response timeouts, stream header timeouts, datagram
+    /// expiry and the wait for the client to acknowledge a close. Defaults to [TokioClock],
+    /// which already follows `tokio::time::pause`; QUIC's own timers, like the idle timeout
+    /// and keep-alives, still use quinn's.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Limit the size of the CONNECT request headers, advertised to clients in SETTINGS.
+    ///
+    /// The size is measured as in RFC 9114: the length of every name and value plus 32
+    /// bytes per header. A larger request is answered with 431 (Request Header Fields
+    /// Too Large) and never returned by [Server::accept]. Unlimited by default, although
+    /// the headers must always fit in a single 64 KiB frame.
+    pub fn with_max_field_section_size(mut self, size: u64) -> Self {
+        self.max_field_section_size = Some(size);
+        self
+    }
+
+    /// Use these codes when a session or stream is dropped without being closed.
+    ///
+    /// Defaults to the values in [codes](crate::proto::codes), which every backend
+    /// shares so a peer can tell why a stream was abandoned.
+    pub fn with_drop_codes(mut self, codes: DropCodes) -> Self {
+        self.drop_codes = codes;
+        self
+    }
+
+    /// Give each client this long to finish its handshake, through to the CONNECT request.
+    ///
+    /// The deadline starts when the first packet arrives, so a slow client can't hold a
+    /// handshake open indefinitely by trickling packets. A client that misses it is
+    /// disconnected, with H3_SETTINGS_ERROR or H3_REQUEST_REJECTED if the QUIC handshake
+    /// was done, and never returned by [Server::accept]. Disabled by default.
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Close a connection after this long without hearing from the client.
+    ///
+    /// Each side advertises its own, and the connection uses the smaller of the two.
+    /// Defaults to 30 seconds.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Send a PING to each client on this interval, keeping idle connections alive.
+    ///
+    /// Disabled by default. A server usually wants to let idle clients time out
+    /// rather than hold them open, so reach for this only when something in the
+    /// path (a NAT or load balancer) drops silent flows sooner than the idle
+    /// timeout would.
+    pub fn with_keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+
+    /// Buffer up to this many bytes of outgoing datagrams per connection that haven't been sent yet.
+    ///
+    /// Once it's full, [Session::send_datagram] discards the oldest buffered datagrams to
+    /// make room, while [Session::send_datagram_wait] waits. Defaults to quinn's 1MiB, and is
+    /// ignored with a [memory budget](Self::with_memory_budget), which pins it small.
+    pub fn with_datagram_send_buffer(mut self, bytes: usize) -> Self {
+        self.datagram_send_buffer = Some(bytes);
+        self
+    }
+
+    /// Accept CONNECT requests sent as 0-RTT data by a resuming client.
+    ///
+    /// An attacker can replay 0-RTT data, so `replay_safe` classifies each early request:
+    /// return true if acting on it twice is harmless (e.g. subscribing to a broadcast).
+    /// Any other request is held until the handshake is confirmed before [Server::accept]
+    /// returns it. Use [Request::is_0rtt] and [Request::confirm] to decide later instead.
+    pub fn with_0rtt(
+        mut self,
+        replay_safe: impl Fn(&ConnectRequest) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.zero_rtt = Some(Arc::new(replay_safe));
+        self
+    }
+
+    /// Cap the number of concurrent sessions per request path, or per a custom key.
+    ///
+    /// Each CONNECT request claims a slot before [Server::accept] returns it. A request
+    /// over the limit is answered with 503 (Service Unavailable) and never returned.
+    /// The slot is released when the request is rejected, or once every clone of the
+    /// accepted [Session] is dropped.
+    ///
+    /// Clone the limits to share them with other servers, or keep a clone to report
+    /// [SessionLimits::active] as a metric.
+    pub fn with_session_limits(mut self, limits: SessionLimits) -> Self {
+        self.session_limits = Some(limits);
+        self
+    }
+
+    /// Bound the sessions that finished their handshake but wait for [Server::accept].
+    ///
+    /// Handshakes run in the background, so a stalled accept loop would otherwise let
+    /// them pile up. A request that finds the queue full is answered with 503 (Service
+    /// Unavailable) and never returned. Unbounded by default.
+    ///
+    /// Keep a clone of the queue to report its length and rejections as metrics.
+    pub fn with_accept_queue(mut self, queue: AcceptQueue) -> Self {
+        self.accept_queue = Some(queue);
+        self
+    }
+
+    /// Hold up to `max` streams (and separately `max` datagrams) sent before the CONNECT response.
+    ///
+    /// Clients may open streams as soon as their request is sent. Those that arrive before
+    /// [Request::respond] are buffered and delivered by the session once it's accepted.
+    /// Any more streams are rejected with WT_BUFFERED_STREAM_REJECTED and any more
+    /// datagrams are dropped. Defaults to 16; 0 rejects every early stream.
+    pub fn with_early_buffer(mut self, max: usize) -> Self {
+        self.early_buffer = max;
+        self
+    }
+
+    /// Reject a request that isn't answered within `timeout` of [Server::accept] returning it.
+    ///
+    /// A [Request] can be queued and answered later, away from the accept loop, e.g. by an
+    /// admission controller. Once the timeout passes, the connection is closed with
+    /// H3_REQUEST_REJECTED, which tells the client it's safe to retry, and answering the
+    /// request fails with [ServerError::RequestTimeout]. Defaults to `None`, which waits forever.
+    pub fn with_response_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// Only accept sessions opened by pages from one of these origins.
+    ///
+    /// A request whose [origin](ConnectRequest::origin) isn't listed is answered with
+    /// 403 (Forbidden) and never returned by [Server::accept]. Browsers always send the
+    /// origin, so a browser-facing server should set this to stop any other site from
+    /// connecting on behalf of its visitors. Requests without an origin, i.e. from
+    /// native clients, are allowed. Only the scheme, host and port of each URL are used.
+    pub fn with_allowed_origins(mut self, origins: impl IntoIterator<Item = url::Url>) -> Self {
+        self.allowed_origins = Some(origins.into_iter().map(|url| url.origin()).collect());
+        self
+    }
+
+    /// Answer plain HTTP/3 requests sent before the CONNECT, so a client can upgrade later.
+    ///
+    /// A client may start with an ordinary request on the connection, such as a GET for
+    /// its configuration, and only then open a WebTransport session. `handler` answers each
+    /// one, in order, before [Server::accept] returns the CONNECT; any request body is left
+    /// unread. Without a handler they're answered with 501 (Not Implemented). The
+    /// [handshake timeout](Self::with_handshake_timeout) still applies until the CONNECT.
+    pub fn with_http_handler(
+        mut self,
+        handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> Self {
+        self.http_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Keep the last `capacity` events of each session, for post-mortem debugging.
+    ///
+    /// See [Session::history]. Defaults to 0, which records nothing.
+    pub fn with_session_history(mut self, capacity: usize) -> Self {
+        self.history = capacity;
+        self
+    }
+
+    /// Append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable.
+    ///
+    /// See [ClientBuilder::with_keylog](crate::ClientBuilder::with_keylog).
+    pub fn with_keylog(mut self, enabled: bool) -> Self {
+        self.keylog = enabled;
+        self
+    }
+
+    /// Require every client to present a certificate chaining to one of these roots (mTLS).
+    ///
+    /// The handshake fails for a client with no certificate, or one from another CA. The
+    /// verified chain is available from [Request::peer_certificates] and
+    /// [Session::peer_certificates]; mapping it to an application identity is up to the
+    /// caller. Building the server fails if `roots` is empty or contains an invalid
+    /// certificate.
+    pub fn with_client_ca_roots(
+        mut self,
+        roots: impl IntoIterator<Item = CertificateDer<'static>>,
+    ) -> Self {
+        self.client_auth = ClientAuth::Roots(roots.into_iter().collect());
+        self
+    }
+
+    /// Verify client certificates (mTLS) with a custom [ClientCertVerifier].
+    ///
+    /// Use this for policies [ServerBuilder::with_client_ca_roots] can't express, such as
+    /// making a certificate optional with
+    /// [WebPkiClientVerifier::builder](rustls::server::WebPkiClientVerifier::builder)`(roots).allow_unauthenticated()`,
+    /// or checking revocation lists.
+    pub fn with_client_cert_verifier(mut self, verifier: Arc<dyn ClientCertVerifier>) -> Self {
+        self.client_auth = ClientAuth::Verifier(verifier);
+        self
+    }
+
+    /// Perform UDP I/O through io_uring, on a dedicated thread, instead of on the endpoint's task.
+    ///
+    /// This cuts syscalls per packet on busy servers, at the cost of a thread and a copy of
+    /// each datagram; see [UringRuntime](crate::UringRuntime) for the trade-offs. Building
+    /// the server fails if the kernel doesn't allow io_uring. Disabled by default.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub fn with_io_uring(mut self, enabled: bool) -> Self {
+        self.runtime = match enabled {
+            true => Arc::new(crate::UringRuntime::new()),
+            false => Arc::new(quinn::TokioRuntime),
+        };
+        self
+    }
+
     /// Supply a certificate used for TLS.
-    // TODO support multiple certs based on...?
+    ///
+    /// The types are re-exported as [crate::CertificateDer] and [crate::PrivateKeyDer], so
+    /// callers don't need to depend on the same version of `rustls-pki-types`.
+    /// See [with_cert_resolver](Self::with_cert_resolver) to serve several hostnames.
     pub fn with_certificate(
         self,
-        chain: Vec<CertificateDer<'static>>,
-        key: PrivateKeyDer<'static>,
+        chain: impl IntoIterator<Item = CertificateDer<'static>>,
+        key: impl Into<PrivateKeyDer<'static>>,
+    ) -> Result<Server, ServerError> {
+        self.build(ServerCert::Single(chain.into_iter().collect(), key.into()))
+    }
+
+    /// Present the certificate held by a [CertReloader], which can be replaced while the server runs.
+    ///
+    /// Each handshake uses the reloader's current certificate, so renewals take effect
+    /// without a restart. See [CertReloader::watch_pem] to reload from files.
+    pub fn with_cert_reloader(self, reloader: CertReloader) -> Result<Server, ServerError> {
+        self.build(ServerCert::Resolver(Arc::new(reloader)))
+    }
+
+    /// Pick the certificate for each handshake with a rustls resolver, such as by SNI.
+    ///
+    /// This lets one endpoint serve several hostnames: [rustls::server::ResolvesServerCertUsingSni]
+    /// maps each name to its own certificate. A handshake fails if the resolver returns None.
+    pub fn with_cert_resolver(
+        self,
+        resolver: Arc<dyn ResolvesServerCert>,
     ) -> Result<Server, ServerError> {
-        let transport = transport_config(self.congestion_controller.as_ref());
-        let config = self.config(chain, key, transport)?;
+        self.build(ServerCert::Resolver(resolver))
+    }
+
+    fn build(self, cert: ServerCert) -> Result<Server, ServerError> {
+        let mut transport = transport_config(
+            self.congestion_controller.as_ref(),
+            self.idle_timeout,
+            self.keep_alive,
+            self.datagram_send_buffer,
+        );
+        if self.memory_budget.is_some() {
+            memory::configure(Arc::get_mut(&mut transport).expect("transport config is unshared"));
+        }
+        let config = self.config(cert, transport)?;
 
-        let server = quinn::Endpoint::server(config, self.addr)
+        let socket = self
+            .socket_options
+            .bind(self.addr)
             .map_err(|e| ServerError::IoError(e.into()))?;
+        let server = quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            Some(config),
+            socket,
+            self.runtime.clone(),
+        )
+        .map_err(|e| ServerError::IoError(e.into()))?;
+
+        let mut server = Server::new(server);
+        server.faults = self.faults;
+        server.memory_budget = self.memory_budget;
+        server.max_field_section_size = self.max_field_section_size;
+        server.drop_codes = self.drop_codes;
+        server.zero_rtt = self.zero_rtt;
+        server.handshake_timeout = self.handshake_timeout;
+        server.session_limits = self.session_limits;
+        server.accept_queue = self.accept_queue;
+        server.early_buffer = self.early_buffer;
+        server.response_timeout = self.response_timeout;
+        server.allowed_origins = self.allowed_origins;
+        server.http_handler = self.http_handler;
+        server.history = self.history;
+        server.clock = self.clock;
 
-        Ok(Server::new(server))
+        Ok(server)
     }
 
     /// Build the quinn config, taking the transport separately so the caller (and the
     /// tests) can tell which one ends up attached.
     fn config(
         &self,
-        chain: Vec<CertificateDer<'static>>,
-        key: PrivateKeyDer<'static>,
+        cert: ServerCert,
         transport: Arc<quinn::TransportConfig>,
     ) -> Result<quinn::ServerConfig, ServerError> {
         // Standard Quinn setup
-        let mut config = rustls::ServerConfig::builder_with_provider(self.provider.clone())
-            .with_protocol_versions(&[&rustls::version::TLS13])?
-            .with_no_client_auth()
-            .with_single_cert(chain, key)?;
+        let builder = rustls::ServerConfig::builder_with_provider(self.provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])?;
+
+        let builder = match &self.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Roots(roots) => {
+                let mut store = rustls::RootCertStore::empty();
+                for root in roots {
+                    store.add(root.clone())?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(store),
+                    self.provider.clone(),
+                )
+                .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            ClientAuth::Verifier(verifier) => builder.with_client_cert_verifier(verifier.clone()),
+        };
+
+        let mut config = match cert {
+            ServerCert::Single(chain, key) => builder.with_single_cert(chain, key)?,
+            ServerCert::Resolver(resolver) => builder.with_cert_resolver(resolver),
+        };
+
+        // quinn only accepts 0 or u32::MAX, and the QUIC limits cap early data anyway.
+        if self.zero_rtt.is_some() {
+            config.max_early_data_size = u32::MAX;
+        }
 
         config.alpn_protocols = vec![crate::ALPN.as_bytes().to_vec()]; // this one is important
+        if self.keylog {
+            config.key_log = Arc::new(rustls::KeyLogFile::new());
+        }
 
         let config: quinn::crypto::rustls::QuicServerConfig = config.try_into().unwrap();
         let mut config = quinn::ServerConfig::with_crypto(Arc::new(config));
@@ -96,7 +509,24 @@ impl ServerBuilder {
 /// A WebTransport server that accepts new sessions.
 pub struct Server {
     endpoint: quinn::Endpoint,
-    accept: FuturesUnordered<BoxFuture<'static, Result<Request, ServerError>>>,
+    accept: JoinSet<Result<(Request, Option<QueueSlot>), ServerError>>,
+    faults: Option<Faults>,
+    memory_budget: Option<MemoryBudget>,
+    max_field_section_size: Option<u64>,
+    drop_codes: DropCodes,
+    zero_rtt: Option<ReplaySafe>,
+    handshake_timeout: Option<Duration>,
+    session_limits: Option<SessionLimits>,
+    accept_queue: Option<AcceptQueue>,
+    early_buffer: usize,
+    response_timeout: Option<Duration>,
+    allowed_origins: Option<Arc<[url::Origin]>>,
+    http_handler: Option<HttpHandler>,
+    history: usize,
+    clock: Arc<dyn Clock>,
+
+    // Set by shutdown(), telling every connection to send GOAWAY.
+    shutdown: watch::Sender<bool>,
 }
 
 impl core::ops::Deref for Server {
@@ -115,54 +545,428 @@ impl Server {
         Self {
             endpoint,
             accept: Default::default(),
+            faults: None,
+            memory_budget: None,
+            max_field_section_size: None,
+            drop_codes: DropCodes::default(),
+            zero_rtt: None,
+            handshake_timeout: None,
+            session_limits: None,
+            accept_queue: None,
+            early_buffer: EARLY_BUFFER,
+            response_timeout: None,
+            allowed_origins: None,
+            http_handler: None,
+            history: 0,
+            clock: Arc::new(TokioClock),
+            shutdown: watch::Sender::new(false),
         }
     }
 
     /// Accept a new WebTransport session Request from a client.
+    ///
+    /// Handshakes run in the background, so they make progress between calls.
+    /// Returns None once the endpoint is closed or [shutdown](Self::shutdown) is called.
     pub async fn accept(&mut self) -> Option<Request> {
+        if *self.shutdown.borrow() {
+            // Abandon any handshakes still in progress.
+            self.accept.abort_all();
+            return None;
+        }
+
         loop {
             tokio::select! {
                 res = self.endpoint.accept() => {
                     let conn = res?;
-                    self.accept.push(Box::pin(async move {
-                        let conn = conn.await?;
-                        Request::accept(conn).await
-                    }));
+                    self.accept.spawn(self.admission().run(conn));
                 }
-                Some(res) = self.accept.next() => {
-                    if let Ok(session) = res {
-                        return Some(session)
+                Some(res) = self.accept.join_next() => {
+                    match res {
+                        // Leaving the queue once the application has the request.
+                        Ok(Ok((request, _slot))) => return Some(self.expire(request)),
+                        Ok(Err(err)) => tracing::debug!(%err, "ignoring failed handshake"),
+                        Err(err) => tracing::warn!(%err, "handshake task failed"),
                     }
                 }
             }
         }
     }
+
+    /// Wait for the next connection attempt, before any of its handshake has run.
+    ///
+    /// An alternative to [Server::accept] for deciding who to talk to before spending CPU
+    /// on TLS, for example to rate-limit by address or to [retry](Incoming::retry) clients
+    /// that haven't proven their address yet. Use one or the other: each connection
+    /// attempt is returned by only one of them. Returns None once the endpoint is closed
+    /// or [shutdown](Self::shutdown) is called.
+    pub async fn accept_incoming(&mut self) -> Option<Incoming> {
+        if *self.shutdown.borrow() {
+            return None;
+        }
+
+        let inner = self.endpoint.accept().await?;
+        Some(Incoming {
+            inner,
+            admission: self.admission(),
+        })
+    }
+
+    // Everything a connection needs from the server to become a Request.
+    fn admission(&self) -> Admission {
+        Admission {
+            faults: self.faults.clone(),
+            memory_budget: self.memory_budget.clone(),
+            max_field_section_size: self.max_field_section_size,
+            drop_codes: self.drop_codes,
+            zero_rtt: self.zero_rtt.clone(),
+            handshake_timeout: self.handshake_timeout,
+            session_limits: self.session_limits.clone(),
+            accept_queue: self.accept_queue.clone(),
+            early_buffer: self.early_buffer,
+            response_timeout: self.response_timeout,
+            allowed_origins: self.allowed_origins.clone(),
+            http_handler: self.http_handler.clone(),
+            history: self.history,
+            clock: self.clock.clone(),
+            shutdown: self.shutdown.subscribe(),
+        }
+    }
+
+    // Start the response timeout once the application has the request.
+    fn expire(&self, mut request: Request) -> Request {
+        request.expiry = self.response_timeout.map(|timeout| request.expiry(timeout));
+        request
+    }
+
+    /// Start a graceful shutdown, sending an HTTP/3 GOAWAY on every connection.
+    ///
+    /// New connections are refused, handshakes still in progress are abandoned, and
+    /// [accept](Self::accept) returns None from now on. Sessions already accepted keep
+    /// running, and so do requests that haven't been answered yet; the client sees them as
+    /// [draining](Session::draining), and if the connection is then closed with `H3_NO_ERROR`,
+    /// [Session::closed] returns [SessionError::GoAway](crate::SessionError::GoAway).
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+        self.endpoint.set_server_config(None);
+    }
+}
+
+/// A connection attempt that hasn't started its handshake, from [Server::accept_incoming].
+///
+/// Nothing has been decrypted yet, so all that's known is where it came from. Dropping it
+/// is the same as [Incoming::ignore].
+pub struct Incoming {
+    inner: quinn::Incoming,
+    admission: Admission,
+}
+
+impl Incoming {
+    /// The client's address.
+    pub fn remote_address(&self) -> std::net::SocketAddr {
+        self.inner.remote_address()
+    }
+
+    /// The local IP address the client sent to, if the platform reports it.
+    pub fn local_ip(&self) -> Option<std::net::IpAddr> {
+        self.inner.local_ip()
+    }
+
+    /// Whether the client has proven it owns [its address](Self::remote_address).
+    ///
+    /// True after a [retry](Self::retry), or when the client presented a token from an
+    /// earlier connection.
+    pub fn remote_address_validated(&self) -> bool {
+        self.inner.remote_address_validated()
+    }
+
+    /// Whether [Self::retry] is allowed, which it isn't once the client has been retried.
+    pub fn may_retry(&self) -> bool {
+        self.inner.may_retry()
+    }
+
+    /// Run the QUIC and HTTP/3 handshakes, returning the request once it arrives.
+    ///
+    /// The request is checked against the server's configuration just like one from
+    /// [Server::accept], and the response timeout starts when this returns.
+    pub async fn accept(self) -> Result<Request, ServerError> {
+        let response_timeout = self.admission.response_timeout;
+        let (mut request, _slot) = self.admission.run(self.inner).await?;
+        request.expiry = response_timeout.map(|timeout| request.expiry(timeout));
+        Ok(request)
+    }
+
+    /// Refuse the connection with CONNECTION_REFUSED, telling the client to try again later.
+    pub fn refuse(self) {
+        self.inner.refuse();
+    }
+
+    /// Ask the client to prove it owns its address, by echoing a token in a new attempt.
+    ///
+    /// That attempt is returned by [Server::accept_incoming] in turn, with
+    /// [Self::remote_address_validated] set. Costs the server almost nothing, so it's
+    /// the usual answer to a flood of spoofed attempts. Gives the attempt back if
+    /// [Self::may_retry] is false.
+    // Handing the attempt back unboxed matches quinn's own Incoming::retry.
+    #[allow(clippy::result_large_err)]
+    pub fn retry(self) -> Result<(), Self> {
+        let admission = self.admission;
+        self.inner.retry().map_err(|err| Self {
+            inner: err.into_incoming(),
+            admission,
+        })
+    }
+
+    /// Drop the attempt without a response, so the client waits until it times out.
+    pub fn ignore(self) {
+        self.inner.ignore();
+    }
+}
+
+// The server's configuration for turning a connection into a Request.
+struct Admission {
+    faults: Option<Faults>,
+    memory_budget: Option<MemoryBudget>,
+    max_field_section_size: Option<u64>,
+    drop_codes: DropCodes,
+    zero_rtt: Option<ReplaySafe>,
+    handshake_timeout: Option<Duration>,
+    session_limits: Option<SessionLimits>,
+    accept_queue: Option<AcceptQueue>,
+    early_buffer: usize,
+    response_timeout: Option<Duration>,
+    allowed_origins: Option<Arc<[url::Origin]>>,
+    http_handler: Option<HttpHandler>,
+    history: usize,
+    clock: Arc<dyn Clock>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Admission {
+    // Run the handshakes and the server's checks, returning the request and its queue slot.
+    async fn run(self, conn: quinn::Incoming) -> Result<(Request, Option<QueueSlot>), ServerError> {
+        let faults = self.faults.map(FaultInjector::new);
+
+        // The QUIC and HTTP/3 handshakes share one deadline, so a slow client can't stretch it.
+        let deadline = self.handshake_timeout.map(|timeout| Deadline {
+            clock: &*self.clock,
+            at: self.clock.now() + timeout,
+        });
+
+        // With 0-RTT, start reading the request before the handshake completes.
+        let (conn, handshake) = match &self.zero_rtt {
+            Some(_) => match conn.accept()?.into_0rtt() {
+                Ok((conn, handshake)) => (conn, Some(handshake)),
+                Err(connecting) => (quic(deadline, connecting).await?, None),
+            },
+            None => (quic(deadline, conn.accept()?).await?, None),
+        };
+        if let Some(stall) = faults.as_ref().and_then(|faults| faults.control_stall()) {
+            self.clock.sleep(stall).await;
+        }
+
+        let mut request = Request::accept_with(
+            conn,
+            self.max_field_section_size,
+            handshake,
+            deadline,
+            self.early_buffer,
+            self.http_handler.as_ref(),
+            Some(self.shutdown),
+        )
+        .await?;
+        if let Some(replay_safe) = &self.zero_rtt {
+            if request.is_0rtt() && !replay_safe(&request.connect) {
+                request.confirm().await?;
+            }
+        }
+        if let Some(allowed) = &self.allowed_origins {
+            if !request.connect.origin_allowed(allowed) {
+                request.reject(http::StatusCode::FORBIDDEN).await?;
+                return Err(ServerError::ForbiddenOrigin);
+            }
+        }
+        if let Some(limits) = &self.session_limits {
+            match limits.acquire(&request.connect) {
+                Some(permit) => request.permit = Some(permit),
+                None => {
+                    request
+                        .reject(http::StatusCode::SERVICE_UNAVAILABLE)
+                        .await?;
+                    return Err(ServerError::TooManySessions);
+                }
+            }
+        }
+        let slot = match &self.accept_queue {
+            Some(queue) => match queue.push() {
+                Some(slot) => Some(slot),
+                None => {
+                    tracing::warn!(max = queue.max(), "accept queue full");
+                    let response = ConnectResponse::new(http::StatusCode::SERVICE_UNAVAILABLE)
+                        .with_retry_after(queue.retry_after());
+                    request.reject(response).await?;
+                    return Err(ServerError::AcceptQueueFull);
+                }
+            },
+            None => None,
+        };
+        request.faults = faults;
+        request.memory_budget = self.memory_budget;
+        request.drop_codes = self.drop_codes;
+        request.history = self.history;
+        request.clock = self.clock;
+        Ok((request, slot))
+    }
 }
 
 /// A mostly complete WebTransport handshake, just awaiting the server's decision on whether to accept or reject the session based on the URL.
 pub struct Request {
     conn: quinn::Connection,
-    settings: Settings,
+    settings: Arc<Settings>,
     connect: Connecting,
+    faults: Option<Arc<FaultInjector>>,
+    memory_budget: Option<MemoryBudget>,
+    drop_codes: DropCodes,
+
+    // The slot claimed from the server's session limits, if any.
+    permit: Option<SessionPermit>,
+
+    // Resolves when the handshake is confirmed, if the request arrived before then.
+    handshake: Option<quinn::ZeroRttAccepted>,
+
+    // Holds streams and datagrams the client sends before we respond.
+    early: Arrivals,
+
+    // Rejects the request if it isn't answered in time.
+    expiry: Option<Expiry>,
+
+    // Applied to the session once accepted.
+    mode: SessionMode,
+    history: usize,
+    clock: Arc<dyn Clock>,
+}
+
+// Where the streams and datagrams sent before the response are held.
+enum Arrivals {
+    // The session owns the connection, so a task buffers everything on it.
+    Buffer(EarlyBuffer),
+
+    // The session shares the connection, so the pool routes its streams.
+    Pool(PoolRoute),
 }
 
 impl Request {
     /// Accept a new WebTransport session from a client.
     pub async fn accept(conn: quinn::Connection) -> Result<Self, ServerError> {
+        Self::accept_with(conn, None, None, None, EARLY_BUFFER, None, None).await
+    }
+
+    async fn accept_with(
+        conn: quinn::Connection,
+        max_field_section_size: Option<u64>,
+        handshake: Option<quinn::ZeroRttAccepted>,
+        deadline: Option<Deadline<'_>>,
+        early_buffer: usize,
+        http_handler: Option<&HttpHandler>,
+        shutdown: Option<watch::Receiver<bool>>,
+    ) -> Result<Self, ServerError> {
         // Perform the H3 handshake by sending/reciving SETTINGS frames.
-        let settings = Settings::connect(&conn).await?;
+        let settings = Settings::connect_with(&conn, max_field_section_size, 1);
+        let mut settings = before(&conn, deadline, codes::h3::SETTINGS_ERROR, settings).await??;
+        if let Some(shutdown) = shutdown {
+            settings = settings.with_shutdown(shutdown);
+        }
 
         // Accept the CONNECT request but don't send a response yet.
-        let connect = Connecting::accept(&conn).await?;
+        let connect = Connecting::accept(&conn, max_field_section_size, http_handler);
+        let connect = before(&conn, deadline, codes::h3::REQUEST_REJECTED, connect).await??;
+
+        // The request could only have been replayed if the handshake is still unconfirmed.
+        let handshake = handshake.and_then(|mut handshake| {
+            (&mut handshake)
+                .now_or_never()
+                .is_none()
+                .then_some(handshake)
+        });
+
+        // Start holding anything the client sends before we respond.
+        let early = EarlyBuffer::new(conn.clone(), early_buffer);
 
         // Return the resulting request with a reference to the settings/connect streams.
         Ok(Self {
             conn,
-            settings,
+            settings: Arc::new(settings),
             connect,
+            faults: None,
+            memory_budget: None,
+            drop_codes: DropCodes::default(),
+            permit: None,
+            handshake,
+            early: Arrivals::Buffer(early),
+            expiry: None,
+            mode: SessionMode::Full,
+            history: 0,
+            clock: Arc::new(TokioClock),
         })
     }
 
+    // A request for one of the sessions sharing a connection, see [crate::SessionPool].
+    pub(crate) fn pooled(
+        conn: quinn::Connection,
+        settings: Arc<Settings>,
+        connect: Connecting,
+        route: PoolRoute,
+    ) -> Self {
+        Self {
+            conn,
+            settings,
+            connect,
+            faults: None,
+            memory_budget: None,
+            drop_codes: DropCodes::default(),
+            permit: None,
+            handshake: None,
+            early: Arrivals::Pool(route),
+            expiry: None,
+            mode: SessionMode::Full,
+            history: 0,
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    /// Returns true if the request arrived as 0-RTT data and the handshake isn't confirmed yet.
+    ///
+    /// Such a request may be a replay, so only act on it if doing so twice is harmless.
+    /// Call [Self::confirm] first otherwise. Always false unless the server was built
+    /// with [ServerBuilder::with_0rtt](crate::ServerBuilder::with_0rtt).
+    pub fn is_0rtt(&self) -> bool {
+        self.handshake.is_some()
+    }
+
+    /// Wait until the handshake is confirmed, after which the request can't be a replay.
+    ///
+    /// Returns immediately if [Self::is_0rtt] is false, and errors if the connection
+    /// fails first, as it does for a replayed request.
+    pub async fn confirm(&mut self) -> Result<(), ServerError> {
+        if let Some(handshake) = self.handshake.take() {
+            // Servers always resolve to false, so check whether the connection failed instead.
+            handshake.await;
+            if let Some(err) = self.conn.close_reason() {
+                return Err(err.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accept the session in this [SessionMode], usually picked from [ConnectRequest::protocols].
+    ///
+    /// A [datagram-only](SessionMode::DatagramOnly) session resets any stream the client
+    /// opens, including those sent before the response. Defaults to [SessionMode::Full].
+    pub fn with_session_mode(mut self, mode: SessionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     pub async fn ok(self) -> Result<Session, ServerError> {
         self.respond(ConnectResponse::OK).await
     }
@@ -170,28 +974,156 @@ impl Request {
     /// Reply to the session with the given response, usually 200 OK.
     ///
     /// [ConnectResponse::with_protocol] can be used to select a subprotocol.
+    ///
+    /// With a [memory budget](ServerBuilder::with_memory_budget), this first waits
+    /// until the budget can cover the session's minimum windows.
     pub async fn respond(
         self,
         response: impl Into<ConnectResponse>,
     ) -> Result<Session, ServerError> {
-        let response = response.into();
+        self.respond_spawned(response.into(), Spawner::Tokio).await
+    }
+
+    /// Like [Request::ok], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn ok_driven(self) -> Result<(Session, SessionDriver), ServerError> {
+        self.respond_driven(ConnectResponse::OK).await
+    }
+
+    /// Like [Request::respond], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn respond_driven(
+        self,
+        response: impl Into<ConnectResponse>,
+    ) -> Result<(Session, SessionDriver), ServerError> {
+        let (spawner, driver) = Spawner::driven();
+        let session = self.respond_spawned(response.into(), spawner).await?;
+        Ok((session, driver))
+    }
+
+    async fn respond_spawned(
+        mut self,
+        response: ConnectResponse,
+        spawner: Spawner,
+    ) -> Result<Session, ServerError> {
+        self.disarm()?;
+
+        let memory = match &self.memory_budget {
+            Some(budget) => Some(memory::reserve(&self.conn, budget).await),
+            None => None,
+        };
+
         let connect = self.connect.respond(response).await?;
-        Ok(Session::new(self.conn, self.settings, connect))
+        let session = match self.early {
+            Arrivals::Buffer(early) => {
+                let early = early.finish().await;
+                Session::new(
+                    self.conn,
+                    self.settings,
+                    connect,
+                    self.drop_codes,
+                    None,
+                    self.mode,
+                    spawner,
+                    self.clock,
+                )
+                .with_early(early)
+            }
+            Arrivals::Pool(route) => Session::new(
+                self.conn,
+                self.settings,
+                connect,
+                self.drop_codes,
+                Some(route),
+                self.mode,
+                spawner,
+                self.clock,
+            ),
+        };
+        Ok(session
+            .with_faults(self.faults)
+            .with_memory(memory)
+            .with_0rtt(self.handshake.is_some())
+            .with_permit(self.permit)
+            .with_history(self.history))
     }
 
     /// Reject the session with the given status code.
-    pub async fn reject(self, status: http::StatusCode) -> Result<(), ServerError> {
-        self.connect.reject(status).await?;
+    ///
+    /// Pass a [ConnectResponse] instead to send headers with it, such as
+    /// `www-authenticate` on a 401.
+    pub async fn reject(mut self, response: impl Into<ConnectResponse>) -> Result<(), ServerError> {
+        self.disarm()?;
+        self.connect.reject(response).await?;
         Ok(())
     }
 
+    /// Reject the session with a 302 Found, asking the client to reconnect to `location`.
+    ///
+    /// Clients only follow it when built with [ClientBuilder::with_follow_redirects](crate::ClientBuilder::with_follow_redirects).
+    pub async fn redirect(mut self, location: url::Url) -> Result<(), ServerError> {
+        self.disarm()?;
+        self.connect.redirect(location).await?;
+        Ok(())
+    }
+
+    // Start the response timeout. Nothing drives a session yet, so it runs on the default spawner.
+    fn expiry(&self, timeout: Duration) -> Expiry {
+        Expiry::new(
+            self.conn.clone(),
+            &*self.clock,
+            &Spawner::default(),
+            timeout,
+        )
+    }
+
+    // Stop the response timeout, failing if it already rejected the request.
+    fn disarm(&mut self) -> Result<(), ServerError> {
+        match self.expiry.take() {
+            Some(expiry) => expiry.disarm(),
+            None => Ok(()),
+        }
+    }
+
     /// Returns the underlying QUIC connection.
     pub fn conn(&self) -> &quinn::Connection {
         &self.conn
     }
 
+    /// The server name (SNI) the client asked for in the TLS handshake, if it sent one.
+    ///
+    /// Usually the host of the CONNECT URL too, but the client sends the two separately.
+    #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+    pub fn server_name(&self) -> Option<String> {
+        let data = self.conn.handshake_data()?;
+        let data = data
+            .downcast::<quinn::crypto::rustls::HandshakeData>()
+            .ok()?;
+        data.server_name
+    }
+
+    /// The remote peer's address.
+    pub fn peer_addr(&self) -> std::net::SocketAddr {
+        self.conn.remote_address()
+    }
+
+    /// The local IP address the client sent to, if the platform reports it.
+    ///
+    /// Tells the addresses apart when listening on a wildcard. See [quinn::Connection::local_ip].
+    pub fn local_ip(&self) -> Option<std::net::IpAddr> {
+        self.conn.local_ip()
+    }
+
+    /// The certificate chain the client authenticated with, leaf first.
+    ///
+    /// `None` unless the server requested a client certificate and the client presented
+    /// one, which the verifier has already accepted. See [ServerBuilder::with_client_ca_roots].
+    pub fn peer_certificates(&self) -> Option<Vec<rustls::pki_types::CertificateDer<'static>>> {
+        crate::session::peer_certificates(&self.conn)
+    }
+
     /// The remote peer's address.
-    #[deprecated(note = "use conn().remote_address() instead")]
+    #[deprecated(note = "use peer_addr() instead")]
     pub fn remote_address(&self) -> std::net::SocketAddr {
         self.conn.remote_address()
     }
@@ -204,6 +1136,96 @@ impl Request {
     }
 }
 
+// When the handshake has to be done by, on the server's clock.
+#[derive(Clone, Copy)]
+struct Deadline<'a> {
+    clock: &'a dyn Clock,
+    at: Instant,
+}
+
+/// Finish the QUIC handshake by `deadline`, abandoning the connection if it passes first.
+async fn quic(
+    deadline: Option<Deadline<'_>>,
+    connecting: quinn::Connecting,
+) -> Result<quinn::Connection, ServerError> {
+    let Some(deadline) = deadline else {
+        return Ok(connecting.await?);
+    };
+
+    // Dropping the handshake closes the connection.
+    match deadline.clock.timeout_at(deadline.at, connecting).await {
+        Ok(res) => Ok(res?),
+        Err(_) => Err(ServerError::HandshakeTimeout),
+    }
+}
+
+/// Run `fut` until `deadline`, closing the connection with `code` if it passes first.
+async fn before<T>(
+    conn: &quinn::Connection,
+    deadline: Option<Deadline<'_>>,
+    code: u64,
+    fut: impl Future<Output = T>,
+) -> Result<T, ServerError> {
+    let Some(deadline) = deadline else {
+        return Ok(fut.await);
+    };
+
+    match deadline.clock.timeout_at(deadline.at, fut).await {
+        Ok(res) => Ok(res),
+        Err(_) => {
+            conn.close(quinn::VarInt::from_u64(code).unwrap(), b"handshake timeout");
+            Err(ServerError::HandshakeTimeout)
+        }
+    }
+}
+
+// Closes the connection with H3_REQUEST_REJECTED unless disarmed before the timeout.
+struct Expiry {
+    // Set by the timer task once it closes the connection.
+    expired: Arc<AtomicBool>,
+
+    // Dropped to stop the timer task.
+    _cancel: oneshot::Sender<()>,
+}
+
+impl Expiry {
+    fn new(
+        conn: quinn::Connection,
+        clock: &dyn Clock,
+        spawner: &Spawner,
+        timeout: Duration,
+    ) -> Self {
+        let sleep = clock.sleep(timeout);
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        let expired = Arc::new(AtomicBool::new(false));
+
+        let fired = expired.clone();
+        spawner.spawn(async move {
+            tokio::select! {
+                _ = sleep => {}
+                _ = cancelled => return,
+            }
+
+            tracing::debug!("rejecting unanswered request");
+            fired.store(true, Ordering::Relaxed);
+            let code = quinn::VarInt::from_u64(codes::h3::REQUEST_REJECTED).unwrap();
+            conn.close(code, b"response timeout");
+        });
+
+        Self {
+            expired,
+            _cancel: cancel,
+        }
+    }
+
+    fn disarm(self) -> Result<(), ServerError> {
+        match self.expired.load(Ordering::Relaxed) {
+            true => Err(ServerError::RequestTimeout),
+            false => Ok(()),
+        }
+    }
+}
+
 impl core::ops::Deref for Request {
     type Target = ConnectRequest;
 
@@ -238,6 +1260,27 @@ mod tests {
             provider,
             addr: "[::]:0".parse().unwrap(),
             congestion_controller: None,
+            faults: None,
+            socket_options: SocketOptions::default(),
+            memory_budget: None,
+            max_field_section_size: None,
+            drop_codes: DropCodes::default(),
+            zero_rtt: None,
+            handshake_timeout: None,
+            idle_timeout: Some(IDLE_TIMEOUT),
+            keep_alive: None,
+            datagram_send_buffer: None,
+            session_limits: None,
+            accept_queue: None,
+            early_buffer: EARLY_BUFFER,
+            response_timeout: None,
+            allowed_origins: None,
+            http_handler: None,
+            history: 0,
+            keylog: false,
+            runtime: Arc::new(quinn::TokioRuntime),
+            client_auth: ClientAuth::None,
+            clock: Arc::new(TokioClock),
         }
     }
 
@@ -250,8 +1293,10 @@ mod tests {
         let builder = builder().with_congestion_control(CongestionControl::LowLatency);
         assert!(builder.congestion_controller.is_some());
 
-        let transport = transport_config(builder.congestion_controller.as_ref());
-        let config = builder.config(chain, key, transport.clone()).unwrap();
+        let transport = transport_config(builder.congestion_controller.as_ref(), None, None, None);
+        let config = builder
+            .config(ServerCert::Single(chain, key), transport.clone())
+            .unwrap();
 
         assert!(Arc::ptr_eq(&config.transport, &transport));
     }
@@ -1,22 +1,143 @@
 use std::{
+    collections::{HashMap, VecDeque},
     fmt,
     future::{poll_fn, Future},
     io::Cursor,
     ops::Deref,
     pin::Pin,
-    sync::{Arc, Mutex, OnceLock},
-    task::{Context, Poll, Waker},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    task::Poll,
+    time::{Duration, Instant},
 };
 
-use bytes::{Bytes, BytesMut};
-use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{
+    future,
+    stream::{FuturesUnordered, Stream, StreamExt},
+    try_join,
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    watch,
+};
+use tracing::Instrument;
+use url::Url;
+use web_transport_trait::{
+    boxed::BoxFuture, Clock, CloseRecord, Closed, DatagramOptions, SessionId, StreamOptions,
+    TokioClock,
+};
 
 use crate::{
-    proto::{ConnectRequest, ConnectResponse, Frame, StreamUni, VarInt},
-    ClientError, Connected, RecvStream, SendStream, SessionError, Settings, WebTransportError,
+    datagram::Router,
+    driver::Spawner,
+    early::Early,
+    memory::Reservation,
+    pool::{PoolRoute, PooledSession, RoutedBi, RoutedUni},
+    proto::{
+        codes::{self, DropCodes},
+        ConnectRequest, ConnectResponse, Frame, HeaderBudget, HeaderViolation, SessionEvent,
+        SessionEventKind, SessionFlow, SessionHistory, SessionMode, SessionPermit, StreamUni,
+        UnknownStreamPolicy, VarInt,
+    },
+    scheduler::Scheduler,
+    ClientError, Connected, DatagramRoute, FaultInjector, RecvStream, SendOrdering, SendStream,
+    SessionDriver, SessionError, Settings, WebTransportError,
 };
 
+// The send side of the CONNECT stream, taken by whoever closes the session.
+type ConnectSend = Arc<tokio::sync::Mutex<Option<quinn::SendStream>>>;
+
+// The code and reason of a CloseWebTransportSession capsule, if the CONNECT stream had one.
+type CloseInfo = Result<Option<(u32, Bytes)>, web_transport_proto::CapsuleError>;
+
+// The session state that run_recv tears down once the CONNECT stream ends.
+struct Teardown {
+    conn: quinn::Connection,
+    error: Arc<OnceLock<SessionError>>,
+    close_record: CloseRecord,
+    flow: SessionFlow,
+    connect_send: ConnectSend,
+    pool: Option<Arc<PooledSession>>,
+    history: SessionHistory,
+}
+
+// Fit a `sendOrder` into quinn's stream priority.
+fn clamp_order(order: i64) -> i32 {
+    order.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+// The span around a session's background tasks, so their events carry its ID.
+fn session_span(id: SessionId) -> tracing::Span {
+    tracing::info_span!("session", %id)
+}
+
+// Datagrams with a max age waiting for send buffer room; newer ones are dropped beyond this.
+const EXPIRING_DATAGRAMS: usize = 256;
+
+// Sends queued datagrams in order as the send buffer makes room, dropping any past their deadline.
+// Runs until every handle to the session is dropped, or the connection is.
+async fn send_expiring(
+    conn: quinn::Connection,
+    clock: Arc<dyn Clock>,
+    mut queue: mpsc::Receiver<(Bytes, Instant)>,
+) {
+    while let Some((data, deadline)) = queue.recv().await {
+        let len = data.len();
+        if clock.now() >= deadline {
+            tracing::trace!(len, "dropping expired outbound datagram");
+            continue;
+        }
+
+        match clock
+            .timeout_at(deadline, conn.send_datagram_wait(data))
+            .await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return,
+            Err(_) => tracing::trace!(len, "dropping expired outbound datagram"),
+        }
+    }
+}
+
+// Closes the connection once every handle to the session is dropped.
+struct SessionDrop {
+    conn: quinn::Connection,
+    code: u32,
+    id: SessionId,
+
+    // A pooled session shares the connection, so only its CONNECT stream is reset.
+    pooled: Option<(Arc<PooledSession>, ConnectSend)>,
+}
+
+impl Drop for SessionDrop {
+    fn drop(&mut self) {
+        if let Some((pool, connect_send)) = &self.pooled {
+            // close() takes the stream, so it's only still there if it wasn't called.
+            let send = connect_send
+                .try_lock()
+                .ok()
+                .and_then(|mut slot| slot.take());
+            if let Some(mut send) = send {
+                tracing::warn!(session = %self.id, "session dropped without calling `close`");
+                let code = web_transport_proto::error_to_http3(self.code);
+                send.reset(quinn::VarInt::try_from(code).unwrap()).ok();
+            }
+            pool.close();
+            return;
+        }
+
+        if self.conn.close_reason().is_none() {
+            tracing::warn!(session = %self.id, "session dropped without calling `close`");
+            let code = web_transport_proto::error_to_http3(self.code);
+            let code = quinn::VarInt::try_from(code).unwrap();
+            self.conn.close(code, b"session dropped");
+        }
+    }
+}
+
 /// An established WebTransport session, acting like a full QUIC connection. See [`quinn::Connection`].
 ///
 /// It is important to remember that WebTransport is layered on top of QUIC:
@@ -30,11 +151,18 @@ use crate::{
 pub struct Session {
     conn: quinn::Connection,
 
+    // Identifies the session in logs, as the `session` span around its background tasks.
+    id: SessionId,
+
+    // Dropped when all references are dropped.
+    #[allow(dead_code)]
+    drop: Arc<SessionDrop>,
+
     // The session ID, as determined by the stream ID of the connect request.
     session_id: Option<VarInt>,
 
-    // The accept logic is stateful, so use an Arc<Mutex> to share it.
-    accept: Option<Arc<Mutex<SessionAccept>>>,
+    // Receives the streams decoded by the accept task.
+    accept: Option<Accepted>,
 
     // Cache the headers in front of each stream we open.
     header_uni: Vec<u8>,
@@ -42,27 +170,95 @@ pub struct Session {
     header_datagram: Vec<u8>,
 
     // Keep a reference to the settings and connect stream to avoid closing them until dropped.
-    #[allow(dead_code)]
+    // Also tells us whether the peer sent GOAWAY.
     settings: Option<Arc<Settings>>,
 
-    // The send side of the CONNECT stream, used to write the CloseWebTransportSession capsule.
-    // Wrapped in Arc<Mutex<Option<...>>> so close() can take it exactly once.
-    connect_send: Arc<Mutex<Option<quinn::SendStream>>>,
+    // The send side of the CONNECT stream, used to write the Drain/CloseWebTransportSession capsules.
+    // Wrapped in Arc<Mutex<Option<...>>> so close() can take it exactly once, after any drain() write.
+    connect_send: Arc<tokio::sync::Mutex<Option<quinn::SendStream>>>,
+
+    // Set once the peer sends a DrainWebTransportSession capsule.
+    draining: Arc<watch::Sender<bool>>,
+
+    // The URL from the peer's latest ReconnectHint capsule, if any.
+    reconnect: Arc<watch::Sender<Option<Url>>>,
+
+    // The peer's session-level flow control limits, shared with every stream we send on.
+    flow: SessionFlow,
 
     // Session error, set once by either local close() or the background task
     // when a remote CloseWebTransportSession capsule is received.
     // Uses OnceLock for set-once, first-writer-wins semantics with lock-free reads.
     error: Arc<OnceLock<SessionError>>,
 
+    // The first close code and reason from either side, kept for the trait's close_reason().
+    close_record: CloseRecord,
+
     // The request sent by the client.
     request: ConnectRequest,
 
     // The response sent by the server.
     response: ConnectResponse,
+
+    // Faults to inject for resilience testing, if configured on the builder.
+    faults: Option<Arc<FaultInjector>>,
+
+    // How long each phase of the client handshake took.
+    handshake: HandshakeTiming,
+
+    // Schedules writes on every send stream, shared with SessionAccept.
+    scheduler: Arc<Scheduler>,
+
+    // The share of the server's memory budget backing this session's windows.
+    memory: Option<Reservation>,
+
+    // Sent when a stream or the session is dropped without being closed.
+    codes: DropCodes,
+
+    // Whether the server accepted the session from unconfirmed 0-RTT data.
+    zero_rtt: bool,
+
+    // The slot this session holds in the server's session limits.
+    permit: Option<Arc<SessionPermit>>,
+
+    // Routes incoming datagrams to per-prefix queues, shared by every clone.
+    datagrams: Arc<Router>,
+
+    // Datagrams that arrived before the CONNECT response, read before any new ones.
+    early_datagrams: Arc<Mutex<VecDeque<Bytes>>>,
+
+    // Queues datagrams with a max age for the task waiting on send buffer room, started by the
+    // first one that has to wait.
+    expiring: Arc<OnceLock<mpsc::Sender<(Bytes, Instant)>>>,
+
+    // Set if the session shares its connection with others, see [SessionPool](crate::SessionPool).
+    pool: Option<Arc<PooledSession>>,
+
+    // Whether streams may be opened and accepted.
+    mode: SessionMode,
+
+    // Runs background tasks, on tokio unless the application drives them.
+    spawner: Spawner,
+
+    // Measures the close timeout and datagram expiry.
+    clock: Arc<dyn Clock>,
+
+    // The most recent events, if the application asked for them.
+    history: SessionHistory,
 }
 
 impl Session {
-    pub(crate) fn new(conn: quinn::Connection, settings: Settings, connect: Connected) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        conn: quinn::Connection,
+        settings: Arc<Settings>,
+        connect: Connected,
+        codes: DropCodes,
+        pool: Option<PoolRoute>,
+        mode: SessionMode,
+        spawner: Spawner,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         // The session ID is the stream ID of the CONNECT request.
         let session_id = connect.session_id();
 
@@ -79,56 +275,232 @@ impl Session {
         session_id.encode(&mut header_datagram);
 
         let error: Arc<OnceLock<SessionError>> = Arc::new(OnceLock::new());
+        let close_record = CloseRecord::new();
+
+        let id = SessionId::next();
+        let span = session_span(id);
+
+        let scheduler = Arc::new(Scheduler::default());
+        let draining = Arc::new(watch::Sender::new(false));
+        let reconnect = Arc::new(watch::Sender::new(None));
+        let flow = SessionFlow::new(settings.peer_limits);
+        let history = SessionHistory::default();
+
+        // Our GOAWAY mustn't cover this session, and someone needs to read the peer's.
+        settings.processed(session_id);
+        if let Some(control) = settings.run() {
+            spawner.spawn(control.instrument(span.clone()));
+        }
+
+        // A pooled session only accepts the streams routed to it.
+        let (pool, routed) = match pool.map(PooledSession::new) {
+            Some((pool, uni, bi)) => (Some(Arc::new(pool)), Some((uni, bi))),
+            None => (None, None),
+        };
+
+        let accept = match mode {
+            SessionMode::Full => {
+                // Accept logic is stateful, so it runs in its own task and every clone receives from it.
+                let (mut accept, accepted, commands) = SessionAccept::new(
+                    conn.clone(),
+                    session_id,
+                    error.clone(),
+                    scheduler.clone(),
+                    flow.clone(),
+                    codes,
+                    clock.clone(),
+                );
+                if let Some((uni, bi)) = routed {
+                    accept.route(uni, bi);
+                }
+                spawner.spawn(accept.run(commands).instrument(span.clone()));
+                Some(accepted)
+            }
+            // Nothing is ever accepted, so streams are reset as they arrive without reading them.
+            SessionMode::DatagramOnly => {
+                let refuse = Self::refuse_streams(conn.clone(), routed);
+                spawner.spawn(refuse.instrument(span.clone()));
+                None
+            }
+        };
 
-        // Accept logic is stateful, so use an Arc<Mutex> to share it.
-        let accept = SessionAccept::new(conn.clone(), session_id, error.clone());
+        let connect_send = Arc::new(tokio::sync::Mutex::new(Some(connect.send)));
+
+        let drop = Arc::new(SessionDrop {
+            conn: conn.clone(),
+            code: codes.session,
+            id,
+            pooled: pool.clone().map(|pool| (pool, connect_send.clone())),
+        });
 
         let this = Self {
             conn,
-            accept: Some(Arc::new(Mutex::new(accept))),
+            id,
+            drop,
+            accept,
             session_id: Some(session_id),
             header_uni,
             header_bi,
             header_datagram,
-            settings: Some(Arc::new(settings)),
-            connect_send: Arc::new(Mutex::new(Some(connect.send))),
+            settings: Some(settings),
+            connect_send,
+            draining: draining.clone(),
+            reconnect: reconnect.clone(),
+            flow: flow.clone(),
             error: error.clone(),
+            close_record: close_record.clone(),
             request: connect.request.clone(),
             response: connect.response.clone(),
+            faults: None,
+            handshake: HandshakeTiming::default(),
+            scheduler,
+            memory: None,
+            codes,
+            zero_rtt: false,
+            permit: None,
+            datagrams: Default::default(),
+            early_datagrams: Default::default(),
+            expiring: Default::default(),
+            pool: pool.clone(),
+            mode,
+            spawner,
+            clock,
+            history: history.clone(),
         };
 
         // Run a background task to read capsules from the CONNECT recv stream.
         let conn2 = this.conn.clone();
-        tokio::spawn(Self::run_recv(conn2, connect.recv, error));
+        let capsules = Self::read_capsules(
+            connect.recv,
+            draining,
+            reconnect,
+            flow.clone(),
+            history.clone(),
+        );
+        let teardown = Teardown {
+            conn: conn2,
+            error,
+            close_record,
+            flow: flow.clone(),
+            connect_send: this.connect_send.clone(),
+            pool,
+            history: history.clone(),
+        };
+        let recv = Self::run_recv(capsules, teardown);
+        this.spawner.spawn(recv.instrument(span.clone()));
+
+        // Run another to write the BLOCKED capsules queued by flow control.
+        let flow = Self::run_flow(flow, this.connect_send.clone(), history);
+        this.spawner.spawn(flow.instrument(span));
 
         this
     }
 
+    // Write flow control capsules on the CONNECT send stream until the session is closed.
+    async fn run_flow(
+        flow: SessionFlow,
+        connect_send: Arc<tokio::sync::Mutex<Option<quinn::SendStream>>>,
+        history: SessionHistory,
+    ) {
+        while let Some(capsule) = poll_fn(|cx| flow.poll_capsule(cx)).await {
+            let frame = Self::capsule_frame(&capsule, &history).expect("flow capsules are tiny");
+
+            let mut slot = connect_send.lock().await;
+            let Some(send) = slot.as_mut() else {
+                // close() already took the stream.
+                return;
+            };
+
+            if let Err(err) = send.write_all(&frame).await {
+                tracing::debug!(?err, "failed to write flow control capsule");
+                return;
+            }
+        }
+    }
+
     // Read capsules from the CONNECT recv stream until it's closed,
     // then record the close error and tear down the connection.
-    async fn run_recv(
-        conn: quinn::Connection,
-        recv: quinn::RecvStream,
-        error: Arc<OnceLock<SessionError>>,
-    ) {
-        let close_info = Self::read_capsules(recv).await;
-        let code = close_info.as_ref().map_or(0, |(c, _)| *c);
+    async fn run_recv(capsules: impl Future<Output = CloseInfo>, teardown: Teardown) {
+        let Teardown {
+            conn,
+            error,
+            close_record,
+            flow,
+            connect_send,
+            pool,
+            history,
+        } = teardown;
+
+        // A pooled session can also be closed locally while the connection stays open.
+        let close_info = match &pool {
+            Some(pool) => tokio::select! {
+                res = capsules => res,
+                _ = pool.closed() => Ok(None),
+            },
+            None => capsules.await,
+        };
+
+        // Wake anything waiting on flow control so it sees the session is gone.
+        flow.close();
+
+        // Recorded even if close() got there first, which marks the race.
+        if let Ok(Some((code, reason))) = &close_info {
+            close_record.remote(*code, reason);
+        }
+
+        if let Some(err) = conn.close_reason() {
+            history.record(SessionEventKind::Error(format!("connection closed: {err}")));
+        }
+
+        let code = match &close_info {
+            Ok(Some((code, _))) => *code,
+            Ok(None) => 0,
+            Err(_) => codes::CAPSULE_ERROR,
+        };
 
         let http3_code: quinn::VarInt = web_transport_proto::error_to_http3(code)
             .try_into()
             .unwrap();
 
+        // A pooled session shares the connection, so only its CONNECT stream is closed.
+        if let Some(pool) = pool {
+            let err: SessionError = match (conn.close_reason(), &close_info) {
+                (Some(err), _) => err.into(),
+                (None, Ok(Some((code, reason)))) => {
+                    WebTransportError::Closed(*code, reason.clone()).into()
+                }
+                (None, _) => WebTransportError::Closed(code, Bytes::new()).into(),
+            };
+
+            // Close our side too, unless close() already took it.
+            if let Some(mut send) = connect_send.lock().await.take() {
+                match close_info {
+                    Ok(_) => send.finish().ok(),
+                    Err(_) => send.reset(http3_code).ok(),
+                };
+            }
+
+            error.set(err).ok();
+            pool.close();
+            return;
+        }
+
         // Try to record the remote close error. If close() already set
         // the error, it owns the connection teardown, so we bail out.
         match close_info {
-            Some((code, reason)) => {
+            Ok(Some((code, reason))) => {
                 let err = WebTransportError::Closed(code, reason.clone());
                 if error.set(err.into()).is_err() {
                     return;
                 }
-                conn.close(http3_code, reason.as_bytes());
+                conn.close(http3_code, &reason);
             }
-            None => {
+            Ok(None) | Err(_) => {
+                // Losing the connection also ends the CONNECT stream; keep its reason.
+                if conn.close_reason().is_some() {
+                    return;
+                }
+
                 let err = quinn::ConnectionError::LocallyClosed.into();
                 if error.set(err).is_err() {
                     return;
@@ -141,22 +513,55 @@ impl Session {
     // Keep reading capsules from the CONNECT recv stream until it's closed.
     // Returns Some((code, reason)) if a CloseWebTransportSession capsule was received,
     // or None if the stream closed without a capsule.
-    async fn read_capsules(recv: quinn::RecvStream) -> Option<(u32, String)> {
+    async fn read_capsules(
+        recv: quinn::RecvStream,
+        draining: Arc<watch::Sender<bool>>,
+        reconnect: Arc<watch::Sender<Option<Url>>>,
+        flow: SessionFlow,
+        history: SessionHistory,
+    ) -> CloseInfo {
         let mut reader = web_transport_proto::Http3CapsuleReader::new(recv);
         loop {
-            match reader.read().await {
+            let res = reader.read().await;
+            match &res {
+                Ok(Some(capsule)) => {
+                    history.record(SessionEventKind::CapsuleReceived(capsule.clone()))
+                }
+                Ok(None) => {}
+                Err(e) => history.record(SessionEventKind::Error(format!("capsule error: {e}"))),
+            }
+
+            match res {
                 Ok(Some(web_transport_proto::Capsule::CloseWebTransportSession {
                     code,
                     reason,
-                })) => return Some((code, reason)),
+                })) => return Ok(Some((code, reason))),
+                Ok(Some(web_transport_proto::Capsule::DrainWebTransportSession)) => {
+                    tracing::debug!("peer is draining the session");
+                    draining.send_replace(true);
+                }
+                Ok(Some(web_transport_proto::Capsule::ReconnectHint { url })) => {
+                    tracing::debug!(%url, "peer sent a reconnect hint");
+                    reconnect.send_replace(Some(url));
+                }
+                Ok(Some(
+                    capsule @ (web_transport_proto::Capsule::WtMaxData { .. }
+                    | web_transport_proto::Capsule::WtMaxStreams { .. }),
+                )) => flow.on_capsule(&capsule),
+                Ok(Some(web_transport_proto::Capsule::WtDataBlocked { limit })) => {
+                    tracing::debug!(limit, "peer is blocked on session data");
+                }
+                Ok(Some(web_transport_proto::Capsule::WtStreamsBlocked { bidi, limit })) => {
+                    tracing::debug!(bidi, limit, "peer is blocked on session streams");
+                }
                 Ok(Some(web_transport_proto::Capsule::Grease { .. })) => {}
                 Ok(Some(web_transport_proto::Capsule::Unknown { typ, payload })) => {
                     tracing::warn!(%typ, size = payload.len(), "unknown capsule");
                 }
-                Ok(None) => return None,
+                Ok(None) => return Ok(None),
                 Err(e) => {
                     tracing::warn!(?e, "failed to read capsule");
-                    return None;
+                    return Err(e);
                 }
             }
         }
@@ -168,87 +573,443 @@ impl Session {
         conn: quinn::Connection,
         request: impl Into<ConnectRequest>,
     ) -> Result<Session, ClientError> {
-        let request = request.into();
+        Self::connect_timed(
+            conn,
+            request.into(),
+            HandshakeTiming::default(),
+            DropCodes::default(),
+            SessionMode::Full,
+            Spawner::Tokio,
+            Arc::new(TokioClock),
+        )
+        .await
+    }
+
+    /// Like [Session::connect], but the session's background work runs in the returned
+    /// [SessionDriver] rather than being spawned onto the tokio runtime.
+    pub async fn connect_driven(
+        conn: quinn::Connection,
+        request: impl Into<ConnectRequest>,
+    ) -> Result<(Session, SessionDriver), ClientError> {
+        let (spawner, driver) = Spawner::driven();
+        let session = Self::connect_timed(
+            conn,
+            request.into(),
+            HandshakeTiming::default(),
+            DropCodes::default(),
+            SessionMode::Full,
+            spawner,
+            Arc::new(TokioClock),
+        )
+        .await?;
+
+        Ok((session, driver))
+    }
+
+    /// Finish the handshake, filling in the HTTP/3 phases of `timing`.
+    pub(crate) async fn connect_timed(
+        conn: quinn::Connection,
+        request: ConnectRequest,
+        mut timing: HandshakeTiming,
+        codes: DropCodes,
+        mode: SessionMode,
+        spawner: Spawner,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Session, ClientError> {
+        let start = Instant::now();
 
         // Perform the H3 handshake by sending/reciving SETTINGS frames.
-        let settings = Settings::connect(&conn).await?;
+        let settings = async {
+            let settings = Settings::connect(&conn).await?;
+            Ok::<_, ClientError>((settings, start.elapsed()))
+        };
+
+        // Send the HTTP/3 CONNECT request without waiting for the peer's SETTINGS, saving a round trip.
+        // They're still validated before the session is returned, abandoning the CONNECT if unsupported.
+        let connect = async { Ok::<_, ClientError>(Connected::open(&conn, request).await?) };
+
+        let ((settings, elapsed), connect) = try_join!(settings, connect)?;
+        timing.settings = Some(elapsed);
+        timing.connect = Some(start.elapsed().saturating_sub(elapsed));
 
-        // Send the HTTP/3 CONNECT request.
-        let connect = Connected::open(&conn, request).await?;
+        tracing::debug!(
+            url = %connect.request.url,
+            dns = ?timing.dns,
+            quic = ?timing.quic,
+            settings = ?timing.settings,
+            connect = ?timing.connect,
+            "handshake complete"
+        );
 
         // Return the resulting session with a reference to the control/connect streams.
         // If either stream is closed, then the session will be closed, so we need to keep them around.
-        let session = Session::new(conn, settings, connect);
+        let mut session = Session::new(
+            conn,
+            Arc::new(settings),
+            connect,
+            codes,
+            None,
+            mode,
+            spawner,
+            clock,
+        );
+        session.handshake = timing;
 
         Ok(session)
     }
 
+    pub(crate) fn with_faults(mut self, faults: Option<Arc<FaultInjector>>) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    // Deliver the streams and datagrams that arrived before the CONNECT response.
+    pub(crate) fn with_early(self, early: Early) -> Self {
+        match &self.accept {
+            Some(accept) => accept.command(AcceptCommand::Early(early.uni, early.bi)),
+            // Only a datagram-only session is accepted without an accept task.
+            None => {
+                early.uni.into_iter().for_each(refuse_uni);
+                for (send, recv) in early.bi {
+                    refuse_bi(send, recv);
+                }
+            }
+        }
+        *self.early_datagrams.lock().unwrap() = early.datagrams;
+        self
+    }
+
+    pub(crate) fn with_memory(mut self, memory: Option<Reservation>) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub(crate) fn with_0rtt(mut self, zero_rtt: bool) -> Self {
+        self.zero_rtt = zero_rtt;
+        self
+    }
+
+    // Reset every stream the peer opens on a datagram-only session, until the session is gone.
+    async fn refuse_streams(
+        conn: quinn::Connection,
+        routed: Option<(
+            mpsc::UnboundedReceiver<RoutedUni>,
+            mpsc::UnboundedReceiver<RoutedBi>,
+        )>,
+    ) {
+        // A pooled session shares the connection, so only the streams routed to it are ours.
+        if let Some((mut uni, mut bi)) = routed {
+            let uni = async {
+                while let Some((recv, _)) = uni.recv().await {
+                    refuse_uni(recv);
+                }
+            };
+            let bi = async {
+                while let Some((send, recv, _)) = bi.recv().await {
+                    refuse_bi(send, recv);
+                }
+            };
+            future::join(uni, bi).await;
+            return;
+        }
+
+        let uni = async {
+            while let Ok(recv) = conn.accept_uni().await {
+                refuse_uni(recv);
+            }
+        };
+        let bi = async {
+            while let Ok((send, recv)) = conn.accept_bi().await {
+                refuse_bi(send, recv);
+            }
+        };
+        future::join(uni, bi).await;
+    }
+
+    /// Whether this session carries streams, or only datagrams.
+    pub fn mode(&self) -> SessionMode {
+        self.mode
+    }
+
+    fn check_streams(&self) -> Result<(), SessionError> {
+        match self.mode {
+            SessionMode::Full => Ok(()),
+            SessionMode::DatagramOnly => Err(WebTransportError::StreamsDisabled.into()),
+        }
+    }
+
+    // Keep the last `capacity` events; 0 keeps none.
+    pub(crate) fn with_history(self, capacity: usize) -> Self {
+        self.history.set_capacity(capacity);
+        self
+    }
+
+    pub(crate) fn with_permit(mut self, permit: Option<SessionPermit>) -> Self {
+        self.permit = permit.map(Arc::new);
+        self
+    }
+
+    /// This session's share of the server's [MemoryBudget](crate::MemoryBudget), if one was configured.
+    ///
+    /// [MemoryAccount::used](crate::MemoryAccount::used) reports the bytes reserved for its windows.
+    pub fn memory(&self) -> Option<&crate::MemoryAccount> {
+        self.memory.as_ref().map(|memory| &memory.account)
+    }
+
     /// Accept a new unidirectional stream. See [`quinn::Connection::accept_uni`].
     pub async fn accept_uni(&self) -> Result<RecvStream, SessionError> {
-        if let Some(accept) = &self.accept {
-            poll_fn(|cx| accept.lock().unwrap().poll_accept_uni(cx))
-                .await
-                .map_err(|e| self.map_error(e))
+        self.check_streams()?;
+        let recv = if let Some(accept) = &self.accept {
+            self.recv_accepted(&accept.uni).await?
         } else {
             let recv = self
                 .conn
                 .accept_uni()
                 .await
                 .map_err(|e| self.map_error(e))?;
-            Ok(RecvStream::new(recv, self.error.clone()))
-        }
+            RecvStream::new(recv, self.error.clone(), self.codes.recv)
+        };
+
+        self.record_stream(recv.quic_id(), false);
+        Ok(recv)
     }
 
     /// Accept a new bidirectional stream. See [`quinn::Connection::accept_bi`].
     pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), SessionError> {
-        if let Some(accept) = &self.accept {
-            poll_fn(|cx| accept.lock().unwrap().poll_accept_bi(cx))
-                .await
-                .map_err(|e| self.map_error(e))
+        self.check_streams()?;
+        let (send, recv) = if let Some(accept) = &self.accept {
+            self.recv_accepted(&accept.bi).await?
         } else {
             let (send, recv) = self.conn.accept_bi().await.map_err(|e| self.map_error(e))?;
-            Ok((
-                SendStream::new(send, self.error.clone()),
-                RecvStream::new(recv, self.error.clone()),
-            ))
+            (
+                SendStream::new(
+                    send,
+                    self.error.clone(),
+                    self.scheduler.clone(),
+                    self.flow.clone(),
+                ),
+                RecvStream::new(recv, self.error.clone(), self.codes.recv),
+            )
+        };
+
+        self.record_stream(recv.quic_id(), false);
+        Ok((send, recv))
+    }
+
+    // Wait for the accept task to deliver a stream, or for it to stop because the session is gone.
+    async fn recv_accepted<T>(&self, ready: &flume::Receiver<T>) -> Result<T, SessionError> {
+        ready.recv_async().await.map_err(|_| match &self.pool {
+            Some(_) => pooled_error(&self.conn, &self.error),
+            None => {
+                let err = self.conn.close_reason();
+                self.map_error(err.unwrap_or(quinn::ConnectionError::LocallyClosed))
+            }
+        })
+    }
+
+    // Add a stream to the history, as opened by us or accepted from the peer.
+    fn record_stream(&self, id: quinn::StreamId, opened: bool) {
+        let bidi = id.dir() == quinn::Dir::Bi;
+        let id = quinn::VarInt::from(id).into_inner();
+
+        self.history.record(match opened {
+            true => SessionEventKind::StreamOpened { id, bidi },
+            false => SessionEventKind::StreamAccepted { id, bidi },
+        });
+    }
+
+    /// Start accepting unidirectional streams of a custom HTTP/3 stream type.
+    ///
+    /// Streams with a type this crate doesn't know, such as HTTP/3 extension streams,
+    /// are dropped once their type has been read. After registering `stream_type`,
+    /// they're queued for [Session::accept_raw_uni] instead. Register before the peer
+    /// might open one, since any that arrived earlier were already dropped.
+    ///
+    /// The WebTransport and QPACK stream types are always handled internally.
+    pub fn register_uni(&self, stream_type: StreamUni) {
+        if let Some(accept) = &self.accept {
+            accept.register_uni(stream_type);
+        }
+    }
+
+    /// Accept a unidirectional stream of a custom HTTP/3 stream type.
+    ///
+    /// This [registers](Session::register_uni) `stream_type` if it wasn't already.
+    /// The stream type has been read, so the returned stream starts at its payload.
+    ///
+    /// A [raw](Session::raw) session has no HTTP/3 framing, so this only returns once the connection is closed.
+    pub async fn accept_raw_uni(&self, stream_type: StreamUni) -> Result<RecvStream, SessionError> {
+        match &self.accept {
+            Some(accept) => self.recv_accepted(&accept.register_uni(stream_type)).await,
+            None => Err(self.map_error(self.conn.closed().await)),
         }
     }
 
     /// Open a new unidirectional stream. See [`quinn::Connection::open_uni`].
     pub async fn open_uni(&self) -> Result<SendStream, SessionError> {
-        let mut send = self.conn.open_uni().await.map_err(|e| self.map_error(e))?;
+        self.open_uni_with_data(Bytes::new()).await
+    }
 
-        // Set the stream priority to max and then write the stream header.
-        // Otherwise the application could write data with lower priority than the header, resulting in queuing.
-        // Also the header is very important for determining the session ID without reliable reset.
-        send.set_priority(i32::MAX).ok();
-        Self::write_full(&mut send, &self.header_uni)
+    /// Open a new unidirectional stream, sending `initial` along with the stream header.
+    ///
+    /// The header and payload are queued in a single write, so a short request goes out
+    /// in the first packet rather than waiting on a second write. Like
+    /// [SendStream::write_chunk], it waits for session flow control credit for all of it.
+    pub async fn open_uni_with_data(&self, initial: Bytes) -> Result<SendStream, SessionError> {
+        self.check_streams()?;
+
+        // Wait until the peer's session-level flow control allows another stream.
+        poll_fn(|cx| self.flow.poll_open(cx, false)).await;
+        self.open_uni_framed(&self.header_uni, self.flow.clone(), initial)
             .await
-            .map_err(|e| self.map_error(e))?;
+    }
 
-        // Reset the stream priority back to the default of 0.
-        send.set_priority(0).ok();
-        Ok(SendStream::new(send, self.error.clone()))
+    /// Open a new unidirectional stream with [StreamOptions].
+    ///
+    /// The priority is set before any of your data is written, keeping as much of the
+    /// [send_order](StreamOptions::send_order) as fits in quinn's `i32`.
+    pub async fn open_uni_with(&self, options: StreamOptions) -> Result<SendStream, SessionError> {
+        let send = self.open_uni().await?;
+        if let Some(order) = options.order() {
+            send.set_priority(clamp_order(order)).ok();
+        }
+        Ok(send)
+    }
+
+    /// Open a unidirectional stream with a custom HTTP/3 stream type.
+    ///
+    /// Only `stream_type` is written, with no session ID, so the peer needs to accept it
+    /// by type (ex. [Session::accept_raw_uni]) rather than with [Session::accept_uni].
+    pub async fn open_raw_uni(&self, stream_type: StreamUni) -> Result<SendStream, SessionError> {
+        let mut header = Vec::new();
+        stream_type.encode(&mut header);
+
+        // Not part of the WebTransport session, so its flow control doesn't apply.
+        self.open_uni_framed(&header, SessionFlow::default(), Bytes::new())
+            .await
+    }
+
+    async fn open_uni_framed(
+        &self,
+        header: &[u8],
+        flow: SessionFlow,
+        initial: Bytes,
+    ) -> Result<SendStream, SessionError> {
+        if let Some(delay) = self.faults.as_ref().and_then(|faults| faults.open_delay()) {
+            self.clock.sleep(delay).await;
+        }
+
+        self.check_pooled()?;
+        let mut send = self.conn.open_uni().await.map_err(|e| self.map_error(e))?;
+        self.write_header(&mut send, header, &flow, initial).await?;
+
+        let mut send = SendStream::new(send, self.error.clone(), self.scheduler.clone(), flow);
+        self.record_stream(send.quic_id(), true);
+        self.inject_reset(&mut send);
+
+        Ok(send)
     }
 
     /// Open a new bidirectional stream. See [`quinn::Connection::open_bi`].
     pub async fn open_bi(&self) -> Result<(SendStream, RecvStream), SessionError> {
+        self.open_bi_with_data(Bytes::new()).await
+    }
+
+    /// Open a new bidirectional stream, sending `initial` along with the stream header.
+    ///
+    /// See [Session::open_uni_with_data]; this suits a request/response exchange, where
+    /// the request fits in the first flight.
+    pub async fn open_bi_with_data(
+        &self,
+        initial: Bytes,
+    ) -> Result<(SendStream, RecvStream), SessionError> {
+        self.check_streams()?;
+        if let Some(delay) = self.faults.as_ref().and_then(|faults| faults.open_delay()) {
+            self.clock.sleep(delay).await;
+        }
+
+        // Wait until the peer's session-level flow control allows another stream.
+        poll_fn(|cx| self.flow.poll_open(cx, true)).await;
+
+        self.check_pooled()?;
         let (mut send, recv) = self.conn.open_bi().await.map_err(|e| self.map_error(e))?;
+        self.write_header(&mut send, &self.header_bi, &self.flow, initial)
+            .await?;
+
+        let mut send = SendStream::new(
+            send,
+            self.error.clone(),
+            self.scheduler.clone(),
+            self.flow.clone(),
+        );
+        self.record_stream(send.quic_id(), true);
+        self.inject_reset(&mut send);
+
+        let recv = RecvStream::new(recv, self.error.clone(), self.codes.recv);
+        Ok((send, recv))
+    }
 
+    /// Open a new bidirectional stream with [StreamOptions].
+    ///
+    /// See [Session::open_uni_with].
+    pub async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(SendStream, RecvStream), SessionError> {
+        let (send, recv) = self.open_bi().await?;
+        if let Some(order) = options.order() {
+            send.set_priority(clamp_order(order)).ok();
+        }
+        Ok((send, recv))
+    }
+
+    // Write the stream header, followed by `initial` in the same write.
+    async fn write_header(
+        &self,
+        send: &mut quinn::SendStream,
+        header: &[u8],
+        flow: &SessionFlow,
+        mut initial: Bytes,
+    ) -> Result<(), SessionError> {
         // Set the stream priority to max and then write the stream header.
         // Otherwise the application could write data with lower priority than the header, resulting in queuing.
         // Also the header is very important for determining the session ID without reliable reset.
         send.set_priority(i32::MAX).ok();
-        Self::write_full(&mut send, &self.header_bi)
-            .await
-            .map_err(|e| self.map_error(e))?;
+        if initial.is_empty() {
+            Self::write_full(send, header)
+                .await
+                .map_err(|e| self.map_error(e))?;
+        } else {
+            // The payload counts against session flow control, which may only allow part of it at a time.
+            let mut header = Bytes::copy_from_slice(header);
+            while !initial.is_empty() {
+                let credit = flow.reserve(initial.len()).await;
+                let chunk = initial.split_to(credit.size());
+                let size = chunk.len();
+
+                let mut chunks = [std::mem::take(&mut header), chunk];
+                match send.write_all_chunks(&mut chunks).await {
+                    Ok(()) => credit.sent(size),
+                    Err(quinn::WriteError::ConnectionLost(err)) => return Err(self.map_error(err)),
+                    Err(err) => return Err(WebTransportError::WriteError(err).into()),
+                }
+            }
+        }
 
         // Reset the stream priority back to the default of 0.
         send.set_priority(0).ok();
-        Ok((
-            SendStream::new(send, self.error.clone()),
-            RecvStream::new(recv, self.error.clone()),
-        ))
+        Ok(())
+    }
+
+    fn inject_reset(&self, send: &mut SendStream) {
+        if let Some(code) = self.faults.as_ref().and_then(|f| f.reset_code()) {
+            tracing::debug!(code, "injecting stream reset");
+            send.reset(code).ok();
+        }
+    }
+
+    fn inject_datagram_loss(&self) -> bool {
+        self.faults.as_ref().is_some_and(|f| f.drop_datagram())
     }
 
     /// Asynchronously receives an application datagram from the remote peer.
@@ -256,12 +1017,45 @@ impl Session {
     /// This method is used to receive an application datagram sent by the remote
     /// peer over the connection.
     /// It waits for a datagram to become available and returns the received bytes.
+    ///
+    /// Datagrams claimed by a [DatagramRoute] are delivered there instead.
     pub async fn read_datagram(&self) -> Result<Bytes, SessionError> {
-        let mut datagram = self
-            .conn
-            .read_datagram()
-            .await
-            .map_err(|e| self.map_error(e))?;
+        self.datagrams.recv_unrouted(self).await
+    }
+
+    /// Deliver incoming datagrams that start with the varint `prefix` to a dedicated queue.
+    ///
+    /// Each [DatagramRoute] is read independently, so a protocol multiplexing datagrams by
+    /// a leading varint doesn't need a dispatch task. Registering a prefix again replaces
+    /// the earlier route, which then receives nothing new.
+    pub fn route_datagrams(&self, prefix: u64) -> DatagramRoute {
+        let queue = self.datagrams.register(prefix);
+        DatagramRoute::new(self.clone(), prefix, queue)
+    }
+
+    pub(crate) fn datagrams(&self) -> &Router {
+        &self.datagrams
+    }
+
+    // Read the next datagram for this session from the connection, stripping the session ID.
+    pub(crate) async fn read_raw_datagram(&self) -> Result<Bytes, SessionError> {
+        // The pool already stripped the session ID.
+        if let Some(pool) = &self.pool {
+            return match pool.read_datagram().await {
+                Some(datagram) => Ok(datagram),
+                None => Err(pooled_error(&self.conn, &self.error)),
+            };
+        }
+
+        let early = self.early_datagrams.lock().unwrap().pop_front();
+        let mut datagram = match early {
+            Some(datagram) => datagram,
+            None => self
+                .conn
+                .read_datagram()
+                .await
+                .map_err(|e| self.map_error(e))?,
+        };
 
         let mut cursor = Cursor::new(&datagram);
 
@@ -280,26 +1074,43 @@ impl Session {
         Ok(datagram)
     }
 
+    /// Returns true if the peer accepts datagrams.
+    ///
+    /// The peer has to enable them in both its HTTP/3 SETTINGS (`SETTINGS_H3_DATAGRAM`) and
+    /// its QUIC transport parameters. A peer may leave them out and only use streams, in
+    /// which case [`send_datagram`](Self::send_datagram) fails with
+    /// [`WebTransportError::DatagramsUnsupported`].
+    pub fn datagrams_supported(&self) -> bool {
+        self.peer_datagrams() && self.conn.max_datagram_size().is_some()
+    }
+
+    // Whether the peer's SETTINGS allow datagrams. A raw session has none to check.
+    fn peer_datagrams(&self) -> bool {
+        self.settings.as_ref().is_none_or(|s| s.peer_datagrams)
+    }
+
+    // Sending a datagram the peer didn't enable is a protocol violation, so refuse.
+    fn check_datagrams(&self) -> Result<(), SessionError> {
+        match self.peer_datagrams() {
+            true => Ok(()),
+            false => Err(WebTransportError::DatagramsUnsupported.into()),
+        }
+    }
+
     /// Sends an application datagram to the remote peer.
     ///
     /// Datagrams are unreliable and may be dropped or delivered out of order.
     /// The data must be smaller than [`max_datagram_size`](Self::max_datagram_size).
     pub fn send_datagram(&self, data: Bytes) -> Result<(), SessionError> {
-        let result = if !self.header_datagram.is_empty() {
-            // Unfortunately, we need to allocate/copy each datagram because of the Quinn API.
-            // Pls go +1 if you care: https://github.com/quinn-rs/quinn/issues/1724
-            let mut buf = BytesMut::with_capacity(self.header_datagram.len() + data.len());
-
-            // Prepend the datagram with the header indicating the session ID.
-            buf.extend_from_slice(&self.header_datagram);
-            buf.extend_from_slice(&data);
-
-            self.conn.send_datagram(buf.into())
-        } else {
-            self.conn.send_datagram(data)
-        };
+        self.check_pooled()?;
+        self.check_datagrams()?;
+        if self.inject_datagram_loss() {
+            return Ok(());
+        }
 
-        result.map_err(|e| self.map_error(e))?;
+        self.conn
+            .send_datagram(self.frame_datagram(data))
+            .map_err(|e| self.map_error(e))?;
         Ok(())
     }
 
@@ -311,32 +1122,103 @@ impl Session {
     /// Datagrams are unreliable and may be dropped or delivered out of order.
     /// The data must be smaller than [`max_datagram_size`](Self::max_datagram_size).
     pub async fn send_datagram_wait(&self, data: Bytes) -> Result<(), SessionError> {
-        let result = if !self.header_datagram.is_empty() {
-            // Unfortunately, we need to allocate/copy each datagram because of the Quinn API.
-            // Pls go +1 if you care: https://github.com/quinn-rs/quinn/issues/1724
-            let mut buf = BytesMut::with_capacity(self.header_datagram.len() + data.len());
+        self.check_pooled()?;
+        self.check_datagrams()?;
+        if self.inject_datagram_loss() {
+            return Ok(());
+        }
 
-            // Prepend the datagram with the header indicating the session ID.
-            buf.extend_from_slice(&self.header_datagram);
-            buf.extend_from_slice(&data);
+        self.conn
+            .send_datagram_wait(self.frame_datagram(data))
+            .await
+            .map_err(|e| self.map_error(e))?;
+        Ok(())
+    }
 
-            self.conn.send_datagram_wait(buf.into()).await
-        } else {
-            self.conn.send_datagram_wait(data).await
+    /// Sends an application datagram with [DatagramOptions].
+    ///
+    /// Without a [max_age](DatagramOptions::max_age), this is [`send_datagram`](Self::send_datagram).
+    /// With one, a full send buffer doesn't push out the oldest datagram; this one waits in the
+    /// background for room instead, and is dropped if none frees up in time. Datagrams wait their
+    /// turn in one queue per session, and are dropped if it's full too. quinn can't expire
+    /// a datagram once it's in the buffer, and has no datagram priorities, so the
+    /// [priority](DatagramOptions::priority) is ignored.
+    pub fn send_datagram_with(
+        &self,
+        data: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), SessionError> {
+        let Some(max_age) = options.max_age else {
+            return self.send_datagram(data);
         };
 
-        result.map_err(|e| self.map_error(e))?;
+        self.check_pooled()?;
+        self.check_datagrams()?;
+        if self.inject_datagram_loss() {
+            return Ok(());
+        }
+
+        let data = self.frame_datagram(data);
+        if data.len() <= self.conn.datagram_send_buffer_space() {
+            self.conn
+                .send_datagram(data)
+                .map_err(|e| self.map_error(e))?;
+            return Ok(());
+        }
+
+        // Fail now for errors that waiting won't fix.
+        match self.conn.max_datagram_size() {
+            Some(max) if data.len() <= max => {}
+            Some(_) => return Err(quinn::SendDatagramError::TooLarge.into()),
+            None => return Err(quinn::SendDatagramError::UnsupportedByPeer.into()),
+        }
+
+        let expiring = self.expiring.get_or_init(|| {
+            let (queue, expiring) = mpsc::channel(EXPIRING_DATAGRAMS);
+            let send = send_expiring(self.conn.clone(), self.clock.clone(), expiring);
+            self.spawner.spawn(send.instrument(session_span(self.id)));
+            queue
+        });
+
+        let deadline = self.clock.now() + max_age;
+        match expiring.try_send((data, deadline)) {
+            Ok(()) => {}
+            Err(TrySendError::Full((data, _))) => {
+                tracing::trace!(
+                    len = data.len(),
+                    "dropping outbound datagram, too many waiting"
+                )
+            }
+            // The connection is gone, and the datagram with it.
+            Err(TrySendError::Closed(_)) => {}
+        }
+
         Ok(())
     }
 
+    // Prepend the header indicating the session ID, if there is one.
+    fn frame_datagram(&self, data: Bytes) -> Bytes {
+        if self.header_datagram.is_empty() {
+            return data;
+        }
+
+        // Unfortunately, we need to allocate/copy each datagram because of the Quinn API.
+        // Pls go +1 if you care: https://github.com/quinn-rs/quinn/issues/1724
+        let mut buf = BytesMut::with_capacity(self.header_datagram.len() + data.len());
+        buf.extend_from_slice(&self.header_datagram);
+        buf.extend_from_slice(&data);
+        buf.into()
+    }
+
     /// Computes the maximum size of datagrams that may be passed to
     /// [`send_datagram`](Self::send_datagram).
+    ///
+    /// Returns `0` when the peer doesn't [support datagrams](Self::datagrams_supported).
     pub fn max_datagram_size(&self) -> usize {
-        let mtu = self
-            .conn
-            .max_datagram_size()
-            .expect("datagram support is required");
-        mtu.saturating_sub(self.header_datagram.len())
+        match self.conn.max_datagram_size() {
+            Some(mtu) if self.peer_datagrams() => mtu.saturating_sub(self.header_datagram.len()),
+            _ => 0,
+        }
     }
 
     /// The number of bytes of available space in the outgoing datagram buffer.
@@ -349,6 +1231,40 @@ impl Session {
             .saturating_sub(self.header_datagram.len())
     }
 
+    /// Returns how many unidirectional streams the peer allows this session: those opened so
+    /// far plus [`remaining_uni`](Self::remaining_uni).
+    ///
+    /// None if the peer didn't limit the session's streams.
+    pub fn max_open_uni(&self) -> Option<u64> {
+        let (opened, remaining) = self.flow.streams(false);
+        Some(opened + remaining?)
+    }
+
+    /// Returns how many more unidirectional streams the peer's session limit allows.
+    ///
+    /// quinn doesn't expose the peer's QUIC stream limit, so [`open_uni`](Self::open_uni) may
+    /// still wait on it even when this is non-zero. None if the peer didn't limit the session.
+    pub fn remaining_uni(&self) -> Option<u64> {
+        self.flow.streams(false).1
+    }
+
+    /// Returns how many bidirectional streams the peer allows this session: those opened so
+    /// far plus [`remaining_bi`](Self::remaining_bi).
+    ///
+    /// None if the peer didn't limit the session's streams.
+    pub fn max_open_bi(&self) -> Option<u64> {
+        let (opened, remaining) = self.flow.streams(true);
+        Some(opened + remaining?)
+    }
+
+    /// Returns how many more bidirectional streams the peer's session limit allows.
+    ///
+    /// quinn doesn't expose the peer's QUIC stream limit, so [`open_bi`](Self::open_bi) may
+    /// still wait on it even when this is non-zero. None if the peer didn't limit the session.
+    pub fn remaining_bi(&self) -> Option<u64> {
+        self.flow.streams(true).1
+    }
+
     /// Close the session with an error code and reason.
     ///
     /// When there is a session ID (WebTransport over HTTP/3), a `CloseWebTransportSession`
@@ -358,64 +1274,205 @@ impl Session {
     /// The capsule write and connection close happen asynchronously in a spawned task.
     /// Callers should `await` [`Session::closed()`] to ensure the capsule has been
     /// delivered. Session operations will fail once the QUIC connection is closed.
+    ///
+    /// Only the first close takes effect, whether it was ours or the peer's; later calls
+    /// are ignored. [`web_transport_trait::Session::close_reason`] returns the one that won.
     pub fn close(&self, code: u32, reason: &[u8]) {
         // Record the local close error. First writer wins — if the background
         // task already set a remote close error, or close() was already called,
         // this is a no-op.
         let err = SessionError::ConnectionError(quinn::ConnectionError::LocallyClosed);
         if self.error.set(err).is_err() {
+            // If the peer closed first, note that this close crossed it.
+            if self.close_record.get().is_some() {
+                self.close_record.local(code, reason);
+            }
             return;
         }
+        self.close_record.local(code, reason);
 
-        if self.session_id.is_some() {
-            // Take the send stream for the capsule write.
-            let send = self.connect_send.lock().unwrap().take();
+        // Wake anything waiting on flow control so it sees the session is closed.
+        self.flow.close();
 
-            if let Some(send) = send {
-                let reason = String::from_utf8_lossy(reason).into_owned();
-                let conn = self.conn.clone();
-                let capsule =
-                    web_transport_proto::Capsule::CloseWebTransportSession { code, reason };
-                let timeout = (self.rtt() * 3).max(Duration::from_millis(100));
+        if self.session_id.is_some() {
+            let connect_send = self.connect_send.clone();
+            let reason = Bytes::copy_from_slice(reason);
+            let conn = self.conn.clone();
+            let pool = self.pool.clone();
+            let capsule = web_transport_proto::Capsule::CloseWebTransportSession { code, reason };
+            let timeout = (self.rtt() * 3).max(Duration::from_millis(100));
+            let history = self.history.clone();
+            let clock = self.clock.clone();
+
+            let close = async move {
+                // Take the send stream for the capsule write, once any drain() write is done.
+                let Ok(mut slot) = clock.timeout(timeout, connect_send.lock()).await else {
+                    tracing::debug!("timeout waiting for drain; force-closing connection");
+                    let http3_code = web_transport_proto::error_to_http3(code);
+                    Self::abort(&conn, pool.as_deref(), http3_code.try_into().unwrap());
+                    return;
+                };
 
-                tokio::spawn(async move {
-                    Self::close_with_capsule(conn, send, capsule, code, timeout).await;
-                });
-            }
+                if let Some(send) = slot.take() {
+                    drop(slot);
+                    let expired = clock.sleep(timeout);
+                    Self::close_with_capsule(conn, send, capsule, code, expired, pool, history)
+                        .await;
+                }
+            };
+            self.spawner.spawn(close.instrument(session_span(self.id)));
         } else {
             // Raw QUIC mode: no capsule needed.
             self.conn.close(code.into(), reason);
         }
     }
 
+    // Encode the capsule, then wrap it in an HTTP/3 DATA frame.
+    // In HTTP/3, capsule data is carried inside DATA frames on the CONNECT
+    // stream (RFC 9297 Section 3.2).
+    fn capsule_frame(
+        capsule: &web_transport_proto::Capsule,
+        history: &SessionHistory,
+    ) -> Option<Vec<u8>> {
+        web_transport_proto::log_frame(
+            web_transport_proto::Direction::Sent,
+            web_transport_proto::WireFrame::Capsule(capsule),
+        );
+        history.record(SessionEventKind::CapsuleSent(capsule.clone()));
+
+        let mut capsule_bytes = Vec::new();
+        capsule.encode(&mut capsule_bytes);
+
+        let mut frame = Vec::new();
+        Frame::DATA.encode(&mut frame);
+        VarInt::try_from(capsule_bytes.len())
+            .ok()?
+            .encode(&mut frame);
+        frame.extend_from_slice(&capsule_bytes);
+
+        Some(frame)
+    }
+
+    /// Ask the peer to wrap up the session with a `DrainWebTransportSession` capsule.
+    ///
+    /// Nothing is closed: streams and datagrams keep flowing in both directions, so a
+    /// server can signal a graceful shutdown and [close](Self::close) once the peer is done.
+    /// A [raw](Self::raw) session has no CONNECT stream, so this does nothing.
+    pub async fn drain(&self) -> Result<(), SessionError> {
+        if self.session_id.is_none() {
+            return Ok(());
+        }
+
+        let mut slot = self.connect_send.lock().await;
+        let Some(send) = slot.as_mut() else {
+            // close() already took the stream.
+            return Err(self.map_error(quinn::ConnectionError::LocallyClosed));
+        };
+
+        let drain = web_transport_proto::Capsule::DrainWebTransportSession;
+        let frame = Self::capsule_frame(&drain, &self.history).expect("drain capsule is tiny");
+
+        send.write_all(&frame).await.map_err(|e| match e {
+            quinn::WriteError::ConnectionLost(e) => self.map_error(e),
+            // The peer stopped the CONNECT stream, which ends the session anyway.
+            _ => self.map_error(quinn::ConnectionError::LocallyClosed),
+        })
+    }
+
+    /// Ask the peer to reconnect to `url` with a `ReconnectHint` capsule.
+    ///
+    /// This is a vendor extension: other implementations ignore it. Nothing is closed, so a
+    /// server migrating its clients usually pairs it with [drain](Self::drain).
+    /// A [raw](Self::raw) session has no CONNECT stream, so this does nothing.
+    pub async fn send_reconnect_hint(&self, url: Url) -> Result<(), SessionError> {
+        if self.session_id.is_none() {
+            return Ok(());
+        }
+
+        let mut slot = self.connect_send.lock().await;
+        let Some(send) = slot.as_mut() else {
+            // close() already took the stream.
+            return Err(self.map_error(quinn::ConnectionError::LocallyClosed));
+        };
+
+        let hint = web_transport_proto::Capsule::ReconnectHint { url };
+        let frame = Self::capsule_frame(&hint, &self.history).expect("URL fits in a varint");
+
+        send.write_all(&frame).await.map_err(|e| match e {
+            quinn::WriteError::ConnectionLost(e) => self.map_error(e),
+            // The peer stopped the CONNECT stream, which ends the session anyway.
+            _ => self.map_error(quinn::ConnectionError::LocallyClosed),
+        })
+    }
+
+    /// Wait until the peer asks to wrap up the session with a `DrainWebTransportSession` capsule.
+    ///
+    /// An HTTP/3 GOAWAY from the peer drains every session on the connection the same way.
+    /// The session keeps working; it's up to the application to finish what it's doing and
+    /// [close](Self::close). Also returns if the session is closed without being drained.
+    pub async fn draining(&self) {
+        let mut draining = self.draining.subscribe();
+        let going_away = async {
+            match &self.settings {
+                Some(settings) => settings.going_away().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = draining.wait_for(|draining| *draining) => {}
+            _ = going_away => {}
+            _ = self.closed() => {}
+        }
+    }
+
+    /// Wait for the peer to send a `ReconnectHint` capsule, returning the URL it suggested.
+    ///
+    /// Returns immediately if a hint already arrived, or `None` once the session is closed
+    /// without one. A later hint replaces an earlier one.
+    pub async fn reconnect_hint(&self) -> Option<Url> {
+        let mut reconnect = self.reconnect.subscribe();
+
+        tokio::select! {
+            res = reconnect.wait_for(Option::is_some) => res.ok()?.clone(),
+            _ = self.closed() => self.reconnect.borrow().clone(),
+        }
+    }
+
+    /// Returns true if the peer has asked to drain the session. See [Self::draining].
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow() || self.goaway().is_some()
+    }
+
+    // The stream ID from the peer's GOAWAY, if it sent one.
+    fn goaway(&self) -> Option<VarInt> {
+        self.settings.as_ref()?.goaway()
+    }
+
     /// Write the CloseWebTransportSession capsule, finish the stream, wait for
-    /// the peer to close the connection (or timeout), then force-close.
+    /// the peer to close the connection (or until `expired`), then force-close.
+    ///
+    /// A pooled session waits for the peer to close its CONNECT stream instead.
     async fn close_with_capsule(
         conn: quinn::Connection,
         mut send: quinn::SendStream,
         capsule: web_transport_proto::Capsule,
         code: u32,
-        timeout: std::time::Duration,
+        expired: BoxFuture<'static, ()>,
+        pool: Option<Arc<PooledSession>>,
+        history: SessionHistory,
     ) {
         let http3_code: quinn::VarInt = web_transport_proto::error_to_http3(code)
             .try_into()
             .unwrap();
+        let pool = pool.as_deref();
 
-        // Encode the capsule, then wrap it in an HTTP/3 DATA frame.
-        // In HTTP/3, capsule data is carried inside DATA frames on the CONNECT
-        // stream (RFC 9297 Section 3.2).
-        let mut capsule_bytes = Vec::new();
-        capsule.encode(&mut capsule_bytes);
-
-        let mut frame = Vec::new();
-        Frame::DATA.encode(&mut frame);
-        let Ok(len) = VarInt::try_from(capsule_bytes.len()) else {
+        let Some(frame) = Self::capsule_frame(&capsule, &history) else {
             tracing::warn!("capsule too large to encode as DATA frame");
-            conn.close(http3_code, b"");
+            send.reset(http3_code).ok();
+            Self::abort(&conn, pool, http3_code);
             return;
         };
-        len.encode(&mut frame);
-        frame.extend_from_slice(&capsule_bytes);
 
         // Bound the entire graceful-close sequence (capsule write, FIN,
         // waiting for the peer) with a single timeout.  Without this, an
@@ -425,43 +1482,121 @@ impl Session {
             // Write the DATA frame to the CONNECT send stream.
             if let Err(e) = send.write_all(&frame).await {
                 tracing::warn!(?e, "failed to write CloseWebTransportSession capsule");
-                conn.close(http3_code, b"");
+                Self::abort(&conn, pool, http3_code);
                 return;
             }
 
             // FIN the send stream so the peer knows no more capsules are coming.
             if let Err(e) = send.finish() {
                 tracing::warn!(?e, "failed to finish CONNECT send stream");
-                conn.close(http3_code, b"");
+                Self::abort(&conn, pool, http3_code);
                 return;
             }
 
             // Wait for the peer to close the CONNECT stream after receiving the capsule.
-            conn.closed().await;
+            match pool {
+                Some(pool) => pool.closed().await,
+                None => {
+                    conn.closed().await;
+                }
+            }
         };
 
-        if tokio::time::timeout(timeout, graceful).await.is_err() {
-            tracing::debug!("timeout waiting for peer to close; force-closing connection");
-            conn.close(http3_code, b"");
+        tokio::select! {
+            biased;
+            _ = graceful => {}
+            _ = expired => {
+                tracing::debug!("timeout waiting for peer to close; force-closing connection");
+                Self::abort(&conn, pool, http3_code);
+            }
+        }
+    }
+
+    // Give up on closing gracefully: close the connection, or just the session if it's pooled.
+    fn abort(conn: &quinn::Connection, pool: Option<&PooledSession>, code: quinn::VarInt) {
+        match pool {
+            Some(pool) => pool.close(),
+            None => conn.close(code, b""),
         }
     }
 
     /// Wait until the session is closed, returning the error. See [`quinn::Connection::closed`].
     ///
     /// If the peer sent a `CloseWebTransportSession` capsule, the returned error will be
-    /// [`WebTransportError::Closed`] with the code and reason from the capsule.
+    /// [`WebTransportError::Closed`] with the code and reason from the capsule. If it sent
+    /// GOAWAY and then closed the connection without an error, it's [`SessionError::GoAway`].
+    /// Either way it's tagged with the session's [id](Self::id), see [`SessionError::InSession`].
     ///
     /// Unlike [`quinn::Connection::closed`], this does **not** return early when
     /// [`close()`](Self::close) has been called. It waits for the underlying QUIC
     /// connection to shut down, ensuring the `CloseWebTransportSession` capsule has
     /// been delivered. Use [`close_reason()`](Self::close_reason) for a non-blocking check.
+    ///
+    /// A pooled session instead waits for its CONNECT stream to close, or the connection.
     pub async fn closed(&self) -> SessionError {
-        self.map_error(self.conn.closed().await)
+        if let Some(pool) = &self.pool {
+            tokio::select! {
+                _ = pool.closed() => {}
+                _ = self.conn.closed() => {}
+            }
+            return self.tag_close(pooled_error(&self.conn, &self.error));
+        }
+
+        self.tag_close(self.map_close(self.conn.closed().await))
     }
 
     /// Return why the session was closed, or None if it's not closed. See [`quinn::Connection::close_reason`].
+    ///
+    /// Use [`web_transport_trait::Session::close_reason`] for the code and reason of the first close instead.
     pub fn close_reason(&self) -> Option<SessionError> {
-        self.conn.close_reason().map(|e| self.map_error(e))
+        if self.pool.as_ref().is_some_and(|pool| pool.is_closed()) {
+            return Some(self.tag_close(pooled_error(&self.conn, &self.error)));
+        }
+
+        let err = self.conn.close_reason()?;
+        Some(self.tag_close(self.map_close(err)))
+    }
+
+    // Like map_error, but a connection closed without an error after GOAWAY was a graceful shutdown.
+    fn map_close(&self, err: quinn::ConnectionError) -> SessionError {
+        let graceful = matches!(
+            &err,
+            quinn::ConnectionError::ApplicationClosed(close)
+                if close.error_code.into_inner() == codes::h3::NO_ERROR
+        );
+
+        match self.goaway() {
+            Some(_) if graceful && self.error.get().is_none() => SessionError::GoAway,
+            _ => self.map_error(err),
+        }
+    }
+
+    // Tag the close error with the session's id, then the recorded events if any are being kept.
+    fn tag_close(&self, err: SessionError) -> SessionError {
+        let err = SessionError::InSession(Box::new(err), self.id);
+        match self.history.capacity() {
+            0 => err,
+            _ => SessionError::WithHistory(Box::new(err), self.history.events()),
+        }
+    }
+
+    /// The most recent events, oldest first, if enabled with
+    /// [ClientBuilder::with_session_history](crate::ClientBuilder::with_session_history) or
+    /// [ServerBuilder::with_session_history](crate::ServerBuilder::with_session_history).
+    ///
+    /// Streams opened and accepted, capsules sent and received, and errors, each timed
+    /// from when the session was established. The same events are attached to the close
+    /// error as [SessionError::WithHistory], so they show up wherever it's logged.
+    pub fn history(&self) -> Vec<SessionEvent> {
+        self.history.events()
+    }
+
+    // A pooled session can close while the connection stays open, so check it first.
+    fn check_pooled(&self) -> Result<(), SessionError> {
+        match (&self.pool, self.error.get()) {
+            (Some(_), Some(err)) => Err(err.clone()),
+            _ => Ok(()),
+        }
     }
 
     /// Replace connection-level errors with the stored session error if available.
@@ -497,18 +1632,47 @@ impl Session {
         request: impl Into<ConnectRequest>,
         response: impl Into<ConnectResponse>,
     ) -> Self {
+        let codes = DropCodes::default();
+        let id = SessionId::next();
+        let drop = Arc::new(SessionDrop {
+            conn: conn.clone(),
+            code: codes.session,
+            id,
+            pooled: None,
+        });
         Self {
             conn,
+            id,
+            drop,
             session_id: None,
             header_uni: Default::default(),
             header_bi: Default::default(),
             header_datagram: Default::default(),
             accept: None,
             settings: None,
-            connect_send: Arc::new(Mutex::new(None)),
+            connect_send: Default::default(),
+            draining: Arc::new(watch::Sender::new(false)),
+            reconnect: Arc::new(watch::Sender::new(None)),
+            flow: SessionFlow::default(),
             error: Arc::new(OnceLock::new()),
+            close_record: CloseRecord::new(),
             request: request.into(),
             response: response.into(),
+            faults: None,
+            handshake: HandshakeTiming::default(),
+            scheduler: Default::default(),
+            memory: None,
+            codes,
+            zero_rtt: false,
+            permit: None,
+            datagrams: Default::default(),
+            early_datagrams: Default::default(),
+            expiring: Default::default(),
+            pool: None,
+            mode: SessionMode::Full,
+            spawner: Spawner::Tokio,
+            clock: Arc::new(TokioClock),
+            history: SessionHistory::default(),
         }
     }
 
@@ -516,17 +1680,112 @@ impl Session {
         &self.request
     }
 
+    /// The identifier this session logs with. See [SessionId].
+    ///
+    /// Every tracing event from the session's background tasks is inside a `session`
+    /// span with this `id`, so log it with your own events to correlate the two.
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
     pub fn response(&self) -> &ConnectResponse {
         &self.response
     }
 
+    /// The protocols the client offered, in order of preference.
+    pub fn protocols(&self) -> &[String] {
+        &self.request.protocols
+    }
+
+    /// The protocol the server selected from [Self::protocols], if any.
+    pub fn protocol(&self) -> Option<&str> {
+        self.response.protocol.as_deref()
+    }
+
     /// Return connection-level statistics.
     pub fn stats(&self) -> SessionStats {
+        let ignored_uni = match &self.accept {
+            Some(accept) => accept.ignored_uni.load(Ordering::Relaxed),
+            None => 0,
+        };
+
         SessionStats {
             stats: self.conn.stats(),
             rtt: self.conn.rtt(),
+            ignored_uni,
+        }
+    }
+
+    /// The round-trip time to the peer, as QUIC measures it from acknowledgements.
+    ///
+    /// This is quinn's smoothed estimate, the same as [SessionStats], so probing costs
+    /// nothing on the wire. It fails only if the session is closed.
+    pub async fn probe_rtt(&self) -> Result<Duration, SessionError> {
+        if let Some(err) = self.error.get() {
+            return Err(err.clone());
+        }
+        if let Some(err) = self.conn.close_reason() {
+            return Err(err.into());
+        }
+
+        Ok(self.conn.rtt())
+    }
+
+    /// Choose what happens to incoming unidirectional streams of an unknown type.
+    ///
+    /// Defaults to [UnknownStreamPolicy::Ignore]. Applies to every clone of the session;
+    /// streams ignored before the call still count towards [UnknownStreamPolicy::CloseAfter].
+    pub fn set_unknown_stream_policy(&self, policy: UnknownStreamPolicy) {
+        if let Some(accept) = &self.accept {
+            accept.command(AcceptCommand::Policy(policy));
+        }
+    }
+
+    /// Limit how long incoming streams may take to send their header, and how long it may be.
+    ///
+    /// Defaults to [HeaderBudget::default]. Applies to every clone of the session, for
+    /// streams that arrive after the call. A stream over the budget is stopped with
+    /// [GENERAL_PROTOCOL_ERROR](codes::h3::GENERAL_PROTOCOL_ERROR) and never accepted.
+    pub fn set_header_budget(&self, budget: HeaderBudget) {
+        if let Some(accept) = &self.accept {
+            *accept.budget.lock().unwrap() = budget;
         }
     }
+
+    /// Choose how writes are scheduled across this session's send streams.
+    ///
+    /// Defaults to [SendOrdering::BestEffort]. Applies to every clone of the session and
+    /// to writes started after the call, including on streams that are already open.
+    pub fn set_send_ordering(&self, ordering: SendOrdering) {
+        self.scheduler.set_ordering(ordering);
+    }
+
+    pub fn send_ordering(&self) -> SendOrdering {
+        self.scheduler.ordering()
+    }
+
+    /// Return how long each phase of the handshake took.
+    ///
+    /// Only client sessions are timed; phases that didn't happen here
+    /// (e.g. DNS when dialing an IP, or everything on the server) are `None`.
+    pub fn handshake_timing(&self) -> HandshakeTiming {
+        self.handshake
+    }
+
+    /// Returns true if the server accepted this session from 0-RTT data, before the
+    /// handshake was confirmed. See [Request::is_0rtt](crate::Request::is_0rtt).
+    pub fn is_0rtt(&self) -> bool {
+        self.zero_rtt
+    }
+
+    /// The certificate chain the peer authenticated with, leaf first.
+    ///
+    /// On a client this is the server's chain. On a server it's `None` unless client
+    /// certificates were requested and the client presented one, which has already been
+    /// verified; see [ServerBuilder::with_client_ca_roots](crate::ServerBuilder::with_client_ca_roots).
+    pub fn peer_certificates(&self) -> Option<Vec<rustls::pki_types::CertificateDer<'static>>> {
+        peer_certificates(&self.conn)
+    }
 }
 
 impl Deref for Session {
@@ -552,134 +1811,480 @@ impl PartialEq for Session {
 impl Eq for Session {}
 
 // Type aliases just so clippy doesn't complain about the complexity.
-type AcceptUni = dyn Stream<Item = Result<quinn::RecvStream, quinn::ConnectionError>> + Send;
-type AcceptBi = dyn Stream<Item = Result<(quinn::SendStream, quinn::RecvStream), quinn::ConnectionError>>
-    + Send;
-type PendingUni = dyn Future<Output = Result<(StreamUni, quinn::RecvStream), SessionError>> + Send;
-type PendingBi = dyn Future<Output = Result<Option<(quinn::SendStream, quinn::RecvStream)>, SessionError>>
+type AcceptUni = dyn Stream<Item = Result<Pin<Box<PendingUni>>, SessionError>> + Send;
+type AcceptBi = dyn Stream<Item = Result<Pin<Box<PendingBi>>, SessionError>> + Send;
+type PendingUni =
+    dyn Future<Output = Result<(StreamUni, quinn::RecvStream, Header), SessionError>> + Send;
+type PendingBi = dyn Future<Output = Result<Option<(quinn::SendStream, quinn::RecvStream, Header)>, SessionError>>
     + Send;
 
+// Reads a stream header a chunk at a time instead of a byte at a time, keeping any
+// payload that arrived alongside it so the first read doesn't need another wakeup.
+#[derive(Default)]
+pub(crate) struct Header {
+    // Received bytes not yet consumed by the header.
+    buf: Bytes,
+    // The stream offset of `buf`.
+    offset: u64,
+    // Caps how many bytes the header may take.
+    budget: HeaderBudget,
+}
+
+impl Header {
+    fn new(budget: HeaderBudget) -> Self {
+        Self {
+            budget,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) async fn read_varint(
+        &mut self,
+        recv: &mut quinn::RecvStream,
+    ) -> Result<VarInt, SessionError> {
+        loop {
+            let decoded = self
+                .budget
+                .decode_varint(self.offset as usize, &self.buf)
+                .map_err(WebTransportError::from)?;
+            if let Some((v, used)) = decoded {
+                self.buf.advance(used);
+                self.offset += used as u64;
+                return Ok(v);
+            }
+
+            let chunk = recv
+                .read_chunk(usize::MAX, true)
+                .await
+                .ok()
+                .flatten()
+                .ok_or(WebTransportError::UnknownSession)?;
+            self.buf = match self.buf.is_empty() {
+                true => chunk.bytes,
+                false => [&self.buf[..], &chunk.bytes[..]].concat().into(),
+            };
+        }
+    }
+
+    fn into_recv(
+        self,
+        recv: quinn::RecvStream,
+        error: Arc<OnceLock<SessionError>>,
+        drop_code: u32,
+    ) -> RecvStream {
+        RecvStream::new(recv, error, drop_code).with_buffered(self.buf, self.offset)
+    }
+}
+
+// quinn's rustls session reports the peer's chain as its identity.
+pub(crate) fn peer_certificates(
+    conn: &quinn::Connection,
+) -> Option<Vec<rustls::pki_types::CertificateDer<'static>>> {
+    let identity = conn.peer_identity()?;
+    let chain = identity
+        .downcast::<Vec<rustls::pki_types::CertificateDer<'static>>>()
+        .ok()?;
+    Some(*chain)
+}
+
+// Run `read` within the budget's timeout, which counts as a violation.
+async fn within_budget<T>(
+    clock: &dyn Clock,
+    budget: HeaderBudget,
+    read: impl Future<Output = Result<T, SessionError>>,
+) -> Result<T, SessionError> {
+    let Some(timeout) = budget.timeout else {
+        return read.await;
+    };
+
+    match clock.timeout(timeout, read).await {
+        Ok(res) => res,
+        Err(_) => Err(WebTransportError::from(HeaderViolation::TimedOut(timeout)).into()),
+    }
+}
+
+// Whether a stream broke its header budget, and should be told so.
+fn is_violation(err: &SessionError) -> bool {
+    matches!(
+        err,
+        SessionError::WebTransportError(WebTransportError::ProtocolViolation(_))
+    )
+}
+
+fn general_protocol_error() -> quinn::VarInt {
+    quinn::VarInt::from_u64(codes::h3::GENERAL_PROTOCOL_ERROR).unwrap()
+}
+
+// Why a pooled session is gone: how it was closed, or else why the connection was.
+fn pooled_error(conn: &quinn::Connection, error: &OnceLock<SessionError>) -> SessionError {
+    error
+        .get()
+        .cloned()
+        .or_else(|| conn.close_reason().map(Into::into))
+        .unwrap_or_else(|| quinn::ConnectionError::LocallyClosed.into())
+}
+
+// Stop a stream the peer opened on a datagram-only session.
+fn refuse_uni(mut recv: quinn::RecvStream) {
+    let code = web_transport_proto::error_to_http3(codes::STREAMS_DISABLED);
+    recv.stop(quinn::VarInt::try_from(code).unwrap()).ok();
+}
+
+fn refuse_bi(mut send: quinn::SendStream, recv: quinn::RecvStream) {
+    let code = web_transport_proto::error_to_http3(codes::STREAMS_DISABLED);
+    send.reset(quinn::VarInt::try_from(code).unwrap()).ok();
+    refuse_uni(recv);
+}
+
+// The receiving end of the accept task, shared by every clone of a session.
+//
+// The task decodes stream headers and hands the streams over channels, so accepting
+// never holds a lock while polling, and a cancelled accept call just stops receiving.
+#[derive(Clone)]
+struct Accepted {
+    uni: flume::Receiver<RecvStream>,
+    bi: flume::Receiver<(SendStream, RecvStream)>,
+
+    // One channel per registered custom stream type.
+    raw_uni: Arc<Mutex<HashMap<StreamUni, flume::Receiver<RecvStream>>>>,
+
+    // The task exits once every clone of the session drops this.
+    commands: mpsc::UnboundedSender<AcceptCommand>,
+
+    // How many unknown uni streams the task dropped.
+    ignored_uni: Arc<AtomicU64>,
+
+    // Applied to each stream as it arrives, so it's shared rather than a command.
+    budget: Arc<Mutex<HeaderBudget>>,
+}
+
+impl Accepted {
+    // Start queueing streams of `typ`, returning where they're delivered.
+    fn register_uni(&self, typ: StreamUni) -> flume::Receiver<RecvStream> {
+        let mut raw_uni = self.raw_uni.lock().unwrap();
+        raw_uni
+            .entry(typ)
+            .or_insert_with(|| {
+                let (send, recv) = flume::unbounded();
+                self.commands.send(AcceptCommand::Register(typ, send)).ok();
+                recv
+            })
+            .clone()
+    }
+
+    fn command(&self, command: AcceptCommand) {
+        // The task only exits once the connection is gone, so there's nothing to apply it to.
+        self.commands.send(command).ok();
+    }
+}
+
+// Changes to the accept task's state, applied in order before any stream that arrives later.
+enum AcceptCommand {
+    Register(StreamUni, flume::Sender<RecvStream>),
+    Policy(UnknownStreamPolicy),
+    Early(
+        Vec<quinn::RecvStream>,
+        Vec<(quinn::SendStream, quinn::RecvStream)>,
+    ),
+}
+
 // Logic just for accepting streams, which is annoying because of the stream header.
-pub struct SessionAccept {
+//
+// Runs as its own task, owning all of its state, and sends each decoded stream to
+// the session over a channel. See [Accepted].
+struct SessionAccept {
     session_id: VarInt,
 
     // Shared session error for propagation to accepted streams.
     error: Arc<OnceLock<SessionError>>,
 
+    // Shared send scheduler for accepted bidirectional streams.
+    scheduler: Arc<Scheduler>,
+
+    // Shared flow control for accepted bidirectional streams.
+    flow: SessionFlow,
+
+    // Applied to the streams we accept.
+    codes: DropCodes,
+
+    // Closed if the peer opens too many unknown streams.
+    conn: quinn::Connection,
+
+    // What to do with unknown uni streams, and how many we've dropped.
+    unknown_streams: UnknownStreamPolicy,
+    ignored_uni: Arc<AtomicU64>,
+
+    // Limits on reading the header of each stream we accept.
+    budget: Arc<Mutex<HeaderBudget>>,
+    clock: Arc<dyn Clock>,
+
     // We also need to keep a reference to the qpack streams if the endpoint (incorrectly) creates them.
     // Again, this is just so they don't get closed until we drop the session.
     qpack_encoder: Option<quinn::RecvStream>,
     qpack_decoder: Option<quinn::RecvStream>,
 
+    // Where decoded streams are delivered, by type.
+    ready_uni: flume::Sender<RecvStream>,
+    ready_bi: flume::Sender<(SendStream, RecvStream)>,
+    raw_uni: HashMap<StreamUni, flume::Sender<RecvStream>>,
+
     accept_uni: Pin<Box<AcceptUni>>,
     accept_bi: Pin<Box<AcceptBi>>,
 
     // Keep track of work being done to read/write the WebTransport stream header.
     pending_uni: FuturesUnordered<Pin<Box<PendingUni>>>,
     pending_bi: FuturesUnordered<Pin<Box<PendingBi>>>,
-
-    // Wakers from concurrent callers of accept_bi / accept_uni.
-    // When one caller gets a stream, all others are woken so they can retry.
-    // This fixes the lost-waker bug where the unfold stream only stores one waker.
-    bi_wakers: Vec<Waker>,
-    uni_wakers: Vec<Waker>,
 }
 
 impl SessionAccept {
-    pub(crate) fn new(
+    // Returns the task's state, and the handle it delivers streams to.
+    fn new(
         conn: quinn::Connection,
         session_id: VarInt,
         error: Arc<OnceLock<SessionError>>,
-    ) -> Self {
-        // Create a stream that just outputs new streams, so it's easy to call from poll.
-        let accept_uni = Box::pin(futures::stream::unfold(conn.clone(), |conn| async {
+        scheduler: Arc<Scheduler>,
+        flow: SessionFlow,
+        codes: DropCodes,
+        clock: Arc<dyn Clock>,
+    ) -> (Self, Accepted, mpsc::UnboundedReceiver<AcceptCommand>) {
+        let budget = Arc::new(Mutex::new(HeaderBudget::default()));
+
+        // Create a stream that just outputs new streams, so it's easy to select on.
+        // Each one comes with a future that decodes its header.
+        let accept_uni = futures::stream::unfold(conn.clone(), |conn| async {
             Some((conn.accept_uni().await, conn))
+        });
+        let uni_budget = budget.clone();
+        let uni_clock = clock.clone();
+        let accept_uni = Box::pin(accept_uni.map(move |res| {
+            let recv = res?;
+            let budget = *uni_budget.lock().unwrap();
+            let decode = Self::decode_uni(recv, session_id, budget, uni_clock.clone());
+            Ok(Box::pin(decode) as Pin<Box<PendingUni>>)
         }));
 
-        let accept_bi = Box::pin(futures::stream::unfold(conn, |conn| async {
+        let accept_bi = futures::stream::unfold(conn.clone(), |conn| async {
             Some((conn.accept_bi().await, conn))
+        });
+        let bi_budget = budget.clone();
+        let bi_clock = clock.clone();
+        let accept_bi = Box::pin(accept_bi.map(move |res| {
+            let (send, recv) = res?;
+            let budget = *bi_budget.lock().unwrap();
+            let decode = Self::decode_bi(send, recv, session_id, budget, bi_clock.clone());
+            Ok(Box::pin(decode) as Pin<Box<PendingBi>>)
         }));
 
-        Self {
+        let (ready_uni, uni) = flume::unbounded();
+        let (ready_bi, bi) = flume::unbounded();
+        let (commands, queued) = mpsc::unbounded_channel();
+        let ignored_uni = Arc::new(AtomicU64::new(0));
+
+        let this = Self {
             session_id,
             error,
+            scheduler,
+            flow,
+            codes,
+
+            conn,
+            unknown_streams: UnknownStreamPolicy::default(),
+            ignored_uni: ignored_uni.clone(),
+            budget: budget.clone(),
+            clock,
 
             qpack_decoder: None,
             qpack_encoder: None,
 
+            ready_uni,
+            ready_bi,
+            raw_uni: HashMap::new(),
+
             accept_uni,
             accept_bi,
 
             pending_uni: FuturesUnordered::new(),
             pending_bi: FuturesUnordered::new(),
+        };
 
-            bi_wakers: Vec::new(),
-            uni_wakers: Vec::new(),
-        }
+        let accepted = Accepted {
+            uni,
+            bi,
+            raw_uni: Default::default(),
+            commands,
+            ignored_uni,
+            budget,
+        };
+
+        (this, accepted, queued)
     }
 
-    // This is poll-based because we accept and decode streams in parallel.
-    // In async land I would use tokio::JoinSet, but that requires a runtime.
-    // It's better to use FuturesUnordered instead because it's agnostic.
-    pub fn poll_accept_uni(
+    // Accept the streams a pool routed to us, instead of reading the connection.
+    // Their headers were already decoded to find the session.
+    fn route(
         &mut self,
-        cx: &mut Context<'_>,
-    ) -> Poll<Result<RecvStream, SessionError>> {
+        mut uni: mpsc::UnboundedReceiver<RoutedUni>,
+        mut bi: mpsc::UnboundedReceiver<RoutedBi>,
+    ) {
+        let (conn, error) = (self.conn.clone(), self.error.clone());
+        self.accept_uni = Box::pin(futures::stream::poll_fn(move |cx| {
+            let res = match std::task::ready!(uni.poll_recv(cx)) {
+                Some((recv, header)) => {
+                    let decoded = Ok((StreamUni::WEBTRANSPORT, recv, header));
+                    Ok(Box::pin(future::ready(decoded)) as Pin<Box<PendingUni>>)
+                }
+                None => Err(pooled_error(&conn, &error)),
+            };
+            Poll::Ready(Some(res))
+        }));
+
+        let (conn, error) = (self.conn.clone(), self.error.clone());
+        self.accept_bi = Box::pin(futures::stream::poll_fn(move |cx| {
+            let res = match std::task::ready!(bi.poll_recv(cx)) {
+                Some((send, recv, header)) => {
+                    let decoded = Ok(Some((send, recv, header)));
+                    Ok(Box::pin(future::ready(decoded)) as Pin<Box<PendingBi>>)
+                }
+                None => Err(pooled_error(&conn, &error)),
+            };
+            Poll::Ready(Some(res))
+        }));
+    }
+
+    // Accept and decode streams until the connection closes or every session handle is gone.
+    //
+    // Dropping the senders on return tells anyone waiting in accept that no more are coming.
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<AcceptCommand>) {
         loop {
-            // Accept any new streams.
-            if let Poll::Ready(Some(res)) = self.accept_uni.poll_next_unpin(cx) {
-                // Start decoding the header and add the future to the list of pending streams.
-                let recv = match res {
-                    Ok(recv) => recv,
-                    Err(e) => {
-                        for waker in self.uni_wakers.drain(..) {
-                            waker.wake();
-                        }
-                        return Poll::Ready(Err(e.into()));
+            tokio::select! {
+                // Commands first, so they apply to every stream decoded after them.
+                biased;
+                command = commands.recv() => match command {
+                    Some(command) => self.apply(command),
+                    None => return,
+                },
+                res = self.accept_uni.next() => match res {
+                    Some(Ok(pending)) => self.pending_uni.push(pending),
+                    Some(Err(err)) => {
+                        tracing::debug!(?err, "stopped accepting streams");
+                        return;
                     }
-                };
-                let pending = Self::decode_uni(recv, self.session_id);
-                self.pending_uni.push(Box::pin(pending));
+                    None => return,
+                },
+                res = self.accept_bi.next() => match res {
+                    Some(Ok(pending)) => self.pending_bi.push(pending),
+                    Some(Err(err)) => {
+                        tracing::debug!(?err, "stopped accepting streams");
+                        return;
+                    }
+                    None => return,
+                },
+                Some(res) = self.pending_uni.next() => match res {
+                    Ok((typ, recv, header)) => self.deliver_uni(typ, recv, header),
+                    // Ignore the error, the stream was probably reset early.
+                    Err(err) => tracing::warn!(?err, "failed to decode unidirectional stream"),
+                },
+                Some(res) = self.pending_bi.next() => match res {
+                    Ok(Some((send, recv, header))) => self.deliver_bi(send, recv, header),
+                    // Ignore the stream if it's one we don't want.
+                    Ok(None) => {}
+                    // Ignore the error, the stream was probably reset early.
+                    Err(err) => tracing::warn!(?err, "failed to decode bidirectional stream"),
+                },
+            }
+        }
+    }
 
-                continue;
+    fn apply(&mut self, command: AcceptCommand) {
+        match command {
+            AcceptCommand::Register(typ, send) => {
+                self.raw_uni.insert(typ, send);
             }
+            AcceptCommand::Policy(policy) => self.unknown_streams = policy,
+            AcceptCommand::Early(uni, bi) => self.push_early(uni, bi),
+        }
+    }
 
-            // Poll the list of pending streams.
-            let (typ, recv) = match self.pending_uni.poll_next_unpin(cx) {
-                Poll::Ready(Some(Ok(res))) => res,
-                Poll::Ready(Some(Err(err))) => {
-                    // Ignore the error, the stream was probably reset early.
-                    tracing::warn!(?err, "failed to decode unidirectional stream");
-                    continue;
-                }
-                Poll::Ready(None) | Poll::Pending => {
-                    if !self.uni_wakers.iter().any(|w| w.will_wake(cx.waker())) {
-                        self.uni_wakers.push(cx.waker().clone());
-                    }
-                    return Poll::Pending;
-                }
-            };
+    // Decode the streams that arrived before the CONNECT response like any others.
+    fn push_early(
+        &mut self,
+        uni: Vec<quinn::RecvStream>,
+        bi: Vec<(quinn::SendStream, quinn::RecvStream)>,
+    ) {
+        let budget = *self.budget.lock().unwrap();
+        for recv in uni {
+            let pending = Self::decode_uni(recv, self.session_id, budget, self.clock.clone());
+            self.pending_uni.push(Box::pin(pending));
+        }
+        for (send, recv) in bi {
+            let pending = Self::decode_bi(send, recv, self.session_id, budget, self.clock.clone());
+            self.pending_bi.push(Box::pin(pending));
+        }
+    }
 
-            // Decide if we keep looping based on the type.
-            match typ {
-                StreamUni::WEBTRANSPORT => {
-                    let recv = RecvStream::new(recv, self.error.clone());
-                    for waker in self.uni_wakers.drain(..) {
-                        waker.wake();
+    // Hand a decoded uni stream to whoever wants its type, or apply the unknown stream policy.
+    fn deliver_uni(&mut self, typ: StreamUni, recv: quinn::RecvStream, header: Header) {
+        let ready = match typ {
+            StreamUni::WEBTRANSPORT => &self.ready_uni,
+            StreamUni::QPACK_DECODER => {
+                self.qpack_decoder = Some(recv);
+                return;
+            }
+            StreamUni::QPACK_ENCODER => {
+                self.qpack_encoder = Some(recv);
+                return;
+            }
+            typ => match self.raw_uni.get(&typ) {
+                Some(ready) => ready,
+                None => {
+                    match typ.reject_code(quinn::VarInt::from(recv.id()).into_inner()) {
+                        Some(code) => self.reject_uni(typ, code),
+                        None => self.ignore_uni(typ),
                     }
-                    return Poll::Ready(Ok(recv));
-                }
-                StreamUni::QPACK_DECODER => {
-                    self.qpack_decoder = Some(recv);
-                }
-                StreamUni::QPACK_ENCODER => {
-                    self.qpack_encoder = Some(recv);
-                }
-                _ => {
-                    // ignore unknown streams
-                    tracing::debug!(?typ, "ignoring unknown unidirectional stream");
+                    return;
                 }
+            },
+        };
+
+        // Only fails once every session handle is gone, dropping the stream.
+        ready
+            .send(header.into_recv(recv, self.error.clone(), self.codes.recv))
+            .ok();
+    }
+
+    fn deliver_bi(&self, send: quinn::SendStream, recv: quinn::RecvStream, header: Header) {
+        // Wrap the streams in our own types for correct error codes.
+        let send = SendStream::new(
+            send,
+            self.error.clone(),
+            self.scheduler.clone(),
+            self.flow.clone(),
+        );
+        let recv = header.into_recv(recv, self.error.clone(), self.codes.recv);
+        self.ready_bi.send((send, recv)).ok();
+    }
+
+    // Close the connection because the peer opened a stream it isn't allowed to.
+    fn reject_uni(&self, typ: StreamUni, code: u64) {
+        tracing::warn!(?typ, "rejecting forbidden unidirectional stream");
+        let code = quinn::VarInt::from_u64(code).unwrap();
+        self.conn.close(code, b"unexpected stream type");
+    }
+
+    // Drop a uni stream of an unknown type, applying the policy.
+    fn ignore_uni(&mut self, typ: StreamUni) {
+        let count = self.ignored_uni.fetch_add(1, Ordering::Relaxed) + 1;
+
+        match self.unknown_streams {
+            UnknownStreamPolicy::CloseAfter(limit) if count > limit => {
+                tracing::warn!(?typ, count, "too many unknown unidirectional streams");
+                let code = quinn::VarInt::from_u64(codes::h3::STREAM_CREATION_ERROR).unwrap();
+                self.conn.close(code, b"too many unknown streams");
             }
+            UnknownStreamPolicy::Warn => {
+                tracing::warn!(?typ, count, "ignoring unknown unidirectional stream");
+            }
+            _ => tracing::debug!(?typ, "ignoring unknown unidirectional stream"),
         }
     }
 
@@ -687,109 +2292,115 @@ impl SessionAccept {
     async fn decode_uni(
         mut recv: quinn::RecvStream,
         expected_session: VarInt,
-    ) -> Result<(StreamUni, quinn::RecvStream), SessionError> {
-        // Read the VarInt at the start of the stream.
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
-        let typ = StreamUni(typ);
+        budget: HeaderBudget,
+        clock: Arc<dyn Clock>,
+    ) -> Result<(StreamUni, quinn::RecvStream, Header), SessionError> {
+        let mut header = Header::new(budget);
+
+        let read = async {
+            // Read the VarInt at the start of the stream.
+            let typ = StreamUni(header.read_varint(&mut recv).await?);
+
+            if typ == StreamUni::WEBTRANSPORT {
+                // Read the session_id and validate it
+                let session_id = header.read_varint(&mut recv).await?;
+                if session_id != expected_session {
+                    return Err(WebTransportError::UnknownSession.into());
+                }
+            }
 
-        if typ == StreamUni::WEBTRANSPORT {
-            // Read the session_id and validate it
-            let session_id = VarInt::read(&mut recv)
-                .await
-                .map_err(|_| WebTransportError::UnknownSession)?;
-            if session_id != expected_session {
-                return Err(WebTransportError::UnknownSession.into());
+            Ok::<_, SessionError>(typ)
+        };
+
+        let typ = match within_budget(&*clock, budget, read).await {
+            Ok(typ) => typ,
+            Err(err) => {
+                if is_violation(&err) {
+                    recv.stop(general_protocol_error()).ok();
+                }
+                return Err(err);
             }
-        }
+        };
 
         // We need to keep a reference to the qpack streams if the endpoint (incorrectly) creates them, so return everything.
-        Ok((typ, recv))
+        Ok((typ, recv, header))
     }
 
-    pub fn poll_accept_bi(
-        &mut self,
-        cx: &mut Context<'_>,
-    ) -> Poll<Result<(SendStream, RecvStream), SessionError>> {
-        loop {
-            // Accept any new streams.
-            if let Poll::Ready(Some(res)) = self.accept_bi.poll_next_unpin(cx) {
-                // Start decoding the header and add the future to the list of pending streams.
-                let (send, recv) = match res {
-                    Ok(pair) => pair,
-                    Err(e) => {
-                        for waker in self.bi_wakers.drain(..) {
-                            waker.wake();
-                        }
-                        return Poll::Ready(Err(e.into()));
-                    }
-                };
-                let pending = Self::decode_bi(send, recv, self.session_id);
-                self.pending_bi.push(Box::pin(pending));
+    // Reads the stream header, returning Some if it's a WebTransport stream.
+    async fn decode_bi(
+        mut send: quinn::SendStream,
+        mut recv: quinn::RecvStream,
+        expected_session: VarInt,
+        budget: HeaderBudget,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Option<(quinn::SendStream, quinn::RecvStream, Header)>, SessionError> {
+        let mut header = Header::new(budget);
+
+        let read = async {
+            let typ = header.read_varint(&mut recv).await?;
+            if Frame(typ) != Frame::WEBTRANSPORT {
+                tracing::debug!(?typ, "ignoring unknown bidirectional stream");
+                return Ok(false);
+            }
 
-                continue;
+            // Read the session ID and validate it.
+            let session_id = header.read_varint(&mut recv).await?;
+            if session_id != expected_session {
+                return Err(WebTransportError::UnknownSession.into());
             }
 
-            // Poll the list of pending streams.
-            let res = match self.pending_bi.poll_next_unpin(cx) {
-                Poll::Ready(Some(Ok(res))) => res,
-                Poll::Ready(Some(Err(err))) => {
-                    // Ignore the error, the stream was probably reset early.
-                    tracing::warn!(?err, "failed to decode bidirectional stream");
-                    continue;
-                }
-                Poll::Ready(None) | Poll::Pending => {
-                    if !self.bi_wakers.iter().any(|w| w.will_wake(cx.waker())) {
-                        self.bi_wakers.push(cx.waker().clone());
-                    }
-                    return Poll::Pending;
-                }
-            };
+            Ok::<_, SessionError>(true)
+        };
 
-            if let Some((send, recv)) = res {
-                // Wrap the streams in our own types for correct error codes.
-                let send = SendStream::new(send, self.error.clone());
-                let recv = RecvStream::new(recv, self.error.clone());
-                for waker in self.bi_wakers.drain(..) {
-                    waker.wake();
+        match within_budget(&*clock, budget, read).await {
+            Ok(true) => Ok(Some((send, recv, header))),
+            Ok(false) => Ok(None),
+            Err(err) => {
+                if is_violation(&err) {
+                    recv.stop(general_protocol_error()).ok();
+                    send.reset(general_protocol_error()).ok();
                 }
-                return Poll::Ready(Ok((send, recv)));
+                Err(err)
             }
-
-            // Keep looping if it's a stream we want to ignore.
         }
     }
+}
 
-    // Reads the stream header, returning Some if it's a WebTransport stream.
-    async fn decode_bi(
-        send: quinn::SendStream,
-        mut recv: quinn::RecvStream,
-        expected_session: VarInt,
-    ) -> Result<Option<(quinn::SendStream, quinn::RecvStream)>, SessionError> {
-        let typ = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
-        if Frame(typ) != Frame::WEBTRANSPORT {
-            tracing::debug!(?typ, "ignoring unknown bidirectional stream");
-            return Ok(None);
-        }
+/// A breakdown of where the time went while establishing a client session.
+///
+/// Each phase is measured back-to-back, so their sum is the connect latency.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HandshakeTiming {
+    /// Resolving the host name.
+    pub dns: Option<Duration>,
 
-        // Read the session ID and validate it.
-        let session_id = VarInt::read(&mut recv)
-            .await
-            .map_err(|_| WebTransportError::UnknownSession)?;
-        if session_id != expected_session {
-            return Err(WebTransportError::UnknownSession.into());
-        }
+    /// The QUIC (and TLS) handshake.
+    pub quic: Option<Duration>,
+
+    /// Exchanging HTTP/3 SETTINGS.
+    pub settings: Option<Duration>,
+
+    /// Waiting for the CONNECT response once SETTINGS were exchanged.
+    ///
+    /// The request is sent alongside SETTINGS, so this is often shorter than a round trip.
+    pub connect: Option<Duration>,
+}
 
-        Ok(Some((send, recv)))
+impl HandshakeTiming {
+    /// The sum of every recorded phase.
+    pub fn total(&self) -> Duration {
+        [self.dns, self.quic, self.settings, self.connect]
+            .into_iter()
+            .flatten()
+            .sum()
     }
 }
 
 pub struct SessionStats {
     stats: quinn::ConnectionStats,
     rtt: std::time::Duration,
+    ignored_uni: u64,
 }
 
 impl web_transport_trait::Stats for SessionStats {
@@ -829,6 +2440,14 @@ impl web_transport_trait::Stats for SessionStats {
             None
         }
     }
+
+    fn congestion_window(&self) -> Option<u64> {
+        Some(self.stats.path.cwnd)
+    }
+
+    fn ignored_uni_streams(&self) -> Option<u64> {
+        Some(self.ignored_uni)
+    }
 }
 
 impl web_transport_trait::Session for Session {
@@ -852,18 +2471,49 @@ impl web_transport_trait::Session for Session {
         Self::open_uni(self).await
     }
 
+    async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(Self::SendStream, Self::RecvStream), Self::Error> {
+        Self::open_bi_with(self, options).await
+    }
+
+    async fn open_uni_with(&self, options: StreamOptions) -> Result<Self::SendStream, Self::Error> {
+        Self::open_uni_with(self, options).await
+    }
+
     fn close(&self, code: u32, reason: &str) {
         Self::close(self, code, reason.as_bytes());
     }
 
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        Self::close(self, code, reason);
+    }
+
     async fn closed(&self) -> Self::Error {
         Self::closed(self).await
     }
 
+    fn close_reason(&self) -> Option<Closed> {
+        self.close_record.get()
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        Some(self.id)
+    }
+
     fn send_datagram(&self, data: Bytes) -> Result<(), Self::Error> {
         Self::send_datagram(self, data)
     }
 
+    async fn send_datagram_wait(&self, data: Bytes) -> Result<(), Self::Error> {
+        Self::send_datagram_wait(self, data).await
+    }
+
+    fn send_datagram_with(&self, data: Bytes, options: DatagramOptions) -> Result<(), Self::Error> {
+        Self::send_datagram_with(self, data, options)
+    }
+
     async fn recv_datagram(&self) -> Result<Bytes, Self::Error> {
         Self::read_datagram(self).await
     }
@@ -872,12 +2522,36 @@ impl web_transport_trait::Session for Session {
         Self::max_datagram_size(self)
     }
 
+    fn datagrams_supported(&self) -> bool {
+        Self::datagrams_supported(self)
+    }
+
     fn protocol(&self) -> Option<&str> {
-        self.response.protocol.as_deref()
+        Self::protocol(self)
     }
 
     #[allow(refining_impl_trait)]
     fn stats(&self) -> SessionStats {
         Self::stats(self)
     }
+
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        Some(self.conn.remote_address())
+    }
+
+    fn max_open_uni(&self) -> Option<u64> {
+        Self::max_open_uni(self)
+    }
+
+    fn remaining_uni(&self) -> Option<u64> {
+        Self::remaining_uni(self)
+    }
+
+    fn max_open_bi(&self) -> Option<u64> {
+        Self::max_open_bi(self)
+    }
+
+    fn remaining_bi(&self) -> Option<u64> {
+        Self::remaining_bi(self)
+    }
 }
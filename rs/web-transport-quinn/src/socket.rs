@@ -0,0 +1,195 @@
+//! Options applied to the UDP socket before it's handed to the QUIC endpoint.
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+
+/// Options for the UDP socket a builder binds.
+///
+/// Every option is best-effort: when the platform doesn't support one, or the kernel
+/// rejects it, a warning is logged and the socket is used without it. The only hard
+/// failure is binding itself.
+#[derive(Clone, Debug, Default)]
+pub struct SocketOptions {
+    tos: Option<u8>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    ipv6_only: Option<bool>,
+}
+
+impl SocketOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark outgoing packets with the given DSCP codepoint (0-63), e.g. 46 for Expedited Forwarding.
+    ///
+    /// Shorthand for [Self::with_tos] with the ECN bits left clear.
+    pub fn with_dscp(self, dscp: u8) -> Self {
+        self.with_tos(dscp << 2)
+    }
+
+    /// Set the IPv4 TOS byte, or the IPv6 traffic class, on outgoing packets.
+    ///
+    /// quinn sets the ECN bits per packet, which overrides the low two bits on platforms
+    /// where it supports ECN.
+    pub fn with_tos(mut self, tos: u8) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// Request a kernel receive buffer of this many bytes (`SO_RCVBUF`).
+    ///
+    /// The kernel may round or cap the value; Linux doubles it and caps it at `net.core.rmem_max`.
+    pub fn with_recv_buffer_size(mut self, bytes: usize) -> Self {
+        self.recv_buffer_size = Some(bytes);
+        self
+    }
+
+    /// Request a kernel send buffer of this many bytes (`SO_SNDBUF`).
+    pub fn with_send_buffer_size(mut self, bytes: usize) -> Self {
+        self.send_buffer_size = Some(bytes);
+        self
+    }
+
+    /// Whether an IPv6 socket refuses IPv4-mapped traffic (`IPV6_V6ONLY`).
+    ///
+    /// Ignored for IPv4 addresses. When unset, the platform default is used, which is
+    /// dual-stack on Linux and macOS but IPv6-only on Windows and the BSDs.
+    pub fn with_ipv6_only(mut self, ipv6_only: bool) -> Self {
+        self.ipv6_only = Some(ipv6_only);
+        self
+    }
+
+    /// Create a UDP socket, apply the options, and bind it to `addr`.
+    pub(crate) fn bind(&self, addr: SocketAddr) -> io::Result<UdpSocket> {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+
+        // Has to happen before bind, so it can't live in `apply`.
+        if let (SocketAddr::V6(_), Some(only)) = (addr, self.ipv6_only) {
+            if let Err(err) = socket.set_only_v6(only) {
+                tracing::warn!(%err, only, "failed to set IPV6_V6ONLY");
+            }
+        }
+
+        self.apply(SockRef::from(&socket), addr);
+
+        socket.bind(&addr.into())?;
+        Ok(socket.into())
+    }
+
+    fn apply(&self, socket: SockRef<'_>, addr: SocketAddr) {
+        if let Some(tos) = self.tos {
+            let res = match addr {
+                SocketAddr::V4(_) => set_tos_v4(&socket, tos),
+                // Dual-stack sockets send IPv4 too, so set both where we can.
+                SocketAddr::V6(_) => {
+                    let _ = set_tos_v4(&socket, tos);
+                    set_tclass_v6(&socket, tos)
+                }
+            };
+            if let Err(err) = res {
+                tracing::warn!(%err, tos, "failed to set TOS, packets will be unmarked");
+            }
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            match socket.set_recv_buffer_size(size) {
+                Ok(()) => tracing::debug!(
+                    requested = size,
+                    actual = ?socket.recv_buffer_size().ok(),
+                    "set receive buffer size"
+                ),
+                Err(err) => tracing::warn!(%err, size, "failed to set receive buffer size"),
+            }
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            match socket.set_send_buffer_size(size) {
+                Ok(()) => tracing::debug!(
+                    requested = size,
+                    actual = ?socket.send_buffer_size().ok(),
+                    "set send buffer size"
+                ),
+                Err(err) => tracing::warn!(%err, size, "failed to set send buffer size"),
+            }
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "fuchsia",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "illumos",
+    target_os = "haiku",
+    target_os = "wasi",
+)))]
+fn set_tos_v4(socket: &Socket, tos: u8) -> io::Result<()> {
+    socket.set_tos_v4(tos.into())
+}
+
+#[cfg(any(
+    target_os = "fuchsia",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "illumos",
+    target_os = "haiku",
+    target_os = "wasi",
+))]
+fn set_tos_v4(_socket: &Socket, _tos: u8) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn set_tclass_v6(socket: &Socket, tos: u8) -> io::Result<()> {
+    socket.set_tclass_v6(tos.into())
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn set_tclass_v6(_socket: &Socket, _tos: u8) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_reach_the_socket() {
+        let options = SocketOptions::new()
+            .with_recv_buffer_size(256 * 1024)
+            .with_ipv6_only(true);
+
+        let socket = options.bind("[::1]:0".parse().unwrap()).unwrap();
+        let socket = SockRef::from(&socket);
+
+        assert!(socket.only_v6().unwrap());
+        // The kernel is free to round, but never below a sane floor for a request this size.
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    }
+
+    #[test]
+    fn dscp_shifts_past_ecn() {
+        assert_eq!(SocketOptions::new().with_dscp(46).tos, Some(0xb8));
+    }
+}
@@ -0,0 +1,145 @@
+//! Helpers for loading certificates and keys into the types the builders accept.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let (chain, key) = web_transport_quinn::tls::load_pem("cert.pem", "key.pem")?;
+//! let server = web_transport_quinn::ServerBuilder::new().with_certificate(chain, key)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use thiserror::Error;
+
+/// An error returned when loading a certificate or key.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid PEM: {0}")]
+    Pem(#[from] rustls::pki_types::pem::Error),
+
+    #[cfg(feature = "pkcs12")]
+    #[error("invalid PKCS#12: {0}")]
+    Pkcs12(#[from] p12_keystore::error::Error),
+
+    #[error("no certificate found")]
+    MissingCertificate,
+
+    #[error("no private key found")]
+    MissingKey,
+}
+
+/// Load every `CERTIFICATE` section from a PEM file, in order.
+///
+/// This is what [ClientBuilder::with_server_certificates](crate::ClientBuilder::with_server_certificates) accepts.
+pub fn load_certs(path: impl AsRef<Path>) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = CertificateDer::pem_file_iter(path)?.collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(Error::MissingCertificate);
+    }
+
+    Ok(certs)
+}
+
+/// Load a certificate chain and private key from PEM files.
+///
+/// The chain is every `CERTIFICATE` section in `cert_path`, leaf first.
+/// The key is the first PKCS#1, PKCS#8, or SEC1 private key in `key_path`.
+pub fn load_pem(
+    cert_path: impl AsRef<Path>,
+    key_path: impl AsRef<Path>,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), Error> {
+    let chain = load_certs(cert_path)?;
+
+    let key = match PrivateKeyDer::from_pem_file(key_path) {
+        Ok(key) => key,
+        Err(rustls::pki_types::pem::Error::NoItemsFound) => return Err(Error::MissingKey),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok((chain, key))
+}
+
+/// Load a certificate chain and private key from a password-protected PKCS#12 (`.p12`/`.pfx`) file.
+///
+/// The first private key entry is used, along with its certificate chain.
+#[cfg(feature = "pkcs12")]
+pub fn load_pkcs12(
+    path: impl AsRef<Path>,
+    password: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), Error> {
+    use p12_keystore::{KeyStore, Pkcs12ImportPolicy};
+    use rustls::pki_types::PrivatePkcs8KeyDer;
+
+    let data = std::fs::read(path)?;
+    let store = KeyStore::from_pkcs12(&data, password, Pkcs12ImportPolicy::Relaxed)?;
+
+    let (_, entry) = store.private_key_chain().ok_or(Error::MissingKey)?;
+
+    let chain: Vec<_> = entry
+        .certs()
+        .iter()
+        .map(|cert| CertificateDer::from(cert.as_der().to_vec()))
+        .collect();
+    if chain.is_empty() {
+        return Err(Error::MissingCertificate);
+    }
+
+    let key = PrivatePkcs8KeyDer::from(entry.key().as_der().to_vec());
+
+    Ok((chain, key.into()))
+}
+
+/// Compute the SHA-256 hash of a certificate, as used by the browser's `serverCertificateHashes`.
+///
+/// Pass the hash of the leaf certificate to [ClientBuilder::with_server_certificate_hashes](crate::ClientBuilder::with_server_certificate_hashes).
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+pub fn certificate_hash(cert: &CertificateDer<'_>) -> Vec<u8> {
+    let provider = crate::crypto::default_provider();
+    crate::crypto::sha256(&provider, cert).as_ref().to_vec()
+}
+
+#[cfg(all(test, any(feature = "aws-lc-rs", feature = "ring")))]
+mod tests {
+    use super::*;
+
+    fn write_pem(dir: &Path) -> (std::path::PathBuf, std::path::PathBuf, Vec<u8>) {
+        let key = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, key.cert.pem()).unwrap();
+        std::fs::write(&key_path, key.signing_key.serialize_pem()).unwrap();
+
+        (cert_path, key_path, key.cert.der().to_vec())
+    }
+
+    #[test]
+    fn loads_pem() {
+        let dir = std::env::temp_dir().join(format!("wtq-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (cert_path, key_path, der) = write_pem(&dir);
+        let (chain, key) = load_pem(&cert_path, &key_path).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].as_ref(), der.as_slice());
+        assert!(matches!(key, PrivateKeyDer::Pkcs8(_)));
+
+        // Swapping the files finds neither a certificate nor a key.
+        assert!(matches!(
+            load_pem(&key_path, &key_path),
+            Err(Error::MissingCertificate)
+        ));
+        assert!(matches!(
+            load_pem(&cert_path, &cert_path),
+            Err(Error::MissingKey)
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,682 @@
+//! UDP I/O through io_uring, for servers where per-packet syscalls dominate.
+//!
+//! quinn's default socket makes a `recvmsg`/`sendmsg` syscall (or a `sendmmsg` batch)
+//! on the endpoint's task. [UringRuntime] instead hands the socket to a dedicated
+//! thread that keeps a pool of receives armed in an io_uring and submits queued sends
+//! in batches, so packets in both directions share one `io_uring_enter` call.
+//!
+//! Timers and tasks still run on tokio; only the socket is replaced.
+
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    io::IoSliceMut,
+    mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    pin::Pin,
+    ptr,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+use io_uring::{opcode, types, IoUring};
+use quinn::{
+    udp::{RecvMeta, Transmit},
+    AsyncTimer, AsyncUdpSocket, Runtime, UdpPoller,
+};
+
+/// Receives kept armed in the ring at all times.
+const RECV_SLOTS: usize = 64;
+
+/// Sends queued or in flight before [AsyncUdpSocket::try_send] reports `WouldBlock`.
+const SEND_SLOTS: usize = 256;
+
+/// Datagrams received but not yet read by quinn. Past this they're dropped, like a
+/// full kernel receive buffer would.
+const RECV_QUEUE: usize = 4096;
+
+/// The largest UDP payload, so a receive is never truncated.
+const MAX_DATAGRAM: usize = 65_535;
+
+// The slot kind lives in the top half of each entry's user data, the index in the bottom.
+const RECV: u64 = 1 << 32;
+const SEND: u64 = 2 << 32;
+const WAKE: u64 = 3 << 32;
+const CANCEL: u64 = 4 << 32;
+
+/// A [quinn::Runtime] that runs tasks and timers on tokio but performs UDP I/O through
+/// io_uring.
+///
+/// Each socket gets its own ring and a thread to drive it. Pass this to
+/// [quinn::Endpoint::new], or use [ServerBuilder::with_io_uring](crate::ServerBuilder::with_io_uring).
+/// Creating the socket fails if the kernel doesn't support io_uring or it's disabled,
+/// for example by seccomp or `kernel.io_uring_disabled`.
+///
+/// GSO, GRO and ECN aren't supported, so each datagram is its own submission.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UringRuntime;
+
+impl UringRuntime {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Runtime for UringRuntime {
+    fn new_timer(&self, t: Instant) -> Pin<Box<dyn AsyncTimer>> {
+        quinn::TokioRuntime.new_timer(t)
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn std::future::Future<Output = ()> + Send>>) {
+        quinn::TokioRuntime.spawn(future)
+    }
+
+    fn wrap_udp_socket(&self, socket: UdpSocket) -> io::Result<Arc<dyn AsyncUdpSocket>> {
+        Ok(Arc::new(UringSocket::new(socket)?))
+    }
+}
+
+/// A datagram the ring has received, waiting for [AsyncUdpSocket::poll_recv].
+struct Datagram {
+    addr: SocketAddr,
+    data: Vec<u8>,
+}
+
+/// A datagram waiting for the ring thread to submit it.
+struct Outgoing {
+    destination: SocketAddr,
+    contents: Vec<u8>,
+}
+
+#[derive(Default)]
+struct State {
+    received: VecDeque<Datagram>,
+    recv_waker: Option<Waker>,
+
+    sends: VecDeque<Outgoing>,
+    // Sends queued or submitted but not yet completed.
+    pending: usize,
+    send_wakers: Vec<Waker>,
+
+    // Whether the ring thread has been signalled and hasn't yet consumed it, so a burst
+    // of sends costs one eventfd write rather than one each.
+    notified: bool,
+    closed: bool,
+    error: Option<io::ErrorKind>,
+}
+
+/// The state shared between the socket handle, its pollers, and the ring thread.
+struct Shared {
+    state: Mutex<State>,
+    // Signals the ring thread, which always has a read armed on it.
+    eventfd: OwnedFd,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn notify(&self) {
+        let one = 1u64;
+        // SAFETY: writes 8 bytes from a live u64 to an fd we own. An eventfd write only
+        // fails if the counter would overflow, which a read resets long before.
+        unsafe {
+            libc::write(
+                self.eventfd.as_raw_fd(),
+                ptr::addr_of!(one).cast(),
+                mem::size_of::<u64>(),
+            )
+        };
+    }
+
+    fn fail(&self, err: &io::Error) {
+        let mut state = self.lock();
+        state.error = Some(err.kind());
+        if let Some(waker) = state.recv_waker.take() {
+            waker.wake();
+        }
+        for waker in state.send_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// A UDP socket whose I/O is performed by an io_uring on a dedicated thread.
+struct UringSocket {
+    socket: Arc<UdpSocket>,
+    shared: Arc<Shared>,
+    may_fragment: bool,
+}
+
+impl UringSocket {
+    fn new(socket: UdpSocket) -> io::Result<Self> {
+        let may_fragment = match set_dont_fragment(&socket) {
+            Ok(()) => false,
+            Err(err) => {
+                tracing::warn!(%err, "failed to disable fragmentation, MTU discovery is off");
+                true
+            }
+        };
+
+        // Entries for every receive and send slot, plus the eventfd read.
+        let ring = IoUring::new((RECV_SLOTS + SEND_SLOTS + 1).next_power_of_two() as u32)?;
+
+        // SAFETY: eventfd returns a new fd we own, or -1.
+        let eventfd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if eventfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let eventfd = unsafe { OwnedFd::from_raw_fd(eventfd) };
+
+        let socket = Arc::new(socket);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            eventfd,
+        });
+
+        let thread = (socket.clone(), shared.clone());
+        std::thread::Builder::new()
+            .name("quinn-uring".into())
+            .spawn(move || {
+                let (socket, shared) = thread;
+                Driver::new(ring, socket, shared).run()
+            })?;
+
+        Ok(Self {
+            socket,
+            shared,
+            may_fragment,
+        })
+    }
+}
+
+impl Drop for UringSocket {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.notify();
+    }
+}
+
+impl fmt::Debug for UringSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UringSocket")
+            .field("local_addr", &self.socket.local_addr().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncUdpSocket for UringSocket {
+    fn create_io_poller(self: Arc<Self>) -> Pin<Box<dyn UdpPoller>> {
+        Box::pin(UringPoller {
+            shared: self.shared.clone(),
+        })
+    }
+
+    fn try_send(&self, transmit: &Transmit) -> io::Result<()> {
+        let mut state = self.shared.lock();
+        if let Some(kind) = state.error {
+            return Err(kind.into());
+        }
+        if state.pending >= SEND_SLOTS {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        state.pending += 1;
+        state.sends.push_back(Outgoing {
+            destination: transmit.destination,
+            contents: transmit.contents.to_vec(),
+        });
+
+        let notify = !mem::replace(&mut state.notified, true);
+        drop(state);
+
+        if notify {
+            self.shared.notify();
+        }
+
+        Ok(())
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.shared.lock();
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+
+        let mut count = 0;
+        for (buf, meta) in bufs.iter_mut().zip(meta.iter_mut()) {
+            let Some(datagram) = state.received.pop_front() else {
+                break;
+            };
+
+            // quinn sizes its buffers for the largest payload it accepts, so anything
+            // longer would be discarded anyway.
+            let len = datagram.data.len().min(buf.len());
+            buf[..len].copy_from_slice(&datagram.data[..len]);
+
+            *meta = RecvMeta::default();
+            meta.addr = datagram.addr;
+            meta.len = len;
+            meta.stride = len;
+            count += 1;
+        }
+
+        if count == 0 {
+            state.recv_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        Poll::Ready(Ok(count))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn may_fragment(&self) -> bool {
+        self.may_fragment
+    }
+}
+
+/// Waits for room in the send queue.
+struct UringPoller {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for UringPoller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UringPoller").finish_non_exhaustive()
+    }
+}
+
+impl UdpPoller for UringPoller {
+    fn poll_writable(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let mut state = self.shared.lock();
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+        if state.pending < SEND_SLOTS {
+            return Poll::Ready(Ok(()));
+        }
+
+        if !state.send_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.send_wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// A receive buffer and the `msghdr` pointing into it, boxed so the kernel's pointers
+/// stay valid while the receive is armed.
+struct RecvSlot {
+    buf: Vec<u8>,
+    addr: libc::sockaddr_storage,
+    iov: libc::iovec,
+    msg: libc::msghdr,
+}
+
+impl RecvSlot {
+    fn new() -> Box<Self> {
+        // SAFETY: all-zero is a valid value for these plain C structs.
+        let mut slot = Box::new(Self {
+            buf: vec![0; MAX_DATAGRAM],
+            addr: unsafe { mem::zeroed() },
+            iov: unsafe { mem::zeroed() },
+            msg: unsafe { mem::zeroed() },
+        });
+
+        slot.iov = libc::iovec {
+            iov_base: slot.buf.as_mut_ptr().cast(),
+            iov_len: slot.buf.len(),
+        };
+        slot.msg.msg_name = ptr::addr_of_mut!(slot.addr).cast();
+        slot.msg.msg_iov = ptr::addr_of_mut!(slot.iov);
+        slot.msg.msg_iovlen = 1;
+        slot
+    }
+
+    /// The datagram a completed receive of `len` bytes left in the slot.
+    fn datagram(&self, len: usize) -> Option<Datagram> {
+        if self.msg.msg_flags & libc::MSG_TRUNC != 0 {
+            return None;
+        }
+
+        Some(Datagram {
+            addr: from_sockaddr(&self.addr)?,
+            data: self.buf[..len].to_vec(),
+        })
+    }
+}
+
+/// A datagram being sent, boxed for the same reason as [RecvSlot].
+struct SendSlot {
+    contents: Vec<u8>,
+    addr: socket2::SockAddr,
+    iov: libc::iovec,
+    msg: libc::msghdr,
+}
+
+impl SendSlot {
+    fn new(outgoing: Outgoing) -> Box<Self> {
+        // SAFETY: as in RecvSlot::new.
+        let mut slot = Box::new(Self {
+            contents: outgoing.contents,
+            addr: outgoing.destination.into(),
+            iov: unsafe { mem::zeroed() },
+            msg: unsafe { mem::zeroed() },
+        });
+
+        slot.iov = libc::iovec {
+            iov_base: slot.contents.as_mut_ptr().cast(),
+            iov_len: slot.contents.len(),
+        };
+        slot.msg.msg_name = slot.addr.as_ptr() as *mut libc::c_void;
+        slot.msg.msg_namelen = slot.addr.len();
+        slot.msg.msg_iov = ptr::addr_of_mut!(slot.iov);
+        slot.msg.msg_iovlen = 1;
+        slot
+    }
+}
+
+/// The ring thread, which owns every buffer the kernel may write into.
+struct Driver {
+    ring: IoUring,
+    socket: Arc<UdpSocket>,
+    shared: Arc<Shared>,
+
+    recv: Vec<Box<RecvSlot>>,
+    send: Vec<Option<Box<SendSlot>>>,
+    free: Vec<usize>,
+    wake: Box<u64>,
+
+    // Submitted entries whose completion hasn't been reaped.
+    outstanding: usize,
+    closing: bool,
+}
+
+impl Driver {
+    fn new(ring: IoUring, socket: Arc<UdpSocket>, shared: Arc<Shared>) -> Self {
+        Self {
+            ring,
+            socket,
+            shared,
+            recv: (0..RECV_SLOTS).map(|_| RecvSlot::new()).collect(),
+            send: (0..SEND_SLOTS).map(|_| None).collect(),
+            free: (0..SEND_SLOTS).rev().collect(),
+            wake: Box::new(0),
+            outstanding: 0,
+            closing: false,
+        }
+    }
+
+    fn run(mut self) {
+        if let Err(err) = self.drive() {
+            tracing::error!(%err, "io_uring socket failed");
+            self.shared.fail(&err);
+
+            // The kernel may still write into the slots, so leak them (and the ring)
+            // rather than free memory out from under it.
+            mem::forget(self);
+        }
+    }
+
+    fn drive(&mut self) -> io::Result<()> {
+        for index in 0..RECV_SLOTS {
+            self.arm_recv(index)?;
+        }
+        self.arm_wake()?;
+
+        let mut completions = Vec::new();
+
+        loop {
+            if !self.closing {
+                self.submit_sends()?;
+            }
+
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                // The completion queue is full; reaping it below makes room.
+                Err(err) if err.raw_os_error() == Some(libc::EBUSY) => {}
+                Err(err) => return Err(err),
+            }
+
+            completions.clear();
+            completions.extend(
+                self.ring
+                    .completion()
+                    .map(|cqe| (cqe.user_data(), cqe.result())),
+            );
+            self.complete(&completions)?;
+
+            if self.closing && self.outstanding == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn complete(&mut self, completions: &[(u64, i32)]) -> io::Result<()> {
+        let mut received = Vec::new();
+        let mut sent = 0;
+        let mut woken = false;
+
+        for &(data, res) in completions {
+            self.outstanding -= 1;
+            let index = data as u32 as usize;
+
+            match data & !(u32::MAX as u64) {
+                RECV if self.closing => {}
+                RECV => {
+                    if res >= 0 {
+                        received.extend(self.recv[index].datagram(res as usize));
+                    } else {
+                        let err = io::Error::from_raw_os_error(-res);
+                        tracing::trace!(%err, "receive failed");
+                    }
+                    self.arm_recv(index)?;
+                }
+                SEND => {
+                    if res < 0 {
+                        let err = io::Error::from_raw_os_error(-res);
+                        tracing::debug!(%err, "send failed");
+                    }
+                    self.send[index] = None;
+                    self.free.push(index);
+                    sent += 1;
+                }
+                WAKE => woken = true,
+                _ => {}
+            }
+        }
+
+        let mut state = self.shared.lock();
+
+        for datagram in received {
+            if state.received.len() >= RECV_QUEUE {
+                tracing::trace!("receive queue full, dropping datagram");
+                continue;
+            }
+            state.received.push_back(datagram);
+        }
+        if !state.received.is_empty() {
+            if let Some(waker) = state.recv_waker.take() {
+                waker.wake();
+            }
+        }
+
+        if sent > 0 {
+            state.pending -= sent;
+            for waker in state.send_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+
+        if woken {
+            state.notified = false;
+            let closed = state.closed;
+            drop(state);
+
+            if closed {
+                self.close()?;
+            } else {
+                self.arm_wake()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Submit as many queued sends as there are free slots.
+    fn submit_sends(&mut self) -> io::Result<()> {
+        let sends: Vec<Outgoing> = {
+            let mut state = self.shared.lock();
+            let count = state.sends.len().min(self.free.len());
+            state.sends.drain(..count).collect()
+        };
+
+        for outgoing in sends {
+            let index = self.free.pop().expect("a send slot per queued send");
+            let slot = self.send[index].insert(SendSlot::new(outgoing));
+            let entry = opcode::SendMsg::new(types::Fd(self.socket.as_raw_fd()), &slot.msg)
+                .build()
+                .user_data(SEND | index as u64);
+            self.push(entry)?;
+        }
+
+        Ok(())
+    }
+
+    fn arm_recv(&mut self, index: usize) -> io::Result<()> {
+        let slot = &mut self.recv[index];
+        slot.msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        slot.msg.msg_flags = 0;
+
+        let entry = opcode::RecvMsg::new(types::Fd(self.socket.as_raw_fd()), &mut slot.msg)
+            .build()
+            .user_data(RECV | index as u64);
+        self.push(entry)
+    }
+
+    fn arm_wake(&mut self) -> io::Result<()> {
+        let entry = opcode::Read::new(
+            types::Fd(self.shared.eventfd.as_raw_fd()),
+            ptr::addr_of_mut!(*self.wake).cast(),
+            mem::size_of::<u64>() as u32,
+        )
+        .build()
+        .user_data(WAKE);
+        self.push(entry)
+    }
+
+    /// Cancel the armed receives; [Driver::drive] returns once everything has completed.
+    fn close(&mut self) -> io::Result<()> {
+        self.closing = true;
+
+        for index in 0..RECV_SLOTS {
+            let entry = opcode::AsyncCancel::new(RECV | index as u64)
+                .build()
+                .user_data(CANCEL);
+            self.push(entry)?;
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, entry: io_uring::squeue::Entry) -> io::Result<()> {
+        // The slots that may be armed at once fit in the queue, except for the cancels
+        // on close, so make room by submitting rather than fail.
+        if self.ring.submission().is_full() {
+            self.ring.submit()?;
+        }
+
+        // SAFETY: every buffer the entry points into is boxed and owned by the driver,
+        // and isn't freed or reused until the entry completes.
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        self.outstanding += 1;
+        Ok(())
+    }
+}
+
+/// Stop the kernel fragmenting outgoing packets, as quinn's own socket does, so path MTU
+/// discovery can probe for larger ones.
+fn set_dont_fragment(socket: &UdpSocket) -> io::Result<()> {
+    let fd = socket.as_raw_fd();
+    if socket.local_addr()?.is_ipv6() {
+        setsockopt(
+            fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_PROBE,
+        )?;
+        // A dual-stack socket sends IPv4 too; an IPv6-only one rejects this.
+        let _ = setsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        );
+        Ok(())
+    } else {
+        setsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        )
+    }
+}
+
+fn setsockopt(
+    fd: libc::c_int,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    // SAFETY: passes a live c_int and its size.
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            ptr::addr_of!(value).cast(),
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    match res {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn from_sockaddr(addr: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match addr.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: the family says the storage holds a sockaddr_in.
+            let addr =
+                unsafe { &*(addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            let ip = Ipv4Addr::from(addr.sin_addr.s_addr.to_ne_bytes());
+            Some(SocketAddr::from((ip, u16::from_be(addr.sin_port))))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the family says the storage holds a sockaddr_in6.
+            let addr =
+                unsafe { &*(addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
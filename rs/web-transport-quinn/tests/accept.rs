@@ -0,0 +1,130 @@
+//! Accepted streams lose their header and keep the payload that arrived with it.
+
+mod common;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use web_transport_quinn::proto::{StreamUni, VarInt};
+
+use common::pair;
+
+#[tokio::test]
+async fn uni_payload_follows_header() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = server.accept_uni().await?;
+    assert!(b"hello".starts_with(recv.peek()));
+    assert_eq!(recv.read_to_end(5).await?, b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bi_reads_span_buffered_and_queued_data() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let (mut send, _recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+    send.write_all(b" world").await?;
+    send.finish()?;
+
+    let (_send, mut recv) = server.accept_bi().await?;
+    let mut buf = [0u8; 11];
+    recv.read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"hello world");
+    assert_eq!(recv.read_chunk(usize::MAX, true).await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn first_flight_arrives_with_the_header() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let (mut send, mut recv) = client
+        .open_bi_with_data(Bytes::from_static(b"ping"))
+        .await?;
+    send.finish()?;
+
+    let (mut reply, mut request) = server.accept_bi().await?;
+    assert_eq!(request.read_to_end(4).await?, b"ping");
+    reply.write_all(b"pong").await?;
+    reply.finish()?;
+    assert_eq!(recv.read_to_end(4).await?, b"pong");
+
+    let mut send = client
+        .open_uni_with_data(Bytes::from_static(b"hello"))
+        .await?;
+    send.write_all(b" world").await?;
+    send.finish()?;
+
+    let mut recv = server.accept_uni().await?;
+    assert_eq!(recv.read_to_end(11).await?, b"hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_uni_stream_types() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let ext = StreamUni(VarInt::from_u32(0x40));
+    server.register_uni(ext);
+
+    // An unregistered type is dropped rather than handed to accept_uni.
+    let mut send = client
+        .open_raw_uni(StreamUni(VarInt::from_u32(0x41)))
+        .await?;
+    send.write_all(b"dropped").await?;
+    send.finish()?;
+
+    let mut send = client.open_raw_uni(ext).await?;
+    send.write_all(b"extension").await?;
+    send.finish()?;
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"webtransport").await?;
+    send.finish()?;
+
+    // Each accept gets only its own type, whichever call happens to decode it.
+    let mut recv = server.accept_uni().await?;
+    assert_eq!(recv.read_to_end(64).await?, b"webtransport");
+
+    let mut recv = server.accept_raw_uni(ext).await?;
+    assert_eq!(recv.read_to_end(64).await?, b"extension");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chunked_reads_span_buffered_and_queued_data() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let (mut send, _recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let (_send, mut recv) = server.accept_bi().await?;
+    let mut data = Vec::new();
+
+    // The trait reads chunks straight from quinn, starting with whatever was buffered.
+    let chunk = web_transport_quinn::generic::RecvStream::read_chunk(&mut recv, 2)
+        .await?
+        .context("finished early")?;
+    data.extend_from_slice(&chunk);
+
+    let mut bufs = vec![Bytes::new(); 4];
+    while let Some(count) = recv.read_chunks(&mut bufs).await? {
+        for chunk in &bufs[..count] {
+            data.extend_from_slice(chunk);
+        }
+    }
+
+    assert_eq!(data, b"hello");
+
+    Ok(())
+}
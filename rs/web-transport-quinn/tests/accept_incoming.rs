@@ -0,0 +1,74 @@
+//! Servers deciding who to talk to before spending anything on the handshake.
+
+mod common;
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{Server, ServerBuilder};
+
+fn server() -> Result<(Server, Url)> {
+    let server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    Ok((server, url))
+}
+
+#[tokio::test]
+async fn accepts_after_inspecting_the_address() -> Result<()> {
+    let (mut server, url) = server()?;
+    let client = common::client()?;
+
+    let accept = tokio::spawn(async move {
+        let incoming = server.accept_incoming().await.context("no connection")?;
+        let addr = incoming.remote_address();
+        let request = incoming.accept().await?;
+        assert_eq!(request.peer_addr(), addr);
+        anyhow::Ok((addr, request.ok().await?))
+    });
+
+    let session = client.connect(url).await.context("connect")?;
+    let (addr, _server_session) = accept.await??;
+    assert!(addr.ip().is_loopback(), "{addr}");
+    drop(session);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refused_connections_fail_to_connect() -> Result<()> {
+    let (mut server, url) = server()?;
+
+    tokio::spawn(async move {
+        while let Some(incoming) = server.accept_incoming().await {
+            incoming.refuse();
+        }
+    });
+
+    let err = common::client()?.connect(url).await.unwrap_err();
+    assert!(err.is_retryable(), "{err:?}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retried_connections_come_back_validated() -> Result<()> {
+    let (mut server, url) = server()?;
+
+    let accept = tokio::spawn(async move {
+        let incoming = server.accept_incoming().await.context("no connection")?;
+        assert!(!incoming.remote_address_validated());
+        assert!(incoming.may_retry());
+        incoming.retry().ok().context("retry")?;
+
+        let incoming = server.accept_incoming().await.context("no retry")?;
+        assert!(incoming.remote_address_validated());
+        assert!(!incoming.may_retry());
+        let request = incoming.accept().await?;
+        request.ok().await.context("accept")
+    });
+
+    let _session = common::client()?.connect(url).await.context("connect")?;
+    accept.await??;
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! Sessions that find the accept queue full are rejected with 503 and counted.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{proto::AcceptQueue, ClientError, ConnectError, ServerBuilder};
+
+#[tokio::test]
+async fn overflow_is_rejected() -> Result<()> {
+    // No room at all, so every request overflows.
+    let queue = AcceptQueue::new(0).with_retry_after(Duration::from_secs(3));
+    let mut server = common::server(ServerBuilder::new().with_accept_queue(queue.clone()))?;
+    let url = common::url(&server)?;
+
+    // Nothing should make it out of the accept loop.
+    let accepted = tokio::spawn(async move { server.accept().await.is_some() });
+
+    let err = common::client()?
+        .connect(url)
+        .await
+        .err()
+        .context("connected past a full queue")?;
+    let ClientError::HttpError(ConnectError::Rejected { status, headers }) = err else {
+        panic!("expected 503, got {err:?}");
+    };
+    assert_eq!(status, http::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(headers.get(http::header::RETRY_AFTER).unwrap(), "3");
+
+    assert_eq!(queue.rejected(), 1);
+    assert!(queue.is_empty());
+
+    let res = tokio::time::timeout(Duration::from_millis(100), accepted).await;
+    assert!(res.is_err(), "the rejected request was returned");
+
+    Ok(())
+}
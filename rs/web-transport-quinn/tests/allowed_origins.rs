@@ -0,0 +1,61 @@
+//! Sessions from an origin the server doesn't allow are rejected with 403.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{http, proto::ConnectRequest, ClientError, ConnectError, ServerBuilder};
+
+#[tokio::test]
+async fn disallowed_origins_are_rejected() -> Result<()> {
+    let app = Url::parse("https://app.example")?;
+    let mut server = common::server(ServerBuilder::new().with_allowed_origins([app.clone()]))?;
+    let url = common::url(&server)?;
+
+    // Hand the origin of every request the server lets through to the test.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            let origin = request.origin();
+            if let Ok(session) = request.ok().await {
+                let _ = tx.send((origin, session));
+            }
+        }
+    });
+
+    let client = common::client()?;
+    let from = |origin: &str| -> Result<ConnectRequest> {
+        Ok(ConnectRequest::new(url.clone()).with_header(http::header::ORIGIN, origin.parse()?))
+    };
+
+    let err = client
+        .connect(from("https://evil.example")?)
+        .await
+        .err()
+        .context("connected from a disallowed origin")?;
+    assert!(
+        matches!(
+            err,
+            ClientError::HttpError(ConnectError::Rejected { status, .. })
+                if status == http::StatusCode::FORBIDDEN
+        ),
+        "expected 403, got {err:?}"
+    );
+
+    let _allowed = client.connect(from("https://app.example")?).await?;
+    let (origin, _session) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await?
+        .context("no session")?;
+    assert_eq!(origin, Some(app.origin()));
+
+    // A native client sends no origin at all.
+    let _native = client.connect(url.clone()).await?;
+    let (origin, _session) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await?
+        .context("no session")?;
+    assert_eq!(origin, None);
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Streams work with both tokio's and futures' `AsyncRead`/`AsyncWrite`, WebTransport codes included.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::ReadError;
+
+use common::pair;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn echo_with_io_copy() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    // The server echoes with tokio's io::copy.
+    let echo = tokio::spawn(async move {
+        let (mut send, mut recv) = server.accept_bi().await?;
+        let copied = tokio::io::copy(&mut recv, &mut send).await?;
+        tokio::io::AsyncWriteExt::shutdown(&mut send).await?;
+        anyhow::Ok((copied, server))
+    });
+
+    // The client uses futures' extension traits, named in full since the streams have
+    // inherent methods of the same names.
+    let (mut send, mut recv) = client.open_bi().await?;
+    futures::AsyncWriteExt::write_all(&mut send, b"hello world").await?;
+    futures::AsyncWriteExt::close(&mut send).await?;
+
+    let mut echoed = Vec::new();
+    let read = futures::AsyncReadExt::read_to_end(&mut recv, &mut echoed);
+    tokio::time::timeout(TIMEOUT, read).await??;
+    assert_eq!(echoed, b"hello world");
+
+    let (copied, _server) = echo.await??;
+    assert_eq!(copied, 11);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reset_code_reaches_async_read() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"partial").await?;
+
+    // Wait for the data, so the stream is accepted before it's reset.
+    let mut recv = tokio::time::timeout(TIMEOUT, server.accept_uni()).await??;
+    let mut buf = [0; 7];
+    let read = tokio::io::AsyncReadExt::read_exact(&mut recv, &mut buf);
+    tokio::time::timeout(TIMEOUT, read).await??;
+    assert_eq!(&buf, b"partial");
+
+    send.reset(42)?;
+    let read = tokio::io::AsyncReadExt::read(&mut recv, &mut buf);
+    let err = tokio::time::timeout(TIMEOUT, read)
+        .await?
+        .err()
+        .context("read past a reset")?;
+
+    // The io::Error carries the WebTransport code, not quinn's HTTP/3 one.
+    let inner = err.get_ref().and_then(|e| e.downcast_ref::<ReadError>());
+    assert!(
+        matches!(inner, Some(ReadError::Reset(42))),
+        "expected a reset with code 42, got {err:?}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Replacing the server certificate while the server keeps running.
+
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::{Context, Result};
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair, KeyUsagePurpose};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quinn::{CertReloader, ClientBuilder, Server, ServerBuilder};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A CA that issues the server certificates, so the client trusts every one of them.
+struct Ca {
+    root: CertificateDer<'static>,
+    params: CertificateParams,
+    key: KeyPair,
+}
+
+/// A leaf certificate, in DER and PEM.
+struct Leaf {
+    der: CertificateDer<'static>,
+    key: PrivateKeyDer<'static>,
+    cert_pem: String,
+    key_pem: String,
+}
+
+impl Ca {
+    fn new() -> Result<Self> {
+        let key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::new())?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "test CA");
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign];
+        let cert = params.self_signed(&key)?;
+
+        Ok(Self {
+            root: CertificateDer::from(cert.der().to_vec()),
+            params,
+            key,
+        })
+    }
+
+    fn issue(&self) -> Result<Leaf> {
+        let key = KeyPair::generate()?;
+        let params = CertificateParams::new(vec!["localhost".into()])?;
+        let cert = params.signed_by(&key, &Issuer::from_params(&self.params, &self.key))?;
+
+        Ok(Leaf {
+            der: CertificateDer::from(cert.der().to_vec()),
+            key: PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+            cert_pem: cert.pem(),
+            key_pem: key.serialize_pem(),
+        })
+    }
+}
+
+/// Accept and respond to every request in the background, returning the server's address.
+fn serve(mut server: Server) -> Result<SocketAddr> {
+    let addr = server.local_addr()?;
+    tokio::spawn(async move {
+        let mut sessions = Vec::new();
+        while let Some(request) = server.accept().await {
+            if let Ok(session) = request.ok().await {
+                sessions.push(session);
+            }
+        }
+    });
+    Ok(addr)
+}
+
+/// Connect to the server and return the leaf certificate it presented.
+async fn presented(ca: &Ca, addr: SocketAddr) -> Result<CertificateDer<'static>> {
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+    let client = ClientBuilder::new().with_root_certificates(vec![ca.root.clone()])?;
+
+    let session = tokio::time::timeout(TIMEOUT, client.connect(url))
+        .await?
+        .context("connect")?;
+
+    let chain = session
+        .peer_certificates()
+        .context("no server certificate")?;
+    chain.into_iter().next().context("empty chain")
+}
+
+#[tokio::test]
+async fn set_applies_to_new_handshakes() -> Result<()> {
+    let ca = Ca::new()?;
+    let first = ca.issue()?;
+    let second = ca.issue()?;
+
+    let reloader = CertReloader::new(vec![first.der.clone()], first.key)?;
+    let server = ServerBuilder::new()
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_cert_reloader(reloader.clone())?;
+    let addr = serve(server)?;
+
+    assert_eq!(presented(&ca, addr).await?, first.der);
+
+    // A key that doesn't match the chain is rejected, and the old certificate stays.
+    let mismatched = ca.issue()?;
+    assert!(reloader
+        .set(vec![second.der.clone()], mismatched.key)
+        .is_err());
+    assert_eq!(presented(&ca, addr).await?, first.der);
+
+    reloader.set(vec![second.der.clone()], second.key)?;
+    assert_eq!(presented(&ca, addr).await?, second.der);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_pem_picks_up_renewals() -> Result<()> {
+    let ca = Ca::new()?;
+    let first = ca.issue()?;
+    let second = ca.issue()?;
+
+    let dir = std::env::temp_dir().join(format!("wt-cert-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    std::fs::write(&cert_path, &first.cert_pem)?;
+    std::fs::write(&key_path, &first.key_pem)?;
+
+    let reloader = CertReloader::new(vec![first.der.clone()], first.key)?;
+    let _watcher = reloader.watch_pem(&cert_path, &key_path, Duration::from_millis(20));
+
+    // Some filesystems only track whole seconds, so make sure the write changes the mtime.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    std::fs::write(&cert_path, &second.cert_pem)?;
+    std::fs::write(&key_path, &second.key_pem)?;
+
+    let reloaded = tokio::time::timeout(TIMEOUT, async {
+        while reloader.current().cert[0] != second.der {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+
+    std::fs::remove_dir_all(&dir).ok();
+    reloaded.context("certificate not reloaded")?;
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! Client-certificate authentication (mTLS) and the peer chain it exposes.
+//!
+//! The negative cases matter most: a certificate from an untrusted CA, or none at all,
+//! must fail the handshake when one is required.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair,
+    KeyUsagePurpose,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::server::WebPkiClientVerifier;
+use url::Url;
+use web_transport_quinn::{ClientBuilder, Server, ServerBuilder};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type Chain = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+/// A CA that issues server and client certificates.
+struct Ca {
+    root: CertificateDer<'static>,
+    params: CertificateParams,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new(name: &str) -> Result<Self> {
+        let key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::new())?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        let cert = params.self_signed(&key)?;
+
+        Ok(Self {
+            root: CertificateDer::from(cert.der().to_vec()),
+            params,
+            key,
+        })
+    }
+
+    fn issue(&self, name: &str, usage: ExtendedKeyUsagePurpose) -> Result<Chain> {
+        let key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(vec![name.into()])?;
+        params.distinguished_name.push(DnType::CommonName, name);
+        params.extended_key_usages = vec![usage];
+
+        let issuer = Issuer::from_params(&self.params, &self.key);
+        let cert = params.signed_by(&key, &issuer)?;
+
+        let chain = vec![CertificateDer::from(cert.der().to_vec())];
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(KeyPair::serialize_der(&key)));
+        Ok((chain, key))
+    }
+
+    fn server_cert(&self) -> Result<Chain> {
+        self.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)
+    }
+
+    fn client_cert(&self, name: &str) -> Result<Chain> {
+        self.issue(name, ExtendedKeyUsagePurpose::ClientAuth)
+    }
+}
+
+fn url_for(addr: SocketAddr) -> Result<Url> {
+    Ok(Url::parse(&format!("https://localhost:{}/", addr.port()))?)
+}
+
+/// Connect with an optional client certificate, returning the server's view of the chain.
+async fn connect(
+    ca: &Ca,
+    mut server: Server,
+    client_cert: Option<Chain>,
+) -> Result<Option<Vec<CertificateDer<'static>>>> {
+    let url = url_for(server.local_addr()?)?;
+
+    let mut client = ClientBuilder::new();
+    if let Some((chain, key)) = client_cert {
+        client = client.with_client_certificate(chain, key);
+    }
+    let client = client.with_root_certificates(vec![ca.root.clone()])?;
+
+    // Respond in the background, since connect waits for the response.
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let seen = request.peer_certificates();
+        let session = request.ok().await?;
+        assert_eq!(session.peer_certificates(), seen);
+        anyhow::Ok((session, seen))
+    });
+
+    let _session = tokio::time::timeout(TIMEOUT, client.connect(url))
+        .await?
+        .context("connect")?;
+    let (_session, seen) = tokio::time::timeout(TIMEOUT, accepted).await???;
+
+    Ok(seen)
+}
+
+/// Connect and expect the handshake to fail, without the server accepting a request.
+async fn rejected(ca: &Ca, mut server: Server, client_cert: Option<Chain>) -> Result<()> {
+    let url = url_for(server.local_addr()?)?;
+    let accepted = tokio::spawn(async move { server.accept().await.is_some() });
+
+    let mut client = ClientBuilder::new();
+    if let Some((chain, key)) = client_cert {
+        client = client.with_client_certificate(chain, key);
+    }
+    let client = client.with_root_certificates(vec![ca.root.clone()])?;
+
+    let res = tokio::time::timeout(TIMEOUT, client.connect(url)).await?;
+    assert!(res.is_err(), "handshake succeeded");
+
+    // Give a wrongly accepted connection the chance to surface.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!accepted.is_finished(), "server accepted the session");
+    accepted.abort();
+
+    Ok(())
+}
+
+fn server() -> Result<ServerBuilder> {
+    Ok(ServerBuilder::new().with_addr("127.0.0.1:0".parse()?))
+}
+
+#[tokio::test]
+async fn required_client_cert_is_exposed() -> Result<()> {
+    let ca = Ca::new("test CA")?;
+    let (chain, key) = ca.server_cert()?;
+    let client_cert = ca.client_cert("client.example")?;
+    let leaf = client_cert.0[0].clone();
+
+    let server = server()?
+        .with_client_ca_roots(vec![ca.root.clone()])
+        .with_certificate(chain, key)?;
+
+    let seen = connect(&ca, server, Some(client_cert)).await?;
+    assert_eq!(seen, Some(vec![leaf]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn untrusted_client_cert_is_rejected() -> Result<()> {
+    let ca = Ca::new("test CA")?;
+    let other = Ca::new("other CA")?;
+    let (chain, key) = ca.server_cert()?;
+
+    let server = server()?
+        .with_client_ca_roots(vec![ca.root.clone()])
+        .with_certificate(chain, key)?;
+
+    rejected(&ca, server, Some(other.client_cert("client.example")?)).await
+}
+
+#[tokio::test]
+async fn missing_client_cert_is_rejected() -> Result<()> {
+    let ca = Ca::new("test CA")?;
+    let (chain, key) = ca.server_cert()?;
+
+    let server = server()?
+        .with_client_ca_roots(vec![ca.root.clone()])
+        .with_certificate(chain, key)?;
+
+    rejected(&ca, server, None).await
+}
+
+#[tokio::test]
+async fn custom_verifier_can_make_the_cert_optional() -> Result<()> {
+    let ca = Ca::new("test CA")?;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(ca.root.clone())?;
+    let verifier = WebPkiClientVerifier::builder_with_provider(
+        Arc::new(roots),
+        web_transport_quinn::crypto::default_provider(),
+    )
+    .allow_unauthenticated()
+    .build()?;
+
+    // Without a certificate, the client is accepted anonymously.
+    let (chain, key) = ca.server_cert()?;
+    let anonymous = server()?
+        .with_client_cert_verifier(verifier.clone())
+        .with_certificate(chain, key)?;
+    assert_eq!(connect(&ca, anonymous, None).await?, None);
+
+    // With one, it's verified and exposed.
+    let (chain, key) = ca.server_cert()?;
+    let client_cert = ca.client_cert("client.example")?;
+    let leaf = client_cert.0[0].clone();
+    let verified = server()?
+        .with_client_cert_verifier(verifier)
+        .with_certificate(chain, key)?;
+    assert_eq!(
+        connect(&ca, verified, Some(client_cert)).await?,
+        Some(vec![leaf])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn empty_roots_fail_the_build() -> Result<()> {
+    let ca = Ca::new("test CA")?;
+    let (chain, key) = ca.server_cert()?;
+
+    let err = server()?
+        .with_client_ca_roots(Vec::new())
+        .with_certificate(chain, key)
+        .err()
+        .context("built with no roots")?;
+    assert_eq!(err.kind(), web_transport_quinn::ErrorKind::InvalidInput);
+
+    Ok(())
+}
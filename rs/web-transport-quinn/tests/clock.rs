@@ -0,0 +1,99 @@
+//! Timeouts follow the clock given to the builder, not the tokio timer.
+
+mod common;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use tokio::sync::{mpsc, watch};
+use web_transport_quinn::{generic::boxed::BoxFuture, Clock, Request, ServerBuilder, ServerError};
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+// A clock that only moves when told to.
+#[derive(Clone)]
+struct ManualClock(Arc<watch::Sender<Instant>>);
+
+impl ManualClock {
+    fn new() -> Self {
+        Self(Arc::new(watch::Sender::new(Instant::now())))
+    }
+
+    fn advance(&self, by: Duration) {
+        self.0.send_modify(|now| *now += by);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.0.borrow()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        let mut now = self.0.subscribe();
+        Box::pin(async move {
+            let _ = now.wait_for(|now| *now >= deadline).await;
+        })
+    }
+}
+
+// Start a server on `clock` that hands each request to a queue, and a client connecting to it.
+async fn queued(clock: ManualClock) -> Result<mpsc::Receiver<Request>> {
+    let mut server = common::server(
+        ServerBuilder::new()
+            .with_response_timeout(Some(TIMEOUT))
+            .with_clock(clock),
+    )?;
+    let url = common::url(&server)?;
+
+    let (queue, requests) = mpsc::channel(8);
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if queue.send(request).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let client = common::client()?;
+        anyhow::Ok(client.connect(url).await?)
+    });
+
+    Ok(requests)
+}
+
+#[tokio::test]
+async fn stopped_clock_never_expires() -> Result<()> {
+    let mut requests = queued(ManualClock::new()).await?;
+
+    let request = requests.recv().await.context("no request")?;
+    tokio::time::sleep(TIMEOUT * 4).await;
+
+    request.ok().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn advanced_clock_expires() -> Result<()> {
+    let clock = ManualClock::new();
+    let mut requests = queued(clock.clone()).await?;
+
+    let request = requests.recv().await.context("no request")?;
+    clock.advance(TIMEOUT * 2);
+
+    // Give the expiry task a chance to run.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let err = request.ok().await.unwrap_err();
+    assert!(
+        matches!(err, ServerError::RequestTimeout),
+        "expected RequestTimeout, got {err:?}"
+    );
+
+    Ok(())
+}
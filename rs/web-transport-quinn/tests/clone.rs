@@ -0,0 +1,127 @@
+//! Any live clone of a session keeps accepting, whichever handles or callers go away,
+//! even if one of them panics.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::time::timeout;
+
+use common::pair;
+
+// Long enough for a stream to arrive over loopback, short enough to fail a stalled test fast.
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn clone_accepts_after_original_dropped() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let clone = server.clone();
+    let accept = tokio::spawn(async move { clone.accept_uni().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(server);
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, accept).await.context("accept stalled")???;
+    assert_eq!(recv.read_to_end(5).await?, b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_accept_uni_hands_off() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let clone = server.clone();
+    let accept = tokio::spawn(async move { clone.accept_uni().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The last caller to poll is the one the connection would wake; cancel it.
+    assert!(timeout(Duration::from_millis(50), server.accept_uni())
+        .await
+        .is_err());
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, accept).await.context("accept stalled")???;
+    assert_eq!(recv.read_to_end(5).await?, b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_accept_bi_hands_off() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let clone = server.clone();
+    let accept = tokio::spawn(async move { clone.accept_bi().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(timeout(Duration::from_millis(50), server.accept_bi())
+        .await
+        .is_err());
+    drop(server);
+
+    let (mut send, _recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let (_send, mut recv) = timeout(WAIT, accept).await.context("accept stalled")???;
+    assert_eq!(recv.read_to_end(5).await?, b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn panicked_accept_leaves_others_working() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    // Panic with an accept call still waiting, as a buggy caller might.
+    let clone = server.clone();
+    let panicked = tokio::spawn(async move {
+        let accept = clone.accept_uni();
+        tokio::pin!(accept);
+        assert!(futures::poll!(&mut accept).is_pending());
+        panic!("caller bug");
+    });
+    assert!(panicked.await.unwrap_err().is_panic());
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, server.accept_uni())
+        .await
+        .context("accept stalled")??;
+    assert_eq!(recv.read_to_end(5).await?, b"hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn streams_wait_for_any_clone() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    // Streams arrive while nobody is accepting, and any clone can take them afterwards.
+    for msg in [b"one", b"two"] {
+        let mut send = client.open_uni().await?;
+        send.write_all(msg).await?;
+        send.finish()?;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let clone = server.clone();
+    let mut first = timeout(WAIT, server.accept_uni()).await??;
+    let mut second = timeout(WAIT, clone.accept_uni()).await??;
+    let mut got = vec![first.read_to_end(3).await?, second.read_to_end(3).await?];
+    got.sort();
+    assert_eq!(got, [b"one", b"two"]);
+
+    Ok(())
+}
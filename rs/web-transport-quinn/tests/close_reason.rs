@@ -0,0 +1,27 @@
+//! Close reasons are relayed as raw bytes, even when they aren't UTF-8.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use web_transport_trait::{Error as _, Session as _};
+
+use common::pair;
+
+#[tokio::test]
+async fn non_utf8_reason_is_relayed_verbatim() -> Result<()> {
+    let (client, server) = pair().await?;
+    server.close_bytes(9, b"bad \xff reason");
+
+    let err = tokio::time::timeout(Duration::from_secs(5), client.closed()).await?;
+    assert_eq!(
+        err.session_error_bytes(),
+        Some((9, bytes::Bytes::from_static(b"bad \xff reason")))
+    );
+    assert_eq!(
+        err.session_error(),
+        Some((9, "bad \u{fffd} reason".to_string()))
+    );
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Setup shared by the integration tests.
+
+// Each test binary uses a different subset.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quinn::{Client, ClientBuilder, Server, ServerBuilder, Session};
+
+/// A self-signed certificate for `localhost`, and its key.
+pub fn certificate() -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
+    let key = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+    let cert = CertificateDer::from(key.cert.der().to_vec());
+    let der = rcgen::KeyPair::serialize_der(&key.signing_key);
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(der));
+
+    Ok((cert, key))
+}
+
+/// Bind `builder` to an ephemeral localhost port, serving a fresh [certificate].
+pub fn server(builder: ServerBuilder) -> Result<Server> {
+    let (cert, key) = certificate()?;
+
+    Ok(builder
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_certificate(vec![cert], key)?)
+}
+
+/// The URL to reach `server` at, by the name on its certificate.
+pub fn url(server: &Server) -> Result<Url> {
+    let port = server.local_addr()?.port();
+    Ok(Url::parse(&format!("https://localhost:{port}/"))?)
+}
+
+/// A client that accepts the server's self-signed certificate.
+pub fn client() -> Result<Client> {
+    Ok(ClientBuilder::new()
+        .dangerous()
+        .with_no_certificate_verification()?)
+}
+
+/// Connect `client` to a server built from `builder`, returning both ends of the session.
+pub async fn connect(builder: ServerBuilder, client: Client) -> Result<(Session, Session)> {
+    let mut server = server(builder)?;
+    let url = url(&server)?;
+
+    // Respond in the background, since connect waits for the response.
+    let accept = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok().await.context("accept")
+    });
+
+    let client = client.connect(url).await.context("connect")?;
+    let server = tokio::time::timeout(Duration::from_secs(5), accept).await???;
+
+    Ok((client, server))
+}
+
+/// Connect to a server, returning both ends of the session.
+pub async fn pair() -> Result<(Session, Session)> {
+    connect(ServerBuilder::new(), client()?).await
+}
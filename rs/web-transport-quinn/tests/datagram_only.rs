@@ -0,0 +1,97 @@
+//! A datagram-only session carries datagrams, and resets any stream the peer opens.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use web_transport_quinn::{
+    proto::{codes, SessionMode},
+    ReadError, ServerBuilder, Session, SessionError, WebTransportError,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Connect a full client to a server that accepts the session as datagram-only.
+async fn pair() -> Result<(Session, Session)> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let request = request.with_session_mode(SessionMode::DatagramOnly);
+        request.ok().await.context("accept")
+    });
+
+    let client = common::client()?.connect(url).await?;
+    let server = tokio::time::timeout(TIMEOUT, accepted).await???;
+
+    Ok((client, server))
+}
+
+#[tokio::test]
+async fn datagrams_still_flow() -> Result<()> {
+    let (client, server) = pair().await?;
+    assert_eq!(server.mode(), SessionMode::DatagramOnly);
+    assert_eq!(client.mode(), SessionMode::Full);
+
+    client.send_datagram(Bytes::from_static(b"ping"))?;
+    let datagram = tokio::time::timeout(TIMEOUT, server.read_datagram()).await??;
+    assert_eq!(datagram, "ping");
+
+    server.send_datagram(Bytes::from_static(b"pong"))?;
+    let datagram = tokio::time::timeout(TIMEOUT, client.read_datagram()).await??;
+    assert_eq!(datagram, "pong");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn peer_streams_are_reset() -> Result<()> {
+    let (client, _server) = pair().await?;
+
+    let (mut send, mut recv) = client.open_bi().await?;
+    send.write_all(b"hello").await?;
+
+    let stopped = tokio::time::timeout(TIMEOUT, send.stopped()).await??;
+    assert_eq!(stopped, Some(codes::STREAMS_DISABLED));
+
+    let mut buf = [0; 16];
+    let err = tokio::time::timeout(TIMEOUT, recv.read(&mut buf))
+        .await?
+        .unwrap_err();
+    assert!(
+        matches!(err, ReadError::Reset(codes::STREAMS_DISABLED)),
+        "unexpected error: {err:?}"
+    );
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    let stopped = tokio::time::timeout(TIMEOUT, send.stopped()).await??;
+    assert_eq!(stopped, Some(codes::STREAMS_DISABLED));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn local_streams_are_refused() -> Result<()> {
+    let (_client, server) = pair().await?;
+
+    for err in [
+        server.open_bi().await.map(drop).unwrap_err(),
+        server.open_uni().await.map(drop).unwrap_err(),
+        server.accept_bi().await.map(drop).unwrap_err(),
+        server.accept_uni().await.map(drop).unwrap_err(),
+    ] {
+        assert!(
+            matches!(
+                err,
+                SessionError::WebTransportError(WebTransportError::StreamsDisabled)
+            ),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    Ok(())
+}
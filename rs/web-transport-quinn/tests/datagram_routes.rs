@@ -0,0 +1,53 @@
+//! Datagrams are delivered to the route registered for their leading varint.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::Bytes;
+
+use common::pair;
+
+#[tokio::test]
+async fn routed_by_prefix() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let audio = server.route_datagrams(1);
+    let video = server.route_datagrams(2);
+
+    // Interleave the prefixes; each route only sees its own, in order.
+    for datagram in [&b"\x01a1"[..], b"\x02v1", b"\x03x", b"\x01a2", b"\x02v2"] {
+        client.send_datagram(Bytes::copy_from_slice(datagram))?;
+    }
+
+    let timeout = Duration::from_secs(5);
+    assert_eq!(
+        tokio::time::timeout(timeout, video.recv()).await??,
+        &b"\x02v1"[..]
+    );
+    assert_eq!(
+        tokio::time::timeout(timeout, video.recv()).await??,
+        &b"\x02v2"[..]
+    );
+    assert_eq!(
+        tokio::time::timeout(timeout, audio.recv()).await??,
+        &b"\x01a1"[..]
+    );
+    assert_eq!(
+        tokio::time::timeout(timeout, audio.recv()).await??,
+        &b"\x01a2"[..]
+    );
+
+    // Prefixes without a route are left for read_datagram.
+    let other = tokio::time::timeout(timeout, server.read_datagram()).await??;
+    assert_eq!(other, &b"\x03x"[..]);
+
+    // Once a route is dropped, its prefix goes back to read_datagram.
+    drop(audio);
+    client.send_datagram(Bytes::from_static(b"\x01a3"))?;
+    let other = tokio::time::timeout(timeout, server.read_datagram()).await??;
+    assert_eq!(other, &b"\x01a3"[..]);
+
+    Ok(())
+}
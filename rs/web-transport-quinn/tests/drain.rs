@@ -0,0 +1,69 @@
+//! Draining asks the peer to wrap up without interrupting the session, optionally pointing
+//! it somewhere else to reconnect.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use url::Url;
+use web_transport_quinn::{SessionError, WebTransportError};
+
+use common::pair;
+
+#[tokio::test]
+async fn drain_keeps_session_open() -> Result<()> {
+    let (client, server) = pair().await?;
+    assert!(!client.is_draining());
+
+    server.drain().await?;
+    tokio::time::timeout(Duration::from_secs(5), client.draining()).await?;
+    assert!(client.is_draining());
+    assert!(!server.is_draining());
+
+    // Streams still work in both directions after the drain.
+    let mut send = client.open_uni().await?;
+    send.write_all(b"bye").await?;
+    send.finish()?;
+    let mut recv = tokio::time::timeout(Duration::from_secs(5), server.accept_uni()).await??;
+    assert_eq!(recv.read_to_end(16).await?, b"bye");
+
+    // Closing still delivers the code and reason.
+    server.close(7, b"done");
+    let err = tokio::time::timeout(Duration::from_secs(5), client.closed()).await?;
+    assert!(
+        matches!(
+            err.without_history(),
+            SessionError::WebTransportError(WebTransportError::Closed(7, _))
+        ),
+        "unexpected close: {err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconnect_hint_reaches_client() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let target = Url::parse("https://relay.example.com:4443/moq")?;
+    server.send_reconnect_hint(target.clone()).await?;
+    server.drain().await?;
+
+    let hint = tokio::time::timeout(Duration::from_secs(5), client.reconnect_hint()).await?;
+    assert_eq!(hint, Some(target));
+    assert!(client.is_draining());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconnect_hint_none_on_close() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    server.close(0, b"");
+    let hint = tokio::time::timeout(Duration::from_secs(5), client.reconnect_hint()).await?;
+    assert_eq!(hint, None);
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Streams and sessions dropped without being closed use the shared drop codes.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use web_transport_quinn::{
+    proto::codes::{self, DropCodes},
+    ServerBuilder, Session, SessionError, WebTransportError,
+};
+
+async fn pair(codes: DropCodes) -> Result<(Session, Session)> {
+    let server = ServerBuilder::new().with_drop_codes(codes);
+    common::connect(server, common::client()?).await
+}
+
+// Open a stream from the client, drop it on the server, and return the stop code.
+async fn dropped_recv_code(codes: DropCodes) -> Result<Option<u32>> {
+    let (client, server) = pair(codes).await?;
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+
+    let recv = server.accept_uni().await?;
+    drop(recv);
+
+    let stopped = tokio::time::timeout(Duration::from_secs(5), send.stopped()).await??;
+    Ok(stopped)
+}
+
+#[tokio::test]
+async fn dropped_recv_stream() -> Result<()> {
+    let code = dropped_recv_code(DropCodes::default()).await?;
+    assert_eq!(code, Some(codes::RECV_DROPPED));
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_drop_codes() -> Result<()> {
+    let codes = DropCodes {
+        recv: 7,
+        ..Default::default()
+    };
+    let code = dropped_recv_code(codes).await?;
+    assert_eq!(code, Some(7));
+    Ok(())
+}
+
+#[tokio::test]
+async fn dropped_session() -> Result<()> {
+    let (client, server) = pair(DropCodes::default()).await?;
+    drop(server);
+
+    let err = tokio::time::timeout(Duration::from_secs(5), client.closed()).await?;
+    assert!(
+        matches!(
+            err.without_history(),
+            SessionError::WebTransportError(WebTransportError::Closed(code, _))
+                if *code == codes::SESSION_DROPPED
+        ),
+        "expected the session drop code, got {err:?}"
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! The peer's reset and stop codes come back as the WebTransport codes it sent.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::time::timeout;
+use web_transport_quinn::generic::Error as _;
+
+use common::pair;
+
+const WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn codes_seen_by_reads_and_writes() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    // Either side of the first reserved HTTP/3 codepoint, and the largest code.
+    for code in [0, 0x1d, 0x1e, u32::MAX] {
+        let (mut send, mut recv) = client.open_bi().await?;
+        send.write_all(b"ping").await?;
+
+        let (mut reply, mut request) = timeout(WAIT, server.accept_bi()).await??;
+        reply.reset(code)?;
+        request.stop(code)?;
+
+        let err = timeout(WAIT, recv.read_to_end(1024))
+            .await?
+            .err()
+            .context("read past a reset")?;
+        assert_eq!(err.stream_error(), Some(code));
+        assert_eq!(recv.reset_code(), Some(code));
+
+        let err = timeout(WAIT, async {
+            loop {
+                if let Err(err) = send.write_all(b"more").await {
+                    return err;
+                }
+            }
+        })
+        .await?;
+        assert_eq!(err.stream_error(), Some(code));
+        assert_eq!(send.stop_code(), Some(code));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn finished_streams_have_no_code() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let (mut send, mut recv) = client.open_bi().await?;
+    send.write_all(b"ping").await?;
+    send.finish()?;
+
+    let (mut reply, mut request) = timeout(WAIT, server.accept_bi()).await??;
+    assert_eq!(request.read_to_end(4).await?, b"ping");
+    reply.finish()?;
+
+    assert_eq!(timeout(WAIT, recv.read_to_end(4)).await??, b"");
+    assert_eq!(recv.reset_code(), None);
+    assert_eq!(timeout(WAIT, send.stopped()).await??, None);
+    assert_eq!(send.stop_code(), None);
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! The server rejects CONNECT requests with headers beyond its advertised limit.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{proto::ConnectRequest, ClientError, ConnectError, ServerBuilder};
+
+#[tokio::test]
+async fn oversized_headers_are_rejected() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new().with_max_field_section_size(1024))?;
+    let url = common::url(&server)?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok().await.context("accept")
+    });
+
+    let client = common::client()?;
+
+    let request = ConnectRequest::new(url.clone()).with_header(
+        http::HeaderName::from_static("x-padding"),
+        http::HeaderValue::from_str(&"a".repeat(2048))?,
+    );
+    let err = client.connect(request).await.err().context("connected")?;
+    assert!(
+        matches!(
+            err,
+            ClientError::HttpError(ConnectError::Rejected { status, .. })
+                if status == http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        ),
+        "expected 431, got {err:?}"
+    );
+
+    // A request under the limit is still accepted.
+    let _client = client.connect(url).await?;
+    let _server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    Ok(())
+}
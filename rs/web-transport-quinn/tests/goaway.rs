@@ -0,0 +1,58 @@
+//! Shutting the server down sends GOAWAY, which the client sees as draining and then as a
+//! graceful close.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{proto::codes, quinn::VarInt, ErrorKind, ServerBuilder, SessionError};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn shutdown_sends_goaway() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Respond in the background, since connect waits for the response.
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let session = request.ok().await.context("accept")?;
+        anyhow::Ok((server, session))
+    });
+
+    let client = common::client()?.connect(url).await.context("connect")?;
+    let (mut server, session) = tokio::time::timeout(TIMEOUT, accepted).await???;
+    assert!(!client.is_draining());
+
+    server.shutdown();
+    assert!(server.accept().await.is_none(), "accepted after shutdown");
+
+    tokio::time::timeout(TIMEOUT, client.draining())
+        .await
+        .context("no GOAWAY")?;
+    assert!(client.is_draining());
+
+    // The session still works until the server closes the connection.
+    let mut send = session.open_uni().await?;
+    send.write_all(b"bye").await?;
+    send.finish()?;
+    let mut recv = client.accept_uni().await?;
+    assert_eq!(recv.read_to_end(3).await?, b"bye");
+
+    server.close(VarInt::from_u64(codes::h3::NO_ERROR)?, b"");
+
+    let err = tokio::time::timeout(TIMEOUT, client.closed()).await?;
+    assert!(
+        matches!(err.without_history(), SessionError::GoAway),
+        "{err:?}"
+    );
+    assert_eq!(
+        err.to_string(),
+        format!("session {}: peer shut down gracefully", client.id())
+    );
+    assert_eq!(err.kind(), ErrorKind::SessionClosed);
+
+    Ok(())
+}
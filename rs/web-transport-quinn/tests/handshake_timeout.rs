@@ -0,0 +1,98 @@
+//! A client that stalls after the QUIC handshake is disconnected instead of pinning the server,
+//! and a client gives up on a server that never answers.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use web_transport_quinn::{proto, ClientBuilder, ClientError, ServerBuilder};
+
+// Connect with a raw QUIC client, send nothing (or only SETTINGS), and return the close code.
+async fn stalled_close_code(send_settings: bool) -> Result<u64> {
+    let (cert, key) = common::certificate()?;
+
+    let mut server = ServerBuilder::new()
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_handshake_timeout(Duration::from_millis(100))
+        .with_certificate(vec![cert.clone()], key)?;
+    let addr = server.local_addr()?;
+    tokio::spawn(async move { server.accept().await.map(|_| ()) });
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert)?;
+    let mut crypto = rustls::ClientConfig::builder_with_provider(
+        web_transport_quinn::crypto::default_provider(),
+    )
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    crypto.alpn_protocols = vec![web_transport_quinn::ALPN.as_bytes().to_vec()];
+
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?;
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+    let conn = client.connect(addr, "localhost")?.await?;
+
+    // Keep the control stream open, as a real client would.
+    let mut _control = None;
+    if send_settings {
+        let mut settings = proto::Settings::default();
+        settings.enable_webtransport(1);
+
+        let mut send = conn.open_uni().await?;
+        settings.write(&mut send).await?;
+        _control = Some(send);
+    }
+
+    let err = tokio::time::timeout(Duration::from_secs(5), conn.closed()).await?;
+    match err {
+        quinn::ConnectionError::ApplicationClosed(close) => Ok(close.error_code.into_inner()),
+        err => anyhow::bail!("expected an application close, got {err:?}"),
+    }
+}
+
+#[tokio::test]
+async fn missing_settings() -> Result<()> {
+    let code = stalled_close_code(false).await?;
+    assert_eq!(code, proto::codes::h3::SETTINGS_ERROR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn missing_connect() -> Result<()> {
+    let code = stalled_close_code(true).await?;
+    assert_eq!(code, proto::codes::h3::REQUEST_REJECTED);
+    Ok(())
+}
+
+#[tokio::test]
+async fn client_gives_up_on_an_unanswered_connect() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Take the request but never respond to it.
+    tokio::spawn(async move {
+        let _request = server.accept().await;
+        std::future::pending::<()>().await
+    });
+
+    let client = ClientBuilder::new()
+        .with_handshake_timeout(Duration::from_millis(200))
+        .dangerous()
+        .with_no_certificate_verification()?;
+    let err = tokio::time::timeout(Duration::from_secs(5), client.connect(url))
+        .await?
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ClientError::Connection(quinn::ConnectionError::TimedOut)
+        ),
+        "{err:?}"
+    );
+    assert!(err.is_retryable());
+
+    Ok(())
+}
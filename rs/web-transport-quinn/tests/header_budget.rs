@@ -0,0 +1,103 @@
+//! Incoming streams must send their header within the session's budget, or be stopped.
+//!
+//! The peer here writes raw bytes to plain QUIC streams, so the headers can be as
+//! broken as needed.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::time::timeout;
+use web_transport_quinn::{
+    proto::{codes, HeaderBudget},
+    Session,
+};
+
+// Long enough for a stream to arrive over loopback, short enough to fail a stalled test fast.
+const WAIT: Duration = Duration::from_secs(5);
+
+// The stream type of a WebTransport uni stream, without the session ID that should follow.
+const TYPE_ONLY: [u8; 2] = [0x40, 0x54];
+
+/// Connect to a server, returning both ends of the session.
+async fn pair(budget: HeaderBudget) -> Result<(Session, Session)> {
+    let (client, server) = common::pair().await?;
+    server.set_header_budget(budget);
+    Ok((client, server))
+}
+
+/// Wait for the server to stop `send`, returning the code.
+async fn stopped(send: &quinn::SendStream) -> Result<u64> {
+    let code = timeout(WAIT, send.stopped())
+        .await
+        .context("never stopped")??
+        .context("finished instead")?;
+    Ok(code.into_inner())
+}
+
+#[tokio::test]
+async fn stalled_header_is_stopped() -> Result<()> {
+    let budget = HeaderBudget {
+        timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let (client, server) = pair(budget).await?;
+
+    let mut send = quinn::Connection::open_uni(&client).await?;
+    send.write_all(&TYPE_ONLY).await?;
+    assert_eq!(stopped(&send).await?, codes::h3::GENERAL_PROTOCOL_ERROR);
+
+    // Bidirectional streams too, here with half of a two byte frame type.
+    let (mut send, _recv) = quinn::Connection::open_bi(&client).await?;
+    send.write_all(&[0x41]).await?;
+    assert_eq!(stopped(&send).await?, codes::h3::GENERAL_PROTOCOL_ERROR);
+
+    // Streams with a whole header are unaffected.
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hi").await?;
+    send.finish()?;
+    let mut recv = timeout(WAIT, server.accept_uni()).await??;
+    assert_eq!(recv.read_to_end(2).await?, b"hi");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oversized_header_is_stopped() -> Result<()> {
+    let budget = HeaderBudget {
+        max_bytes: 4,
+        timeout: None,
+    };
+    let (client, _server) = pair(budget).await?;
+
+    // The first byte announces an 8 byte stream type, so there's no need to wait for the rest.
+    let mut send = quinn::Connection::open_uni(&client).await?;
+    send.write_all(&[0xc0]).await?;
+    assert_eq!(stopped(&send).await?, codes::h3::GENERAL_PROTOCOL_ERROR);
+
+    // A type that fits, followed by a session ID that doesn't.
+    let mut send = quinn::Connection::open_uni(&client).await?;
+    send.write_all(&TYPE_ONLY).await?;
+    send.write_all(&[0x80, 0, 0, 0]).await?;
+    assert_eq!(stopped(&send).await?, codes::h3::GENERAL_PROTOCOL_ERROR);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn default_budget_fits_the_longest_header() -> Result<()> {
+    let (client, server) = pair(HeaderBudget::default()).await?;
+
+    // An 8 byte stream type and an 8 byte session ID, both valid encodings.
+    let mut send = quinn::Connection::open_uni(&client).await?;
+    send.write_all(&[0xc0, 0, 0, 0, 0, 0, 0, 0x54]).await?;
+    send.write_all(&[0xc0, 0, 0, 0, 0, 0, 0, 0]).await?;
+    send.write_all(b"hi").await?;
+    send.finish()?;
+
+    let mut recv = timeout(WAIT, server.accept_uni()).await??;
+    assert_eq!(recv.read_to_end(2).await?, b"hi");
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! A session nobody uses is closed after the idle timeout, by whichever side has the shorter
+//! one, unless a keep-alive holds it open.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{ClientBuilder, ServerBuilder, Session, SessionError};
+
+const IDLE: Duration = Duration::from_millis(300);
+const KEEP_ALIVE: Duration = Duration::from_millis(100);
+
+/// Connect to a server with a short idle timeout, returning both ends of the session.
+async fn pair(keep_alive: Option<Duration>) -> Result<(Session, Session)> {
+    // The server's timeout is shorter, so it's the one that applies.
+    let server = ServerBuilder::new().with_idle_timeout(IDLE);
+
+    let mut client = ClientBuilder::new().with_idle_timeout(IDLE * 100);
+    if let Some(interval) = keep_alive {
+        client = client.with_keep_alive(interval);
+    }
+    let client = client.dangerous().with_no_certificate_verification()?;
+
+    common::connect(server, client).await
+}
+
+#[tokio::test]
+async fn idle_sessions_time_out() -> Result<()> {
+    let (session, _server) = pair(None).await?;
+
+    let err = tokio::time::timeout(Duration::from_secs(5), session.closed())
+        .await
+        .context("the idle timeout never fired")?;
+    assert!(
+        matches!(
+            err.without_history(),
+            SessionError::ConnectionError(quinn::ConnectionError::TimedOut)
+        ),
+        "{err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_alive_holds_idle_sessions_open() -> Result<()> {
+    let (session, _server) = pair(Some(KEEP_ALIVE)).await?;
+
+    // Several idle timeouts pass without the session closing.
+    let closed = tokio::time::timeout(IDLE * 5, session.closed()).await;
+    assert!(closed.is_err(), "closed despite the keep-alive: {closed:?}");
+
+    Ok(())
+}
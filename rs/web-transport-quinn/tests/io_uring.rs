@@ -0,0 +1,47 @@
+//! A server using io_uring for UDP I/O carries streams and datagrams like the default socket.
+
+#![cfg(all(feature = "io-uring", target_os = "linux"))]
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quinn::{ClientBuilder, ServerBuilder};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn io_uring_server_round_trips() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new().with_io_uring(true))?;
+    let url = common::url(&server)?;
+
+    let client = common::client()?;
+    let (client, request) = tokio::join!(client.connect(url), server.accept());
+    let client = client.context("connect")?;
+    let session = request.context("no request")?.ok().await?;
+
+    // Large enough to span many packets in each direction.
+    let payload = vec![0x55u8; 1024 * 1024];
+
+    let (mut send, mut recv) = client.open_bi().await?;
+    send.write_all(&payload).await?;
+    send.finish()?;
+
+    let (mut echo, mut accepted) = session.accept_bi().await?;
+    let data = accepted.read_to_end(payload.len()).await?;
+    echo.write_all(&data).await?;
+    echo.finish()?;
+
+    let echoed = tokio::time::timeout(TIMEOUT, recv.read_to_end(payload.len())).await??;
+    assert_eq!(echoed, payload);
+
+    client.send_datagram(Bytes::from_static(b"ping"))?;
+    let datagram = tokio::time::timeout(TIMEOUT, session.read_datagram()).await??;
+    assert_eq!(datagram, "ping");
+
+    Ok(())
+}
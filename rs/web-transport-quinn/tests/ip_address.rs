@@ -0,0 +1,64 @@
+//! Servers reached by IP address are verified against the certificate's IP SANs.
+
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::{Context, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use url::Url;
+use web_transport_quinn::{ClientBuilder, ClientError, Server, ServerBuilder};
+
+fn server(addr: SocketAddr, names: &[&str]) -> Result<(Server, CertificateDer<'static>)> {
+    let names: Vec<_> = names.iter().map(|name| name.to_string()).collect();
+    let key = rcgen::generate_simple_self_signed(names)?;
+    let cert = CertificateDer::from(key.cert.der().to_vec());
+    let der = rcgen::KeyPair::serialize_der(&key.signing_key);
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(der));
+
+    let server = ServerBuilder::new()
+        .with_addr(addr)
+        .with_certificate(vec![cert.clone()], key)?;
+    Ok((server, cert))
+}
+
+async fn connect(addr: SocketAddr, names: &[&str]) -> Result<Result<(), ClientError>> {
+    let (mut server, cert) = server(addr, names)?;
+    let url = Url::parse(&format!("https://{}/", server.local_addr()?))?;
+
+    tokio::spawn(async move {
+        if let Some(request) = server.accept().await {
+            let _session = request.ok().await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    let client = ClientBuilder::new().with_root_certificates(vec![cert])?;
+    let session = tokio::time::timeout(Duration::from_secs(5), client.connect(url))
+        .await
+        .context("timed out")?;
+    Ok(session.map(|_| ()))
+}
+
+#[tokio::test]
+async fn ipv4_san() -> Result<()> {
+    connect("127.0.0.1:0".parse()?, &["127.0.0.1"]).await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn ipv6_san() -> Result<()> {
+    connect("[::1]:0".parse()?, &["::1"]).await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn dns_san_does_not_match_ip() -> Result<()> {
+    let err = connect("127.0.0.1:0".parse()?, &["localhost"])
+        .await?
+        .err()
+        .context("connected without an IP SAN")?;
+    assert!(
+        matches!(err, ClientError::Connection(_)),
+        "expected a handshake failure, got {err:?}"
+    );
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! A client can make plain HTTP/3 requests before upgrading the connection to WebTransport.
+
+mod common;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::sync::mpsc;
+use url::Url;
+use web_transport_quinn::{
+    http,
+    proto::{HttpRequest, HttpResponse},
+    ServerBuilder, Session,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Upgraded {
+    response: HttpResponse,
+    client: Session,
+    server: Session,
+}
+
+// Start a server and a raw QUIC client that can send requests before any CONNECT.
+async fn serve(
+    builder: ServerBuilder,
+) -> Result<(
+    quinn::Endpoint,
+    SocketAddr,
+    mpsc::UnboundedReceiver<Session>,
+)> {
+    let (cert, key) = common::certificate()?;
+
+    let mut server = builder
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_certificate(vec![cert.clone()], key)?;
+    let addr = server.local_addr()?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if let Ok(session) = request.ok().await {
+                let _ = tx.send(session);
+            }
+        }
+    });
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert)?;
+    let mut crypto = rustls::ClientConfig::builder_with_provider(
+        web_transport_quinn::crypto::default_provider(),
+    )
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    crypto.alpn_protocols = vec![web_transport_quinn::ALPN.as_bytes().to_vec()];
+
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?;
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+    Ok((client, addr, rx))
+}
+
+// Send a GET on a fresh connection, then upgrade it to WebTransport.
+async fn get_then_upgrade(builder: ServerBuilder) -> Result<Upgraded> {
+    let (client, addr, mut accepted) = serve(builder).await?;
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+    let conn = client.connect(addr, "localhost")?.await?;
+
+    // The GET's stream comes first, so the server answers it before it sees the CONNECT.
+    let (mut send, mut recv) = conn.open_bi().await?;
+    HttpRequest::new(http::Method::GET, url.join("config")?)
+        .write(&mut send)
+        .await?;
+    send.finish()?;
+
+    let response = async { anyhow::Ok(HttpResponse::read(&mut recv).await?) };
+    let session = async { anyhow::Ok(Session::connect(conn, url).await?) };
+    let upgrade = async { tokio::try_join!(response, session) };
+    let (response, client) = tokio::time::timeout(TIMEOUT, upgrade).await??;
+
+    let server = tokio::time::timeout(TIMEOUT, accepted.recv())
+        .await?
+        .context("no session")?;
+
+    Ok(Upgraded {
+        response,
+        client,
+        server,
+    })
+}
+
+#[tokio::test]
+async fn handler_answers_before_the_connect() -> Result<()> {
+    let builder = ServerBuilder::new().with_http_handler(|request| {
+        match (&request.method, request.url.path()) {
+            (&http::Method::GET, "/config") => {
+                HttpResponse::new(http::StatusCode::OK).with_body("hello")
+            }
+            _ => HttpResponse::new(http::StatusCode::NOT_FOUND),
+        }
+    });
+
+    let upgraded = get_then_upgrade(builder).await?;
+    assert_eq!(upgraded.response.status, http::StatusCode::OK);
+    assert_eq!(upgraded.response.body, "hello");
+
+    // The connection carries a working session after the plain request.
+    let mut send = upgraded.client.open_uni().await?;
+    send.write_all(b"upgraded").await?;
+    send.finish()?;
+
+    let mut recv = tokio::time::timeout(TIMEOUT, upgraded.server.accept_uni()).await??;
+    let read = tokio::time::timeout(TIMEOUT, recv.read_to_end(16)).await??;
+    assert_eq!(read, b"upgraded");
+    assert_eq!(upgraded.server.request().url.path(), "/");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn without_a_handler_requests_are_not_implemented() -> Result<()> {
+    let upgraded = get_then_upgrade(ServerBuilder::new()).await?;
+    assert_eq!(upgraded.response.status, http::StatusCode::NOT_IMPLEMENTED);
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Sessions reserve their windows from a shared memory budget and wait when it's exhausted.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::sync::mpsc;
+use url::Url;
+use web_transport_quinn::{MemoryBudget, ServerBuilder, Session};
+
+// The least a session reserves: both windows at 64 KiB plus both datagram buffers.
+const FLOOR: usize = 4 * 64 * 1024;
+
+/// Start a server sharing `budget`, returning its URL and the sessions it accepts.
+fn server(budget: MemoryBudget) -> Result<(Url, mpsc::UnboundedReceiver<Session>)> {
+    let mut server = common::server(ServerBuilder::new().with_memory_budget(budget))?;
+    let url = common::url(&server)?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Ok(session) = request.ok().await {
+                    tx.send(session).ok();
+                }
+            });
+        }
+    });
+
+    Ok((url, rx))
+}
+
+async fn connect(url: &Url) -> Result<Session> {
+    let client = common::client()?;
+    client.connect(url.clone()).await.context("connect")
+}
+
+#[tokio::test]
+async fn session_releases_its_reservation() -> Result<()> {
+    let budget = MemoryBudget::new(1024 * 1024);
+    let (url, mut sessions) = server(budget.clone())?;
+
+    let client = connect(&url).await?;
+    let session = sessions.recv().await.context("no session")?;
+
+    // The budget is small, so the session takes all of it.
+    let used = session.memory().context("no account")?.used();
+    assert_eq!(used, budget.limit());
+    assert_eq!(budget.used(), used);
+
+    // Data still flows through the narrowed windows.
+    let payload = vec![7u8; 4 * 1024 * 1024];
+    let mut send = client.open_uni().await?;
+    let write = async {
+        send.write_all(&payload).await?;
+        anyhow::Ok(send.finish()?)
+    };
+    let read = async {
+        let mut recv = session.accept_uni().await?;
+        anyhow::Ok(recv.read_to_end(payload.len()).await?)
+    };
+    let (written, read) = tokio::join!(write, read);
+    written?;
+    assert_eq!(read?.len(), payload.len());
+
+    drop(session);
+    assert_eq!(budget.used(), 0);
+    assert_eq!(budget.peak(), budget.limit());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn respond_waits_for_budget() -> Result<()> {
+    let budget = MemoryBudget::new(FLOOR);
+    let (url, mut sessions) = server(budget.clone())?;
+
+    let _first = connect(&url).await?;
+    let first = sessions.recv().await.context("no session")?;
+    assert_eq!(budget.used(), FLOOR);
+
+    // The second session can't be accepted until the first lets go.
+    let second = tokio::spawn(async move { connect(&url).await });
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), sessions.recv())
+            .await
+            .is_err()
+    );
+
+    drop(first);
+    let _second = sessions.recv().await.context("no session")?;
+    second.await??;
+    assert_eq!(budget.used(), FLOOR);
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! A probe reports QUIC's round-trip estimate, and fails once the session is closed.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use common::pair;
+
+#[tokio::test]
+async fn probe_round_trip() -> Result<()> {
+    let (client, _server) = pair().await?;
+
+    let rtt = client.probe_rtt().await?;
+    assert!(rtt < Duration::from_secs(1), "loopback rtt was {rtt:?}");
+
+    client.close(0, b"bye");
+    assert!(client.probe_rtt().await.is_err());
+
+    Ok(())
+}
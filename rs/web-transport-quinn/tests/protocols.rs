@@ -0,0 +1,74 @@
+//! The offered and selected subprotocols are exposed, and a selection can be required.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{
+    proto::{ConnectRequest, ConnectResponse},
+    ClientBuilder, ClientError, ConnectError, ServerBuilder,
+};
+
+// Start a server that selects the client's second offered protocol, if it offered two.
+fn server() -> Result<Url> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            let response = match request.protocols.get(1) {
+                Some(protocol) => ConnectResponse::OK.with_protocol(protocol),
+                None => ConnectResponse::OK,
+            };
+            if let Ok(session) = request.respond(response).await {
+                // Hold the session open until the client hangs up.
+                tokio::spawn(async move { session.closed().await });
+            }
+        }
+    });
+
+    Ok(url)
+}
+
+#[tokio::test]
+async fn offered_and_selected() -> Result<()> {
+    let url = server()?;
+    let client = ClientBuilder::new()
+        .require_protocol(true)
+        .dangerous()
+        .with_no_certificate_verification()?;
+
+    let request = ConnectRequest::new(url).with_protocols(["a".to_string(), "b".to_string()]);
+    let session = tokio::time::timeout(Duration::from_secs(5), client.connect(request)).await??;
+    assert_eq!(session.protocols(), &["a", "b"]);
+    assert_eq!(session.protocol(), Some("b"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn required_protocol_missing() -> Result<()> {
+    let url = server()?;
+    let client = common::client()?;
+
+    // The server ignores a single offer, which is fine unless a protocol is required.
+    let request = ConnectRequest::new(url).with_protocol("a");
+    let session = client.connect(request.clone()).await?;
+    assert_eq!(session.protocols(), &["a"]);
+    assert_eq!(session.protocol(), None);
+
+    let err = client
+        .require_protocol(true)
+        .connect(request)
+        .await
+        .err()
+        .context("connected without a protocol")?;
+    assert!(
+        matches!(err, ClientError::HttpError(ConnectError::NoProtocol)),
+        "expected NoProtocol, got {err:?}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! Reading every buffered chunk at once, bounded by a total size.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use common::pair;
+
+#[tokio::test]
+async fn read_ready_chunks_drains_buffered() -> Result<()> {
+    let (client, server) = pair().await?;
+
+    let mut send = client.open_uni().await?;
+    for part in [&b"hello "[..], b"there ", b"world"] {
+        send.write_all(part).await?;
+    }
+    send.finish()?;
+
+    let mut recv = tokio::time::timeout(Duration::from_secs(5), server.accept_uni()).await??;
+
+    // Never more than asked for, even with more buffered.
+    let chunks = recv.read_ready_chunks(4).await?.context("finished early")?;
+    assert!(chunks.iter().map(|c| c.len()).sum::<usize>() <= 4);
+    let mut data: Vec<u8> = chunks.concat();
+
+    while let Some(chunks) =
+        tokio::time::timeout(Duration::from_secs(5), recv.read_ready_chunks(1024)).await??
+    {
+        assert!(!chunks.is_empty());
+        data.extend(chunks.concat());
+    }
+    assert_eq!(data, b"hello there world");
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! A server can send clients elsewhere with a 3xx, and clients follow only when asked to.
+
+mod common;
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{ClientBuilder, ClientError, ConnectError, ErrorKind, ServerBuilder};
+
+/// The blue server redirects every session to green, which accepts it.
+async fn blue_green() -> Result<(Url, Url)> {
+    let mut blue = common::server(ServerBuilder::new())?;
+    let mut green = common::server(ServerBuilder::new())?;
+
+    let blue_url = common::url(&blue)?;
+    let green_url = common::url(&green)?;
+
+    let location = green_url.clone();
+    tokio::spawn(async move {
+        while let Some(request) = blue.accept().await {
+            request.redirect(location.clone()).await.ok();
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(request) = green.accept().await {
+            if let Ok(session) = request.ok().await {
+                session.closed().await;
+            }
+        }
+    });
+
+    Ok((blue_url, green_url))
+}
+
+#[tokio::test]
+async fn follows_redirect() -> Result<()> {
+    let (blue, green) = blue_green().await?;
+
+    let client = ClientBuilder::new()
+        .with_follow_redirects(1)
+        .dangerous()
+        .with_no_certificate_verification()?;
+
+    let session = client.connect(blue).await.context("connect")?;
+    assert_eq!(session.request().url, green);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn redirect_is_an_error_by_default() -> Result<()> {
+    let (blue, green) = blue_green().await?;
+
+    let client = ClientBuilder::new()
+        .dangerous()
+        .with_no_certificate_verification()?;
+
+    let err = client.connect(blue).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Rejected);
+
+    match err {
+        ClientError::HttpError(ConnectError::Redirect { status, location }) => {
+            assert_eq!(status, http::StatusCode::FOUND);
+            assert_eq!(location, green);
+        }
+        err => panic!("expected a redirect, got {err:?}"),
+    }
+
+    Ok(())
+}
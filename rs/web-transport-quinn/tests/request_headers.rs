@@ -0,0 +1,42 @@
+//! Headers sent with the CONNECT request reach the server's `Request`, repeated ones included.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{http, proto::ConnectRequest, ServerBuilder};
+
+#[tokio::test]
+async fn server_sees_request_headers() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Authorize the session from its headers, as an application would.
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        let cookies: Vec<String> = request
+            .headers
+            .get_all(http::header::COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap().to_string())
+            .collect();
+
+        let session = match request.headers.get(http::header::AUTHORIZATION) {
+            Some(token) if token == "Bearer secret" => request.ok().await?,
+            _ => anyhow::bail!("unauthorized"),
+        };
+        anyhow::Ok((session, cookies))
+    });
+
+    let request = ConnectRequest::new(url)
+        .with_header(http::header::AUTHORIZATION, "Bearer secret".parse()?)
+        .with_header(http::header::COOKIE, "a=1".parse()?)
+        .with_header(http::header::COOKIE, "b=2".parse()?);
+
+    let _client = common::client()?.connect(request).await?;
+    let (_server, cookies) = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+    assert_eq!(cookies, ["a=1", "b=2"]);
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! A client sees the headers the server rejected it with, so it can authenticate and retry.
+
+mod common;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{
+    http,
+    proto::{ConnectRequest, ConnectResponse},
+    ClientError, ConnectError, ServerBuilder,
+};
+
+#[tokio::test]
+async fn rejection_carries_headers() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Ask for a bearer token, and accept any request that has one.
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if request.headers.contains_key(http::header::AUTHORIZATION) {
+                if let Ok(session) = request.ok().await {
+                    tokio::spawn(async move { session.closed().await });
+                }
+            } else {
+                let response = ConnectResponse::new(http::StatusCode::UNAUTHORIZED).with_header(
+                    http::header::WWW_AUTHENTICATE,
+                    http::HeaderValue::from_static("Bearer realm=\"moq\""),
+                );
+                request.reject(response).await.ok();
+            }
+        }
+    });
+
+    let client = common::client()?;
+
+    let err = client
+        .connect(url.clone())
+        .await
+        .err()
+        .context("connected without credentials")?;
+    let ClientError::HttpError(ConnectError::Rejected { status, headers }) = err else {
+        panic!("expected a 401, got {err:?}");
+    };
+    assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        headers.get(http::header::WWW_AUTHENTICATE),
+        Some(&http::HeaderValue::from_static("Bearer realm=\"moq\""))
+    );
+
+    let request = ConnectRequest::new(url).with_header(
+        http::header::AUTHORIZATION,
+        http::HeaderValue::from_static("Bearer secret"),
+    );
+    let _session = client.connect(request).await?;
+
+    Ok(())
+}
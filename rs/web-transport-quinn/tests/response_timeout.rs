@@ -0,0 +1,74 @@
+//! A request can be answered away from the accept loop, but not after the response timeout.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::sync::mpsc;
+use web_transport_quinn::{Request, ServerBuilder, ServerError, Session};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+// Start a server that hands each request to a queue, and a client connecting to it.
+async fn queued() -> Result<(
+    mpsc::Receiver<Request>,
+    tokio::task::JoinHandle<Result<Session>>,
+)> {
+    let mut server = common::server(ServerBuilder::new().with_response_timeout(Some(TIMEOUT)))?;
+    let url = common::url(&server)?;
+
+    // The accept loop only queues requests, so it never waits on a decision.
+    let (queue, requests) = mpsc::channel(8);
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if queue.send(request).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let client = tokio::spawn(async move {
+        let client = common::client()?;
+        anyhow::Ok(client.connect(url).await?)
+    });
+
+    Ok((requests, client))
+}
+
+#[tokio::test]
+async fn answered_in_time() -> Result<()> {
+    let (mut requests, client) = queued().await?;
+
+    let request = requests.recv().await.context("no request")?;
+    let server = tokio::spawn(async move { request.ok().await });
+
+    let client = tokio::time::timeout(Duration::from_secs(5), client).await???;
+    let server = server.await??;
+
+    // The session outlives the timeout.
+    tokio::time::sleep(TIMEOUT * 2).await;
+    assert!(client.close_reason().is_none());
+    assert!(server.close_reason().is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_after_timeout() -> Result<()> {
+    let (mut requests, client) = queued().await?;
+
+    let request = requests.recv().await.context("no request")?;
+    tokio::time::sleep(TIMEOUT * 2).await;
+
+    let err = request.ok().await.unwrap_err();
+    assert!(
+        matches!(err, ServerError::RequestTimeout),
+        "expected RequestTimeout, got {err:?}"
+    );
+
+    let res = tokio::time::timeout(Duration::from_secs(5), client).await??;
+    assert!(res.is_err(), "client connected to a rejected request");
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! A resumption token minted on accept links a later session back to its state.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{
+    proto::{ConnectRequest, ConnectResponse, ResumptionStore},
+    ServerBuilder,
+};
+
+#[tokio::test]
+async fn resume_session() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Count the sessions linked together by each token.
+    let accepted = tokio::spawn(async move {
+        let store = ResumptionStore::default();
+        let mut resumed = Vec::new();
+        let mut sessions = Vec::new();
+
+        for _ in 0..2 {
+            let request = server.accept().await.context("no request")?;
+            let count = match request.resumption_token() {
+                Some(token) => store.redeem(&token).context("unknown token")? + 1,
+                None => 0,
+            };
+            resumed.push(count);
+
+            let token = store.mint(count);
+            let response = ConnectResponse::OK.with_resumption_token(token);
+            sessions.push(request.respond(response).await?);
+        }
+
+        // Return the sessions too, so they aren't dropped before the client reads the response.
+        anyhow::Ok((resumed, sessions))
+    });
+
+    let client = common::client()?;
+
+    let first = client.connect(url.clone()).await?;
+    let token = first
+        .response()
+        .resumption_token
+        .clone()
+        .context("no resumption token")?;
+    first.close(0, b"network change");
+
+    let request = ConnectRequest::new(url).with_resumption_token(&token);
+    let second = client.connect(request).await?;
+    assert!(second.response().resumption_token.is_some());
+    assert_ne!(second.response().resumption_token, Some(token));
+
+    let (resumed, _sessions) = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+    assert_eq!(resumed, vec![0, 1]);
+
+    Ok(())
+}
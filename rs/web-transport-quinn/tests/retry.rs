@@ -0,0 +1,90 @@
+//! Clients can retry a connection the server refused for now, and only that.
+
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{proto::RetryPolicy, ClientError, ConnectError, ServerBuilder};
+
+const POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    initial_backoff: Duration::from_millis(10),
+    max_backoff: Duration::from_millis(50),
+    jitter: 0.5,
+};
+
+/// A server that answers the first `refusals` requests with `status`, and accepts the rest.
+///
+/// Returns its URL and the number of requests it has seen.
+fn server(status: http::StatusCode, refusals: usize) -> Result<(Url, Arc<AtomicUsize>)> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counter = seen.clone();
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if counter.fetch_add(1, Ordering::SeqCst) < refusals {
+                request.reject(status).await.ok();
+            } else if let Ok(session) = request.ok().await {
+                session.closed().await;
+            }
+        }
+    });
+
+    Ok((url, seen))
+}
+
+#[tokio::test]
+async fn retries_until_the_server_has_room() -> Result<()> {
+    let (url, seen) = server(http::StatusCode::SERVICE_UNAVAILABLE, 2)?;
+
+    common::client()?
+        .connect_with_retry(url, POLICY)
+        .await
+        .context("the third attempt should have been accepted")?;
+    assert_eq!(seen.load(Ordering::SeqCst), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() -> Result<()> {
+    let (url, seen) = server(http::StatusCode::SERVICE_UNAVAILABLE, usize::MAX)?;
+
+    let err = common::client()?
+        .connect_with_retry(url, POLICY)
+        .await
+        .unwrap_err();
+    assert!(err.is_retryable(), "{err:?}");
+    assert_eq!(seen.load(Ordering::SeqCst), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn does_not_retry_a_refusal_that_would_repeat() -> Result<()> {
+    let (url, seen) = server(http::StatusCode::FORBIDDEN, usize::MAX)?;
+
+    let err = common::client()?
+        .connect_with_retry(url, POLICY)
+        .await
+        .unwrap_err();
+    match err {
+        ClientError::HttpError(ConnectError::Rejected { status, .. }) => {
+            assert_eq!(status, http::StatusCode::FORBIDDEN);
+        }
+        err => panic!("expected a 403, got {err:?}"),
+    }
+    assert_eq!(seen.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
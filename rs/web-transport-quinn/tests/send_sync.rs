@@ -0,0 +1,28 @@
+//! The public types stay usable from multi-threaded generic code.
+//!
+//! `web-transport-trait` only asks for `MaybeSend`/`MaybeSync`, which are `Send`/`Sync` on
+//! native targets. Asserting them here catches a field that quietly makes a type `!Send`
+//! (an `Rc`, a non-`Send` boxed future) at compile time, before it breaks a downstream spawn.
+//! The traits return `impl Future`, so they aren't dyn-compatible and there's no object to check.
+
+use static_assertions::assert_impl_all;
+use web_transport_quinn::{
+    ReadError, RecvStream, Request, SendStream, Session, SessionDriver, SessionError, WriteError,
+};
+
+assert_impl_all!(Session: web_transport_trait::Session, Send, Sync, Clone);
+assert_impl_all!(SendStream: web_transport_trait::SendStream, Send);
+assert_impl_all!(RecvStream: web_transport_trait::RecvStream, Send);
+assert_impl_all!(SessionError: web_transport_trait::Error, Send, Sync);
+assert_impl_all!(WriteError: web_transport_trait::Error, Send, Sync);
+assert_impl_all!(ReadError: web_transport_trait::Error, Send, Sync);
+
+// A request can be queued and answered from another task.
+assert_impl_all!(Request: Send);
+
+// A driver can be run on any executor, including a multi-threaded one.
+assert_impl_all!(SessionDriver: std::future::Future, Send);
+
+// Streams plug into both tokio's and futures' I/O utilities.
+assert_impl_all!(SendStream: tokio::io::AsyncWrite, futures::io::AsyncWrite, Unpin);
+assert_impl_all!(RecvStream: tokio::io::AsyncRead, futures::io::AsyncRead, Unpin);
@@ -0,0 +1,169 @@
+//! One server presenting a different certificate for each hostname, picked by SNI,
+//! and the request telling the server which name the client asked for.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair, KeyUsagePurpose};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    server::ResolvesServerCertUsingSni,
+    sign::CertifiedKey,
+};
+use tokio::sync::mpsc;
+use url::Url;
+use web_transport_quinn::{crypto, Server, ServerBuilder, Session};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A CA that issues a certificate per hostname, so the client trusts every one of them.
+struct Ca {
+    root: CertificateDer<'static>,
+    params: CertificateParams,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Result<Self> {
+        let key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::new())?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "test CA");
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign];
+        let cert = params.self_signed(&key)?;
+
+        Ok(Self {
+            root: CertificateDer::from(cert.der().to_vec()),
+            params,
+            key,
+        })
+    }
+
+    fn issue(&self, name: &str) -> Result<CertifiedKey> {
+        let key = KeyPair::generate()?;
+        let params = CertificateParams::new(vec![name.into()])?;
+        let cert = params.signed_by(&key, &Issuer::from_params(&self.params, &self.key))?;
+
+        let chain = vec![CertificateDer::from(cert.der().to_vec())];
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+        Ok(CertifiedKey::from_der(
+            chain,
+            key,
+            &crypto::default_provider(),
+        )?)
+    }
+}
+
+/// What the server saw of a request before responding to it.
+struct Seen {
+    server_name: Option<String>,
+    peer_addr: SocketAddr,
+    local_ip: Option<IpAddr>,
+    _session: Session,
+}
+
+/// Accept and respond to every request in the background, reporting what the server saw.
+fn serve(mut server: Server) -> mpsc::UnboundedReceiver<Seen> {
+    let (seen, requests) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            let server_name = request.server_name();
+            let peer_addr = request.peer_addr();
+            let local_ip = request.local_ip();
+            let Ok(session) = request.ok().await else {
+                continue;
+            };
+
+            let report = Seen {
+                server_name,
+                peer_addr,
+                local_ip,
+                _session: session,
+            };
+            if seen.send(report).is_err() {
+                break;
+            }
+        }
+    });
+    requests
+}
+
+/// Connect to `addr` with `name` as the SNI, returning the leaf certificate the server presented.
+async fn presented(
+    ca: &Ca,
+    requests: &mut mpsc::UnboundedReceiver<Seen>,
+    addr: SocketAddr,
+    name: &str,
+) -> Result<CertificateDer<'static>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(ca.root.clone())?;
+    let mut config = rustls::ClientConfig::builder_with_provider(crypto::default_provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![web_transport_quinn::ALPN.as_bytes().to_vec()];
+
+    let config = quinn::crypto::rustls::QuicClientConfig::try_from(config)?;
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(config)));
+
+    let conn = tokio::time::timeout(TIMEOUT, client.connect(addr, name)?).await??;
+    let url = Url::parse(&format!("https://{name}:{}/", addr.port()))?;
+
+    let session = tokio::time::timeout(TIMEOUT, Session::connect(conn, url))
+        .await?
+        .context("connect")?;
+    let seen = tokio::time::timeout(TIMEOUT, requests.recv())
+        .await?
+        .context("no request")?;
+
+    // The server can see who the client asked for, and from where, before responding.
+    assert_eq!(seen.server_name.as_deref(), Some(name));
+    assert_eq!(seen.peer_addr, client.local_addr()?);
+    if let Some(ip) = seen.local_ip {
+        assert_eq!(ip, addr.ip());
+    }
+
+    let chain = session
+        .peer_certificates()
+        .context("no server certificate")?;
+    chain.into_iter().next().context("empty chain")
+}
+
+#[tokio::test]
+async fn picks_the_certificate_by_server_name() -> Result<()> {
+    let ca = Ca::new()?;
+    let first = ca.issue("one.example")?;
+    let second = ca.issue("two.example")?;
+    let (first_leaf, second_leaf) = (first.cert[0].clone(), second.cert[0].clone());
+
+    let mut resolver = ResolvesServerCertUsingSni::new();
+    resolver.add("one.example", first)?;
+    resolver.add("two.example", second)?;
+
+    let server = ServerBuilder::new()
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_cert_resolver(Arc::new(resolver))?;
+    let addr = server.local_addr()?;
+    let mut requests = serve(server);
+
+    let leaf = presented(&ca, &mut requests, addr, "one.example").await?;
+    assert_eq!(leaf, first_leaf);
+
+    let leaf = presented(&ca, &mut requests, addr, "two.example").await?;
+    assert_eq!(leaf, second_leaf);
+    assert_ne!(first_leaf, second_leaf);
+
+    // A name without a certificate fails the handshake.
+    assert!(presented(&ca, &mut requests, addr, "three.example")
+        .await
+        .is_err());
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! A driven session does its background work in the returned driver, not on spawned tasks.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{ServerBuilder, SessionError, WebTransportError};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn driver_reads_the_close() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        request.ok_driven().await.context("accept")
+    });
+
+    let client = common::client()?.connect(url).await?;
+    let (server, driver) = tokio::time::timeout(TIMEOUT, accepted).await???;
+
+    // Run the driver on a local set, as an application pinning it to one thread would.
+    let local = tokio::task::LocalSet::new();
+    let driver = local.spawn_local(driver);
+
+    local
+        .run_until(async {
+            // Only the driver reads the CLOSE_WEBTRANSPORT_SESSION capsule.
+            client.close(7, b"bye");
+            let err = tokio::time::timeout(TIMEOUT, server.closed()).await?;
+            assert!(
+                matches!(
+                    err.without_history(),
+                    SessionError::WebTransportError(WebTransportError::Closed(7, _))
+                ),
+                "unexpected error: {err:?}"
+            );
+
+            // With the session gone, the driver has nothing left to do.
+            drop(server);
+            tokio::time::timeout(TIMEOUT, driver).await??;
+            anyhow::Ok(())
+        })
+        .await
+}
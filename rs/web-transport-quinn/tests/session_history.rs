@@ -0,0 +1,85 @@
+//! A session keeps its most recent events, and attaches them to the close error.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{
+    proto::{Capsule, SessionEventKind},
+    ClientBuilder, ServerBuilder, SessionError, WebTransportError,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn history_is_attached_to_the_close_error() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Close the session once the client's stream arrives.
+    let accepted = tokio::spawn(async move {
+        let session = server.accept().await.context("no request")?.ok().await?;
+        let mut recv = session.accept_uni().await?;
+        recv.read_to_end(16).await?;
+        session.close(7, b"bye");
+        session.closed().await;
+        anyhow::Ok(())
+    });
+
+    let client = ClientBuilder::new()
+        .with_session_history(32)
+        .dangerous()
+        .with_no_certificate_verification()?
+        .connect(url)
+        .await?;
+
+    let mut send = client.open_uni().await?;
+    send.write_all(b"hello").await?;
+    send.finish()?;
+
+    let err = tokio::time::timeout(TIMEOUT, client.closed()).await?;
+    tokio::time::timeout(TIMEOUT, accepted).await???;
+
+    // The error still matches as the peer's close, underneath the history.
+    assert!(
+        matches!(
+            err.without_history(),
+            SessionError::WebTransportError(WebTransportError::Closed(7, _))
+        ),
+        "expected a close with code 7, got {err:?}"
+    );
+
+    let SessionError::WithHistory(_, events) = &err else {
+        anyhow::bail!("no history attached: {err:?}");
+    };
+    assert_eq!(events.len(), client.history().len());
+
+    // Flow control capsules may come in between, so only check the order.
+    let opened = events
+        .iter()
+        .position(|event| {
+            matches!(
+                event.kind,
+                SessionEventKind::StreamOpened { bidi: false, .. }
+            )
+        })
+        .context("stream open not recorded")?;
+    let closed = events
+        .iter()
+        .position(|event| {
+            matches!(
+                event.kind,
+                SessionEventKind::CapsuleReceived(Capsule::CloseWebTransportSession {
+                    code: 7,
+                    ..
+                })
+            )
+        })
+        .context("close capsule not recorded")?;
+    assert!(opened < closed, "events out of order: {events:?}");
+    assert!(events.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+    assert!(format!("{err:?}").contains("StreamOpened"));
+
+    Ok(())
+}
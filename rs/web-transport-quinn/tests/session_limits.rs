@@ -0,0 +1,66 @@
+//! Sessions over a path's limit are rejected with 503 until a slot frees up.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{proto::SessionLimits, ClientError, ConnectError, ServerBuilder};
+
+#[tokio::test]
+async fn sessions_are_limited_per_path() -> Result<()> {
+    let limits = SessionLimits::new().with_limit("/broadcast", 1);
+    let mut server = common::server(ServerBuilder::new().with_session_limits(limits.clone()))?;
+    let base = Url::parse(&format!(
+        "https://localhost:{}/",
+        server.local_addr()?.port()
+    ))?;
+
+    // Accept everything the limits let through, handing the sessions to the test.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(request) = server.accept().await {
+            if let Ok(session) = request.ok().await {
+                let _ = tx.send(session);
+            }
+        }
+    });
+
+    let client = common::client()?;
+
+    let broadcast = base.join("/broadcast")?;
+    let first = client.connect(broadcast.clone()).await?;
+    let accepted = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await?
+        .context("no session")?;
+    assert_eq!(limits.active("/broadcast"), 1);
+
+    let err = client
+        .connect(broadcast.clone())
+        .await
+        .err()
+        .context("connected over the limit")?;
+    assert!(
+        matches!(
+            err,
+            ClientError::HttpError(ConnectError::Rejected { status, .. })
+                if status == http::StatusCode::SERVICE_UNAVAILABLE
+        ),
+        "expected 503, got {err:?}"
+    );
+
+    // Other paths have no limit.
+    let _other = client.connect(base.join("/other")?).await?;
+    let _other_accepted = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await?;
+
+    // Dropping the accepted session frees its slot.
+    accepted.close(0, b"bye");
+    drop(accepted);
+    drop(first);
+    assert_eq!(limits.active("/broadcast"), 0);
+
+    let _again = client.connect(broadcast).await?;
+
+    Ok(())
+}
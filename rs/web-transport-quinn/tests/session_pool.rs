@@ -0,0 +1,161 @@
+//! A session pool accepts several sessions over one connection, routing each its own streams.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use url::Url;
+use web_transport_quinn::{
+    proto::{ConnectRequest, ConnectResponse, Frame, VarInt},
+    quinn, ServerBuilder, Session, SessionPool,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Connect a client session to a server that pools up to `max_sessions` on its connection.
+// Returns the client, the pool, and the server's end of that first session.
+async fn pair(max_sessions: u32) -> Result<(Session, SessionPool, Session, Url)> {
+    let server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    // Accept the QUIC connection directly, rather than a single session on it.
+    let pool = tokio::spawn(async move {
+        let incoming = (*server).accept().await.context("no connection")?;
+        anyhow::Ok(SessionPool::new(incoming.await?, max_sessions).await?)
+    });
+
+    // The client waits for the response, so connect in the background.
+    let client = tokio::spawn({
+        let url = url.join("a")?;
+        async move { anyhow::Ok(common::client()?.connect(url).await?) }
+    });
+
+    let mut pool = tokio::time::timeout(TIMEOUT, pool).await???;
+    let request = tokio::time::timeout(TIMEOUT, pool.accept())
+        .await?
+        .context("no request")?;
+    assert_eq!(request.url.path(), "/a");
+    let a = request.ok().await?;
+    let client = tokio::time::timeout(TIMEOUT, client).await???;
+
+    Ok((client, pool, a, url))
+}
+
+// Our client only supports one session per connection, so send another CONNECT by hand.
+async fn connect_raw(
+    conn: &quinn::Connection,
+    url: Url,
+) -> Result<(quinn::SendStream, quinn::RecvStream, ConnectResponse)> {
+    let (mut send, mut recv) = conn.open_bi().await?;
+    ConnectRequest::new(url).write(&mut send).await?;
+    let response = ConnectResponse::read_any_status(&mut recv).await?;
+    Ok((send, recv, response))
+}
+
+fn session_id(connect: &quinn::SendStream) -> VarInt {
+    let id = quinn::VarInt::from(connect.id()).into_inner();
+    VarInt::try_from(id).unwrap()
+}
+
+#[tokio::test]
+async fn routes_streams_and_datagrams_by_session() -> Result<()> {
+    let (client, mut pool, a, url) = pair(2).await?;
+    let conn = (*client).clone();
+
+    let connect = tokio::spawn({
+        let conn = conn.clone();
+        let url = url.join("b")?;
+        async move { connect_raw(&conn, url).await }
+    });
+    let request = tokio::time::timeout(TIMEOUT, pool.accept())
+        .await?
+        .context("no request")?;
+    assert_eq!(request.url.path(), "/b");
+    let b = request.ok().await?;
+
+    let (connect_b, _recv, response) = connect.await??;
+    assert_eq!(response.status, http::StatusCode::OK);
+    let id_b = session_id(&connect_b);
+
+    // Open a stream on each session, the second by writing its header by hand.
+    let (mut send, _) = client.open_bi().await?;
+    send.write_all(b"to a").await?;
+    send.finish()?;
+
+    let (mut send, _) = conn.open_bi().await?;
+    let mut header = Vec::new();
+    Frame::WEBTRANSPORT.encode(&mut header);
+    id_b.encode(&mut header);
+    send.write_all(&header).await?;
+    send.write_all(b"to b").await?;
+    send.finish()?;
+
+    let (_, mut recv) = tokio::time::timeout(TIMEOUT, b.accept_bi()).await??;
+    assert_eq!(recv.read_to_end(16).await?, b"to b");
+    let (_, mut recv) = tokio::time::timeout(TIMEOUT, a.accept_bi()).await??;
+    assert_eq!(recv.read_to_end(16).await?, b"to a");
+
+    // Datagrams are routed by their session ID prefix too.
+    let mut datagram = Vec::new();
+    id_b.encode(&mut datagram);
+    datagram.extend_from_slice(b"dgram b");
+    conn.send_datagram(datagram.into())?;
+    client.send_datagram(Bytes::from_static(b"dgram a"))?;
+
+    let datagram = tokio::time::timeout(TIMEOUT, b.read_datagram()).await??;
+    assert_eq!(datagram, "dgram b");
+    let datagram = tokio::time::timeout(TIMEOUT, a.read_datagram()).await??;
+    assert_eq!(datagram, "dgram a");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn closing_one_session_keeps_the_others() -> Result<()> {
+    let (client, mut pool, a, url) = pair(2).await?;
+    let conn = (*client).clone();
+
+    let connect = tokio::spawn({
+        let conn = conn.clone();
+        let url = url.join("b")?;
+        async move { connect_raw(&conn, url).await }
+    });
+    let request = tokio::time::timeout(TIMEOUT, pool.accept())
+        .await?
+        .context("no request")?;
+    let b = request.ok().await?;
+    let (mut connect_b, mut recv_b, _) = connect.await??;
+
+    // The server closes session B, and the client answers by finishing its CONNECT stream.
+    b.close(7, b"bye");
+    let capsules = tokio::time::timeout(TIMEOUT, recv_b.read_to_end(1024)).await??;
+    assert!(!capsules.is_empty());
+    connect_b.finish()?;
+
+    tokio::time::timeout(TIMEOUT, b.closed()).await?;
+    assert!(b.close_reason().is_some());
+    assert!(b.open_bi().await.is_err());
+
+    // Session A and the connection are unaffected.
+    assert!(a.close_reason().is_none());
+    let mut send = client.open_uni().await?;
+    send.write_all(b"still here").await?;
+    send.finish()?;
+    let mut recv = tokio::time::timeout(TIMEOUT, a.accept_uni()).await??;
+    assert_eq!(recv.read_to_end(16).await?, b"still here");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejects_sessions_over_the_limit() -> Result<()> {
+    let (client, _pool, _a, url) = pair(1).await?;
+    let conn = (*client).clone();
+
+    let (_send, _recv, response) = tokio::time::timeout(TIMEOUT, connect_raw(&conn, url)).await??;
+    assert_eq!(response.status, http::StatusCode::TOO_MANY_REQUESTS);
+
+    Ok(())
+}
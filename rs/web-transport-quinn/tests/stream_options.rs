@@ -0,0 +1,46 @@
+//! Streams opened with `StreamOptions` start at the requested priority.
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use web_transport_quinn::{generic, ServerBuilder, StreamOptions};
+
+#[tokio::test]
+async fn streams_open_at_their_priority() -> Result<()> {
+    let mut server = common::server(ServerBuilder::new())?;
+    let url = common::url(&server)?;
+
+    let accepted = tokio::spawn(async move {
+        let request = server.accept().await.context("no request")?;
+        anyhow::Ok(request.ok().await?)
+    });
+
+    let client = common::client()?.connect(url).await?;
+    let _server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    let send = client
+        .open_uni_with(StreamOptions::default().with_priority(7))
+        .await?;
+    assert_eq!(send.priority()?, 7);
+
+    // The send order wins over the priority, and is clamped to quinn's range.
+    let options = StreamOptions::default()
+        .with_priority(7)
+        .with_send_order(i64::MAX);
+    let (send, _recv) = client.open_bi_with(options).await?;
+    assert_eq!(send.priority()?, i32::MAX);
+
+    // Without options, the stream keeps the default once its header is written.
+    let send = client.open_uni_with(StreamOptions::default()).await?;
+    assert_eq!(send.priority()?, 0);
+
+    // Through the trait too.
+    let send =
+        generic::Session::open_uni_with(&client, StreamOptions::default().with_send_order(-3))
+            .await?;
+    assert_eq!(send.priority()?, -3);
+
+    Ok(())
+}
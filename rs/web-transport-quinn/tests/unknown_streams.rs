@@ -0,0 +1,149 @@
+//! Unidirectional streams of an unknown type are counted, and can close the connection.
+//! Push streams, which a WebTransport endpoint never allows, always do.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{
+    generic::Stats,
+    proto::{self, StreamUni, UnknownStreamPolicy, VarInt},
+    ServerBuilder, Session,
+};
+
+const UNKNOWN: StreamUni = StreamUni(VarInt::from_u32(0x7f));
+
+// Start a server and connect to it, returning both sessions.
+async fn pair(policy: UnknownStreamPolicy) -> Result<(Session, Session)> {
+    let (cert, key) = common::certificate()?;
+
+    let mut server = ServerBuilder::new()
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_certificate(vec![cert.clone()], key)?;
+    let addr = server.local_addr()?;
+
+    let accepted = tokio::spawn(async move {
+        let session = server.accept().await.context("no request")?.ok().await?;
+        session.set_unknown_stream_policy(policy);
+        anyhow::Ok(session)
+    });
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert)?;
+    let mut crypto = rustls::ClientConfig::builder_with_provider(
+        web_transport_quinn::crypto::default_provider(),
+    )
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    crypto.alpn_protocols = vec![web_transport_quinn::ALPN.as_bytes().to_vec()];
+
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?;
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+    let url = Url::parse(&format!("https://localhost:{}/", addr.port()))?;
+    let conn = client.connect(addr, "localhost")?.await?;
+    let client = Session::connect(conn, url).await?;
+    let server = tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    Ok((server, client))
+}
+
+#[tokio::test]
+async fn counts_ignored_streams() -> Result<()> {
+    let (server, client) = pair(UnknownStreamPolicy::Warn).await?;
+    assert_eq!(server.stats().ignored_uni_streams(), Some(0));
+
+    let mut unknown = Vec::new();
+    for _ in 0..3 {
+        unknown.push(client.open_raw_uni(UNKNOWN).await?);
+    }
+    client.open_uni().await?.write_all(b"hi").await?;
+
+    let mut recv = server.accept_uni().await?;
+    assert_eq!(recv.read_to_end(2).await?, b"hi");
+
+    // Keep accepting in the background so the remaining headers get decoded.
+    let background = server.clone();
+    tokio::spawn(async move { background.accept_uni().await });
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while server.stats().ignored_uni_streams() != Some(3) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn drops_streams_without_an_accept_call() -> Result<()> {
+    let (server, client) = pair(UnknownStreamPolicy::Ignore).await?;
+
+    let mut unknown = Vec::new();
+    for _ in 0..2 {
+        unknown.push(client.open_raw_uni(UNKNOWN).await?);
+    }
+
+    // Headers are decoded as streams arrive, not when the application asks for one.
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while server.stats().ignored_uni_streams() != Some(2) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn closes_after_threshold() -> Result<()> {
+    let (server, client) = pair(UnknownStreamPolicy::CloseAfter(1)).await?;
+    tokio::spawn(async move { server.accept_uni().await });
+
+    let mut unknown = Vec::new();
+    for _ in 0..2 {
+        unknown.push(client.open_raw_uni(UNKNOWN).await?);
+    }
+
+    let err =
+        tokio::time::timeout(Duration::from_secs(5), quinn::Connection::closed(&client)).await?;
+    match err {
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(
+                close.error_code.into_inner(),
+                proto::codes::h3::STREAM_CREATION_ERROR
+            );
+        }
+        err => anyhow::bail!("expected an application close, got {err:?}"),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejects_push_streams() -> Result<()> {
+    let (server, client) = pair(UnknownStreamPolicy::Ignore).await?;
+    tokio::spawn(async move { server.accept_uni().await });
+
+    // Only servers may push, so a push stream from the client closes the connection.
+    let _push = client.open_raw_uni(StreamUni::PUSH).await?;
+
+    let err =
+        tokio::time::timeout(Duration::from_secs(5), quinn::Connection::closed(&client)).await?;
+    match err {
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(
+                close.error_code.into_inner(),
+                proto::codes::h3::STREAM_CREATION_ERROR
+            );
+        }
+        err => anyhow::bail!("expected an application close, got {err:?}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! CONNECT requests sent as 0-RTT data are classified before they're accepted.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use url::Url;
+use web_transport_quinn::{ServerBuilder, Session};
+
+#[tokio::test]
+async fn unsafe_request_waits_for_confirmation() -> Result<()> {
+    let (cert, key) = common::certificate()?;
+
+    let mut server = ServerBuilder::new()
+        .with_addr("127.0.0.1:0".parse()?)
+        .with_0rtt(|request| request.url.path() == "/safe")
+        .with_certificate(vec![cert.clone()], key)?;
+    let addr = server.local_addr()?;
+
+    let accepted = tokio::spawn(async move {
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let request = server.accept().await.context("no request")?;
+            assert!(!request.is_0rtt(), "unsafe requests are confirmed first");
+
+            let session = request.ok().await?;
+            assert!(!session.is_0rtt());
+            session.open_uni().await?.write_all(b"hi").await?;
+            sessions.push(session);
+        }
+        anyhow::Ok(sessions)
+    });
+
+    // A raw client that keeps its TLS session cache around, so it can resume with 0-RTT.
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert)?;
+    let mut crypto = rustls::ClientConfig::builder_with_provider(
+        web_transport_quinn::crypto::default_provider(),
+    )
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    crypto.alpn_protocols = vec![web_transport_quinn::ALPN.as_bytes().to_vec()];
+    crypto.enable_early_data = true;
+
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?;
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+    let url = Url::parse(&format!("https://localhost:{}/unsafe", addr.port()))?;
+
+    // The first connection performs a full handshake and receives a session ticket.
+    let conn = client.connect(addr, "localhost")?.await?;
+    let first = Session::connect(conn, url.clone()).await?;
+    first.accept_uni().await?.read_to_end(2).await?;
+
+    // The second resumes and sends SETTINGS and CONNECT as 0-RTT data.
+    let (conn, early) = client
+        .connect(addr, "localhost")?
+        .into_0rtt()
+        .ok()
+        .context("no 0-RTT keys")?;
+    let second = Session::connect(conn, url).await?;
+    second.accept_uni().await?.read_to_end(2).await?;
+    assert!(early.await, "the server accepted the 0-RTT data");
+
+    tokio::time::timeout(Duration::from_secs(5), accepted).await???;
+
+    Ok(())
+}
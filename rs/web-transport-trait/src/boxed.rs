@@ -0,0 +1,699 @@
+//! Type-erased sessions and streams, for storing any backend behind a single type.
+//!
+//! [Session] and the stream traits return `impl Future`, so they can't be used as trait
+//! objects. [Session::boxed] wraps a session in a [BoxedSession] instead, which implements
+//! the same traits with boxed futures, streams and errors. It costs an allocation per call,
+//! but a library can hold `BoxedSession` without being generic over the backend.
+//!
+//! ```
+//! # use web_transport_trait::{boxed::BoxedSession, Session};
+//! struct Peer {
+//!     session: BoxedSession,
+//! }
+//!
+//! fn peer(session: impl Session) -> Peer {
+//!     Peer {
+//!         session: session.boxed(),
+//!     }
+//! }
+//! ```
+
+use std::{fmt, future::Future, net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+
+use crate::{
+    Closed, DatagramOptions, Error, ErrorKind, MaybeSend, MaybeSync, RecvStream, SendStream,
+    Session, SessionId, Stats, StreamOptions,
+};
+
+/// A pinned, boxed future, which is `Send` on native targets.
+#[cfg(not(target_family = "wasm"))]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A pinned, boxed future, which is `Send` on native targets.
+#[cfg(target_family = "wasm")]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+// An object-safe [Error] that can still be downcast to the backend's error.
+trait DynError: Error {
+    fn as_std(&self) -> &(dyn std::error::Error + 'static);
+}
+
+impl<E: Error> DynError for E {
+    fn as_std(&self) -> &(dyn std::error::Error + 'static) {
+        self
+    }
+}
+
+/// An error from any backend, returned by the boxed session and streams.
+pub struct BoxedError(Box<dyn DynError>);
+
+impl BoxedError {
+    pub fn new(err: impl Error) -> Self {
+        Self(Box::new(err))
+    }
+
+    /// Returns the backend's error if it's of type `E`.
+    pub fn downcast_ref<E: Error>(&self) -> Option<&E> {
+        self.0.as_std().downcast_ref()
+    }
+}
+
+impl fmt::Debug for BoxedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.0.as_std(), f)
+    }
+}
+
+impl fmt::Display for BoxedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.0.as_std(), f)
+    }
+}
+
+impl std::error::Error for BoxedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.as_std().source()
+    }
+}
+
+impl Error for BoxedError {
+    fn session_error(&self) -> Option<(u32, String)> {
+        self.0.session_error()
+    }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        self.0.session_error_bytes()
+    }
+
+    fn stream_error(&self) -> Option<u32> {
+        self.0.stream_error()
+    }
+
+    fn kind(&self) -> ErrorKind {
+        self.0.kind()
+    }
+
+    fn code(&self) -> Option<u32> {
+        self.0.code()
+    }
+}
+
+// The object-safe half of [Session], implemented for every session.
+trait DynSession: MaybeSend + MaybeSync + 'static {
+    fn accept_uni(&self) -> BoxFuture<'_, Result<BoxedRecvStream, BoxedError>>;
+    fn accept_bi(&self) -> BoxFuture<'_, Result<(BoxedSendStream, BoxedRecvStream), BoxedError>>;
+    fn open_bi(&self) -> BoxFuture<'_, Result<(BoxedSendStream, BoxedRecvStream), BoxedError>>;
+    fn open_uni(&self) -> BoxFuture<'_, Result<BoxedSendStream, BoxedError>>;
+    fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> BoxFuture<'_, Result<(BoxedSendStream, BoxedRecvStream), BoxedError>>;
+    fn open_uni_with(
+        &self,
+        options: StreamOptions,
+    ) -> BoxFuture<'_, Result<BoxedSendStream, BoxedError>>;
+    fn send_datagram(&self, payload: Bytes) -> Result<(), BoxedError>;
+    fn send_datagram_wait(&self, payload: Bytes) -> BoxFuture<'_, Result<(), BoxedError>>;
+    fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), BoxedError>;
+    fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, BoxedError>>;
+    fn max_datagram_size(&self) -> usize;
+    fn datagrams_supported(&self) -> bool;
+    fn protocol(&self) -> Option<&str>;
+    fn close(&self, code: u32, reason: &str);
+    fn close_bytes(&self, code: u32, reason: &[u8]);
+    fn close_reason(&self) -> Option<Closed>;
+    fn closed(&self) -> BoxFuture<'_, BoxedError>;
+    fn stats(&self) -> Box<dyn Stats + '_>;
+    fn id(&self) -> Option<SessionId>;
+    fn peer_addr(&self) -> Option<SocketAddr>;
+    fn max_open_uni(&self) -> Option<u64>;
+    fn remaining_uni(&self) -> Option<u64>;
+    fn max_open_bi(&self) -> Option<u64>;
+    fn remaining_bi(&self) -> Option<u64>;
+}
+
+impl<S: Session> DynSession for S {
+    fn accept_uni(&self) -> BoxFuture<'_, Result<BoxedRecvStream, BoxedError>> {
+        Box::pin(async move {
+            let recv = Session::accept_uni(self).await.map_err(BoxedError::new)?;
+            Ok(BoxedRecvStream::new(recv))
+        })
+    }
+
+    fn accept_bi(&self) -> BoxFuture<'_, Result<(BoxedSendStream, BoxedRecvStream), BoxedError>> {
+        Box::pin(async move {
+            let (send, recv) = Session::accept_bi(self).await.map_err(BoxedError::new)?;
+            Ok((BoxedSendStream::new(send), BoxedRecvStream::new(recv)))
+        })
+    }
+
+    fn open_bi(&self) -> BoxFuture<'_, Result<(BoxedSendStream, BoxedRecvStream), BoxedError>> {
+        Box::pin(async move {
+            let (send, recv) = Session::open_bi(self).await.map_err(BoxedError::new)?;
+            Ok((BoxedSendStream::new(send), BoxedRecvStream::new(recv)))
+        })
+    }
+
+    fn open_uni(&self) -> BoxFuture<'_, Result<BoxedSendStream, BoxedError>> {
+        Box::pin(async move {
+            let send = Session::open_uni(self).await.map_err(BoxedError::new)?;
+            Ok(BoxedSendStream::new(send))
+        })
+    }
+
+    fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> BoxFuture<'_, Result<(BoxedSendStream, BoxedRecvStream), BoxedError>> {
+        Box::pin(async move {
+            let (send, recv) = Session::open_bi_with(self, options)
+                .await
+                .map_err(BoxedError::new)?;
+            Ok((BoxedSendStream::new(send), BoxedRecvStream::new(recv)))
+        })
+    }
+
+    fn open_uni_with(
+        &self,
+        options: StreamOptions,
+    ) -> BoxFuture<'_, Result<BoxedSendStream, BoxedError>> {
+        Box::pin(async move {
+            let send = Session::open_uni_with(self, options)
+                .await
+                .map_err(BoxedError::new)?;
+            Ok(BoxedSendStream::new(send))
+        })
+    }
+
+    fn send_datagram(&self, payload: Bytes) -> Result<(), BoxedError> {
+        Session::send_datagram(self, payload).map_err(BoxedError::new)
+    }
+
+    fn send_datagram_wait(&self, payload: Bytes) -> BoxFuture<'_, Result<(), BoxedError>> {
+        Box::pin(async move {
+            Session::send_datagram_wait(self, payload)
+                .await
+                .map_err(BoxedError::new)
+        })
+    }
+
+    fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), BoxedError> {
+        Session::send_datagram_with(self, payload, options).map_err(BoxedError::new)
+    }
+
+    fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, BoxedError>> {
+        Box::pin(async move { Session::recv_datagram(self).await.map_err(BoxedError::new) })
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        Session::max_datagram_size(self)
+    }
+
+    fn datagrams_supported(&self) -> bool {
+        Session::datagrams_supported(self)
+    }
+
+    fn protocol(&self) -> Option<&str> {
+        Session::protocol(self)
+    }
+
+    fn close(&self, code: u32, reason: &str) {
+        Session::close(self, code, reason)
+    }
+
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        Session::close_bytes(self, code, reason)
+    }
+
+    fn close_reason(&self) -> Option<Closed> {
+        Session::close_reason(self)
+    }
+
+    fn closed(&self) -> BoxFuture<'_, BoxedError> {
+        Box::pin(async move { BoxedError::new(Session::closed(self).await) })
+    }
+
+    fn stats(&self) -> Box<dyn Stats + '_> {
+        Box::new(Session::stats(self))
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        Session::id(self)
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        Session::peer_addr(self)
+    }
+
+    fn max_open_uni(&self) -> Option<u64> {
+        Session::max_open_uni(self)
+    }
+
+    fn remaining_uni(&self) -> Option<u64> {
+        Session::remaining_uni(self)
+    }
+
+    fn max_open_bi(&self) -> Option<u64> {
+        Session::max_open_bi(self)
+    }
+
+    fn remaining_bi(&self) -> Option<u64> {
+        Session::remaining_bi(self)
+    }
+}
+
+/// A [Session] from any backend, created with [Session::boxed].
+///
+/// Cloning is cheap; clones share the same session.
+#[derive(Clone)]
+pub struct BoxedSession(Arc<dyn DynSession>);
+
+impl BoxedSession {
+    pub fn new(session: impl Session) -> Self {
+        Self(Arc::new(session))
+    }
+}
+
+impl Session for BoxedSession {
+    type SendStream = BoxedSendStream;
+    type RecvStream = BoxedRecvStream;
+    type Error = BoxedError;
+
+    fn accept_uni(&self) -> impl Future<Output = Result<BoxedRecvStream, BoxedError>> + MaybeSend {
+        self.0.accept_uni()
+    }
+
+    fn accept_bi(
+        &self,
+    ) -> impl Future<Output = Result<(BoxedSendStream, BoxedRecvStream), BoxedError>> + MaybeSend
+    {
+        self.0.accept_bi()
+    }
+
+    fn open_bi(
+        &self,
+    ) -> impl Future<Output = Result<(BoxedSendStream, BoxedRecvStream), BoxedError>> + MaybeSend
+    {
+        self.0.open_bi()
+    }
+
+    fn open_uni(&self) -> impl Future<Output = Result<BoxedSendStream, BoxedError>> + MaybeSend {
+        self.0.open_uni()
+    }
+
+    fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> impl Future<Output = Result<(BoxedSendStream, BoxedRecvStream), BoxedError>> + MaybeSend
+    {
+        self.0.open_bi_with(options)
+    }
+
+    fn open_uni_with(
+        &self,
+        options: StreamOptions,
+    ) -> impl Future<Output = Result<BoxedSendStream, BoxedError>> + MaybeSend {
+        self.0.open_uni_with(options)
+    }
+
+    fn send_datagram(&self, payload: Bytes) -> Result<(), BoxedError> {
+        self.0.send_datagram(payload)
+    }
+
+    fn send_datagram_wait(
+        &self,
+        payload: Bytes,
+    ) -> impl Future<Output = Result<(), BoxedError>> + MaybeSend {
+        self.0.send_datagram_wait(payload)
+    }
+
+    fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), BoxedError> {
+        self.0.send_datagram_with(payload, options)
+    }
+
+    fn recv_datagram(&self) -> impl Future<Output = Result<Bytes, BoxedError>> + MaybeSend {
+        self.0.recv_datagram()
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.0.max_datagram_size()
+    }
+
+    fn datagrams_supported(&self) -> bool {
+        self.0.datagrams_supported()
+    }
+
+    fn protocol(&self) -> Option<&str> {
+        self.0.protocol()
+    }
+
+    fn close(&self, code: u32, reason: &str) {
+        self.0.close(code, reason)
+    }
+
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        self.0.close_bytes(code, reason)
+    }
+
+    fn close_reason(&self) -> Option<Closed> {
+        self.0.close_reason()
+    }
+
+    fn closed(&self) -> impl Future<Output = BoxedError> + MaybeSend {
+        self.0.closed()
+    }
+
+    fn stats(&self) -> impl Stats {
+        BoxedStats(self.0.stats())
+    }
+
+    fn id(&self) -> Option<SessionId> {
+        self.0.id()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    fn max_open_uni(&self) -> Option<u64> {
+        self.0.max_open_uni()
+    }
+
+    fn remaining_uni(&self) -> Option<u64> {
+        self.0.remaining_uni()
+    }
+
+    fn max_open_bi(&self) -> Option<u64> {
+        self.0.max_open_bi()
+    }
+
+    fn remaining_bi(&self) -> Option<u64> {
+        self.0.remaining_bi()
+    }
+
+    fn boxed(self) -> BoxedSession {
+        self
+    }
+}
+
+// Forwards every metric, since `Box<dyn Stats>` doesn't implement [Stats] itself.
+struct BoxedStats<'a>(Box<dyn Stats + 'a>);
+
+impl Stats for BoxedStats<'_> {
+    fn bytes_sent(&self) -> Option<u64> {
+        self.0.bytes_sent()
+    }
+
+    fn bytes_received(&self) -> Option<u64> {
+        self.0.bytes_received()
+    }
+
+    fn bytes_lost(&self) -> Option<u64> {
+        self.0.bytes_lost()
+    }
+
+    fn packets_sent(&self) -> Option<u64> {
+        self.0.packets_sent()
+    }
+
+    fn packets_received(&self) -> Option<u64> {
+        self.0.packets_received()
+    }
+
+    fn packets_lost(&self) -> Option<u64> {
+        self.0.packets_lost()
+    }
+
+    fn rtt(&self) -> Option<Duration> {
+        self.0.rtt()
+    }
+
+    fn estimated_send_rate(&self) -> Option<u64> {
+        self.0.estimated_send_rate()
+    }
+
+    fn congestion_window(&self) -> Option<u64> {
+        self.0.congestion_window()
+    }
+
+    fn datagrams_dropped(&self) -> Option<u64> {
+        self.0.datagrams_dropped()
+    }
+
+    fn ignored_uni_streams(&self) -> Option<u64> {
+        self.0.ignored_uni_streams()
+    }
+}
+
+// The object-safe half of [SendStream].
+trait DynSendStream: MaybeSend {
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, BoxedError>>;
+    fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), BoxedError>>;
+    fn set_priority(&mut self, order: u8);
+    fn finish(&mut self) -> Result<(), BoxedError>;
+    fn reset(&mut self, code: u32);
+    fn closed(&mut self) -> BoxFuture<'_, Result<(), BoxedError>>;
+}
+
+impl<S: SendStream + 'static> DynSendStream for S {
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, BoxedError>> {
+        Box::pin(async move { SendStream::write(self, buf).await.map_err(BoxedError::new) })
+    }
+
+    fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), BoxedError>> {
+        Box::pin(async move {
+            SendStream::write_chunk(self, chunk)
+                .await
+                .map_err(BoxedError::new)
+        })
+    }
+
+    fn set_priority(&mut self, order: u8) {
+        SendStream::set_priority(self, order)
+    }
+
+    fn finish(&mut self) -> Result<(), BoxedError> {
+        SendStream::finish(self).map_err(BoxedError::new)
+    }
+
+    fn reset(&mut self, code: u32) {
+        SendStream::reset(self, code)
+    }
+
+    fn closed(&mut self) -> BoxFuture<'_, Result<(), BoxedError>> {
+        Box::pin(async move { SendStream::closed(self).await.map_err(BoxedError::new) })
+    }
+}
+
+/// A [SendStream] from any backend, opened through a [BoxedSession].
+pub struct BoxedSendStream(Box<dyn DynSendStream>);
+
+impl BoxedSendStream {
+    pub fn new(stream: impl SendStream + 'static) -> Self {
+        Self(Box::new(stream))
+    }
+}
+
+impl SendStream for BoxedSendStream {
+    type Error = BoxedError;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, BoxedError> {
+        self.0.write(buf).await
+    }
+
+    fn write_chunk(
+        &mut self,
+        chunk: Bytes,
+    ) -> impl Future<Output = Result<(), BoxedError>> + MaybeSend {
+        // Pass the chunk through whole, so the backend's zero-copy path survives.
+        self.0.write_chunk(chunk)
+    }
+
+    fn set_priority(&mut self, order: u8) {
+        self.0.set_priority(order)
+    }
+
+    fn finish(&mut self) -> Result<(), BoxedError> {
+        self.0.finish()
+    }
+
+    fn reset(&mut self, code: u32) {
+        self.0.reset(code)
+    }
+
+    fn closed(&mut self) -> impl Future<Output = Result<(), BoxedError>> + MaybeSend {
+        self.0.closed()
+    }
+}
+
+// The object-safe half of [RecvStream].
+trait DynRecvStream: MaybeSend {
+    fn read<'a>(
+        &'a mut self,
+        dst: &'a mut [u8],
+    ) -> BoxFuture<'a, Result<Option<usize>, BoxedError>>;
+    fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, BoxedError>>;
+    fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> BoxFuture<'_, Result<Option<Vec<Bytes>>, BoxedError>>;
+    fn stop(&mut self, code: u32);
+    fn closed(&mut self) -> BoxFuture<'_, Result<(), BoxedError>>;
+}
+
+impl<R: RecvStream + 'static> DynRecvStream for R {
+    fn read<'a>(
+        &'a mut self,
+        dst: &'a mut [u8],
+    ) -> BoxFuture<'a, Result<Option<usize>, BoxedError>> {
+        Box::pin(async move { RecvStream::read(self, dst).await.map_err(BoxedError::new) })
+    }
+
+    fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, BoxedError>> {
+        Box::pin(async move {
+            RecvStream::read_chunk(self, max)
+                .await
+                .map_err(BoxedError::new)
+        })
+    }
+
+    fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> BoxFuture<'_, Result<Option<Vec<Bytes>>, BoxedError>> {
+        Box::pin(async move {
+            RecvStream::read_ready_chunks(self, max_total)
+                .await
+                .map_err(BoxedError::new)
+        })
+    }
+
+    fn stop(&mut self, code: u32) {
+        RecvStream::stop(self, code)
+    }
+
+    fn closed(&mut self) -> BoxFuture<'_, Result<(), BoxedError>> {
+        Box::pin(async move { RecvStream::closed(self).await.map_err(BoxedError::new) })
+    }
+}
+
+/// A [RecvStream] from any backend, accepted through a [BoxedSession].
+pub struct BoxedRecvStream(Box<dyn DynRecvStream>);
+
+impl BoxedRecvStream {
+    pub fn new(stream: impl RecvStream + 'static) -> Self {
+        Self(Box::new(stream))
+    }
+}
+
+impl RecvStream for BoxedRecvStream {
+    type Error = BoxedError;
+
+    async fn read(&mut self, dst: &mut [u8]) -> Result<Option<usize>, BoxedError> {
+        self.0.read(dst).await
+    }
+
+    fn read_chunk(
+        &mut self,
+        max: usize,
+    ) -> impl Future<Output = Result<Option<Bytes>, BoxedError>> + MaybeSend {
+        self.0.read_chunk(max)
+    }
+
+    fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> impl Future<Output = Result<Option<Vec<Bytes>>, BoxedError>> + MaybeSend {
+        self.0.read_ready_chunks(max_total)
+    }
+
+    fn stop(&mut self, code: u32) {
+        self.0.stop(code)
+    }
+
+    fn closed(&mut self) -> impl Future<Output = Result<(), BoxedError>> + MaybeSend {
+        self.0.closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Reset(u32);
+
+    impl fmt::Display for Reset {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "reset: {}", self.0)
+        }
+    }
+
+    impl std::error::Error for Reset {}
+
+    impl Error for Reset {
+        fn session_error(&self) -> Option<(u32, String)> {
+            None
+        }
+
+        fn stream_error(&self) -> Option<u32> {
+            Some(self.0)
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::StreamReset
+        }
+    }
+
+    // Hands out its bytes in one read, then fails as if the peer reset the stream.
+    struct Resetting(Option<Vec<u8>>);
+
+    impl RecvStream for Resetting {
+        type Error = Reset;
+
+        async fn read(&mut self, dst: &mut [u8]) -> Result<Option<usize>, Reset> {
+            let data = self.0.take().ok_or(Reset(7))?;
+            dst[..data.len()].copy_from_slice(&data);
+            Ok(Some(data.len()))
+        }
+
+        fn stop(&mut self, _code: u32) {}
+
+        async fn closed(&mut self) -> Result<(), Reset> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn boxed_stream_forwards() {
+        let mut recv = BoxedRecvStream::new(Resetting(Some(b"hello".to_vec())));
+        futures::executor::block_on(async {
+            let chunk = RecvStream::read_chunk(&mut recv, 16)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(chunk, "hello");
+
+            // The error keeps its classification, and the original is still reachable.
+            let err = RecvStream::read_chunk(&mut recv, 16).await.unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::StreamReset);
+            assert_eq!(err.code(), Some(7));
+            assert_eq!(err.to_string(), "reset: 7");
+            assert_eq!(err.downcast_ref::<Reset>().unwrap().0, 7);
+        });
+    }
+}
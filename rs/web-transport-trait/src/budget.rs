@@ -0,0 +1,255 @@
+//! A byte budget shared by every connection in a process.
+
+use std::{
+    fmt,
+    future::poll_fn,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Poll, Waker},
+};
+
+/// A hard cap on the bytes queued across every connection that shares it.
+///
+/// Backends reserve from the budget before buffering stream data or datagrams
+/// and release it once those bytes leave their custody. When the budget is
+/// exhausted, new data waits (streams) or is dropped (datagrams) instead of
+/// growing memory without bound.
+///
+/// Cloning is cheap; clones share the same budget. Each connection draws from
+/// its own [MemoryAccount] so its usage can be reported separately.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    limit: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    used: usize,
+    peak: usize,
+
+    // Woken whenever bytes are released.
+    waiters: Vec<Waker>,
+}
+
+impl MemoryBudget {
+    /// Create a budget allowing at most `limit` bytes to be queued at once.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                limit,
+                state: Mutex::default(),
+            }),
+        }
+    }
+
+    /// The maximum number of bytes that may be queued at once.
+    pub fn limit(&self) -> usize {
+        self.shared.limit
+    }
+
+    /// The number of bytes currently reserved.
+    pub fn used(&self) -> usize {
+        self.shared.state.lock().unwrap().used
+    }
+
+    /// The highest [used](Self::used) value seen so far.
+    pub fn peak(&self) -> usize {
+        self.shared.state.lock().unwrap().peak
+    }
+
+    /// The number of bytes that can be reserved right now.
+    pub fn available(&self) -> usize {
+        self.limit().saturating_sub(self.used())
+    }
+
+    /// Start tracking a new connection's usage against this budget.
+    pub fn account(&self) -> MemoryAccount {
+        MemoryAccount {
+            budget: self.clone(),
+            used: Arc::default(),
+        }
+    }
+
+    // Reserve between `min` and `max` bytes, or register `waker` and return None.
+    //
+    // A reservation larger than the whole limit is granted once nothing else is
+    // reserved, so an oversized write is throttled rather than wedged forever.
+    fn reserve(&self, min: usize, max: usize, waker: Option<&Waker>) -> Option<usize> {
+        let mut state = self.shared.state.lock().unwrap();
+        let available = self.shared.limit.saturating_sub(state.used);
+
+        let size = if max == 0 {
+            0
+        } else if available >= min.max(1) {
+            max.min(available)
+        } else if state.used == 0 {
+            max
+        } else {
+            if let Some(waker) = waker {
+                if !state.waiters.iter().any(|w| w.will_wake(waker)) {
+                    state.waiters.push(waker.clone());
+                }
+            }
+            return None;
+        };
+
+        state.used += size;
+        state.peak = state.peak.max(state.used);
+        Some(size)
+    }
+
+    fn release(&self, size: usize) {
+        if size == 0 {
+            return;
+        }
+
+        let waiters = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.used -= size;
+            std::mem::take(&mut state.waiters)
+        };
+
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limit", &self.limit())
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+/// One connection's share of a [MemoryBudget].
+///
+/// Reservations made through the account count against both the budget and
+/// the account, so [MemoryAccount::used] reports what this connection holds.
+#[derive(Clone)]
+pub struct MemoryAccount {
+    budget: MemoryBudget,
+    used: Arc<AtomicUsize>,
+}
+
+impl MemoryAccount {
+    /// The budget this account draws from.
+    pub fn budget(&self) -> &MemoryBudget {
+        &self.budget
+    }
+
+    /// The number of bytes this connection currently has reserved.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Reserve exactly `size` bytes if they're available right now.
+    pub fn try_reserve(&self, size: usize) -> Option<MemoryPermit> {
+        let size = self.budget.reserve(size, size, None)?;
+        Some(self.permit(size))
+    }
+
+    /// Reserve as much of `size` as is available right now, if any.
+    pub fn try_reserve_up_to(&self, size: usize) -> Option<MemoryPermit> {
+        let size = self.budget.reserve(1, size, None)?;
+        Some(self.permit(size))
+    }
+
+    /// Reserve as much of `size` as is available, registering `waker` to be
+    /// woken when bytes are released if nothing is.
+    pub fn poll_reserve_up_to(&self, waker: &Waker, size: usize) -> Poll<MemoryPermit> {
+        match self.budget.reserve(1, size, Some(waker)) {
+            Some(size) => Poll::Ready(self.permit(size)),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Wait until exactly `size` bytes can be reserved.
+    pub async fn reserve(&self, size: usize) -> MemoryPermit {
+        poll_fn(
+            |cx| match self.budget.reserve(size, size, Some(cx.waker())) {
+                Some(size) => Poll::Ready(self.permit(size)),
+                None => Poll::Pending,
+            },
+        )
+        .await
+    }
+
+    /// An empty permit, to be grown with [MemoryPermit::absorb].
+    pub fn empty(&self) -> MemoryPermit {
+        self.permit(0)
+    }
+
+    fn permit(&self, size: usize) -> MemoryPermit {
+        self.used.fetch_add(size, Ordering::Relaxed);
+        MemoryPermit {
+            account: self.clone(),
+            size,
+        }
+    }
+}
+
+impl fmt::Debug for MemoryAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryAccount")
+            .field("used", &self.used())
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+/// Bytes reserved from a [MemoryAccount], returned to the budget on drop.
+pub struct MemoryPermit {
+    account: MemoryAccount,
+    size: usize,
+}
+
+impl MemoryPermit {
+    /// The number of bytes this permit holds.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The account these bytes were reserved from.
+    pub fn account(&self) -> &MemoryAccount {
+        &self.account
+    }
+
+    /// Return up to `size` bytes to the budget early, keeping the rest.
+    pub fn release(&mut self, size: usize) {
+        let size = size.min(self.size);
+        self.size -= size;
+        self.account.used.fetch_sub(size, Ordering::Relaxed);
+        self.account.budget.release(size);
+    }
+
+    /// Take over another permit's bytes.
+    ///
+    /// Both permits should come from the same account.
+    pub fn absorb(&mut self, mut other: MemoryPermit) {
+        self.size += std::mem::take(&mut other.size);
+    }
+}
+
+impl Drop for MemoryPermit {
+    fn drop(&mut self) {
+        self.release(self.size);
+    }
+}
+
+impl fmt::Debug for MemoryPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryPermit")
+            .field("size", &self.size)
+            .finish()
+    }
+}
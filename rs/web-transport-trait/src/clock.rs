@@ -0,0 +1,100 @@
+//! A source of time for timeouts.
+
+use std::{
+    fmt,
+    future::{poll_fn, Future},
+    pin::pin,
+    sync::Arc,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use crate::{boxed::BoxFuture, MaybeSend, MaybeSync};
+
+/// The time a backend measures its timeouts against: handshakes, keep-alives, stream
+/// headers and the like.
+///
+/// Backends default to `TokioClock`, which `tokio::time::pause` already controls. Pass
+/// another clock to a builder's `with_clock` to drive time some other way, such as a mock
+/// advanced by hand. QUIC's own timers, like loss recovery, run on the transport's clock.
+pub trait Clock: MaybeSend + MaybeSync + 'static {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// Complete once [Clock::now] reaches `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()>;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        (**self).sleep_until(deadline)
+    }
+}
+
+impl dyn Clock {
+    /// Complete once `duration` has passed.
+    pub fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.sleep_until(self.now() + duration)
+    }
+
+    /// Run `fut` for up to `duration`, failing with [Elapsed] if it takes longer.
+    pub fn timeout<F: Future>(
+        &self,
+        duration: Duration,
+        fut: F,
+    ) -> impl Future<Output = Result<F::Output, Elapsed>> {
+        self.timeout_at(self.now() + duration, fut)
+    }
+
+    /// Run `fut` until `deadline`, failing with [Elapsed] if it passes first.
+    pub fn timeout_at<F: Future>(
+        &self,
+        deadline: Instant,
+        fut: F,
+    ) -> impl Future<Output = Result<F::Output, Elapsed>> {
+        let mut sleep = self.sleep_until(deadline);
+
+        async move {
+            let mut fut = pin!(fut);
+            poll_fn(|cx| {
+                if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                    return Poll::Ready(Ok(res));
+                }
+                sleep.as_mut().poll(cx).map(|()| Err(Elapsed))
+            })
+            .await
+        }
+    }
+}
+
+/// The error returned when a clock's `timeout` passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// The tokio timer, which `tokio::time::pause` and `advance` control.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
@@ -0,0 +1,123 @@
+//! Keeps how a session was closed, for inspection after the fact.
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+
+/// Which end of a session closed it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CloseSide {
+    /// We closed the session with [Session::close](crate::Session::close).
+    Local,
+
+    /// The peer closed the session.
+    Remote,
+}
+
+/// How a session was closed, returned by [Session::close_reason](crate::Session::close_reason).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Closed {
+    /// The end whose close took effect.
+    pub side: CloseSide,
+
+    /// The application error code.
+    pub code: u32,
+
+    /// The reason, exactly as it was sent or received.
+    pub reason: Bytes,
+
+    /// Whether the other end closed too, after this close was recorded.
+    ///
+    /// A close from the same end never counts; only a local close that crossed
+    /// (or followed) the peer's, or vice versa.
+    pub raced: bool,
+}
+
+/// Records the first close of a session, for backends implementing [Session::close_reason](crate::Session::close_reason).
+///
+/// Backends call [local](Self::local) from `close` and [remote](Self::remote) when the
+/// peer's close arrives. The first close wins and is never replaced; a close from the
+/// other end only sets [Closed::raced].
+///
+/// Cloning is cheap; clones share the same record.
+#[derive(Clone, Debug, Default)]
+pub struct CloseRecord(Arc<Mutex<Option<Closed>>>);
+
+impl CloseRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a local close, returning true if it was the first close of the session.
+    ///
+    /// Returns false if the session was already closed by either end, in which case
+    /// the caller should do nothing else.
+    pub fn local(&self, code: u32, reason: &[u8]) -> bool {
+        self.record(CloseSide::Local, code, reason)
+    }
+
+    /// Record a close from the peer, returning true if it was the first close of the session.
+    pub fn remote(&self, code: u32, reason: &[u8]) -> bool {
+        self.record(CloseSide::Remote, code, reason)
+    }
+
+    fn record(&self, side: CloseSide, code: u32, reason: &[u8]) -> bool {
+        let mut closed = self.0.lock().unwrap();
+        match closed.as_mut() {
+            Some(closed) => {
+                if closed.side != side {
+                    closed.raced = true;
+                }
+                false
+            }
+            None => {
+                *closed = Some(Closed {
+                    side,
+                    code,
+                    reason: Bytes::copy_from_slice(reason),
+                    raced: false,
+                });
+                true
+            }
+        }
+    }
+
+    /// Return the first close, or None if the session hasn't been closed.
+    pub fn get(&self) -> Option<Closed> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_close_wins() {
+        let record = CloseRecord::new();
+        assert_eq!(record.get(), None);
+
+        assert!(record.local(1, b"first"));
+        assert!(!record.local(2, b"second"));
+
+        let closed = record.get().unwrap();
+        assert_eq!(closed.side, CloseSide::Local);
+        assert_eq!(closed.code, 1);
+        assert_eq!(closed.reason, "first");
+        assert!(!closed.raced);
+    }
+
+    #[test]
+    fn other_end_marks_the_race() {
+        let record = CloseRecord::new();
+        assert!(record.remote(7, b"peer"));
+        assert!(!record.local(1, b"us"));
+
+        let closed = record.get().unwrap();
+        assert_eq!(closed.side, CloseSide::Remote);
+        assert_eq!(closed.code, 7);
+        assert_eq!(closed.reason, "peer");
+        assert!(closed.raced);
+    }
+}
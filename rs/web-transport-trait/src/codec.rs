@@ -0,0 +1,380 @@
+//! Length-delimited messages on top of any [SendStream] and [RecvStream].
+//!
+//! Each message is prefixed with its length as a QUIC variable-length integer, the same
+//! framing WebTransport itself uses, so a browser peer can decode it with a few lines of
+//! JavaScript. Enable the `serde` feature to send values encoded as JSON.
+
+use std::fmt;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{RecvStream, SendStream};
+
+/// The largest message accepted unless configured otherwise: 1 MiB.
+pub const DEFAULT_MAX_SIZE: usize = 1024 * 1024;
+
+// The largest value a QUIC varint can hold.
+const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// An error sending or receiving a framed message.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CodecError<E> {
+    /// The underlying stream failed.
+    Stream(E),
+
+    /// The message is larger than the configured maximum.
+    TooLarge { size: u64, max: usize },
+
+    /// The stream ended partway through a message.
+    UnexpectedEnd,
+
+    /// The message couldn't be encoded or decoded as JSON.
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+}
+
+impl<E: fmt::Display> fmt::Display for CodecError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stream(err) => write!(f, "stream error: {err}"),
+            Self::TooLarge { size, max } => write!(f, "message too large: {size} > {max}"),
+            Self::UnexpectedEnd => write!(f, "stream ended partway through a message"),
+            #[cfg(feature = "serde")]
+            Self::Json(err) => write!(f, "json error: {err}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CodecError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Stream(err) => Some(err),
+            #[cfg(feature = "serde")]
+            Self::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Writes length-delimited messages to a [SendStream].
+pub struct FramedWrite<S> {
+    stream: S,
+    max_size: usize,
+}
+
+impl<S: SendStream> FramedWrite<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Refuse to send a message larger than `max_size`. Defaults to [DEFAULT_MAX_SIZE].
+    ///
+    /// Match the peer's limit, since it rejects anything larger anyway.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Write a single message, waiting until the stream accepts all of it.
+    pub async fn send(&mut self, message: impl Into<Bytes>) -> Result<(), CodecError<S::Error>> {
+        let message = message.into();
+        if message.len() > self.max_size {
+            return Err(CodecError::TooLarge {
+                size: message.len() as u64,
+                max: self.max_size,
+            });
+        }
+
+        let mut header = BytesMut::with_capacity(8);
+        encode_varint(message.len() as u64, &mut header);
+
+        let mut frame = header.freeze().chain(message);
+        self.stream
+            .write_all_buf(&mut frame)
+            .await
+            .map_err(CodecError::Stream)
+    }
+
+    /// Write a value encoded as JSON.
+    #[cfg(feature = "serde")]
+    pub async fn send_json<T: serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), CodecError<S::Error>> {
+        let message = serde_json::to_vec(value).map_err(CodecError::Json)?;
+        self.send(message).await
+    }
+
+    /// Finish the stream, so the peer's [FramedRead::recv] returns `None` after the last message.
+    pub fn finish(&mut self) -> Result<(), CodecError<S::Error>> {
+        self.stream.finish().map_err(CodecError::Stream)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// Reads length-delimited messages from a [RecvStream].
+pub struct FramedRead<R> {
+    stream: R,
+    max_size: usize,
+
+    // Bytes read past the end of the previous message.
+    buffer: BytesMut,
+}
+
+impl<R: RecvStream> FramedRead<R> {
+    pub fn new(stream: R) -> Self {
+        Self {
+            stream,
+            max_size: DEFAULT_MAX_SIZE,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Reject a message larger than `max_size`. Defaults to [DEFAULT_MAX_SIZE].
+    ///
+    /// The length is checked before anything is buffered, so a peer can't make us
+    /// allocate more than this by announcing a huge message.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Read the next message, or `None` if the stream finished cleanly between messages.
+    ///
+    /// Not cancel safe in general: a message that's partially read when the future is
+    /// dropped stays buffered, but only if the stream's own reads are cancel safe.
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, CodecError<R::Error>> {
+        loop {
+            if let Some(message) = self.decode()? {
+                return Ok(Some(message));
+            }
+
+            self.buffer.reserve(4096);
+            let read = self
+                .stream
+                .read_buf(&mut self.buffer)
+                .await
+                .map_err(CodecError::Stream)?;
+
+            if read.is_none() {
+                return match self.buffer.is_empty() {
+                    true => Ok(None),
+                    false => Err(CodecError::UnexpectedEnd),
+                };
+            }
+        }
+    }
+
+    /// Read the next message and decode it from JSON.
+    #[cfg(feature = "serde")]
+    pub async fn recv_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<T>, CodecError<R::Error>> {
+        match self.recv().await? {
+            Some(message) => serde_json::from_slice(&message)
+                .map(Some)
+                .map_err(CodecError::Json),
+            None => Ok(None),
+        }
+    }
+
+    // Split a whole message off the front of the buffer, if one has arrived.
+    fn decode(&mut self) -> Result<Option<Bytes>, CodecError<R::Error>> {
+        let mut peek = &self.buffer[..];
+        let Some(size) = decode_varint(&mut peek) else {
+            return Ok(None);
+        };
+
+        if size > self.max_size as u64 {
+            return Err(CodecError::TooLarge {
+                size,
+                max: self.max_size,
+            });
+        }
+
+        let header = self.buffer.len() - peek.len();
+        let missing = (size as usize).saturating_sub(peek.len());
+        if missing > 0 {
+            // Make room for the rest up front, rather than growing bit by bit.
+            self.buffer.reserve(missing);
+            return Ok(None);
+        }
+
+        self.buffer.advance(header);
+        Ok(Some(self.buffer.split_to(size as usize).freeze()))
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
+    }
+
+    /// Return the stream, along with any bytes already read past the last message.
+    pub fn into_parts(self) -> (R, Bytes) {
+        (self.stream, self.buffer.freeze())
+    }
+}
+
+// Encode a QUIC variable-length integer (RFC 9000 Section 16).
+fn encode_varint<B: BufMut>(value: u64, buf: &mut B) {
+    debug_assert!(value <= MAX_VARINT);
+    match value {
+        0..=0x3f => buf.put_u8(value as u8),
+        0x40..=0x3fff => buf.put_u16(0x4000 | value as u16),
+        0x4000..=0x3fff_ffff => buf.put_u32(0x8000_0000 | value as u32),
+        _ => buf.put_u64(0xc000_0000_0000_0000 | value),
+    }
+}
+
+// Decode a QUIC variable-length integer, or None if the buffer doesn't hold all of it.
+fn decode_varint<B: Buf>(buf: &mut B) -> Option<u64> {
+    let first = *buf.chunk().first()?;
+    let size = 1 << (first >> 6);
+    if buf.remaining() < size {
+        return None;
+    }
+
+    let mut value = u64::from(buf.get_u8() & 0x3f);
+    for _ in 1..size {
+        value = (value << 8) | u64::from(buf.get_u8());
+    }
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[derive(Debug)]
+    struct Closed;
+
+    impl fmt::Display for Closed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "closed")
+        }
+    }
+
+    impl std::error::Error for Closed {}
+
+    impl crate::Error for Closed {
+        fn session_error(&self) -> Option<(u32, String)> {
+            None
+        }
+    }
+
+    // Accepts at most 3 bytes per write, to exercise partial writes.
+    #[derive(Default)]
+    struct Sink(Vec<u8>);
+
+    impl SendStream for Sink {
+        type Error = Closed;
+
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Closed> {
+            let size = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..size]);
+            Ok(size)
+        }
+
+        fn set_priority(&mut self, _order: u8) {}
+
+        fn finish(&mut self) -> Result<(), Closed> {
+            Ok(())
+        }
+
+        fn reset(&mut self, _code: u32) {}
+
+        async fn closed(&mut self) -> Result<(), Closed> {
+            Ok(())
+        }
+    }
+
+    // Hands out the bytes in the chunks given, to exercise messages split across reads.
+    struct Source(VecDeque<Vec<u8>>);
+
+    impl RecvStream for Source {
+        type Error = Closed;
+
+        async fn read(&mut self, dst: &mut [u8]) -> Result<Option<usize>, Closed> {
+            let Some(mut chunk) = self.0.pop_front() else {
+                return Ok(None);
+            };
+
+            let size = chunk.len().min(dst.len());
+            dst[..size].copy_from_slice(&chunk[..size]);
+            if size < chunk.len() {
+                self.0.push_front(chunk.split_off(size));
+            }
+
+            Ok(Some(size))
+        }
+
+        fn stop(&mut self, _code: u32) {}
+
+        async fn closed(&mut self) -> Result<(), Closed> {
+            Ok(())
+        }
+    }
+
+    fn encode(messages: &[&[u8]]) -> Vec<u8> {
+        let mut write = FramedWrite::new(Sink::default());
+        futures::executor::block_on(async {
+            for message in messages {
+                write.send(message.to_vec()).await.unwrap();
+            }
+        });
+        write.into_inner().0
+    }
+
+    #[test]
+    fn roundtrip_across_chunks() {
+        let long = vec![7; 300];
+        let wire = encode(&[b"hello", b"", &long]);
+        assert_eq!(&wire[..6], b"\x05hello");
+        assert_eq!(&wire[7..9], [0x41, 0x2c]);
+
+        // Feed the bytes back one at a time.
+        let chunks = wire.iter().map(|b| vec![*b]).collect();
+        let mut read = FramedRead::new(Source(chunks));
+        futures::executor::block_on(async {
+            assert_eq!(read.recv().await.unwrap().unwrap(), "hello");
+            assert_eq!(read.recv().await.unwrap().unwrap(), "");
+            assert_eq!(read.recv().await.unwrap().unwrap(), long);
+            assert!(read.recv().await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn rejects_oversized_and_truncated() {
+        let wire = encode(&[&[0; 100]]);
+
+        let source = Source(VecDeque::from([wire.clone()]));
+        let mut read = FramedRead::new(source).with_max_size(99);
+        let err = futures::executor::block_on(read.recv()).unwrap_err();
+        assert!(matches!(err, CodecError::TooLarge { size: 100, max: 99 }));
+
+        let source = Source(VecDeque::from([wire[..50].to_vec()]));
+        let mut read = FramedRead::new(source);
+        let err = futures::executor::block_on(read.recv()).unwrap_err();
+        assert!(matches!(err, CodecError::UnexpectedEnd));
+    }
+}
@@ -0,0 +1,32 @@
+//! Per-datagram send options.
+
+use std::time::Duration;
+
+/// How to send a datagram with [Session::send_datagram_with](crate::Session::send_datagram_with).
+///
+/// Mirrors the browser's `outgoingMaxAge` and `sendOrder`. Both are hints: a backend that
+/// can't honor one sends the datagram as [Session::send_datagram](crate::Session::send_datagram) would.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DatagramOptions {
+    /// Drop the datagram if it's still queued locally after this long, rather than send it late.
+    ///
+    /// None keeps it until it's sent or pushed out by a full queue.
+    pub max_age: Option<Duration>,
+
+    /// Send the datagram before those with a lower priority, like [SendStream::set_priority](crate::SendStream::set_priority).
+    pub priority: Option<u8>,
+}
+
+impl DatagramOptions {
+    /// Drop the datagram if it's still queued locally after `max_age`.
+    pub const fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Send the datagram ahead of those with a lower `priority`.
+    pub const fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+}
@@ -0,0 +1,172 @@
+//! Opt-in fault injection for resilience testing.
+//!
+//! [Faults] are applied locally on top of a real QUIC connection, so the peer observes
+//! genuine delays, resets, and loss rather than a mock's idea of them.
+//! Nothing is injected unless a backend's builder is given a [Faults] via `with_faults`.
+
+use std::{
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The faults to inject into each [Session](crate::Session).
+///
+/// Probabilities are in the range `0.0..=1.0`; anything outside is clamped.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    open_delay: Option<Duration>,
+    reset: Option<(f64, u32)>,
+    datagram_loss: f64,
+    control_stall: Option<Duration>,
+    seed: Option<u64>,
+}
+
+impl Faults {
+    /// Create an empty set of faults, injecting nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait this long before opening each stream.
+    pub fn with_open_delay(mut self, delay: Duration) -> Self {
+        self.open_delay = Some(delay);
+        self
+    }
+
+    /// Reset opened streams with `code`, each with the given probability.
+    ///
+    /// The stream is still returned, so the application sees the reset on its first write.
+    pub fn with_reset(mut self, probability: f64, code: u32) -> Self {
+        self.reset = Some((probability, code));
+        self
+    }
+
+    /// Silently drop outgoing datagrams with the given probability.
+    pub fn with_datagram_loss(mut self, probability: f64) -> Self {
+        self.datagram_loss = probability;
+        self
+    }
+
+    /// Hold back the HTTP/3 control stream this long during the handshake, delaying SETTINGS.
+    pub fn with_control_stall(mut self, stall: Duration) -> Self {
+        self.control_stall = Some(stall);
+        self
+    }
+
+    /// Seed the random number generator so a failing run can be reproduced.
+    ///
+    /// Each session starts from the same seed. Defaults to a random seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// The per-session state behind [Faults]: the configuration plus the RNG that drives it.
+///
+/// Backends create one per session and consult it as streams and datagrams are sent.
+/// Delays are returned rather than slept, so each backend waits on its own timer.
+pub struct FaultInjector {
+    faults: Faults,
+    rng: Mutex<u64>,
+}
+
+impl FaultInjector {
+    /// Start injecting `faults`.
+    pub fn new(faults: Faults) -> Arc<Self> {
+        let seed = faults.seed.unwrap_or_else(|| {
+            std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish()
+        });
+
+        Arc::new(Self {
+            faults,
+            rng: Mutex::new(seed),
+        })
+    }
+
+    /// How long to wait before opening a stream, if configured.
+    pub fn open_delay(&self) -> Option<Duration> {
+        self.faults.open_delay
+    }
+
+    /// How long to wait before starting the HTTP/3 handshake, if configured.
+    pub fn control_stall(&self) -> Option<Duration> {
+        self.faults.control_stall
+    }
+
+    /// Returns the code to reset a newly opened stream with, if it should be reset.
+    pub fn reset_code(&self) -> Option<u32> {
+        let (probability, code) = self.faults.reset?;
+        self.roll(probability).then_some(code)
+    }
+
+    /// Returns true if an outgoing datagram should be dropped.
+    pub fn drop_datagram(&self) -> bool {
+        self.roll(self.faults.datagram_loss)
+    }
+
+    fn roll(&self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+
+        if probability >= 1.0 {
+            return true;
+        }
+
+        // splitmix64: tiny, fast, and good enough for picking victims.
+        let mut state = self.rng.lock().unwrap();
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        // Use the top 53 bits to get a uniform f64 in [0, 1).
+        ((z >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_injected_by_default() {
+        let faults = FaultInjector::new(Faults::new());
+
+        for _ in 0..1000 {
+            assert!(!faults.drop_datagram());
+            assert_eq!(faults.reset_code(), None);
+        }
+    }
+
+    #[test]
+    fn certain_probabilities_always_fire() {
+        let faults = FaultInjector::new(Faults::new().with_datagram_loss(1.0).with_reset(1.0, 42));
+
+        for _ in 0..1000 {
+            assert!(faults.drop_datagram());
+            assert_eq!(faults.reset_code(), Some(42));
+        }
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let faults = Faults::new().with_datagram_loss(0.5).with_seed(7);
+
+        let a = FaultInjector::new(faults.clone());
+        let b = FaultInjector::new(faults);
+
+        let a: Vec<bool> = (0..1000).map(|_| a.drop_datagram()).collect();
+        let b: Vec<bool> = (0..1000).map(|_| b.drop_datagram()).collect();
+        assert_eq!(a, b);
+
+        // Roughly half should be dropped.
+        let dropped = a.iter().filter(|&&d| d).count();
+        assert!((400..600).contains(&dropped), "dropped {dropped}");
+    }
+}
@@ -0,0 +1,250 @@
+//! Racing connection attempts across resolved addresses (RFC 8305, "Happy Eyeballs v2").
+//!
+//! A host often resolves to both AAAA and A records, and one of the two families may be
+//! broken on the local network. Rather than waiting for the first address to time out,
+//! attempts are staggered by a short delay and the first to succeed wins.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::{poll_fn, Future},
+    io,
+    net::SocketAddr,
+    task::Poll,
+    time::Duration,
+};
+
+use crate::Clock;
+
+/// The delay between starting connection attempts, as recommended by RFC 8305.
+pub const DEFAULT_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// The error returned by [race] when there are no addresses to connect to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoAddresses;
+
+impl fmt::Display for NoAddresses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no addresses to connect to")
+    }
+}
+
+impl std::error::Error for NoAddresses {}
+
+impl From<NoAddresses> for io::Error {
+    fn from(err: NoAddresses) -> Self {
+        io::Error::other(err)
+    }
+}
+
+/// Reorder addresses so the families alternate, starting with the resolver's first choice.
+pub fn interleave(addrs: impl IntoIterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    let addrs: Vec<SocketAddr> = addrs.into_iter().collect();
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+
+    let prefer_v6 = first.is_ipv6();
+    let (mut preferred, mut other): (VecDeque<SocketAddr>, VecDeque<SocketAddr>) =
+        addrs.iter().partition(|addr| addr.is_ipv6() == prefer_v6);
+
+    let mut ordered = Vec::with_capacity(addrs.len());
+    while !preferred.is_empty() || !other.is_empty() {
+        ordered.extend(preferred.pop_front());
+        ordered.extend(other.pop_front());
+    }
+
+    ordered
+}
+
+/// Start `connect` for each address in turn, `delay` apart on `clock`, returning the first success.
+///
+/// A failed attempt starts the next one immediately instead of waiting out the delay.
+/// If every attempt fails, the last error is returned, and [NoAddresses] if `addrs` is empty.
+pub async fn race<T, E, F, Fut>(
+    clock: &dyn Clock,
+    addrs: Vec<SocketAddr>,
+    delay: Duration,
+    mut connect: F,
+) -> Result<T, E>
+where
+    E: From<NoAddresses>,
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut pending = addrs.into_iter();
+    let Some(first) = pending.next() else {
+        return Err(NoAddresses.into());
+    };
+
+    let mut attempts = vec![Box::pin(connect(first))];
+    let mut timer = clock.sleep(delay);
+    let mut last_err = None;
+
+    poll_fn(|cx| loop {
+        let mut i = 0;
+        while i < attempts.len() {
+            match attempts[i].as_mut().poll(cx) {
+                Poll::Ready(Ok(conn)) => return Poll::Ready(Ok(conn)),
+                Poll::Ready(Err(err)) => {
+                    attempts.swap_remove(i);
+                    last_err = Some(err);
+
+                    if let Some(addr) = pending.next() {
+                        attempts.push(Box::pin(connect(addr)));
+                        timer = clock.sleep(delay);
+                    }
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+
+        if attempts.is_empty() {
+            // Only a failure removes an attempt, so there's always an error here.
+            return Poll::Ready(Err(last_err.take().unwrap()));
+        }
+
+        if pending.as_slice().is_empty() || timer.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        // The delay elapsed without a winner, so race the next address too.
+        let addr = pending.next().unwrap();
+        attempts.push(Box::pin(connect(addr)));
+        timer = clock.sleep(delay);
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::boxed::BoxFuture;
+
+    // A clock whose sleeps either finish at once or never do.
+    struct Timer {
+        fires: bool,
+    }
+
+    impl Clock for Timer {
+        fn now(&self) -> Instant {
+            Instant::now()
+        }
+
+        fn sleep_until(&self, _deadline: Instant) -> BoxFuture<'static, ()> {
+            match self.fires {
+                true => Box::pin(std::future::ready(())),
+                false => Box::pin(std::future::pending()),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Failed {
+        Empty,
+        Addr(SocketAddr),
+    }
+
+    impl From<NoAddresses> for Failed {
+        fn from(_: NoAddresses) -> Self {
+            Self::Empty
+        }
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn interleaves_families() {
+        let addrs = [
+            addr("[2001:db8::1]:443"),
+            addr("[2001:db8::2]:443"),
+            addr("192.0.2.1:443"),
+            addr("[2001:db8::3]:443"),
+            addr("192.0.2.2:443"),
+        ];
+
+        assert_eq!(
+            interleave(addrs),
+            [
+                addr("[2001:db8::1]:443"),
+                addr("192.0.2.1:443"),
+                addr("[2001:db8::2]:443"),
+                addr("192.0.2.2:443"),
+                addr("[2001:db8::3]:443"),
+            ]
+        );
+    }
+
+    #[test]
+    fn failure_starts_next_attempt() {
+        let addrs = vec![addr("[2001:db8::1]:443"), addr("192.0.2.1:443")];
+
+        // The delay never elapses, so the second attempt must have been started by the failure.
+        let clock = Timer { fires: false };
+        let winner = futures::executor::block_on(race(
+            &clock,
+            addrs,
+            DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            |addr| async move {
+                match addr.is_ipv6() {
+                    true => Err(Failed::Addr(addr)),
+                    false => Ok(addr),
+                }
+            },
+        ));
+
+        assert_eq!(winner, Ok(addr("192.0.2.1:443")));
+    }
+
+    #[test]
+    fn hung_attempt_is_overtaken() {
+        let addrs = vec![addr("[2001:db8::1]:443"), addr("192.0.2.1:443")];
+
+        let clock = Timer { fires: true };
+        let winner = futures::executor::block_on(race(
+            &clock,
+            addrs,
+            DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            |addr| async move {
+                if addr.is_ipv6() {
+                    std::future::pending::<()>().await;
+                }
+                Ok::<_, Failed>(addr)
+            },
+        ));
+
+        assert_eq!(winner, Ok(addr("192.0.2.1:443")));
+    }
+
+    #[test]
+    fn returns_last_error() {
+        let addrs = vec![addr("[2001:db8::1]:443"), addr("192.0.2.1:443")];
+
+        let clock = Timer { fires: false };
+        let res = futures::executor::block_on(race(
+            &clock,
+            addrs,
+            DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            |addr| async move { Err::<(), _>(Failed::Addr(addr)) },
+        ));
+
+        assert_eq!(res, Err(Failed::Addr(addr("192.0.2.1:443"))));
+    }
+
+    #[test]
+    fn no_addresses_is_an_error() {
+        let clock = Timer { fires: true };
+        let res = futures::executor::block_on(race(
+            &clock,
+            Vec::new(),
+            DEFAULT_CONNECTION_ATTEMPT_DELAY,
+            |addr| async move { Ok::<_, Failed>(addr) },
+        ));
+
+        assert_eq!(res, Err(Failed::Empty));
+    }
+}
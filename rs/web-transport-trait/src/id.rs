@@ -0,0 +1,32 @@
+//! Identifiers for correlating a session's log lines.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A short number identifying a session within this process, returned by [Session::id](crate::Session::id).
+///
+/// Backends allocate one per session, from a process-wide counter, and record it as the
+/// `id` field of a `session` tracing span around everything the session does in the
+/// background. Log it alongside your own events to correlate the two. It's unrelated to
+/// the QUIC connection ID or the CONNECT stream ID, and isn't sent to the peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SessionId(u64);
+
+impl SessionId {
+    /// Allocate the next ID, unique until the counter wraps.
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// The ID as a number.
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
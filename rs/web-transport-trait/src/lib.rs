@@ -1,8 +1,31 @@
+mod budget;
+mod clock;
+mod close;
+mod datagram;
+mod fault;
+mod id;
+mod stream;
 mod util;
 
+pub mod boxed;
+pub mod happy_eyeballs;
+
+#[cfg(feature = "codec")]
+pub mod codec;
+
+use std::fmt;
 use std::future::Future;
 use std::time::Duration;
 
+pub use crate::budget::{MemoryAccount, MemoryBudget, MemoryPermit};
+#[cfg(feature = "tokio")]
+pub use crate::clock::TokioClock;
+pub use crate::clock::{Clock, Elapsed};
+pub use crate::close::{CloseRecord, CloseSide, Closed};
+pub use crate::datagram::DatagramOptions;
+pub use crate::fault::{FaultInjector, Faults};
+pub use crate::id::SessionId;
+pub use crate::stream::StreamOptions;
 pub use crate::util::{MaybeSend, MaybeSync};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
@@ -50,12 +73,79 @@ pub trait Stats {
     fn estimated_send_rate(&self) -> Option<u64> {
         None
     }
+
+    /// The congestion window, in bytes: how much may be in flight before waiting for acks.
+    fn congestion_window(&self) -> Option<u64> {
+        None
+    }
+
+    /// Total datagrams dropped locally, rather than lost in the network.
+    ///
+    /// A datagram is dropped when it can't be queued for sending, or arrives faster
+    /// than the application reads it.
+    fn datagrams_dropped(&self) -> Option<u64> {
+        None
+    }
+
+    /// Incoming unidirectional streams dropped because their type was unknown.
+    fn ignored_uni_streams(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// Default stats implementation that returns `None` for all metrics.
 pub struct StatsUnavailable;
 impl Stats for StatsUnavailable {}
 
+/// A coarse classification of an error, shared by every backend.
+///
+/// Backend error enums are `#[non_exhaustive]` and differ in shape, so match on this
+/// instead when deciding how to react. More kinds may be added in the future.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The peer closed the session or the connection.
+    ///
+    /// [Error::session_error] returns the code and reason when it was an application close.
+    SessionClosed,
+
+    /// The session or stream was already closed locally.
+    LocallyClosed,
+
+    /// The handshake or the connection timed out.
+    TimedOut,
+
+    /// The server declined the session, with a non-2xx status or a redirect.
+    Rejected,
+
+    /// The peer reset the stream with RESET_STREAM.
+    StreamReset,
+
+    /// The peer asked us to stop sending with STOP_SENDING.
+    StreamStopped,
+
+    /// The stream or the handshake ended before the expected data arrived.
+    UnexpectedEnd,
+
+    /// The data exceeded a limit, like a datagram that's too large to send.
+    TooLarge,
+
+    /// The peer doesn't support WebTransport or the requested feature, like datagrams.
+    Unsupported,
+
+    /// The peer violated the protocol, or sent something that couldn't be parsed.
+    Protocol,
+
+    /// The configuration or an argument was invalid, like a certificate or a URL.
+    InvalidInput,
+
+    /// The local socket or runtime failed.
+    Io,
+
+    /// Anything else.
+    Other,
+}
+
 /// Error trait for WebTransport operations.
 ///
 /// Implementations must be Send + Sync + 'static for use across async boundaries.
@@ -63,12 +153,95 @@ pub trait Error: std::error::Error + MaybeSend + MaybeSync + 'static {
     /// Returns the error code and reason if this was an application error.
     ///
     /// NOTE: Reason reasons are technically bytes on the wire, but we convert to a String for convenience.
+    /// Invalid UTF-8 is replaced lossily; use [Error::session_error_bytes] for the exact bytes.
     fn session_error(&self) -> Option<(u32, String)>;
 
+    /// Returns the error code and the reason exactly as the peer sent it.
+    ///
+    /// The default re-encodes [Error::session_error], so backends that keep the raw bytes should override it.
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        self.session_error()
+            .map(|(code, reason)| (code, Bytes::from(reason)))
+    }
+
     /// Returns the error code if this was a stream error.
     fn stream_error(&self) -> Option<u32> {
         None
     }
+
+    /// Returns what kind of error this is.
+    ///
+    /// The default only recognizes application closes; implementations should override it.
+    fn kind(&self) -> ErrorKind {
+        match self.session_error() {
+            Some(_) => ErrorKind::SessionClosed,
+            None => ErrorKind::Other,
+        }
+    }
+
+    /// Returns the application error code, from either the stream or the session.
+    fn code(&self) -> Option<u32> {
+        self.stream_error()
+            .or_else(|| self.session_error().map(|(code, _)| code))
+    }
+}
+
+/// An error returned by [RecvStream::read_to_end_strict].
+#[derive(Debug)]
+pub enum ReadToEndError<E> {
+    /// The stream was longer than the limit; nothing read is returned.
+    TooLong { limit: usize },
+
+    /// The underlying stream failed.
+    Stream(E),
+}
+
+impl<E: fmt::Display> fmt::Display for ReadToEndError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLong { limit } => write!(f, "stream longer than the limit of {limit} bytes"),
+            Self::Stream(err) => write!(f, "stream error: {err}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ReadToEndError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::TooLong { .. } => None,
+            Self::Stream(err) => Some(err),
+        }
+    }
+}
+
+impl<E: Error> Error for ReadToEndError<E> {
+    fn session_error(&self) -> Option<(u32, String)> {
+        match self {
+            Self::TooLong { .. } => None,
+            Self::Stream(err) => err.session_error(),
+        }
+    }
+
+    fn session_error_bytes(&self) -> Option<(u32, Bytes)> {
+        match self {
+            Self::TooLong { .. } => None,
+            Self::Stream(err) => err.session_error_bytes(),
+        }
+    }
+
+    fn stream_error(&self) -> Option<u32> {
+        match self {
+            Self::TooLong { .. } => None,
+            Self::Stream(err) => err.stream_error(),
+        }
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TooLong { .. } => ErrorKind::TooLarge,
+            Self::Stream(err) => err.kind(),
+        }
+    }
 }
 
 /// A WebTransport Session, able to accept/create streams and send/recv datagrams.
@@ -76,8 +249,8 @@ pub trait Error: std::error::Error + MaybeSend + MaybeSync + 'static {
 /// The session can be cloned to create multiple handles.
 /// The session will be closed on drop.
 pub trait Session: Clone + MaybeSend + MaybeSync + 'static {
-    type SendStream: SendStream;
-    type RecvStream: RecvStream;
+    type SendStream: SendStream + 'static;
+    type RecvStream: RecvStream + 'static;
     type Error: Error;
 
     /// Block until the peer creates a new unidirectional stream.
@@ -97,6 +270,39 @@ pub trait Session: Clone + MaybeSend + MaybeSync + 'static {
     /// Open a new unidirectional stream, which may block when there are too many concurrent streams.
     fn open_uni(&self) -> impl Future<Output = Result<Self::SendStream, Self::Error>> + MaybeSend;
 
+    /// Open a new unidirectional stream with [StreamOptions], like a priority to send it at.
+    ///
+    /// The default opens the stream with [Session::open_uni] and then sets its priority.
+    fn open_uni_with(
+        &self,
+        options: StreamOptions,
+    ) -> impl Future<Output = Result<Self::SendStream, Self::Error>> + MaybeSend {
+        async move {
+            let mut send = self.open_uni().await?;
+            if let Some(order) = options.order_u8() {
+                send.set_priority(order);
+            }
+            Ok(send)
+        }
+    }
+
+    /// Open a new bidirectional stream with [StreamOptions], like a priority to send it at.
+    ///
+    /// The default opens the stream with [Session::open_bi] and then sets its priority.
+    fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> impl Future<Output = Result<(Self::SendStream, Self::RecvStream), Self::Error>> + MaybeSend
+    {
+        async move {
+            let (mut send, recv) = self.open_bi().await?;
+            if let Some(order) = options.order_u8() {
+                send.set_priority(order);
+            }
+            Ok((send, recv))
+        }
+    }
+
     /// Send a datagram over the network.
     ///
     /// QUIC datagrams may be dropped for any reason:
@@ -108,12 +314,46 @@ pub trait Session: Clone + MaybeSend + MaybeSync + 'static {
     /// - ???
     fn send_datagram(&self, payload: Bytes) -> Result<(), Self::Error>;
 
+    /// Send a datagram, waiting for room in the outgoing buffer instead of dropping it.
+    ///
+    /// This applies backpressure when the application sends faster than the connection
+    /// can, but the datagram may still be lost in the network. The default falls back to
+    /// [Session::send_datagram] for backends that can't wait.
+    fn send_datagram_wait(
+        &self,
+        payload: Bytes,
+    ) -> impl Future<Output = Result<(), Self::Error>> + MaybeSend {
+        let result = self.send_datagram(payload);
+        async move { result }
+    }
+
+    /// Send a datagram with [DatagramOptions], like the browser's `outgoingMaxAge` and `sendOrder`.
+    ///
+    /// The default ignores the options and falls back to [Session::send_datagram].
+    fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), Self::Error> {
+        let _ = options;
+        self.send_datagram(payload)
+    }
+
     /// Receive a datagram over the network.
     fn recv_datagram(&self) -> impl Future<Output = Result<Bytes, Self::Error>> + MaybeSend;
 
     /// The maximum size of a datagram that can be sent.
     fn max_datagram_size(&self) -> usize;
 
+    /// Return true if the peer accepts datagrams.
+    ///
+    /// A peer may only enable streams, in which case [Session::send_datagram] fails with an
+    /// [ErrorKind::Unsupported] error. The default assumes support when [Session::max_datagram_size]
+    /// is non-zero.
+    fn datagrams_supported(&self) -> bool {
+        self.max_datagram_size() > 0
+    }
+
     /// Return the negotiated WebTransport subprotocol, if any.
     fn protocol(&self) -> Option<&str> {
         None
@@ -122,13 +362,80 @@ pub trait Session: Clone + MaybeSend + MaybeSync + 'static {
     /// Close the connection immediately with a code and reason.
     fn close(&self, code: u32, reason: &str);
 
+    /// Close the connection immediately with a code and a reason of arbitrary bytes.
+    ///
+    /// The wire doesn't require the reason to be UTF-8, so proxies can use this to relay a reason verbatim.
+    /// The default falls back to [Session::close] with a lossy conversion.
+    fn close_bytes(&self, code: u32, reason: &[u8]) {
+        self.close(code, &String::from_utf8_lossy(reason))
+    }
+
     /// Block until the connection is closed by either side.
     fn closed(&self) -> impl Future<Output = Self::Error> + MaybeSend;
 
+    /// Return how the session was closed, or None if it hasn't been.
+    ///
+    /// Closing is idempotent: the first close wins, whether it was ours or the peer's,
+    /// and later calls to [Session::close] are ignored. [Closed::raced] records whether
+    /// the other end closed too. Backends track this with a [CloseRecord]; the default
+    /// returns None for those that don't.
+    fn close_reason(&self) -> Option<Closed> {
+        None
+    }
+
     /// Return connection-level statistics, if supported.
     fn stats(&self) -> impl Stats {
         StatsUnavailable
     }
+
+    /// Return the identifier this session's backend logs with, if it has one.
+    ///
+    /// See [SessionId]; the default returns None for backends that don't assign one.
+    fn id(&self) -> Option<SessionId> {
+        None
+    }
+
+    /// Return the remote peer's address, if known.
+    ///
+    /// This is `None` when the backend can't see the socket, for example in the browser.
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        None
+    }
+
+    /// Return how many unidirectional streams the peer allows: those opened so far plus [Session::remaining_uni].
+    ///
+    /// The peer can raise this during the session. The default returns None, for backends that don't know.
+    fn max_open_uni(&self) -> Option<u64> {
+        None
+    }
+
+    /// Return how many more unidirectional streams can be opened without waiting for the peer.
+    ///
+    /// [Session::open_uni] blocks once this reaches zero, until the peer grants more.
+    fn remaining_uni(&self) -> Option<u64> {
+        None
+    }
+
+    /// Return how many bidirectional streams the peer allows: those opened so far plus [Session::remaining_bi].
+    ///
+    /// The peer can raise this during the session. The default returns None, for backends that don't know.
+    fn max_open_bi(&self) -> Option<u64> {
+        None
+    }
+
+    /// Return how many more bidirectional streams can be opened without waiting for the peer.
+    ///
+    /// [Session::open_bi] blocks once this reaches zero, until the peer grants more.
+    fn remaining_bi(&self) -> Option<u64> {
+        None
+    }
+
+    /// Erase the backend, so the session can be stored without being generic over it.
+    ///
+    /// See the [boxed] module.
+    fn boxed(self) -> boxed::BoxedSession {
+        boxed::BoxedSession::new(self)
+    }
 }
 
 /// An outgoing stream of bytes to the peer.
@@ -247,7 +554,7 @@ pub trait SendStream: MaybeSend {
 /// An incoming stream of bytes from the peer.
 ///
 /// All bytes are flushed in order and the stream is flow controlled.
-/// The stream will be closed with STOP_SENDING code=0 when dropped.
+/// The stream will be closed with STOP_SENDING when dropped, using a backend-defined code.
 pub trait RecvStream: MaybeSend {
     type Error: Error;
 
@@ -282,6 +589,23 @@ pub trait RecvStream: MaybeSend {
         }
     }
 
+    /// Read some data into an owned buffer, handing it back alongside the result.
+    ///
+    /// Unlike [Self::read] and [Self::read_buf], nothing is borrowed across the await,
+    /// so the future can be stored or moved between tasks without a self-referential buffer.
+    /// Data is appended into the spare capacity, so `reserve` before calling.
+    /// The number of bytes read is returned, or None if the stream is closed.
+    /// The buffer comes back even on error, so it can be reused.
+    fn read_owned(
+        &mut self,
+        mut buf: BytesMut,
+    ) -> impl Future<Output = (BytesMut, Result<Option<usize>, Self::Error>)> + MaybeSend {
+        async move {
+            let size = self.read_buf(&mut buf).await;
+            (buf, size)
+        }
+    }
+
     /// Read the next chunk of data, up to the max size.
     ///
     /// This returns a chunk of data instead of copying, which may be more efficient.
@@ -298,6 +622,19 @@ pub trait RecvStream: MaybeSend {
         }
     }
 
+    /// Read every chunk that's already been received, up to `max_total` bytes in total.
+    ///
+    /// Only waits if nothing is ready, so a proxy can forward a burst with one wakeup.
+    /// Returns None if the stream is closed.
+    ///
+    /// The default reads a single chunk; backends that buffer incoming data return all of it.
+    fn read_ready_chunks(
+        &mut self,
+        max_total: usize,
+    ) -> impl Future<Output = Result<Option<Vec<Bytes>>, Self::Error>> + MaybeSend {
+        async move { Ok(self.read_chunk(max_total).await?.map(|chunk| vec![chunk])) }
+    }
+
     /// Send a `STOP_SENDING` QUIC code, informing the peer that no more data will be read.
     ///
     /// An implementation MUST do this on Drop otherwise flow control will be leaked.
@@ -321,6 +658,47 @@ pub trait RecvStream: MaybeSend {
         }
     }
 
+    /// A helper to keep reading until the stream is finished or `limit` bytes have arrived.
+    ///
+    /// Anything past `limit` is left unread, so a longer stream is silently truncated;
+    /// see [Self::read_to_end_strict] to fail instead.
+    fn read_to_end(
+        &mut self,
+        limit: usize,
+    ) -> impl Future<Output = Result<Bytes, Self::Error>> + MaybeSend {
+        async move {
+            let mut buf = BytesMut::new();
+            while buf.len() < limit {
+                match self.read_chunk(limit - buf.len()).await? {
+                    Some(chunk) => buf.extend_from_slice(&chunk),
+                    None => break,
+                }
+            }
+            Ok(buf.freeze())
+        }
+    }
+
+    /// A helper to read a whole message, until the stream is finished.
+    ///
+    /// Like [Self::read_to_end], but fails with [ReadToEndError::TooLong] rather than
+    /// truncating if the stream is longer than `limit`.
+    fn read_to_end_strict(
+        &mut self,
+        limit: usize,
+    ) -> impl Future<Output = Result<Bytes, ReadToEndError<Self::Error>>> + MaybeSend {
+        async move {
+            // Read one byte past the limit, to tell a stream that ends there from a longer one.
+            let buf = self
+                .read_to_end(limit.saturating_add(1))
+                .await
+                .map_err(ReadToEndError::Stream)?;
+            if buf.len() > limit {
+                return Err(ReadToEndError::TooLong { limit });
+            }
+            Ok(buf)
+        }
+    }
+
     /// A helper to keep reading until the buffer is full.
     fn read_all_buf<B: BufMut + MaybeSend>(
         &mut self,
@@ -0,0 +1,43 @@
+//! Options for opening a stream.
+
+/// How to open a stream with [Session::open_uni_with](crate::Session::open_uni_with) or
+/// [Session::open_bi_with](crate::Session::open_bi_with).
+///
+/// Unlike calling [SendStream::set_priority](crate::SendStream::set_priority) once the stream is
+/// open, none of its data is ever queued at the default priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// The browser's `sendOrder`: streams with higher values are sent first.
+    ///
+    /// Takes precedence over [StreamOptions::priority]. Backends with a narrower range clamp it.
+    pub send_order: Option<i64>,
+
+    /// The priority as [SendStream::set_priority](crate::SendStream::set_priority) takes it,
+    /// where higher values are sent first.
+    pub priority: Option<u8>,
+}
+
+impl StreamOptions {
+    /// Send the stream ahead of those with a lower `send_order`.
+    pub const fn with_send_order(mut self, send_order: i64) -> Self {
+        self.send_order = Some(send_order);
+        self
+    }
+
+    /// Send the stream ahead of those with a lower `priority`.
+    pub const fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// The order to send the stream in, on the `sendOrder` scale.
+    pub fn order(&self) -> Option<i64> {
+        self.send_order.or(self.priority.map(i64::from))
+    }
+
+    /// The order to send the stream in, clamped to the [SendStream::set_priority](crate::SendStream::set_priority) scale.
+    pub fn order_u8(&self) -> Option<u8> {
+        self.order()
+            .map(|order| order.clamp(0, u8::MAX.into()) as u8)
+    }
+}
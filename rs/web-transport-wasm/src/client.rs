@@ -12,6 +12,8 @@ pub use web_sys::WebTransportCongestionControl as CongestionControl;
 #[derive(Debug, Default)]
 pub struct ClientBuilder {
     options: WebTransportOptions,
+    protocols: Vec<String>,
+    require_protocol: bool,
 }
 
 impl ClientBuilder {
@@ -41,14 +43,19 @@ impl ClientBuilder {
     /// Advertise the application protocols (subprotocols) offered for negotiation.
     ///
     /// The server selects one of these, available afterwards via [`Session::protocol`].
-    pub fn with_protocols<I, S>(self, protocols: I) -> Self
+    pub fn with_protocols<I, S>(mut self, protocols: I) -> Self
     where
         I: IntoIterator<Item = S>,
         S: AsRef<str>,
     {
+        self.protocols = protocols
+            .into_iter()
+            .map(|p| p.as_ref().to_string())
+            .collect();
+
         let array = Array::new();
-        for protocol in protocols {
-            array.push(&JsValue::from_str(protocol.as_ref()));
+        for protocol in &self.protocols {
+            array.push(&JsValue::from_str(protocol));
         }
 
         // web-sys 0.3 has no binding for `WebTransportOptions.protocols`, so set it via Reflect.
@@ -57,6 +64,16 @@ impl ClientBuilder {
         self
     }
 
+    /// Fail the connection unless the server selects one of the offered protocols.
+    ///
+    /// By default a server that ignores the offered protocols is accepted, leaving
+    /// [`Session::protocol`] as `None`. When required, such a session is closed and
+    /// [`Client::connect`] fails with [`Error::NoProtocol`].
+    pub fn require_protocol(mut self, required: bool) -> Self {
+        self.require_protocol = required;
+        self
+    }
+
     /// Supply sha256 hashes for accepted certificates, instead of using a root CA
     pub fn with_server_certificate_hashes(self, hashes: Vec<Vec<u8>>) -> Client {
         let hashes: Vec<WebTransportHash> = hashes
@@ -74,12 +91,16 @@ impl ClientBuilder {
             .set_server_certificate_hashes(hashes.as_slice());
         Client {
             options: self.options,
+            protocols: self.protocols,
+            require_protocol: self.require_protocol,
         }
     }
 
     pub fn with_system_roots(self) -> Client {
         Client {
             options: self.options,
+            protocols: self.protocols,
+            require_protocol: self.require_protocol,
         }
     }
 }
@@ -90,6 +111,8 @@ impl ClientBuilder {
 #[derive(Clone, Debug, Default)]
 pub struct Client {
     options: WebTransportOptions,
+    protocols: Vec<String>,
+    require_protocol: bool,
 }
 
 impl Client {
@@ -98,6 +121,12 @@ impl Client {
         let inner = WebTransport::new_with_options(url.as_str(), &self.options)?;
         JsFuture::from(inner.ready()).await?;
 
-        Ok(Session::new(inner, url))
+        let session = Session::new(inner, url).with_offered(self.protocols.clone());
+        if self.require_protocol && session.protocol().is_none() {
+            session.close(0, "no protocol selected");
+            return Err(Error::NoProtocol);
+        }
+
+        Ok(session)
     }
 }
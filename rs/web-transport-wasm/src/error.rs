@@ -1,7 +1,9 @@
 use wasm_bindgen::prelude::*;
+use web_transport_trait::ErrorKind;
 
 /// A WebTransport error classified based on the source.
 #[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("webtransport session error: {0:?}")]
     Session(web_sys::WebTransportError),
@@ -14,9 +16,24 @@ pub enum Error {
 
     #[error("unknown error: {0:?}")]
     Unknown(JsValue),
+
+    #[error("server didn't select any of the offered protocols")]
+    NoProtocol,
 }
 
 impl Error {
+    /// Returns what kind of error this is.
+    ///
+    /// The browser doesn't say whether a stream error was a reset or a stop, so both are [ErrorKind::StreamReset].
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Session(_) => ErrorKind::SessionClosed,
+            Error::Stream(_) => ErrorKind::StreamReset,
+            Error::Streams(_) | Error::Unknown(_) => ErrorKind::Other,
+            Error::NoProtocol => ErrorKind::Protocol,
+        }
+    }
+
     /// The error code used when closing the stream or session.
     pub fn code(&self) -> Option<u8> {
         match self {
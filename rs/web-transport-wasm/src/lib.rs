@@ -47,3 +47,5 @@ pub use recv::*;
 pub use send::*;
 #[cfg(web_sys_unstable_apis)]
 pub use session::*;
+
+pub use web_transport_trait::{DatagramOptions, ErrorKind, StreamOptions};
@@ -1,15 +1,17 @@
 use bytes::Bytes;
-use js_sys::{Function, Reflect, Uint8Array};
+use js_sys::{Function, Object, Reflect, Uint8Array};
 use url::Url;
-use wasm_bindgen::JsCast;
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
     WebTransport, WebTransportBidirectionalStream, WebTransportCloseInfo,
-    WebTransportDatagramDuplexStream, WebTransportSendStream, WritableStream,
+    WebTransportDatagramDuplexStream, WebTransportSendStream, WebTransportSendStreamOptions,
+    WritableStream,
 };
 
 use crate::{Error, RecvStream, SendStream};
 use web_streams::{Reader, Writer};
+use web_transport_trait::{DatagramOptions, StreamOptions};
 
 /// A session represents a connection between a client and a server.
 ///
@@ -22,6 +24,7 @@ use web_streams::{Reader, Writer};
 pub struct Session {
     inner: WebTransport,
     url: Url,
+    protocols: Vec<String>,
     protocol: Option<String>,
 }
 
@@ -29,18 +32,29 @@ pub struct Session {
 /// `.writable` property is deprecated, non-standard, and unimplemented by Safari
 /// (there `.writable` is `undefined`, so datagram sending throws). Prefer
 /// `createWritable()`, falling back to `.writable` for browsers that still have it.
+/// `options` is a `WebTransportSendOptions`, or undefined; the fallback ignores it.
 /// MDN — the `.writable` deprecation note and the feature-detect example:
 /// <https://developer.mozilla.org/en-US/docs/Web/API/WebTransportDatagramDuplexStream/writable>
 /// <https://developer.mozilla.org/en-US/docs/Web/API/WebTransport/datagrams#writing_an_outgoing_datagram>
-fn datagram_writable(dg: &WebTransportDatagramDuplexStream) -> WritableStream {
+fn datagram_writable(dg: &WebTransportDatagramDuplexStream, options: &JsValue) -> WritableStream {
     Reflect::get(dg, &"createWritable".into())
         .ok()
         .and_then(|f| f.dyn_into::<Function>().ok())
-        .and_then(|f| f.call0(dg).ok())
+        .and_then(|f| f.call1(dg, options).ok())
         .and_then(|ws| ws.dyn_into::<WritableStream>().ok())
         .unwrap_or_else(|| dg.writable())
 }
 
+// Build the `WebTransportSendStreamOptions` for a new stream.
+// `sendOrder` is set through Reflect like `SendStream::set_priority`, as a plain JS number.
+fn send_stream_options(options: StreamOptions) -> Result<WebTransportSendStreamOptions, Error> {
+    let object = Object::new();
+    if let Some(order) = options.order() {
+        Reflect::set(&object, &"sendOrder".into(), &(order as f64).into())?;
+    }
+    Ok(object.unchecked_into())
+}
+
 impl Session {
     pub fn new(inner: WebTransport, url: Url) -> Self {
         // TODO use the web_sys bindings when updated.
@@ -52,10 +66,17 @@ impl Session {
         Self {
             inner,
             url,
+            protocols: Vec::new(),
             protocol,
         }
     }
 
+    // Record the protocols the client offered, since the browser doesn't expose them.
+    pub(crate) fn with_offered(mut self, protocols: Vec<String>) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
     /// Accept a new unidirectional stream from the peer.
     pub async fn accept_uni(&self) -> Result<RecvStream, Error> {
         let mut reader = Reader::new(&self.inner.incoming_unidirectional_streams())?;
@@ -101,9 +122,71 @@ impl Session {
         Ok(send)
     }
 
+    /// Creates a new bidirectional stream with [StreamOptions].
+    ///
+    /// The order becomes the stream's `sendOrder` as the browser creates it.
+    pub async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(SendStream, RecvStream), Error> {
+        let options = send_stream_options(options)?;
+        let stream: WebTransportBidirectionalStream = JsFuture::from(
+            self.inner
+                .create_bidirectional_stream_with_options(&options),
+        )
+        .await?;
+
+        let send = SendStream::new(stream.writable())?;
+        let recv = RecvStream::new(stream.readable())?;
+
+        Ok((send, recv))
+    }
+
+    /// Creates a new unidirectional stream with [StreamOptions].
+    ///
+    /// The order becomes the stream's `sendOrder` as the browser creates it.
+    pub async fn open_uni_with(&self, options: StreamOptions) -> Result<SendStream, Error> {
+        let options = send_stream_options(options)?;
+        let stream: WebTransportSendStream = JsFuture::from(
+            self.inner
+                .create_unidirectional_stream_with_options(&options),
+        )
+        .await?;
+
+        let send = SendStream::new(stream)?;
+        Ok(send)
+    }
+
     /// Send a datagram over the network.
     pub async fn send_datagram(&self, payload: Bytes) -> Result<(), Error> {
-        let mut writer = Writer::new(&datagram_writable(&self.inner.datagrams()))?;
+        let datagrams = self.inner.datagrams();
+        let mut writer = Writer::new(&datagram_writable(&datagrams, &JsValue::UNDEFINED))?;
+        writer.write(&Uint8Array::from(payload.as_ref())).await?;
+        Ok(())
+    }
+
+    /// Send a datagram with [DatagramOptions].
+    ///
+    /// The priority becomes the `sendOrder` of its `WebTransportSendOptions`. The max age
+    /// sets the session's `outgoingMaxAge`, which the browser applies to every datagram
+    /// still queued, including those sent after this one without a max age.
+    pub async fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), Error> {
+        let datagrams = self.inner.datagrams();
+        if let Some(max_age) = options.max_age {
+            let millis = max_age.as_secs_f64() * 1000.0;
+            Reflect::set(&datagrams, &"outgoingMaxAge".into(), &millis.into())?;
+        }
+
+        let send = Object::new();
+        if let Some(priority) = options.priority {
+            Reflect::set(&send, &"sendOrder".into(), &priority.into())?;
+        }
+
+        let mut writer = Writer::new(&datagram_writable(&datagrams, &send))?;
         writer.write(&Uint8Array::from(payload.as_ref())).await?;
         Ok(())
     }
@@ -152,6 +235,13 @@ impl Session {
     pub fn protocol(&self) -> Option<&str> {
         self.protocol.as_deref()
     }
+
+    /// Return the application protocols offered by the client, in order of preference.
+    ///
+    /// Empty for a session created with [`Session::new`] rather than a [`Client`](crate::Client).
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
 }
 
 impl PartialEq for Session {
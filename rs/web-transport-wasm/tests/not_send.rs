@@ -0,0 +1,13 @@
+//! The browser types hold `JsValue`s, which are `!Send`.
+//!
+//! `web-transport-trait` relaxes `MaybeSend`/`MaybeSync` to nothing on WASM for exactly this
+//! reason. Pinning it down here means a wrapper that claims `Send` (an `unsafe impl`, say) is
+//! caught at compile time instead of becoming unsound.
+#![cfg(target_arch = "wasm32")]
+
+use static_assertions::assert_not_impl_any;
+use web_transport_wasm::{RecvStream, SendStream, Session};
+
+assert_not_impl_any!(Session: Send, Sync);
+assert_not_impl_any!(SendStream: Send);
+assert_not_impl_any!(RecvStream: Send);
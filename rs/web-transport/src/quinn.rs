@@ -1,10 +1,10 @@
-use bytes::{Buf, BufMut, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use url::Url;
 
 // Export the Quinn implementation to simplify Cargo.toml
 pub use web_transport_quinn as quinn;
 
-pub use web_transport_quinn::CongestionControl;
+pub use web_transport_quinn::{CongestionControl, DatagramOptions, ErrorKind, StreamOptions};
 
 /// Create a [Client] that can be used to dial multiple [Session]s.
 #[derive(Default, Clone)]
@@ -41,6 +41,14 @@ impl ClientBuilder {
         self
     }
 
+    /// Fail the connection unless the server selects one of the offered protocols.
+    pub fn require_protocol(self, required: bool) -> Self {
+        Self {
+            inner: self.inner.require_protocol(required),
+            ..self
+        }
+    }
+
     /// Accept the server's certificate hashes (sha256) instead of using a root CA.
     pub fn with_server_certificate_hashes(self, hashes: Vec<Vec<u8>>) -> Result<Client, Error> {
         Ok(Client {
@@ -139,6 +147,27 @@ impl Session {
         Ok(self.inner.open_uni().await.map(SendStream::new)?)
     }
 
+    /// Open a new bidirectional stream with [StreamOptions], like the browser's `sendOrder`.
+    pub async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(SendStream, RecvStream), Error> {
+        Ok(self
+            .inner
+            .open_bi_with(options)
+            .await
+            .map(|(s, r)| (SendStream::new(s), RecvStream::new(r)))?)
+    }
+
+    /// Open a new unidirectional stream with [StreamOptions], like the browser's `sendOrder`.
+    pub async fn open_uni_with(&self, options: StreamOptions) -> Result<SendStream, Error> {
+        Ok(self
+            .inner
+            .open_uni_with(options)
+            .await
+            .map(SendStream::new)?)
+    }
+
     /// Send a datagram over the network.
     ///
     /// QUIC datagrams may be dropped for any reason:
@@ -153,6 +182,15 @@ impl Session {
         Ok(self.inner.send_datagram(payload)?)
     }
 
+    /// Send a datagram with [DatagramOptions], like the browser's `outgoingMaxAge` and `sendOrder`.
+    pub async fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), Error> {
+        Ok(self.inner.send_datagram_with(payload, options)?)
+    }
+
     /// The maximum size of a datagram that can be sent.
     pub async fn max_datagram_size(&self) -> usize {
         self.inner.max_datagram_size()
@@ -180,7 +218,17 @@ impl Session {
 
     /// Return the application protocol used to create the session.
     pub fn protocol(&self) -> Option<&str> {
-        self.inner.response().protocol.as_deref()
+        self.inner.protocol()
+    }
+
+    /// Return the application protocols offered by the client, in order of preference.
+    pub fn protocols(&self) -> &[String] {
+        self.inner.protocols()
+    }
+
+    /// Return the remote peer's address.
+    pub fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        Some(self.inner.remote_address())
     }
 }
 
@@ -296,6 +344,19 @@ impl RecvStream {
         Ok(Some(size))
     }
 
+    /// Read some data into an owned buffer, handing it back alongside the result.
+    ///
+    /// Nothing is borrowed across the await, which keeps pipelines free of self-referential buffers.
+    /// The number of bytes read is returned, or None if the stream is closed.
+    /// The buffer comes back even on error, so it can be reused.
+    pub async fn read_owned(
+        &mut self,
+        mut buf: BytesMut,
+    ) -> (BytesMut, Result<Option<usize>, Error>) {
+        let size = self.read_buf(&mut buf).await;
+        (buf, size)
+    }
+
     /// Send a `STOP_SENDING` QUIC code.
     pub fn stop(&mut self, code: u32) {
         self.inner.stop(code).ok();
@@ -320,6 +381,7 @@ impl RecvStream {
 /// The source can either be a session error or a stream error.
 /// TODO This interface is currently not generic.
 #[derive(Debug, thiserror::Error, Clone)]
+#[non_exhaustive]
 pub enum Error {
     #[error("session error: {0}")]
     Session(#[from] quinn::SessionError),
@@ -337,6 +399,19 @@ pub enum Error {
     Read(quinn::ReadError),
 }
 
+impl Error {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Session(e) => e.kind(),
+            Error::Server(e) => e.kind(),
+            Error::Client(e) => e.kind(),
+            Error::Write(e) => e.kind(),
+            Error::Read(e) => e.kind(),
+        }
+    }
+}
+
 impl From<quinn::WriteError> for Error {
     fn from(e: quinn::WriteError) -> Self {
         match e {
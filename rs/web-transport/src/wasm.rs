@@ -1,7 +1,7 @@
-use bytes::{Buf, BufMut, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use url::Url;
 
-pub use web_transport_wasm::CongestionControl;
+pub use web_transport_wasm::{CongestionControl, DatagramOptions, ErrorKind, StreamOptions};
 
 // Export the Wasm implementation to simplify Cargo.toml
 pub use web_transport_wasm as wasm;
@@ -42,6 +42,13 @@ impl ClientBuilder {
         }
     }
 
+    /// Fail the connection unless the server selects one of the offered protocols.
+    pub fn require_protocol(self, required: bool) -> Self {
+        Self {
+            inner: self.inner.require_protocol(required),
+        }
+    }
+
     pub fn with_server_certificate_hashes(self, hashes: Vec<Vec<u8>>) -> Result<Client, Error> {
         Ok(Client {
             inner: self.inner.with_server_certificate_hashes(hashes),
@@ -89,6 +96,18 @@ impl Session {
         self.0.open_uni().await.map(SendStream)
     }
 
+    pub async fn open_bi_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<(SendStream, RecvStream), Error> {
+        let (s, r) = self.0.open_bi_with(options).await?;
+        Ok((SendStream(s), RecvStream(r)))
+    }
+
+    pub async fn open_uni_with(&self, options: StreamOptions) -> Result<SendStream, Error> {
+        self.0.open_uni_with(options).await.map(SendStream)
+    }
+
     /// Close the connection immediately
     pub fn close(&self, code: u32, reason: &str) {
         self.0.close(code, reason)
@@ -103,6 +122,15 @@ impl Session {
         self.0.send_datagram(payload).await
     }
 
+    /// Send a datagram with [DatagramOptions], like the browser's `outgoingMaxAge` and `sendOrder`.
+    pub async fn send_datagram_with(
+        &self,
+        payload: Bytes,
+        options: DatagramOptions,
+    ) -> Result<(), Error> {
+        self.0.send_datagram_with(payload, options).await
+    }
+
     pub async fn recv_datagram(&self) -> Result<Bytes, Error> {
         self.0.recv_datagram().await
     }
@@ -116,6 +144,16 @@ impl Session {
     pub fn protocol(&self) -> Option<&str> {
         self.0.protocol()
     }
+
+    /// Return the application protocols offered by the client, in order of preference.
+    pub fn protocols(&self) -> &[String] {
+        self.0.protocols()
+    }
+
+    /// Return the remote peer's address, which the browser never exposes.
+    pub fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        None
+    }
 }
 
 impl From<web_transport_wasm::Session> for Session {
@@ -175,6 +213,19 @@ impl RecvStream {
         self.0.read_buf(buf).await
     }
 
+    /// Read some data into an owned buffer, handing it back alongside the result.
+    ///
+    /// Nothing is borrowed across the await, which keeps pipelines free of self-referential buffers.
+    /// The number of bytes read is returned, or None if the stream is closed.
+    /// The buffer comes back even on error, so it can be reused.
+    pub async fn read_owned(
+        &mut self,
+        mut buf: BytesMut,
+    ) -> (BytesMut, Result<Option<usize>, Error>) {
+        let size = self.read_buf(&mut buf).await;
+        (buf, size)
+    }
+
     /// Send a `STOP_SENDING` QUIC code.
     pub fn stop(&mut self, code: u32) {
         self.0.stop(&code.to_string())